@@ -0,0 +1,57 @@
+#[path = "build/bindings.rs"]
+mod bindings;
+
+use std::path::Path;
+
+// Every event the plugin emits and the type of its payload, for guest-js/bindings.ts
+const EVENTS: &[(&str, &str)] = &[
+    ("pipe-state-changed", "PipeStateChangedPayload"),
+    ("pipe-connected", "PipeConnectedPayload"),
+    ("pipe-disconnected", "PipeDisconnectedPayload"),
+    ("pipe-timeout", "PipeTimeoutPayload"),
+    ("pipe-connection-failed", "PipeConnectionFailedPayload"),
+    ("pipe-stats", "PipeMetricsSnapshot"),
+    ("target-resolution", "TargetResolutionPayload"),
+    ("encoder-fallback", "EncoderFallbackPayload"),
+    ("frames-dropped", "FramesDroppedPayload"),
+    ("audio-dropped", "AudioDroppedPayload"),
+    ("microphone-level", "MicrophoneLevel"),
+    ("microphone-stopped", "MicrophoneStoppedPayload"),
+    ("desktop-capture-stopped", "DesktopCaptureStoppedPayload"),
+    ("video-frame", "VideoFramePayload"),
+    ("file-transfer-progress", "FileTransferProgress"),
+    ("file-transfer-complete", "FileTransferComplete"),
+    ("vr-pointer", "VrPointerPayload"),
+    ("vr-key", "VrKeyPayload"),
+    ("frame-acked", "FrameAckedPayload"),
+    ("osc-message", "OscMessage"),
+    ("transform-update", "TransformUpdatePayload"),
+    ("gaze-update", "Gaze"),
+    ("hand-tracking-changed", "HandTrackingChanged"),
+    ("device-status", "DeviceStatus"),
+    ("device-battery-low", "BatteryLow"),
+    ("playspace-changed", "Playspace"),
+    ("dashboard-visibility", "DashboardVisibility"),
+    ("transform-update:${string}", "TransformUpdatePayload"),
+    ("overlay-transform", "OverlayTransformPayload"),
+    ("transform-invalid", "TransformInvalidPayload"),
+    ("controller-input", "ControllerInputPayload"),
+    ("backend-started", "BackendStartedPayload"),
+    ("backend-stopped", "BackendStoppedPayload"),
+    ("backend-crashed", "BackendCrashedPayload"),
+    ("backend-control", "BackendControlPayload"),
+    ("second-instance", "SecondInstancePayload"),
+    ("config-reloaded", "ConfigReloadedPayload"),
+    ("crash-report", "CrashReport"),
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build");
+    // The commands are the #[tauri::command] fns of src/; tauri-plugin generates an allow-/deny- permission for each
+    let commands = bindings::generate("petplay-ipc", Path::new("src"), Path::new("guest-js/bindings.ts"), EVENTS)
+        .unwrap_or_else(|e| panic!("generating TypeScript bindings: {}", e));
+    let commands: &'static [&'static str] =
+        Box::leak(commands.into_iter().map(|command| &*Box::leak(command.into_boxed_str())).collect());
+    tauri_plugin::Builder::new(commands).build();
+}
@@ -0,0 +1,583 @@
+// --- TypeScript bindings ---
+// Writes guest-js/bindings.ts at build time, from the plugin's own source:
+// a typed wrapper for every #[tauri::command] in lib.rs, the payload type of
+// every event in EVENTS, and every struct/enum those reach. The TypeScript
+// types follow the Rust definitions and their serde attributes (rename_all,
+// tag, untagged, flatten, default, skip...), so the frontend can't drift from
+// what the commands actually take and return.
+//
+// Only what's needed here is understood: named types are looked up among the
+// top-level structs and enums of src/*.rs, and anything else that isn't a
+// primitive, string, collection or Option becomes `unknown` with a warning.
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::Write as _,
+    fs,
+    path::Path,
+};
+use syn::{
+    Attribute, Fields, FnArg, GenericArgument, Item, ItemEnum, ItemFn, ItemStruct, LitStr,
+    Pat, PathArguments, ReturnType, Token, Type,
+};
+
+// Types with a hand-written Serialize impl, and what they serialize to
+const OVERRIDES: &[(&str, &str)] = &[("PipeError", "{ code: string; message: string; ioKind: string | null }")];
+
+pub struct Command {
+    pub name: String,
+    args: Vec<(String, String, bool)>,
+    returns: String,
+    // Takes the request body (tauri::ipc::Request) instead of arguments
+    raw: bool,
+}
+
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    transparent: bool,
+    default: bool,
+    flatten: bool,
+    skip: bool,
+    skip_serializing_if: bool,
+}
+
+fn serde_attrs(attrs: &[Attribute]) -> SerdeAttrs {
+    let mut parsed = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            let string = |meta: &syn::meta::ParseNestedMeta| -> syn::Result<Option<String>> {
+                if meta.input.peek(Token![=]) {
+                    Ok(Some(meta.value()?.parse::<LitStr>()?.value()))
+                } else {
+                    if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|inner| {
+                            if inner.input.peek(Token![=]) {
+                                inner.value()?.parse::<LitStr>()?;
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    Ok(None)
+                }
+            };
+            let key = meta.path.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+            let value = string(&meta)?;
+            match key.as_str() {
+                "rename" => parsed.rename = value,
+                "rename_all" => parsed.rename_all = value,
+                "tag" => parsed.tag = value,
+                "content" => parsed.content = value,
+                "untagged" => parsed.untagged = true,
+                "transparent" => parsed.transparent = true,
+                "default" => parsed.default = true,
+                "flatten" => parsed.flatten = true,
+                "skip" | "skip_serializing" => parsed.skip = true,
+                "skip_serializing_if" => parsed.skip_serializing_if = true,
+                _ => {}
+            }
+            Ok(())
+        });
+    }
+    parsed
+}
+
+fn derives_serde(attrs: &[Attribute]) -> bool {
+    attrs.iter().filter(|attr| attr.path().is_ident("derive")).any(|attr| {
+        let mut found = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            let name = meta.path.segments.last().map(|segment| segment.ident.to_string()).unwrap_or_default();
+            found |= name == "Serialize" || name == "Deserialize";
+            Ok(())
+        });
+        found
+    })
+}
+
+fn is_command(function: &ItemFn) -> bool {
+    function.attrs.iter().any(|attr| {
+        let segments: Vec<String> = attr.path().segments.iter().map(|segment| segment.ident.to_string()).collect();
+        segments == ["tauri", "command"]
+    })
+}
+
+// "sent_at_us" -> ["sent", "at", "us"], "SharedTexture" -> ["shared", "texture"]
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for c in name.chars() {
+        if c == '_' {
+            words.push(std::mem::take(&mut current));
+        } else if c.is_uppercase() && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+            current.push(c.to_ascii_lowercase());
+        } else {
+            current.push(c.to_ascii_lowercase());
+        }
+    }
+    words.push(current);
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+}
+
+// Apply a serde rename_all rule to a field or variant name
+fn rename(name: &str, rule: Option<&str>) -> String {
+    let words = words(name);
+    match rule {
+        None => name.to_string(),
+        Some("lowercase") => words.concat(),
+        Some("UPPERCASE") => words.concat().to_ascii_uppercase(),
+        Some("PascalCase") => words.iter().map(|word| capitalize(word)).collect(),
+        Some("camelCase") => {
+            words.iter().enumerate().map(|(i, word)| if i == 0 { word.clone() } else { capitalize(word) }).collect()
+        }
+        Some("snake_case") => words.join("_"),
+        Some("SCREAMING_SNAKE_CASE") => words.join("_").to_ascii_uppercase(),
+        Some("kebab-case") => words.join("-"),
+        Some("SCREAMING-KEBAB-CASE") => words.join("-").to_ascii_uppercase(),
+        Some(other) => {
+            println!("cargo:warning=bindings: unknown rename rule {:?}", other);
+            name.to_string()
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn property(name: &str) -> String {
+    let identifier = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if identifier {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn union(variants: Vec<String>) -> String {
+    let mut unique: Vec<String> = Vec::new();
+    for variant in variants {
+        if !unique.contains(&variant) {
+            unique.push(variant);
+        }
+    }
+    if unique.is_empty() {
+        "never".to_string()
+    } else {
+        unique.join(" | ")
+    }
+}
+
+// Wrap unions and intersections so they can be used as an array element
+fn element(ts: &str) -> String {
+    if ts.contains(' ') && !ts.starts_with('{') {
+        format!("({})", ts)
+    } else {
+        ts.to_string()
+    }
+}
+
+enum Definition {
+    Struct(ItemStruct),
+    Enum(ItemEnum),
+}
+
+struct Generator {
+    definitions: HashMap<String, Vec<Definition>>,
+    // Named types still to be written, and those already written (sorted by name)
+    pending: VecDeque<String>,
+    written: BTreeMap<String, String>,
+}
+
+impl Generator {
+    fn new(items: Vec<Item>) -> Self {
+        let mut definitions: HashMap<String, Vec<Definition>> = HashMap::new();
+        for item in items {
+            match item {
+                Item::Struct(item) => definitions.entry(item.ident.to_string()).or_default().push(Definition::Struct(item)),
+                Item::Enum(item) => definitions.entry(item.ident.to_string()).or_default().push(Definition::Enum(item)),
+                _ => {}
+            }
+        }
+        Self { definitions, pending: VecDeque::new(), written: BTreeMap::new() }
+    }
+
+    fn reference(&mut self, name: &str) -> String {
+        if let Some((_, ts)) = OVERRIDES.iter().find(|(overridden, _)| *overridden == name) {
+            self.written.entry(name.to_string()).or_insert_with(|| ts.to_string());
+            return name.to_string();
+        }
+        if self.written.contains_key(name) {
+            return name.to_string();
+        }
+        // Only definitions that serde derives for can be described
+        let serialized = self.definitions.get(name).map(|found| {
+            found.iter().filter(|definition| match definition {
+                Definition::Struct(item) => derives_serde(&item.attrs),
+                Definition::Enum(item) => derives_serde(&item.attrs),
+            }).count()
+        });
+        match serialized {
+            Some(1) => {
+                if !self.pending.iter().any(|pending| pending == name) {
+                    self.pending.push_back(name.to_string());
+                }
+                name.to_string()
+            }
+            Some(0) | None => {
+                println!("cargo:warning=bindings: no serde type named {}, typed as unknown", name);
+                "unknown".to_string()
+            }
+            Some(_) => {
+                println!("cargo:warning=bindings: several serde types named {}, typed as unknown", name);
+                "unknown".to_string()
+            }
+        }
+    }
+
+    fn generic_args(arguments: &PathArguments) -> Vec<&Type> {
+        match arguments {
+            PathArguments::AngleBracketed(arguments) => arguments
+                .args
+                .iter()
+                .filter_map(|argument| match argument {
+                    GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::Reference(reference) => self.ty(&reference.elem),
+            Type::Paren(paren) => self.ty(&paren.elem),
+            Type::Group(group) => self.ty(&group.elem),
+            Type::Array(array) => format!("{}[]", element(&self.ty(&array.elem))),
+            Type::Slice(slice) => format!("{}[]", element(&self.ty(&slice.elem))),
+            Type::Tuple(tuple) if tuple.elems.is_empty() => "null".to_string(),
+            Type::Tuple(tuple) => {
+                let elements: Vec<String> = tuple.elems.iter().map(|elem| self.ty(elem)).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Type::Path(path) => {
+                let Some(segment) = path.path.segments.last() else {
+                    return "unknown".to_string();
+                };
+                let args = Self::generic_args(&segment.arguments);
+                let name = segment.ident.to_string();
+                match (name.as_str(), args.as_slice()) {
+                    (
+                        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize"
+                        | "f32" | "f64",
+                        _,
+                    ) => "number".to_string(),
+                    ("bool", _) => "boolean".to_string(),
+                    ("String" | "str" | "char" | "PathBuf" | "Path", _) => "string".to_string(),
+                    ("Value", _) => "unknown".to_string(),
+                    // tauri::ipc::Response, a raw response body
+                    ("Response", _) => "ArrayBuffer".to_string(),
+                    ("Option", [inner]) => format!("{} | null", self.ty(inner)),
+                    ("Box" | "Arc" | "Rc" | "Cow", [.., inner]) => self.ty(inner),
+                    ("Vec" | "VecDeque" | "HashSet" | "BTreeSet", [inner]) => format!("{}[]", element(&self.ty(inner))),
+                    ("HashMap" | "BTreeMap", [key, value]) => {
+                        let (key, value) = (self.ty(key), self.ty(value));
+                        if key == "string" || key == "number" {
+                            format!("Record<{}, {}>", key, value)
+                        } else {
+                            format!("Partial<Record<{}, {}>>", key, value)
+                        }
+                    }
+                    _ => self.reference(&name),
+                }
+            }
+            _ => {
+                println!("cargo:warning=bindings: unsupported type, typed as unknown");
+                "unknown".to_string()
+            }
+        }
+    }
+
+    // The members of a struct-like body, e.g. `{ width: number; height?: number }`,
+    // plus the flattened types to intersect it with
+    fn fields(&mut self, fields: &Fields, rename_all: Option<&str>, container_default: bool) -> (Vec<String>, Vec<String>) {
+        let mut members = Vec::new();
+        let mut flattened = Vec::new();
+        for field in fields.iter() {
+            let attrs = serde_attrs(&field.attrs);
+            if attrs.skip {
+                continue;
+            }
+            let ty = self.ty(&field.ty);
+            if attrs.flatten {
+                flattened.push(ty);
+                continue;
+            }
+            let Some(ident) = &field.ident else {
+                continue;
+            };
+            let name = attrs.rename.clone().unwrap_or_else(|| rename(&ident.to_string(), rename_all));
+            // Missing optional fields deserialize fine; skipped ones are missing from the output
+            let optional = container_default || attrs.default || attrs.skip_serializing_if || ty.ends_with("| null");
+            members.push(format!("{}{}: {}", property(&name), if optional { "?" } else { "" }, ty));
+        }
+        (members, flattened)
+    }
+
+    fn object(members: &[String], flattened: &[String]) -> String {
+        let mut parts = Vec::new();
+        if !members.is_empty() || flattened.is_empty() {
+            parts.push(format!("{{ {} }}", members.join("; ")).replace("{  }", "{}"));
+        }
+        parts.extend(flattened.iter().map(|ty| element(ty)));
+        parts.join(" & ")
+    }
+
+    fn unnamed(&mut self, fields: &Fields) -> String {
+        let elements: Vec<String> = fields.iter().map(|field| self.ty(&field.ty)).collect();
+        match elements.as_slice() {
+            [single] => single.clone(),
+            _ => format!("[{}]", elements.join(", ")),
+        }
+    }
+
+    fn structure(&mut self, item: &ItemStruct) -> String {
+        let attrs = serde_attrs(&item.attrs);
+        match &item.fields {
+            Fields::Unit => "null".to_string(),
+            Fields::Unnamed(_) => self.unnamed(&item.fields),
+            Fields::Named(_) if attrs.transparent => self.unnamed(&item.fields),
+            Fields::Named(_) => {
+                let (members, flattened) = self.fields(&item.fields, attrs.rename_all.as_deref(), attrs.default);
+                Self::object(&members, &flattened)
+            }
+        }
+    }
+
+    fn enumeration(&mut self, item: &ItemEnum) -> String {
+        let attrs = serde_attrs(&item.attrs);
+        let mut variants = Vec::new();
+        for variant in &item.variants {
+            let variant_attrs = serde_attrs(&variant.attrs);
+            if variant_attrs.skip {
+                continue;
+            }
+            let name = variant_attrs.rename.clone().unwrap_or_else(|| rename(&variant.ident.to_string(), attrs.rename_all.as_deref()));
+            let tag = quote(&name);
+            let field_rule = variant_attrs.rename_all.as_deref();
+            let ts = match (&variant.fields, &attrs.tag, &attrs.content) {
+                // Untagged: just the content
+                (Fields::Unit, _, _) if attrs.untagged => "null".to_string(),
+                (Fields::Unnamed(_), _, _) if attrs.untagged => self.unnamed(&variant.fields),
+                (Fields::Named(_), _, _) if attrs.untagged => {
+                    let (members, flattened) = self.fields(&variant.fields, field_rule, false);
+                    Self::object(&members, &flattened)
+                }
+                // Adjacently tagged: { tag: "name", content: ... }
+                (Fields::Unit, Some(tag_key), Some(_)) => format!("{{ {}: {} }}", property(tag_key), tag),
+                (fields, Some(tag_key), Some(content_key)) => {
+                    let content = match fields {
+                        Fields::Named(_) => {
+                            let (members, flattened) = self.fields(fields, field_rule, false);
+                            Self::object(&members, &flattened)
+                        }
+                        _ => self.unnamed(fields),
+                    };
+                    format!("{{ {}: {}; {}: {} }}", property(tag_key), tag, property(content_key), content)
+                }
+                // Internally tagged: the tag sits beside the fields
+                (Fields::Unit, Some(tag_key), None) => format!("{{ {}: {} }}", property(tag_key), tag),
+                (Fields::Unnamed(_), Some(tag_key), None) => {
+                    format!("{{ {}: {} }} & {}", property(tag_key), tag, element(&self.unnamed(&variant.fields)))
+                }
+                (Fields::Named(_), Some(tag_key), None) => {
+                    let (mut members, flattened) = self.fields(&variant.fields, field_rule, false);
+                    members.insert(0, format!("{}: {}", property(tag_key), tag));
+                    Self::object(&members, &flattened)
+                }
+                // Externally tagged (serde's default): "name", or { name: ... }
+                (Fields::Unit, None, _) => tag,
+                (Fields::Unnamed(_), None, _) => format!("{{ {}: {} }}", property(&name), self.unnamed(&variant.fields)),
+                (Fields::Named(_), None, _) => {
+                    let (members, flattened) = self.fields(&variant.fields, field_rule, false);
+                    format!("{{ {}: {} }}", property(&name), Self::object(&members, &flattened))
+                }
+            };
+            variants.push(if ts.contains(" & ") && !ts.starts_with('(') { format!("({})", ts) } else { ts });
+        }
+        union(variants)
+    }
+
+    fn write_pending(&mut self) {
+        while let Some(name) = self.pending.pop_front() {
+            if self.written.contains_key(&name) {
+                continue;
+            }
+            // reference() made sure there's exactly one serde definition
+            let definitions = self.definitions.remove(&name).unwrap_or_default();
+            let Some(definition) = definitions.into_iter().find(|definition| match definition {
+                Definition::Struct(item) => derives_serde(&item.attrs),
+                Definition::Enum(item) => derives_serde(&item.attrs),
+            }) else {
+                continue;
+            };
+            let ts = match &definition {
+                Definition::Struct(item) => self.structure(item),
+                Definition::Enum(item) => self.enumeration(item),
+            };
+            self.written.insert(name, ts);
+        }
+    }
+
+    fn command(&mut self, function: &ItemFn) -> Command {
+        let mut args = Vec::new();
+        let mut raw = false;
+        for input in &function.sig.inputs {
+            let FnArg::Typed(input) = input else {
+                continue;
+            };
+            let Pat::Ident(pat) = input.pat.as_ref() else {
+                continue;
+            };
+            let type_name = match input.ty.as_ref() {
+                Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()).unwrap_or_default(),
+                _ => String::new(),
+            };
+            let ts = match type_name.as_str() {
+                // Injected by Tauri, not passed from JS
+                "State" | "AppHandle" | "Window" | "Webview" | "WebviewWindow" => continue,
+                "Request" => {
+                    raw = true;
+                    continue;
+                }
+                "Channel" => "Channel<ArrayBuffer>".to_string(),
+                _ => self.ty(&input.ty),
+            };
+            let optional = ts.ends_with("| null");
+            args.push((rename(&pat.ident.to_string(), Some("camelCase")), ts, optional));
+        }
+        let returns = match &function.sig.output {
+            ReturnType::Default => "void".to_string(),
+            ReturnType::Type(_, ty) => match ty.as_ref() {
+                Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Result") => {
+                    let segment = path.path.segments.last().expect("checked above");
+                    match Self::generic_args(&segment.arguments).first() {
+                        Some(ok) => self.ty(ok),
+                        None => "unknown".to_string(),
+                    }
+                }
+                ty => self.ty(ty),
+            },
+        };
+        Command { name: function.sig.ident.to_string(), args, returns, raw }
+    }
+}
+
+fn render(plugin: &str, commands: &[Command], events: &[(String, String)], types: &BTreeMap<String, String>) -> String {
+    let mut ts = String::new();
+    let _ = writeln!(ts, "// Generated by build.rs (build/bindings.rs) from the plugin's commands and event payloads.");
+    let _ = writeln!(ts, "// Don't edit by hand; it's rewritten on every build.");
+    let _ = writeln!(ts, "import {{ Channel, invoke }} from '@tauri-apps/api/core';");
+    let _ = writeln!(ts, "import {{ listen, type EventCallback, type UnlistenFn }} from '@tauri-apps/api/event';");
+    let _ = writeln!(ts);
+    let _ = writeln!(ts, "export {{ Channel }};");
+    let _ = writeln!(ts);
+    let _ = writeln!(ts, "const PLUGIN = 'plugin:{}|';", plugin);
+    let _ = writeln!(ts);
+    let _ = writeln!(ts, "// Failed commands reject with a PipeError");
+    let _ = writeln!(ts, "export const commands = {{");
+    for command in commands {
+        let function = rename(&command.name, Some("camelCase"));
+        let invoked = format!("`${{PLUGIN}}{}`", command.name);
+        if command.raw {
+            let _ = writeln!(
+                ts,
+                "  {}: (body: ArrayBuffer | Uint8Array, headers?: Record<string, string>) =>\n    invoke<{}>({}, body, headers ? {{ headers }} : undefined),",
+                function, command.returns, invoked
+            );
+        } else if command.args.is_empty() {
+            let _ = writeln!(ts, "  {}: () => invoke<{}>({}),", function, command.returns, invoked);
+        } else {
+            let members: Vec<String> = command
+                .args
+                .iter()
+                .map(|(name, ty, optional)| format!("{}{}: {}", name, if *optional { "?" } else { "" }, ty))
+                .collect();
+            let default = if command.args.iter().all(|(_, _, optional)| *optional) { " = {}" } else { "" };
+            let _ = writeln!(
+                ts,
+                "  {}: (args: {{ {} }}{}) => invoke<{}>({}, args),",
+                function,
+                members.join("; "),
+                default,
+                command.returns,
+                invoked
+            );
+        }
+    }
+    let _ = writeln!(ts, "}};");
+    let _ = writeln!(ts);
+    let _ = writeln!(ts, "export type Events = {{");
+    for (name, payload) in events {
+        if name.contains("${") {
+            let _ = writeln!(ts, "  [event: `{}`]: {};", name, payload);
+        } else {
+            let _ = writeln!(ts, "  {}: {};", quote(name), payload);
+        }
+    }
+    let _ = writeln!(ts, "}};");
+    let _ = writeln!(ts);
+    let _ = writeln!(ts, "export function listenTo<E extends keyof Events & string>(event: E, handler: EventCallback<Events[E]>): Promise<UnlistenFn> {{");
+    let _ = writeln!(ts, "  return listen<Events[E]>(event, handler);");
+    let _ = writeln!(ts, "}}");
+    for (name, ty) in types {
+        let _ = writeln!(ts);
+        let _ = writeln!(ts, "export type {} = {};", name, ty);
+    }
+    ts
+}
+
+// Parse src/, write the bindings to `out` (if they changed) and return the command names
+pub fn generate(plugin: &str, src: &Path, out: &Path, events: &[(&str, &str)]) -> Result<Vec<String>, String> {
+    let mut items = Vec::new();
+    let mut functions = Vec::new();
+    let mut files: Vec<_> = fs::read_dir(src).map_err(|e| e.to_string())?.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    files.sort();
+    for file in files.into_iter().filter(|file| file.extension().is_some_and(|extension| extension == "rs")) {
+        let text = fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        let parsed = syn::parse_file(&text).map_err(|e| format!("{}: {}", file.display(), e))?;
+        for item in parsed.items {
+            match item {
+                Item::Fn(function) if is_command(&function) => functions.push(function),
+                item => items.push(item),
+            }
+        }
+    }
+
+    let mut generator = Generator::new(items);
+    let commands: Vec<Command> = functions.iter().map(|function| generator.command(function)).collect();
+    let events: Vec<(String, String)> = events.iter().map(|(name, payload)| (name.to_string(), generator.reference(payload))).collect();
+    // What failed commands reject with
+    generator.reference("PipeError");
+    generator.write_pending();
+
+    let ts = render(plugin, &commands, &events, &generator.written);
+    if fs::read_to_string(out).ok().as_deref() != Some(ts.as_str()) {
+        if let Some(dir) = out.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(out, ts).map_err(|e| format!("{}: {}", out.display(), e))?;
+    }
+    Ok(commands.into_iter().map(|command| command.name).collect())
+}
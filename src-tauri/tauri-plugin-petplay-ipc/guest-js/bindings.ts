@@ -0,0 +1,366 @@
+// Generated by build.rs (build/bindings.rs) from the plugin's commands and event payloads.
+// Don't edit by hand; it's rewritten on every build.
+import { Channel, invoke } from '@tauri-apps/api/core';
+import { listen, type EventCallback, type UnlistenFn } from '@tauri-apps/api/event';
+
+export { Channel };
+
+const PLUGIN = 'plugin:petplay-ipc|';
+
+// Failed commands reject with a PipeError
+export const commands = {
+  sendFrameData: (body: ArrayBuffer | Uint8Array, headers?: Record<string, string>) =>
+    invoke<number | null>(`${PLUGIN}send_frame_data`, body, headers ? { headers } : undefined),
+  setTargetFps: (args: { fps: number }) => invoke<null>(`${PLUGIN}set_target_fps`, args),
+  startRecording: (args: { path?: string | null } = {}) => invoke<string>(`${PLUGIN}start_recording`, args),
+  stopRecording: () => invoke<RecordingSummary>(`${PLUGIN}stop_recording`),
+  startSessionRecording: (args: { path?: string | null } = {}) => invoke<string>(`${PLUGIN}start_session_recording`, args),
+  stopSessionRecording: () => invoke<SessionSummary>(`${PLUGIN}stop_session_recording`),
+  replaySession: (args: { path: string; speed?: number | null }) => invoke<SessionReplaySummary>(`${PLUGIN}replay_session`, args),
+  captureScreenshot: (args: { path: string }) => invoke<Screenshot>(`${PLUGIN}capture_screenshot`, args),
+  exportDiagnostics: (args: { path?: string | null } = {}) => invoke<DiagnosticsBundle>(`${PLUGIN}export_diagnostics`, args),
+  showFramePreview: (args: { visible: boolean }) => invoke<null>(`${PLUGIN}show_frame_preview`, args),
+  subscribeFramePreview: (args: { channel: Channel<ArrayBuffer> }) => invoke<void>(`${PLUGIN}subscribe_frame_preview`, args),
+  setLogLevel: (args: { level: LogLevel; module?: string | null }) => invoke<null>(`${PLUGIN}set_log_level`, args),
+  launchBackend: (args: { executable?: string | null; args?: string[] | null } = {}) => invoke<number | null>(`${PLUGIN}launch_backend`, args),
+  stopBackend: () => invoke<boolean>(`${PLUGIN}stop_backend`),
+  createPipeConnection: (args: { name: string; path: string; window?: string | null }) => invoke<null>(`${PLUGIN}create_pipe_connection`, args),
+  destroyPipeConnection: (args: { name: string }) => invoke<null>(`${PLUGIN}destroy_pipe_connection`, args),
+  listPipeConnections: () => invoke<NamedPipeStatus[]>(`${PLUGIN}list_pipe_connections`),
+  registerOverlay: (args: { name: string }) => invoke<Overlay>(`${PLUGIN}register_overlay`, args),
+  createOverlay: (args: { name: string; settings?: OverlaySettings | null }) => invoke<OverlayInfo>(`${PLUGIN}create_overlay`, args),
+  listOverlays: () => invoke<OverlayInfo[]>(`${PLUGIN}list_overlays`),
+  setOverlayTransform: (args: { matrix: number[]; overlayId?: number | null }) => invoke<null>(`${PLUGIN}set_overlay_transform`, args),
+  setOverlayWidth: (args: { widthMeters: number; overlayId?: number | null }) => invoke<null>(`${PLUGIN}set_overlay_width`, args),
+  setOverlayCurvature: (args: { curvature: number; overlayId?: number | null }) => invoke<null>(`${PLUGIN}set_overlay_curvature`, args),
+  setOverlayAlpha: (args: { alpha: number; overlayId?: number | null }) => invoke<null>(`${PLUGIN}set_overlay_alpha`, args),
+  getOverlayPlacement: (args: { overlayId?: number | null } = {}) => invoke<OverlayPlacement>(`${PLUGIN}get_overlay_placement`, args),
+  unregisterOverlay: (args: { id: number }) => invoke<null>(`${PLUGIN}unregister_overlay`, args),
+  setDashboardMode: (args: { enabled: boolean; iconPath?: string | null }) => invoke<DashboardConfig>(`${PLUGIN}set_dashboard_mode`, args),
+  destroyOverlay: (args: { id: number }) => invoke<null>(`${PLUGIN}destroy_overlay`, args),
+  setOverlayAttachment: (args: { device?: string | null; overlayId?: number | null } = {}) => invoke<null>(`${PLUGIN}set_overlay_attachment`, args),
+  pauseStream: () => invoke<boolean>(`${PLUGIN}pause_stream`),
+  resumeStream: () => invoke<boolean>(`${PLUGIN}resume_stream`),
+  shareGpuTexture: (args: { texture: SharedTexture }) => invoke<null>(`${PLUGIN}share_gpu_texture`, args),
+  configureStream: (args: { options: StreamOptions }) => invoke<null>(`${PLUGIN}configure_stream`, args),
+  setFrameChannel: (args: { channel: FrameChannel }) => invoke<string>(`${PLUGIN}set_frame_channel`, args),
+  connectPipes: () => invoke<void>(`${PLUGIN}connect_pipes`),
+  disconnectPipes: () => invoke<null>(`${PLUGIN}disconnect_pipes`),
+  reconnectFramePipe: () => invoke<null>(`${PLUGIN}reconnect_frame_pipe`),
+  setPipePaths: (args: { frame: string; transform: string; input?: string | null; audio?: string | null; microphone?: string | null; video?: string | null; files?: string | null; pointer?: string | null }) => invoke<null>(`${PLUGIN}set_pipe_paths`, args),
+  configurePipe: (args: { inBufferSize?: number | null; outBufferSize?: number | null; mode?: PipeMode | null } = {}) => invoke<PipeOptions>(`${PLUGIN}configure_pipe`, args),
+  getBackendInfo: () => invoke<BackendInfo | null>(`${PLUGIN}get_backend_info`),
+  setHeartbeat: (args: { config: HeartbeatConfig }) => invoke<null>(`${PLUGIN}set_heartbeat`, args),
+  setTransformRate: (args: { rateHz: number }) => invoke<void>(`${PLUGIN}set_transform_rate`, args),
+  getLastTransform: (args: { device: string }) => invoke<LastTransformPayload | null>(`${PLUGIN}get_last_transform`, args),
+  setTransformFilter: (args: { mode?: FilterMode | null; minCutoff?: number | null; beta?: number | null; dCutoff?: number | null } = {}) => invoke<FilterConfig>(`${PLUGIN}set_transform_filter`, args),
+  setAdaptiveQuality: (args: { mode?: AdaptiveMode | null; targetFps?: number | null; minScale?: number | null } = {}) => invoke<AdaptiveConfig>(`${PLUGIN}set_adaptive_quality`, args),
+  setFoveation: (args: { enabled?: boolean | null; radius?: number | null; falloff?: number | null; maxBlock?: number | null } = {}) => invoke<FoveationConfig>(`${PLUGIN}set_foveation`, args),
+  getWebrtcConfig: () => invoke<WebRtcConfig>(`${PLUGIN}get_webrtc_config`),
+  submitRemotePoses: (body: ArrayBuffer | Uint8Array, headers?: Record<string, string>) =>
+    invoke<number>(`${PLUGIN}submit_remote_poses`, body, headers ? { headers } : undefined),
+  enableOscBridge: (args: { enabled: boolean }) => invoke<OscConfig>(`${PLUGIN}enable_osc_bridge`, args),
+  configureOsc: (args: { sendTo?: string | null; listen?: string | null; sendTracking?: boolean | null; sendInput?: boolean | null } = {}) => invoke<OscConfig>(`${PLUGIN}configure_osc`, args),
+  enableNdiOutput: (args: { enabled: boolean; name?: string | null; frameRate?: number | null }) => invoke<NdiConfig>(`${PLUGIN}enable_ndi_output`, args),
+  enableOpenvr: (args: { enabled: boolean; widthMeters?: number | null }) => invoke<OpenVrConfig>(`${PLUGIN}enable_openvr`, args),
+  enableOpenxr: (args: { enabled: boolean; widthMeters?: number | null }) => invoke<OpenXrConfig>(`${PLUGIN}enable_openxr`, args),
+  addSink: (args: { sink: SinkSpec; id?: string | null; queueDepth?: number | null; backpressure?: BackpressurePolicy | null }) => invoke<string>(`${PLUGIN}add_sink`, args),
+  removeSink: (args: { id: string }) => invoke<null>(`${PLUGIN}remove_sink`, args),
+  listSinks: () => invoke<SinkInfo[]>(`${PLUGIN}list_sinks`),
+  setPosePrediction: (args: { horizonMs: number }) => invoke<null>(`${PLUGIN}set_pose_prediction`, args),
+  subscribeTransforms: (args: { channel: Channel<ArrayBuffer>; devices?: string[] | null; format?: TransformFormat | null }) => invoke<number>(`${PLUGIN}subscribe_transforms`, args),
+  unsubscribeTransforms: (args: { id: number }) => invoke<boolean>(`${PLUGIN}unsubscribe_transforms`, args),
+  getDeviceStatus: () => invoke<DeviceStatus[]>(`${PLUGIN}get_device_status`),
+  getPlayspaceBounds: () => invoke<Playspace | null>(`${PLUGIN}get_playspace_bounds`),
+  subscribeHands: (args: { channel: Channel<ArrayBuffer>; hand?: Hand | null }) => invoke<number>(`${PLUGIN}subscribe_hands`, args),
+  unsubscribeHands: (args: { id: number }) => invoke<boolean>(`${PLUGIN}unsubscribe_hands`, args),
+  subscribeVideo: (args: { channel: Channel<ArrayBuffer>; streamId?: number | null }) => invoke<number>(`${PLUGIN}subscribe_video`, args),
+  unsubscribeVideo: (args: { id: number }) => invoke<boolean>(`${PLUGIN}unsubscribe_video`, args),
+  sendHapticPulse: (args: { device: string; durationUs: number; amplitude: number }) => invoke<null>(`${PLUGIN}send_haptic_pulse`, args),
+  sendAudioChunk: (body: ArrayBuffer | Uint8Array, headers?: Record<string, string>) =>
+    invoke<null>(`${PLUGIN}send_audio_chunk`, body, headers ? { headers } : undefined),
+  sendFileToBackend: (body: ArrayBuffer | Uint8Array, headers?: Record<string, string>) =>
+    invoke<number>(`${PLUGIN}send_file_to_backend`, body, headers ? { headers } : undefined),
+  takeReceivedFile: (args: { transferId: number }) => invoke<ArrayBuffer>(`${PLUGIN}take_received_file`, args),
+  listAudioInputs: () => invoke<AudioInput[]>(`${PLUGIN}list_audio_inputs`),
+  startMicrophone: (args: { deviceId?: string | null } = {}) => invoke<MicrophoneInfo>(`${PLUGIN}start_microphone`, args),
+  stopMicrophone: () => invoke<null>(`${PLUGIN}stop_microphone`),
+  startDesktopCapture: (args: { target: CaptureTarget; options?: CaptureOptions | null }) => invoke<DesktopCaptureInfo>(`${PLUGIN}start_desktop_capture`, args),
+  stopDesktopCapture: (args: { overlayId?: number | null } = {}) => invoke<null>(`${PLUGIN}stop_desktop_capture`, args),
+  listCapturableWindows: (args: { thumbnailSize?: number | null } = {}) => invoke<CapturableWindow[]>(`${PLUGIN}list_capturable_windows`, args),
+  captureWindow: (args: { hwnd: number; options?: WindowCaptureOptions | null }) => invoke<WindowCaptureInfo>(`${PLUGIN}capture_window`, args),
+  getLatencyHistogram: (args: { reset?: boolean | null } = {}) => invoke<FrameLatencySnapshot>(`${PLUGIN}get_latency_histogram`, args),
+  getPipeMetrics: () => invoke<PipeMetricsSnapshot>(`${PLUGIN}get_pipe_metrics`),
+  setPipeStatsInterval: (args: { intervalMs: number }) => invoke<void>(`${PLUGIN}set_pipe_stats_interval`, args),
+  getConnectionStatus: () => invoke<ConnectionStatus>(`${PLUGIN}get_connection_status`),
+  setReconnectPolicy: (args: { policy: ReconnectPolicy }) => invoke<void>(`${PLUGIN}set_reconnect_policy`, args),
+};
+
+export type Events = {
+  'pipe-state-changed': PipeStateChangedPayload;
+  'pipe-connected': PipeConnectedPayload;
+  'pipe-disconnected': PipeDisconnectedPayload;
+  'pipe-timeout': PipeTimeoutPayload;
+  'pipe-connection-failed': PipeConnectionFailedPayload;
+  'pipe-stats': PipeMetricsSnapshot;
+  'target-resolution': TargetResolutionPayload;
+  'encoder-fallback': EncoderFallbackPayload;
+  'frames-dropped': FramesDroppedPayload;
+  'audio-dropped': AudioDroppedPayload;
+  'microphone-level': MicrophoneLevel;
+  'microphone-stopped': MicrophoneStoppedPayload;
+  'desktop-capture-stopped': DesktopCaptureStoppedPayload;
+  'video-frame': VideoFramePayload;
+  'file-transfer-progress': FileTransferProgress;
+  'file-transfer-complete': FileTransferComplete;
+  'vr-pointer': VrPointerPayload;
+  'vr-key': VrKeyPayload;
+  'frame-acked': FrameAckedPayload;
+  'osc-message': OscMessage;
+  'transform-update': TransformUpdatePayload;
+  'gaze-update': Gaze;
+  'hand-tracking-changed': HandTrackingChanged;
+  'device-status': DeviceStatus;
+  'device-battery-low': BatteryLow;
+  'playspace-changed': Playspace;
+  'dashboard-visibility': DashboardVisibility;
+  [event: `transform-update:${string}`]: TransformUpdatePayload;
+  'overlay-transform': OverlayTransformPayload;
+  'transform-invalid': TransformInvalidPayload;
+  'controller-input': ControllerInputPayload;
+  'backend-started': BackendStartedPayload;
+  'backend-stopped': BackendStoppedPayload;
+  'backend-crashed': BackendCrashedPayload;
+  'backend-control': BackendControlPayload;
+  'second-instance': SecondInstancePayload;
+  'config-reloaded': ConfigReloadedPayload;
+  'crash-report': CrashReport;
+};
+
+export function listenTo<E extends keyof Events & string>(event: E, handler: EventCallback<Events[E]>): Promise<UnlistenFn> {
+  return listen<Events[E]>(event, handler);
+}
+
+export type AdaptiveConfig = { mode?: AdaptiveMode; targetFps?: number; minScale?: number };
+
+export type AdaptiveMode = 'off' | 'resolution' | 'compression' | 'auto';
+
+export type AlphaMode = 'straight' | 'premultiplied';
+
+export type AudioDroppedPayload = { pipe: PipeKind; dropped: number; total: number };
+
+export type AudioInput = { id: string; name: string; isDefault: boolean };
+
+export type BackendControlPayload = { connection?: string | null } & ControlMessage;
+
+export type BackendCrashedPayload = { exitCode?: number | null; restarts: number; restartInMs?: number | null };
+
+export type BackendInfo = { protocolVersion: number; capabilities: number; compression: string[]; gpuTexture: boolean; heartbeat: boolean; multiDevice: boolean; pixelFormats: string[]; rowStride: boolean; delta: boolean; control: boolean; frameAck: boolean; tiledCompression: boolean; linearColor: boolean; premultipliedAlpha: boolean; flipY: boolean; backendName?: string | null };
+
+export type BackendStartedPayload = { pid?: number | null; executable: string; restarts: number };
+
+export type BackendStoppedPayload = { exitCode?: number | null; requested: boolean };
+
+export type BackpressurePolicy = 'drop-oldest' | 'drop-newest' | 'block';
+
+export type BatteryLow = { device: string; deviceId: number; batteryPercent: number };
+
+export type Bucket = { leUs?: number | null; count: number };
+
+export type BufferPoolStats = { hits: number; misses: number; discarded: number; retainedBuffers: number; retainedBytes: number };
+
+export type CapturableWindow = { hwnd: number; title: string; processId: number; width: number; height: number; minimized: boolean; thumbnail?: string | null };
+
+export type CaptureOptions = { maxFps?: number; cursor?: boolean; overlayId?: number };
+
+export type CaptureTarget = { monitor: number } | { window: number };
+
+export type ColorOptions = { transfer?: TransferFunction | null; alpha?: AlphaMode | null; flipY?: boolean | null };
+
+export type Compression = 'none' | 'lz4' | 'zstd';
+
+export type ConfigReloadedPayload = { path: string; applied: string[]; rejected: string[]; restartRequired: string[] };
+
+export type ConnectFailure = 'not-found' | 'busy' | 'unauthorized' | 'other';
+
+export type ConnectionStatus = { frame: PipeStatus; transform: PipeStatus; input: PipeStatus; audio: PipeStatus; microphone: PipeStatus; video: PipeStatus; files: PipeStatus; pointer: PipeStatus; tasks: Record<string, TaskHealth> };
+
+export type ControlMessage = { kind: 'pause' } | { kind: 'resume' } | { kind: 'resolution-changed'; width: number; height: number } | { kind: 'overlay-hidden'; overlayId: number } | { kind: 'overlay-shown'; overlayId: number } | { kind: 'set-overlay-transform'; overlayId: number; matrix: number[] } | { kind: 'set-overlay-width'; overlayId: number; widthMeters: number } | { kind: 'set-overlay-curvature'; overlayId: number; curvature: number } | { kind: 'set-overlay-alpha'; overlayId: number; alpha: number } | { kind: 'set-overlay-attachment'; overlayId: number; deviceId?: number | null } | { kind: 'set-dashboard-mode'; enabled: boolean; iconPath?: string | null } | { kind: 'dashboard-shown' } | { kind: 'dashboard-hidden' } | { kind: 'unknown'; code: number; body: number[] };
+
+export type ControllerInputPayload = { device: string; deviceId: number; control: string; pressed: boolean; touched: boolean; x: number; y: number; timestampUs: number };
+
+export type CrashReport = { path: string; timestampMs: number; message: string; report: string };
+
+export type DashboardConfig = { enabled?: boolean; iconPath?: string | null };
+
+export type DashboardVisibility = { visible: boolean };
+
+export type DesktopCaptureInfo = { target: CaptureTarget; overlayId: number; monitor: string; width: number; height: number };
+
+export type DesktopCaptureStoppedPayload = { overlayId: number; reason: string };
+
+export type DeviceStatus = { device: string; deviceId: number; batteryPercent?: number | null; charging: boolean; connected: boolean; tracking: TrackingState; firmware: string };
+
+export type DiagnosticsBundle = { path: string; files: string[]; bytes: number };
+
+export type EncoderBackend = 'auto' | 'nvenc' | 'amf' | 'qsv';
+
+export type EncoderFallbackPayload = { reason: string };
+
+export type EncoderSettings = { codec?: VideoCodec; backend?: EncoderBackend; bitrateKbps?: number; keyframeInterval?: number; framerate?: number; input?: YuvLayout };
+
+export type FileTransferComplete = { transferId: number; direction: TransferDirection; name: string; size: number; error?: string | null };
+
+export type FileTransferProgress = { transferId: number; direction: TransferDirection; name: string; bytes: number; total: number };
+
+export type FilterConfig = { mode?: FilterMode; minCutoff?: number; beta?: number; dCutoff?: number };
+
+export type FilterMode = 'off' | 'one-euro' | 'bypass';
+
+export type FoveationConfig = { enabled?: boolean; radius?: number; falloff?: number; maxBlock?: number };
+
+export type FrameAckedPayload = { connection?: string | null; sequence: number; frames: number; roundTripUs?: number | null };
+
+export type FrameChannel = 'pipe' | 'shared-memory';
+
+export type FrameLatencySnapshot = { ipc: HistogramSnapshot; enqueue: HistogramSnapshot; queued: HistogramSnapshot; write: HistogramSnapshot; total: HistogramSnapshot };
+
+export type FramesDroppedPayload = { dropped: number; total: number };
+
+export type Gaze = { origin: number[]; direction: number[]; confidence: number; leftOpenness: number; rightOpenness: number; timestampUs: number; overlayHit?: OverlayHit | null };
+
+export type Hand = 'left' | 'right';
+
+export type HandTrackingChanged = { hand: Hand; tracked: boolean };
+
+export type HeartbeatConfig = { intervalMs?: number; timeoutMs?: number };
+
+export type HistogramSnapshot = { count: number; sumUs: number; meanUs: number; maxUs: number; p50Us: number; p95Us: number; p99Us: number; buckets: Bucket[] };
+
+export type ImageCodec = 'png' | 'jpeg';
+
+export type KeyKind = 'down' | 'up';
+
+export type LastTransformPayload = { device: string; matrix: number[]; ageUs: number } & Pose;
+
+export type LogLevel = 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+export type MicrophoneInfo = { device: AudioInput; sampleRate: number; channels: number };
+
+export type MicrophoneLevel = { peak: number; rms: number };
+
+export type MicrophoneStoppedPayload = { reason: string };
+
+export type NamedPipeStatus = { name: string; window?: string | null; frame: PipeStatus };
+
+export type NdiConfig = { enabled?: boolean; name?: string; groups?: string; frameRate?: number };
+
+export type OpenVrConfig = { enabled?: boolean; overlayKey?: string; overlayName?: string; widthMeters?: number; poseRateHz?: number; libraryPath?: string | null };
+
+export type OpenXrConfig = { enabled?: boolean; widthMeters?: number; placement?: number; poseRateHz?: number; libraryPath?: string | null };
+
+export type OscArg = number | boolean | string;
+
+export type OscConfig = { enabled?: boolean; sendTo?: string; listen?: string; sendTracking?: boolean; sendInput?: boolean };
+
+export type OscMessage = { address: string; args: OscArg[] };
+
+export type Overlay = { id: number; name: string; window: string };
+
+export type OverlayHit = { overlayId: number; x: number; y: number };
+
+export type OverlayInfo = { placement: OverlayPlacement } & Overlay;
+
+export type OverlayPlacement = { transform?: number[] | null; widthMeters?: number | null; curvature?: number | null; alpha?: number | null; attachedDeviceId?: number | null };
+
+export type OverlaySettings = { attachedDevice?: string | null; transform?: number[] | null; widthMeters?: number | null };
+
+export type OverlayTransformPayload = { overlayId: number; matrix: number[] };
+
+export type PipeConnectedPayload = { pipe: PipeKind; connection?: string | null; path: string };
+
+export type PipeConnectionFailedPayload = { pipe: PipeKind; connection?: string | null; attempts: number; lastError?: string | null; lastFailure?: ConnectFailure | null };
+
+export type PipeDisconnectedPayload = { pipe: PipeKind; connection?: string | null; reason: string };
+
+export type PipeError = { code: string; message: string; ioKind: string | null };
+
+export type PipeKind = 'frame' | 'transform' | 'input' | 'audio' | 'microphone' | 'video' | 'files' | 'pointer';
+
+export type PipeMetricsSnapshot = { framesSent: number; bytesSent: number; framesDropped: number; framesCoalesced: number; framesRejected: number; rateLimited: number; payloadsTooLarge: number; transformsReceived: number; fps: number; targetFps: number; bytesPerSec: number; transformsPerSec: number; avgWriteUs: number; lastSequence: number; lastLatencyUs: number; bufferPool: BufferPoolStats };
+
+export type PipeMode = 'byte' | 'message';
+
+export type PipeOperation = 'read' | 'write';
+
+export type PipeOptions = { inBufferSize?: number | null; outBufferSize?: number | null; mode?: PipeMode };
+
+export type PipeState = { state: 'disconnected' } | { state: 'connecting'; attempt: number } | { state: 'handshaking' } | { state: 'connected'; sinceMs: number } | { state: 'backoff'; untilMs: number };
+
+export type PipeStateChangedPayload = { pipe: PipeKind; connection?: string | null; state: PipeState; previous: PipeState };
+
+export type PipeStatus = { path: string; state: PipeState; connected: boolean; connectedSinceMs?: number | null; failedAttempts: number; lastError?: string | null; lastFailure?: ConnectFailure | null; gaveUp: boolean; connects: number };
+
+export type PipeTimeoutPayload = { pipe: PipeKind; connection?: string | null; operation: PipeOperation; timeoutMs: number };
+
+export type Playspace = { standingZero: number[]; playArea: number[]; bounds: number[][] };
+
+export type PointerKind = 'move' | 'down' | 'up' | 'leave' | 'scroll';
+
+export type Pose = { deviceId: number; position: number[]; orientation: number[]; linearVelocity: number[]; angularVelocity: number[]; timestampUs: number };
+
+export type ReconnectPolicy = { initialDelayMs?: number; maxDelayMs?: number; multiplier?: number; jitter?: number; maxRetries?: number | null };
+
+export type RecordingSummary = { path: string; frames: number; bytes: number; dropped: number; durationMs: number };
+
+export type Screenshot = { path: string; width: number; height: number; sequence: number; overlayId: number };
+
+export type SecondInstancePayload = { args: string[]; cwd: string };
+
+export type SessionReplaySummary = { replayed: number; skipped: number; durationMs: number };
+
+export type SessionSummary = { path: string; messages: number; bytes: number; dropped: number; durationMs: number };
+
+export type SharedTexture = { handle: number; width: number; height: number; dxgiFormat: number; keyedMutexKey?: number | null; ntHandle?: boolean };
+
+export type SinkInfo = { id: string; description: string; dropped: number } & SinkQueueOptions;
+
+export type SinkQueueOptions = { queueDepth?: number; backpressure?: BackpressurePolicy };
+
+export type SinkSpec = { kind: 'spout'; name: string } | ({ kind: 'ndi' } & NdiConfig);
+
+export type StreamOptions = { backpressure?: BackpressurePolicy | null; queueDepth?: number | null; compression?: Compression | null; encoding?: EncoderSettings | null; delta?: boolean | null; keyframeInterval?: number | null; spoutSender?: string | null; targetFps?: number | null; ackWindow?: number | null; zstdLevel?: number | null; compressionWorkers?: number | null; color?: ColorOptions | null };
+
+export type TargetResolutionPayload = { scale: number; width: number; height: number };
+
+export type TaskHealth = { running: boolean; panics: number; restarts: number; lastPanic?: string | null; gaveUp: boolean };
+
+export type TrackingState = 'not-tracking' | 'ok' | 'out-of-range' | 'calibrating';
+
+export type TransferDirection = 'toBackend' | 'fromBackend';
+
+export type TransferFunction = 'srgb' | 'linear';
+
+export type TransformFormat = 'pose' | 'matrix';
+
+export type TransformInvalidPayload = { source: string; reason: string; values: number[] };
+
+export type TransformUpdatePayload = { device: string; matrix: number[] } & Pose;
+
+export type VideoCodec = 'none' | 'h264' | 'hevc';
+
+export type VideoFramePayload = { streamId: number; codec: ImageCodec; timestampUs: number; bytes: number };
+
+export type VrKeyPayload = { overlayId: number; kind: KeyKind; key: string; shift: boolean; ctrl: boolean; alt: boolean; meta: boolean; timestampUs: number };
+
+export type VrPointerPayload = { overlayId: number; device: string; kind: PointerKind; button: number; x: number; y: number; deltaX: number; deltaY: number; timestampUs: number };
+
+export type WebRtcCodec = 'vp8' | 'h264';
+
+export type WebRtcConfig = { enabled?: boolean; whipUrl?: string; whipToken?: string; codec?: WebRtcCodec; maxFps?: number; receivePoses?: boolean };
+
+export type WindowCaptureInfo = { hwnd: number; title: string; overlayId: number };
+
+export type WindowCaptureOptions = { maxFps?: number; overlayId?: number };
+
+export type YuvLayout = 'nv12' | 'i420';
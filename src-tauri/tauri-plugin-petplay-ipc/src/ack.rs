@@ -0,0 +1,138 @@
+// --- Frame acknowledgements ---
+// Backends that accept CAP_FRAME_ACK answer the frames they've taken (Frame
+// and FrameReady messages) with a FrameAck on the frame pipe carrying the
+// frame's sequence number, the same one send_frame_data returned. An ack
+// covers every earlier frame as well, so a backend may ack only the newest
+// of several. Each ack becomes a "frame-acked" event with the round trip.
+// Frames that never reach the pipe (replaced under the FPS limit, dropped by
+// backpressure, lost with a connection) are never acknowledged.
+//
+// With an ack window set ([stream] ackWindow, or configure_stream) the writer
+// keeps at most that many unacknowledged frames on the pipe. If no ack comes
+// within the write timeout it stops waiting for the missing ones, so a
+// backend that stopped acking slows the stream down but can't stall it.
+//
+// FrameAck payload:
+//   [0..8)  sequence number of the newest frame taken (u64 LE)
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+pub const ACK_PAYLOAD_SIZE: usize = 8;
+
+// What one FrameAck settled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Acked {
+    // Frames this ack covered that were still waiting for one
+    pub frames: usize,
+    // From writing the acked frame to its ack; None if it wasn't waiting (already covered by a later ack)
+    pub round_trip_us: Option<u64>,
+}
+
+pub struct AckTracker {
+    // Unacknowledged frames allowed on the pipe; 0 for no limit
+    window: AtomicU32,
+    // Sequence number -> when the frame was written (µs since the Unix epoch)
+    in_flight: Mutex<BTreeMap<u64, u64>>,
+    acked: Notify,
+}
+
+impl AckTracker {
+    pub fn new(window: u32) -> Self {
+        Self { window: AtomicU32::new(window), in_flight: Mutex::new(BTreeMap::new()), acked: Notify::new() }
+    }
+
+    pub fn window(&self) -> u32 {
+        self.window.load(Ordering::Relaxed)
+    }
+
+    pub fn set_window(&self, window: u32) {
+        self.window.store(window, Ordering::Relaxed);
+        self.acked.notify_waiters();
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().len()
+    }
+
+    pub fn sent(&self, sequence: u64, written_us: u64) {
+        self.in_flight.lock().insert(sequence, written_us);
+    }
+
+    // Settle `sequence` and every frame before it
+    pub fn ack(&self, sequence: u64, now_us: u64) -> Acked {
+        let mut in_flight = self.in_flight.lock();
+        let later = in_flight.split_off(&sequence.saturating_add(1));
+        let settled = std::mem::replace(&mut *in_flight, later);
+        drop(in_flight);
+        self.acked.notify_waiters();
+        Acked {
+            frames: settled.len(),
+            round_trip_us: settled.get(&sequence).map(|written_us| now_us.saturating_sub(*written_us)),
+        }
+    }
+
+    // Stop waiting for any ack, e.g. because the connection they'd come on is gone
+    pub fn reset(&self) {
+        self.in_flight.lock().clear();
+        self.acked.notify_waiters();
+    }
+
+    fn has_room(&self) -> bool {
+        let window = self.window() as usize;
+        window == 0 || self.in_flight() < window
+    }
+
+    // Wait until the window has room for another frame; false if `limit` passed first
+    pub async fn wait_for_room(&self, limit: Option<Duration>) -> bool {
+        let deadline = limit.map(|limit| tokio::time::Instant::now() + limit);
+        loop {
+            let acked = self.acked.notified();
+            tokio::pin!(acked);
+            // Registered before checking, so an ack in between isn't missed
+            acked.as_mut().enable();
+            if self.has_room() {
+                return true;
+            }
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, acked).await.is_err() {
+                        return false;
+                    }
+                }
+                None => acked.await,
+            }
+        }
+    }
+}
+
+pub fn decode(payload: &[u8]) -> Result<u64, String> {
+    match payload.get(..ACK_PAYLOAD_SIZE) {
+        Some(sequence) => Ok(LittleEndian::read_u64(sequence)),
+        None => Err(format!("frame ack of {} bytes is shorter than {}", payload.len(), ACK_PAYLOAD_SIZE)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn an_ack_opens_the_window_for_everything_before_it() {
+        let tracker = AckTracker::new(2);
+        tracker.sent(1, 100);
+        tracker.sent(2, 200);
+        assert!(!tracker.wait_for_room(Some(Duration::from_millis(10))).await);
+
+        assert_eq!(tracker.ack(2, 250), Acked { frames: 2, round_trip_us: Some(50) });
+        assert_eq!(tracker.in_flight(), 0);
+        assert!(tracker.wait_for_room(Some(Duration::from_millis(10))).await);
+        // A late ack for a frame already covered settles nothing
+        assert_eq!(tracker.ack(1, 300), Acked { frames: 0, round_trip_us: None });
+    }
+}
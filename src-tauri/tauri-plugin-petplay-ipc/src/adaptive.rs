@@ -0,0 +1,217 @@
+// --- Adaptive quality ---
+// Degrades the stream step by step when the frame pipe can't keep up, and
+// recovers once it can. The writer task reports how long each pipe write
+// took; once per metrics sample the controller compares the average write
+// against the frame interval (1 / target_fps):
+//
+//   above HIGH_WATERMARK of it   step down: compress harder (none -> LZ4 ->
+//                                zstd, as far as the backend accepts), then
+//                                ask the frontend for a lower resolution
+//   below LOW_WATERMARK of it    step back up in the reverse order, after
+//   for RECOVER_SAMPLES samples  RECOVER_SAMPLES calm samples in a row
+//
+// A lower resolution is requested with a "target-resolution" event; the
+// frontend scales its capture by `scale`. `mode` limits which of the two
+// knobs the controller may turn. Turning it off restores both.
+use crate::compression::Compression;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+const HIGH_WATERMARK: f64 = 0.9;
+const LOW_WATERMARK: f64 = 0.5;
+const RECOVER_SAMPLES: u32 = 3;
+// Each resolution step multiplies the scale by this (about half the pixels every two steps)
+const SCALE_STEP: f32 = 0.75;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdaptiveMode {
+    #[default]
+    Off,
+    Resolution,
+    Compression,
+    // Compression first, resolution once compression is maxed out
+    Auto,
+}
+
+impl AdaptiveMode {
+    fn uses_compression(self) -> bool {
+        matches!(self, Self::Compression | Self::Auto)
+    }
+
+    fn uses_resolution(self) -> bool {
+        matches!(self, Self::Resolution | Self::Auto)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AdaptiveConfig {
+    pub mode: AdaptiveMode,
+    // Frame rate the pipe should sustain; writes slower than its interval mean the pipe is falling behind
+    pub target_fps: u32,
+    // Lowest resolution scale the frontend is asked for
+    pub min_scale: f32,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self { mode: AdaptiveMode::Off, target_fps: 90, min_scale: 0.5 }
+    }
+}
+
+impl AdaptiveConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target_fps == 0 {
+            return Err("Target frame rate must be positive".to_string());
+        }
+        if !(self.min_scale > 0.0 && self.min_scale <= 1.0) {
+            return Err(format!("Minimum scale must be in (0, 1], got {}", self.min_scale));
+        }
+        Ok(())
+    }
+
+    fn frame_interval_us(&self) -> f64 {
+        1_000_000.0 / self.target_fps as f64
+    }
+}
+
+// A step the controller took, for the caller to apply and report
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Adjustment {
+    // Frames are now compressed with this codec (the configured one when back to normal)
+    Compression(Compression),
+    // Resolution scale changed from `from` to `to`
+    Resolution { from: f32, to: f32 },
+}
+
+struct Controller {
+    config: AdaptiveConfig,
+    // Writes since the last sample
+    writes: u64,
+    total_write_us: u64,
+    // Consecutive samples below LOW_WATERMARK
+    calm_samples: u32,
+    // Codec used instead of the configured one while stepped down
+    compression: Option<Compression>,
+    scale: f32,
+}
+
+pub struct AdaptiveController {
+    controller: Mutex<Controller>,
+}
+
+impl AdaptiveController {
+    pub fn new(config: AdaptiveConfig) -> Self {
+        Self {
+            controller: Mutex::new(Controller {
+                config,
+                writes: 0,
+                total_write_us: 0,
+                calm_samples: 0,
+                compression: None,
+                scale: 1.0,
+            }),
+        }
+    }
+
+    pub fn config(&self) -> AdaptiveConfig {
+        self.controller.lock().config
+    }
+
+    // Returns the adjustments that undo steps the new mode no longer allows
+    pub fn set_config(&self, config: AdaptiveConfig, configured: Compression) -> Vec<Adjustment> {
+        let mut controller = self.controller.lock();
+        controller.config = config;
+        controller.calm_samples = 0;
+        let mut restored = Vec::new();
+        if !config.mode.uses_compression() && controller.compression.take().is_some() {
+            restored.push(Adjustment::Compression(configured));
+        }
+        if !config.mode.uses_resolution() && controller.scale < 1.0 {
+            restored.push(Adjustment::Resolution { from: controller.scale, to: 1.0 });
+            controller.scale = 1.0;
+        }
+        restored
+    }
+
+    pub fn record_write(&self, write_us: u64) {
+        let mut controller = self.controller.lock();
+        controller.writes += 1;
+        controller.total_write_us += write_us;
+    }
+
+    // The codec to use given the configured one; stronger while stepped down
+    pub fn compression(&self, configured: Compression) -> Compression {
+        self.controller.lock().effective_compression(configured)
+    }
+
+    // Evaluate the writes since the last call. `accepts` says whether the backend takes a codec.
+    pub fn sample(&self, configured: Compression, accepts: impl Fn(Compression) -> bool) -> Option<Adjustment> {
+        let mut controller = self.controller.lock();
+        let (writes, total_write_us) = (controller.writes, controller.total_write_us);
+        controller.writes = 0;
+        controller.total_write_us = 0;
+        if controller.config.mode == AdaptiveMode::Off || writes == 0 {
+            // Nothing to judge while idle
+            return None;
+        }
+        let load = total_write_us as f64 / writes as f64 / controller.config.frame_interval_us();
+        if load > HIGH_WATERMARK {
+            controller.calm_samples = 0;
+            return controller.step_down(configured, &accepts);
+        }
+        if load < LOW_WATERMARK {
+            controller.calm_samples += 1;
+            if controller.calm_samples >= RECOVER_SAMPLES {
+                controller.calm_samples = 0;
+                return controller.step_up(configured, &accepts);
+            }
+        } else {
+            controller.calm_samples = 0;
+        }
+        None
+    }
+}
+
+impl Controller {
+    fn effective_compression(&self, configured: Compression) -> Compression {
+        self.compression.map_or(configured, |stepped| stepped.max(configured))
+    }
+
+    fn step_down(&mut self, configured: Compression, accepts: &impl Fn(Compression) -> bool) -> Option<Adjustment> {
+        if self.config.mode.uses_compression() {
+            let current = self.effective_compression(configured);
+            let stronger = [Compression::Lz4, Compression::Zstd]
+                .into_iter()
+                .find(|&codec| codec > current && accepts(codec));
+            if let Some(codec) = stronger {
+                self.compression = Some(codec);
+                return Some(Adjustment::Compression(codec));
+            }
+        }
+        if self.config.mode.uses_resolution() && self.scale > self.config.min_scale {
+            let from = self.scale;
+            self.scale = (self.scale * SCALE_STEP).max(self.config.min_scale);
+            return Some(Adjustment::Resolution { from, to: self.scale });
+        }
+        None
+    }
+
+    fn step_up(&mut self, configured: Compression, accepts: &impl Fn(Compression) -> bool) -> Option<Adjustment> {
+        // Resolution comes back first: it's the step that's visible
+        if self.scale < 1.0 {
+            let from = self.scale;
+            let to = self.scale / SCALE_STEP;
+            // Snap to full resolution instead of stopping a rounding error short of it
+            self.scale = if to > 0.99 { 1.0 } else { to };
+            return Some(Adjustment::Resolution { from, to: self.scale });
+        }
+        let stepped = self.compression?;
+        let weaker = [Compression::Lz4]
+            .into_iter()
+            .find(|&codec| codec < stepped && codec > configured && accepts(codec));
+        self.compression = weaker;
+        Some(Adjustment::Compression(self.effective_compression(configured)))
+    }
+}
@@ -0,0 +1,307 @@
+// --- Audio channel ---
+// The overlay backend plays sound spatialized at the overlay it comes from.
+// The webview captures its own audio (e.g. with an AudioWorklet) and hands
+// interleaved PCM to send_audio_chunk as a raw body, describing it in headers:
+// "sample-rate" (Hz, default 48000), "channels" (default 2), "sample-format"
+// (f32 or s16, little endian, default f32) and "overlay-id" (default: the
+// window's overlay). Chunks go out as AudioChunk messages on a pipe of their
+// own (petplay-ipc-audio), so a burst of large frames never holds up sound.
+//
+// Backpressure is separate from the frame queue's: a short queue of chunks,
+// where the oldest is dropped when it's full, and chunks that waited longer
+// than maxLatencyMs by the time the pipe takes them are dropped too. Late
+// sound is worse than a gap. Both emit "audio-dropped".
+//
+//   [audio]
+//   queueDepth = 8
+//   maxLatencyMs = 200
+//
+// Payload layout (little endian):
+//   [0..4)   overlay id (u32)
+//   [4..8)   sample rate in Hz (u32)
+//   [8]      channels (u8)
+//   [9]      sample format (u8, 0 = f32, 1 = s16)
+//   [10..12) reserved, 0
+//   [12..20) capture timestamp, microseconds since the Unix epoch (u64)
+//   [20..24) frames, i.e. samples per channel (u32)
+//   [24..)   interleaved samples
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+pub const AUDIO_HEADER_SIZE: usize = 24;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+pub const DEFAULT_CHANNELS: u8 = 2;
+const MIN_SAMPLE_RATE: u32 = 8_000;
+const MAX_SAMPLE_RATE: u32 = 192_000;
+const MAX_CHANNELS: u8 = 8;
+// Longest chunk accepted, so one call can't queue seconds of sound
+const MAX_CHUNK_DURATION: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AudioConfig {
+    // Chunks waiting for the audio pipe before the oldest is dropped
+    pub queue_depth: usize,
+    // Chunks older than this when their turn comes are dropped instead of played late
+    pub max_latency_ms: u64,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self { queue_depth: 8, max_latency_ms: 200 }
+    }
+}
+
+impl AudioConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.queue_depth == 0 {
+            return Err("queueDepth must be positive".to_string());
+        }
+        if self.max_latency_ms == 0 {
+            return Err("maxLatencyMs must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleFormat {
+    F32,
+    S16,
+}
+
+impl SampleFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "f32" => Some(Self::F32),
+            "s16" => Some(Self::S16),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Self::F32 => 0,
+            Self::S16 => 1,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::F32),
+            1 => Some(Self::S16),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::S16 => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub sample_format: SampleFormat,
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        Self { sample_rate: DEFAULT_SAMPLE_RATE, channels: DEFAULT_CHANNELS, sample_format: SampleFormat::F32 }
+    }
+}
+
+impl AudioFormat {
+    // Frames (samples per channel) in `bytes` of interleaved samples, which have to be whole frames
+    pub fn frames(&self, bytes: usize) -> Result<u32, String> {
+        if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&self.sample_rate) {
+            return Err(format!("Sample rate must be between {} and {} Hz", MIN_SAMPLE_RATE, MAX_SAMPLE_RATE));
+        }
+        if self.channels == 0 || self.channels > MAX_CHANNELS {
+            return Err(format!("Channels must be between 1 and {}", MAX_CHANNELS));
+        }
+        let frame_size = self.channels as usize * self.sample_format.bytes_per_sample();
+        if bytes == 0 || !bytes.is_multiple_of(frame_size) {
+            return Err(format!("Audio chunk of {} bytes isn't a whole number of {} byte frames", bytes, frame_size));
+        }
+        let frames = bytes / frame_size;
+        let max_frames = self.sample_rate as u128 * MAX_CHUNK_DURATION.as_millis() / 1000;
+        if frames as u128 > max_frames {
+            return Err(format!("Audio chunk of {} frames is longer than {:?}", frames, MAX_CHUNK_DURATION));
+        }
+        Ok(frames as u32)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioHeader {
+    pub overlay_id: u32,
+    pub format: AudioFormat,
+    pub timestamp_us: u64,
+    pub frames: u32,
+}
+
+impl AudioHeader {
+    pub fn encode(&self) -> [u8; AUDIO_HEADER_SIZE] {
+        let mut bytes = [0u8; AUDIO_HEADER_SIZE];
+        LittleEndian::write_u32(&mut bytes[0..4], self.overlay_id);
+        LittleEndian::write_u32(&mut bytes[4..8], self.format.sample_rate);
+        bytes[8] = self.format.channels;
+        bytes[9] = self.format.sample_format.code();
+        LittleEndian::write_u64(&mut bytes[12..20], self.timestamp_us);
+        LittleEndian::write_u32(&mut bytes[20..24], self.frames);
+        bytes
+    }
+
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let bytes = payload.get(..AUDIO_HEADER_SIZE)?;
+        Some(Self {
+            overlay_id: LittleEndian::read_u32(&bytes[0..4]),
+            format: AudioFormat {
+                sample_rate: LittleEndian::read_u32(&bytes[4..8]),
+                channels: bytes[8],
+                sample_format: SampleFormat::from_code(bytes[9])?,
+            },
+            timestamp_us: LittleEndian::read_u64(&bytes[12..20]),
+            frames: LittleEndian::read_u32(&bytes[20..24]),
+        })
+    }
+}
+
+// A chunk waiting for the audio pipe; the samples are reference counted like queued frames' pixels
+#[derive(Clone)]
+pub struct AudioChunk {
+    pub header: AudioHeader,
+    pub samples: Bytes,
+    pub queued_at: Instant,
+}
+
+pub struct AudioQueue {
+    chunks: Mutex<VecDeque<AudioChunk>>,
+    depth: AtomicUsize,
+    max_latency_ms: AtomicU64,
+    chunk_available: Notify,
+    // Total chunks dropped, for being full or too late, since startup
+    dropped: AtomicU64,
+}
+
+impl AudioQueue {
+    pub fn new(config: AudioConfig) -> Self {
+        Self {
+            chunks: Mutex::new(VecDeque::with_capacity(config.queue_depth)),
+            depth: AtomicUsize::new(config.queue_depth.max(1)),
+            max_latency_ms: AtomicU64::new(config.max_latency_ms),
+            chunk_available: Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    // Enqueue a chunk, dropping the oldest ones if the queue is full; returns how many were dropped
+    pub fn push(&self, chunk: AudioChunk) -> usize {
+        let depth = self.depth.load(Ordering::Relaxed);
+        let mut chunks = self.chunks.lock();
+        let mut dropped = 0;
+        while chunks.len() >= depth {
+            chunks.pop_front();
+            dropped += 1;
+        }
+        chunks.push_back(chunk);
+        drop(chunks);
+        self.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+        self.chunk_available.notify_one();
+        dropped
+    }
+
+    // Wait for the next chunk that isn't too late yet, and how many too-late ones were dropped on the way
+    pub async fn pop(&self) -> (AudioChunk, usize) {
+        let mut late = 0;
+        loop {
+            let chunk = self.chunks.lock().pop_front();
+            match chunk {
+                Some(chunk) if chunk.queued_at.elapsed() > self.max_latency() => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    late += 1;
+                }
+                Some(chunk) => return (chunk, late),
+                None => self.chunk_available.notified().await,
+            }
+        }
+    }
+
+    // Discard every pending chunk (e.g. after a disconnect)
+    pub fn clear(&self) -> usize {
+        let mut chunks = self.chunks.lock();
+        let count = chunks.len();
+        chunks.clear();
+        count
+    }
+
+    pub fn configure(&self, config: AudioConfig) {
+        self.depth.store(config.queue_depth.max(1), Ordering::Relaxed);
+        self.max_latency_ms.store(config.max_latency_ms, Ordering::Relaxed);
+    }
+
+    fn max_latency(&self) -> Duration {
+        Duration::from_millis(self.max_latency_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn dropped_chunks(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(overlay_id: u32, queued_at: Instant) -> AudioChunk {
+        let header = AudioHeader { overlay_id, format: AudioFormat::default(), timestamp_us: 0, frames: 1 };
+        AudioChunk { header, samples: Bytes::from_static(&[0; 8]), queued_at }
+    }
+
+    #[test]
+    fn chunks_must_be_whole_frames_of_a_sane_format() {
+        let stereo_f32 = AudioFormat::default();
+        assert_eq!(stereo_f32.frames(480 * 8), Ok(480));
+        assert!(stereo_f32.frames(6).is_err());
+        assert!(stereo_f32.frames(0).is_err());
+        assert!(stereo_f32.frames(48_001 * 8).is_err());
+        let mono_s16 = AudioFormat { channels: 1, sample_format: SampleFormat::S16, ..stereo_f32 };
+        assert_eq!(mono_s16.frames(6), Ok(3));
+        assert!(AudioFormat { sample_rate: 4_000, ..stereo_f32 }.frames(8).is_err());
+        assert!(AudioFormat { channels: 9, ..stereo_f32 }.frames(72).is_err());
+
+        let header = AudioHeader { overlay_id: 3, format: mono_s16, timestamp_us: 1234, frames: 3 };
+        assert_eq!(AudioHeader::decode(&header.encode()), Some(header));
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_drops_the_oldest_and_late_chunks_are_skipped() {
+        let queue = AudioQueue::new(AudioConfig { queue_depth: 2, max_latency_ms: 50 });
+        let now = Instant::now();
+        assert_eq!(queue.push(chunk(1, now)), 0);
+        assert_eq!(queue.push(chunk(2, now)), 0);
+        assert_eq!(queue.push(chunk(3, now)), 1);
+        assert_eq!(queue.pop().await.0.header.overlay_id, 2);
+
+        let stale = now.checked_sub(Duration::from_secs(1)).unwrap_or(now);
+        queue.clear();
+        queue.push(chunk(4, stale));
+        queue.push(chunk(5, Instant::now()));
+        let (next, late) = queue.pop().await;
+        assert_eq!((next.header.overlay_id, late), (5, 1));
+        assert_eq!(queue.dropped_chunks(), 2);
+    }
+}
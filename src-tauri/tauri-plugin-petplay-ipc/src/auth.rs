@@ -0,0 +1,138 @@
+// --- Pipe authentication ---
+// Any local process can create the pipe names the app connects to, so before
+// anything else goes over a pipe (the Hello on the frame pipe, poses on the
+// transform pipe, input on the input pipe) both sides prove they know a
+// shared token, without ever sending it:
+//
+//   app -> backend   AuthChallenge: 32 random bytes (nonce A)
+//   backend -> app   AuthResponse:  HMAC-SHA256(token, "petplay-backend" || A), then 32 random bytes (nonce B)
+//   app -> backend   AuthProof:     HMAC-SHA256(token, "petplay-app" || B)
+//
+// The app drops a connection whose response is wrong or late, so an impostor
+// never sees a frame; the backend is expected to close on a wrong proof, so an
+// impostor can't pose as the app either. The token is `authToken` from
+// puppyweb.toml, else the PETPLAY_IPC_TOKEN environment variable, else a
+// random one generated for this session. A backend launched by the app (see
+// backend.rs) is given it in PETPLAY_IPC_TOKEN; one started by hand needs the
+// token configured on both sides.
+use crate::protocol::{self, MessageType};
+use sha2::{Digest, Sha256};
+use std::{fmt, io, sync::Arc, time::Duration};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+pub const TOKEN_ENV: &str = "PETPLAY_IPC_TOKEN";
+pub const AUTH_TIMEOUT: Duration = Duration::from_secs(1);
+const NONCE_SIZE: usize = 32;
+const MAC_SIZE: usize = 32;
+const BACKEND_LABEL: &[u8] = b"petplay-backend";
+const APP_LABEL: &[u8] = b"petplay-app";
+// Generated tokens are this many random bytes, hex encoded
+const GENERATED_TOKEN_BYTES: usize = 32;
+
+#[derive(Clone)]
+pub struct AuthToken(Arc<str>);
+
+impl AuthToken {
+    // The configured token, else the one in the environment, else a fresh random one
+    pub fn resolve(configured: Option<&str>) -> io::Result<Self> {
+        if let Some(token) = configured.filter(|token| !token.is_empty()) {
+            return Ok(Self(token.into()));
+        }
+        if let Some(token) = std::env::var(TOKEN_ENV).ok().filter(|token| !token.is_empty()) {
+            return Ok(Self(token.into()));
+        }
+        Self::generate()
+    }
+
+    pub fn generate() -> io::Result<Self> {
+        let bytes: [u8; GENERATED_TOKEN_BYTES] = random()?;
+        Ok(Self(bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>().into()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn mac(&self, label: &[u8], nonce: &[u8]) -> [u8; MAC_SIZE] {
+        hmac_sha256(self.0.as_bytes(), &[label, nonce])
+    }
+}
+
+// Never log the token itself
+impl fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuthToken(..)")
+    }
+}
+
+// App side: challenge the backend on a freshly opened pipe and answer its challenge
+pub async fn authenticate<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, token: &AuthToken) -> io::Result<()> {
+    let challenge: [u8; NONCE_SIZE] = random()?;
+    stream.write_all(&protocol::encode(MessageType::AuthChallenge, 0, &challenge)).await?;
+    let response = read(stream, MessageType::AuthResponse).await?;
+    if response.len() != MAC_SIZE + NONCE_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed AuthResponse"));
+    }
+    let (mac, backend_nonce) = response.split_at(MAC_SIZE);
+    if !constant_time_eq(mac, &token.mac(BACKEND_LABEL, &challenge)) {
+        return Err(unauthorized("backend failed authentication (is the same authToken configured on both sides?)"));
+    }
+    stream.write_all(&protocol::encode(MessageType::AuthProof, 0, &token.mac(APP_LABEL, backend_nonce))).await
+}
+
+// Backend side, for the mock backend: answer the app's challenge and check its proof
+pub async fn accept<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, token: &AuthToken) -> io::Result<()> {
+    let challenge = read(stream, MessageType::AuthChallenge).await?;
+    let nonce: [u8; NONCE_SIZE] = random()?;
+    let mut response = token.mac(BACKEND_LABEL, &challenge).to_vec();
+    response.extend_from_slice(&nonce);
+    stream.write_all(&protocol::encode(MessageType::AuthResponse, 0, &response)).await?;
+    let proof = read(stream, MessageType::AuthProof).await?;
+    if !constant_time_eq(&proof, &token.mac(APP_LABEL, &nonce)) {
+        return Err(unauthorized("app failed authentication"));
+    }
+    Ok(())
+}
+
+async fn read<R: AsyncRead + Unpin>(reader: &mut R, expected: MessageType) -> io::Result<Vec<u8>> {
+    match tokio::time::timeout(AUTH_TIMEOUT, protocol::read_message(reader)).await {
+        Ok(Ok(message)) if message.header.message_type == expected => Ok(message.payload),
+        Ok(Ok(message)) => Err(unauthorized(&format!("expected {:?}, got {:?}", expected, message.header.message_type))),
+        Ok(Err(e)) => Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        Err(_) => Err(unauthorized(&format!("no {:?} within {:?}", expected, AUTH_TIMEOUT))),
+    }
+}
+
+fn unauthorized(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message.to_string())
+}
+
+fn random<const N: usize>() -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes)
+}
+
+// HMAC (RFC 2104) over the concatenation of `parts`
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; MAC_SIZE] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..MAC_SIZE].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    for part in parts {
+        inner.update(part);
+    }
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
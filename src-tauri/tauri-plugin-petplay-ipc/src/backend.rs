@@ -0,0 +1,249 @@
+// --- Backend process lifecycle ---
+// Instead of requiring the overlay backend to be started by hand, the app can
+// launch the configured executable itself (launch_backend, or autoStart in
+// puppyweb.toml). A watchdog task owns the child process: when it exits with
+// a failure it's restarted after a backoff delay, and the frontend hears
+// about it through backend-started / backend-crashed / backend-stopped
+// events. A backend that ran for at least stableAfterMs before crashing starts
+// the backoff over. stop_backend (and app exit) kill the child and wait for it.
+// The child gets the pipe auth token in PETPLAY_IPC_TOKEN (see auth.rs).
+use crate::{
+    auth::{AuthToken, TOKEN_ENV},
+    backoff::{Backoff, ReconnectPolicy},
+    runtime::IpcRuntime,
+    tasks::TaskSlot,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tauri::{AppHandle, Emitter};
+use tracing::{error, info, warn};
+use tokio::{
+    process::{Child, Command},
+    sync::Notify,
+    time::sleep,
+};
+
+// How long stop waits for the killed backend to be reaped
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackendConfig {
+    // Backend executable; launch_backend fails while this is unset
+    pub executable: Option<String>,
+    pub args: Vec<String>,
+    // Defaults to the app's working directory
+    pub working_dir: Option<String>,
+    // Launch the backend when the app starts
+    pub auto_start: bool,
+    // Delays between restarts after a crash
+    pub restart: ReconnectPolicy,
+    // Uptime after which a crash no longer counts towards the restart backoff
+    pub stable_after_ms: u64,
+}
+
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self {
+            executable: None,
+            args: Vec::new(),
+            working_dir: None,
+            auto_start: false,
+            restart: ReconnectPolicy::default(),
+            stable_after_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendStartedPayload {
+    pid: Option<u32>,
+    executable: String,
+    // Restarts since the last launch_backend
+    restarts: u32,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendCrashedPayload {
+    // None if killed by a signal
+    exit_code: Option<i32>,
+    restarts: u32,
+    // None when the restart policy is exhausted and the watchdog gave up
+    restart_in_ms: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendStoppedPayload {
+    exit_code: Option<i32>,
+    // False if the backend exited cleanly on its own
+    requested: bool,
+}
+
+// Owns the watchdog task; managed as Tauri state
+pub struct BackendSupervisor {
+    rt: IpcRuntime,
+    app_handle: AppHandle,
+    watchdog: TaskSlot,
+    // Tells the current watchdog to kill its child; fresh for every launch
+    stop: Mutex<Arc<Notify>>,
+    // Called after every (re)start so the pipe loops can connect right away
+    on_started: Arc<dyn Fn() + Send + Sync>,
+    auth_token: AuthToken,
+}
+
+impl BackendSupervisor {
+    pub fn new(
+        rt: IpcRuntime,
+        app_handle: AppHandle,
+        auth_token: AuthToken,
+        on_started: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            rt,
+            app_handle,
+            auth_token,
+            watchdog: TaskSlot::default(),
+            stop: Mutex::new(Arc::new(Notify::new())),
+            on_started: Arc::new(on_started),
+        }
+    }
+
+    // Start the backend and hand it to a new watchdog, replacing (and killing) any previous one.
+    // Fails only if the first start fails; restarts are reported through events.
+    pub fn launch(&self, config: BackendConfig) -> io::Result<Option<u32>> {
+        self.watchdog.abort();
+        // Child processes have to be spawned inside the runtime that reaps them
+        let _runtime = self.rt.enter();
+        let child = spawn_child(&config, &self.auth_token)?;
+        let pid = child.id();
+        let stop = Arc::new(Notify::new());
+        *self.stop.lock() = Arc::clone(&stop);
+        let watchdog = Watchdog {
+            app_handle: self.app_handle.clone(),
+            config,
+            auth_token: self.auth_token.clone(),
+            stop,
+            on_started: Arc::clone(&self.on_started),
+        };
+        watchdog.started(pid, 0);
+        self.watchdog.replace(|| self.rt.spawn(watchdog.run(child)));
+        Ok(pid)
+    }
+
+    // Kill the backend and wait until it's gone; returns false if none was running
+    pub async fn stop(&self) -> bool {
+        if !self.watchdog.is_running() {
+            return false;
+        }
+        let stop = Arc::clone(&self.stop.lock());
+        stop.notify_one();
+        let stopped = async {
+            while self.watchdog.is_running() {
+                sleep(STOP_POLL_INTERVAL).await;
+            }
+        };
+        if tokio::time::timeout(STOP_TIMEOUT, stopped).await.is_err() {
+            warn!("[Rust Backend] Backend didn't stop in time.");
+        }
+        // Dropping the child (kill_on_drop) is the fallback if the kill didn't go through
+        self.watchdog.abort();
+        info!("[Rust Backend] Stopped backend.");
+        emit(&self.app_handle, "backend-stopped", BackendStoppedPayload { exit_code: None, requested: true });
+        true
+    }
+}
+
+struct Watchdog {
+    app_handle: AppHandle,
+    config: BackendConfig,
+    auth_token: AuthToken,
+    stop: Arc<Notify>,
+    on_started: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl Watchdog {
+    fn started(&self, pid: Option<u32>, restarts: u32) {
+        let executable = self.config.executable.clone().unwrap_or_default();
+        info!("[Rust Backend] Started {} (pid {}).", executable, pid.map_or("?".to_string(), |pid| pid.to_string()));
+        emit(&self.app_handle, "backend-started", BackendStartedPayload { pid, executable, restarts });
+        (self.on_started)();
+    }
+
+    // Wait for the child to exit and restart it after failures until the policy gives up
+    async fn run(self, mut child: Child) {
+        let mut backoff = Backoff::new(self.config.restart);
+        let mut restarts = 0;
+        loop {
+            let started = Instant::now();
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = self.stop.notified() => {
+                    let _ = child.kill().await;
+                    return;
+                }
+            };
+            let exit_code = status.as_ref().ok().and_then(|status| status.code());
+            match status {
+                Ok(status) if status.success() => {
+                    info!("[Rust Backend] Backend exited.");
+                    emit(&self.app_handle, "backend-stopped", BackendStoppedPayload { exit_code, requested: false });
+                    return;
+                }
+                Ok(status) => warn!("[Rust Backend] Backend crashed ({}).", status),
+                Err(e) => warn!("[Rust Backend] Lost track of the backend process: {}.", e),
+            }
+            if started.elapsed() >= Duration::from_millis(self.config.stable_after_ms) {
+                backoff = Backoff::new(self.config.restart);
+            }
+
+            // Keep trying until a start succeeds or the policy runs out
+            child = loop {
+                let delay = backoff.next_delay();
+                let payload = BackendCrashedPayload { exit_code, restarts, restart_in_ms: delay.map(|delay| delay.as_millis() as u64) };
+                emit(&self.app_handle, "backend-crashed", payload);
+                let Some(delay) = delay else {
+                    error!("[Rust Backend] Giving up after {} restarts.", restarts);
+                    return;
+                };
+                tokio::select! {
+                    _ = sleep(delay) => {}
+                    _ = self.stop.notified() => return,
+                }
+                restarts += 1;
+                match spawn_child(&self.config, &self.auth_token) {
+                    Ok(child) => break child,
+                    Err(e) => warn!("[Rust Backend] Failed to restart backend: {}.", e),
+                }
+            };
+            self.started(child.id(), restarts);
+        }
+    }
+}
+
+fn spawn_child(config: &BackendConfig, auth_token: &AuthToken) -> io::Result<Child> {
+    let Some(executable) = config.executable.as_deref().filter(|executable| !executable.is_empty()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no backend executable configured"));
+    };
+    let mut command = Command::new(executable);
+    command.args(&config.args).env(TOKEN_ENV, auth_token.as_str()).stdin(Stdio::null()).kill_on_drop(true);
+    if let Some(dir) = &config.working_dir {
+        command.current_dir(dir);
+    }
+    command.spawn()
+}
+
+fn emit<S: Serialize + Clone>(app_handle: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app_handle.emit(event, payload) {
+        error!("[Rust Backend] Error emitting {} event: {}", event, e);
+    }
+}
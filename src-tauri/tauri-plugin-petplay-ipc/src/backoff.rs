@@ -0,0 +1,77 @@
+// --- Reconnect backoff ---
+// Exponential backoff with jitter shared by every connection loop.
+// Jitter keeps the frame and transform loops (and several app instances)
+// from hammering a restarting backend in lockstep.
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReconnectPolicy {
+    // Delay before the first retry
+    pub initial_delay_ms: u64,
+    // Upper bound for the delay between retries
+    pub max_delay_ms: u64,
+    // Growth factor applied after every failed attempt
+    pub multiplier: f64,
+    // Fraction (0..=1) of each delay that is randomized away
+    pub jitter: f64,
+    // Give up after this many failed attempts (None = retry forever)
+    pub max_retries: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 250,
+            max_delay_ms: 30_000,
+            multiplier: 2.0,
+            jitter: 0.3,
+            max_retries: Some(20),
+        }
+    }
+}
+
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self { policy, attempt: 0 }
+    }
+
+    // Number of failed attempts so far
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    // Register a failed attempt and return how long to wait before the next one,
+    // or None once max_retries is exhausted
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        self.attempt = self.attempt.saturating_add(1);
+        if self.policy.max_retries.is_some_and(|max| self.attempt > max) {
+            return None;
+        }
+
+        let exponent = (self.attempt - 1).min(63) as i32;
+        let base = self.policy.initial_delay_ms as f64 * self.policy.multiplier.max(1.0).powi(exponent);
+        let capped = base.min(self.policy.max_delay_ms as f64);
+        let jitter = self.policy.jitter.clamp(0.0, 1.0);
+        let delay = capped * (1.0 - jitter * random_unit());
+        Some(Duration::from_millis(delay as u64))
+    }
+}
+
+// Uniform-ish value in [0, 1) without pulling in a RNG crate:
+// every RandomState is seeded with fresh randomness by std
+fn random_unit() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
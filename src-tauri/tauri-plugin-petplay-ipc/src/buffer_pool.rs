@@ -0,0 +1,179 @@
+// --- Buffer pool ---
+// A frame goes through several buffers on its way to the pipe: the copy out
+// of the IPC body, the pixel format conversion, the compressed pixels and
+// their tiles. At 90 FPS and 4K that used to be hundreds of megabytes of
+// fresh allocations a second. Those stages take their buffers from here
+// instead, and a buffer dropped after the frame was written (or replaced
+// under the FPS limit, or dropped by backpressure) comes back to be reused.
+//
+// Buffers come in power-of-two size classes, so a frame of a slightly
+// different size still finds one. A few buffers per class are kept, up to a
+// total; anything beyond that, or bigger than the largest class, is simply
+// freed. Hit/miss counts are part of get_pipe_metrics.
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+const MIN_CLASS_SHIFT: u32 = 12; // 4 KiB
+const MAX_CLASS_SHIFT: u32 = 27; // 128 MiB, a 4096x4096 RGBA frame with room to spare
+const CLASSES: usize = (MAX_CLASS_SHIFT - MIN_CLASS_SHIFT + 1) as usize;
+// Enough for the frames queued, held back and being written at once
+const MAX_BUFFERS_PER_CLASS: usize = 4;
+const MAX_RETAINED_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferPoolStats {
+    // Buffers handed out from the pool
+    pub hits: u64,
+    // Buffers that had to be allocated
+    pub misses: u64,
+    // Buffers freed on return because the pool was full or they were too big
+    pub discarded: u64,
+    // Buffers and bytes waiting to be reused
+    pub retained_buffers: usize,
+    pub retained_bytes: usize,
+}
+
+pub struct BufferPool {
+    classes: [Mutex<Vec<Vec<u8>>>; CLASSES],
+    retained_bytes: AtomicUsize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self {
+            classes: std::array::from_fn(|_| Mutex::new(Vec::new())),
+            retained_bytes: AtomicUsize::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        }
+    }
+}
+
+// Index of the smallest class holding `capacity` bytes, if any does
+fn class_of(capacity: usize) -> Option<usize> {
+    let shift = capacity.max(1).next_power_of_two().trailing_zeros().max(MIN_CLASS_SHIFT);
+    (shift <= MAX_CLASS_SHIFT).then(|| (shift - MIN_CLASS_SHIFT) as usize)
+}
+
+impl BufferPool {
+    // An empty buffer with room for at least `capacity` bytes
+    pub fn take(self: &Arc<Self>, capacity: usize) -> PooledBuffer {
+        let buffer = match class_of(capacity) {
+            Some(class) => match self.classes[class].lock().pop() {
+                Some(buffer) => {
+                    self.retained_bytes.fetch_sub(buffer.capacity(), Ordering::Relaxed);
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    buffer
+                }
+                None => {
+                    self.misses.fetch_add(1, Ordering::Relaxed);
+                    Vec::with_capacity(1 << (class as u32 + MIN_CLASS_SHIFT))
+                }
+            },
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(capacity)
+            }
+        };
+        PooledBuffer { buffer, pool: Arc::clone(self) }
+    }
+
+    fn give_back(&self, mut buffer: Vec<u8>) {
+        let capacity = buffer.capacity();
+        // Only buffers the pool made have an exact class size
+        let class = class_of(capacity).filter(|_| capacity.is_power_of_two() && capacity >= 1 << MIN_CLASS_SHIFT);
+        if let Some(class) = class {
+            let mut buffers = self.classes[class].lock();
+            if buffers.len() < MAX_BUFFERS_PER_CLASS && self.retained_bytes.load(Ordering::Relaxed) + capacity <= MAX_RETAINED_BYTES {
+                buffer.clear();
+                self.retained_bytes.fetch_add(capacity, Ordering::Relaxed);
+                buffers.push(buffer);
+                return;
+            }
+        }
+        self.discarded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+            retained_buffers: self.classes.iter().map(|buffers| buffers.lock().len()).sum(),
+            retained_bytes: self.retained_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// A buffer that goes back to its pool when dropped
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: Arc<BufferPool>,
+}
+
+impl PooledBuffer {
+    // Share the buffer as Bytes; it returns to the pool once the last clone is dropped
+    pub fn into_bytes(self) -> Bytes {
+        Bytes::from_owner(self)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.give_back(std::mem::take(&mut self.buffer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_returned_buffer_is_reused_for_a_similar_size() {
+        let pool = Arc::new(BufferPool::default());
+
+        let mut buffer = pool.take(3_000_000);
+        buffer.extend_from_slice(&[1; 3_000_000]);
+        let address = buffer.as_ptr();
+        drop(buffer.into_bytes());
+
+        let buffer = pool.take(2_500_000);
+        assert_eq!(buffer.as_ptr(), address);
+        assert!(buffer.is_empty());
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses, stats.retained_buffers), (1, 1, 0));
+    }
+}
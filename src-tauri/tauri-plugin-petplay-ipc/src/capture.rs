@@ -0,0 +1,708 @@
+// --- Desktop capture ---
+// Streams a monitor or a single window to an overlay without the webview
+// drawing it: start_desktop_capture duplicates the monitor with DXGI desktop
+// duplication on a thread of its own and hands every new image to the frame
+// pipeline the way send_frame_data does (target FPS, sinks, recorder, writer
+// task) as BGRA8 frames for the chosen overlay. stop_desktop_capture ends it,
+// and so does starting another capture for the same overlay or destroying
+// the overlay.
+//
+// A window is captured by cropping its monitor's image to the window's frame
+// as DWM draws it (without the invisible resize borders), read again for
+// every image so the stream follows moves and resizes. Whatever covers the
+// window is captured with it, and nothing is sent while it's minimized or off
+// the monitor it was on when capture started.
+//
+// Duplicated images don't contain the mouse pointer; with cursor (the
+// default) it's drawn in on the CPU from the shape DXGI reports. maxFps
+// (default 30) caps how often the monitor is read, on top of the stream's
+// target FPS, and an image only goes out when the desktop changed or the
+// pointer moved. Duplication is set up again after a mode change or a
+// switch to the secure desktop (UAC, the lock screen), which pause it;
+// "desktop-capture-stopped" says when capture ended on its own, e.g. the
+// window was closed or the monitor unplugged. Rotated monitors come out
+// unrotated.
+//
+//   startDesktopCapture({ target: { window: hwnd }, options: { maxFps: 60, overlayId: 2 } })
+//
+// Windows only; elsewhere start_desktop_capture fails with Unsupported.
+use serde::{Deserialize, Serialize};
+use std::io;
+
+pub const MAX_CAPTURE_FPS: u32 = 240;
+
+// What start_desktop_capture reads; { monitor: 0 } or { window: hwnd }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaptureTarget {
+    // Index of the monitor, counting the outputs of each graphics adapter in turn (0 is usually the primary)
+    Monitor(u32),
+    // HWND of a top-level window
+    Window(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptureOptions {
+    pub max_fps: u32,
+    // Draw the mouse pointer into the image
+    pub cursor: bool,
+    // Overlay the frames are for
+    pub overlay_id: u32,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self { max_fps: 30, cursor: true, overlay_id: 0 }
+    }
+}
+
+impl CaptureOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_CAPTURE_FPS).contains(&self.max_fps) {
+            return Err(format!("maxFps must be between 1 and {}", MAX_CAPTURE_FPS));
+        }
+        Ok(())
+    }
+}
+
+// Result of start_desktop_capture
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopCaptureInfo {
+    pub target: CaptureTarget,
+    pub overlay_id: u32,
+    // Device name of the monitor being duplicated, e.g. \\.\DISPLAY1
+    pub monitor: String,
+    // Size of the monitor's image; a window's frames are cropped from it
+    pub width: u32,
+    pub height: u32,
+}
+
+// A rectangle in pixels, right and bottom exclusive
+#[cfg_attr(not(windows), allow(dead_code))] // Only the Windows capture crops and draws pointers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl Rect {
+    pub fn width(&self) -> u32 {
+        (self.right - self.left).max(0) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.bottom - self.top).max(0) as u32
+    }
+
+    // The part inside `other`, or None if they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let rect = Rect {
+            left: self.left.max(other.left),
+            top: self.top.max(other.top),
+            right: self.right.min(other.right),
+            bottom: self.bottom.min(other.bottom),
+        };
+        (rect.width() > 0 && rect.height() > 0).then_some(rect)
+    }
+
+    pub fn offset(&self, dx: i32, dy: i32) -> Rect {
+        Rect { left: self.left + dx, top: self.top + dy, right: self.right + dx, bottom: self.bottom + dy }
+    }
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorKind {
+    // BGRA blended by its alpha
+    Color,
+    // 1 bit per pixel: an AND mask on top of an XOR mask, each `height` rows
+    Monochrome,
+    // BGRA whose alpha says whether to replace the pixel (0) or XOR it with the color (0xFF)
+    MaskedColor,
+}
+
+// A pointer shape as DXGI reports it
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CursorShape {
+    pub kind: CursorKind,
+    pub width: u32,
+    // Height of the pointer; a monochrome shape has twice as many rows
+    pub height: u32,
+    pub pitch: usize,
+    pub pixels: Vec<u8>,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl CursorShape {
+    fn rows(&self) -> usize {
+        match self.kind {
+            CursorKind::Monochrome => self.height as usize * 2,
+            CursorKind::Color | CursorKind::MaskedColor => self.height as usize,
+        }
+    }
+
+    fn row_bytes(&self) -> usize {
+        match self.kind {
+            CursorKind::Monochrome => (self.width as usize).div_ceil(8),
+            CursorKind::Color | CursorKind::MaskedColor => self.width as usize * 4,
+        }
+    }
+}
+
+// Draw `shape` with its top-left corner at (x, y) into a BGRA image, clipped to the image.
+// A shape whose pixels don't cover its size is left out.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn draw_cursor(image: &mut [u8], width: u32, height: u32, stride: usize, shape: &CursorShape, x: i32, y: i32) {
+    if shape.pitch < shape.row_bytes() || shape.pixels.len() < shape.pitch * shape.rows() || image.len() < stride * height as usize {
+        return;
+    }
+    for row in 0..shape.height as usize {
+        let Ok(image_y) = usize::try_from(y + row as i32) else { continue };
+        if image_y >= height as usize {
+            break;
+        }
+        let line = &shape.pixels[row * shape.pitch..];
+        for column in 0..shape.width as usize {
+            let Ok(image_x) = usize::try_from(x + column as i32) else { continue };
+            if image_x >= width as usize {
+                break;
+            }
+            let pixel = &mut image[image_y * stride + image_x * 4..][..3];
+            match shape.kind {
+                CursorKind::Color => {
+                    let color = &line[column * 4..][..4];
+                    let alpha = color[3] as u32;
+                    for (channel, source) in pixel.iter_mut().zip(color) {
+                        *channel = ((*source as u32 * alpha + *channel as u32 * (255 - alpha) + 127) / 255) as u8;
+                    }
+                }
+                CursorKind::MaskedColor => {
+                    let color = &line[column * 4..][..4];
+                    if color[3] == 0 {
+                        pixel.copy_from_slice(&color[..3]);
+                    } else {
+                        pixel.iter_mut().zip(color).for_each(|(channel, source)| *channel ^= source);
+                    }
+                }
+                CursorKind::Monochrome => {
+                    let bit = 0x80 >> (column % 8);
+                    let and = line[column / 8] & bit != 0;
+                    let xor = shape.pixels[(row + shape.height as usize) * shape.pitch + column / 8] & bit != 0;
+                    for channel in pixel.iter_mut() {
+                        *channel = if and { *channel } else { 0 } ^ if xor { 0xFF } else { 0 };
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A running capture; it stops when dropped
+pub struct DesktopCapture {
+    info: DesktopCaptureInfo,
+    _capture: platform::Capture,
+}
+
+impl DesktopCapture {
+    // Start duplicating `target` and hand every image (width, height, packed BGRA8 rows) to `on_frame` on the
+    // capture thread; `on_stopped` is called there if capture fails after it started
+    pub fn start(
+        target: CaptureTarget,
+        options: CaptureOptions,
+        on_frame: impl FnMut(u32, u32, &[u8]) + Send + 'static,
+        on_stopped: impl FnOnce(io::Error) + Send + 'static,
+    ) -> io::Result<Self> {
+        let (info, capture) = platform::Capture::start(target, options, on_frame, on_stopped)?;
+        Ok(Self { info, _capture: capture })
+    }
+
+    pub fn info(&self) -> &DesktopCaptureInfo {
+        &self.info
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{draw_cursor, CaptureOptions, CaptureTarget, CursorKind, CursorShape, DesktopCaptureInfo, Rect};
+    use std::{
+        ffi::c_void,
+        io, mem,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    };
+    use windows::{
+        core::Interface,
+        Win32::{
+            Foundation::{HMODULE, HWND, RECT},
+            Graphics::{
+                Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+                Direct3D11::{
+                    D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_CPU_ACCESS_READ, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                    D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+                },
+                Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+                Dxgi::{
+                    Common::DXGI_SAMPLE_DESC, CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory1, IDXGIOutput1, IDXGIOutputDuplication,
+                    DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_NOT_FOUND, DXGI_ERROR_WAIT_TIMEOUT, DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+                    DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME,
+                    DXGI_OUTPUT_DESC,
+                },
+                Gdi::{MonitorFromWindow, MONITOR_DEFAULTTONULL},
+            },
+            UI::WindowsAndMessaging::{IsIconic, IsWindow},
+        },
+    };
+    use tracing::info;
+
+    // How long AcquireNextFrame waits for a change, and so how often the thread checks for stop
+    const ACQUIRE_TIMEOUT_MS: u32 = 100;
+    // Between attempts to set duplication up again after losing it
+    const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+    fn not_found(message: String) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, message)
+    }
+
+    fn to_rect(rect: RECT) -> Rect {
+        Rect { left: rect.left, top: rect.top, right: rect.right, bottom: rect.bottom }
+    }
+
+    fn hwnd(handle: u64) -> HWND {
+        HWND(handle as usize as *mut c_void)
+    }
+
+    // Every output of every adapter, in the order monitor indexes count them
+    fn outputs() -> io::Result<Vec<(IDXGIAdapter1, IDXGIOutput1, DXGI_OUTPUT_DESC)>> {
+        let mut found = Vec::new();
+        // SAFETY: plain DXGI calls on objects owned by their wrappers; enumeration ends with DXGI_ERROR_NOT_FOUND
+        unsafe {
+            let factory: IDXGIFactory1 = CreateDXGIFactory1().map_err(io::Error::other)?;
+            for adapter_index in 0.. {
+                let adapter = match factory.EnumAdapters1(adapter_index) {
+                    Ok(adapter) => adapter,
+                    Err(e) if e.code() == DXGI_ERROR_NOT_FOUND => break,
+                    Err(e) => return Err(io::Error::other(e)),
+                };
+                for output_index in 0.. {
+                    let output = match adapter.EnumOutputs(output_index) {
+                        Ok(output) => output,
+                        Err(e) if e.code() == DXGI_ERROR_NOT_FOUND => break,
+                        Err(e) => return Err(io::Error::other(e)),
+                    };
+                    let desc = output.GetDesc().map_err(io::Error::other)?;
+                    found.push((adapter.clone(), output.cast().map_err(io::Error::other)?, desc));
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    fn find_output(target: CaptureTarget) -> io::Result<(IDXGIAdapter1, IDXGIOutput1, DXGI_OUTPUT_DESC)> {
+        match target {
+            CaptureTarget::Monitor(index) => {
+                outputs()?.into_iter().nth(index as usize).ok_or_else(|| not_found(format!("There is no monitor {}", index)))
+            }
+            CaptureTarget::Window(handle) => {
+                // SAFETY: any value is safe to pass as a window handle; an unknown one is reported as such
+                let monitor = unsafe {
+                    if !IsWindow(Some(hwnd(handle))).as_bool() {
+                        return Err(not_found(format!("There is no window {:#x}", handle)));
+                    }
+                    MonitorFromWindow(hwnd(handle), MONITOR_DEFAULTTONULL)
+                };
+                outputs()?
+                    .into_iter()
+                    .find(|(_, _, desc)| !monitor.is_invalid() && desc.Monitor == monitor)
+                    .ok_or_else(|| not_found(format!("Window {:#x} isn't on a monitor", handle)))
+            }
+        }
+    }
+
+    // The part of the monitor's image to send, or None if there's nothing to send right now
+    fn crop(target: CaptureTarget, desktop: Rect) -> io::Result<Option<Rect>> {
+        let CaptureTarget::Window(handle) = target else {
+            return Ok(Some(desktop.offset(-desktop.left, -desktop.top)));
+        };
+        let mut bounds = RECT::default();
+        // SAFETY: `bounds` is a RECT, the size DWMWA_EXTENDED_FRAME_BOUNDS writes
+        unsafe {
+            if !IsWindow(Some(hwnd(handle))).as_bool() {
+                return Err(not_found(format!("Window {:#x} was closed", handle)));
+            }
+            if IsIconic(hwnd(handle)).as_bool() {
+                return Ok(None);
+            }
+            DwmGetWindowAttribute(hwnd(handle), DWMWA_EXTENDED_FRAME_BOUNDS, (&mut bounds as *mut RECT).cast(), mem::size_of::<RECT>() as u32)
+                .map_err(io::Error::other)?;
+        }
+        Ok(to_rect(bounds).intersect(&desktop).map(|visible| visible.offset(-desktop.left, -desktop.top)))
+    }
+
+    fn device_name(desc: &DXGI_OUTPUT_DESC) -> String {
+        let len = desc.DeviceName.iter().position(|&c| c == 0).unwrap_or(desc.DeviceName.len());
+        String::from_utf16_lossy(&desc.DeviceName[..len])
+    }
+
+    enum Next {
+        Unchanged,
+        Changed,
+        // Duplication has to be set up again
+        Lost,
+    }
+
+    // Duplication of one output, with a CPU-readable copy of its latest image and the pointer
+    struct Duplication {
+        device: ID3D11Device,
+        context: ID3D11DeviceContext,
+        duplication: IDXGIOutputDuplication,
+        // Where the monitor is on the desktop
+        desktop: Rect,
+        staging: Option<(ID3D11Texture2D, Rect)>,
+        // Top-left corner of the pointer on the monitor, None while it's hidden or on another monitor
+        pointer: Option<(i32, i32)>,
+        shape: Option<CursorShape>,
+        shape_buffer: Vec<u8>,
+    }
+
+    impl Duplication {
+        fn open(target: CaptureTarget) -> io::Result<(Self, DXGI_OUTPUT_DESC)> {
+            let (adapter, output, desc) = find_output(target)?;
+            let (mut device, mut context) = (None, None);
+            // SAFETY: out pointers are valid for the calls; the device is made on the output's own adapter, which
+            // DuplicateOutput requires. It fails e.g. while the secure desktop is shown.
+            let duplication = unsafe {
+                D3D11CreateDevice(
+                    &adapter,
+                    D3D_DRIVER_TYPE_UNKNOWN,
+                    HMODULE::default(),
+                    D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                    None,
+                    D3D11_SDK_VERSION,
+                    Some(&mut device),
+                    None,
+                    Some(&mut context),
+                )
+                .map_err(io::Error::other)?;
+                let (Some(device), Some(context)) = (device, context) else {
+                    return Err(io::Error::other("D3D11CreateDevice returned no device"));
+                };
+                let duplication = output.DuplicateOutput(&device).map_err(io::Error::other)?;
+                Self {
+                    device,
+                    context,
+                    duplication,
+                    desktop: to_rect(desc.DesktopCoordinates),
+                    staging: None,
+                    pointer: None,
+                    shape: None,
+                    shape_buffer: Vec::new(),
+                }
+            };
+            Ok((duplication, desc))
+        }
+
+        // Wait up to ACQUIRE_TIMEOUT_MS for the desktop or the pointer to change, and take the change in
+        fn next(&mut self, cursor: bool) -> io::Result<Next> {
+            let mut info = DXGI_OUTDUPL_FRAME_INFO::default();
+            let mut resource = None;
+            // SAFETY: out pointers are valid for the call; an acquired frame is released below
+            match unsafe { self.duplication.AcquireNextFrame(ACQUIRE_TIMEOUT_MS, &mut info, &mut resource) } {
+                Ok(()) => {}
+                Err(e) if e.code() == DXGI_ERROR_WAIT_TIMEOUT => return Ok(Next::Unchanged),
+                Err(e) if e.code() == DXGI_ERROR_ACCESS_LOST => return Ok(Next::Lost),
+                Err(e) => return Err(io::Error::other(e)),
+            }
+            let mut changed = false;
+            let mut take = || {
+                if cursor && info.LastMouseUpdateTime != 0 {
+                    let position = info.PointerPosition;
+                    self.pointer = position.Visible.as_bool().then_some((position.Position.x, position.Position.y));
+                    changed = true;
+                }
+                if cursor && info.PointerShapeBufferSize > 0 {
+                    self.read_shape(info.PointerShapeBufferSize)?;
+                    changed = true;
+                }
+                if let (true, Some(resource)) = (info.LastPresentTime != 0, &resource) {
+                    self.copy(&resource.cast().map_err(io::Error::other)?)?;
+                    changed = true;
+                }
+                io::Result::Ok(())
+            };
+            let taken = take();
+            drop(resource);
+            // SAFETY: the frame acquired above, whose surface was dropped
+            let released = unsafe { self.duplication.ReleaseFrame() };
+            match (taken, released) {
+                (Err(e), _) => Err(e),
+                (_, Err(e)) if e.code() == DXGI_ERROR_ACCESS_LOST => Ok(Next::Lost),
+                (_, Err(e)) => Err(io::Error::other(e)),
+                _ if changed => Ok(Next::Changed),
+                _ => Ok(Next::Unchanged),
+            }
+        }
+
+        fn read_shape(&mut self, size: u32) -> io::Result<()> {
+            self.shape_buffer.resize(size as usize, 0);
+            let (mut required, mut info) = (0, DXGI_OUTDUPL_POINTER_SHAPE_INFO::default());
+            // SAFETY: the buffer holds `size` bytes, the size the frame info asked for
+            unsafe { self.duplication.GetFramePointerShape(size, self.shape_buffer.as_mut_ptr().cast(), &mut required, &mut info) }
+                .map_err(io::Error::other)?;
+            let (kind, height) = match info.Type as i32 {
+                kind if kind == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 => (CursorKind::Color, info.Height),
+                kind if kind == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR.0 => (CursorKind::MaskedColor, info.Height),
+                // The AND and XOR masks are counted as one bitmap of twice the height
+                kind if kind == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME.0 => (CursorKind::Monochrome, info.Height / 2),
+                _ => {
+                    self.shape = None;
+                    return Ok(());
+                }
+            };
+            let pixels = self.shape_buffer[..(required as usize).min(self.shape_buffer.len())].to_vec();
+            self.shape = Some(CursorShape { kind, width: info.Width, height, pitch: info.Pitch as usize, pixels });
+            Ok(())
+        }
+
+        // Copy the desktop image into the staging texture, which is made again when the size changes
+        fn copy(&mut self, texture: &ID3D11Texture2D) -> io::Result<()> {
+            let mut desc = D3D11_TEXTURE2D_DESC::default();
+            // SAFETY: plain D3D11 calls; `desc` describes a CPU-readable copy of the acquired texture
+            unsafe {
+                texture.GetDesc(&mut desc);
+                let size = Rect { left: 0, top: 0, right: desc.Width as i32, bottom: desc.Height as i32 };
+                if self.staging.as_ref().is_none_or(|(_, staged)| *staged != size) {
+                    let staging_desc = D3D11_TEXTURE2D_DESC {
+                        MipLevels: 1,
+                        ArraySize: 1,
+                        SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+                        Usage: D3D11_USAGE_STAGING,
+                        BindFlags: 0,
+                        CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+                        MiscFlags: 0,
+                        ..desc
+                    };
+                    let mut staging = None;
+                    self.device.CreateTexture2D(&staging_desc, None, Some(&mut staging)).map_err(io::Error::other)?;
+                    let staging = staging.ok_or_else(|| io::Error::other("CreateTexture2D returned no texture"))?;
+                    self.staging = Some((staging, size));
+                }
+                if let Some((staging, _)) = &self.staging {
+                    self.context.CopyResource(staging, texture);
+                }
+            }
+            Ok(())
+        }
+
+        // The `crop` of the latest image as packed, opaque BGRA8 rows with the pointer drawn in; false if
+        // there's no image yet or the crop is outside of it
+        fn read(&self, crop: Rect, image: &mut Vec<u8>) -> io::Result<bool> {
+            let Some((staging, size)) = &self.staging else {
+                return Ok(false);
+            };
+            let Some(crop) = crop.intersect(size) else {
+                return Ok(false);
+            };
+            let row_bytes = crop.width() as usize * 4;
+            image.clear();
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            // SAFETY: the mapped staging texture has size.height() rows of RowPitch bytes of BGRA8 pixels, and
+            // the crop lies within it; it's unmapped before returning
+            unsafe {
+                self.context.Map(staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped)).map_err(io::Error::other)?;
+                let data = mapped.pData as *const u8;
+                for y in crop.top..crop.bottom {
+                    let row = data.add(y as usize * mapped.RowPitch as usize + crop.left as usize * 4);
+                    image.extend_from_slice(std::slice::from_raw_parts(row, row_bytes));
+                }
+                self.context.Unmap(staging, 0);
+            }
+            // The desktop image's alpha isn't defined
+            image.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 0xFF);
+            if let (Some((x, y)), Some(shape)) = (self.pointer, &self.shape) {
+                draw_cursor(image, crop.width(), crop.height(), row_bytes, shape, x - crop.left, y - crop.top);
+            }
+            Ok(true)
+        }
+    }
+
+    // Set duplication up again once the desktop allows it; None if told to stop first
+    fn reopen(target: CaptureTarget, stop: &AtomicBool) -> io::Result<Option<Duplication>> {
+        info!("[Rust Desktop Capture] Duplication was lost; setting it up again.");
+        while !stop.load(Ordering::Acquire) {
+            match Duplication::open(target) {
+                Ok((duplication, _)) => return Ok(Some(duplication)),
+                // The monitor or the window is gone for good
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(e),
+                Err(_) => thread::sleep(RETRY_INTERVAL),
+            }
+        }
+        Ok(None)
+    }
+
+    fn run(
+        mut duplication: Duplication,
+        target: CaptureTarget,
+        options: CaptureOptions,
+        stop: &AtomicBool,
+        on_frame: &mut impl FnMut(u32, u32, &[u8]),
+    ) -> io::Result<()> {
+        let period = Duration::from_secs_f64(1.0 / options.max_fps as f64);
+        let mut due = Instant::now();
+        let mut image = Vec::new();
+        while !stop.load(Ordering::Acquire) {
+            // Changes made in the meantime pile up in the duplication and come out as one
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+            match duplication.next(options.cursor)? {
+                Next::Unchanged => continue,
+                Next::Changed => {}
+                Next::Lost => {
+                    // The old duplication has to be released before there can be a new one
+                    drop(duplication);
+                    match reopen(target, stop)? {
+                        Some(reopened) => duplication = reopened,
+                        None => return Ok(()),
+                    }
+                    continue;
+                }
+            }
+            let Some(crop) = crop(target, duplication.desktop)? else {
+                continue;
+            };
+            if duplication.read(crop, &mut image)? {
+                on_frame(crop.width(), crop.height(), &image);
+                due = (due + period).max(Instant::now());
+            }
+        }
+        Ok(())
+    }
+
+    pub struct Capture {
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Capture {
+        pub fn start(
+            target: CaptureTarget,
+            options: CaptureOptions,
+            mut on_frame: impl FnMut(u32, u32, &[u8]) + Send + 'static,
+            on_stopped: impl FnOnce(io::Error) + Send + 'static,
+        ) -> io::Result<(DesktopCaptureInfo, Self)> {
+            let stop = Arc::new(AtomicBool::new(false));
+            let (opened, result) = mpsc::sync_channel(1);
+            let stopping = Arc::clone(&stop);
+            let thread = thread::Builder::new().name("puppyweb-desktop-capture".to_string()).spawn(move || {
+                let duplication = match Duplication::open(target) {
+                    Ok((duplication, desc)) => {
+                        let _ = opened.send(Ok(DesktopCaptureInfo {
+                            target,
+                            overlay_id: options.overlay_id,
+                            monitor: device_name(&desc),
+                            width: duplication.desktop.width(),
+                            height: duplication.desktop.height(),
+                        }));
+                        duplication
+                    }
+                    Err(e) => {
+                        let _ = opened.send(Err(e));
+                        return;
+                    }
+                };
+                if let Err(e) = run(duplication, target, options, &stopping, &mut on_frame) {
+                    on_stopped(e);
+                }
+            })?;
+            let capture = Self { stop, thread: Some(thread) };
+            let info = result.recv().map_err(|_| io::Error::other("desktop capture thread ended before duplicating the monitor"))??;
+            Ok((info, capture))
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Release);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::{CaptureOptions, CaptureTarget, DesktopCaptureInfo};
+    use std::io;
+
+    // Can't be created, so there's never a capture to stop
+    pub enum Capture {}
+
+    impl Capture {
+        pub fn start(
+            _target: CaptureTarget,
+            _options: CaptureOptions,
+            _on_frame: impl FnMut(u32, u32, &[u8]) + Send + 'static,
+            _on_stopped: impl FnOnce(io::Error) + Send + 'static,
+        ) -> io::Result<(DesktopCaptureInfo, Self)> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "Desktop capture is only available on Windows"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_are_cropped_to_the_monitor() {
+        let monitor = Rect { left: 1920, top: 0, right: 3840, bottom: 1080 };
+        let window = Rect { left: 1800, top: 100, right: 2400, bottom: 500 };
+        let visible = window.intersect(&monitor).unwrap();
+        assert_eq!(visible.offset(-monitor.left, -monitor.top), Rect { left: 0, top: 100, right: 480, bottom: 500 });
+        assert_eq!(Rect { left: 0, top: 0, right: 1920, bottom: 1080 }.intersect(&monitor), None);
+        assert!(CaptureOptions { max_fps: 0, ..CaptureOptions::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn every_kind_of_pointer_is_drawn_and_clipped() {
+        // 2x1 image of mid grey
+        let grey = || vec![0x80, 0x80, 0x80, 0xFF, 0x80, 0x80, 0x80, 0xFF];
+
+        let mut image = grey();
+        let color = CursorShape { kind: CursorKind::Color, width: 2, height: 1, pitch: 8, pixels: vec![0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0] };
+        draw_cursor(&mut image, 2, 1, 8, &color, 0, 0);
+        assert_eq!(image, [0, 0, 0xFF, 0xFF, 0x80, 0x80, 0x80, 0xFF]);
+
+        // Replaced where the mask alpha is 0, XORed where it's 0xFF
+        let mut image = grey();
+        let masked = CursorShape { kind: CursorKind::MaskedColor, width: 2, height: 1, pitch: 8, pixels: vec![1, 2, 3, 0, 0xFF, 0xFF, 0xFF, 0xFF] };
+        draw_cursor(&mut image, 2, 1, 8, &masked, 0, 0);
+        assert_eq!(image, [1, 2, 3, 0xFF, 0x7F, 0x7F, 0x7F, 0xFF]);
+
+        // AND 0 / XOR 0 is black, AND 1 / XOR 1 inverts
+        let mut image = grey();
+        let monochrome = CursorShape { kind: CursorKind::Monochrome, width: 2, height: 1, pitch: 1, pixels: vec![0b0100_0000, 0b0100_0000] };
+        draw_cursor(&mut image, 2, 1, 8, &monochrome, 0, 0);
+        assert_eq!(image, [0, 0, 0, 0xFF, 0x7F, 0x7F, 0x7F, 0xFF]);
+
+        // Half off the left edge: only the shape's second pixel lands, in column 0
+        let mut image = grey();
+        let opaque = CursorShape { pixels: vec![0xFF, 0, 0, 0xFF, 0, 0xFF, 0, 0xFF], ..color };
+        draw_cursor(&mut image, 2, 1, 8, &opaque, -1, 0);
+        assert_eq!(image, [0, 0xFF, 0, 0xFF, 0x80, 0x80, 0x80, 0xFF]);
+
+        // A shape too short for its size is skipped
+        let mut image = grey();
+        draw_cursor(&mut image, 2, 1, 8, &CursorShape { pixels: vec![0; 4], ..color }, 0, 0);
+        assert_eq!(image, grey());
+    }
+}
@@ -0,0 +1,176 @@
+// --- Fault injection ---
+// Makes the pipes misbehave on purpose, so the reconnect logic and the
+// frontend's handling of dropped connections and bad data can be exercised
+// without a flaky backend. Debug builds only; a release build ignores the
+// [chaos] section with a warning. Set in puppyweb.toml:
+//
+//   [chaos]
+//   enabled = true
+//   writeLatencyMs = 20             every frame write waits this long first,
+//   writeJitterMs = 30              plus up to this much more
+//   disconnectChance = 0.001        per frame write and per transform message
+//   truncateTransformChance = 0.05  per Poses / Transform message
+//   corruptFrameChance = 0.01       per frame written to the pipe
+//   seed = 42                       the same faults in the same order every run
+//
+// An injected disconnect goes through the same teardown and reconnect as a
+// real one. Truncated transforms are cut short before they're decoded, as if
+// the backend had sent a partial record. Corrupted frames have a few random
+// bytes of their header or pixels flipped after compression, so the backend
+// sees what a broken writer would send.
+use crate::protocol::{Message, MessageType};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{io, time::Duration};
+use tracing::debug;
+
+// Bytes flipped in a corrupted frame
+const CORRUPTED_BYTES: usize = 8;
+// Upper bound on writeLatencyMs + writeJitterMs, beyond which every write would just time out
+const MAX_WRITE_DELAY_MS: u64 = 60_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChaosConfig {
+    pub enabled: bool,
+    pub write_latency_ms: u64,
+    pub write_jitter_ms: u64,
+    // Chances between 0 and 1
+    pub disconnect_chance: f64,
+    pub truncate_transform_chance: f64,
+    pub corrupt_frame_chance: f64,
+    // Random per run if unset
+    pub seed: Option<u64>,
+}
+
+impl ChaosConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && !cfg!(debug_assertions) {
+            return Err("fault injection is only available in debug builds".to_string());
+        }
+        let chances = [
+            ("disconnectChance", self.disconnect_chance),
+            ("truncateTransformChance", self.truncate_transform_chance),
+            ("corruptFrameChance", self.corrupt_frame_chance),
+        ];
+        if let Some((name, _)) = chances.iter().find(|(_, chance)| !(0.0..=1.0).contains(chance)) {
+            return Err(format!("{} must be between 0 and 1", name));
+        }
+        if self.write_latency_ms.saturating_add(self.write_jitter_ms) > MAX_WRITE_DELAY_MS {
+            return Err(format!("writeLatencyMs and writeJitterMs add up to more than {} ms", MAX_WRITE_DELAY_MS));
+        }
+        Ok(())
+    }
+}
+
+pub struct Chaos {
+    config: ChaosConfig,
+    // xorshift64* state; never 0
+    rng: Mutex<u64>,
+}
+
+impl Chaos {
+    pub fn new(config: ChaosConfig) -> Self {
+        let seed = config.seed.unwrap_or_else(|| {
+            let mut bytes = [0u8; 8];
+            // A fixed seed is as good as any if the OS has no randomness to give
+            let _ = getrandom::getrandom(&mut bytes);
+            u64::from_le_bytes(bytes)
+        });
+        Self { config, rng: Mutex::new(seed.max(1)) }
+    }
+
+    fn next(&self) -> u64 {
+        let mut state = self.rng.lock();
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // True with probability `chance`, never while disabled
+    fn roll(&self, chance: f64) -> bool {
+        if !self.config.enabled || chance <= 0.0 {
+            return false;
+        }
+        // The top 53 bits as a uniform value in [0, 1)
+        let uniform = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        uniform < chance
+    }
+
+    // Called right before each frame write: waits out the injected latency, then maybe fails the write
+    pub async fn before_write(&self) -> io::Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let delay_ms = self.config.write_latency_ms + self.next() % (self.config.write_jitter_ms + 1);
+        if delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        }
+        if self.roll(self.config.disconnect_chance) {
+            return Err(io::Error::new(io::ErrorKind::ConnectionReset, "disconnect injected by chaos mode"));
+        }
+        Ok(())
+    }
+
+    // Whether to drop the transform connection after this message
+    pub fn disconnect_transform(&self) -> bool {
+        self.roll(self.config.disconnect_chance)
+    }
+
+    // The message, with its pose records or matrix cut short now and then
+    pub fn truncate_transform(&self, mut message: Message) -> Message {
+        let transform = matches!(message.header.message_type, MessageType::Poses | MessageType::Transform);
+        if transform && !message.payload.is_empty() && self.roll(self.config.truncate_transform_chance) {
+            let length = (self.next() % message.payload.len() as u64) as usize;
+            debug!("[Rust Chaos] Truncating {:?} message from {} to {} bytes.", message.header.message_type, message.payload.len(), length);
+            message.payload.truncate(length);
+        }
+        message
+    }
+
+    // A copy of the frame message payload `parts` with some bytes flipped, now and then
+    pub fn corrupt_frame(&self, parts: [&[u8]; 2]) -> Option<Vec<u8>> {
+        if !self.roll(self.config.corrupt_frame_chance) {
+            return None;
+        }
+        let mut payload = parts.concat();
+        if payload.is_empty() {
+            return None;
+        }
+        for _ in 0..CORRUPTED_BYTES {
+            let index = (self.next() % payload.len() as u64) as usize;
+            payload[index] ^= (self.next() % 255 + 1) as u8;
+        }
+        debug!("[Rust Chaos] Corrupted a {} byte frame.", payload.len());
+        Some(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageHeader;
+
+    fn poses(payload: Vec<u8>) -> Message {
+        let header = MessageHeader { version: 3, message_type: MessageType::Poses, flags: 0, length: payload.len() as u32 };
+        Message { header, payload }
+    }
+
+    #[tokio::test]
+    async fn faults_are_injected_only_while_enabled() {
+        let always = ChaosConfig { truncate_transform_chance: 1.0, corrupt_frame_chance: 1.0, disconnect_chance: 1.0, seed: Some(7), ..ChaosConfig::default() };
+        let off = Chaos::new(always);
+        assert_eq!(off.truncate_transform(poses(vec![1; 66])).payload.len(), 66);
+        assert!(off.corrupt_frame([&[1; 40], &[2; 64]]).is_none());
+        assert!(off.before_write().await.is_ok());
+
+        let on = Chaos::new(ChaosConfig { enabled: true, ..always });
+        assert!(on.truncate_transform(poses(vec![1; 66])).payload.len() < 66);
+        let corrupted = on.corrupt_frame([&[1; 40], &[2; 64]]).unwrap();
+        assert_eq!(corrupted.len(), 104);
+        assert_ne!(corrupted, [[1; 40].as_slice(), &[2; 64]].concat());
+        assert_eq!(on.before_write().await.unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+        assert!(on.disconnect_transform());
+    }
+}
@@ -0,0 +1,147 @@
+// --- Command line ---
+// Parsed by the app's run() before the Tauri builder is created and handed to
+// init_with_args. Values given here override puppyweb.toml for this launch
+// only (they're never written back), so scripts can start several copies
+// against different backends:
+//
+//   puppyweb --frame-pipe \\.\pipe\backend-b-frames --transform-pipe \\.\pipe\backend-b-transform
+//
+// Each distinct frame pipe gets its own single-instance guard (see
+// single_instance.rs), so only copies pointed at the same backend hand off to
+// each other. Both "--name value" and "--name=value" are accepted.
+use crate::{
+    config::{AppConfig, LogLevel},
+    recording::REPLAY_FLAG,
+};
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: puppyweb [options]
+
+Options:
+  --config <file>            Read settings from <file> instead of puppyweb.toml in the app config directory
+  --frame-pipe <path>        Frame pipe to connect to
+  --transform-pipe <path>    Transform pipe to connect to
+  --input-pipe <path>        Input pipe to connect to
+  --audio-pipe <path>        Audio pipe to connect to
+  --microphone-pipe <path>   Microphone pipe to connect to
+  --video-pipe <path>        Video pipe to connect to
+  --files-pipe <path>        File transfer pipe to connect to
+  --pointer-pipe <path>      VR pointer pipe to connect to
+  --mock-backend, --mock     Serve the pipes from the built-in mock backend
+  --log-level <level>        error, warn, info, debug or trace
+  --replay <file>            Feed a frame recording into the frame pipe, then exit
+  -h, --help                 Show this help";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CliArgs {
+    pub config: Option<PathBuf>,
+    pub frame_pipe: Option<String>,
+    pub transform_pipe: Option<String>,
+    pub input_pipe: Option<String>,
+    pub audio_pipe: Option<String>,
+    pub microphone_pipe: Option<String>,
+    pub video_pipe: Option<String>,
+    pub files_pipe: Option<String>,
+    pub pointer_pipe: Option<String>,
+    pub mock_backend: bool,
+    pub log_level: Option<LogLevel>,
+    // Recording to replay (see recording.rs)
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    // -h / --help
+    Help,
+    Invalid(String),
+}
+
+impl CliArgs {
+    // Parse the process arguments; prints the usage and exits on --help or a bad argument
+    pub fn parse() -> Self {
+        match Self::try_parse_from(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(CliError::Help) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(CliError::Invalid(message)) => {
+                eprintln!("error: {}\n\n{}", message, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // Parse `args`, which don't include the program name
+    pub fn try_parse_from(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline.clone().or_else(|| args.next()).ok_or_else(|| CliError::Invalid(format!("{} needs a value", name)))
+            };
+            match name.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                "--frame-pipe" => parsed.frame_pipe = Some(value()?),
+                "--transform-pipe" => parsed.transform_pipe = Some(value()?),
+                "--input-pipe" => parsed.input_pipe = Some(value()?),
+                "--audio-pipe" => parsed.audio_pipe = Some(value()?),
+                "--microphone-pipe" => parsed.microphone_pipe = Some(value()?),
+                "--video-pipe" => parsed.video_pipe = Some(value()?),
+                "--files-pipe" => parsed.files_pipe = Some(value()?),
+                "--pointer-pipe" => parsed.pointer_pipe = Some(value()?),
+                "--mock-backend" | "--mock" => parsed.mock_backend = true,
+                "--log-level" => {
+                    let level = value()?;
+                    let level = level.parse().map_err(|_| CliError::Invalid(format!("unknown log level {:?}", level)))?;
+                    parsed.log_level = Some(level);
+                }
+                REPLAY_FLAG => parsed.replay = Some(PathBuf::from(value()?)),
+                // Added by macOS when launched from the Finder
+                name if name.starts_with("-psn_") => {}
+                _ => return Err(CliError::Invalid(format!("unexpected argument {:?}", name))),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // Override the settings that were given on the command line
+    pub fn apply(&self, config: &mut AppConfig) {
+        if let Some(path) = &self.frame_pipe {
+            config.pipes.frame = path.clone();
+        }
+        if let Some(path) = &self.transform_pipe {
+            config.pipes.transform = path.clone();
+        }
+        if let Some(path) = &self.input_pipe {
+            config.pipes.input = path.clone();
+        }
+        if let Some(path) = &self.audio_pipe {
+            config.pipes.audio = path.clone();
+        }
+        if let Some(path) = &self.microphone_pipe {
+            config.pipes.microphone = path.clone();
+        }
+        if let Some(path) = &self.video_pipe {
+            config.pipes.video = path.clone();
+        }
+        if let Some(path) = &self.files_pipe {
+            config.pipes.files = path.clone();
+        }
+        if let Some(path) = &self.pointer_pipe {
+            config.pipes.pointer = path.clone();
+        }
+        if self.mock_backend {
+            config.mock_backend = true;
+        }
+        if let Some(level) = self.log_level {
+            config.log_level = level;
+        }
+    }
+}
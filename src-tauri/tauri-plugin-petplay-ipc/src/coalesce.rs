@@ -0,0 +1,84 @@
+// --- Transform coalescing ---
+// The backend can push poses at 250Hz+ per device, far more than the webview
+// can render. Instead of emitting every pose, the transform listener parks
+// the latest one per device here and an emitter task flushes them at a fixed
+// rate, so intermediate poses are simply overwritten. A rate of 0 turns
+// coalescing off and every pose is emitted as it arrives.
+//
+// Independently of coalescing, the newest pose per device is kept for
+// get_last_transform, for render loops that pull a pose right before drawing.
+use crate::pose::Pose;
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+// Typical display refresh; the webview can't show poses any faster than this
+pub const DEFAULT_TRANSFORM_RATE_HZ: u32 = 60;
+// Used by the emitter to re-check the rate while coalescing is off
+pub const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+struct Latest {
+    pose: Pose,
+    matrix: Vec<f32>,
+    received: Instant,
+}
+
+pub struct TransformCoalescer {
+    // Latest pose and matrix per device, waiting for the next flush
+    pending: Mutex<BTreeMap<u32, (Pose, Vec<f32>)>>,
+    // Newest pose and matrix per device and when it arrived; never flushed
+    latest: Mutex<BTreeMap<u32, Latest>>,
+    rate_hz: AtomicU32,
+}
+
+impl TransformCoalescer {
+    pub fn new(rate_hz: u32) -> Self {
+        Self {
+            pending: Mutex::new(BTreeMap::new()),
+            latest: Mutex::new(BTreeMap::new()),
+            rate_hz: AtomicU32::new(rate_hz),
+        }
+    }
+
+    pub fn rate_hz(&self) -> u32 {
+        self.rate_hz.load(Ordering::Relaxed)
+    }
+
+    pub fn set_rate_hz(&self, rate_hz: u32) {
+        self.rate_hz.store(rate_hz, Ordering::Relaxed);
+    }
+
+    // Time between flushes, None while coalescing is off
+    pub fn interval(&self) -> Option<Duration> {
+        match self.rate_hz() {
+            0 => None,
+            rate_hz => Some(Duration::from_secs_f64(1.0 / rate_hz as f64)),
+        }
+    }
+
+    // Park a pose until the next flush. Returns it back if coalescing is off
+    // and the caller should emit it right away.
+    pub fn push(&self, pose: Pose, matrix: Vec<f32>) -> Option<(Pose, Vec<f32>)> {
+        self.latest.lock().insert(pose.device_id, Latest { pose, matrix: matrix.clone(), received: Instant::now() });
+        if self.rate_hz() == 0 {
+            return Some((pose, matrix));
+        }
+        self.pending.lock().insert(pose.device_id, (pose, matrix));
+        None
+    }
+
+    // Newest pose and matrix of a device, and how long ago it arrived
+    pub fn latest(&self, device_id: u32) -> Option<(Pose, Vec<f32>, Duration)> {
+        let latest = self.latest.lock();
+        let latest = latest.get(&device_id)?;
+        Some((latest.pose, latest.matrix.clone(), latest.received.elapsed()))
+    }
+
+    // Everything parked since the last flush, in device order
+    pub fn take_pending(&self) -> Vec<(Pose, Vec<f32>)> {
+        std::mem::take(&mut *self.pending.lock()).into_values().collect()
+    }
+}
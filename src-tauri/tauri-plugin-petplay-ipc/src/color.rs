@@ -0,0 +1,242 @@
+// --- Color management ---
+// Frames come from the page (and from desktop captures) as sRGB-encoded,
+// straight-alpha rows from the top down, but backends don't all want that:
+// some sample their texture as linear light, some composite with
+// premultiplied alpha, some (OpenGL ones) expect the bottom row first. The
+// frame pipe's writer task can convert frames on their way out, after any
+// pixel format conversion and foveation (the preview still shows them as
+// drawn):
+//
+//   transfer  "srgb" as drawn, or "linear" (color channels decoded through the
+//             sRGB curve, still 8 bits per channel)
+//   alpha     "straight" as drawn, or "premultiplied" (color times alpha,
+//             after linearizing)
+//   flipY     send the rows bottom-up
+//
+// A setting left out follows what the backend asked for in the handshake
+// (CAP_LINEAR_COLOR, CAP_PREMULTIPLIED_ALPHA, CAP_FLIP_Y, see handshake.rs;
+// get_backend_info lists them), so a backend that says what it wants needs
+// no configuration. They're set with configure_stream's color option, which
+// replaces all three, or under [stream.color] in puppyweb.toml (which named
+// connections start from too):
+//
+//   [stream.color]
+//   alpha = "premultiplied"
+//   flipY = true
+//
+// Conversions work on 8-bit RGB(A) and BGR(A) rows; NV12 frames are turned
+// into RGBA8 first. Alpha (and RGB8 without it) is never premultiplied into
+// itself. On x86_64 premultiplying runs in SSE2 on 4 pixels at a time and
+// produces exactly the bytes the scalar code would; linearizing is a table
+// lookup. Frames going to the hardware encoder aren't converted.
+use crate::{
+    buffer_pool::BufferPool,
+    frame_queue::QueuedFrame,
+    handshake::{CAP_FLIP_Y, CAP_LINEAR_COLOR, CAP_PREMULTIPLIED_ALPHA},
+    pixel_format::{self, PixelFormat},
+    protocol::FrameHeader,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFunction {
+    Srgb,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaMode {
+    Straight,
+    Premultiplied,
+}
+
+// A stream's color settings; None follows the backend
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ColorOptions {
+    pub transfer: Option<TransferFunction>,
+    pub alpha: Option<AlphaMode>,
+    pub flip_y: Option<bool>,
+}
+
+impl ColorOptions {
+    // What to do to frames for a backend that accepted `capabilities`
+    pub fn resolve(&self, capabilities: u32) -> ColorConversion {
+        ColorConversion {
+            linearize: self.transfer.map_or(capabilities & CAP_LINEAR_COLOR != 0, |transfer| transfer == TransferFunction::Linear),
+            premultiply: self.alpha.map_or(capabilities & CAP_PREMULTIPLIED_ALPHA != 0, |alpha| alpha == AlphaMode::Premultiplied),
+            flip_y: self.flip_y.unwrap_or(capabilities & CAP_FLIP_Y != 0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColorConversion {
+    pub linearize: bool,
+    pub premultiply: bool,
+    pub flip_y: bool,
+}
+
+// sRGB-encoded value to the 8-bit linear one
+fn linear_table() -> &'static [u8; 256] {
+    static TABLE: OnceLock<[u8; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|value| {
+            let encoded = value as f64 / 255.0;
+            let linear = if encoded <= 0.04045 { encoded / 12.92 } else { ((encoded + 0.055) / 1.055).powf(2.4) };
+            (linear * 255.0).round() as u8
+        })
+    })
+}
+
+// The frame converted (into a buffer from `buffers`, packed), or None if there's nothing to do
+pub fn convert(frame: &QueuedFrame, conversion: ColorConversion, buffers: &Arc<BufferPool>) -> Option<QueuedFrame> {
+    if conversion == ColorConversion::default() {
+        return None;
+    }
+    let rgba = match frame.header.pixel_format {
+        PixelFormat::Nv12 => pixel_format::packed_rgba(frame, buffers),
+        _ => None,
+    };
+    let frame = rgba.as_ref().unwrap_or(frame);
+    let header = frame.header;
+    let (height, stride) = (header.height as usize, header.stride as usize);
+    let row_bytes = header.pixel_format.min_stride(header.width) as usize;
+    let has_alpha = matches!(header.pixel_format, PixelFormat::Rgba8 | PixelFormat::Bgra8);
+
+    let mut pixels = buffers.take(row_bytes * height);
+    for y in 0..height {
+        let source = if conversion.flip_y { height - 1 - y } else { y };
+        pixels.extend_from_slice(&frame.pixels[source * stride..][..row_bytes]);
+    }
+    if conversion.linearize {
+        let table = linear_table();
+        match has_alpha {
+            true => pixels.chunks_exact_mut(4).for_each(|pixel| pixel[..3].iter_mut().for_each(|channel| *channel = table[*channel as usize])),
+            false => pixels.iter_mut().for_each(|channel| *channel = table[*channel as usize]),
+        }
+    }
+    if conversion.premultiply && has_alpha {
+        premultiply(Kernel::detect(), &mut pixels);
+    }
+    let header = FrameHeader { stride: row_bytes as u32, ..header };
+    Some(QueuedFrame { header, pixels: pixels.into_bytes() })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kernel {
+    // Only picked by the tests on x86_64, where SSE2 is always there
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+}
+
+impl Kernel {
+    #[cfg(target_arch = "x86_64")]
+    fn detect() -> Self {
+        Self::Sse2
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn detect() -> Self {
+        Self::Scalar
+    }
+}
+
+// Multiply the color of every 4-byte pixel by its alpha (the last byte), rounding to nearest
+fn premultiply(kernel: Kernel, pixels: &mut [u8]) {
+    // Safety: the kernel only touches whole 16-byte blocks within `pixels`
+    let done = match kernel {
+        Kernel::Scalar => 0,
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Sse2 => unsafe { x86::premultiply_sse2(pixels) },
+    };
+    for pixel in pixels[done..].chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+// (c * a + 127) / 255 in 16-bit lanes: t = c * a + 127 stays below 65153, where (t + (t >> 8) + 1) >> 8
+// is exactly t / 255
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    unsafe fn premultiply_half(pixels: __m128i) -> __m128i {
+        // Each pixel's alpha in all four of its lanes
+        let alpha = _mm_shufflehi_epi16(_mm_shufflelo_epi16(pixels, 0xFF), 0xFF);
+        let t = _mm_add_epi16(_mm_mullo_epi16(pixels, alpha), _mm_set1_epi16(127));
+        _mm_srli_epi16(_mm_add_epi16(_mm_add_epi16(t, _mm_srli_epi16(t, 8)), _mm_set1_epi16(1)), 8)
+    }
+
+    pub unsafe fn premultiply_sse2(pixels: &mut [u8]) -> usize {
+        let zero = _mm_setzero_si128();
+        let alpha_mask = _mm_set1_epi32(0xFF00_0000u32 as i32);
+        let mut offset = 0;
+        while offset + 16 <= pixels.len() {
+            let block = pixels.as_mut_ptr().add(offset) as *mut __m128i;
+            let four = _mm_loadu_si128(block);
+            let low = premultiply_half(_mm_unpacklo_epi8(four, zero));
+            let high = premultiply_half(_mm_unpackhi_epi8(four, zero));
+            // Alpha stays as it was rather than multiplied by itself
+            let color = _mm_andnot_si128(alpha_mask, _mm_packus_epi16(low, high));
+            _mm_storeu_si128(block, _mm_or_si128(color, _mm_and_si128(four, alpha_mask)));
+            offset += 16;
+        }
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(format: PixelFormat, width: u32, height: u32, stride: u32, pixels: Vec<u8>) -> QueuedFrame {
+        QueuedFrame::test(0, format, width, height, stride, pixels)
+    }
+
+    #[test]
+    fn settings_left_out_follow_the_backend() {
+        let options = ColorOptions { alpha: Some(AlphaMode::Straight), ..ColorOptions::default() };
+        let everything = CAP_LINEAR_COLOR | CAP_PREMULTIPLIED_ALPHA | CAP_FLIP_Y;
+        assert_eq!(options.resolve(everything), ColorConversion { linearize: true, premultiply: false, flip_y: true });
+        assert_eq!(options.resolve(0), ColorConversion::default());
+        let buffers = Arc::new(BufferPool::default());
+        assert!(convert(&frame(PixelFormat::Rgba8, 1, 1, 4, vec![1, 2, 3, 4]), ColorConversion::default(), &buffers).is_none());
+    }
+
+    #[test]
+    fn rows_are_flipped_linearized_and_premultiplied() {
+        let buffers = Arc::new(BufferPool::default());
+        // 1x2 BGRA with 4 bytes of row padding
+        let bgra = frame(PixelFormat::Bgra8, 1, 2, 8, vec![255, 128, 0, 255, 9, 9, 9, 9, 200, 200, 200, 128, 9, 9, 9, 9]);
+        let conversion = ColorConversion { linearize: true, premultiply: true, flip_y: true };
+        let converted = convert(&bgra, conversion, &buffers).unwrap();
+        assert_eq!(converted.header.stride, 4);
+        // 200 is 147 in linear light, times 128/255 is 74; 128 is 55
+        assert_eq!(&converted.pixels[..], [74, 74, 74, 128, 255, 55, 0, 255]);
+
+        // RGB8 has no alpha to premultiply by
+        let rgb = frame(PixelFormat::Rgb8, 1, 1, 3, vec![0, 128, 255]);
+        assert_eq!(&convert(&rgb, conversion, &buffers).unwrap().pixels[..], [0, 55, 255]);
+    }
+
+    #[test]
+    fn simd_premultiplies_like_the_scalar_code() {
+        // Every color against every alpha, plus a ragged tail the scalar code finishes
+        let mut pixels: Vec<u8> = (0..=255u8).flat_map(|alpha| (0..=255u8).flat_map(move |color| [color, 255 - color, color / 2, alpha])).collect();
+        pixels.extend_from_slice(&[200, 100, 50, 77, 1, 2, 3, 4]);
+        let mut scalar = pixels.clone();
+        premultiply(Kernel::Scalar, &mut scalar);
+        premultiply(Kernel::detect(), &mut pixels);
+        assert_eq!(pixels, scalar);
+        assert_eq!(&scalar[scalar.len() - 8..], [60, 30, 15, 77, 0, 0, 0, 4]);
+    }
+}
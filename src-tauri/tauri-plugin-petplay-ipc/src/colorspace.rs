@@ -0,0 +1,488 @@
+// --- Colorspace conversion ---
+// Hardware encoders work in 4:2:0 YUV. Handing ffmpeg RGBA makes it run a
+// software swscale pass on every frame before the encoder sees it, so the
+// frame pipeline converts to NV12 (or I420, see EncoderSettings::input) itself
+// and ffmpeg passes the planes straight through.
+//
+// BT.601 limited range, the inverse of yuv_to_rgb in pixel_format.rs; each
+// chroma sample is the average of a 2x2 block (edge pixels are repeated for
+// odd sizes). On x86_64 SSE2 and AVX2 kernels (picked at runtime) convert the
+// bulk of every row in 16-bit fixed point and produce exactly the bytes the
+// scalar code would; the scalar code does the columns left at the right edge
+// and everything on other targets.
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum YuvLayout {
+    // Y plane, then one half-resolution plane of interleaved U/V samples
+    #[default]
+    Nv12,
+    // Y plane, then a half-resolution U plane, then a V plane
+    I420,
+}
+
+impl YuvLayout {
+    // ffmpeg -pix_fmt name
+    pub fn ffmpeg_pix_fmt(self) -> &'static str {
+        match self {
+            Self::Nv12 => "nv12",
+            Self::I420 => "yuv420p",
+        }
+    }
+}
+
+// Bytes of a converted frame, either layout; chroma planes round odd sizes up
+pub fn frame_size(width: usize, height: usize) -> usize {
+    width * height + 2 * width.div_ceil(2) * height.div_ceil(2)
+}
+
+// Convert tightly packed RGBA to a newly allocated frame in `layout`
+pub fn to_yuv(layout: YuvLayout, rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut out = vec![0u8; frame_size(width, height)];
+    match layout {
+        YuvLayout::Nv12 => rgba_to_nv12(rgba, width, height, &mut out),
+        YuvLayout::I420 => rgba_to_i420(rgba, width, height, &mut out),
+    }
+    out
+}
+
+pub fn rgba_to_nv12(rgba: &[u8], width: usize, height: usize, out: &mut [u8]) {
+    convert(Kernel::detect(), YuvLayout::Nv12, rgba, width, height, out);
+}
+
+pub fn rgba_to_i420(rgba: &[u8], width: usize, height: usize, out: &mut [u8]) {
+    convert(Kernel::detect(), YuvLayout::I420, rgba, width, height, out);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kernel {
+    // Only picked by the tests on x86_64, where SSE2 is always there
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+impl Kernel {
+    #[cfg(target_arch = "x86_64")]
+    fn detect() -> Self {
+        if is_x86_feature_detected!("avx2") {
+            Self::Avx2
+        } else {
+            Self::Sse2
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn detect() -> Self {
+        Self::Scalar
+    }
+}
+
+// Where a row of chroma samples goes
+enum Chroma<'a> {
+    Interleaved(&'a mut [u8]),
+    Planar(&'a mut [u8], &'a mut [u8]),
+}
+
+impl Chroma<'_> {
+    fn set(&mut self, index: usize, u: u8, v: u8) {
+        match self {
+            Self::Interleaved(uv) => {
+                uv[index * 2] = u;
+                uv[index * 2 + 1] = v;
+            }
+            Self::Planar(u_row, v_row) => {
+                u_row[index] = u;
+                v_row[index] = v;
+            }
+        }
+    }
+}
+
+fn convert(kernel: Kernel, layout: YuvLayout, rgba: &[u8], width: usize, height: usize, out: &mut [u8]) {
+    assert!(rgba.len() >= width * height * 4, "RGBA buffer too small for {}x{}", width, height);
+    assert!(out.len() >= frame_size(width, height), "YUV buffer too small for {}x{}", width, height);
+    let chroma_width = width.div_ceil(2);
+    let (luma, chroma) = out.split_at_mut(width * height);
+    let (first, second) = match layout {
+        YuvLayout::Nv12 => (chroma, &mut [][..]),
+        YuvLayout::I420 => chroma.split_at_mut(chroma_width * height.div_ceil(2)),
+    };
+
+    for pair in 0..height.div_ceil(2) {
+        let top_y = pair * 2;
+        // The last row of an odd height is its own pair
+        let bottom_y = (top_y + 1).min(height - 1);
+        let top = &rgba[top_y * width * 4..][..width * 4];
+        let bottom = &rgba[bottom_y * width * 4..][..width * 4];
+        luma_row(kernel, top, &mut luma[top_y * width..][..width]);
+        if bottom_y != top_y {
+            luma_row(kernel, bottom, &mut luma[bottom_y * width..][..width]);
+        }
+        let mut row = match layout {
+            YuvLayout::Nv12 => Chroma::Interleaved(&mut first[pair * chroma_width * 2..][..chroma_width * 2]),
+            YuvLayout::I420 => Chroma::Planar(
+                &mut first[pair * chroma_width..][..chroma_width],
+                &mut second[pair * chroma_width..][..chroma_width],
+            ),
+        };
+        chroma_row(kernel, top, bottom, width, &mut row);
+    }
+}
+
+fn luma_row(kernel: Kernel, rgba: &[u8], luma: &mut [u8]) {
+    // Safety: rows hold luma.len() RGBA pixels, which is all the kernels read
+    let done = match kernel {
+        Kernel::Scalar => 0,
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Sse2 => unsafe { x86::luma_sse2(rgba, luma) },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { x86::luma_avx2(rgba, luma) },
+    };
+    for (y, pixel) in luma[done..].iter_mut().zip(rgba[done * 4..].chunks_exact(4)) {
+        *y = luma_of(pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+    }
+}
+
+fn chroma_row(kernel: Kernel, top: &[u8], bottom: &[u8], width: usize, out: &mut Chroma) {
+    // Safety: both rows hold `width` RGBA pixels and the kernels only take whole pairs of them
+    let done = match kernel {
+        Kernel::Scalar => 0,
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Sse2 => unsafe { x86::chroma_sse2(top, bottom, width, out) },
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { x86::chroma_avx2(top, bottom, width, out) },
+    };
+    for index in done..width.div_ceil(2) {
+        let left = index * 2 * 4;
+        let right = (index * 2 + 1).min(width - 1) * 4;
+        let average = |channel: usize| {
+            let sum = top[left + channel] as i32 + top[right + channel] as i32
+                + bottom[left + channel] as i32 + bottom[right + channel] as i32;
+            (sum + 2) >> 2
+        };
+        let (u, v) = chroma_of(average(0), average(1), average(2));
+        out.set(index, u, v);
+    }
+}
+
+// 8-bit fixed point BT.601 coefficients, rows for Y, U and V
+const Y_COEFFS: [i32; 3] = [66, 129, 25];
+const U_COEFFS: [i32; 3] = [-38, -74, 112];
+const V_COEFFS: [i32; 3] = [112, -94, -18];
+
+fn weigh(coeffs: [i32; 3], r: i32, g: i32, b: i32) -> i32 {
+    (coeffs[0] * r + coeffs[1] * g + coeffs[2] * b + 128) >> 8
+}
+
+fn luma_of(r: i32, g: i32, b: i32) -> u8 {
+    (weigh(Y_COEFFS, r, g, b) + 16) as u8
+}
+
+fn chroma_of(r: i32, g: i32, b: i32) -> (u8, u8) {
+    ((weigh(U_COEFFS, r, g, b) + 128) as u8, (weigh(V_COEFFS, r, g, b) + 128) as u8)
+}
+
+// The kernels do the same sums in 16-bit lanes. Y's weighted sum reaches 56228, so it wraps as i16
+// but is exact as u16 and is shifted logically; U and V stay within +-28688 and shift arithmetically.
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{Chroma, U_COEFFS, V_COEFFS, Y_COEFFS};
+    use std::arch::x86_64::*;
+
+    // --- SSE2, 8 pixels / 8 chroma samples at a time ---
+
+    // R, G and B of 8 RGBA pixels as i16 lanes
+    unsafe fn channels_sse2(pixels: *const u8) -> [__m128i; 3] {
+        let mask = _mm_set1_epi32(0xFF);
+        let lo = _mm_loadu_si128(pixels as *const __m128i);
+        let hi = _mm_loadu_si128(pixels.add(16) as *const __m128i);
+        let r = _mm_packs_epi32(_mm_and_si128(lo, mask), _mm_and_si128(hi, mask));
+        let g = _mm_packs_epi32(_mm_and_si128(_mm_srli_epi32(lo, 8), mask), _mm_and_si128(_mm_srli_epi32(hi, 8), mask));
+        let b = _mm_packs_epi32(_mm_and_si128(_mm_srli_epi32(lo, 16), mask), _mm_and_si128(_mm_srli_epi32(hi, 16), mask));
+        [r, g, b]
+    }
+
+    // coeffs . rgb + 128, before the shift
+    unsafe fn weigh_sse2(coeffs: [i32; 3], [r, g, b]: [__m128i; 3]) -> __m128i {
+        let r = _mm_mullo_epi16(r, _mm_set1_epi16(coeffs[0] as i16));
+        let g = _mm_mullo_epi16(g, _mm_set1_epi16(coeffs[1] as i16));
+        let b = _mm_mullo_epi16(b, _mm_set1_epi16(coeffs[2] as i16));
+        _mm_add_epi16(_mm_add_epi16(r, g), _mm_add_epi16(b, _mm_set1_epi16(128)))
+    }
+
+    // Averages of the 2x2 blocks of 8 pixels from each row, as 4 i32 lanes per channel
+    unsafe fn block_averages_sse2(top: *const u8, bottom: *const u8) -> [__m128i; 3] {
+        let (top, bottom) = (channels_sse2(top), channels_sse2(bottom));
+        let ones = _mm_set1_epi16(1);
+        let mut averages = [_mm_setzero_si128(); 3];
+        for (average, (top, bottom)) in averages.iter_mut().zip(top.into_iter().zip(bottom)) {
+            let sum = _mm_add_epi32(_mm_madd_epi16(top, ones), _mm_madd_epi16(bottom, ones));
+            *average = _mm_srai_epi32(_mm_add_epi32(sum, _mm_set1_epi32(2)), 2);
+        }
+        averages
+    }
+
+    pub unsafe fn luma_sse2(rgba: &[u8], luma: &mut [u8]) -> usize {
+        debug_assert!(rgba.len() >= luma.len() * 4);
+        let mut x = 0;
+        while x + 8 <= luma.len() {
+            let sum = weigh_sse2(Y_COEFFS, channels_sse2(rgba.as_ptr().add(x * 4)));
+            let y = _mm_add_epi16(_mm_srli_epi16(sum, 8), _mm_set1_epi16(16));
+            _mm_storel_epi64(luma.as_mut_ptr().add(x) as *mut __m128i, _mm_packus_epi16(y, y));
+            x += 8;
+        }
+        x
+    }
+
+    pub unsafe fn chroma_sse2(top: &[u8], bottom: &[u8], width: usize, out: &mut Chroma) -> usize {
+        debug_assert!(top.len() >= width * 4 && bottom.len() >= width * 4);
+        let mut index = 0;
+        while (index + 8) * 2 <= width {
+            let offset = index * 2 * 4;
+            let first = block_averages_sse2(top.as_ptr().add(offset), bottom.as_ptr().add(offset));
+            let second = block_averages_sse2(top.as_ptr().add(offset + 32), bottom.as_ptr().add(offset + 32));
+            let rgb = [0, 1, 2].map(|channel| _mm_packs_epi32(first[channel], second[channel]));
+            let bias = _mm_set1_epi16(128);
+            let u = _mm_add_epi16(_mm_srai_epi16(weigh_sse2(U_COEFFS, rgb), 8), bias);
+            let v = _mm_add_epi16(_mm_srai_epi16(weigh_sse2(V_COEFFS, rgb), 8), bias);
+            let (u, v) = (_mm_packus_epi16(u, u), _mm_packus_epi16(v, v));
+            match out {
+                Chroma::Interleaved(uv) => {
+                    _mm_storeu_si128(uv[index * 2..][..16].as_mut_ptr() as *mut __m128i, _mm_unpacklo_epi8(u, v));
+                }
+                Chroma::Planar(u_row, v_row) => {
+                    _mm_storel_epi64(u_row[index..][..8].as_mut_ptr() as *mut __m128i, u);
+                    _mm_storel_epi64(v_row[index..][..8].as_mut_ptr() as *mut __m128i, v);
+                }
+            }
+            index += 8;
+        }
+        index
+    }
+
+    // --- AVX2, 16 pixels / 16 chroma samples at a time ---
+
+    // Packing works per 128-bit lane; this puts the 64-bit quarters back in order
+    const IN_ORDER: i32 = 0b11_01_10_00;
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn channels_avx2(pixels: *const u8) -> [__m256i; 3] {
+        let mask = _mm256_set1_epi32(0xFF);
+        let lo = _mm256_loadu_si256(pixels as *const __m256i);
+        let hi = _mm256_loadu_si256(pixels.add(32) as *const __m256i);
+        let r = _mm256_packs_epi32(_mm256_and_si256(lo, mask), _mm256_and_si256(hi, mask));
+        let g = _mm256_packs_epi32(
+            _mm256_and_si256(_mm256_srli_epi32(lo, 8), mask),
+            _mm256_and_si256(_mm256_srli_epi32(hi, 8), mask),
+        );
+        let b = _mm256_packs_epi32(
+            _mm256_and_si256(_mm256_srli_epi32(lo, 16), mask),
+            _mm256_and_si256(_mm256_srli_epi32(hi, 16), mask),
+        );
+        [
+            _mm256_permute4x64_epi64(r, IN_ORDER),
+            _mm256_permute4x64_epi64(g, IN_ORDER),
+            _mm256_permute4x64_epi64(b, IN_ORDER),
+        ]
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn weigh_avx2(coeffs: [i32; 3], [r, g, b]: [__m256i; 3]) -> __m256i {
+        let r = _mm256_mullo_epi16(r, _mm256_set1_epi16(coeffs[0] as i16));
+        let g = _mm256_mullo_epi16(g, _mm256_set1_epi16(coeffs[1] as i16));
+        let b = _mm256_mullo_epi16(b, _mm256_set1_epi16(coeffs[2] as i16));
+        _mm256_add_epi16(_mm256_add_epi16(r, g), _mm256_add_epi16(b, _mm256_set1_epi16(128)))
+    }
+
+    // The 16 low bytes of packing `values`, in order
+    #[target_feature(enable = "avx2")]
+    unsafe fn pack_bytes_avx2(values: __m256i) -> __m128i {
+        _mm256_castsi256_si128(_mm256_permute4x64_epi64(_mm256_packus_epi16(values, values), IN_ORDER))
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn block_averages_avx2(top: *const u8, bottom: *const u8) -> [__m256i; 3] {
+        let (top, bottom) = (channels_avx2(top), channels_avx2(bottom));
+        let ones = _mm256_set1_epi16(1);
+        let mut averages = [_mm256_setzero_si256(); 3];
+        for (average, (top, bottom)) in averages.iter_mut().zip(top.into_iter().zip(bottom)) {
+            let sum = _mm256_add_epi32(_mm256_madd_epi16(top, ones), _mm256_madd_epi16(bottom, ones));
+            *average = _mm256_srai_epi32(_mm256_add_epi32(sum, _mm256_set1_epi32(2)), 2);
+        }
+        averages
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn luma_avx2(rgba: &[u8], luma: &mut [u8]) -> usize {
+        debug_assert!(rgba.len() >= luma.len() * 4);
+        let mut x = 0;
+        while x + 16 <= luma.len() {
+            let sum = weigh_avx2(Y_COEFFS, channels_avx2(rgba.as_ptr().add(x * 4)));
+            let y = _mm256_add_epi16(_mm256_srli_epi16(sum, 8), _mm256_set1_epi16(16));
+            _mm_storeu_si128(luma.as_mut_ptr().add(x) as *mut __m128i, pack_bytes_avx2(y));
+            x += 16;
+        }
+        x
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn chroma_avx2(top: &[u8], bottom: &[u8], width: usize, out: &mut Chroma) -> usize {
+        debug_assert!(top.len() >= width * 4 && bottom.len() >= width * 4);
+        let mut index = 0;
+        while (index + 16) * 2 <= width {
+            let offset = index * 2 * 4;
+            let first = block_averages_avx2(top.as_ptr().add(offset), bottom.as_ptr().add(offset));
+            let second = block_averages_avx2(top.as_ptr().add(offset + 64), bottom.as_ptr().add(offset + 64));
+            let mut rgb = [_mm256_setzero_si256(); 3];
+            for (channel, packed) in rgb.iter_mut().enumerate() {
+                *packed = _mm256_permute4x64_epi64(_mm256_packs_epi32(first[channel], second[channel]), IN_ORDER);
+            }
+            let bias = _mm256_set1_epi16(128);
+            let u = pack_bytes_avx2(_mm256_add_epi16(_mm256_srai_epi16(weigh_avx2(U_COEFFS, rgb), 8), bias));
+            let v = pack_bytes_avx2(_mm256_add_epi16(_mm256_srai_epi16(weigh_avx2(V_COEFFS, rgb), 8), bias));
+            match out {
+                Chroma::Interleaved(uv) => {
+                    let uv = uv[index * 2..][..32].as_mut_ptr();
+                    _mm_storeu_si128(uv as *mut __m128i, _mm_unpacklo_epi8(u, v));
+                    _mm_storeu_si128(uv.add(16) as *mut __m128i, _mm_unpackhi_epi8(u, v));
+                }
+                Chroma::Planar(u_row, v_row) => {
+                    _mm_storeu_si128(u_row[index..][..16].as_mut_ptr() as *mut __m128i, u);
+                    _mm_storeu_si128(v_row[index..][..16].as_mut_ptr() as *mut __m128i, v);
+                }
+            }
+            index += 16;
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic noise, so failures reproduce
+    fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect()
+    }
+
+    fn kernels() -> Vec<Kernel> {
+        #[allow(unused_mut)]
+        let mut kernels = vec![Kernel::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            kernels.push(Kernel::Sse2);
+            if is_x86_feature_detected!("avx2") {
+                kernels.push(Kernel::Avx2);
+            }
+        }
+        kernels
+    }
+
+    fn convert_with(kernel: Kernel, layout: YuvLayout, rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let mut out = vec![0u8; frame_size(width, height)];
+        convert(kernel, layout, rgba, width, height, &mut out);
+        out
+    }
+
+    // Floating point BT.601 limited range (Rec. 601 / JFIF derivation)
+    fn reference(r: f32, g: f32, b: f32) -> [f32; 3] {
+        let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+        let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+        let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+        [y, u, v]
+    }
+
+    #[test]
+    fn solid_colors_match_bt601() {
+        // RGB, then the expected Y, U, V
+        let cases = [
+            ([0, 0, 0], [16, 128, 128]),
+            ([255, 255, 255], [235, 128, 128]),
+            ([255, 0, 0], [82, 90, 240]),
+            ([0, 255, 0], [144, 54, 34]),
+            ([0, 0, 255], [41, 240, 110]),
+            ([128, 128, 128], [126, 128, 128]),
+        ];
+        for kernel in kernels() {
+            for ([r, g, b], expected) in cases {
+                // Wide enough that the SIMD kernels do the bulk of it
+                let (width, height) = (64, 4);
+                let rgba: Vec<u8> = [r, g, b, 255].repeat(width * height);
+                let nv12 = convert_with(kernel, YuvLayout::Nv12, &rgba, width, height);
+                let (luma, chroma) = nv12.split_at(width * height);
+                assert!(luma.iter().all(|&y| y == expected[0]), "{:?} Y of {:?}", kernel, [r, g, b]);
+                for uv in chroma.chunks_exact(2) {
+                    assert_eq!(uv, &expected[1..], "{:?} UV of {:?}", kernel, [r, g, b]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scalar_is_within_one_of_the_reference() {
+        let rgba = noise(4096 * 4, 7);
+        for pixel in rgba.chunks_exact(4) {
+            let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+            let [y, u, v] = reference(r as f32, g as f32, b as f32);
+            let (u8_u, u8_v) = chroma_of(r, g, b);
+            assert!((luma_of(r, g, b) as f32 - y).abs() <= 1.0, "Y of {:?}", pixel);
+            assert!((u8_u as f32 - u).abs() <= 1.0, "U of {:?}", pixel);
+            assert!((u8_v as f32 - v).abs() <= 1.0, "V of {:?}", pixel);
+        }
+    }
+
+    #[test]
+    fn chroma_averages_each_2x2_block() {
+        // Left column black, right column white: every block averages to mid grey
+        let (width, height) = (2, 2);
+        let rgba = [[0, 0, 0, 255], [255, 255, 255, 255]].concat().repeat(height);
+        let average = ((255 * 2 + 2) >> 2) as f32;
+        let [_, u, v] = reference(average, average, average);
+        let nv12 = convert_with(Kernel::Scalar, YuvLayout::Nv12, &rgba, width, height);
+        assert_eq!(&nv12[..4], &[16, 235, 16, 235]);
+        assert_eq!(nv12[4], u.round() as u8);
+        assert_eq!(nv12[5], v.round() as u8);
+    }
+
+    #[test]
+    fn simd_kernels_match_scalar_exactly() {
+        // Widths around the 8 and 16 pixel kernel steps, odd ones included
+        for (width, height) in [(1, 1), (7, 3), (8, 2), (15, 5), (16, 4), (17, 1), (31, 7), (32, 2), (33, 3), (100, 6), (257, 9)] {
+            let rgba = noise(width * height * 4, (width * 31 + height) as u32);
+            for layout in [YuvLayout::Nv12, YuvLayout::I420] {
+                let expected = convert_with(Kernel::Scalar, layout, &rgba, width, height);
+                for kernel in kernels() {
+                    let actual = convert_with(kernel, layout, &rgba, width, height);
+                    assert!(actual == expected, "{:?} {:?} differs from scalar at {}x{}", kernel, layout, width, height);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn i420_holds_the_same_samples_as_nv12() {
+        let (width, height) = (37, 11);
+        let rgba = noise(width * height * 4, 99);
+        let nv12 = to_yuv(YuvLayout::Nv12, &rgba, width, height);
+        let i420 = to_yuv(YuvLayout::I420, &rgba, width, height);
+        let luma = width * height;
+        let chroma = width.div_ceil(2) * height.div_ceil(2);
+        assert_eq!(nv12[..luma], i420[..luma]);
+        for (index, uv) in nv12[luma..].chunks_exact(2).enumerate() {
+            assert_eq!(uv, &[i420[luma + index], i420[luma + chroma + index]]);
+        }
+    }
+}
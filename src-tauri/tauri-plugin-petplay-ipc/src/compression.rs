@@ -0,0 +1,198 @@
+// --- Frame compression ---
+// Pixel data can optionally be compressed before it goes over the frame pipe.
+// The frame header itself stays uncompressed so the backend can read the
+// dimensions and sequence number before deciding how to decode the rest;
+// the codec used is signalled through the message flags.
+//
+// Compression runs on Tokio's blocking thread pool so the writer task's
+// thread stays free. Backends that accept CAP_TILED_COMPRESSION get large
+// frames split into tiles (consecutive byte ranges of the pixels) that are
+// compressed in parallel, one blocking task each, and sent with FLAG_TILED:
+//
+//   [0..2)   tile count (u16 LE)
+//   [2..4)   reserved
+//   then per tile:
+//   [0..4)   compressed tile length (u32 LE), followed by the tile compressed on its own
+//
+// Decompressing the tiles in order and joining them gives the pixels. The
+// zstd level and the number of workers come from [stream] zstdLevel and
+// compressionWorkers, or configure_stream. Tiles and payloads are compressed
+// into buffers from the buffer pool.
+use crate::buffer_pool::{BufferPool, PooledBuffer};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicI32, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// Message flags (protocol header) marking a compressed frame
+pub const FLAG_LZ4: u16 = 1 << 0;
+pub const FLAG_ZSTD: u16 = 1 << 1;
+// Set beside FLAG_LZ4 / FLAG_ZSTD when the pixels were compressed in tiles
+pub const FLAG_TILED: u16 = 1 << 3;
+
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+// Frames are only split into tiles of at least this size; smaller ones aren't worth the overhead
+const MIN_TILE_BYTES: usize = 512 * 1024;
+const TILED_HEADER_SIZE: usize = 4;
+const TILE_LENGTH_SIZE: usize = 4;
+const LZ4_SIZE_PREFIX: usize = 4;
+// compressionWorkers = 0 uses one per core, up to this many
+const MAX_AUTO_WORKERS: usize = 8;
+pub const MAX_WORKERS: usize = 64;
+
+pub fn zstd_levels() -> RangeInclusive<i32> {
+    zstd::compression_level_range()
+}
+
+// Ordered from no to strongest compression (used by adaptive.rs)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    // LZ4 block format, prefixed with the uncompressed size (u32 LE)
+    Lz4,
+    // Single zstd frame
+    Zstd,
+}
+
+impl Compression {
+    pub fn flag(self) -> u16 {
+        match self {
+            Self::None => 0,
+            Self::Lz4 => FLAG_LZ4,
+            Self::Zstd => FLAG_ZSTD,
+        }
+    }
+
+    // The most `len` bytes can compress to
+    fn max_compressed_len(self, len: usize) -> usize {
+        match self {
+            Self::None => len,
+            Self::Lz4 => LZ4_SIZE_PREFIX + lz4_flex::block::get_maximum_output_size(len),
+            Self::Zstd => zstd::zstd_safe::compress_bound(len),
+        }
+    }
+
+    // Compress `data` into `out`, replacing its contents
+    pub fn compress(self, data: &[u8], zstd_level: i32, out: &mut Vec<u8>) -> io::Result<()> {
+        out.clear();
+        match self {
+            Self::None => out.extend_from_slice(data),
+            Self::Lz4 => {
+                out.resize(self.max_compressed_len(data.len()), 0);
+                out[..LZ4_SIZE_PREFIX].copy_from_slice(&(data.len() as u32).to_le_bytes());
+                let written = lz4_flex::block::compress_into(data, &mut out[LZ4_SIZE_PREFIX..]).map_err(io::Error::other)?;
+                out.truncate(LZ4_SIZE_PREFIX + written);
+            }
+            Self::Zstd => {
+                out.resize(self.max_compressed_len(data.len()), 0);
+                let written = zstd::bulk::compress_to_buffer(data, out, zstd_level)?;
+                out.truncate(written);
+            }
+        }
+        Ok(())
+    }
+
+    // The same, into a buffer from `buffers`
+    fn compress_pooled(self, data: &[u8], zstd_level: i32, buffers: &Arc<BufferPool>) -> io::Result<PooledBuffer> {
+        let mut out = buffers.take(self.max_compressed_len(data.len()));
+        self.compress(data, zstd_level, &mut out)?;
+        Ok(out)
+    }
+}
+
+// Compresses frames off the writer task, in parallel tiles where the backend takes them
+pub struct Compressor {
+    zstd_level: AtomicI32,
+    // 0 for one per core
+    workers: AtomicUsize,
+    buffers: Arc<BufferPool>,
+}
+
+impl Compressor {
+    pub fn new(zstd_level: i32, workers: usize, buffers: Arc<BufferPool>) -> Self {
+        Self { zstd_level: AtomicI32::new(zstd_level), workers: AtomicUsize::new(workers), buffers }
+    }
+
+    pub fn set_zstd_level(&self, level: i32) {
+        self.zstd_level.store(level, Ordering::Relaxed);
+    }
+
+    pub fn set_workers(&self, workers: usize) {
+        self.workers.store(workers, Ordering::Relaxed);
+    }
+
+    fn workers(&self) -> usize {
+        match self.workers.load(Ordering::Relaxed) {
+            0 => std::thread::available_parallelism().map_or(1, |cores| cores.get().min(MAX_AUTO_WORKERS)),
+            workers => workers,
+        }
+    }
+
+    // Compress `pixels`, in tiles if `tiled` is allowed and the frame is big enough.
+    // Returns the payload and whether it was tiled.
+    pub async fn compress(&self, compression: Compression, pixels: Bytes, tiled: bool) -> io::Result<(PooledBuffer, bool)> {
+        let level = self.zstd_level.load(Ordering::Relaxed);
+        let tiles = if tiled { (pixels.len() / MIN_TILE_BYTES).clamp(1, self.workers()) } else { 1 };
+        if tiles == 1 {
+            let buffers = Arc::clone(&self.buffers);
+            let compressed = tokio::task::spawn_blocking(move || compression.compress_pooled(&pixels, level, &buffers))
+                .await
+                .map_err(io::Error::other)??;
+            return Ok((compressed, false));
+        }
+        let tile_len = pixels.len().div_ceil(tiles);
+        let jobs: Vec<_> = (0..tiles)
+            .map(|tile| {
+                let tile = pixels.slice(tile * tile_len..((tile + 1) * tile_len).min(pixels.len()));
+                let buffers = Arc::clone(&self.buffers);
+                tokio::task::spawn_blocking(move || compression.compress_pooled(&tile, level, &buffers))
+            })
+            .collect();
+        let mut compressed = Vec::with_capacity(tiles);
+        for job in jobs {
+            compressed.push(job.await.map_err(io::Error::other)??);
+        }
+        let length = TILED_HEADER_SIZE + compressed.iter().map(|tile| TILE_LENGTH_SIZE + tile.len()).sum::<usize>();
+        let mut payload = self.buffers.take(length);
+        payload.extend_from_slice(&(tiles as u16).to_le_bytes());
+        payload.extend_from_slice(&[0; 2]);
+        for tile in compressed {
+            payload.extend_from_slice(&(tile.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&tile);
+        }
+        Ok((payload, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    #[tokio::test]
+    async fn tiles_decompress_back_into_the_frame() {
+        let pixels: Vec<u8> = (0..4 * MIN_TILE_BYTES).map(|i| (i / 7) as u8).collect();
+        let compressor = Compressor::new(DEFAULT_ZSTD_LEVEL, 3, Arc::default());
+
+        let (payload, tiled) = compressor.compress(Compression::Lz4, Bytes::from(pixels.clone()), true).await.unwrap();
+        assert!(tiled);
+        assert_eq!(LittleEndian::read_u16(&payload[..2]), 3);
+        let mut rest = &payload[TILED_HEADER_SIZE..];
+        let mut decompressed = Vec::new();
+        while !rest.is_empty() {
+            let length = LittleEndian::read_u32(&rest[..TILE_LENGTH_SIZE]) as usize;
+            let tile = &rest[TILE_LENGTH_SIZE..TILE_LENGTH_SIZE + length];
+            decompressed.extend(lz4_flex::decompress_size_prepended(tile).unwrap());
+            rest = &rest[TILE_LENGTH_SIZE + length..];
+        }
+        assert_eq!(decompressed, pixels);
+    }
+}
@@ -0,0 +1,359 @@
+// --- Configuration file ---
+// Settings read at startup from puppyweb.toml in the app config
+// directory (e.g. %APPDATA%\<identifier>\ on Windows). Every key is
+// optional; anything left out keeps the built-in default, and a missing or
+// unreadable file just means all defaults. Commands that persist their
+// setting (set_pipe_paths, configure_pipe) write it back here. Some keys can
+// be overridden for a single launch on the command line (see cli.rs), and
+// edits to the file are picked up while the app runs (see config_watch.rs). Keys use
+// the same camelCase names as the matching commands, e.g.
+//
+//   logLevel = "debug"
+//
+//   [pipes]
+//   frame = '\\.\pipe\petplay-ipc-frames'
+//   transform = "tcp://192.168.1.20:7101"
+//
+//   [pipeOptions]
+//   outBufferSize = 16777216
+//
+//   [stream]
+//   queueDepth = 3
+//   backpressure = "drop-oldest"
+//   compression = "zstd"
+//   zstdLevel = 5
+//   delta = true
+//   targetFps = 90
+//
+//   [timeouts]
+//   writeMs = 2000
+//
+//   [adaptive]
+//   mode = "auto"
+//   targetFps = 72
+//
+//   [foveation]
+//   enabled = true
+//   radius = 0.2
+//
+//   [reconnect]
+//   initialDelayMs = 500
+//   maxRetries = 50
+//
+//   [limits]
+//   maxPayloadBytes = 67174400
+//
+//   [pipeSecurity]
+//   allowedSids = ["S-1-5-18"]
+//
+//   [backend]
+//   executable = 'C:\PetPlay\backend.exe'
+//   autoStart = true
+//
+//   [metricsEndpoint]
+//   enabled = true
+//   port = 9464
+//
+//   [audio]
+//   maxLatencyMs = 100
+//
+//   [gaze]
+//   rateHz = 30
+//
+//   [deviceStatus]
+//   lowBatteryPercent = 15
+//
+//   [openvr]
+//   enabled = true
+//
+//   [openxr]
+//   enabled = true
+//   widthMeters = 1.5
+//
+//   [dashboard]
+//   enabled = true
+//   iconPath = "C:/PuppyWeb/icon.png"
+//
+//   [vrPointer]
+//   injectDom = true
+//
+//   [chaos]
+//   enabled = true
+//   disconnectChance = 0.001
+//
+// authToken (top level, like logLevel) is the secret the backend has to prove
+// it knows before the pipes are used; see auth.rs.
+use crate::{
+    adaptive::AdaptiveConfig,
+    backend::BackendConfig,
+    backoff::ReconnectPolicy,
+    audio::AudioConfig,
+    chaos::ChaosConfig,
+    color::ColorOptions,
+    coalesce::DEFAULT_TRANSFORM_RATE_HZ,
+    compression::{self, Compression, DEFAULT_ZSTD_LEVEL},
+    dashboard::DashboardConfig,
+    delta::DEFAULT_KEYFRAME_INTERVAL,
+    device_status::DeviceStatusConfig,
+    foveation::FoveationConfig,
+    frame_queue::{BackpressurePolicy, DEFAULT_QUEUE_DEPTH},
+    gaze::GazeConfig,
+    heartbeat::HeartbeatConfig,
+    metrics_http::MetricsEndpointConfig,
+    vr_pointer::VrPointerConfig,
+    transport::{PipeOptions, AUDIO_PIPE_PATH, FILES_PIPE_PATH, FRAME_PIPE_PATH, INPUT_PIPE_PATH, MICROPHONE_PIPE_PATH, POINTER_PIPE_PATH, TRANSFORM_PIPE_PATH, VIDEO_PIPE_PATH},
+    ndi::NdiConfig,
+    openvr::OpenVrConfig,
+    openxr::OpenXrConfig,
+    osc::OscConfig,
+    pipe_security::PipeSecurity,
+    rate_limit::CommandLimits,
+    webrtc::WebRtcConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io, path::Path, time::Duration};
+
+pub const CONFIG_FILE_NAME: &str = "puppyweb.toml";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(level: &str) -> Result<Self, ()> {
+        [Self::Error, Self::Warn, Self::Info, Self::Debug, Self::Trace].into_iter().find(|known| known.as_str() == level).ok_or(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PipePaths {
+    pub frame: String,
+    pub transform: String,
+    pub input: String,
+    pub audio: String,
+    pub microphone: String,
+    pub video: String,
+    pub files: String,
+    pub pointer: String,
+}
+
+impl Default for PipePaths {
+    fn default() -> Self {
+        Self {
+            frame: FRAME_PIPE_PATH.to_string(),
+            transform: TRANSFORM_PIPE_PATH.to_string(),
+            input: INPUT_PIPE_PATH.to_string(),
+            audio: AUDIO_PIPE_PATH.to_string(),
+            microphone: MICROPHONE_PIPE_PATH.to_string(),
+            video: VIDEO_PIPE_PATH.to_string(),
+            files: FILES_PIPE_PATH.to_string(),
+            pointer: POINTER_PIPE_PATH.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StreamConfig {
+    pub queue_depth: usize,
+    pub backpressure: BackpressurePolicy,
+    pub compression: Compression,
+    // Largest frame send_frame_data accepts, in bytes of pixel data
+    pub max_frame_bytes: usize,
+    // Send frames XORed against the previous one when the backend accepts it (delta.rs)
+    pub delta: bool,
+    // Frames between whole frames while delta frames are on
+    pub keyframe_interval: u32,
+    // Also publish frames as this Spout sender (Windows, see spout.rs); empty for none
+    pub spout_sender: String,
+    // Frames per overlay and second sent on at most, newest first (see frame_rate.rs); 0 for no limit
+    pub target_fps: u32,
+    // Unacknowledged frames allowed on the pipe when the backend acks them (see ack.rs); 0 for no limit
+    pub ack_window: u32,
+    // zstd compression level; higher is smaller but slower
+    pub zstd_level: i32,
+    // Threads compressing tiles of one frame in parallel (see compression.rs); 0 for one per core
+    pub compression_workers: usize,
+    // Gamma, alpha and row order of frames sent (see color.rs); left out follows the backend
+    pub color: ColorOptions,
+}
+
+// 4096x4096 RGBA
+pub const DEFAULT_MAX_FRAME_BYTES: usize = 4096 * 4096 * 4;
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+            backpressure: BackpressurePolicy::default(),
+            compression: Compression::default(),
+            max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+            delta: false,
+            keyframe_interval: DEFAULT_KEYFRAME_INTERVAL,
+            spout_sender: String::new(),
+            target_fps: 0,
+            ack_window: 0,
+            zstd_level: DEFAULT_ZSTD_LEVEL,
+            compression_workers: 0,
+            color: ColorOptions::default(),
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let levels = compression::zstd_levels();
+        if !levels.contains(&self.zstd_level) {
+            return Err(format!("zstdLevel {} is outside {}..={}", self.zstd_level, levels.start(), levels.end()));
+        }
+        if self.compression_workers > compression::MAX_WORKERS {
+            return Err(format!("compressionWorkers must be at most {}", compression::MAX_WORKERS));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransformConfig {
+    // Max transform-update events per second per device (see coalesce.rs); 0 emits every pose
+    pub rate_hz: u32,
+}
+
+impl Default for TransformConfig {
+    fn default() -> Self {
+        Self { rate_hz: DEFAULT_TRANSFORM_RATE_HZ }
+    }
+}
+
+// Limits on single pipe operations. An operation that takes longer counts as a
+// disconnect (and emits pipe-timeout), so a hung backend can't stall the writer
+// while it holds the pipe. 0 waits forever.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeoutConfig {
+    // Writing one frame to the frame pipe
+    pub write_ms: u64,
+    // Waiting for the next message on the transform pipe. Off by default: the
+    // backend may have nothing to send for a while, and heartbeats catch dead pipes.
+    pub read_ms: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self { write_ms: 2000, read_ms: 0 }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn write(&self) -> Option<Duration> {
+        (self.write_ms > 0).then(|| Duration::from_millis(self.write_ms))
+    }
+
+    pub fn read(&self) -> Option<Duration> {
+        (self.read_ms > 0).then(|| Duration::from_millis(self.read_ms))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppConfig {
+    pub log_level: LogLevel,
+    // Per-module overrides of log_level, keyed by module name (see logging.rs)
+    pub log_modules: BTreeMap<String, LogLevel>,
+    pub pipes: PipePaths,
+    // Pipe buffer sizes and mode (see transport.rs)
+    pub pipe_options: PipeOptions,
+    // Accounts allowed at the other end of the local pipes (see pipe_security.rs)
+    pub pipe_security: PipeSecurity,
+    // Payload cap and per-command call rates (see rate_limit.rs)
+    pub limits: CommandLimits,
+    pub stream: StreamConfig,
+    pub transforms: TransformConfig,
+    // Rate of gaze-update events (see gaze.rs)
+    pub gaze: GazeConfig,
+    // Low battery warnings from device status reports (see device_status.rs)
+    pub device_status: DeviceStatusConfig,
+    pub timeouts: TimeoutConfig,
+    // Stepping quality down when the pipe falls behind (see adaptive.rs)
+    pub adaptive: AdaptiveConfig,
+    // Averaging the frame's periphery around the gaze point (see foveation.rs)
+    pub foveation: FoveationConfig,
+    pub reconnect: ReconnectPolicy,
+    pub heartbeat: HeartbeatConfig,
+    pub backend: BackendConfig,
+    // Publishing the overlay to a WebRTC peer (see webrtc.rs)
+    pub webrtc: WebRtcConfig,
+    // Mirroring poses and input to OSC (see osc.rs)
+    pub osc: OscConfig,
+    // Publishing frames as an NDI source (see ndi.rs)
+    pub ndi: NdiConfig,
+    // Showing the overlay in SteamVR without a backend, with the "openvr" cargo feature (see openvr.rs)
+    pub openvr: OpenVrConfig,
+    // Showing the overlay as an OpenXR overlay session, with the "openxr" cargo feature (see openxr.rs)
+    pub openxr: OpenXrConfig,
+    // Showing the surface as a SteamVR dashboard tab (see dashboard.rs)
+    pub dashboard: DashboardConfig,
+    // Prometheus metrics over HTTP on localhost (see metrics_http.rs)
+    pub metrics_endpoint: MetricsEndpointConfig,
+    // Queue and latency limits of the audio and microphone pipes (see audio.rs)
+    pub audio: AudioConfig,
+    // Replaying VR pointer events into the page (see vr_pointer.rs)
+    pub vr_pointer: VrPointerConfig,
+    // Injected latency, disconnects and corrupt data, debug builds only (see chaos.rs)
+    pub chaos: ChaosConfig,
+    // Serve the pipes from a built-in fake backend (also --mock-backend on the command line, see mock_backend.rs)
+    pub mock_backend: bool,
+    // Don't show the connection status icon in the system tray (see tray.rs)
+    pub hide_tray: bool,
+    // Allow more than one running copy of the app (see single_instance.rs)
+    pub multi_instance: bool,
+    // Shared secret for the pipe handshake; a random one is generated per session if unset (see auth.rs)
+    pub auth_token: Option<String>,
+}
+
+impl AppConfig {
+    // Parse the file at `path`; a file that doesn't exist yields the defaults
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    // Re-read the file, apply `change` and write it back, so settings changed at
+    // runtime survive a restart. Comments in the file are not preserved.
+    pub fn update(path: &Path, change: impl FnOnce(&mut Self)) -> io::Result<()> {
+        let mut config = Self::load(path)?;
+        change(&mut config);
+        let text = toml::to_string_pretty(&config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+}
@@ -0,0 +1,262 @@
+// --- Config hot reload ---
+// puppyweb.toml (or the --config file) is checked for changes once a second
+// by its modification time and size. A changed file is parsed again (with
+// the command line overrides re-applied, see cli.rs) and whatever can change
+// while running is applied right away:
+//
+//   logLevel, [logModules]            new log filter
+//   [transforms]                      transform-update rate
+//   [gaze]                            gaze-update rate
+//   [deviceStatus]                    low battery threshold
+//   [foveation]                       foveated frames
+//   [stream]                          backpressure, queue depth, compression (and its
+//                                     level and workers),
+//                                     delta frames, keyframe interval, Spout sender,
+//                                     target FPS, ack window, color conversion
+//   [reconnect]                       policy for the next connection attempt
+//   [audio]                           audio and microphone queue depth and latency limit
+//   [vrPointer]                       DOM injection of VR pointer events
+//   [pipes], [pipeOptions]            every pipe is disconnected and reconnected
+//
+// Anything else that changed only takes effect after a restart. Either way a
+// "config-reloaded" event lists what was applied, what was rejected as invalid
+// and what wasn't applied. A file that
+// doesn't parse is reported and ignored until it's fixed. Writes by
+// set_pipe_paths and configure_pipe come back through here too, but their
+// values are already in effect, so they don't reconnect a second time.
+use crate::{
+    cli::CliArgs,
+    config::AppConfig,
+    logging::Logging,
+    supervisor::TaskSupervisor,
+    FramePipeState, StreamOptions,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
+};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Top-level keys handled by apply(); changes to any other key need a restart
+const RELOADABLE_KEYS: &[&str] = &["logLevel", "logModules", "transforms", "gaze", "deviceStatus", "foveation", "stream", "reconnect", "audio", "vrPointer", "pipes", "pipeOptions"];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigReloadedPayload {
+    pub path: String,
+    // Top-level keys whose new values are in effect
+    pub applied: Vec<String>,
+    // Reloadable keys whose new values didn't validate; the old ones stay in effect
+    pub rejected: Vec<String>,
+    // Top-level keys that changed but only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
+// Watch `path` until the app exits; `config` is what's in effect now
+pub fn spawn(supervisor: &TaskSupervisor, app_handle: AppHandle, path: PathBuf, config: AppConfig) {
+    // What's in effect survives a restart of the task, so nothing is applied twice
+    let config = Arc::new(Mutex::new(config));
+    supervisor.spawn("config-watch", move || {
+        let (app_handle, path, config) = (app_handle.clone(), path.clone(), Arc::clone(&config));
+        async move {
+            let mut stamp = file_stamp(&path);
+            let mut ticker = interval(POLL_INTERVAL);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let new_stamp = file_stamp(&path);
+                if new_stamp == stamp {
+                    continue;
+                }
+                stamp = new_stamp;
+                let mut new_config = match AppConfig::load(&path) {
+                    Ok(new_config) => new_config,
+                    Err(e) => {
+                        warn!("[Rust Config] Not reloading {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                if let Some(args) = app_handle.try_state::<CliArgs>() {
+                    args.apply(&mut new_config);
+                }
+                let current = config.lock().clone();
+                let Some(state) = app_handle.try_state::<FramePipeState>().map(|state| state.inner().clone()) else {
+                    return;
+                };
+                let (mut applied, restart_required) = changed_keys(&current, &new_config);
+                if applied.is_empty() && restart_required.is_empty() {
+                    continue;
+                }
+                let rejected: Vec<String> = apply(&app_handle, &state, &current, &new_config).await.into_iter().map(String::from).collect();
+                applied.retain(|key| !rejected.contains(key));
+                info!(
+                    "[Rust Config] Reloaded {} (applied {:?}, rejected {:?}, restart required for {:?}).",
+                    path.display(),
+                    applied,
+                    rejected,
+                    restart_required
+                );
+                let payload = ConfigReloadedPayload { path: path.display().to_string(), applied, rejected, restart_required };
+                if let Err(e) = app_handle.emit("config-reloaded", payload) {
+                    error!("[Rust Config] Error emitting config-reloaded event: {}", e);
+                }
+                *config.lock() = new_config;
+            }
+        }
+    });
+}
+
+// Returns the top-level keys whose new values were rejected
+async fn apply(app_handle: &AppHandle, state: &FramePipeState, old: &AppConfig, new: &AppConfig) -> Vec<&'static str> {
+    let mut rejected = Vec::new();
+    if new.log_level != old.log_level || new.log_modules != old.log_modules {
+        if let Some(logging) = app_handle.try_state::<Logging>() {
+            if let Err(e) = logging.configure(new.log_level, &new.log_modules) {
+                warn!("[Rust Config] Ignoring log levels: {}", e);
+                rejected.extend(["logLevel", "logModules"]);
+            }
+        }
+    }
+    if new.transforms != old.transforms {
+        info!("[Rust Transform Pipe] Transform rate set to {} Hz.", new.transforms.rate_hz);
+        state.transforms.set_rate_hz(new.transforms.rate_hz);
+    }
+    if new.gaze != old.gaze {
+        match new.gaze.validate() {
+            Ok(()) => {
+                info!("[Rust Transform Pipe] Gaze rate set to {} Hz.", new.gaze.rate_hz);
+                state.gaze.set_rate_hz(new.gaze.rate_hz);
+            }
+            Err(e) => {
+                warn!("[Rust Config] Ignoring gaze settings: {}", e);
+                rejected.push("gaze");
+            }
+        }
+    }
+    if new.device_status != old.device_status {
+        match new.device_status.validate() {
+            Ok(()) => {
+                info!("[Rust Transform Pipe] Low battery warning set to {}%.", new.device_status.low_battery_percent);
+                state.device_status.set_config(new.device_status);
+            }
+            Err(e) => {
+                warn!("[Rust Config] Ignoring device status settings: {}", e);
+                rejected.push("deviceStatus");
+            }
+        }
+    }
+    if new.foveation != old.foveation {
+        match new.foveation.validate() {
+            Ok(()) => {
+                info!("[Rust Frame Pipe] Foveation set to {:?}.", new.foveation);
+                state.foveation.set_config(new.foveation);
+            }
+            Err(e) => {
+                warn!("[Rust Config] Ignoring foveation settings: {}", e);
+                rejected.push("foveation");
+            }
+        }
+    }
+    let (old_stream, new_stream) = (&old.stream, &new.stream);
+    let options = StreamOptions {
+        backpressure: changed(old_stream.backpressure, new_stream.backpressure),
+        queue_depth: changed(old_stream.queue_depth, new_stream.queue_depth),
+        compression: changed(old_stream.compression, new_stream.compression),
+        delta: changed(old_stream.delta, new_stream.delta),
+        keyframe_interval: changed(old_stream.keyframe_interval, new_stream.keyframe_interval),
+        spout_sender: changed(&old_stream.spout_sender, &new_stream.spout_sender).cloned(),
+        target_fps: changed(old_stream.target_fps, new_stream.target_fps),
+        ack_window: changed(old_stream.ack_window, new_stream.ack_window),
+        zstd_level: changed(old_stream.zstd_level, new_stream.zstd_level),
+        compression_workers: changed(old_stream.compression_workers, new_stream.compression_workers),
+        color: changed(old_stream.color, new_stream.color),
+        ..StreamOptions::default()
+    };
+    if let Err(e) = state.configure_stream(options) {
+        warn!("[Rust Config] Ignoring stream settings: {}", e);
+        rejected.push("stream");
+    }
+    if new.reconnect != old.reconnect {
+        info!("[Rust Connection] Reconnect policy set to {:?}.", new.reconnect);
+        state.connections.set_reconnect_policy(new.reconnect);
+    }
+    if new.audio != old.audio {
+        match new.audio.validate() {
+            Ok(()) => {
+                info!("[Rust Audio Pipe] Audio queue set to {:?}.", new.audio);
+                state.audio.configure(new.audio);
+                state.microphone_queue.configure(new.audio);
+            }
+            Err(e) => {
+                warn!("[Rust Config] Ignoring audio settings: {}", e);
+                rejected.push("audio");
+            }
+        }
+    }
+    if new.vr_pointer != old.vr_pointer {
+        info!("[Rust Pointer Pipe] DOM injection {}.", if new.vr_pointer.inject_dom { "on" } else { "off" });
+        state.inject_pointer_dom.store(new.vr_pointer.inject_dom, Ordering::Relaxed);
+    }
+    // Compared with what's in use, which set_pipe_paths and configure_pipe may have changed since the last load
+    let paths_changed = new.pipes != *state.pipe_paths.lock();
+    let options_changed = new.pipe_options != *state.pipe_options.lock();
+    if options_changed {
+        match new.pipe_options.validate() {
+            Ok(()) => {
+                state.disconnect().await;
+                *state.pipe_options.lock() = new.pipe_options;
+                info!("[Rust Connection] Pipe options set to {:?}, reconnecting.", new.pipe_options);
+                if !paths_changed {
+                    state.connect();
+                }
+            }
+            Err(e) => {
+                warn!("[Rust Config] Ignoring pipe options: {}", e);
+                rejected.push("pipeOptions");
+            }
+        }
+    }
+    if paths_changed {
+        state.set_pipe_paths(new.pipes.clone()).await;
+    }
+    rejected
+}
+
+fn changed<T: PartialEq>(old: T, new: T) -> Option<T> {
+    (old != new).then_some(new)
+}
+
+// Top-level keys that differ between the two configs, split into reloadable and not
+fn changed_keys(old: &AppConfig, new: &AppConfig) -> (Vec<String>, Vec<String>) {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut applied = Vec::new();
+    let mut restart_required = Vec::new();
+    for (key, value) in &new {
+        if old.get(key) == Some(value) {
+            continue;
+        }
+        if RELOADABLE_KEYS.contains(&key.as_str()) {
+            applied.push(key.clone());
+        } else {
+            restart_required.push(key.clone());
+        }
+    }
+    (applied, restart_required)
+}
+
+// Changes when the file is written; None while it doesn't exist
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
@@ -0,0 +1,377 @@
+// --- Connection tracking ---
+// Single source of truth for whether the frame, transform, input, audio, microphone, video, files and pointer pipes are up.
+// Each pipe moves through an explicit state machine driven by the connection
+// loops: Disconnected -> Connecting -> Handshaking -> Connected, back to
+// Disconnected when the connection is lost, and into Backoff between failed
+// attempts (Disconnected once the retries run out). Every transition emits
+// "pipe-state-changed" with the new and previous state. It also emits pipe-connected /
+// pipe-disconnected / pipe-connection-failed / pipe-timeout events, and
+// get_connection_status reads from it. It also carries the reconnect policy
+// all loops use. Frame pipes created through the PipeManager get their own
+// named tracker, whose events carry the connection name. Anything else that
+// mirrors the connection state (the tray) can subscribe() to be woken on every
+// change.
+use crate::{backoff::ReconnectPolicy, config::PipePaths, supervisor::TaskHealth, transport::ConnectFailure};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+use tracing::error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeKind {
+    Frame,
+    Transform,
+    Input,
+    Audio,
+    Microphone,
+    Video,
+    Files,
+    Pointer,
+}
+
+// Where a pipe is in its connection lifecycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum PipeState {
+    // No connection and no loop trying to make one
+    Disconnected,
+    // Opening the pipe; `attempt` counts from 1 since the last successful connect
+    Connecting { attempt: u32 },
+    // Pipe open, checking the peer and authenticating (and on the frame pipe, the capability handshake)
+    Handshaking,
+    Connected {
+        // Milliseconds since the Unix epoch
+        #[serde(rename = "sinceMs")]
+        since_ms: u64,
+    },
+    // Waiting before the next attempt
+    Backoff {
+        // Milliseconds since the Unix epoch
+        #[serde(rename = "untilMs")]
+        until_ms: u64,
+    },
+}
+
+// Status of one pipe as returned by get_connection_status
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipeStatus {
+    pub path: String,
+    pub state: PipeState,
+    // Same as state being Connected
+    pub connected: bool,
+    // Milliseconds since the Unix epoch of the last connect, if currently connected
+    pub connected_since_ms: Option<u64>,
+    // Failed connect attempts since the last successful connect
+    pub failed_attempts: u32,
+    pub last_error: Option<String>,
+    // Kind of the last failed connect attempt, cleared on connect
+    pub last_failure: Option<ConnectFailure>,
+    // The connection loop exhausted its retries and stopped
+    pub gave_up: bool,
+    // Successful connects since startup; all but the first are reconnects
+    pub connects: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionStatus {
+    pub frame: PipeStatus,
+    pub transform: PipeStatus,
+    pub input: PipeStatus,
+    pub audio: PipeStatus,
+    pub microphone: PipeStatus,
+    pub video: PipeStatus,
+    pub files: PipeStatus,
+    pub pointer: PipeStatus,
+    // Background tasks by name, filled in by get_connection_status (see supervisor.rs)
+    pub tasks: BTreeMap<String, TaskHealth>,
+}
+
+#[derive(Clone, Serialize)]
+struct PipeConnectedPayload {
+    pipe: PipeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>,
+    path: String,
+}
+
+#[derive(Clone, Serialize)]
+struct PipeStateChangedPayload {
+    pipe: PipeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>,
+    state: PipeState,
+    previous: PipeState,
+}
+
+#[derive(Clone, Serialize)]
+struct PipeDisconnectedPayload {
+    pipe: PipeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>,
+    reason: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PipeConnectionFailedPayload {
+    pipe: PipeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>,
+    attempts: u32,
+    last_error: Option<String>,
+    last_failure: Option<ConnectFailure>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PipeTimeoutPayload {
+    pipe: PipeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>,
+    operation: PipeOperation,
+    timeout_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeOperation {
+    Read,
+    Write,
+}
+
+#[derive(Clone)]
+pub struct ConnectionTracker {
+    status: Arc<Mutex<ConnectionStatus>>,
+    reconnect_policy: Arc<Mutex<ReconnectPolicy>>,
+    app_handle: AppHandle,
+    // Set for the trackers of named connections (None for the main pipes)
+    name: Option<String>,
+    // Bumped after every status change
+    changes: Arc<watch::Sender<u64>>,
+}
+
+impl PipeStatus {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            state: PipeState::Disconnected,
+            connected: false,
+            connected_since_ms: None,
+            failed_attempts: 0,
+            last_error: None,
+            last_failure: None,
+            gave_up: false,
+            connects: 0,
+        }
+    }
+}
+
+impl ConnectionTracker {
+    pub fn new(app_handle: AppHandle, paths: &PipePaths) -> Self {
+        Self {
+            status: Arc::new(Mutex::new(ConnectionStatus {
+                frame: PipeStatus::new(&paths.frame),
+                transform: PipeStatus::new(&paths.transform),
+                input: PipeStatus::new(&paths.input),
+                audio: PipeStatus::new(&paths.audio),
+                microphone: PipeStatus::new(&paths.microphone),
+                video: PipeStatus::new(&paths.video),
+                files: PipeStatus::new(&paths.files),
+                pointer: PipeStatus::new(&paths.pointer),
+                tasks: BTreeMap::new(),
+            })),
+            reconnect_policy: Arc::new(Mutex::new(ReconnectPolicy::default())),
+            app_handle,
+            name: None,
+            changes: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    // Tracker for a named connection that only has a frame pipe
+    pub fn named(app_handle: AppHandle, name: &str, frame_path: &str) -> Self {
+        let paths = PipePaths {
+            frame: frame_path.to_string(),
+            transform: String::new(),
+            input: String::new(),
+            audio: String::new(),
+            microphone: String::new(),
+            video: String::new(),
+            files: String::new(),
+            pointer: String::new(),
+        };
+        Self { name: Some(name.to_string()), ..Self::new(app_handle, &paths) }
+    }
+
+    // Name of the connection for trackers made with named()
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn snapshot(&self) -> ConnectionStatus {
+        self.status.lock().clone()
+    }
+
+    // snapshot, unless the status is locked right now (e.g. from a panic hook, by the panicking thread)
+    pub fn try_snapshot(&self) -> Option<ConnectionStatus> {
+        self.status.try_lock().map(|status| status.clone())
+    }
+
+    pub fn is_connected(&self, pipe: PipeKind) -> bool {
+        let status = self.status.lock();
+        match pipe {
+            PipeKind::Frame => status.frame.connected,
+            PipeKind::Transform => status.transform.connected,
+            PipeKind::Input => status.input.connected,
+            PipeKind::Audio => status.audio.connected,
+            PipeKind::Microphone => status.microphone.connected,
+            PipeKind::Video => status.video.connected,
+            PipeKind::Files => status.files.connected,
+            PipeKind::Pointer => status.pointer.connected,
+        }
+    }
+
+    // Receiver that is marked changed whenever any pipe's status changes
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+
+    // Policy a connection loop should use for its next run
+    pub fn reconnect_policy(&self) -> ReconnectPolicy {
+        *self.reconnect_policy.lock()
+    }
+
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        *self.reconnect_policy.lock() = policy;
+    }
+
+    fn with_pipe<T>(&self, pipe: PipeKind, f: impl FnOnce(&mut PipeStatus) -> T) -> T {
+        let mut status = self.status.lock();
+        let pipe_status = match pipe {
+            PipeKind::Frame => &mut status.frame,
+            PipeKind::Transform => &mut status.transform,
+            PipeKind::Input => &mut status.input,
+            PipeKind::Audio => &mut status.audio,
+            PipeKind::Microphone => &mut status.microphone,
+            PipeKind::Video => &mut status.video,
+            PipeKind::Files => &mut status.files,
+            PipeKind::Pointer => &mut status.pointer,
+        };
+        let previous = pipe_status.state;
+        let result = f(pipe_status);
+        let state = pipe_status.state;
+        drop(status);
+        self.changes.send_modify(|version| *version = version.wrapping_add(1));
+        if state != previous {
+            let payload = PipeStateChangedPayload { pipe, connection: self.name.clone(), state, previous };
+            if let Err(e) = self.app_handle.emit("pipe-state-changed", payload) {
+                error!("[Rust Connection] Error emitting pipe-state-changed event: {}", e);
+            }
+        }
+        result
+    }
+
+    // Called when the pipe is pointed somewhere else (set_pipe_paths)
+    pub fn set_path(&self, pipe: PipeKind, path: &str) {
+        self.with_pipe(pipe, |status| status.path = path.to_string());
+    }
+
+    // Called by a connection loop before each attempt to open the pipe
+    pub fn mark_connecting(&self, pipe: PipeKind) {
+        self.with_pipe(pipe, |status| status.state = PipeState::Connecting { attempt: status.failed_attempts.saturating_add(1) });
+    }
+
+    // Called once the pipe is open, before the peer check, authentication and handshake
+    pub fn mark_handshaking(&self, pipe: PipeKind) {
+        self.with_pipe(pipe, |status| status.state = PipeState::Handshaking);
+    }
+
+    // Called after a failed attempt when the loop is about to wait `delay` before the next one
+    pub fn mark_backoff(&self, pipe: PipeKind, delay: Duration) {
+        let until_ms = now_ms().saturating_add(delay.as_millis() as u64);
+        self.with_pipe(pipe, |status| status.state = PipeState::Backoff { until_ms });
+    }
+
+    // Called by a connection loop once the pipe is open and the handshake is done
+    pub fn mark_connected(&self, pipe: PipeKind) {
+        let path = self.with_pipe(pipe, |status| {
+            let since_ms = now_ms();
+            status.state = PipeState::Connected { since_ms };
+            status.connected = true;
+            status.connected_since_ms = Some(since_ms);
+            status.failed_attempts = 0;
+            status.last_error = None;
+            status.last_failure = None;
+            status.gave_up = false;
+            status.connects += 1;
+            status.path.clone()
+        });
+        if let Err(e) = self.app_handle.emit("pipe-connected", PipeConnectedPayload { pipe, connection: self.name.clone(), path }) {
+            error!("[Rust Connection] Error emitting pipe-connected event: {}", e);
+        }
+    }
+
+    // Called when a connection is lost or torn down; only emits pipe-disconnected if we thought we were connected
+    pub fn mark_disconnected(&self, pipe: PipeKind, reason: impl Into<String>) {
+        let reason = reason.into();
+        let was_connected = self.with_pipe(pipe, |status| {
+            let was_connected = status.connected;
+            status.state = PipeState::Disconnected;
+            status.connected = false;
+            status.connected_since_ms = None;
+            status.last_error = Some(reason.clone());
+            was_connected
+        });
+        if was_connected {
+            if let Err(e) = self.app_handle.emit("pipe-disconnected", PipeDisconnectedPayload { pipe, connection: self.name.clone(), reason }) {
+                error!("[Rust Connection] Error emitting pipe-disconnected event: {}", e);
+            }
+        }
+    }
+
+    // Called when a read or write took longer than its timeout; the caller then disconnects
+    pub fn record_timeout(&self, pipe: PipeKind, operation: PipeOperation, timeout: Duration) {
+        let payload = PipeTimeoutPayload { pipe, connection: self.name.clone(), operation, timeout_ms: timeout.as_millis() as u64 };
+        if let Err(e) = self.app_handle.emit("pipe-timeout", payload) {
+            error!("[Rust Connection] Error emitting pipe-timeout event: {}", e);
+        }
+    }
+
+    // Called for every failed connect attempt
+    pub fn record_failure(&self, pipe: PipeKind, failure: ConnectFailure, error: impl Into<String>) {
+        let error = error.into();
+        self.with_pipe(pipe, |status| {
+            status.failed_attempts = status.failed_attempts.saturating_add(1);
+            status.last_error = Some(error);
+            status.last_failure = Some(failure);
+            status.gave_up = false;
+        });
+    }
+
+    // Called when a connection loop runs out of retries and stops for good
+    pub fn mark_gave_up(&self, pipe: PipeKind) {
+        let (attempts, last_error, last_failure) = self.with_pipe(pipe, |status| {
+            status.state = PipeState::Disconnected;
+            status.gave_up = true;
+            (status.failed_attempts, status.last_error.clone(), status.last_failure)
+        });
+        let payload = PipeConnectionFailedPayload { pipe, connection: self.name.clone(), attempts, last_error, last_failure };
+        if let Err(e) = self.app_handle.emit("pipe-connection-failed", payload) {
+            error!("[Rust Connection] Error emitting pipe-connection-failed event: {}", e);
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,182 @@
+// --- Backend control messages ---
+// Besides Pongs, the backend can tell the app about things that happen on its
+// side ("stop sending frames for now", "the headset runs at a new
+// resolution", "the user hid an overlay") with Control messages on the frame
+// pipe. Each one becomes a "backend-control" event for the frontend. Only
+// backends that acknowledged CAP_CONTROL in the handshake send them, and only
+// those get the app's own: pause and resume, when pause_stream and
+// resume_stream stop and restart the frames, and the overlay placement set
+// with set_overlay_transform and friends (see placement.rs) and the dashboard
+// mode (see dashboard.rs).
+//
+// Control payload:
+//   [0..2)  control kind (u16 LE)
+//   [2..)   kind specific body:
+//     1 pause               (empty) the backend isn't showing frames, the app may stop sending
+//     2 resume              (empty) frames are wanted again
+//     3 resolution-changed  [0..4) width, [4..8) height (u32 LE), size frames should be rendered at
+//     4 overlay-hidden      [0..4) overlay id (u32 LE)
+//     5 overlay-shown       [0..4) overlay id (u32 LE)
+//     6 set-overlay-transform  [0..4) overlay id (u32 LE), [4..68) placement matrix, 16 f32 LE, row-major
+//     7 set-overlay-width      [0..4) overlay id (u32 LE), [4..8) width in meters (f32 LE)
+//     8 set-overlay-curvature  [0..4) overlay id (u32 LE), [4..8) curvature, 0 flat to 1 a full circle (f32 LE)
+//     9 set-overlay-alpha      [0..4) overlay id (u32 LE), [4..8) opacity 0..1 (f32 LE)
+//    10 set-overlay-attachment [0..4) overlay id (u32 LE), [4..8) device id the transform is relative to,
+//                              0xFFFFFFFF for none (u32 LE)
+//    11 set-dashboard-mode     [0] 1 to show the surface in the SteamVR dashboard, 0 to stop (u8),
+//                              [1..) icon path (UTF-8, empty for none)
+//    12 dashboard-shown        (empty) the user opened the surface's dashboard tab
+//    13 dashboard-hidden       (empty) the user left it
+//
+// Kinds this version doesn't know are passed on with their raw body, so a
+// newer backend can talk to a newer frontend without the app in between
+// having to understand it.
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+
+const KIND_SIZE: usize = 2;
+const KIND_PAUSE: u16 = 1;
+const KIND_RESUME: u16 = 2;
+const KIND_RESOLUTION_CHANGED: u16 = 3;
+const KIND_OVERLAY_HIDDEN: u16 = 4;
+const KIND_OVERLAY_SHOWN: u16 = 5;
+const KIND_SET_OVERLAY_TRANSFORM: u16 = 6;
+const KIND_SET_OVERLAY_WIDTH: u16 = 7;
+const KIND_SET_OVERLAY_CURVATURE: u16 = 8;
+const KIND_SET_OVERLAY_ALPHA: u16 = 9;
+const KIND_SET_OVERLAY_ATTACHMENT: u16 = 10;
+const KIND_SET_DASHBOARD_MODE: u16 = 11;
+const KIND_DASHBOARD_SHOWN: u16 = 12;
+const KIND_DASHBOARD_HIDDEN: u16 = 13;
+const NO_DEVICE: u32 = u32::MAX;
+
+// Payload of "backend-control"
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ControlMessage {
+    Pause,
+    Resume,
+    ResolutionChanged { width: u32, height: u32 },
+    OverlayHidden {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+    },
+    OverlayShown {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+    },
+    SetOverlayTransform {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+        matrix: [f32; 16],
+    },
+    SetOverlayWidth {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+        #[serde(rename = "widthMeters")]
+        width_meters: f32,
+    },
+    SetOverlayCurvature {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+        curvature: f32,
+    },
+    SetOverlayAlpha {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+        alpha: f32,
+    },
+    SetOverlayAttachment {
+        #[serde(rename = "overlayId")]
+        overlay_id: u32,
+        #[serde(rename = "deviceId")]
+        device_id: Option<u32>,
+    },
+    SetDashboardMode {
+        enabled: bool,
+        #[serde(rename = "iconPath")]
+        icon_path: Option<String>,
+    },
+    DashboardShown,
+    DashboardHidden,
+    // A kind this version doesn't know
+    Unknown { code: u16, body: Vec<u8> },
+}
+
+pub fn encode(message: &ControlMessage) -> Vec<u8> {
+    let (kind, body) = match message {
+        ControlMessage::Pause => (KIND_PAUSE, Vec::new()),
+        ControlMessage::Resume => (KIND_RESUME, Vec::new()),
+        ControlMessage::ResolutionChanged { width, height } => {
+            (KIND_RESOLUTION_CHANGED, [width.to_le_bytes(), height.to_le_bytes()].concat())
+        }
+        ControlMessage::OverlayHidden { overlay_id } => (KIND_OVERLAY_HIDDEN, overlay_id.to_le_bytes().to_vec()),
+        ControlMessage::OverlayShown { overlay_id } => (KIND_OVERLAY_SHOWN, overlay_id.to_le_bytes().to_vec()),
+        ControlMessage::SetOverlayTransform { overlay_id, matrix } => {
+            let mut body = overlay_id.to_le_bytes().to_vec();
+            matrix.iter().for_each(|value| body.extend_from_slice(&value.to_le_bytes()));
+            (KIND_SET_OVERLAY_TRANSFORM, body)
+        }
+        ControlMessage::SetOverlayWidth { overlay_id, width_meters } => {
+            (KIND_SET_OVERLAY_WIDTH, [overlay_id.to_le_bytes(), width_meters.to_le_bytes()].concat())
+        }
+        ControlMessage::SetOverlayCurvature { overlay_id, curvature } => {
+            (KIND_SET_OVERLAY_CURVATURE, [overlay_id.to_le_bytes(), curvature.to_le_bytes()].concat())
+        }
+        ControlMessage::SetOverlayAlpha { overlay_id, alpha } => {
+            (KIND_SET_OVERLAY_ALPHA, [overlay_id.to_le_bytes(), alpha.to_le_bytes()].concat())
+        }
+        ControlMessage::SetOverlayAttachment { overlay_id, device_id } => {
+            (KIND_SET_OVERLAY_ATTACHMENT, [overlay_id.to_le_bytes(), device_id.unwrap_or(NO_DEVICE).to_le_bytes()].concat())
+        }
+        ControlMessage::SetDashboardMode { enabled, icon_path } => {
+            (KIND_SET_DASHBOARD_MODE, [&[*enabled as u8][..], icon_path.as_deref().unwrap_or_default().as_bytes()].concat())
+        }
+        ControlMessage::DashboardShown => (KIND_DASHBOARD_SHOWN, Vec::new()),
+        ControlMessage::DashboardHidden => (KIND_DASHBOARD_HIDDEN, Vec::new()),
+        ControlMessage::Unknown { code, body } => (*code, body.clone()),
+    };
+    [&kind.to_le_bytes()[..], &body].concat()
+}
+
+pub fn decode(payload: &[u8]) -> Result<ControlMessage, String> {
+    if payload.len() < KIND_SIZE {
+        return Err(format!("control message of {} bytes has no kind", payload.len()));
+    }
+    let kind = LittleEndian::read_u16(&payload[..KIND_SIZE]);
+    let body = &payload[KIND_SIZE..];
+    let u32_at = |offset: usize| {
+        body.get(offset..offset + 4)
+            .map(LittleEndian::read_u32)
+            .ok_or_else(|| format!("control kind {} needs {} body bytes, got {}", kind, offset + 4, body.len()))
+    };
+    let f32_at = |offset: usize| u32_at(offset).map(f32::from_bits);
+    Ok(match kind {
+        KIND_PAUSE => ControlMessage::Pause,
+        KIND_RESUME => ControlMessage::Resume,
+        KIND_RESOLUTION_CHANGED => ControlMessage::ResolutionChanged { width: u32_at(0)?, height: u32_at(4)? },
+        KIND_OVERLAY_HIDDEN => ControlMessage::OverlayHidden { overlay_id: u32_at(0)? },
+        KIND_OVERLAY_SHOWN => ControlMessage::OverlayShown { overlay_id: u32_at(0)? },
+        KIND_SET_OVERLAY_TRANSFORM => {
+            let mut matrix = [0.0; 16];
+            for (index, value) in matrix.iter_mut().enumerate() {
+                *value = f32_at(4 + index * 4)?;
+            }
+            ControlMessage::SetOverlayTransform { overlay_id: u32_at(0)?, matrix }
+        }
+        KIND_SET_OVERLAY_WIDTH => ControlMessage::SetOverlayWidth { overlay_id: u32_at(0)?, width_meters: f32_at(4)? },
+        KIND_SET_OVERLAY_CURVATURE => ControlMessage::SetOverlayCurvature { overlay_id: u32_at(0)?, curvature: f32_at(4)? },
+        KIND_SET_OVERLAY_ALPHA => ControlMessage::SetOverlayAlpha { overlay_id: u32_at(0)?, alpha: f32_at(4)? },
+        KIND_SET_OVERLAY_ATTACHMENT => {
+            ControlMessage::SetOverlayAttachment { overlay_id: u32_at(0)?, device_id: Some(u32_at(4)?).filter(|id| *id != NO_DEVICE) }
+        }
+        KIND_SET_DASHBOARD_MODE => {
+            let (enabled, icon_path) = body.split_first().ok_or_else(|| format!("control kind {} needs a body", kind))?;
+            let icon_path = String::from_utf8(icon_path.to_vec()).map_err(|e| format!("icon path isn't UTF-8: {}", e))?;
+            ControlMessage::SetDashboardMode { enabled: *enabled != 0, icon_path: Some(icon_path).filter(|path| !path.is_empty()) }
+        }
+        KIND_DASHBOARD_SHOWN => ControlMessage::DashboardShown,
+        KIND_DASHBOARD_HIDDEN => ControlMessage::DashboardHidden,
+        code => ControlMessage::Unknown { code, body: body.to_vec() },
+    })
+}
@@ -0,0 +1,183 @@
+// --- Panic reports ---
+// A panic used to leave nothing behind but a line on a console release builds
+// don't have. The panic hook installed at startup writes a report to the log
+// directory instead (crash-<unix seconds>-<pid>.txt): the panic message and
+// location, the thread, a backtrace and the connection state at the time.
+// That includes panics in background tasks the supervisor recovers from;
+// they're bugs all the same. Native crashes (e.g. inside the NDI or Spout
+// libraries) aren't caught.
+//
+// On the next start the reports the previous run left are read back, and
+// once the frontend has loaded each one is emitted as a "crash-report" event,
+// so the app can offer to attach it to a bug report. Reported files are
+// renamed to crash-*.reported.txt and only the newest MAX_REPORTS are kept.
+use crate::supervisor::panic_message;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    backtrace::Backtrace,
+    cmp::Reverse,
+    fmt::Write as _,
+    fs, io,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::{AppHandle, Emitter};
+use tracing::{error, info, warn};
+
+const REPORT_PREFIX: &str = "crash-";
+const PENDING_SUFFIX: &str = ".txt";
+const REPORTED_SUFFIX: &str = ".reported.txt";
+const MAX_REPORTS: usize = 10;
+// Reports are read back whole; anything longer isn't one of ours
+const MAX_REPORT_BYTES: u64 = 1024 * 1024;
+
+// Payload of "crash-report"
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub path: PathBuf,
+    // Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    // The panic message
+    pub message: String,
+    // The whole report
+    pub report: String,
+}
+
+// Reports of the previous run, until the frontend can be told about them
+pub struct PendingReports(Mutex<Vec<CrashReport>>);
+
+impl PendingReports {
+    pub fn new(reports: Vec<CrashReport>) -> Self {
+        Self(Mutex::new(reports))
+    }
+
+    // Emit "crash-report" for each report not emitted yet
+    pub fn emit(&self, app_handle: &AppHandle) {
+        for mut report in std::mem::take(&mut *self.0.lock()) {
+            info!("[Rust Crash] The previous run panicked: {} (report at {}).", report.message, report.path.display());
+            if let Err(e) = mark_reported(&mut report) {
+                warn!("[Rust Crash] Can't mark {} as reported: {}", report.path.display(), e);
+            }
+            if let Err(e) = app_handle.emit("crash-report", report) {
+                error!("[Rust Crash] Error emitting crash-report event: {}", e);
+            }
+        }
+    }
+}
+
+// Write a report for every panic to `dir`; `state` describes the connections at the time, if it can
+pub fn install(dir: PathBuf, state: impl Fn() -> Option<String> + Send + Sync + 'static) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let report = format_report(info, state());
+        match write_report(&dir, &report) {
+            Ok(path) => error!("[Rust Crash] Panic report written to {}.", path.display()),
+            Err(e) => error!("[Rust Crash] Failed to write a panic report to {}: {}", dir.display(), e),
+        }
+        previous(info);
+    }));
+}
+
+fn format_report(info: &PanicHookInfo<'_>, state: Option<String>) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "PuppyWeb {} panicked", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Message: {}", panic_message(info.payload()));
+    if let Some(location) = info.location() {
+        let _ = writeln!(report, "Location: {}", location);
+    }
+    let _ = writeln!(report, "Thread: {}", std::thread::current().name().unwrap_or("<unnamed>"));
+    let _ = writeln!(report, "OS: {} {}", std::env::consts::OS, std::env::consts::ARCH);
+    let _ = writeln!(report, "\nBacktrace:\n{}", Backtrace::force_capture());
+    // The state can't be read if the panic happened while it was locked
+    let _ = writeln!(report, "\nConnection state:\n{}", state.as_deref().unwrap_or("(unavailable)"));
+    report
+}
+
+fn write_report(dir: &Path, report: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let path = dir.join(format!("{}{}-{}{}", REPORT_PREFIX, secs, std::process::id(), PENDING_SUFFIX));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+// Reports in `dir` that haven't been emitted yet, oldest first, and prune the old ones
+pub fn pending(dir: &Path) -> Vec<CrashReport> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            warn!("[Rust Crash] Can't look for panic reports in {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut reports: Vec<(u64, PathBuf)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let secs = name.strip_prefix(REPORT_PREFIX)?.split('-').next()?.parse().ok()?;
+            name.ends_with(PENDING_SUFFIX).then_some((secs, path))
+        })
+        .collect();
+    reports.sort_by_key(|(secs, _)| Reverse(*secs));
+    for (_, path) in reports.drain(reports.len().min(MAX_REPORTS)..) {
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("[Rust Crash] Can't remove old panic report {}: {}", path.display(), e);
+        }
+    }
+    reports
+        .into_iter()
+        .rev()
+        .filter(|(_, path)| !path.to_string_lossy().ends_with(REPORTED_SUFFIX))
+        .filter_map(|(secs, path)| match read_report(&path) {
+            Ok(report) => Some(CrashReport {
+                timestamp_ms: secs * 1000,
+                message: report.lines().find_map(|line| line.strip_prefix("Message: ")).unwrap_or_default().to_string(),
+                report,
+                path,
+            }),
+            Err(e) => {
+                warn!("[Rust Crash] Can't read panic report {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+fn read_report(path: &Path) -> io::Result<String> {
+    if fs::metadata(path)?.len() > MAX_REPORT_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "too large for a panic report"));
+    }
+    fs::read_to_string(path)
+}
+
+// Mark `report` as emitted, so it isn't again on the next start
+pub fn mark_reported(report: &mut CrashReport) -> io::Result<()> {
+    let name = report.path.to_string_lossy();
+    let reported = PathBuf::from(format!("{}{}", name.strip_suffix(PENDING_SUFFIX).unwrap_or(&name), REPORTED_SUFFIX));
+    fs::rename(&report.path, &reported)?;
+    report.path = reported;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_report_is_pending_until_marked_reported() {
+        let dir = std::env::temp_dir().join(format!("puppyweb-crash-test-{}", std::process::id()));
+        write_report(&dir, "PuppyWeb panicked\nMessage: boom\n").unwrap();
+
+        let mut reports = pending(&dir);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].message, "boom");
+        mark_reported(&mut reports[0]).unwrap();
+        assert!(pending(&dir).is_empty());
+        assert!(reports[0].path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
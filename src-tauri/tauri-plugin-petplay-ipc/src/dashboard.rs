@@ -0,0 +1,102 @@
+// --- Dashboard mode ---
+// Instead of floating in the room, the surface (overlay 0) can live in the
+// SteamVR dashboard as a tab of its own, with an icon in the dashboard's
+// bar. It's switched with [dashboard] enabled in puppyweb.toml or with
+// set_dashboard_mode. The backend is asked with a set-dashboard-mode Control
+// message (see control.rs; again after every reconnect) and the in-process
+// OpenVR layer (see openvr.rs) is restarted to create a dashboard overlay
+// instead of a plain one. OpenXR has no dashboard.
+//
+// Whenever the user opens or leaves the tab, the backend sends
+// dashboard-shown or dashboard-hidden (the OpenVR layer notices it when it
+// polls poses), and "dashboard-visibility" is emitted so the UI can pause
+// animations or lay itself out for the dashboard.
+//
+//   [dashboard]
+//   enabled = true
+//   iconPath = "C:/PuppyWeb/icon.png"
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Longest icon path passed on; OpenVR's own limit on file paths
+const MAX_ICON_PATH_LEN: usize = 1024;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DashboardConfig {
+    pub enabled: bool,
+    // PNG shown in the dashboard's bar, ideally square; SteamVR shows a generic icon without one
+    pub icon_path: Option<String>,
+}
+
+impl DashboardConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(path) = &self.icon_path {
+            if path.is_empty() || path.len() > MAX_ICON_PATH_LEN || path.contains('\0') {
+                return Err(format!("iconPath must be 1 to {} bytes without NUL characters", MAX_ICON_PATH_LEN));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Payload of "dashboard-visibility"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct DashboardVisibility {
+    pub visible: bool,
+}
+
+// Where a runtime layer reports the dashboard tab opening (true) or closing (false)
+pub type DashboardEvents = Arc<dyn Fn(bool) + Send + Sync>;
+
+// The mode in effect and whether the tab is open
+#[derive(Default)]
+pub struct DashboardState {
+    config: Mutex<DashboardConfig>,
+    visible: Mutex<bool>,
+}
+
+impl DashboardState {
+    pub fn new(config: DashboardConfig) -> Self {
+        Self { config: Mutex::new(config), visible: Mutex::default() }
+    }
+
+    pub fn config(&self) -> DashboardConfig {
+        self.config.lock().clone()
+    }
+
+    // Leaving the mode forgets the tab was open
+    pub fn set_config(&self, config: DashboardConfig) {
+        if !config.enabled {
+            *self.visible.lock() = false;
+        }
+        *self.config.lock() = config;
+    }
+
+    // Returns whether it changed; reports outside dashboard mode are ignored
+    pub fn set_visible(&self, visible: bool) -> bool {
+        if !self.config.lock().enabled {
+            return false;
+        }
+        std::mem::replace(&mut *self.visible.lock(), visible) != visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visibility_is_only_tracked_in_dashboard_mode() {
+        let state = DashboardState::default();
+        assert!(!state.set_visible(true));
+        state.set_config(DashboardConfig { enabled: true, icon_path: None });
+        assert!(state.set_visible(true));
+        assert!(!state.set_visible(true));
+        state.set_config(DashboardConfig::default());
+        state.set_config(DashboardConfig { enabled: true, icon_path: None });
+        assert!(!state.set_visible(false));
+        assert!(DashboardConfig { enabled: true, icon_path: Some(String::new()) }.validate().is_err());
+    }
+}
@@ -0,0 +1,103 @@
+// --- Delta frames ---
+// Most frames of a UI overlay are nearly identical to the one before. With
+// delta encoding on (and CAP_DELTA accepted), the writer XORs each frame
+// against the previous frame of the same overlay: unchanged pixels become
+// zero bytes, which LZ4 collapses into almost nothing. Such frames carry
+// FLAG_DELTA next to the codec flag; the backend decompresses the pixels and
+// XORs them onto the last frame it has for that overlay.
+//
+// A frame goes out whole (a keyframe, no FLAG_DELTA) when
+//   - the overlay has no previous frame on this connection (first frame, reconnect)
+//   - its size, stride or pixel format changed
+//   - keyframe_interval frames have gone by since the last keyframe
+// so a backend that lost track catches up within one interval.
+use crate::{pixel_format::PixelFormat, protocol::FrameHeader};
+use std::collections::HashMap;
+
+// Message flag (protocol header) of a frame whose pixels are XORed against the previous one
+pub const FLAG_DELTA: u16 = 1 << 2;
+
+pub const DEFAULT_KEYFRAME_INTERVAL: u32 = 60;
+
+// The last frame sent for an overlay
+struct Reference {
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixel_format: PixelFormat,
+    pixels: Vec<u8>,
+    // Frames sent since (and including) the last keyframe
+    since_keyframe: u32,
+}
+
+impl Reference {
+    fn matches(&self, header: &FrameHeader, len: usize) -> bool {
+        self.width == header.width
+            && self.height == header.height
+            && self.stride == header.stride
+            && self.pixel_format == header.pixel_format
+            && self.pixels.len() == len
+    }
+}
+
+pub struct DeltaEncoder {
+    keyframe_interval: u32,
+    // Keyed by overlay id; frames of different overlays are interleaved on the pipe
+    references: HashMap<u32, Reference>,
+}
+
+impl DeltaEncoder {
+    pub fn new(keyframe_interval: u32) -> Self {
+        Self { keyframe_interval, references: HashMap::new() }
+    }
+
+    pub fn set_keyframe_interval(&mut self, keyframe_interval: u32) {
+        self.keyframe_interval = keyframe_interval;
+    }
+
+    // Forget every previous frame so the next frame of each overlay is a keyframe
+    pub fn reset(&mut self) {
+        self.references.clear();
+    }
+
+    // The frame XORed against the previous one of its overlay, or None if it has to go out as a keyframe.
+    // Either way the frame becomes the reference for the next one.
+    pub fn encode(&mut self, header: &FrameHeader, pixels: &[u8]) -> Option<Vec<u8>> {
+        let interval = self.keyframe_interval.max(1);
+        match self.references.get_mut(&header.overlay_id) {
+            Some(reference) if reference.matches(header, pixels.len()) && reference.since_keyframe < interval => {
+                reference.since_keyframe += 1;
+                let mut delta = std::mem::replace(&mut reference.pixels, pixels.to_vec());
+                xor_in_place(&mut delta, pixels);
+                Some(delta)
+            }
+            _ => {
+                self.references.insert(
+                    header.overlay_id,
+                    Reference {
+                        width: header.width,
+                        height: header.height,
+                        stride: header.stride,
+                        pixel_format: header.pixel_format,
+                        pixels: pixels.to_vec(),
+                        since_keyframe: 1,
+                    },
+                );
+                None
+            }
+        }
+    }
+}
+
+// In word-sized chunks so the compiler can vectorize it
+fn xor_in_place(target: &mut [u8], other: &[u8]) {
+    let mut target_words = target.chunks_exact_mut(8);
+    let mut other_words = other.chunks_exact(8);
+    for (a, b) in (&mut target_words).zip(&mut other_words) {
+        let word = u64::from_ne_bytes(a[..].try_into().unwrap()) ^ u64::from_ne_bytes(b.try_into().unwrap());
+        a.copy_from_slice(&word.to_ne_bytes());
+    }
+    for (a, b) in target_words.into_remainder().iter_mut().zip(other_words.remainder()) {
+        *a ^= b;
+    }
+}
@@ -0,0 +1,267 @@
+// --- Device status ---
+// Besides poses, the backend reports each tracked device's battery, tracking
+// state and firmware in DeviceStatus messages on the transform pipe, whenever
+// one of them changes (and once per device after connecting). The latest
+// status per device is kept for get_device_status, and every change is
+// emitted as "device-status". When a device other than the headset (which
+// warns on its own) drops to lowBatteryPercent or below without charging,
+// "device-battery-low" is emitted once; it's armed again when the device
+// charges or climbs a few percent above the threshold. 0 turns the warning
+// off. The plugin doesn't pop up anything itself: an app that wants an OS
+// notification shows one from that event, e.g. with Tauri's notification
+// plugin.
+//
+//   [deviceStatus]
+//   lowBatteryPercent = 20
+//
+// Payload: one or more records back to back, each length-prefixed like pose
+// records (see pose.rs) so fields can be appended later. Record body:
+//   [0..4)   device id, as in poses (u32, little endian)
+//   [4]      battery percent 0..100, or 0xFF for a device without a battery (u8)
+//   [5]      flags (u8, bit 0 = charging, bit 1 = connected)
+//   [6]      tracking state (u8, 0 = not tracking, 1 = ok, 2 = out of range, 3 = calibrating)
+//   [7]      firmware version length in bytes (u8)
+//   [8..)    firmware version (UTF-8)
+use crate::pose::{self, DEVICE_HMD, RECORD_LENGTH_SIZE};
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+pub const MIN_STATUS_SIZE: usize = 8;
+const NO_BATTERY: u8 = 0xFF;
+const FLAG_CHARGING: u8 = 1 << 0;
+const FLAG_CONNECTED: u8 = 1 << 1;
+// Percent above the threshold a battery has to climb before it's warned about again
+const HYSTERESIS: u8 = 5;
+pub const DEFAULT_LOW_BATTERY_PERCENT: u8 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DeviceStatusConfig {
+    // Battery level that triggers device-battery-low; 0 never warns
+    pub low_battery_percent: u8,
+}
+
+impl Default for DeviceStatusConfig {
+    fn default() -> Self {
+        Self { low_battery_percent: DEFAULT_LOW_BATTERY_PERCENT }
+    }
+}
+
+impl DeviceStatusConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.low_battery_percent > 100 {
+            return Err("lowBatteryPercent must be at most 100".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrackingState {
+    NotTracking,
+    Ok,
+    OutOfRange,
+    Calibrating,
+}
+
+impl TrackingState {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::NotTracking),
+            1 => Some(Self::Ok),
+            2 => Some(Self::OutOfRange),
+            3 => Some(Self::Calibrating),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Self::NotTracking => 0,
+            Self::Ok => 1,
+            Self::OutOfRange => 2,
+            Self::Calibrating => 3,
+        }
+    }
+}
+
+// Payload of "device-status" and an entry of get_device_status
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceStatus {
+    // As in transform-update events, e.g. "controller-left"
+    pub device: String,
+    pub device_id: u32,
+    // None for devices without a battery
+    pub battery_percent: Option<u8>,
+    pub charging: bool,
+    pub connected: bool,
+    pub tracking: TrackingState,
+    pub firmware: String,
+}
+
+// Payload of "device-battery-low"
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatteryLow {
+    pub device: String,
+    pub device_id: u32,
+    pub battery_percent: u8,
+}
+
+impl DeviceStatus {
+    // Length-prefixed record, as in a DeviceStatus message
+    pub fn encode_record(&self) -> Vec<u8> {
+        // Cut over-long versions at a character boundary
+        let mut length = self.firmware.len().min(u8::MAX as usize);
+        while !self.firmware.is_char_boundary(length) {
+            length -= 1;
+        }
+        let firmware = &self.firmware.as_bytes()[..length];
+        let mut record = vec![0u8; RECORD_LENGTH_SIZE + MIN_STATUS_SIZE];
+        LittleEndian::write_u16(&mut record[..RECORD_LENGTH_SIZE], (MIN_STATUS_SIZE + firmware.len()) as u16);
+        let body = &mut record[RECORD_LENGTH_SIZE..];
+        LittleEndian::write_u32(&mut body[0..4], self.device_id);
+        body[4] = self.battery_percent.unwrap_or(NO_BATTERY);
+        body[5] = if self.charging { FLAG_CHARGING } else { 0 } | if self.connected { FLAG_CONNECTED } else { 0 };
+        body[6] = self.tracking.code();
+        body[7] = firmware.len() as u8;
+        record.extend_from_slice(firmware);
+        record
+    }
+
+    fn decode(body: &[u8]) -> Result<Self, String> {
+        let device_id = LittleEndian::read_u32(&body[0..4]);
+        let battery_percent = match body[4] {
+            NO_BATTERY => None,
+            percent if percent <= 100 => Some(percent),
+            percent => return Err(format!("battery at {}%", percent)),
+        };
+        let tracking = TrackingState::from_code(body[6]).ok_or_else(|| format!("unknown tracking state {}", body[6]))?;
+        let firmware = body
+            .get(MIN_STATUS_SIZE..MIN_STATUS_SIZE + body[7] as usize)
+            .ok_or_else(|| format!("record of {} bytes is too short for a {} byte firmware version", body.len(), body[7]))?;
+        Ok(Self {
+            device: pose::device_name(device_id).into_owned(),
+            device_id,
+            battery_percent,
+            charging: body[5] & FLAG_CHARGING != 0,
+            connected: body[5] & FLAG_CONNECTED != 0,
+            tracking,
+            firmware: String::from_utf8_lossy(firmware).into_owned(),
+        })
+    }
+}
+
+pub fn decode_records(payload: &[u8]) -> Result<Vec<DeviceStatus>, String> {
+    let mut statuses = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        if rest.len() < RECORD_LENGTH_SIZE {
+            return Err(format!("{} trailing bytes after last record", rest.len()));
+        }
+        let length = LittleEndian::read_u16(&rest[..RECORD_LENGTH_SIZE]) as usize;
+        if length < MIN_STATUS_SIZE {
+            return Err(format!("record of {} bytes is shorter than {}", length, MIN_STATUS_SIZE));
+        }
+        let body = rest
+            .get(RECORD_LENGTH_SIZE..RECORD_LENGTH_SIZE + length)
+            .ok_or_else(|| format!("record of {} bytes runs past end of payload", length))?;
+        statuses.push(DeviceStatus::decode(body)?);
+        rest = &rest[RECORD_LENGTH_SIZE + length..];
+    }
+    Ok(statuses)
+}
+
+// Latest status per device, and which batteries have been warned about
+pub struct DeviceStatusBoard {
+    devices: Mutex<BTreeMap<u32, DeviceStatus>>,
+    warned: Mutex<HashSet<u32>>,
+    low_battery_percent: AtomicU8,
+}
+
+impl DeviceStatusBoard {
+    pub fn new(config: DeviceStatusConfig) -> Self {
+        Self { devices: Mutex::default(), warned: Mutex::default(), low_battery_percent: AtomicU8::new(config.low_battery_percent) }
+    }
+
+    pub fn set_config(&self, config: DeviceStatusConfig) {
+        self.low_battery_percent.store(config.low_battery_percent, Ordering::Relaxed);
+    }
+
+    // Every known device, by device id
+    pub fn all(&self) -> Vec<DeviceStatus> {
+        self.devices.lock().values().cloned().collect()
+    }
+
+    // Forget the devices of a previous connection
+    pub fn clear(&self) {
+        self.devices.lock().clear();
+        self.warned.lock().clear();
+    }
+
+    // Record a status. Returns whether it differs from the last one for the
+    // device, and the warning if its battery just went low.
+    pub fn update(&self, status: DeviceStatus) -> (bool, Option<BatteryLow>) {
+        let threshold = self.low_battery_percent.load(Ordering::Relaxed);
+        let low_battery = match status.battery_percent {
+            Some(percent) if status.device_id != DEVICE_HMD && threshold != 0 => {
+                let mut warned = self.warned.lock();
+                if status.charging || percent > threshold.saturating_add(HYSTERESIS) {
+                    warned.remove(&status.device_id);
+                    None
+                } else if percent <= threshold && warned.insert(status.device_id) {
+                    Some(BatteryLow { device: status.device.clone(), device_id: status.device_id, battery_percent: percent })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let changed = self.devices.lock().insert(status.device_id, status.clone()).as_ref() != Some(&status);
+        (changed, low_battery)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pose::DEVICE_CONTROLLER_LEFT;
+
+    #[test]
+    fn statuses_round_trip_and_low_batteries_warn_once() {
+        let status = |percent: u8, charging: bool| DeviceStatus {
+            device: "controller-left".to_string(),
+            device_id: DEVICE_CONTROLLER_LEFT,
+            battery_percent: Some(percent),
+            charging,
+            connected: true,
+            tracking: TrackingState::Ok,
+            firmware: "1.2.3".to_string(),
+        };
+        let base_station = DeviceStatus { device: "device-4".to_string(), device_id: 4, battery_percent: None, firmware: String::new(), ..status(0, false) };
+        let mut payload = status(50, false).encode_record();
+        payload.extend_from_slice(&base_station.encode_record());
+        assert_eq!(decode_records(&payload).unwrap(), [status(50, false), base_station]);
+        assert!(decode_records(&payload[..payload.len() - 1]).is_err());
+        let mut overcharged = status(50, false).encode_record();
+        overcharged[RECORD_LENGTH_SIZE + 4] = 101;
+        assert!(decode_records(&overcharged).is_err());
+
+        let board = DeviceStatusBoard::new(DeviceStatusConfig { low_battery_percent: 20 });
+        assert_eq!(board.update(status(50, false)), (true, None));
+        assert_eq!(board.update(status(50, false)), (false, None));
+        assert!(board.update(status(20, false)).1.is_some_and(|low| low.battery_percent == 20));
+        // Once per discharge, until the battery is charged
+        assert_eq!(board.update(status(19, false)).1, None);
+        assert_eq!(board.update(status(22, true)).1, None);
+        assert!(board.update(status(18, false)).1.is_some());
+        assert_eq!(board.all().len(), 1);
+    }
+}
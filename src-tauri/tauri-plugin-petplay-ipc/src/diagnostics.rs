@@ -0,0 +1,250 @@
+// --- Diagnostics bundle ---
+// export_diagnostics puts everything needed to look into a bug report into
+// one zip file the user can attach:
+//
+//   about.json          app version, OS and when the bundle was made
+//   config.toml         puppyweb.toml as loaded (authToken redacted)
+//   metrics.json        get_pipe_metrics and get_latency_histogram, per connection
+//   connections.json    get_connection_status and list_pipe_connections
+//   backend.json        get_backend_info, if a backend completed the handshake
+//   last-frame.png      the last frame handed to send_frame_data, if any
+//   logs/               the newest log files (their last MAX_LOG_BYTES each)
+//
+// The zip is written here rather than with a zip crate: the entries are
+// built in memory, deflated and written with the plain (non-zip64) format,
+// which is plenty for a few megabytes of logs.
+use crate::logging::LOG_FILE_PREFIX;
+use flate2::{write::DeflateEncoder, Crc};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAX_LOG_FILES_IN_BUNDLE: usize = 3;
+// Only the end of longer log files goes in
+const MAX_LOG_BYTES: u64 = 16 * 1024 * 1024;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+// 2.0, the first version with deflate
+const ZIP_VERSION: u16 = 20;
+const METHOD_DEFLATE: u16 = 8;
+// Entry names are UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+
+// Result of export_diagnostics
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsBundle {
+    pub path: PathBuf,
+    // Names of the files in the zip
+    pub files: Vec<String>,
+    pub bytes: u64,
+}
+
+// Write `entries` (name, contents) as a zip file at `path`
+pub fn write_bundle(path: &Path, entries: &[(String, Vec<u8>)]) -> io::Result<DiagnosticsBundle> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?), SystemTime::now());
+    for (name, contents) in entries {
+        zip.add(name, contents)?;
+    }
+    let bytes = zip.finish()?;
+    Ok(DiagnosticsBundle { path: path.to_path_buf(), files: entries.iter().map(|(name, _)| name.clone()).collect(), bytes })
+}
+
+// The newest log files in `dir` as "logs/<file name>" entries
+pub fn recent_logs(dir: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let metadata = entry.metadata()?;
+        if metadata.is_file() && name.starts_with(LOG_FILE_PREFIX) {
+            logs.push((metadata.modified().unwrap_or(UNIX_EPOCH), name, entry.path()));
+        }
+    }
+    logs.sort_by_key(|(modified, _, _)| Reverse(*modified));
+    logs.into_iter()
+        .take(MAX_LOG_FILES_IN_BUNDLE)
+        .map(|(_, name, path)| Ok((format!("logs/{}", name), read_tail(&path, MAX_LOG_BYTES)?)))
+        .collect()
+}
+
+fn read_tail(path: &Path, max_bytes: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+    file.seek(SeekFrom::Start(length.saturating_sub(max_bytes)))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    compressed: u32,
+    uncompressed: u32,
+    offset: u32,
+}
+
+struct ZipWriter<W: Write> {
+    out: W,
+    // Bytes written so far, i.e. where the next header goes
+    offset: u32,
+    entries: Vec<CentralEntry>,
+    // MS-DOS time and date every entry is stamped with
+    time: u16,
+    date: u16,
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "diagnostics bundle is larger than a zip without zip64 can hold")
+}
+
+impl<W: Write> ZipWriter<W> {
+    fn new(out: W, now: SystemTime) -> Self {
+        let (time, date) = dos_time(now);
+        Self { out, offset: 0, entries: Vec::new(), time, date }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.offset = u32::try_from(bytes.len()).ok().and_then(|len| self.offset.checked_add(len)).ok_or_else(too_large)?;
+        Ok(())
+    }
+
+    fn add(&mut self, name: &str, contents: &[u8]) -> io::Result<()> {
+        let mut crc = Crc::new();
+        crc.update(contents);
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(contents)?;
+        let compressed = encoder.finish()?;
+        let entry = CentralEntry {
+            name: name.to_string(),
+            crc: crc.sum(),
+            compressed: u32::try_from(compressed.len()).map_err(|_| too_large())?,
+            uncompressed: u32::try_from(contents.len()).map_err(|_| too_large())?,
+            offset: self.offset,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        self.entry_fields(&mut header, &entry);
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header)?;
+        self.write(&compressed)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    // The fields local and central headers share, from the flags to the name length
+    fn entry_fields(&self, header: &mut Vec<u8>, entry: &CentralEntry) {
+        header.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        header.extend_from_slice(&METHOD_DEFLATE.to_le_bytes());
+        header.extend_from_slice(&self.time.to_le_bytes());
+        header.extend_from_slice(&self.date.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed.to_le_bytes());
+        header.extend_from_slice(&entry.uncompressed.to_le_bytes());
+        header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+    }
+
+    // Write the central directory; returns the size of the whole file
+    fn finish(mut self) -> io::Result<u64> {
+        let directory_offset = self.offset;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            let mut header = Vec::with_capacity(46 + entry.name.len());
+            header.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            header.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // made by
+            header.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // needed to extract
+            self.entry_fields(&mut header, entry);
+            header.extend_from_slice(&[0; 12]); // extra and comment length, disk, internal and external attributes
+            header.extend_from_slice(&entry.offset.to_le_bytes());
+            header.extend_from_slice(entry.name.as_bytes());
+            self.write(&header)?;
+        }
+        let count = u16::try_from(entries.len()).map_err(|_| too_large())?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes());
+        end.extend_from_slice(&[0; 4]); // this disk, disk with the directory
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&(self.offset - directory_offset).to_le_bytes());
+        end.extend_from_slice(&directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.write(&end)?;
+        self.out.flush()?;
+        Ok(self.offset as u64)
+    }
+}
+
+// MS-DOS (time, date) of `at` in UTC; the format can't go before 1980
+fn dos_time(at: SystemTime) -> (u16, u16) {
+    let secs = at.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's days_from_civil, inverted)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = ((secs_of_day / 3600) << 11) | (((secs_of_day / 60) % 60) << 5) | ((secs_of_day % 60) / 2);
+    let date = (((year - 1980).min(127) as u64) << 9) | ((month as u64) << 5) | day as u64;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps_are_converted_to_dos_time() {
+        // 2024-02-29 13:45:30 UTC
+        let at = UNIX_EPOCH + Duration::from_secs(1_709_214_330);
+        assert_eq!(dos_time(at), ((13 << 11) | (45 << 5) | 15, (44 << 9) | (2 << 5) | 29));
+    }
+
+    #[test]
+    fn a_bundle_is_a_zip_with_every_entry() {
+        let path = std::env::temp_dir().join(format!("puppyweb-diagnostics-test-{}.zip", std::process::id()));
+        let contents = "line\n".repeat(1000).into_bytes();
+        let bundle = write_bundle(&path, &[("logs/puppyweb.log".to_string(), contents.clone())]).unwrap();
+        let zip = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(bundle.bytes, zip.len() as u64);
+
+        // The end of central directory record points at the one central header, which points at the entry
+        let end = &zip[zip.len() - 22..];
+        assert_eq!(u32::from_le_bytes(end[0..4].try_into().unwrap()), END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        let directory = u32::from_le_bytes(end[16..20].try_into().unwrap()) as usize;
+        let central = &zip[directory..];
+        assert_eq!(u32::from_le_bytes(central[0..4].try_into().unwrap()), CENTRAL_HEADER_SIGNATURE);
+        let compressed = u32::from_le_bytes(central[20..24].try_into().unwrap()) as usize;
+        let offset = u32::from_le_bytes(central[42..46].try_into().unwrap()) as usize;
+        let name_length = u16::from_le_bytes(zip[offset + 26..offset + 28].try_into().unwrap()) as usize;
+        let data = &zip[offset + 30 + name_length..offset + 30 + name_length + compressed];
+        let mut decompressed = Vec::new();
+        DeflateDecoder::new(data).read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, contents);
+    }
+}
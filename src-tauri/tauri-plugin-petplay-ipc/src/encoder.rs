@@ -0,0 +1,177 @@
+// --- Hardware video encoding ---
+// Optional H.264/HEVC path for bandwidth-constrained setups. Encoding is
+// delegated to an ffmpeg child process using a hardware encoder (NVENC, AMF or
+// QuickSync): raw 4:2:0 frames (converted from RGBA by colorspace.rs) go into
+// its stdin and the Annex-B elementary stream coming out of its stdout is
+// forwarded to the backend in VideoChunk messages. If ffmpeg or a matching hardware encoder isn't available the
+// frame pipeline falls back to sending raw frames.
+use crate::colorspace::YuvLayout;
+use serde::{Deserialize, Serialize};
+use std::{io, process::Stdio};
+use tokio::{
+    io::AsyncWriteExt,
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+
+pub const FFMPEG_EXECUTABLE: &str = "ffmpeg";
+
+// Message flags (protocol header) of a VideoChunk telling the backend which codec it carries
+pub const FLAG_H264: u16 = 1 << 0;
+pub const FLAG_HEVC: u16 = 1 << 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    // Raw frames, no encoding
+    #[default]
+    None,
+    H264,
+    Hevc,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncoderBackend {
+    // First hardware encoder ffmpeg reports, in the order below
+    #[default]
+    Auto,
+    Nvenc,
+    Amf,
+    Qsv,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EncoderSettings {
+    pub codec: VideoCodec,
+    pub backend: EncoderBackend,
+    pub bitrate_kbps: u32,
+    // Frames between keyframes (GOP length)
+    pub keyframe_interval: u32,
+    // Nominal input rate, only used by the encoder's rate control
+    pub framerate: u32,
+    // Layout frames are converted to before they're fed to ffmpeg
+    pub input: YuvLayout,
+}
+
+impl Default for EncoderSettings {
+    fn default() -> Self {
+        Self {
+            codec: VideoCodec::None,
+            backend: EncoderBackend::Auto,
+            bitrate_kbps: 20_000,
+            keyframe_interval: 60,
+            framerate: 60,
+            input: YuvLayout::Nv12,
+        }
+    }
+}
+
+impl VideoCodec {
+    pub fn flag(self) -> u16 {
+        match self {
+            Self::None => 0,
+            Self::H264 => FLAG_H264,
+            Self::Hevc => FLAG_HEVC,
+        }
+    }
+
+    // ffmpeg muxer name for the raw elementary stream
+    fn ffmpeg_format(self) -> &'static str {
+        match self {
+            Self::Hevc => "hevc",
+            _ => "h264",
+        }
+    }
+}
+
+// ffmpeg encoder name for a codec/backend pair, e.g. "h264_nvenc"
+fn ffmpeg_encoder_name(codec: VideoCodec, backend: EncoderBackend) -> Option<String> {
+    let codec = match codec {
+        VideoCodec::None => return None,
+        VideoCodec::H264 => "h264",
+        VideoCodec::Hevc => "hevc",
+    };
+    let backend = match backend {
+        EncoderBackend::Auto => return None,
+        EncoderBackend::Nvenc => "nvenc",
+        EncoderBackend::Amf => "amf",
+        EncoderBackend::Qsv => "qsv",
+    };
+    Some(format!("{}_{}", codec, backend))
+}
+
+// Ask ffmpeg which encoders it was built with and pick a hardware one for the codec
+async fn detect_encoder(codec: VideoCodec, preferred: EncoderBackend) -> io::Result<String> {
+    let output = Command::new(FFMPEG_EXECUTABLE)
+        .args(["-hide_banner", "-encoders"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let candidates: &[EncoderBackend] = match preferred {
+        EncoderBackend::Auto => &[EncoderBackend::Nvenc, EncoderBackend::Amf, EncoderBackend::Qsv],
+        EncoderBackend::Nvenc => &[EncoderBackend::Nvenc],
+        EncoderBackend::Amf => &[EncoderBackend::Amf],
+        EncoderBackend::Qsv => &[EncoderBackend::Qsv],
+    };
+    candidates
+        .iter()
+        .filter_map(|backend| ffmpeg_encoder_name(codec, *backend))
+        .find(|name| listing.split_whitespace().any(|word| word == name))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no {:?} hardware encoder available", codec)))
+}
+
+// A running ffmpeg encoder for one resolution
+pub struct VideoEncoder {
+    child: Child,
+    stdin: ChildStdin,
+    pub settings: EncoderSettings,
+    pub width: u32,
+    pub height: u32,
+    pub encoder_name: String,
+}
+
+impl VideoEncoder {
+    // Detect a hardware encoder and start ffmpeg; the returned stdout carries the encoded stream
+    pub async fn start(settings: EncoderSettings, width: u32, height: u32) -> io::Result<(Self, ChildStdout)> {
+        let encoder_name = detect_encoder(settings.codec, settings.backend).await?;
+        let mut child = Command::new(FFMPEG_EXECUTABLE)
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", settings.input.ffmpeg_pix_fmt()])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-framerate", &settings.framerate.max(1).to_string()])
+            .args(["-i", "pipe:0"])
+            .args(["-c:v", &encoder_name])
+            .args(["-b:v", &format!("{}k", settings.bitrate_kbps)])
+            .args(["-g", &settings.keyframe_interval.max(1).to_string()])
+            // No B-frames: every packet can be shown as soon as it's decoded
+            .args(["-bf", "0"])
+            .args(["-f", settings.codec.ffmpeg_format(), "pipe:1"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| io::Error::other("ffmpeg stdin unavailable"))?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("ffmpeg stdout unavailable"))?;
+        Ok((Self { child, stdin, settings, width, height, encoder_name }, stdout))
+    }
+
+    // Feed one frame, already in the settings' input layout, to the encoder
+    pub async fn encode(&mut self, pixels: &[u8]) -> io::Result<()> {
+        self.stdin.write_all(pixels).await
+    }
+
+    pub fn matches(&self, settings: &EncoderSettings, width: u32, height: u32) -> bool {
+        self.settings == *settings && self.width == width && self.height == height
+    }
+
+    // Close stdin so ffmpeg flushes, then make sure the process is gone
+    pub async fn stop(mut self) {
+        let _ = self.stdin.shutdown().await;
+        drop(self.stdin);
+        let _ = self.child.kill().await;
+    }
+}
@@ -0,0 +1,105 @@
+// --- Command errors ---
+// Every command that can fail returns a PipeError. It reaches the frontend as
+// { code, message, ioKind } so the UI can match on `code` instead of parsing
+// human-readable strings; ioKind is only set for I/O failures.
+use crate::{connection::PipeKind, protocol::ProtocolError};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PipeError {
+    #[error("request body must be raw bytes")]
+    RequestBodyMustBeRaw,
+    #[error("payload of {length} bytes is too small (need at least {min})")]
+    PayloadTooSmall { length: usize, min: usize },
+    #[error("{width}x{height} frame needs {expected} bytes of pixels, got {actual}")]
+    FrameSizeMismatch { width: u32, height: u32, expected: u64, actual: usize },
+    #[error("frame of {bytes} bytes exceeds the {max} byte limit")]
+    FrameTooLarge { bytes: u64, max: usize },
+    #[error("request body of {bytes} bytes exceeds the {max} byte limit")]
+    PayloadTooLarge { bytes: usize, max: usize },
+    #[error("{command} is limited to {rate} calls per second")]
+    RateLimited { command: String, rate: u32 },
+    #[error("{0:?} pipe not connected")]
+    NotConnected(PipeKind),
+    #[error("write to {pipe:?} pipe failed: {message}")]
+    WriteFailed { pipe: PipeKind, io_kind: io::ErrorKind, message: String },
+    #[error("protocol violation: {0}")]
+    ProtocolViolation(String),
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0} is full")]
+    QueueFull(&'static str),
+    #[error("{0} is not running")]
+    Unavailable(&'static str),
+    #[error("no frame has been sent yet")]
+    NoFrame,
+    #[error("{context}: {message}")]
+    Io { context: &'static str, io_kind: io::ErrorKind, message: String },
+}
+
+impl PipeError {
+    pub fn write_failed(pipe: PipeKind, e: &io::Error) -> Self {
+        Self::WriteFailed { pipe, io_kind: e.kind(), message: e.to_string() }
+    }
+
+    pub fn io(context: &'static str, e: &io::Error) -> Self {
+        Self::Io { context, io_kind: e.kind(), message: e.to_string() }
+    }
+
+    // An output (Spout, NDI, ...) that couldn't be started: bad settings, not available here, or an OS error
+    pub fn sink_start(context: &'static str, e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::InvalidInput => Self::InvalidArgument(e.to_string()),
+            io::ErrorKind::Unsupported => Self::Unsupported(e.to_string()),
+            _ => Self::io(context, e),
+        }
+    }
+
+    // Stable identifier the frontend can match on
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::RequestBodyMustBeRaw => "RequestBodyMustBeRaw",
+            Self::PayloadTooSmall { .. } => "PayloadTooSmall",
+            Self::FrameSizeMismatch { .. } => "FrameSizeMismatch",
+            Self::FrameTooLarge { .. } => "FrameTooLarge",
+            Self::PayloadTooLarge { .. } => "PayloadTooLarge",
+            Self::RateLimited { .. } => "RateLimited",
+            Self::NotConnected(_) => "NotConnected",
+            Self::WriteFailed { .. } => "WriteFailed",
+            Self::ProtocolViolation(_) => "ProtocolViolation",
+            Self::Unsupported(_) => "Unsupported",
+            Self::InvalidArgument(_) => "InvalidArgument",
+            Self::QueueFull(_) => "QueueFull",
+            Self::Unavailable(_) => "Unavailable",
+            Self::NoFrame => "NoFrame",
+            Self::Io { .. } => "Io",
+        }
+    }
+
+    fn io_kind(&self) -> Option<String> {
+        match self {
+            Self::WriteFailed { io_kind, .. } | Self::Io { io_kind, .. } => Some(format!("{:?}", io_kind)),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProtocolError> for PipeError {
+    fn from(e: ProtocolError) -> Self {
+        Self::ProtocolViolation(e.to_string())
+    }
+}
+
+impl Serialize for PipeError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("PipeError", 3)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("ioKind", &self.io_kind())?;
+        error.end()
+    }
+}
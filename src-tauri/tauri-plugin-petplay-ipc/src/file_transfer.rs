@@ -0,0 +1,376 @@
+// --- File transfer ---
+// Avatars, configs and the like exchanged with the backend without either
+// side touching the other's filesystem. Files travel on a pipe of their own
+// (petplay-ipc-files), in either direction, as the same four messages:
+//
+//   sender    FileOffer   id, size, SHA-256 and name of the file
+//   receiver  FileAccept  id and the offset to start at (what it already has)
+//   sender    FileChunk   id, offset and up to CHUNK_SIZE bytes, in order
+//   receiver  FileDone    id and whether the SHA-256 of what arrived matched
+//
+// send_file_to_backend takes the file as a raw body ("file-name" header) and
+// returns the transfer id. Transfers outlive a dropped connection: after a
+// reconnect the app offers every unfinished file again, and the backend
+// answers with how much it already has, so only the rest is sent. The app
+// does the same for files it receives, keeping partial ones by id. Progress
+// is reported as "file-transfer-progress" and the outcome as
+// "file-transfer-complete"; a received file then waits in memory until the
+// frontend collects it with take_received_file.
+//
+// Payload layouts (little endian):
+//   FileOffer   [0..4) id (u32), [4..12) size (u64), [12..44) SHA-256, [44..) name (UTF-8)
+//   FileAccept  [0..4) id (u32), [4..12) offset (u64)
+//   FileChunk   [0..4) id (u32), [4..12) offset (u64), [12..) data
+//   FileDone    [0..4) id (u32), [4] status (u8, 0 = verified, 1 = checksum mismatch, 2 = rejected)
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicU32, Ordering},
+};
+use tokio::sync::Notify;
+
+pub const CHUNK_SIZE: usize = 256 * 1024;
+pub const CHUNK_HEADER_SIZE: usize = 12;
+// Largest file either way; files are held in memory until they're sent or collected
+pub const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+// Files being received at once, finished or not
+const MAX_INCOMING: usize = 8;
+const MAX_NAME_LENGTH: usize = 255;
+const OFFER_HEADER_SIZE: usize = 44;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferDirection {
+    ToBackend,
+    FromBackend,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Verified,
+    ChecksumMismatch,
+    Rejected,
+}
+
+impl FileStatus {
+    fn code(self) -> u8 {
+        match self {
+            Self::Verified => 0,
+            Self::ChecksumMismatch => 1,
+            Self::Rejected => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Verified),
+            1 => Some(Self::ChecksumMismatch),
+            2 => Some(Self::Rejected),
+            _ => None,
+        }
+    }
+
+    // None if the file arrived intact
+    pub fn error(self) -> Option<String> {
+        match self {
+            Self::Verified => None,
+            Self::ChecksumMismatch => Some("Checksum mismatch".to_string()),
+            Self::Rejected => Some("Rejected by the receiver".to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileOffer {
+    pub id: u32,
+    pub size: u64,
+    pub sha256: [u8; 32],
+    pub name: String,
+}
+
+impl FileOffer {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; OFFER_HEADER_SIZE];
+        LittleEndian::write_u32(&mut bytes[0..4], self.id);
+        LittleEndian::write_u64(&mut bytes[4..12], self.size);
+        bytes[12..44].copy_from_slice(&self.sha256);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes
+    }
+
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let header = payload.get(..OFFER_HEADER_SIZE)?;
+        Some(Self {
+            id: LittleEndian::read_u32(&header[0..4]),
+            size: LittleEndian::read_u64(&header[4..12]),
+            sha256: header[12..44].try_into().ok()?,
+            name: String::from_utf8(payload[OFFER_HEADER_SIZE..].to_vec()).ok()?,
+        })
+    }
+}
+
+// FileAccept and the header of FileChunk: a transfer id and an offset into the file
+pub fn encode_position(id: u32, offset: u64) -> [u8; CHUNK_HEADER_SIZE] {
+    let mut bytes = [0u8; CHUNK_HEADER_SIZE];
+    LittleEndian::write_u32(&mut bytes[0..4], id);
+    LittleEndian::write_u64(&mut bytes[4..12], offset);
+    bytes
+}
+
+pub fn decode_position(payload: &[u8]) -> Option<(u32, u64)> {
+    let bytes = payload.get(..CHUNK_HEADER_SIZE)?;
+    Some((LittleEndian::read_u32(&bytes[0..4]), LittleEndian::read_u64(&bytes[4..12])))
+}
+
+pub fn encode_done(id: u32, status: FileStatus) -> [u8; 5] {
+    let mut bytes = [0u8; 5];
+    LittleEndian::write_u32(&mut bytes[0..4], id);
+    bytes[4] = status.code();
+    bytes
+}
+
+pub fn decode_done(payload: &[u8]) -> Option<(u32, FileStatus)> {
+    let bytes = payload.get(..5)?;
+    Some((LittleEndian::read_u32(&bytes[0..4]), FileStatus::from_code(bytes[4])?))
+}
+
+// Payload of "file-transfer-progress"
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTransferProgress {
+    pub transfer_id: u32,
+    pub direction: TransferDirection,
+    pub name: String,
+    // Bytes sent or received so far, counting what was already there when it resumed
+    pub bytes: u64,
+    pub total: u64,
+}
+
+// Payload of "file-transfer-complete"; a received file with no error can be collected with take_received_file
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTransferComplete {
+    pub transfer_id: u32,
+    pub direction: TransferDirection,
+    pub name: String,
+    pub size: u64,
+    pub error: Option<String>,
+}
+
+struct Outgoing {
+    offer: FileOffer,
+    data: Bytes,
+}
+
+struct Incoming {
+    offer: FileOffer,
+    data: Vec<u8>,
+    // Verified and waiting for take_received_file
+    complete: bool,
+}
+
+// What a received chunk amounted to
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkOutcome {
+    Progress(FileTransferProgress),
+    // The last chunk; the file was verified (and kept) or thrown away
+    Done { status: FileStatus, name: String, size: u64 },
+}
+
+pub struct ReceivedFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+// Both directions' transfers, shared by the command, the files pipe reader and its writer task
+pub struct FileTransfers {
+    next_id: AtomicU32,
+    outgoing: Mutex<HashMap<u32, Outgoing>>,
+    incoming: Mutex<HashMap<u32, Incoming>>,
+    // Outgoing transfers the backend accepted, with the offset to send from, for the writer task
+    accepted: Mutex<VecDeque<(u32, u64)>>,
+    accepted_available: Notify,
+}
+
+impl Default for FileTransfers {
+    fn default() -> Self {
+        Self {
+            next_id: AtomicU32::new(1),
+            outgoing: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+            accepted: Mutex::new(VecDeque::new()),
+            accepted_available: Notify::new(),
+        }
+    }
+}
+
+impl FileTransfers {
+    // Start sending `data` as `name`; returns the offer to put on the pipe
+    pub fn send(&self, name: &str, data: Bytes) -> Result<FileOffer, String> {
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(format!("File name must be 1 to {} bytes long", MAX_NAME_LENGTH));
+        }
+        if data.len() as u64 > MAX_FILE_SIZE {
+            return Err(format!("File of {} bytes exceeds the {} byte limit", data.len(), MAX_FILE_SIZE));
+        }
+        let offer = FileOffer { id: self.next_id.fetch_add(1, Ordering::Relaxed), size: data.len() as u64, sha256: sha256(&data), name: name.to_string() };
+        self.outgoing.lock().insert(offer.id, Outgoing { offer: offer.clone(), data });
+        Ok(offer)
+    }
+
+    // Every file not confirmed by the backend yet, to offer again after a reconnect
+    pub fn unfinished_offers(&self) -> Vec<FileOffer> {
+        let mut offers: Vec<_> = self.outgoing.lock().values().map(|outgoing| outgoing.offer.clone()).collect();
+        offers.sort_by_key(|offer| offer.id);
+        offers
+    }
+
+    // The backend accepted a file; false if there's no such transfer or the offset is past its end
+    pub fn accept(&self, id: u32, offset: u64) -> bool {
+        let valid = self.outgoing.lock().get(&id).is_some_and(|outgoing| offset <= outgoing.offer.size);
+        if valid {
+            self.accepted.lock().push_back((id, offset));
+            self.accepted_available.notify_one();
+        }
+        valid
+    }
+
+    // Wait for the next accepted transfer to send
+    pub async fn next_accepted(&self) -> (u32, u64) {
+        loop {
+            if let Some(accepted) = self.accepted.lock().pop_front() {
+                return accepted;
+            }
+            self.accepted_available.notified().await;
+        }
+    }
+
+    // The chunk of an outgoing file at `offset` with the file's name and size, or None once it's all sent
+    pub fn chunk(&self, id: u32, offset: u64) -> Option<(Bytes, String, u64)> {
+        let outgoing = self.outgoing.lock();
+        let outgoing = outgoing.get(&id)?;
+        let start = usize::try_from(offset).ok().filter(|&start| start < outgoing.data.len())?;
+        let end = (start + CHUNK_SIZE).min(outgoing.data.len());
+        Some((outgoing.data.slice(start..end), outgoing.offer.name.clone(), outgoing.offer.size))
+    }
+
+    // The backend is done with an outgoing file, successfully or not; forgets it
+    pub fn finish_outgoing(&self, id: u32) -> Option<FileOffer> {
+        self.outgoing.lock().remove(&id).map(|outgoing| outgoing.offer)
+    }
+
+    // A file offered by the backend; Ok with where to start (after the part already received), or why it's refused
+    pub fn receive_offer(&self, offer: FileOffer) -> Result<u64, String> {
+        if offer.size > MAX_FILE_SIZE {
+            return Err(format!("file of {} bytes exceeds the {} byte limit", offer.size, MAX_FILE_SIZE));
+        }
+        if offer.name.is_empty() || offer.name.len() > MAX_NAME_LENGTH {
+            return Err("invalid file name".to_string());
+        }
+        let mut incoming = self.incoming.lock();
+        if let Some(existing) = incoming.get(&offer.id) {
+            // The same file offered again after a reconnect picks up where it left off
+            if existing.offer == offer && !existing.complete {
+                return Ok(existing.data.len() as u64);
+            }
+        }
+        if !incoming.contains_key(&offer.id) && incoming.len() >= MAX_INCOMING {
+            return Err(format!("already receiving {} files", MAX_INCOMING));
+        }
+        incoming.insert(offer.id, Incoming { data: Vec::with_capacity(offer.size as usize), offer, complete: false });
+        Ok(0)
+    }
+
+    // A chunk of a file being received; Err if it isn't the next one expected
+    pub fn receive_chunk(&self, id: u32, offset: u64, data: &[u8]) -> Result<ChunkOutcome, String> {
+        let mut incoming = self.incoming.lock();
+        let Some(file) = incoming.get_mut(&id).filter(|file| !file.complete) else {
+            return Err(format!("no file {} being received", id));
+        };
+        if offset != file.data.len() as u64 {
+            return Err(format!("chunk of file {} at offset {}, expected {}", id, offset, file.data.len()));
+        }
+        let (name, size) = (file.offer.name.clone(), file.offer.size);
+        if file.data.len() as u64 + data.len() as u64 > size {
+            incoming.remove(&id);
+            return Ok(ChunkOutcome::Done { status: FileStatus::Rejected, name, size });
+        }
+        file.data.extend_from_slice(data);
+        if (file.data.len() as u64) < size {
+            let bytes = file.data.len() as u64;
+            return Ok(ChunkOutcome::Progress(FileTransferProgress { transfer_id: id, direction: TransferDirection::FromBackend, name, bytes, total: size }));
+        }
+        let status = if sha256(&file.data) == file.offer.sha256 {
+            file.complete = true;
+            FileStatus::Verified
+        } else {
+            incoming.remove(&id);
+            FileStatus::ChecksumMismatch
+        };
+        Ok(ChunkOutcome::Done { status, name, size })
+    }
+
+    // A verified received file, handed over once
+    pub fn take_received(&self, id: u32) -> Option<ReceivedFile> {
+        let mut incoming = self.incoming.lock();
+        if !incoming.get(&id).is_some_and(|file| file.complete) {
+            return None;
+        }
+        incoming.remove(&id).map(|file| ReceivedFile { name: file.offer.name, data: file.data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer_of(id: u32, data: &[u8]) -> FileOffer {
+        FileOffer { id, size: data.len() as u64, sha256: sha256(data), name: "avatar.vrm".to_string() }
+    }
+
+    #[test]
+    fn received_files_resume_and_are_verified() {
+        let transfers = FileTransfers::default();
+        let data = vec![7u8; 10];
+        let offer = offer_of(3, &data);
+        assert_eq!(FileOffer::decode(&offer.encode()), Some(offer.clone()));
+
+        assert_eq!(transfers.receive_offer(offer.clone()), Ok(0));
+        assert!(matches!(transfers.receive_chunk(3, 0, &data[..4]), Ok(ChunkOutcome::Progress(FileTransferProgress { bytes: 4, total: 10, .. }))));
+        assert!(transfers.receive_chunk(3, 2, &data[4..]).is_err());
+        // Offered again after a reconnect: only the rest is needed
+        assert_eq!(transfers.receive_offer(offer), Ok(4));
+        assert!(matches!(transfers.receive_chunk(3, 4, &data[4..]), Ok(ChunkOutcome::Done { status: FileStatus::Verified, size: 10, .. })));
+        assert_eq!(transfers.take_received(3).map(|file| file.data), Some(data.clone()));
+        assert!(transfers.take_received(3).is_none());
+
+        let corrupted = FileOffer { sha256: [0; 32], ..offer_of(4, &data) };
+        transfers.receive_offer(corrupted).unwrap();
+        assert!(matches!(transfers.receive_chunk(4, 0, &data), Ok(ChunkOutcome::Done { status: FileStatus::ChecksumMismatch, .. })));
+        assert!(transfers.take_received(4).is_none());
+    }
+
+    #[tokio::test]
+    async fn sent_files_are_chunked_from_the_accepted_offset() {
+        let transfers = FileTransfers::default();
+        let offer = transfers.send("config.json", Bytes::from(vec![1u8; CHUNK_SIZE + 5])).unwrap();
+        assert!(!transfers.accept(offer.id, offer.size + 1));
+        assert!(transfers.accept(offer.id, CHUNK_SIZE as u64));
+        assert_eq!(transfers.next_accepted().await, (offer.id, CHUNK_SIZE as u64));
+        let (chunk, _, total) = transfers.chunk(offer.id, CHUNK_SIZE as u64).unwrap();
+        assert_eq!((chunk.len(), total), (5, offer.size));
+        assert!(transfers.chunk(offer.id, offer.size).is_none());
+        assert_eq!(transfers.unfinished_offers(), std::slice::from_ref(&offer));
+        assert_eq!(transfers.finish_outgoing(offer.id), Some(offer));
+        assert!(transfers.unfinished_offers().is_empty());
+        assert_eq!(decode_done(&encode_done(9, FileStatus::ChecksumMismatch)), Some((9, FileStatus::ChecksumMismatch)));
+    }
+}
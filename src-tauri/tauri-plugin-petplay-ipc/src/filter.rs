@@ -0,0 +1,216 @@
+// --- Transform smoothing ---
+// Optional one-euro filter (Casiez et al. 2012) on incoming poses, per device.
+// It low-passes heavily while a device is still, which hides tracking jitter,
+// and opens up as the device moves, so fast motion doesn't lag. Position is
+// filtered per axis; orientation is slerped towards the new pose, with the
+// cutoff driven by the angular speed. set_transform_filter picks the mode and
+// parameters:
+//
+//   off       poses pass through untouched and no filter state is kept
+//   one-euro  poses (and their matrices) are replaced by the filtered ones
+//   bypass    the filter keeps running but the raw poses are emitted, for
+//             comparing against "one-euro" without a warm-up jump
+use crate::pose::Pose;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    f32::consts::PI,
+};
+
+// Used when two poses carry the same timestamp (or go back in time)
+const FALLBACK_DT_S: f32 = 1.0 / 90.0;
+// Longer gaps than this restart the filter instead of smoothing across them
+const MAX_DT_S: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterMode {
+    #[default]
+    Off,
+    OneEuro,
+    Bypass,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FilterConfig {
+    pub mode: FilterMode,
+    // Cutoff frequency (Hz) at rest; lower means smoother but laggier
+    pub min_cutoff: f32,
+    // How fast the cutoff rises with speed; higher means less lag when moving
+    pub beta: f32,
+    // Cutoff (Hz) of the speed estimate itself
+    pub d_cutoff: f32,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self { mode: FilterMode::Off, min_cutoff: 1.0, beta: 0.5, d_cutoff: 1.0 }
+    }
+}
+
+impl FilterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        let beta_valid = self.beta.is_finite() && self.beta >= 0.0;
+        if !(positive(self.min_cutoff) && positive(self.d_cutoff) && beta_valid) {
+            return Err("Filter cutoffs must be positive and beta must not be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Smoothing factor of a first-order low-pass at `cutoff` Hz for a step of `dt` seconds
+fn alpha(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+// One-euro state of a single scalar
+#[derive(Clone, Copy)]
+struct Scalar {
+    value: f32,
+    speed: f32,
+}
+
+impl Scalar {
+    fn new(value: f32) -> Self {
+        Self { value, speed: 0.0 }
+    }
+
+    fn filter(&mut self, value: f32, dt: f32, config: &FilterConfig) -> f32 {
+        self.speed = lerp(self.speed, (value - self.value) / dt, alpha(config.d_cutoff, dt));
+        let cutoff = config.min_cutoff + config.beta * self.speed.abs();
+        self.value = lerp(self.value, value, alpha(cutoff, dt));
+        self.value
+    }
+}
+
+struct DeviceFilter {
+    position: [Scalar; 3],
+    orientation: [f32; 4],
+    // Smoothed angular speed in rad/s
+    angular_speed: f32,
+    timestamp_us: u64,
+}
+
+impl DeviceFilter {
+    fn new(pose: &Pose) -> Self {
+        Self {
+            position: pose.position.map(Scalar::new),
+            orientation: pose.orientation,
+            angular_speed: 0.0,
+            timestamp_us: pose.timestamp_us,
+        }
+    }
+
+    fn filter(&mut self, pose: &Pose, config: &FilterConfig) -> Pose {
+        let elapsed_s = pose.timestamp_us.saturating_sub(self.timestamp_us) as f32 / 1_000_000.0;
+        if elapsed_s > MAX_DT_S {
+            *self = Self::new(pose);
+            return *pose;
+        }
+        let dt = if elapsed_s > 0.0 { elapsed_s } else { FALLBACK_DT_S };
+        self.timestamp_us = pose.timestamp_us;
+
+        let mut position = [0.0; 3];
+        for ((filtered, axis), value) in position.iter_mut().zip(&mut self.position).zip(pose.position) {
+            *filtered = axis.filter(value, dt, config);
+        }
+
+        // Same scheme on the rotation angle between the last filtered and the new orientation
+        let target = same_hemisphere(self.orientation, pose.orientation);
+        let angle = angle_between(self.orientation, target);
+        self.angular_speed = lerp(self.angular_speed, angle / dt, alpha(config.d_cutoff, dt));
+        let cutoff = config.min_cutoff + config.beta * self.angular_speed;
+        self.orientation = slerp(self.orientation, target, alpha(cutoff, dt));
+
+        Pose { position, orientation: self.orientation, ..*pose }
+    }
+}
+
+// q and -q are the same rotation; pick the one closer to `reference` so we interpolate the short way round
+fn same_hemisphere(reference: [f32; 4], q: [f32; 4]) -> [f32; 4] {
+    if dot(reference, q) < 0.0 {
+        q.map(|c| -c)
+    } else {
+        q
+    }
+}
+
+fn dot(a: [f32; 4], b: [f32; 4]) -> f32 {
+    a.iter().zip(&b).map(|(a, b)| a * b).sum()
+}
+
+fn angle_between(a: [f32; 4], b: [f32; 4]) -> f32 {
+    2.0 * dot(a, b).abs().min(1.0).acos()
+}
+
+fn normalize(q: [f32; 4]) -> [f32; 4] {
+    let length = dot(q, q).sqrt();
+    if length > 0.0 {
+        q.map(|c| c / length)
+    } else {
+        [0.0, 0.0, 0.0, 1.0]
+    }
+}
+
+fn slerp(from: [f32; 4], to: [f32; 4], t: f32) -> [f32; 4] {
+    let cos = dot(from, to).min(1.0);
+    // Nearly identical: a normalized lerp is just as good and avoids dividing by ~0
+    if cos > 0.9995 {
+        return normalize([0, 1, 2, 3].map(|i| lerp(from[i], to[i], t)));
+    }
+    let theta = cos.acos();
+    let sin = theta.sin();
+    let (a, b) = (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin);
+    normalize([0, 1, 2, 3].map(|i| from[i] * a + to[i] * b))
+}
+
+#[derive(Default)]
+pub struct TransformFilter {
+    config: Mutex<FilterConfig>,
+    devices: Mutex<BTreeMap<u32, DeviceFilter>>,
+}
+
+impl TransformFilter {
+    pub fn config(&self) -> FilterConfig {
+        *self.config.lock()
+    }
+
+    pub fn set_config(&self, config: FilterConfig) {
+        *self.config.lock() = config;
+        if config.mode == FilterMode::Off {
+            self.devices.lock().clear();
+        }
+    }
+
+    // Forget the filter state, e.g. after a reconnect so the first pose isn't smoothed against a stale one
+    pub fn reset(&self) {
+        self.devices.lock().clear();
+    }
+
+    // Returns the pose to emit and whether it differs from the one passed in
+    pub fn apply(&self, pose: Pose) -> (Pose, bool) {
+        let config = self.config();
+        if config.mode == FilterMode::Off {
+            return (pose, false);
+        }
+        let filtered = match self.devices.lock().entry(pose.device_id) {
+            Entry::Occupied(mut device) => device.get_mut().filter(&pose, &config),
+            Entry::Vacant(device) => {
+                device.insert(DeviceFilter::new(&pose));
+                pose
+            }
+        };
+        match config.mode {
+            FilterMode::OneEuro => (filtered, true),
+            _ => (pose, false),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// --- Foveated frames ---
+// Away from where the user is looking, the eye can't tell a 4K overlay from a
+// blurry one. With foveation on, the writer averages the frame's periphery
+// into flat blocks before it's compressed: 16x16 pixel tiles near the gaze
+// point stay as they are, and further out each tile is split into 2x2, 4x4,
+// ... blocks of one color, the block size doubling every `falloff` of
+// distance past `radius`. The frame keeps its size and pixel format, so
+// backends need nothing new; flat blocks just compress (LZ4/zstd, deltas)
+// far better than detail does.
+//
+// The gaze point comes from the Gaze messages' overlay hit (see gaze.rs) and
+// only applies to frames for that overlay, while it's fresh and the tracker
+// is confident; anything else is sent at full quality. NV12 frames and
+// frames for the hardware encoder are never foveated.
+//
+//   [foveation]
+//   enabled = true
+//   radius = 0.15   # full quality this far from the gaze point, as a fraction of the frame's longer side
+//   falloff = 0.1   # block size doubles every this much further out
+//   maxBlock = 8    # largest block, in pixels: 2, 4, 8 or 16
+use crate::{buffer_pool::BufferPool, frame_queue::QueuedFrame, gaze::Gaze, pixel_format::PixelFormat};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+pub const TILE_SIZE: usize = 16;
+// Gaze this old means the tracker stopped reporting (or the eyes are closed)
+const GAZE_TIMEOUT: Duration = Duration::from_millis(200);
+// Below this the tracker is guessing; better a sharp frame than a blurred focus
+const MIN_CONFIDENCE: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FoveationConfig {
+    pub enabled: bool,
+    pub radius: f32,
+    pub falloff: f32,
+    pub max_block: u32,
+}
+
+impl Default for FoveationConfig {
+    fn default() -> Self {
+        Self { enabled: false, radius: 0.15, falloff: 0.1, max_block: 8 }
+    }
+}
+
+impl FoveationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=2.0).contains(&self.radius) {
+            return Err("radius must be between 0 and 2".to_string());
+        }
+        if !(self.falloff > 0.0 && self.falloff <= 2.0) {
+            return Err("falloff must be above 0 and at most 2".to_string());
+        }
+        if ![2, 4, 8, 16].contains(&self.max_block) {
+            return Err("maxBlock must be 2, 4, 8 or 16".to_string());
+        }
+        Ok(())
+    }
+
+    // Side of the blocks for a tile this far from the gaze point (relative to the frame's longer side)
+    fn block_size(&self, distance: f32) -> usize {
+        if distance <= self.radius {
+            return 1;
+        }
+        let steps = ((distance - self.radius) / self.falloff) as u32 + 1;
+        (1usize << steps.min(4)).min(self.max_block as usize)
+    }
+}
+
+struct GazePoint {
+    overlay_id: u32,
+    x: f32,
+    y: f32,
+    received: Instant,
+}
+
+pub struct Foveation {
+    config: Mutex<FoveationConfig>,
+    gaze: Mutex<Option<GazePoint>>,
+}
+
+impl Foveation {
+    pub fn new(config: FoveationConfig) -> Self {
+        Self { config: Mutex::new(config), gaze: Mutex::new(None) }
+    }
+
+    pub fn config(&self) -> FoveationConfig {
+        *self.config.lock()
+    }
+
+    pub fn set_config(&self, config: FoveationConfig) {
+        *self.config.lock() = config;
+    }
+
+    // Follow a (validated) gaze sample; one that isn't on any overlay ends foveation until the next
+    pub fn update_gaze(&self, gaze: &Gaze) {
+        *self.gaze.lock() = gaze
+            .overlay_hit
+            .filter(|_| gaze.confidence >= MIN_CONFIDENCE)
+            .map(|hit| GazePoint { overlay_id: hit.overlay_id, x: hit.x, y: hit.y, received: Instant::now() });
+    }
+
+    // Settings and gaze point to foveate a frame for this overlay with, if it should be
+    pub fn target(&self, overlay_id: u32) -> Option<(FoveationConfig, (f32, f32))> {
+        let config = self.config();
+        if !config.enabled {
+            return None;
+        }
+        let gaze = self.gaze.lock();
+        let gaze = gaze.as_ref().filter(|gaze| gaze.overlay_id == overlay_id && gaze.received.elapsed() < GAZE_TIMEOUT)?;
+        Some((config, (gaze.x, gaze.y)))
+    }
+}
+
+// A copy of the frame with its periphery averaged around `center` (0..1 on both axes), or None for NV12
+pub fn foveate(frame: &QueuedFrame, config: &FoveationConfig, center: (f32, f32), buffers: &Arc<BufferPool>) -> Option<QueuedFrame> {
+    let header = frame.header;
+    let bytes_per_pixel = match header.pixel_format {
+        PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+        PixelFormat::Rgb8 => 3,
+        PixelFormat::Nv12 => return None,
+    };
+    let (width, height, stride) = (header.width as usize, header.height as usize, header.stride as usize);
+    let mut pixels = buffers.take(frame.pixels.len());
+    pixels.extend_from_slice(&frame.pixels);
+    let scale = width.max(height) as f32;
+    let (center_x, center_y) = (center.0 * width as f32, center.1 * height as f32);
+    for tile_y in (0..height).step_by(TILE_SIZE) {
+        for tile_x in (0..width).step_by(TILE_SIZE) {
+            let (tile_width, tile_height) = (TILE_SIZE.min(width - tile_x), TILE_SIZE.min(height - tile_y));
+            let dx = tile_x as f32 + tile_width as f32 / 2.0 - center_x;
+            let dy = tile_y as f32 + tile_height as f32 / 2.0 - center_y;
+            let block = config.block_size(dx.hypot(dy) / scale);
+            if block == 1 {
+                continue;
+            }
+            for y in (tile_y..tile_y + tile_height).step_by(block) {
+                for x in (tile_x..tile_x + tile_width).step_by(block) {
+                    let area = Area { x, y, width: block.min(tile_x + tile_width - x), height: block.min(tile_y + tile_height - y) };
+                    average(&mut pixels, stride, bytes_per_pixel, area);
+                }
+            }
+        }
+    }
+    Some(QueuedFrame { header, pixels: pixels.into_bytes() })
+}
+
+struct Area {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+// Fill the area with its average color
+fn average(pixels: &mut [u8], stride: usize, bytes_per_pixel: usize, area: Area) {
+    let row = |y: usize| y * stride + area.x * bytes_per_pixel..y * stride + (area.x + area.width) * bytes_per_pixel;
+    let mut sums = [0u32; 4];
+    for y in area.y..area.y + area.height {
+        for pixel in pixels[row(y)].chunks_exact(bytes_per_pixel) {
+            for (sum, value) in sums.iter_mut().zip(pixel) {
+                *sum += *value as u32;
+            }
+        }
+    }
+    let count = (area.width * area.height) as u32;
+    let color = sums.map(|sum| ((sum + count / 2) / count) as u8);
+    for y in area.y..area.y + area.height {
+        for pixel in pixels[row(y)].chunks_exact_mut(bytes_per_pixel) {
+            pixel.copy_from_slice(&color[..bytes_per_pixel]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_periphery_is_averaged_and_the_gaze_tile_kept() {
+        let (width, height) = (64u32, 32u32);
+        // A checkerboard, which any averaging flattens to gray
+        let pixels: Vec<u8> = (0..width * height).flat_map(|i| if (i % width + i / width) % 2 == 0 { [255; 4] } else { [0, 0, 0, 255] }).collect();
+        let frame = QueuedFrame::test(0, PixelFormat::Rgba8, width, height, width * 4, pixels.clone());
+        let config = FoveationConfig { enabled: true, radius: 0.2, falloff: 0.1, max_block: 4 };
+        let foveated = foveate(&frame, &config, (0.0, 0.0), &Arc::new(BufferPool::default())).unwrap();
+        assert_eq!(foveated.pixels.len(), pixels.len());
+        // The tile under the gaze point is untouched, the far corner is flat
+        assert_eq!(foveated.pixels[..16], pixels[..16]);
+        let far = ((height as usize - 1) * width as usize + width as usize - 1) * 4;
+        assert_eq!(foveated.pixels[far..far + 4], [128, 128, 128, 255]);
+
+        assert_eq!(config.block_size(0.05), 1);
+        assert_eq!(config.block_size(0.25), 2);
+        assert_eq!(config.block_size(5.0), 4);
+        assert!(FoveationConfig { max_block: 3, ..config }.validate().is_err());
+    }
+}
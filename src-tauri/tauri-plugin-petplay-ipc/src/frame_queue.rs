@@ -0,0 +1,239 @@
+// --- Frame queue ---
+// Bounded queue between send_frame_data and the single frame writer task.
+// What happens when the queue is full is decided by the BackpressurePolicy;
+// the default drops the oldest pending frame, since for a live overlay the
+// newest frame is always the one worth sending.
+use crate::protocol::FrameHeader;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use tokio::sync::Notify;
+
+pub const DEFAULT_QUEUE_DEPTH: usize = 2;
+
+// What to do with a new frame when the queue is already full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackpressurePolicy {
+    // Discard the oldest queued frame to make room
+    #[default]
+    DropOldest,
+    // Discard the incoming frame
+    DropNewest,
+    // Make the sender wait until the writer frees a slot
+    Block,
+}
+
+// A frame waiting for the writer. The header is encoded when the frame is
+// written, and the pixels are reference counted, so queueing, dropping and
+// writing a frame never copies or reallocates the pixel data.
+#[derive(Clone)]
+pub struct QueuedFrame {
+    pub header: FrameHeader,
+    pub pixels: Bytes,
+}
+
+#[cfg(test)]
+impl QueuedFrame {
+    // Frame number `sequence` for overlay 0, with rows `stride` bytes apart
+    pub fn test(sequence: u64, pixel_format: crate::pixel_format::PixelFormat, width: u32, height: u32, stride: u32, pixels: impl Into<Bytes>) -> Self {
+        let header = FrameHeader { width, height, sequence, timestamp_us: 0, overlay_id: 0, stride, pixel_format };
+        Self { header, pixels: pixels.into() }
+    }
+}
+
+pub struct FrameQueue {
+    frames: Mutex<VecDeque<QueuedFrame>>,
+    depth: AtomicUsize,
+    policy: Mutex<BackpressurePolicy>,
+    frame_available: Notify,
+    space_available: Notify,
+    // Total frames discarded by the backpressure policy since startup
+    dropped: AtomicU64,
+}
+
+impl FrameQueue {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            frames: Mutex::new(VecDeque::with_capacity(depth)),
+            depth: AtomicUsize::new(depth.max(1)),
+            policy: Mutex::new(BackpressurePolicy::default()),
+            frame_available: Notify::new(),
+            space_available: Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    // Enqueue a frame according to the current policy,
+    // returning how many frames were dropped as a result
+    pub async fn push(&self, frame: QueuedFrame) -> usize {
+        let mut dropped = 0;
+        let mut frame = Some(frame);
+        loop {
+            let depth = self.depth();
+            let policy = self.policy();
+            // Keep the lock in its own scope so the guard is never held across an await
+            let space = {
+                let mut frames = self.frames.lock();
+                if frames.len() < depth {
+                    frames.extend(frame.take());
+                    None
+                } else {
+                    match policy {
+                        BackpressurePolicy::DropOldest => {
+                            while frames.len() >= depth {
+                                frames.pop_front();
+                                dropped += 1;
+                            }
+                            frames.extend(frame.take());
+                            None
+                        }
+                        BackpressurePolicy::DropNewest => {
+                            self.dropped.fetch_add(1, Ordering::Relaxed);
+                            return 1;
+                        }
+                        // Register for the wakeup while still holding the lock so a pop can't slip in between
+                        BackpressurePolicy::Block => Some(self.space_available.notified()),
+                    }
+                }
+            };
+            match space {
+                Some(space) => space.await,
+                None => break,
+            }
+        }
+        self.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+        self.frame_available.notify_one();
+        dropped
+    }
+
+    // Wait for the next frame
+    pub async fn pop(&self) -> QueuedFrame {
+        loop {
+            let frame = self.frames.lock().pop_front();
+            if let Some(frame) = frame {
+                self.space_available.notify_waiters();
+                return frame;
+            }
+            self.frame_available.notified().await;
+        }
+    }
+
+    // Discard every pending frame (e.g. after a disconnect)
+    pub fn clear(&self) -> usize {
+        let mut frames = self.frames.lock();
+        let count = frames.len();
+        frames.clear();
+        drop(frames);
+        self.space_available.notify_waiters();
+        count
+    }
+
+    pub fn policy(&self) -> BackpressurePolicy {
+        *self.policy.lock()
+    }
+
+    pub fn set_policy(&self, policy: BackpressurePolicy) {
+        *self.policy.lock() = policy;
+        // Blocked senders re-check under the new policy
+        self.space_available.notify_waiters();
+    }
+
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.lock().is_empty()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    // Change the queue depth, trimming the oldest frames if it shrank
+    pub fn set_depth(&self, depth: usize) -> usize {
+        let depth = depth.max(1);
+        self.depth.store(depth, Ordering::Relaxed);
+        let mut frames = self.frames.lock();
+        let mut dropped = 0;
+        while frames.len() > depth {
+            frames.pop_front();
+            dropped += 1;
+        }
+        drop(frames);
+        self.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+        self.space_available.notify_waiters();
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel_format::PixelFormat;
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    // Counts allocations per thread, so tests running in parallel don't disturb each other
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations() -> usize {
+        ALLOCATIONS.with(Cell::get)
+    }
+
+    fn frame(sequence: u64, pixels: &Bytes) -> QueuedFrame {
+        QueuedFrame::test(sequence, PixelFormat::Rgba8, 1920, 1080, 1920 * 4, pixels.clone())
+    }
+
+    // 1000 full HD frames through the queue, including drops, with no per-frame allocation or copy
+    #[tokio::test(flavor = "current_thread")]
+    async fn frames_pass_through_without_allocating_or_copying() {
+        const FRAMES: u64 = 1000;
+        let pixels = Bytes::from(vec![0u8; 1920 * 1080 * 4]);
+        let queue = FrameQueue::new(DEFAULT_QUEUE_DEPTH);
+        // Warm up: grows the queue to its capacity and turns the pixel buffer into a shared one
+        for sequence in 0..=DEFAULT_QUEUE_DEPTH as u64 {
+            queue.push(frame(sequence, &pixels)).await;
+        }
+        queue.clear();
+
+        let before = allocations();
+        for sequence in 0..FRAMES {
+            // One more than fits, so drop-oldest discards a frame every round
+            for _ in 0..=DEFAULT_QUEUE_DEPTH {
+                queue.push(frame(sequence, &pixels)).await;
+            }
+            let popped = queue.pop().await;
+            assert_eq!(popped.pixels.as_ptr(), pixels.as_ptr());
+            queue.clear();
+        }
+
+        assert_eq!(allocations() - before, 0);
+        assert_eq!(queue.dropped_frames(), FRAMES + 1);
+    }
+}
@@ -0,0 +1,152 @@
+// --- Frame rate limit ---
+// The webview may call send_frame_data far more often than the compositor
+// shows frames (a 144Hz monitor driving a 90Hz headset, a page rendering as
+// fast as it can). With a target FPS set, a frame arriving less than one
+// frame interval after the previous one of the same overlay isn't queued:
+// it's held back and goes out once the interval is up, and a newer frame
+// arriving meanwhile replaces it (latest wins). The queue, sinks and
+// recorder only ever see the target rate, and the effective rate is the fps
+// in get_pipe_metrics. A target of 0 lets every frame through.
+use crate::frame_queue::QueuedFrame;
+use parking_lot::Mutex;
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+// Beyond this, limiting would cost more than it saves
+pub const MAX_TARGET_FPS: u32 = 1000;
+
+#[derive(Default)]
+struct OverlaySlot {
+    last_released: Option<Instant>,
+    held: Option<QueuedFrame>,
+}
+
+pub struct FrameRateLimiter {
+    target_fps: AtomicU32,
+    overlays: Mutex<BTreeMap<u32, OverlaySlot>>,
+    // Wakes the release task when a frame is held back
+    held: Notify,
+    // Held frames replaced by a newer one before they went out
+    coalesced: AtomicU64,
+}
+
+impl FrameRateLimiter {
+    pub fn new(target_fps: u32) -> Self {
+        Self {
+            target_fps: AtomicU32::new(target_fps.min(MAX_TARGET_FPS)),
+            overlays: Mutex::new(BTreeMap::new()),
+            held: Notify::new(),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.target_fps.load(Ordering::Relaxed)
+    }
+
+    // Held frames go out at the new rate (or right away when the limit is lifted)
+    pub fn set_target_fps(&self, target_fps: u32) {
+        self.target_fps.store(target_fps.min(MAX_TARGET_FPS), Ordering::Relaxed);
+        self.held.notify_one();
+    }
+
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        match self.target_fps() {
+            0 => None,
+            fps => Some(Duration::from_secs(1) / fps),
+        }
+    }
+
+    // The frame if it may go out now; otherwise it's held for take_due
+    pub fn admit(&self, frame: QueuedFrame) -> Option<QueuedFrame> {
+        let interval = self.interval()?;
+        let now = Instant::now();
+        let mut overlays = self.overlays.lock();
+        let slot = overlays.entry(frame.header.overlay_id).or_default();
+        if slot.held.is_none() && slot.last_released.is_none_or(|released| now >= released + interval) {
+            slot.last_released = Some(now);
+            return Some(frame);
+        }
+        if slot.held.replace(frame).is_some() {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+        drop(overlays);
+        self.held.notify_one();
+        None
+    }
+
+    // Held frames whose interval is up, and when the next of the others is due
+    pub fn take_due(&self) -> (Vec<QueuedFrame>, Option<Instant>) {
+        let interval = self.interval();
+        let now = Instant::now();
+        let mut due = Vec::new();
+        let mut next = None::<Instant>;
+        for slot in self.overlays.lock().values_mut() {
+            if slot.held.is_none() {
+                continue;
+            }
+            let release_at = match (interval, slot.last_released) {
+                (Some(interval), Some(released)) => released + interval,
+                _ => now,
+            };
+            if release_at <= now {
+                slot.last_released = Some(now);
+                due.extend(slot.held.take());
+            } else {
+                next = Some(next.map_or(release_at, |next| next.min(release_at)));
+            }
+        }
+        (due, next)
+    }
+
+    // Resolves once a frame has been held (or the target changed) since the last call
+    pub async fn frame_held(&self) {
+        self.held.notified().await;
+    }
+
+    // Forget held frames, returning how many there were
+    pub fn clear(&self) -> usize {
+        self.overlays.lock().values_mut().filter_map(|slot| slot.held.take()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pixel_format::PixelFormat;
+    use bytes::Bytes;
+
+    fn frame(sequence: u64) -> QueuedFrame {
+        QueuedFrame::test(sequence, PixelFormat::Rgba8, 1, 1, 4, Bytes::from_static(&[0; 4]))
+    }
+
+    #[test]
+    fn frames_within_an_interval_are_coalesced_latest_wins() {
+        let limiter = FrameRateLimiter::new(10);
+
+        assert!(limiter.admit(frame(1)).is_some());
+        for sequence in 2..=5 {
+            assert!(limiter.admit(frame(sequence)).is_none());
+        }
+        let (due, next) = limiter.take_due();
+        assert!(due.is_empty());
+        assert!(next.is_some());
+        assert_eq!(limiter.coalesced(), 3);
+
+        std::thread::sleep(Duration::from_millis(110));
+        let (due, next) = limiter.take_due();
+        assert_eq!(due.iter().map(|frame| frame.header.sequence).collect::<Vec<_>>(), [5]);
+        assert!(next.is_none());
+
+        limiter.set_target_fps(0);
+        assert!(limiter.admit(frame(6)).is_some());
+    }
+}
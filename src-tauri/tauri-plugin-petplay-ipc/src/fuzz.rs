@@ -0,0 +1,180 @@
+// --- Fuzzing entry points ---
+// Only built with the "fuzzing" feature, for the cargo-fuzz targets in fuzz/.
+// Each entry point takes arbitrary bytes as if a misbehaving backend had sent
+// them on one pipe, splits them into messages the way the reader tasks do and
+// runs every payload through the decoder its reader would use. Any panic is
+// a bug: it would take the reader task (and its pipe) down with it. So is a
+// decoder that doesn't consume what it reports, which would leave the reader
+// spinning on the same bytes instead of dropping the connection.
+//
+//   cd fuzz && cargo +nightly fuzz run transform_pipe
+use crate::{
+    ack, control, device_status,
+    file_transfer::{self, FileOffer, FileTransfers, CHUNK_HEADER_SIZE},
+    gaze::Gaze,
+    hands,
+    handshake::BackendInfo,
+    input, overlays,
+    playspace::Playspace,
+    pose::{self, Pose},
+    protocol::{self, FrameHeader, MessageType, HEADER_SIZE, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+    validation,
+    video::{self, VideoHeader, VIDEO_HEADER_SIZE},
+    vr_pointer,
+};
+
+// Hand each complete message at the start of `data` to `handle`, stopping at the first error or partial message
+fn messages(mut data: &[u8], mut handle: impl FnMut(MessageType, &[u8])) {
+    while let Ok(Some((message, consumed))) = protocol::decode_message(data) {
+        assert_eq!(consumed, HEADER_SIZE + message.payload.len(), "decode_message consumed more or less than the message");
+        handle(message.header.message_type, &message.payload);
+        data = &data[consumed..];
+    }
+}
+
+// What the backend sends on the frame pipe: the HelloAck, control messages and acks
+pub fn frame_pipe(data: &[u8]) {
+    messages(data, |message_type, payload| match message_type {
+        MessageType::HelloAck => {
+            if let Ok(info) = BackendInfo::decode_ack(payload) {
+                assert!((MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&info.protocol_version));
+            }
+        }
+        MessageType::Control => {
+            let _ = control::decode(payload);
+        }
+        MessageType::FrameAck => {
+            let _ = ack::decode(payload);
+        }
+        _ => {}
+    });
+}
+
+// Poses, single transforms and overlay placements, checked like the transform reader does
+pub fn transform_pipe(data: &[u8]) {
+    messages(data, |message_type, payload| match message_type {
+        MessageType::Poses => {
+            for pose in pose::decode_records(payload).unwrap_or_default() {
+                if validation::check_pose(&pose).is_ok() {
+                    let _ = pose.to_matrix();
+                }
+            }
+        }
+        MessageType::Transform => {
+            if let Some(matrix) = protocol::decode_transform(payload) {
+                if validation::check_matrix(&matrix).is_ok() {
+                    let _ = Pose::from_matrix(&matrix);
+                }
+            }
+        }
+        MessageType::OverlayTransform => {
+            if let Ok((_, matrix)) = overlays::decode_transform(payload) {
+                let _ = validation::check_matrix(&matrix);
+            }
+        }
+        MessageType::HandSkeleton => {
+            for skeleton in hands::decode_records(payload).unwrap_or_default() {
+                if skeleton.tracked && validation::check_hand(&skeleton).is_ok() {
+                    assert_eq!(hands::decode_records(&skeleton.encode_record()), Ok(vec![skeleton]), "hand skeleton doesn't round trip");
+                }
+            }
+        }
+        MessageType::DeviceStatus => {
+            // Invalid UTF-8 in the firmware version comes back longer, and may not fit again
+            for status in device_status::decode_records(payload).unwrap_or_default().into_iter().filter(|status| status.firmware.len() <= u8::MAX as usize) {
+                assert_eq!(device_status::decode_records(&status.encode_record()), Ok(vec![status]), "device status doesn't round trip");
+            }
+        }
+        MessageType::Playspace => {
+            if let Ok(playspace) = Playspace::decode(payload) {
+                if validation::check_playspace(&playspace).is_ok() {
+                    assert_eq!(Playspace::decode(&playspace.encode()), Ok(playspace), "playspace doesn't round trip");
+                }
+            }
+        }
+        MessageType::Gaze => {
+            if let Ok(gaze) = Gaze::decode(payload) {
+                let _ = validation::check_gaze(&gaze);
+            }
+        }
+        _ => {}
+    });
+}
+
+pub fn input_pipe(data: &[u8]) {
+    messages(data, |message_type, payload| {
+        if message_type == MessageType::ControllerInput {
+            let _ = input::decode_records(payload);
+        }
+    });
+}
+
+// A frame header as any protocol version; the fields it has must come back out of encode unchanged
+pub fn frame_header(data: &[u8]) {
+    for version in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+        let Some(header) = FrameHeader::decode(data, version) else { continue };
+        // Up to the timestamp on v1, the overlay id on v2 and the pixel format on v3; the rest is reserved
+        let meaningful = match version {
+            1 => 24,
+            2 => 28,
+            _ => 33,
+        };
+        assert_eq!(&header.encode()[..meaningful], &data[..meaningful], "v{} frame header doesn't round-trip", version);
+    }
+}
+
+// File offers and chunks received the way the files reader does, plus the sender's messages
+pub fn files_pipe(data: &[u8]) {
+    let transfers = FileTransfers::default();
+    messages(data, |message_type, payload| match message_type {
+        MessageType::FileOffer => {
+            if let Some(offer) = FileOffer::decode(payload) {
+                let _ = transfers.receive_offer(offer);
+            }
+        }
+        MessageType::FileChunk => {
+            if let Some((id, offset)) = file_transfer::decode_position(payload) {
+                let _ = transfers.receive_chunk(id, offset, &payload[CHUNK_HEADER_SIZE..]);
+            }
+        }
+        MessageType::FileAccept => {
+            if let Some((id, offset)) = file_transfer::decode_position(payload) {
+                let _ = transfers.accept(id, offset);
+            }
+        }
+        MessageType::FileDone => {
+            let _ = file_transfer::decode_done(payload);
+        }
+        _ => {}
+    });
+}
+
+// Video frames, decoded as if a channel were subscribed
+pub fn video_pipe(data: &[u8]) {
+    messages(data, |message_type, payload| {
+        if message_type == MessageType::VideoFrame {
+            if let Some(header) = VideoHeader::decode(payload) {
+                if let Ok(image) = video::decode(header.codec, &payload[VIDEO_HEADER_SIZE..]) {
+                    assert_eq!(image.pixels.len(), image.width as usize * image.height as usize * 4, "decoded image isn't packed RGBA");
+                }
+            }
+        }
+    });
+}
+
+pub fn pointer_pipe(data: &[u8]) {
+    messages(data, |message_type, payload| match message_type {
+        MessageType::VrPointer => {
+            if let Ok(event) = vr_pointer::decode_pointer(payload) {
+                assert!((0.0..=1.0).contains(&event.x) && (0.0..=1.0).contains(&event.y), "pointer outside the overlay");
+                vr_pointer::pointer_script(&event);
+            }
+        }
+        MessageType::VrKey => {
+            if let Ok(event) = vr_pointer::decode_key(payload) {
+                vr_pointer::key_script(&event);
+            }
+        }
+        _ => {}
+    });
+}
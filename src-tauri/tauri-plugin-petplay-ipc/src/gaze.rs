@@ -0,0 +1,189 @@
+// --- Eye tracking ---
+// Headsets with eye tracking report where the user is looking in Gaze
+// messages on the transform pipe, at the tracker's rate (often 120-250 Hz).
+// They're validated like poses (see validation.rs) and emitted as
+// "gaze-update", at most rateHz times a second: a sample arriving sooner
+// after the previous emit is dropped, and since trackers sample continuously
+// the next one is never far behind. 0 emits every sample. Every valid
+// sample, throttled or not, also moves the foveation center (see
+// foveation.rs).
+//
+//   [gaze]
+//   rateHz = 30
+//
+// Gaze payload (little endian):
+//   [0..12)  combined gaze origin x, y, z in meters, in the same space as poses (f32)
+//   [12..24) combined gaze direction x, y, z, unit length (f32)
+//   [24..28) confidence, 0 (no idea) to 1 (f32)
+//   [28..32) left eye openness, 0 (closed) to 1 (wide open) (f32)
+//   [32..36) right eye openness (f32)
+//   [36..44) timestamp, microseconds since the Unix epoch (u64)
+// Optionally followed by where the gaze meets an overlay, which only the
+// backend knows since it places the quads:
+//   [44..48) overlay id, or 0xFFFFFFFF if the gaze isn't on any overlay (u32)
+//   [48..52) x on the overlay, 0 at the left edge to 1 at the right (f32)
+//   [52..56) y on the overlay, 0 at the top edge to 1 at the bottom (f32)
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant},
+};
+
+pub const GAZE_PAYLOAD_SIZE: usize = 56;
+// Without the overlay hit
+pub const MIN_GAZE_PAYLOAD_SIZE: usize = 44;
+const NO_OVERLAY: u32 = u32::MAX;
+// Plenty for moving focus around the UI
+pub const DEFAULT_GAZE_RATE_HZ: u32 = 30;
+const MAX_GAZE_RATE_HZ: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GazeConfig {
+    // Max gaze-update events per second; 0 emits every sample
+    pub rate_hz: u32,
+}
+
+impl Default for GazeConfig {
+    fn default() -> Self {
+        Self { rate_hz: DEFAULT_GAZE_RATE_HZ }
+    }
+}
+
+impl GazeConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rate_hz > MAX_GAZE_RATE_HZ {
+            return Err(format!("rateHz must be at most {}", MAX_GAZE_RATE_HZ));
+        }
+        Ok(())
+    }
+}
+
+// Payload of "gaze-update"
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Gaze {
+    pub origin: [f32; 3],
+    pub direction: [f32; 3],
+    pub confidence: f32,
+    pub left_openness: f32,
+    pub right_openness: f32,
+    pub timestamp_us: u64,
+    pub overlay_hit: Option<OverlayHit>,
+}
+
+// Where the gaze meets an overlay
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayHit {
+    pub overlay_id: u32,
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Gaze {
+    pub fn decode(payload: &[u8]) -> Result<Self, String> {
+        if payload.len() < MIN_GAZE_PAYLOAD_SIZE {
+            return Err(format!("gaze message of {} bytes is shorter than {}", payload.len(), MIN_GAZE_PAYLOAD_SIZE));
+        }
+        let float = |index: usize| LittleEndian::read_f32(&payload[index * 4..index * 4 + 4]);
+        let overlay_hit = match payload.get(44..GAZE_PAYLOAD_SIZE) {
+            Some(hit) if LittleEndian::read_u32(&hit[0..4]) != NO_OVERLAY => {
+                Some(OverlayHit { overlay_id: LittleEndian::read_u32(&hit[0..4]), x: float(12), y: float(13) })
+            }
+            _ => None,
+        };
+        Ok(Self {
+            origin: [float(0), float(1), float(2)],
+            direction: [float(3), float(4), float(5)],
+            confidence: float(6),
+            left_openness: float(7),
+            right_openness: float(8),
+            timestamp_us: LittleEndian::read_u64(&payload[36..44]),
+            overlay_hit,
+        })
+    }
+
+    pub fn encode(&self) -> [u8; GAZE_PAYLOAD_SIZE] {
+        let mut payload = [0u8; GAZE_PAYLOAD_SIZE];
+        let scalars = [self.confidence, self.left_openness, self.right_openness];
+        let values = self.origin.iter().chain(&self.direction).chain(&scalars);
+        for (index, value) in values.enumerate() {
+            LittleEndian::write_f32(&mut payload[index * 4..index * 4 + 4], *value);
+        }
+        LittleEndian::write_u64(&mut payload[36..44], self.timestamp_us);
+        let hit = self.overlay_hit.unwrap_or(OverlayHit { overlay_id: NO_OVERLAY, ..OverlayHit::default() });
+        LittleEndian::write_u32(&mut payload[44..48], hit.overlay_id);
+        LittleEndian::write_f32(&mut payload[48..52], hit.x);
+        LittleEndian::write_f32(&mut payload[52..56], hit.y);
+        payload
+    }
+
+    // Confidence, openness and the overlay hit pulled into 0..1, for trackers that overshoot a little
+    pub fn clamped(self) -> Self {
+        Self {
+            confidence: self.confidence.clamp(0.0, 1.0),
+            left_openness: self.left_openness.clamp(0.0, 1.0),
+            right_openness: self.right_openness.clamp(0.0, 1.0),
+            overlay_hit: self.overlay_hit.map(|hit| OverlayHit { x: hit.x.clamp(0.0, 1.0), y: hit.y.clamp(0.0, 1.0), ..hit }),
+            ..self
+        }
+    }
+}
+
+// Drops samples arriving sooner than 1/rate after the last one let through
+pub struct GazeThrottle {
+    rate_hz: AtomicU32,
+    last_emit: Mutex<Option<Instant>>,
+}
+
+impl GazeThrottle {
+    pub fn new(rate_hz: u32) -> Self {
+        Self { rate_hz: AtomicU32::new(rate_hz), last_emit: Mutex::new(None) }
+    }
+
+    pub fn set_rate_hz(&self, rate_hz: u32) {
+        self.rate_hz.store(rate_hz, Ordering::Relaxed);
+    }
+
+    // Whether a sample arriving at `now` should be emitted
+    pub fn admit(&self, now: Instant) -> bool {
+        let rate_hz = self.rate_hz.load(Ordering::Relaxed);
+        let mut last_emit = self.last_emit.lock();
+        if rate_hz != 0 {
+            let interval = Duration::from_secs_f64(1.0 / rate_hz as f64);
+            if last_emit.is_some_and(|last| now.saturating_duration_since(last) < interval) {
+                return false;
+            }
+        }
+        *last_emit = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaze_round_trips_and_is_throttled_to_the_rate() {
+        let hit = OverlayHit { overlay_id: 2, x: 0.25, y: 0.75 };
+        let gaze = Gaze { origin: [0.0, 1.6, 0.0], direction: [0.0, 0.0, -1.0], confidence: 1.2, left_openness: 0.5, right_openness: 1.0, timestamp_us: 42, overlay_hit: Some(hit) };
+        let decoded = Gaze::decode(&gaze.encode()).unwrap();
+        assert_eq!(decoded, gaze);
+        assert_eq!(decoded.clamped().confidence, 1.0);
+        // Backends that don't report overlay hits
+        assert_eq!(Gaze::decode(&gaze.encode()[..MIN_GAZE_PAYLOAD_SIZE]).unwrap().overlay_hit, None);
+        assert!(Gaze::decode(&gaze.encode()[..40]).is_err());
+
+        let throttle = GazeThrottle::new(10);
+        let start = Instant::now();
+        assert!(throttle.admit(start));
+        assert!(!throttle.admit(start + Duration::from_millis(50)));
+        assert!(throttle.admit(start + Duration::from_millis(100)));
+        throttle.set_rate_hz(0);
+        assert!(throttle.admit(start + Duration::from_millis(101)));
+    }
+}
@@ -0,0 +1,59 @@
+// --- Shared GPU texture frames ---
+// Instead of reading pixels back to the CPU, a producer that owns a D3D11
+// texture created with D3D11_RESOURCE_MISC_SHARED(_NTHANDLE | _KEYEDMUTEX)
+// can hand its DXGI shared handle to share_gpu_texture. Only the handle and
+// a little metadata travel over the frame pipe; the backend opens the same
+// texture with OpenSharedResource(1) and the pixels never leave the GPU.
+// With a keyed mutex the producer releases the texture with `keyed_mutex_key`
+// and the backend acquires it with that key before sampling. NT handles are
+// process-local: the backend duplicates them out of this process
+// (GetNamedPipeClientProcessId + DuplicateHandle) before opening them.
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Deserialize;
+
+pub const SHARED_TEXTURE_SIZE: usize = 48;
+
+// Payload flags
+pub const FLAG_KEYED_MUTEX: u32 = 1 << 0;
+pub const FLAG_NT_HANDLE: u32 = 1 << 1;
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedTexture {
+    // HANDLE value from IDXGIResource(1)::GetSharedHandle / CreateSharedHandle
+    pub handle: u64,
+    pub width: u32,
+    pub height: u32,
+    // DXGI_FORMAT value, e.g. 28 = R8G8B8A8_UNORM, 87 = B8G8R8A8_UNORM
+    pub dxgi_format: u32,
+    // Key the backend must use for IDXGIKeyedMutex::AcquireSync, if the texture has one
+    #[serde(default)]
+    pub keyed_mutex_key: Option<u64>,
+    // The handle comes from CreateSharedHandle (NT handle) rather than GetSharedHandle
+    #[serde(default)]
+    pub nt_handle: bool,
+}
+
+impl SharedTexture {
+    // Layout: handle u64, width u32, height u32, format u32, flags u32,
+    // acquire key u64, sequence u64, timestamp us u64 (all little endian)
+    pub fn encode(&self, sequence: u64, timestamp_us: u64) -> [u8; SHARED_TEXTURE_SIZE] {
+        let mut flags = 0;
+        if self.keyed_mutex_key.is_some() {
+            flags |= FLAG_KEYED_MUTEX;
+        }
+        if self.nt_handle {
+            flags |= FLAG_NT_HANDLE;
+        }
+        let mut bytes = [0u8; SHARED_TEXTURE_SIZE];
+        LittleEndian::write_u64(&mut bytes[0..8], self.handle);
+        LittleEndian::write_u32(&mut bytes[8..12], self.width);
+        LittleEndian::write_u32(&mut bytes[12..16], self.height);
+        LittleEndian::write_u32(&mut bytes[16..20], self.dxgi_format);
+        LittleEndian::write_u32(&mut bytes[20..24], flags);
+        LittleEndian::write_u64(&mut bytes[24..32], self.keyed_mutex_key.unwrap_or(0));
+        LittleEndian::write_u64(&mut bytes[32..40], sequence);
+        LittleEndian::write_u64(&mut bytes[40..48], timestamp_us);
+        bytes
+    }
+}
@@ -0,0 +1,245 @@
+// --- Hand tracking ---
+// With hand tracking on, the backend sends a HandSkeleton message on the
+// transform pipe per tracking update: one record per hand, length-prefixed
+// like pose records (see pose.rs), so the joint count can vary and fields can
+// be appended later. Joints come in the OpenXR XR_EXT_hand_tracking order, 26
+// of them: palm, wrist, then thumb metacarpal, proximal, distal and tip, then
+// index, middle, ring and little finger metacarpal, proximal, intermediate,
+// distal and tip.
+//
+// At 26 joints a hand is too much for JSON at tracking rate, so skeletons
+// only go out as raw bytes over a channel (subscribe_hands), optionally for
+// one hand only, in the record body layout below. The frontend reads the
+// header with a DataView and the joints with
+// new Float32Array(buffer, 16, jointCount * 8). "hand-tracking-changed" is
+// emitted when a hand starts or stops being tracked.
+//
+// Record body (little endian):
+//   [0]      hand (u8, 0 = left, 1 = right)
+//   [1]      flags (u8, bit 0 = tracked; joints of an untracked hand are stale)
+//   [2..4)   joint count (u16)
+//   [4..8)   reserved, 0
+//   [8..16)  timestamp, microseconds since the Unix epoch (u64)
+//   [16..)   per joint, 32 bytes: position x, y, z in meters, orientation
+//            quaternion x, y, z, w, radius in meters (8 x f32)
+use crate::pose::RECORD_LENGTH_SIZE;
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::{Channel, InvokeResponseBody};
+
+pub const HAND_HEADER_SIZE: usize = 16;
+pub const JOINT_SIZE: usize = 32;
+// OpenXR's joint set; other counts are passed through as sent
+pub const OPENXR_JOINT_COUNT: usize = 26;
+const MAX_JOINTS: usize = 64;
+const FLAG_TRACKED: u8 = 1 << 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Left),
+            1 => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    // As in transform-invalid sources, e.g. "hand-left"
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Right => "right",
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Self::Left => 0,
+            Self::Right => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Joint {
+    pub position: [f32; 3],
+    pub orientation: [f32; 4],
+    pub radius: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HandSkeleton {
+    pub hand: Hand,
+    pub tracked: bool,
+    pub timestamp_us: u64,
+    pub joints: Vec<Joint>,
+}
+
+// Payload of "hand-tracking-changed"
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandTrackingChanged {
+    pub hand: Hand,
+    pub tracked: bool,
+}
+
+impl HandSkeleton {
+    // Record body as sent to subscribers, without the length prefix or anything past the joints
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = vec![0u8; HAND_HEADER_SIZE + self.joints.len() * JOINT_SIZE];
+        body[0] = self.hand.code();
+        body[1] = if self.tracked { FLAG_TRACKED } else { 0 };
+        LittleEndian::write_u16(&mut body[2..4], self.joints.len() as u16);
+        LittleEndian::write_u64(&mut body[8..16], self.timestamp_us);
+        for (joint, bytes) in self.joints.iter().zip(body[HAND_HEADER_SIZE..].chunks_exact_mut(JOINT_SIZE)) {
+            LittleEndian::write_f32_into(&joint.position, &mut bytes[0..12]);
+            LittleEndian::write_f32_into(&joint.orientation, &mut bytes[12..28]);
+            LittleEndian::write_f32(&mut bytes[28..32], joint.radius);
+        }
+        body
+    }
+
+    // Length-prefixed record, as in a HandSkeleton message
+    pub fn encode_record(&self) -> Vec<u8> {
+        let body = self.encode();
+        let mut record = vec![0u8; RECORD_LENGTH_SIZE];
+        LittleEndian::write_u16(&mut record, body.len() as u16);
+        record.extend_from_slice(&body);
+        record
+    }
+
+    fn decode(body: &[u8]) -> Result<Self, String> {
+        let hand = Hand::from_code(body[0]).ok_or_else(|| format!("unknown hand {}", body[0]))?;
+        let joint_count = LittleEndian::read_u16(&body[2..4]) as usize;
+        if joint_count > MAX_JOINTS {
+            return Err(format!("{} joints is more than {}", joint_count, MAX_JOINTS));
+        }
+        let joints = body
+            .get(HAND_HEADER_SIZE..HAND_HEADER_SIZE + joint_count * JOINT_SIZE)
+            .ok_or_else(|| format!("record of {} bytes is too short for {} joints", body.len(), joint_count))?
+            .chunks_exact(JOINT_SIZE)
+            .map(|bytes| {
+                let mut joint = Joint::default();
+                LittleEndian::read_f32_into(&bytes[0..12], &mut joint.position);
+                LittleEndian::read_f32_into(&bytes[12..28], &mut joint.orientation);
+                joint.radius = LittleEndian::read_f32(&bytes[28..32]);
+                joint
+            })
+            .collect();
+        Ok(Self { hand, tracked: body[1] & FLAG_TRACKED != 0, timestamp_us: LittleEndian::read_u64(&body[8..16]), joints })
+    }
+}
+
+pub fn decode_records(payload: &[u8]) -> Result<Vec<HandSkeleton>, String> {
+    let mut hands = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        if rest.len() < RECORD_LENGTH_SIZE {
+            return Err(format!("{} trailing bytes after last record", rest.len()));
+        }
+        let length = LittleEndian::read_u16(&rest[..RECORD_LENGTH_SIZE]) as usize;
+        if length < HAND_HEADER_SIZE {
+            return Err(format!("record of {} bytes is shorter than {}", length, HAND_HEADER_SIZE));
+        }
+        let body = rest
+            .get(RECORD_LENGTH_SIZE..RECORD_LENGTH_SIZE + length)
+            .ok_or_else(|| format!("record of {} bytes runs past end of payload", length))?;
+        hands.push(HandSkeleton::decode(body)?);
+        rest = &rest[RECORD_LENGTH_SIZE + length..];
+    }
+    Ok(hands)
+}
+
+struct Subscriber {
+    channel: Channel<InvokeResponseBody>,
+    // Only this hand, or both if None
+    hand: Option<Hand>,
+}
+
+#[derive(Default)]
+pub struct HandSubscribers {
+    subscribers: Mutex<Vec<Subscriber>>,
+    // Whether each hand (left, right) was tracked in its last skeleton
+    tracked: Mutex<[bool; 2]>,
+}
+
+impl HandSubscribers {
+    // Returns the subscription id (the channel id) for unsubscribe
+    pub fn subscribe(&self, channel: Channel<InvokeResponseBody>, hand: Option<Hand>) -> u32 {
+        let id = channel.id();
+        self.subscribers.lock().push(Subscriber { channel, hand });
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        let mut subscribers = self.subscribers.lock();
+        let before = subscribers.len();
+        subscribers.retain(|subscriber| subscriber.channel.id() != id);
+        subscribers.len() != before
+    }
+
+    // Send a skeleton to every interested subscriber, dropping those whose window is gone.
+    // Returns the change if the hand started or stopped being tracked.
+    pub fn send(&self, skeleton: &HandSkeleton) -> Option<HandTrackingChanged> {
+        let changed = {
+            let mut tracked = self.tracked.lock();
+            let was_tracked = std::mem::replace(&mut tracked[skeleton.hand.code() as usize], skeleton.tracked);
+            (was_tracked != skeleton.tracked).then_some(HandTrackingChanged { hand: skeleton.hand, tracked: skeleton.tracked })
+        };
+        let mut subscribers = self.subscribers.lock();
+        let mut message = None;
+        subscribers.retain(|subscriber| {
+            if subscriber.hand.is_some_and(|hand| hand != skeleton.hand) {
+                return true;
+            }
+            let message = message.get_or_insert_with(|| skeleton.encode());
+            subscriber.channel.send(InvokeResponseBody::Raw(message.clone())).is_ok()
+        });
+        changed
+    }
+
+    // Both hands count as untracked again, e.g. after the transform pipe reconnects
+    pub fn reset(&self) -> Vec<HandTrackingChanged> {
+        let mut tracked = self.tracked.lock();
+        let lost = [Hand::Left, Hand::Right]
+            .into_iter()
+            .filter(|hand| tracked[hand.code() as usize])
+            .map(|hand| HandTrackingChanged { hand, tracked: false })
+            .collect();
+        *tracked = [false; 2];
+        lost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_records_round_trip_and_track_changes() {
+        let joint = Joint { position: [0.1, 1.2, -0.3], orientation: [0.0, 0.0, 0.0, 1.0], radius: 0.01 };
+        let left = HandSkeleton { hand: Hand::Left, tracked: true, timestamp_us: 7, joints: vec![joint; OPENXR_JOINT_COUNT] };
+        let right = HandSkeleton { hand: Hand::Right, tracked: false, timestamp_us: 7, joints: Vec::new() };
+        let mut payload = left.encode_record();
+        payload.extend_from_slice(&right.encode_record());
+        assert_eq!(decode_records(&payload).unwrap(), [left.clone(), right]);
+        assert!(decode_records(&payload[..payload.len() - 1]).is_err());
+
+        // A record claiming more joints than it holds
+        let mut short = left.encode_record();
+        LittleEndian::write_u16(&mut short[RECORD_LENGTH_SIZE + 2..RECORD_LENGTH_SIZE + 4], 27);
+        assert!(decode_records(&short).is_err());
+
+        let subscribers = HandSubscribers::default();
+        assert!(subscribers.send(&left).is_some_and(|change| change.tracked));
+        assert!(subscribers.send(&left).is_none());
+        assert_eq!(subscribers.reset().len(), 1);
+    }
+}
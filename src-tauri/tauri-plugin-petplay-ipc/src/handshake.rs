@@ -0,0 +1,178 @@
+// --- Connection handshake ---
+// Right after the frame pipe opens we send a Hello listing the protocol
+// versions and features this app supports and wait (briefly) for the
+// backend's HelloAck with the version it picked and the features it accepts.
+// Anything not acknowledged is never used on that connection. A backend that
+// never answers is treated as speaking version 1 with nothing optional.
+//
+// Hello payload:
+//   [0..4)  capability bits (u32 LE)
+//   [4]     lowest protocol version supported
+//   [5]     highest protocol version supported
+//   [6..8)  reserved
+// HelloAck payload (only the capabilities are required, older backends stop there):
+//   [0..4)  accepted capability bits (u32 LE)
+//   [4]     protocol version the backend picked
+//   [5]     reserved
+//   [6..8)  backend name length (u16 LE), followed by the name (UTF-8)
+use crate::{
+    pixel_format::PixelFormat,
+    protocol::{self, MessageType, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+};
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+use std::{io, time::Duration};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+// Capability bits exchanged in Hello / HelloAck
+pub const CAP_LZ4: u32 = 1 << 0;
+pub const CAP_ZSTD: u32 = 1 << 1;
+pub const CAP_GPU_TEXTURE: u32 = 1 << 2;
+// Backend answers Ping with Pong (see heartbeat.rs)
+pub const CAP_HEARTBEAT: u32 = 1 << 3;
+// Backend sends Poses records for several devices instead of single Transform matrices
+pub const CAP_MULTI_DEVICE: u32 = 1 << 4;
+// Backend takes these pixel formats as is (v3 frame header); RGBA8 is always accepted
+pub const CAP_FORMAT_BGRA8: u32 = 1 << 5;
+pub const CAP_FORMAT_RGB8: u32 = 1 << 6;
+pub const CAP_FORMAT_NV12: u32 = 1 << 7;
+// Backend honours the row stride in the v3 frame header instead of needing packed rows
+pub const CAP_ROW_STRIDE: u32 = 1 << 8;
+// Backend applies delta frames (FLAG_DELTA, see delta.rs); implies it decodes LZ4
+pub const CAP_DELTA: u32 = 1 << 9;
+// Backend may send Control messages on the frame pipe (see control.rs)
+pub const CAP_CONTROL: u32 = 1 << 10;
+// Backend acknowledges the frames it has taken with FrameAck (see ack.rs)
+pub const CAP_FRAME_ACK: u32 = 1 << 11;
+// Backend decompresses frames sent in tiles (FLAG_TILED, see compression.rs)
+pub const CAP_TILED_COMPRESSION: u32 = 1 << 12;
+// Backend wants frames linearized, premultiplied or bottom-up unless configured otherwise (see color.rs)
+pub const CAP_LINEAR_COLOR: u32 = 1 << 13;
+pub const CAP_PREMULTIPLIED_ALPHA: u32 = 1 << 14;
+pub const CAP_FLIP_Y: u32 = 1 << 15;
+const CAP_COLOR: u32 = CAP_LINEAR_COLOR | CAP_PREMULTIPLIED_ALPHA | CAP_FLIP_Y;
+const CAP_PIXEL_LAYOUT: u32 = CAP_FORMAT_BGRA8 | CAP_FORMAT_RGB8 | CAP_FORMAT_NV12 | CAP_ROW_STRIDE;
+
+// DXGI texture sharing only exists on Windows
+pub const CLIENT_CAPABILITIES: u32 = CAP_LZ4
+    | CAP_ZSTD
+    | CAP_HEARTBEAT
+    | CAP_MULTI_DEVICE
+    | CAP_PIXEL_LAYOUT
+    | CAP_DELTA
+    | CAP_CONTROL
+    | CAP_FRAME_ACK
+    | CAP_TILED_COMPRESSION
+    | CAP_COLOR
+    | if cfg!(windows) { CAP_GPU_TEXTURE } else { 0 };
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Outcome of the handshake, as returned by get_backend_info
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendInfo {
+    pub protocol_version: u8,
+    // Raw capability bits both sides agreed on
+    pub capabilities: u32,
+    pub compression: Vec<&'static str>,
+    pub gpu_texture: bool,
+    pub heartbeat: bool,
+    pub multi_device: bool,
+    // Pixel formats frames can be sent in without conversion
+    pub pixel_formats: Vec<&'static str>,
+    pub row_stride: bool,
+    pub delta: bool,
+    pub control: bool,
+    pub frame_ack: bool,
+    pub tiled_compression: bool,
+    // How the backend asked for frames (see color.rs)
+    pub linear_color: bool,
+    pub premultiplied_alpha: bool,
+    pub flip_y: bool,
+    // None if the backend didn't send one (or didn't answer at all)
+    pub backend_name: Option<String>,
+}
+
+impl BackendInfo {
+    fn new(protocol_version: u8, capabilities: u32, backend_name: Option<String>) -> Self {
+        let mut compression = Vec::new();
+        if capabilities & CAP_LZ4 != 0 {
+            compression.push("lz4");
+        }
+        if capabilities & CAP_ZSTD != 0 {
+            compression.push("zstd");
+        }
+        // The layout fields only exist from v3 on
+        let layout = if protocol_version >= 3 { capabilities } else { 0 };
+        let pixel_formats = [PixelFormat::Rgba8, PixelFormat::Bgra8, PixelFormat::Rgb8, PixelFormat::Nv12]
+            .into_iter()
+            .filter(|format| layout & format.capability() == format.capability())
+            .map(PixelFormat::name)
+            .collect();
+        Self {
+            protocol_version,
+            capabilities,
+            compression,
+            gpu_texture: capabilities & CAP_GPU_TEXTURE != 0,
+            heartbeat: capabilities & CAP_HEARTBEAT != 0,
+            multi_device: capabilities & CAP_MULTI_DEVICE != 0,
+            pixel_formats,
+            row_stride: layout & CAP_ROW_STRIDE != 0,
+            delta: capabilities & CAP_DELTA != 0,
+            control: capabilities & CAP_CONTROL != 0,
+            frame_ack: capabilities & CAP_FRAME_ACK != 0,
+            tiled_compression: capabilities & CAP_TILED_COMPRESSION != 0,
+            linear_color: capabilities & CAP_LINEAR_COLOR != 0,
+            premultiplied_alpha: capabilities & CAP_PREMULTIPLIED_ALPHA != 0,
+            flip_y: capabilities & CAP_FLIP_Y != 0,
+            backend_name,
+        }
+    }
+
+    pub fn decode_ack(payload: &[u8]) -> io::Result<Self> {
+        if payload.len() < 4 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "HelloAck too short"));
+        }
+        let capabilities = LittleEndian::read_u32(&payload[..4]) & CLIENT_CAPABILITIES;
+        let version = payload.get(4).copied().unwrap_or(MIN_PROTOCOL_VERSION);
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "backend picked protocol version {}, this app supports {}..={}",
+                    version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+                ),
+            ));
+        }
+        let backend_name = payload.get(6..8).and_then(|length| {
+            let length = LittleEndian::read_u16(length) as usize;
+            let name = payload.get(8..8 + length)?;
+            Some(String::from_utf8_lossy(name).into_owned())
+        });
+        Ok(Self::new(version, capabilities, backend_name))
+    }
+}
+
+// Run the handshake and return what both sides agreed on
+pub async fn negotiate<R, W>(reader: &mut R, writer: &mut W) -> io::Result<BackendInfo>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut hello = [0u8; 8];
+    LittleEndian::write_u32(&mut hello[..4], CLIENT_CAPABILITIES);
+    hello[4] = MIN_PROTOCOL_VERSION;
+    hello[5] = PROTOCOL_VERSION;
+    writer.write_all(&protocol::encode(MessageType::Hello, 0, &hello)).await?;
+
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, protocol::read_message(reader)).await {
+        Ok(Ok(message)) if message.header.message_type == MessageType::HelloAck => BackendInfo::decode_ack(&message.payload),
+        Ok(Ok(message)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected HelloAck, got {:?}", message.header.message_type),
+        )),
+        Ok(Err(e)) => Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        // No answer: older backend without optional features
+        Err(_) => Ok(BackendInfo::new(MIN_PROTOCOL_VERSION, 0, None)),
+    }
+}
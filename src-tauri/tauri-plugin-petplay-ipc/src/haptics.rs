@@ -0,0 +1,75 @@
+// --- Haptic feedback ---
+// send_haptic_pulse queues pulses for a background task that writes them to
+// the input pipe as HapticPulse messages (app -> backend). The queue is
+// bounded so a runaway UI can't pile up seconds of vibration, and pulses for
+// the same device are spaced at least MIN_PULSE_INTERVAL apart because the
+// controllers can't render pulses closer together than that anyway.
+//
+// Payload layout (little endian):
+//   [0..4)   device id (u32, same ids as pose records)
+//   [4..8)   duration in microseconds (u32)
+//   [8..12)  amplitude 0..1 (f32)
+//   [12..20) timestamp, microseconds since the Unix epoch (u64)
+use crate::error::PipeError;
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+pub const HAPTIC_PULSE_SIZE: usize = 20;
+pub const HAPTIC_QUEUE_CAPACITY: usize = 32;
+pub const MIN_PULSE_INTERVAL: Duration = Duration::from_millis(5);
+pub const MAX_PULSE_DURATION_US: u32 = 5_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HapticPulse {
+    pub device_id: u32,
+    pub duration_us: u32,
+    pub amplitude: f32,
+}
+
+impl HapticPulse {
+    // Checks the values coming from the frontend; amplitude is clamped rather than rejected
+    pub fn new(device_id: u32, duration_us: u32, amplitude: f32) -> Result<Self, PipeError> {
+        if !amplitude.is_finite() {
+            return Err(PipeError::InvalidArgument(format!("Invalid haptic amplitude {}", amplitude)));
+        }
+        if duration_us == 0 || duration_us > MAX_PULSE_DURATION_US {
+            return Err(PipeError::InvalidArgument(format!(
+                "Haptic duration must be between 1 and {} us",
+                MAX_PULSE_DURATION_US
+            )));
+        }
+        Ok(Self { device_id, duration_us, amplitude: amplitude.clamp(0.0, 1.0) })
+    }
+
+    pub fn encode(&self, timestamp_us: u64) -> [u8; HAPTIC_PULSE_SIZE] {
+        let mut bytes = [0u8; HAPTIC_PULSE_SIZE];
+        LittleEndian::write_u32(&mut bytes[0..4], self.device_id);
+        LittleEndian::write_u32(&mut bytes[4..8], self.duration_us);
+        LittleEndian::write_f32(&mut bytes[8..12], self.amplitude);
+        LittleEndian::write_u64(&mut bytes[12..20], timestamp_us);
+        bytes
+    }
+}
+
+// Per-device spacing of pulses
+#[derive(Default)]
+pub struct PulseRateLimiter {
+    last_sent: HashMap<u32, Instant>,
+}
+
+impl PulseRateLimiter {
+    // How long to hold a pulse for this device before it may be sent
+    pub fn delay(&self, device_id: u32, now: Instant) -> Duration {
+        self.last_sent
+            .get(&device_id)
+            .map(|last| (*last + MIN_PULSE_INTERVAL).saturating_duration_since(now))
+            .unwrap_or_default()
+    }
+
+    pub fn record(&mut self, device_id: u32, now: Instant) {
+        self.last_sent.insert(device_id, now);
+    }
+}
@@ -0,0 +1,119 @@
+// --- Heartbeat ---
+// A half-open pipe (backend hung, or killed without closing its end) isn't
+// noticed until a write fails, which on the input and transform pipes may be
+// never. When the backend advertises CAP_HEARTBEAT in its HelloAck we send a
+// Ping on every pipe each interval; the backend answers with a Pong. Any
+// message received counts as a sign of life, and a pipe that stays silent
+// for longer than the timeout is declared dead and reconnected.
+//
+// Ping and Pong payloads are a u64 LE timestamp (microseconds since the Unix
+// epoch); the Pong echoes the Ping's.
+use crate::{
+    protocol::{self, MessageType},
+    transport::PlatformTransport,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncWriteExt, WriteHalf},
+    sync::Mutex as TokioMutex,
+    time::sleep,
+};
+
+// How often the monitor re-checks the config while heartbeats are off
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HeartbeatConfig {
+    // Time between pings; 0 turns heartbeats off
+    pub interval_ms: u64,
+    // Silence after which a pipe is considered dead
+    pub timeout_ms: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self { interval_ms: 1000, timeout_ms: 5000 }
+    }
+}
+
+impl HeartbeatConfig {
+    fn interval(&self) -> Option<Duration> {
+        (self.interval_ms > 0).then(|| Duration::from_millis(self.interval_ms))
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+// When we last heard from the peer on one connection
+pub struct Liveness {
+    started: Instant,
+    last_seen_us: AtomicU64,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self { started: Instant::now(), last_seen_us: AtomicU64::new(0) }
+    }
+}
+
+impl Liveness {
+    pub fn touch(&self) {
+        self.last_seen_us.store(self.started.elapsed().as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn silence(&self) -> Duration {
+        let last_seen = Duration::from_micros(self.last_seen_us.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last_seen)
+    }
+}
+
+// Heartbeat traffic only matters as a sign of life, handlers skip it otherwise
+pub fn is_heartbeat(message_type: MessageType) -> bool {
+    matches!(message_type, MessageType::Ping | MessageType::Pong)
+}
+
+// Pings the peer through `writer` until it goes silent for too long or the
+// writer goes away, and returns why. `enabled` is checked on every tick so
+// heartbeats start and stop with the negotiated capability.
+pub async fn monitor(
+    writer: &TokioMutex<Option<WriteHalf<PlatformTransport>>>,
+    liveness: &Liveness,
+    config: &Mutex<HeartbeatConfig>,
+    enabled: impl Fn() -> bool,
+) -> String {
+    loop {
+        let config = *config.lock();
+        let Some(interval) = config.interval().filter(|_| enabled()) else {
+            // Not enforced right now; don't count this time as silence later
+            liveness.touch();
+            sleep(IDLE_POLL_INTERVAL).await;
+            continue;
+        };
+        sleep(interval).await;
+        let silence = liveness.silence();
+        if silence > config.timeout() {
+            return format!("No response for {} ms", silence.as_millis());
+        }
+        let mut payload = [0u8; 8];
+        LittleEndian::write_u64(&mut payload, protocol::timestamp_us());
+        // A write stuck on a peer that stopped reading holds the lock; skip the ping and let the timeout catch it
+        let Ok(mut writer_guard) = tokio::time::timeout(interval, writer.lock()).await else {
+            continue;
+        };
+        let Some(writer) = writer_guard.as_mut() else {
+            return "Connection closed".to_string();
+        };
+        if let Err(e) = writer.write_all(&protocol::encode(MessageType::Ping, 0, &payload)).await {
+            return format!("Ping failed: {}", e);
+        }
+    }
+}
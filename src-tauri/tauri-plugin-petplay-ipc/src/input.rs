@@ -0,0 +1,104 @@
+// --- Controller input ---
+// Payload of a ControllerInput message (input pipe, backend -> app): one or
+// more variable-length records, each describing the new state of a single
+// control that changed. Buttons only use the pressed/touched bits, triggers
+// and grips report their pull in x, joysticks and touchpads report x and y.
+//
+//   [0..2)   record length in bytes, not counting these two (u16)
+//   [2..6)   device id (u32, same ids as pose records)
+//   [6..8)   control id (u16, see control_name)
+//   [8]      state bits: 1 = pressed, 2 = touched
+//   [9]      reserved
+//   [10..14) x in -1..1 (axes) or 0..1 (triggers) (f32)
+//   [14..18) y in -1..1 (f32)
+//   [18..26) timestamp, microseconds since the Unix epoch (u64)
+//
+// All values little endian.
+use byteorder::{ByteOrder, LittleEndian};
+use std::borrow::Cow;
+
+pub const RECORD_LENGTH_SIZE: usize = 2;
+// Smallest record body this version understands
+pub const MIN_RECORD_SIZE: usize = 24;
+
+pub const STATE_PRESSED: u8 = 1 << 0;
+pub const STATE_TOUCHED: u8 = 1 << 1;
+
+// Name used in "controller-input" events
+pub fn control_name(control_id: u16) -> Cow<'static, str> {
+    match control_id {
+        0 => Cow::Borrowed("system"),
+        1 => Cow::Borrowed("menu"),
+        2 => Cow::Borrowed("grip"),
+        3 => Cow::Borrowed("trigger"),
+        4 => Cow::Borrowed("touchpad"),
+        5 => Cow::Borrowed("joystick"),
+        6 => Cow::Borrowed("a"),
+        7 => Cow::Borrowed("b"),
+        other => Cow::Owned(format!("control-{}", other)),
+    }
+}
+
+// Inverse of control_name
+pub fn control_id_from_name(name: &str) -> Option<u16> {
+    match name {
+        "system" => Some(0),
+        "menu" => Some(1),
+        "grip" => Some(2),
+        "trigger" => Some(3),
+        "touchpad" => Some(4),
+        "joystick" => Some(5),
+        "a" => Some(6),
+        "b" => Some(7),
+        other => other.strip_prefix("control-")?.parse().ok(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputEvent {
+    pub device_id: u32,
+    pub control_id: u16,
+    pub pressed: bool,
+    pub touched: bool,
+    pub x: f32,
+    pub y: f32,
+    pub timestamp_us: u64,
+}
+
+impl InputEvent {
+    // Decode one record body (without its length prefix)
+    fn decode(body: &[u8]) -> Self {
+        let state = body[6];
+        Self {
+            device_id: LittleEndian::read_u32(&body[0..4]),
+            control_id: LittleEndian::read_u16(&body[4..6]),
+            pressed: state & STATE_PRESSED != 0,
+            touched: state & STATE_TOUCHED != 0,
+            x: LittleEndian::read_f32(&body[8..12]),
+            y: LittleEndian::read_f32(&body[12..16]),
+            timestamp_us: LittleEndian::read_u64(&body[16..24]),
+        }
+    }
+}
+
+// Split a ControllerInput payload into records; like pose records, one bad
+// record invalidates the whole message
+pub fn decode_records(payload: &[u8]) -> Result<Vec<InputEvent>, String> {
+    let mut events = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        if rest.len() < RECORD_LENGTH_SIZE {
+            return Err(format!("{} trailing bytes after last record", rest.len()));
+        }
+        let length = LittleEndian::read_u16(&rest[..RECORD_LENGTH_SIZE]) as usize;
+        if length < MIN_RECORD_SIZE {
+            return Err(format!("record of {} bytes is shorter than {}", length, MIN_RECORD_SIZE));
+        }
+        let body = rest
+            .get(RECORD_LENGTH_SIZE..RECORD_LENGTH_SIZE + length)
+            .ok_or_else(|| format!("record of {} bytes runs past end of payload", length))?;
+        events.push(InputEvent::decode(body));
+        rest = &rest[RECORD_LENGTH_SIZE + length..];
+    }
+    Ok(events)
+}
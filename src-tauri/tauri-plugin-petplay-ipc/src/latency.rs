@@ -0,0 +1,139 @@
+// --- Frame latency histograms ---
+// Where a frame's time goes between the webview and the pipe, so stutter can
+// be pinned on the webview/IPC layer, our queue, or a backend that's slow to
+// read. Each stage has a lock-free histogram with fixed, roughly logarithmic
+// buckets; get_latency_histogram returns them with estimated percentiles.
+//
+// Stages (all in microseconds):
+//   ipc      webview send -> send_frame_data running (only for frames that
+//            carry a "sent-at-us" request header, see send_frame_data)
+//   enqueue  send_frame_data running -> frame in the queue (copy + backpressure)
+//   queued   send_frame_data running -> writer task picked the frame up
+//   write    pipe write (or encoder hand-off) duration
+//   total    send_frame_data running -> write completed
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Upper bounds of all but the last bucket, which takes everything slower
+pub const BUCKET_BOUNDS_US: [u64; 14] = [
+    50, 100, 250, 500, 1_000, 2_000, 4_000, 8_000, 16_000, 33_000, 66_000, 125_000, 250_000, 1_000_000,
+];
+const BUCKET_COUNT: usize = BUCKET_BOUNDS_US.len() + 1;
+
+#[derive(Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKET_COUNT],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bucket {
+    // None for the overflow bucket
+    pub le_us: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub sum_us: u64,
+    pub mean_us: u64,
+    pub max_us: u64,
+    // Estimated as the upper bound of the bucket the percentile falls in
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub p99_us: u64,
+    pub buckets: Vec<Bucket>,
+}
+
+impl Histogram {
+    pub fn record(&self, value_us: u64) {
+        let bucket = BUCKET_BOUNDS_US.iter().position(|bound| value_us <= *bound).unwrap_or(BUCKET_COUNT - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(value_us, Ordering::Relaxed);
+        self.max_us.fetch_max(value_us, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let counts: Vec<u64> = self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).collect();
+        // Sum the buckets rather than reading `count` so the percentiles stay consistent with them
+        let count: u64 = counts.iter().sum();
+        let max_us = self.max_us.load(Ordering::Relaxed);
+        let percentile = |fraction: f64| {
+            let rank = ((count as f64 * fraction).ceil() as u64).max(1);
+            let mut seen = 0;
+            for (bucket, bucket_count) in counts.iter().enumerate() {
+                seen += bucket_count;
+                if seen >= rank {
+                    return BUCKET_BOUNDS_US.get(bucket).map_or(max_us, |bound| (*bound).min(max_us));
+                }
+            }
+            0
+        };
+        let sum_us = self.sum_us.load(Ordering::Relaxed);
+        HistogramSnapshot {
+            count,
+            sum_us,
+            mean_us: sum_us.checked_div(self.count.load(Ordering::Relaxed)).unwrap_or(0),
+            max_us,
+            p50_us: percentile(0.50),
+            p95_us: percentile(0.95),
+            p99_us: percentile(0.99),
+            buckets: counts
+                .iter()
+                .enumerate()
+                .map(|(bucket, count)| Bucket { le_us: BUCKET_BOUNDS_US.get(bucket).copied(), count: *count })
+                .collect(),
+        }
+    }
+
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum_us.store(0, Ordering::Relaxed);
+        self.max_us.store(0, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct FrameLatency {
+    pub ipc: Histogram,
+    pub enqueue: Histogram,
+    pub queued: Histogram,
+    pub write: Histogram,
+    pub total: Histogram,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FrameLatencySnapshot {
+    pub ipc: HistogramSnapshot,
+    pub enqueue: HistogramSnapshot,
+    pub queued: HistogramSnapshot,
+    pub write: HistogramSnapshot,
+    pub total: HistogramSnapshot,
+}
+
+impl FrameLatency {
+    pub fn snapshot(&self) -> FrameLatencySnapshot {
+        FrameLatencySnapshot {
+            ipc: self.ipc.snapshot(),
+            enqueue: self.enqueue.snapshot(),
+            queued: self.queued.snapshot(),
+            write: self.write.snapshot(),
+            total: self.total.snapshot(),
+        }
+    }
+
+    pub fn reset(&self) {
+        for histogram in [&self.ipc, &self.enqueue, &self.queued, &self.write, &self.total] {
+            histogram.reset();
+        }
+    }
+}
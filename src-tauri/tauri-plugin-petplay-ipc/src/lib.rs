@@ -0,0 +1,4998 @@
+// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+
+// --- Add necessary imports ---
+use byteorder::{LittleEndian, ReadBytesExt}; 
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, Cursor}, 
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tauri::{
+    ipc::{Channel, Invoke, InvokeBody, InvokeResponseBody},
+    plugin::{Builder, TauriPlugin},
+    webview::PageLoadEvent,
+    AppHandle, Emitter, Manager, RunEvent, State, WebviewUrl, WebviewWindowBuilder, WindowEvent, Wry,
+};
+// --- Tokio Imports ---
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf}, 
+    sync::{mpsc, Mutex as TokioMutex, MutexGuard as TokioMutexGuard}, 
+    time::sleep,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, debug_span, error, info, warn, Instrument};
+
+mod ack;
+mod adaptive;
+mod audio;
+mod auth;
+mod backend;
+mod backoff;
+mod buffer_pool;
+mod capture;
+mod chaos;
+mod cli;
+mod coalesce;
+mod color;
+mod colorspace;
+mod compression;
+mod config;
+mod config_watch;
+mod connection;
+mod control;
+mod crash_report;
+mod dashboard;
+mod delta;
+mod device_status;
+mod diagnostics;
+mod encoder;
+mod error;
+mod file_transfer;
+mod filter;
+mod foveation;
+mod frame_queue;
+mod frame_rate;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+mod gaze;
+mod gpu_texture;
+mod hands;
+mod handshake;
+mod haptics;
+mod heartbeat;
+mod input;
+mod latency;
+mod logging;
+mod metrics;
+mod metrics_http;
+mod microphone;
+mod mock_backend;
+mod ndi;
+mod openvr;
+mod openxr;
+mod osc;
+mod overlays;
+mod pipe_manager;
+mod pipe_security;
+mod pixel_format;
+mod placement;
+mod playspace;
+mod pose;
+mod pose_source;
+mod prediction;
+mod preview;
+mod protocol;
+mod rate_limit;
+mod recording;
+mod runtime;
+mod screenshot;
+mod session;
+mod shm;
+mod single_instance;
+mod sink;
+mod spout;
+mod supervisor;
+mod tasks;
+mod transform_stream;
+mod transport;
+mod tray;
+mod validation;
+mod video;
+mod vr_pointer;
+mod webrtc;
+mod window_capture;
+use ack::AckTracker;
+use adaptive::{AdaptiveConfig, AdaptiveController, AdaptiveMode, Adjustment};
+use audio::{AudioChunk, AudioConfig, AudioFormat, AudioHeader, AudioQueue, SampleFormat};
+use auth::AuthToken;
+use backend::BackendSupervisor;
+use backoff::{Backoff, ReconnectPolicy};
+use buffer_pool::BufferPool;
+use capture::{CaptureOptions, CaptureTarget, DesktopCapture, DesktopCaptureInfo};
+use chaos::{Chaos, ChaosConfig};
+use coalesce::{TransformCoalescer, IDLE_POLL_INTERVAL};
+use color::{ColorConversion, ColorOptions};
+use colorspace::YuvLayout;
+use compression::{Compression, Compressor, DEFAULT_ZSTD_LEVEL, FLAG_TILED};
+use config::{AppConfig, LogLevel, PipePaths, StreamConfig, TimeoutConfig, CONFIG_FILE_NAME};
+use connection::{ConnectionStatus, ConnectionTracker, PipeKind, PipeOperation};
+use control::ControlMessage;
+use crash_report::PendingReports;
+use dashboard::{DashboardConfig, DashboardState, DashboardVisibility};
+use delta::{DeltaEncoder, FLAG_DELTA};
+use device_status::{BatteryLow, DeviceStatus, DeviceStatusBoard, DeviceStatusConfig};
+use diagnostics::DiagnosticsBundle;
+use encoder::{EncoderSettings, VideoCodec, VideoEncoder};
+use error::PipeError;
+use file_transfer::{ChunkOutcome, FileOffer, FileStatus, FileTransferComplete, FileTransferProgress, FileTransfers, TransferDirection, CHUNK_HEADER_SIZE};
+use filter::{FilterConfig, FilterMode, TransformFilter};
+use foveation::{Foveation, FoveationConfig};
+use frame_queue::{BackpressurePolicy, FrameQueue, QueuedFrame};
+use frame_rate::{FrameRateLimiter, MAX_TARGET_FPS};
+use gaze::{Gaze, GazeConfig, GazeThrottle};
+use gpu_texture::SharedTexture;
+use hands::{Hand, HandSubscribers, HandTrackingChanged};
+use handshake::BackendInfo;
+use haptics::{HapticPulse, PulseRateLimiter, HAPTIC_QUEUE_CAPACITY};
+use heartbeat::{HeartbeatConfig, Liveness};
+use input::InputEvent;
+use latency::FrameLatencySnapshot;
+use logging::Logging;
+use metrics::{PipeMetrics, PipeMetricsSnapshot, DEFAULT_SAMPLE_INTERVAL_MS};
+use metrics_http::{MetricsEndpointConfig, PipeSample};
+use microphone::{AudioInput, LevelMeter, Microphone, MicrophoneInfo, LEVEL_INTERVAL};
+use ndi::NdiConfig;
+use openvr::{OpenVrConfig, OPENVR_SINK_ID};
+use openxr::{OpenXrConfig, OPENXR_SINK_ID};
+use osc::{Incoming, OscBridge, OscConfig};
+use overlays::{Overlay, OverlayInfo, OverlayRegistry, OverlaySettings, DEFAULT_OVERLAY_ID};
+use pipe_manager::{NamedPipeStatus, PipeManager};
+use pipe_security::PipeSecurity;
+use pixel_format::PixelFormat;
+use placement::{OverlayPlacement, PlacementChange, PlacementState};
+use playspace::{Playspace, PlayspaceState};
+use pose::Pose;
+use pose_source::PoseSources;
+use prediction::{PosePredictor, MAX_HORIZON_US};
+use preview::{FramePreview, PREVIEW_PAGE, PREVIEW_WINDOW_LABEL};
+use protocol::{FrameHeader, Message, MessageType, ProtocolError, CLIENT_FRAME_HEADER_SIZE, MIN_PROTOCOL_VERSION};
+use rate_limit::{CommandLimiter, CommandLimits};
+use recording::{Recorder, RecordingSummary, Replay};
+use runtime::IpcRuntime;
+use screenshot::Screenshot;
+use session::{Direction, SessionRecorder, SessionReplay, SessionReplaySummary, SessionSummary};
+use shm::{FrameChannel, SharedFrameRing};
+use sink::{FrameSink, SinkFuture, SinkInfo, SinkQueueOptions, SinkSet, SinkSpec, NDI_SINK_ID, PIPE_SINK_ID, SPOUT_SINK_ID};
+use supervisor::TaskSupervisor;
+use tasks::{Generation, TaskSlot};
+use transform_stream::{TransformFormat, TransformSubscribers};
+use transport::{ConnectFailure, PipeMode, PipeOptions, PlatformTransport, Transport};
+use validation::Invalid;
+use video::{EncodedFrame, VideoFeed, VideoFramePayload, VideoHeader, VIDEO_HEADER_SIZE};
+use webrtc::WebRtcConfig;
+use window_capture::{CapturableWindow, WindowCapture, WindowCaptureInfo, WindowCaptureOptions, DEFAULT_THUMBNAIL_SIZE};
+
+// Write half of an outbound pipe, shared by the task writing to it and the listener (re)connecting it
+type PipeWriter = Arc<TokioMutex<Option<WriteHalf<PlatformTransport>>>>;
+
+// --- Define the state struct to hold the pipe connection ---
+// Frame pipe state (now asynchronous)
+// Cheap to clone: every field is shared, so background tasks hold their own copy
+#[derive(Clone)]
+pub struct FramePipeState {
+    // Use Tokio's Mutex for async locking
+    // Store the write half of the pipe if connection is successful
+    pipe_writer: PipeWriter,
+    // Advanced by every new connection loop and by disconnects; a loop whose token is stale can't install its writer
+    frame_generation: Generation,
+    // Frames waiting for the writer task
+    queue: Arc<FrameQueue>,
+    // Tauri's runtime; tasks are spawned through it so they can be aborted on exit
+    rt: IpcRuntime,
+    // Shared-memory ring used instead of the pipe for pixel data (None = pipe mode)
+    shm_ring: Arc<parking_lot::Mutex<Option<SharedFrameRing>>>,
+    // Reports connect/disconnect to get_connection_status and the frontend
+    connections: ConnectionTracker,
+    // Background connection tasks, kept so they can be torn down on demand
+    // and so a new loop always replaces (never races) the previous one
+    tasks: Arc<PipeTasks>,
+    // Needed by the transform listener to emit events
+    app_handle: AppHandle,
+    // Sequence number for the next frame handed to send_frame_data
+    next_sequence: Arc<AtomicU64>,
+    // Counters exposed through get_pipe_metrics
+    metrics: Arc<PipeMetrics>,
+    // How often the "pipe-stats" event is emitted (0 = never)
+    stats_interval_ms: Arc<AtomicU64>,
+    // Codec requested through configure_stream
+    compression: Arc<parking_lot::Mutex<Compression>>,
+    // Capabilities the backend accepted in the handshake of the current connection
+    backend_capabilities: Arc<AtomicU32>,
+    // Full handshake result for get_backend_info (None until the first connect)
+    backend_info: Arc<parking_lot::Mutex<Option<BackendInfo>>>,
+    // Hardware encoding requested through configure_stream
+    encoder_settings: Arc<parking_lot::Mutex<EncoderSettings>>,
+    // Running ffmpeg encoder, (re)started lazily by the writer task
+    video_encoder: Arc<TokioMutex<Option<VideoEncoder>>>,
+    // Set when no encoder could be used; cleared when the settings change
+    encoder_failed: Arc<AtomicBool>,
+    // Write half of the input pipe, used for haptic pulses (None while disconnected)
+    input_writer: PipeWriter,
+    // Write half of the audio pipe (None while disconnected)
+    audio_writer: PipeWriter,
+    // Chunks from send_audio_chunk waiting for the audio writer task
+    audio: Arc<AudioQueue>,
+    // Write half of the microphone pipe (None while disconnected)
+    microphone_writer: PipeWriter,
+    // Captured chunks waiting for the microphone writer task
+    microphone_queue: Arc<AudioQueue>,
+    // Capture started by start_microphone
+    microphone: Arc<parking_lot::Mutex<Option<Microphone>>>,
+    // Captures started by start_desktop_capture and capture_window, keyed by the overlay they stream to
+    captures: Arc<parking_lot::Mutex<BTreeMap<u32, OverlayCapture>>>,
+    // Subscribers and latest frames of the video pipe's streams
+    video: Arc<VideoFeed>,
+    // Write half of the files pipe (None while disconnected)
+    files_writer: PipeWriter,
+    // Files being sent and received, kept across reconnects
+    file_transfers: Arc<FileTransfers>,
+    // Replay VR pointer events into the page as DOM events (config file)
+    inject_pointer_dom: Arc<AtomicBool>,
+    // Pulses waiting for the haptics task
+    haptics: mpsc::Sender<HapticPulse>,
+    // Latest pose per device between transform-update emits
+    transforms: Arc<TransformCoalescer>,
+    // Drops gaze samples over the gaze-update rate (config file)
+    gaze: Arc<GazeThrottle>,
+    // Latest battery, tracking state and firmware per device (get_device_status)
+    device_status: Arc<DeviceStatusBoard>,
+    // Optional smoothing of incoming poses (set_transform_filter)
+    transform_filter: Arc<TransformFilter>,
+    // Extrapolates poses to compensate pipeline latency (set_pose_prediction)
+    pose_predictor: Arc<PosePredictor>,
+    // Ping interval and dead-connection timeout for all pipes
+    heartbeat: Arc<parking_lot::Mutex<HeartbeatConfig>>,
+    // Where to find the backend's pipes/sockets (config file, or set_pipe_paths)
+    pipe_paths: Arc<parking_lot::Mutex<PipePaths>>,
+    // OS buffer sizes and pipe mode for new connections (config file, or configure_pipe)
+    pipe_options: Arc<parking_lot::Mutex<PipeOptions>>,
+    // Frame write / transform read timeouts (config file)
+    timeouts: TimeoutConfig,
+    // Frames with more pixel data than this are rejected (config file)
+    max_frame_bytes: usize,
+    // Set once shutdown() has run so exit events arriving later don't repeat it
+    shut_down: Arc<AtomicBool>,
+    // Overlay quads registered by the webview windows
+    overlays: Arc<OverlayRegistry>,
+    // Copies queued frames to a file between start_recording and stop_recording
+    recorder: Arc<Recorder>,
+    // Captures both directions of every pipe between start_session_recording and stop_session_recording
+    session: Arc<SessionRecorder>,
+    // Holds back frames over the target FPS, newest first (config file, or set_target_fps)
+    frame_rate: Arc<FrameRateLimiter>,
+    // Reusable buffers for the frame copies, conversions and compressed payloads (see buffer_pool.rs)
+    buffers: Arc<BufferPool>,
+    // Compression level and worker threads (config file, or configure_stream)
+    compressor: Arc<Compressor>,
+    // Frames written but not yet acknowledged by the backend (see ack.rs)
+    acks: Arc<AckTracker>,
+    // Set by pause_stream: frames are still accepted but dropped until resume_stream
+    paused: Arc<AtomicBool>,
+    // Most recent frame from send_frame_data, for capture_screenshot
+    last_frame: Arc<parking_lot::Mutex<Option<QueuedFrame>>>,
+    // Mirrors outgoing frames to the preview window while it's open (show_frame_preview)
+    preview: Arc<FramePreview>,
+    // Steps compression and resolution when pipe writes fall behind (config file, or set_adaptive_quality)
+    adaptive: Arc<AdaptiveController>,
+    // Averages the periphery of frames for the overlay the user looks at (config file, or set_foveation)
+    foveation: Arc<Foveation>,
+    // Gamma, alpha and row order frames are sent in (config file, or configure_stream)
+    color: Arc<parking_lot::Mutex<ColorOptions>>,
+    // XOR frames against the previous one when the backend accepts it (config file, or configure_stream)
+    delta_frames: Arc<AtomicBool>,
+    // Previous frame per overlay, only touched by the writer task and the connect loop
+    delta: Arc<parking_lot::Mutex<DeltaEncoder>>,
+    // Outputs fed beside the pipe, each from its own queue (Spout, NDI; see sink.rs)
+    sinks: Arc<SinkSet>,
+    // Settings of the "ndi" sink, kept while it's stopped for enable_ndi_output to turn it back on
+    ndi_config: Arc<parking_lot::Mutex<NdiConfig>>,
+    // Settings of the in-process OpenVR overlay, kept while it's stopped for enable_openvr (see openvr.rs)
+    openvr_config: Arc<parking_lot::Mutex<OpenVrConfig>>,
+    // Settings of the in-process OpenXR overlay, kept while it's stopped for enable_openxr (see openxr.rs)
+    openxr_config: Arc<parking_lot::Mutex<OpenXrConfig>>,
+    // Runtimes polled for poses beside the transform pipe (see pose_source.rs)
+    pose_sources: Arc<PoseSources>,
+    // Placement set with set_overlay_transform and friends, and the runtime layers it goes to (see placement.rs)
+    placement: Arc<PlacementState>,
+    // Whether the surface goes in the SteamVR dashboard, and whether its tab is open (see dashboard.rs)
+    dashboard: Arc<DashboardState>,
+    // Secret every pipe's backend has to prove it knows before it's used (see auth.rs)
+    auth_token: AuthToken,
+    // Accounts allowed to serve the local pipes (config file, see pipe_security.rs)
+    pipe_security: Arc<PipeSecurity>,
+    // Payload cap and call rates checked before any command runs (config file, see rate_limit.rs)
+    limiter: Arc<CommandLimiter>,
+    // Restarts the connection loops, listeners and writer task if they panic
+    supervisor: TaskSupervisor,
+    // Faults injected into the pipes on purpose (config file, debug builds only; see chaos.rs)
+    chaos: Arc<Chaos>,
+}
+
+#[derive(Default)]
+struct PipeTasks {
+    frame_connect: TaskSlot,
+    // Reads the frame pipe's backend->app side and pings it while connected
+    frame_heartbeat: TaskSlot,
+    transform_listener: TaskSlot,
+    input_listener: TaskSlot,
+    audio_listener: TaskSlot,
+    microphone_listener: TaskSlot,
+    video_listener: TaskSlot,
+    files_listener: TaskSlot,
+    pointer_listener: TaskSlot,
+    writer: TaskSlot,
+    audio_writer: TaskSlot,
+    microphone_writer: TaskSlot,
+    files_writer: TaskSlot,
+    // Queues the frames the FPS limit held back once their turn comes
+    frame_release: TaskSlot,
+    metrics_sampler: TaskSlot,
+    haptics: TaskSlot,
+    transform_emitter: TaskSlot,
+    encoder_output: TaskSlot,
+}
+
+// --- Define Payload Struct ---
+#[derive(Clone, Serialize)]
+struct TransformUpdatePayload {
+    device: String, // "hmd", "controller-left", "controller-right", "device-<id>"
+    #[serde(flatten)]
+    pose: Pose, // deviceId, position, orientation, velocities, timestampUs
+    matrix: Vec<f32>, // The same pose as a 16-element flat matrix (row-major)
+}
+
+// Result of get_last_transform
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LastTransformPayload {
+    device: String,
+    #[serde(flatten)]
+    pose: Pose,
+    matrix: Vec<f32>, // Row-major 4x4, as in transform-update
+    age_us: u64,      // Time since the pose arrived from the backend
+}
+
+// A pose or matrix from the transform pipe that failed validation and was dropped
+#[derive(Clone, Serialize)]
+struct TransformInvalidPayload {
+    source: String, // Device name as in transform-update, or "overlay-<id>"
+    reason: String,
+    values: Vec<f32>, // What was checked: the matrix, or position, orientation and velocities of a pose
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OverlayTransformPayload {
+    overlay_id: u32,
+    matrix: Vec<f32>, // 16-element flat placement matrix (row-major)
+}
+
+// One control that changed state, as emitted in "controller-input"
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ControllerInputPayload {
+    device: String, // Same names as the per-device transform events
+    device_id: u32,
+    control: String, // "trigger", "grip", "joystick", "a", ...
+    pressed: bool,
+    touched: bool,
+    x: f32, // Trigger/grip pull, or horizontal axis
+    y: f32, // Vertical axis
+    timestamp_us: u64,
+}
+
+// Resolution the adaptive controller asks the frontend to capture at
+#[derive(Clone, Serialize)]
+struct TargetResolutionPayload {
+    scale: f32, // Of the full capture resolution
+    width: u32, // Suggested size, from the last frame's size and the change in scale
+    height: u32,
+}
+
+#[derive(Clone, Serialize)]
+struct FramesDroppedPayload {
+    dropped: usize, // Frames dropped by this send
+    total: u64, // Frames dropped since startup
+}
+
+#[derive(Clone, Serialize)]
+struct AudioDroppedPayload {
+    pipe: PipeKind, // Audio or microphone
+    dropped: usize, // Chunks dropped just now, for a full queue or for being too late
+    total: u64, // Chunks dropped since startup
+}
+
+#[derive(Clone, Serialize)]
+struct MicrophoneStoppedPayload {
+    reason: String,
+}
+
+// A capture streaming to an overlay; it stops when dropped
+enum OverlayCapture {
+    Desktop(DesktopCapture),
+    Window(WindowCapture),
+}
+
+impl OverlayCapture {
+    // Shown in logs
+    fn describe(&self) -> String {
+        match self {
+            Self::Desktop(capture) => format!("{:?}", capture.info().target),
+            Self::Window(capture) => format!("window {:#x}", capture.info().hwnd),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DesktopCaptureStoppedPayload {
+    overlay_id: u32,
+    reason: String,
+}
+
+// Options accepted by configure_stream; fields left out keep their current value
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamOptions {
+    backpressure: Option<BackpressurePolicy>,
+    queue_depth: Option<usize>,
+    compression: Option<Compression>,
+    encoding: Option<EncoderSettings>,
+    delta: Option<bool>,
+    keyframe_interval: Option<u32>,
+    // Spout sender name; "" stops the sender
+    spout_sender: Option<String>,
+    // 0 lifts the limit
+    target_fps: Option<u32>,
+    // Unacknowledged frames allowed on the pipe; 0 for no limit
+    ack_window: Option<u32>,
+    zstd_level: Option<i32>,
+    // 0 for one per core
+    compression_workers: Option<usize>,
+    // Replaces all three color settings; see color.rs
+    color: Option<ColorOptions>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameAckedPayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>,
+    sequence: u64, // As returned by send_frame_data
+    frames: usize, // Frames this ack settled: the acked one and any earlier ones still waiting
+    round_trip_us: Option<u64>, // From writing the frame to its ack; None if the frame itself had been settled already
+}
+
+#[derive(Clone, Serialize)]
+struct BackendControlPayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connection: Option<String>, // Named connection the message came in on
+    #[serde(flatten)]
+    message: ControlMessage,
+}
+
+#[derive(Clone, Serialize)]
+struct EncoderFallbackPayload {
+    reason: String,
+}
+
+// --- Constants ---
+// Pipe/socket paths are platform specific and live in the transport module
+const ENCODED_CHUNK_SIZE: usize = 64 * 1024; // Max encoder output forwarded per VideoChunk message
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(2); // Per step, so a stuck backend can't hang the exit
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+const SESSION_REPLAY_BUFFER: usize = 1024 * 1024; // Per pipe, between a session replay and the readers
+const REPLAY_CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100); // --replay waits this long between checks for a backend
+const RECORDINGS_DIR: &str = "recordings"; // In the app data directory, for frame and session recordings
+const SCREENSHOTS_DIR: &str = "screenshots"; // In the app data directory, for capture_screenshot
+const DIAGNOSTICS_DIR: &str = "diagnostics"; // In the app data directory, for export_diagnostics
+
+impl FramePipeState {
+    // Initialize the state and spawn the connection loops and writer task
+    fn new(
+        rt: IpcRuntime,
+        connections: ConnectionTracker,
+        app_handle: AppHandle,
+        config: &AppConfig,
+        auth_token: AuthToken,
+    ) -> Self {
+        let (haptics, haptics_rx) = mpsc::channel(HAPTIC_QUEUE_CAPACITY);
+        let state = Self::with_config(rt, connections, app_handle, config, auth_token, haptics);
+        if !config.stream.spout_sender.is_empty() {
+            if let Err(e) = state.set_spout_sender(Some(&config.stream.spout_sender)) {
+                error!("[Rust Spout] Failed to start Spout sender {:?}: {}", config.stream.spout_sender, e);
+            }
+        }
+        if config.ndi.enabled {
+            if let Err(e) = state.apply_ndi_config(config.ndi.clone()) {
+                error!("[Rust NDI] Failed to start NDI output: {}", e);
+            }
+        }
+        if config.openvr.enabled {
+            if let Err(e) = state.apply_openvr_config(config.openvr.clone()) {
+                error!("[Rust OpenVR] Failed to start the OpenVR overlay: {}", e);
+            }
+        }
+        if config.openxr.enabled {
+            if let Err(e) = state.apply_openxr_config(config.openxr.clone()) {
+                error!("[Rust OpenXR] Failed to start the OpenXR overlay: {}", e);
+            }
+        }
+        state.spawn_connection_loop();
+        state.spawn_transform_listener();
+        state.spawn_input_listener();
+        state.spawn_audio_listener();
+        state.spawn_microphone_listener();
+        state.spawn_video_listener();
+        state.spawn_files_listener();
+        state.spawn_pointer_listener();
+        state.spawn_writer_task();
+        state.spawn_audio_writer_task(PipeKind::Audio);
+        state.spawn_audio_writer_task(PipeKind::Microphone);
+        state.spawn_files_writer_task();
+        state.spawn_frame_release_task();
+        state.spawn_metrics_sampler();
+        state.spawn_haptics_task(haptics_rx);
+        state.spawn_transform_emitter();
+        state
+    }
+
+    // A named connection (see pipe_manager.rs): only a frame pipe at `path`, with its own queue and writer
+    fn new_named(
+        rt: IpcRuntime,
+        app_handle: AppHandle,
+        config: &AppConfig,
+        auth_token: AuthToken,
+        name: &str,
+        path: &str,
+    ) -> Self {
+        let connections = ConnectionTracker::named(app_handle.clone(), name, path);
+        connections.set_reconnect_policy(config.reconnect);
+        let config = AppConfig { pipes: PipePaths { frame: path.to_string(), ..config.pipes.clone() }, ..config.clone() };
+        // Haptics only go through the main input pipe
+        let (haptics, _) = mpsc::channel(1);
+        let state = Self::with_config(rt, connections, app_handle, &config, auth_token, haptics);
+        state.spawn_connection_loop();
+        state.spawn_writer_task();
+        state.spawn_frame_release_task();
+        state
+    }
+
+    fn with_config(
+        rt: IpcRuntime,
+        connections: ConnectionTracker,
+        app_handle: AppHandle,
+        config: &AppConfig,
+        auth_token: AuthToken,
+        haptics: mpsc::Sender<HapticPulse>,
+    ) -> Self {
+        let buffers = Arc::new(BufferPool::default());
+        let state = Self {
+            pipe_writer: Arc::new(TokioMutex::new(None)),
+            frame_generation: Generation::default(),
+            queue: Arc::new(FrameQueue::new(config.stream.queue_depth)),
+            shm_ring: Arc::new(parking_lot::Mutex::new(None)),
+            connections,
+            tasks: Arc::new(PipeTasks::default()),
+            app_handle,
+            next_sequence: Arc::new(AtomicU64::new(0)),
+            metrics: Arc::new(PipeMetrics::default()),
+            stats_interval_ms: Arc::new(AtomicU64::new(0)),
+            compression: Arc::new(parking_lot::Mutex::new(config.stream.compression)),
+            backend_capabilities: Arc::new(AtomicU32::new(0)),
+            backend_info: Arc::new(parking_lot::Mutex::new(None)),
+            encoder_settings: Arc::new(parking_lot::Mutex::new(EncoderSettings::default())),
+            video_encoder: Arc::new(TokioMutex::new(None)),
+            encoder_failed: Arc::new(AtomicBool::new(false)),
+            input_writer: Arc::new(TokioMutex::new(None)),
+            audio_writer: Arc::new(TokioMutex::new(None)),
+            audio: Arc::new(AudioQueue::new(config.audio)),
+            microphone_writer: Arc::new(TokioMutex::new(None)),
+            microphone_queue: Arc::new(AudioQueue::new(config.audio)),
+            microphone: Arc::new(parking_lot::Mutex::new(None)),
+            captures: Arc::new(parking_lot::Mutex::new(BTreeMap::new())),
+            video: Arc::new(VideoFeed::default()),
+            files_writer: Arc::new(TokioMutex::new(None)),
+            file_transfers: Arc::new(FileTransfers::default()),
+            inject_pointer_dom: Arc::new(AtomicBool::new(config.vr_pointer.inject_dom)),
+            haptics,
+            transforms: Arc::new(TransformCoalescer::new(config.transforms.rate_hz)),
+            gaze: Arc::new(GazeThrottle::new(config.gaze.rate_hz)),
+            device_status: Arc::new(DeviceStatusBoard::new(config.device_status)),
+            transform_filter: Arc::new(TransformFilter::default()),
+            pose_predictor: Arc::new(PosePredictor::default()),
+            heartbeat: Arc::new(parking_lot::Mutex::new(config.heartbeat)),
+            pipe_paths: Arc::new(parking_lot::Mutex::new(config.pipes.clone())),
+            pipe_options: Arc::new(parking_lot::Mutex::new(config.pipe_options)),
+            timeouts: config.timeouts,
+            max_frame_bytes: config.stream.max_frame_bytes,
+            shut_down: Arc::new(AtomicBool::new(false)),
+            overlays: Arc::new(OverlayRegistry::default()),
+            recorder: Arc::new(Recorder::default()),
+            session: Arc::new(SessionRecorder::default()),
+            frame_rate: Arc::new(FrameRateLimiter::new(config.stream.target_fps)),
+            compressor: Arc::new(Compressor::new(
+                config.stream.zstd_level,
+                config.stream.compression_workers,
+                Arc::clone(&buffers),
+            )),
+            buffers,
+            acks: Arc::new(AckTracker::new(config.stream.ack_window)),
+            paused: Arc::new(AtomicBool::new(false)),
+            last_frame: Arc::new(parking_lot::Mutex::new(None)),
+            preview: Arc::new(FramePreview::default()),
+            adaptive: Arc::new(AdaptiveController::new(config.adaptive)),
+            foveation: Arc::new(Foveation::new(config.foveation)),
+            color: Arc::new(parking_lot::Mutex::new(config.stream.color)),
+            delta_frames: Arc::new(AtomicBool::new(config.stream.delta)),
+            delta: Arc::new(parking_lot::Mutex::new(DeltaEncoder::new(config.stream.keyframe_interval))),
+            sinks: Arc::new(SinkSet::default()),
+            ndi_config: Arc::new(parking_lot::Mutex::new(NdiConfig { enabled: false, ..config.ndi.clone() })),
+            openvr_config: Arc::new(parking_lot::Mutex::new(OpenVrConfig { enabled: false, ..config.openvr.clone() })),
+            openxr_config: Arc::new(parking_lot::Mutex::new(OpenXrConfig { enabled: false, ..config.openxr.clone() })),
+            pose_sources: Arc::new(PoseSources::default()),
+            placement: Arc::new(PlacementState::default()),
+            dashboard: Arc::new(DashboardState::new(config.dashboard.clone())),
+            auth_token,
+            pipe_security: Arc::new(config.pipe_security.clone()),
+            limiter: Arc::new(CommandLimiter::new(config.limits.clone())),
+            supervisor: TaskSupervisor::new(rt.clone()),
+            chaos: Arc::new(Chaos::new(config.chaos)),
+            rt,
+        };
+        state.queue.set_policy(config.stream.backpressure);
+        state
+    }
+
+    // Spawns the task that emits the coalesced poses at the configured transform rate
+    fn spawn_transform_emitter(&self) {
+        let app_handle = self.app_handle.clone();
+        let transforms = Arc::clone(&self.transforms);
+        self.tasks.transform_emitter.replace(|| self.supervisor.spawn("transform-emitter", move || {
+            let (app_handle, transforms) = (app_handle.clone(), Arc::clone(&transforms));
+            async move {
+                loop {
+                    sleep(transforms.interval().unwrap_or(IDLE_POLL_INTERVAL)).await;
+                    // Also drains whatever was parked right before coalescing was turned off
+                    for (pose, matrix) in transforms.take_pending() {
+                        emit_transform_update(&app_handle, pose, matrix);
+                    }
+                }
+            }
+        }));
+    }
+
+    // Spawns the task that writes queued haptic pulses to the input pipe, spacing them per device
+    fn spawn_haptics_task(&self, pulses: mpsc::Receiver<HapticPulse>) {
+        let input_writer = Arc::clone(&self.input_writer);
+        // Kept outside the task so a restarted one picks up the pulses still queued
+        let pulses = Arc::new(TokioMutex::new(pulses));
+        self.tasks.haptics.replace(|| self.supervisor.spawn("haptics", move || {
+            let (input_writer, pulses) = (Arc::clone(&input_writer), Arc::clone(&pulses));
+            async move {
+                let mut pulses = pulses.lock().await;
+                let mut limiter = PulseRateLimiter::default();
+                while let Some(pulse) = pulses.recv().await {
+                    let delay = limiter.delay(pulse.device_id, Instant::now());
+                    if !delay.is_zero() {
+                        sleep(delay).await;
+                    }
+                    let mut writer_guard = input_writer.lock().await;
+                    let Some(writer) = writer_guard.as_mut() else {
+                        warn!("[Rust Haptics] Input pipe not connected, dropping pulse for {}.", pose::device_name(pulse.device_id));
+                        continue;
+                    };
+                    let message = protocol::encode(MessageType::HapticPulse, 0, &pulse.encode(protocol::timestamp_us()));
+                    match writer.write_all(&message).await {
+                        Ok(()) => limiter.record(pulse.device_id, Instant::now()),
+                        // The input listener notices the broken pipe on its read side and reconnects
+                        Err(e) => error!("[Rust Haptics] Error writing haptic pulse: {}", e),
+                    }
+                }
+            }
+        }));
+    }
+
+    // Queue, write half and message type of an outbound audio pipe
+    fn audio_channel(&self, pipe: PipeKind) -> (&Arc<AudioQueue>, &PipeWriter, MessageType) {
+        match pipe {
+            PipeKind::Microphone => (&self.microphone_queue, &self.microphone_writer, MessageType::MicrophoneChunk),
+            _ => (&self.audio, &self.audio_writer, MessageType::AudioChunk),
+        }
+    }
+
+    // Spawns the task that writes queued chunks to the audio or microphone pipe, skipping those that waited too long
+    fn spawn_audio_writer_task(&self, pipe: PipeKind) {
+        let (slot, name) = match pipe {
+            PipeKind::Microphone => (&self.tasks.microphone_writer, "microphone-writer"),
+            _ => (&self.tasks.audio_writer, "audio-writer"),
+        };
+        let state = self.clone();
+        slot.replace(|| self.supervisor.spawn(name, move || {
+            let state = state.clone();
+            async move {
+                let (queue, writer, message_type) = state.audio_channel(pipe);
+                loop {
+                    let (chunk, late) = queue.pop().await;
+                    emit_audio_dropped(&state.app_handle, pipe, queue, late);
+                    let mut writer_guard = writer.lock().await;
+                    // Chunks queued right before a disconnect have nowhere to go
+                    let Some(writer) = writer_guard.as_mut() else {
+                        continue;
+                    };
+                    let header = chunk.header.encode();
+                    state.session.record_sent(pipe, message_type, &header, &chunk.samples);
+                    let write = write_message(writer, message_type, 0, [&header, &chunk.samples]);
+                    let result = match state.timeouts.write() {
+                        Some(limit) => tokio::time::timeout(limit, write).await.unwrap_or_else(|_| {
+                            state.connections.record_timeout(pipe, PipeOperation::Write, limit);
+                            Err(io::Error::new(io::ErrorKind::TimedOut, format!("audio write took longer than {:?}", limit)))
+                        }),
+                        None => write.await,
+                    };
+                    // The pipe's listener notices the broken pipe on its read side and reconnects
+                    if let Err(e) = result {
+                        error!("[Rust Audio Pipe] Error writing {:?}: {}", message_type, e);
+                    }
+                }
+            }
+        }));
+    }
+
+    // Write a message to the files pipe; false if it isn't connected
+    async fn write_files_message(&self, message_type: MessageType, prefix: &[u8], body: &Bytes) -> io::Result<bool> {
+        let mut writer_guard = self.files_writer.lock().await;
+        let Some(writer) = writer_guard.as_mut() else {
+            return Ok(false);
+        };
+        self.session.record_sent(PipeKind::Files, message_type, prefix, body);
+        let write = write_message(writer, message_type, 0, [prefix, body]);
+        match self.timeouts.write() {
+            Some(limit) => tokio::time::timeout(limit, write).await.unwrap_or_else(|_| {
+                self.connections.record_timeout(PipeKind::Files, PipeOperation::Write, limit);
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("file transfer write took longer than {:?}", limit)))
+            })?,
+            None => write.await?,
+        };
+        Ok(true)
+    }
+
+    // Offer a file to the backend; it's sent once the backend accepts it
+    async fn offer_file(&self, offer: &FileOffer) -> io::Result<bool> {
+        self.write_files_message(MessageType::FileOffer, &offer.encode(), &Bytes::new()).await
+    }
+
+    // Spawns the task that sends the chunks of files the backend accepted, one file at a time
+    fn spawn_files_writer_task(&self) {
+        let state = self.clone();
+        self.tasks.files_writer.replace(|| self.supervisor.spawn("files-writer", move || {
+            let state = state.clone();
+            async move {
+                let transfers = &state.file_transfers;
+                loop {
+                    let (id, mut offset) = transfers.next_accepted().await;
+                    while let Some((chunk, name, total)) = transfers.chunk(id, offset) {
+                        match state.write_files_message(MessageType::FileChunk, &file_transfer::encode_position(id, offset), &chunk).await {
+                            Ok(true) => {}
+                            // The rest goes out when the file is offered again after the reconnect
+                            Ok(false) => break,
+                            Err(e) => {
+                                error!("[Rust Files Pipe] Error writing file {}: {}", id, e);
+                                break;
+                            }
+                        }
+                        offset += chunk.len() as u64;
+                        let progress = FileTransferProgress { transfer_id: id, direction: TransferDirection::ToBackend, name, bytes: offset, total };
+                        if let Err(e) = state.app_handle.emit("file-transfer-progress", progress) {
+                            error!("[Rust Files Pipe] Error emitting file-transfer-progress event: {}", e);
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    // Open a microphone and stream it over the microphone pipe, replacing the one capturing so far
+    fn start_microphone(&self, device_id: Option<String>) -> Result<MicrophoneInfo, PipeError> {
+        let mut microphone = self.microphone.lock();
+        // Two captures of the same device may not be allowed, so the old one goes first
+        *microphone = None;
+        let (app_handle, connections, queue) = (self.app_handle.clone(), self.connections.clone(), Arc::clone(&self.microphone_queue));
+        let mut meter = LevelMeter::default();
+        let mut level_due = Instant::now() + LEVEL_INTERVAL;
+        let on_chunk = move |format: AudioFormat, samples: &[u8]| {
+            meter.measure(format, samples);
+            if Instant::now() >= level_due {
+                level_due += LEVEL_INTERVAL;
+                if let Err(e) = app_handle.emit("microphone-level", meter.take()) {
+                    error!("[Rust Microphone] Error emitting microphone-level event: {}", e);
+                }
+            }
+            // Nothing piles up while there's no backend to take it
+            if !connections.is_connected(PipeKind::Microphone) {
+                return;
+            }
+            let frames = samples.len() / (format.channels as usize * format.sample_format.bytes_per_sample());
+            let header = AudioHeader { overlay_id: 0, format, timestamp_us: protocol::timestamp_us(), frames: frames as u32 };
+            let dropped = queue.push(AudioChunk { header, samples: Bytes::copy_from_slice(samples), queued_at: Instant::now() });
+            emit_audio_dropped(&app_handle, PipeKind::Microphone, &queue, dropped);
+        };
+        let app_handle = self.app_handle.clone();
+        let on_stopped = move |e: io::Error| {
+            error!("[Rust Microphone] Capture stopped: {}", e);
+            if let Err(e) = app_handle.emit("microphone-stopped", MicrophoneStoppedPayload { reason: e.to_string() }) {
+                error!("[Rust Microphone] Error emitting microphone-stopped event: {}", e);
+            }
+        };
+        let started = Microphone::start(device_id, on_chunk, on_stopped).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => PipeError::InvalidArgument(format!("Unknown audio input: {}", e)),
+            _ => PipeError::sink_start("Failed to open the microphone", &e),
+        })?;
+        let info = started.info().clone();
+        info!("[Rust Microphone] Capturing {} ({} Hz, {} channels).", info.device.name, info.sample_rate, info.channels);
+        *microphone = Some(started);
+        Ok(info)
+    }
+
+    // Where a capture thread hands its images (packed BGRA8) for `overlay_id`: into the frame pipeline,
+    // the way send_frame_data would
+    fn capture_frames(&self, overlay_id: u32) -> impl FnMut(u32, u32, &[u8]) + Send + 'static {
+        let state = self.clone();
+        move |width: u32, height: u32, pixels: &[u8]| {
+            // Like send_frame_data: paused streams swallow frames, and frames need somewhere to go
+            if state.paused.load(Ordering::Acquire) || (!state.connections.is_connected(PipeKind::Frame) && state.sinks.is_empty()) {
+                return;
+            }
+            if check_frame_size(PixelFormat::Bgra8, width, height, width * 4, pixels.len(), state.max_frame_bytes).is_err() {
+                state.metrics.record_frame_rejected();
+                return;
+            }
+            let header = FrameHeader {
+                width,
+                height,
+                sequence: state.next_sequence.fetch_add(1, Ordering::Relaxed),
+                timestamp_us: protocol::timestamp_us(),
+                overlay_id,
+                stride: width * 4,
+                pixel_format: PixelFormat::Bgra8,
+            };
+            let mut buffer = state.buffers.take(pixels.len());
+            buffer.extend_from_slice(pixels);
+            let frame = QueuedFrame { header, pixels: buffer.into_bytes() };
+            *state.last_frame.lock() = Some(frame.clone());
+            // The capture thread waits for its turn, the way send_frame_data's caller would
+            if let Some(frame) = state.frame_rate.admit(frame) {
+                state.rt.block_on(state.deliver(frame));
+            }
+        }
+    }
+
+    // Reports a capture to `overlay_id` that ended on its own with "desktop-capture-stopped"
+    fn capture_stopped(&self, overlay_id: u32) -> impl FnOnce(io::Error) + Send + 'static {
+        let app_handle = self.app_handle.clone();
+        move |e: io::Error| {
+            error!("[Rust Desktop Capture] Capture to overlay {} stopped: {}", overlay_id, e);
+            let payload = DesktopCaptureStoppedPayload { overlay_id, reason: e.to_string() };
+            if let Err(e) = app_handle.emit("desktop-capture-stopped", payload) {
+                error!("[Rust Desktop Capture] Error emitting desktop-capture-stopped event: {}", e);
+            }
+        }
+    }
+
+    fn check_capture_overlay(&self, overlay_id: u32) -> Result<(), PipeError> {
+        if overlay_id != DEFAULT_OVERLAY_ID && self.overlays.get(overlay_id).is_none() {
+            return Err(PipeError::InvalidArgument(format!("Unknown overlay {}", overlay_id)));
+        }
+        Ok(())
+    }
+
+    // Duplicate a monitor or window into the frame pipeline, replacing the overlay's capture
+    fn start_desktop_capture(&self, target: CaptureTarget, options: CaptureOptions) -> Result<DesktopCaptureInfo, PipeError> {
+        options.validate().map_err(PipeError::InvalidArgument)?;
+        let overlay_id = options.overlay_id;
+        self.check_capture_overlay(overlay_id)?;
+        // A process may only duplicate a monitor so many times, so the old capture goes first.
+        // Its thread is joined outside the lock
+        let replaced = self.captures.lock().remove(&overlay_id);
+        drop(replaced);
+        let started = DesktopCapture::start(target, options, self.capture_frames(overlay_id), self.capture_stopped(overlay_id)).map_err(|e| {
+            match e.kind() {
+                io::ErrorKind::NotFound => PipeError::InvalidArgument(e.to_string()),
+                _ => PipeError::sink_start("Failed to start desktop capture", &e),
+            }
+        })?;
+        let info = started.info().clone();
+        info!("[Rust Desktop Capture] Capturing {:?} on {} ({}x{}) to overlay {} at up to {} FPS.", target, info.monitor, info.width, info.height, overlay_id, options.max_fps);
+        // A capture started for the same overlay in the meantime makes way, stopped outside the lock
+        let replaced = self.captures.lock().insert(overlay_id, OverlayCapture::Desktop(started));
+        drop(replaced);
+        Ok(info)
+    }
+
+    // Draw a window into the frame pipeline, replacing the overlay's capture
+    fn capture_window(&self, hwnd: u64, options: WindowCaptureOptions) -> Result<WindowCaptureInfo, PipeError> {
+        options.validate().map_err(PipeError::InvalidArgument)?;
+        let overlay_id = options.overlay_id;
+        self.check_capture_overlay(overlay_id)?;
+        let replaced = self.captures.lock().remove(&overlay_id);
+        drop(replaced);
+        let started = WindowCapture::start(hwnd, options, self.capture_frames(overlay_id), self.capture_stopped(overlay_id)).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => PipeError::InvalidArgument(e.to_string()),
+            _ => PipeError::sink_start("Failed to capture the window", &e),
+        })?;
+        let info = started.info().clone();
+        info!("[Rust Desktop Capture] Capturing window {:#x} ({:?}) to overlay {} at up to {} FPS.", hwnd, info.title, overlay_id, options.max_fps);
+        let replaced = self.captures.lock().insert(overlay_id, OverlayCapture::Window(started));
+        drop(replaced);
+        Ok(info)
+    }
+
+    // Spawns the task that turns metric counters into rates and emits "pipe-stats"
+    fn spawn_metrics_sampler(&self) {
+        let state = self.clone();
+        self.tasks.metrics_sampler.replace(|| self.supervisor.spawn("metrics-sampler", move || {
+            let state = state.clone();
+            async move {
+                loop {
+                    let interval_ms = state.stats_interval_ms.load(Ordering::Relaxed);
+                    let period_ms = if interval_ms == 0 { DEFAULT_SAMPLE_INTERVAL_MS } else { interval_ms };
+                    sleep(Duration::from_millis(period_ms)).await;
+                    state.metrics.sample_rates();
+                    state.adapt_quality();
+                    if interval_ms > 0 {
+                        if let Err(e) = state.app_handle.emit("pipe-stats", state.metrics_snapshot()) {
+                            error!("[Rust Metrics] Error emitting pipe-stats event: {}", e);
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    // Let the adaptive controller judge the last sample's writes and apply whatever it decides
+    fn adapt_quality(&self) {
+        let configured = *self.compression.lock();
+        if let Some(adjustment) = self.adaptive.sample(configured, |codec| self.backend_accepts(codec)) {
+            self.apply_adjustment(adjustment);
+        }
+    }
+
+    fn apply_adjustment(&self, adjustment: Adjustment) {
+        match adjustment {
+            // negotiated_compression picks it up with the next frame
+            Adjustment::Compression(codec) => info!("[Rust Adaptive] Frame compression now {:?}.", codec),
+            Adjustment::Resolution { from, to } => {
+                // Suggest the size the frontend's current frames would have at the new scale
+                let (width, height) = self
+                    .last_frame
+                    .lock()
+                    .as_ref()
+                    .map_or((0, 0), |frame| (frame.header.width, frame.header.height));
+                let rescale = |size: u32| ((size as f32 * to / from / 2.0).round() as u32 * 2).max(2);
+                let payload = TargetResolutionPayload { scale: to, width: rescale(width), height: rescale(height) };
+                info!("[Rust Adaptive] Requesting resolution scale {:.2} ({}x{}).", to, payload.width, payload.height);
+                if let Err(e) = self.app_handle.emit("target-resolution", payload) {
+                    error!("[Rust Adaptive] Error emitting target-resolution event: {}", e);
+                }
+            }
+        }
+    }
+
+    fn metrics_snapshot(&self) -> PipeMetricsSnapshot {
+        self.metrics.snapshot(
+            self.queue.dropped_frames(),
+            self.frame_rate.coalesced(),
+            self.frame_rate.target_fps(),
+            self.buffers.stats(),
+        )
+    }
+
+    // Refuse an invoke whose raw body is over the cap or whose command is over its rate, and count it
+    fn check_invoke(&self, command: &str, body: &InvokeBody) -> Result<(), PipeError> {
+        // JSON bodies are small and were already parsed by Tauri; the raw ones are what can get huge
+        let body_len = match body {
+            InvokeBody::Raw(bytes) => Some(bytes.len()),
+            InvokeBody::Json(_) => None,
+        };
+        let result = self.limiter.check(command, body_len);
+        match &result {
+            Err(PipeError::PayloadTooLarge { .. }) => self.metrics.record_payload_too_large(),
+            Err(PipeError::RateLimited { .. }) => self.metrics.record_rate_limited(),
+            _ => {}
+        }
+        if let Err(e) = &result {
+            debug!("[Rust Limits] Refused {}: {}", command, e);
+        }
+        result
+    }
+
+    // Start whichever of the pipes isn't connected or already trying to connect
+    fn connect(&self) {
+        if !self.tasks.frame_connect.is_running() && !self.connections.is_connected(PipeKind::Frame) {
+            self.spawn_connection_loop();
+        }
+        if !self.tasks.transform_listener.is_running() {
+            self.spawn_transform_listener();
+        }
+        if !self.tasks.input_listener.is_running() {
+            self.spawn_input_listener();
+        }
+        if !self.tasks.audio_listener.is_running() {
+            self.spawn_audio_listener();
+        }
+        if !self.tasks.microphone_listener.is_running() {
+            self.spawn_microphone_listener();
+        }
+        if !self.tasks.video_listener.is_running() {
+            self.spawn_video_listener();
+        }
+        if !self.tasks.files_listener.is_running() {
+            self.spawn_files_listener();
+        }
+        if !self.tasks.pointer_listener.is_running() {
+            self.spawn_pointer_listener();
+        }
+    }
+
+    // Stop all connection tasks and close the frame pipe
+    async fn disconnect(&self) {
+        self.tasks.frame_connect.abort();
+        // Dropping the aborted task's reader closes the transform pipe
+        if self.tasks.transform_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Transform, "Disconnected by request");
+        }
+        if self.tasks.input_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Input, "Disconnected by request");
+        }
+        if self.tasks.audio_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Audio, "Disconnected by request");
+        }
+        if self.tasks.microphone_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Microphone, "Disconnected by request");
+        }
+        if self.tasks.video_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Video, "Disconnected by request");
+        }
+        if self.tasks.files_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Files, "Disconnected by request");
+        }
+        if self.tasks.pointer_listener.abort() {
+            self.connections.mark_disconnected(PipeKind::Pointer, "Disconnected by request");
+        }
+        self.input_writer.lock().await.take();
+        self.audio_writer.lock().await.take();
+        self.audio.clear();
+        self.microphone_writer.lock().await.take();
+        self.microphone_queue.clear();
+        self.files_writer.lock().await.take();
+        self.close_frame_writer("Disconnected by request").await;
+    }
+
+    // Flush queued frames, say goodbye to the backend and close every pipe before the app exits
+    async fn shutdown(&self) {
+        if self.shut_down.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        info!("[Rust Connection] Shutting down pipes...");
+        // No reconnects or new inbound connections from here on
+        self.tasks.frame_connect.abort();
+        self.frame_generation.advance();
+        self.tasks.frame_heartbeat.abort();
+        self.tasks.transform_listener.abort();
+        self.tasks.input_listener.abort();
+        self.tasks.audio_listener.abort();
+        self.tasks.audio_writer.abort();
+        self.tasks.microphone_listener.abort();
+        self.tasks.microphone_writer.abort();
+        self.tasks.video_listener.abort();
+        self.tasks.files_listener.abort();
+        self.tasks.files_writer.abort();
+        self.tasks.pointer_listener.abort();
+        // Stops the capture thread
+        self.microphone.lock().take();
+
+        // Give the writer task a moment to get what's queued onto the pipe
+        let flush = async {
+            while !self.queue.is_empty() && self.connections.is_connected(PipeKind::Frame) {
+                sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        };
+        if tokio::time::timeout(SHUTDOWN_FLUSH_TIMEOUT, flush).await.is_err() {
+            warn!("[Rust Frame Pipe] Dropping {} frames that couldn't be flushed before exit.", self.queue.clear());
+        }
+        // Taking the writer waits for a write already in progress to finish
+        let frame_writer = tokio::time::timeout(SHUTDOWN_FLUSH_TIMEOUT, self.pipe_writer.lock())
+            .await
+            .ok()
+            .and_then(|mut guard| guard.take());
+        self.connections.mark_disconnected(PipeKind::Frame, "Shutting down");
+        self.tasks.writer.abort();
+        self.tasks.frame_release.abort();
+        self.tasks.encoder_output.abort();
+        self.tasks.haptics.abort();
+        self.tasks.metrics_sampler.abort();
+        self.tasks.transform_emitter.abort();
+        if let Some(encoder) = self.video_encoder.lock().await.take() {
+            encoder.stop().await;
+        }
+        // A recording still running gets its buffered frames written out
+        match self.recorder.stop().await {
+            Some(Ok(summary)) => info!("[Rust Recorder] Saved {} frames to {}.", summary.frames, summary.path.display()),
+            Some(Err(e)) => error!("[Rust Recorder] Error finishing recording: {}", e),
+            None => {}
+        }
+        match self.session.stop().await {
+            Some(Ok(summary)) => info!("[Rust Session] Saved {} messages to {}.", summary.messages, summary.path.display()),
+            Some(Err(e)) => error!("[Rust Session] Error finishing session recording: {}", e),
+            None => {}
+        }
+
+        let goodbye = protocol::encode(MessageType::Goodbye, 0, &[]);
+        let input_writer = self.input_writer.lock().await.take();
+        let audio_writer = self.audio_writer.lock().await.take();
+        let microphone_writer = self.microphone_writer.lock().await.take();
+        let files_writer = self.files_writer.lock().await.take();
+        let writers = [
+            (PipeKind::Frame, frame_writer),
+            (PipeKind::Input, input_writer),
+            (PipeKind::Audio, audio_writer),
+            (PipeKind::Microphone, microphone_writer),
+            (PipeKind::Files, files_writer),
+        ];
+        for (pipe, writer) in writers {
+            let Some(mut writer) = writer else {
+                continue;
+            };
+            let closed = async {
+                writer.write_all(&goodbye).await?;
+                writer.shutdown().await
+            };
+            match tokio::time::timeout(SHUTDOWN_FLUSH_TIMEOUT, closed).await {
+                Ok(Ok(())) => info!("[Rust Connection] Closed {:?} pipe.", pipe),
+                Ok(Err(e)) => error!("[Rust Connection] Error closing {:?} pipe: {}", pipe, e),
+                Err(_) => warn!("[Rust Connection] Timed out closing {:?} pipe.", pipe),
+            }
+        }
+        // The transform pipe was closed when its listener task was aborted
+    }
+
+    // Feed a recording into the frame queue at the pace it was recorded (the --replay mode).
+    // Frames get fresh sequence numbers and timestamps, as if the webview had just sent them.
+    async fn replay(&self, path: &Path) -> io::Result<u64> {
+        let mut recording = Replay::open(path, self.max_frame_bytes).await?;
+        // Nothing would take the frames before the backend is there
+        while !self.connections.is_connected(PipeKind::Frame) {
+            sleep(REPLAY_CONNECT_POLL_INTERVAL).await;
+        }
+        info!("[Rust Recorder] Replaying {}.", path.display());
+        let started = tokio::time::Instant::now();
+        let mut replayed = 0;
+        while let Some((offset_us, frame)) = recording.next_frame().await? {
+            tokio::time::sleep_until(started + Duration::from_micros(offset_us)).await;
+            if !self.connections.is_connected(PipeKind::Frame) {
+                // Like send_frame_data, frames arriving while disconnected are refused
+                continue;
+            }
+            let header = FrameHeader {
+                sequence: self.next_sequence.fetch_add(1, Ordering::Relaxed),
+                timestamp_us: protocol::timestamp_us(),
+                ..frame.header
+            };
+            self.queue.push(QueuedFrame { header, ..frame }).await;
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+
+    // Feed what the backend sent in a session recording back through the pipe readers, at `speed` times the
+    // recorded pace (0 for as fast as possible), so the frontend gets the same events without a backend
+    async fn replay_session(&self, path: &Path, speed: f64) -> io::Result<SessionReplaySummary> {
+        let mut recording = SessionReplay::open(path).await?;
+        info!("[Rust Session] Replaying {}.", path.display());
+        let (frame_pipe, mut frame_feed) = tokio::io::duplex(SESSION_REPLAY_BUFFER);
+        let (transform_pipe, mut transform_feed) = tokio::io::duplex(SESSION_REPLAY_BUFFER);
+        let (input_pipe, mut input_feed) = tokio::io::duplex(SESSION_REPLAY_BUFFER);
+        let (video_pipe, mut video_feed) = tokio::io::duplex(SESSION_REPLAY_BUFFER);
+        let (pointer_pipe, mut pointer_feed) = tokio::io::duplex(SESSION_REPLAY_BUFFER);
+        let liveness = Liveness::default();
+        let readers = async {
+            let mut transform_pipe = BufReader::new(transform_pipe);
+            let mut input_pipe = BufReader::new(input_pipe);
+            let mut video_pipe = BufReader::new(video_pipe);
+            let mut pointer_pipe = BufReader::new(pointer_pipe);
+            tokio::join!(
+                read_frame_pipe(frame_pipe, &liveness, |message| self.handle_frame_pipe_message(message)),
+                handle_transform_connection(&mut transform_pipe, self, &liveness),
+                handle_input_connection(&mut input_pipe, self.app_handle.clone(), &self.session, &liveness),
+                handle_video_connection(&mut video_pipe, self, &liveness),
+                handle_pointer_connection(&mut pointer_pipe, self, &liveness),
+            );
+        };
+        let feed = async move {
+            let started = tokio::time::Instant::now();
+            let mut summary = SessionReplaySummary::default();
+            while let Some(recorded) = recording.next_message().await? {
+                if recorded.direction == Direction::ToBackend {
+                    summary.skipped += 1;
+                    continue;
+                }
+                if speed > 0.0 {
+                    tokio::time::sleep_until(started + Duration::from_micros(recorded.offset_us).div_f64(speed)).await;
+                }
+                let feed = match recorded.pipe {
+                    PipeKind::Frame => &mut frame_feed,
+                    PipeKind::Transform => &mut transform_feed,
+                    PipeKind::Input => &mut input_feed,
+                    PipeKind::Video => &mut video_feed,
+                    PipeKind::Pointer => &mut pointer_feed,
+                    // Nothing comes back on the audio pipes but heartbeats, which aren't recorded, and file
+                    // transfers would answer the live backend
+                    PipeKind::Audio | PipeKind::Microphone | PipeKind::Files => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                };
+                let message = &recorded.message;
+                feed.write_all(&protocol::encode(message.header.message_type, message.header.flags, &message.payload)).await?;
+                summary.replayed += 1;
+                summary.duration_ms = recorded.offset_us / 1000;
+            }
+            // Closing the feeds ends the readers
+            io::Result::Ok(summary)
+        };
+        let (summary, ()) = tokio::join!(feed, readers);
+        summary
+    }
+
+    // Drop the current frame pipe connection (if any) and start connecting again
+    async fn reconnect_frame(&self) {
+        self.tasks.frame_connect.abort();
+        self.close_frame_writer("Reconnect requested").await;
+        self.spawn_connection_loop();
+    }
+
+    // Shut down and forget the frame writer, discarding anything still queued
+    async fn close_frame_writer(&self, reason: &str) {
+        self.tasks.frame_heartbeat.abort();
+        // A connection loop that was aborted mid-handshake mustn't bring the pipe back
+        self.frame_generation.advance();
+        let writer = self.pipe_writer.lock().await.take();
+        self.connections.mark_disconnected(PipeKind::Frame, reason);
+        self.queue.clear();
+        self.acks.reset();
+        if let Some(mut writer) = writer {
+            let _ = writer.shutdown().await;
+        }
+    }
+
+    // Spawns the transform pipe listener in the background
+    fn spawn_transform_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Transform, &self.tasks.transform_listener);
+    }
+
+    fn spawn_input_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Input, &self.tasks.input_listener);
+    }
+
+    fn spawn_audio_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Audio, &self.tasks.audio_listener);
+    }
+
+    fn spawn_microphone_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Microphone, &self.tasks.microphone_listener);
+    }
+
+    fn spawn_video_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Video, &self.tasks.video_listener);
+    }
+
+    fn spawn_files_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Files, &self.tasks.files_listener);
+    }
+
+    fn spawn_pointer_listener(&self) {
+        self.spawn_inbound_listener(PipeKind::Pointer, &self.tasks.pointer_listener);
+    }
+
+    fn spawn_inbound_listener(&self, pipe: PipeKind, slot: &TaskSlot) {
+        let state = self.clone();
+        let name = match pipe {
+            PipeKind::Input => "input-listener",
+            PipeKind::Audio => "audio-listener",
+            PipeKind::Microphone => "microphone-listener",
+            PipeKind::Video => "video-listener",
+            PipeKind::Files => "files-listener",
+            PipeKind::Pointer => "pointer-listener",
+            _ => "transform-listener",
+        };
+        slot.replace(|| self.supervisor.spawn(name, move || inbound_pipe_listener(pipe, state.clone())));
+    }
+
+    // Spawns the connection loop in the background, aborting any loop that is still retrying
+    fn spawn_connection_loop(&self) {
+        let state = self.clone();
+        // Aborting the old loop isn't enough: one already past its last await would still install its writer
+        let token = self.frame_generation.advance();
+        let connect_loop = move || {
+            let state = state.clone();
+            let pipe_writer = Arc::clone(&state.pipe_writer);
+            let connections = state.connections.clone();
+            let backend_capabilities = Arc::clone(&state.backend_capabilities);
+            let backend_info = Arc::clone(&state.backend_info);
+            async move {
+                let mut backoff = Backoff::new(connections.reconnect_policy());
+                loop {
+                    let path = state.pipe_paths.lock().frame.clone();
+                    let options = *state.pipe_options.lock();
+                    debug!("[Rust Frame Pipe] Attempting to connect to frame pipe: {}", path);
+                    connections.mark_connecting(PipeKind::Frame);
+                    match open_frame_pipe(&path, &options, &state.pipe_security, &state.auth_token, &connections).await {
+                        Ok((reader, writer, info)) => {
+                            let Some(mut pipe_guard) = lock_if_current(&pipe_writer, &state.frame_generation, token).await else {
+                                info!("[Rust Frame Pipe] Dropping frame pipe connection, a newer connection attempt replaced it.");
+                                break;
+                            };
+                            info!(
+                                "[Rust Frame Pipe] Successfully connected to frame pipe ({}, protocol v{}, capabilities {:#x}).",
+                                info.backend_name.as_deref().unwrap_or("unnamed backend"),
+                                info.protocol_version,
+                                info.capabilities
+                            );
+                            backend_capabilities.store(info.capabilities, Ordering::Release);
+                            *backend_info.lock() = Some(info);
+                            // The backend has no previous frames on a new connection
+                            state.delta.lock().reset();
+                            state.acks.reset();
+                            *pipe_guard = Some(writer);
+                            connections.mark_connected(PipeKind::Frame);
+                            drop(pipe_guard);
+                            state.announce_overlays().await;
+                            state.announce_placements().await;
+                            state.announce_dashboard().await;
+                            // A new backend doesn't know the stream was paused before it connected
+                            if state.paused.load(Ordering::Acquire) {
+                                state.send_control(&ControlMessage::Pause).await;
+                            }
+                            // Disconnect monitoring: a failed write or a heartbeat timeout sets the Option back to None
+                            // and restarts the connection loop.
+                            state.spawn_frame_heartbeat(reader);
+                            break; // Exit loop once connected.
+                        }
+                        Err(e) => {
+                            let failure = ConnectFailure::of(&e);
+                            connections.record_failure(PipeKind::Frame, failure, e.to_string());
+                            let Some(delay) = backoff.next_delay() else {
+                                error!(
+                                    "[Rust Frame Pipe] Failed to connect to frame pipe ({}): {}. Giving up after {} attempts.",
+                                    failure,
+                                    e,
+                                    backoff.attempt() - 1
+                                );
+                                connections.mark_gave_up(PipeKind::Frame);
+                                break;
+                            };
+                            warn!("[Rust Frame Pipe] Failed to connect to frame pipe ({}): {}. Retrying in {:?}...", failure, e, delay);
+                            connections.mark_backoff(PipeKind::Frame, delay);
+                            sleep(delay).await;
+                        }
+                    }
+                }
+            }
+        };
+        self.tasks.frame_connect.replace(|| self.supervisor.spawn("frame-connect", connect_loop));
+    }
+
+    // Compression to use for the next frame: the requested codec if the backend accepted it
+    fn negotiated_compression(&self) -> Compression {
+        // The adaptive controller may have stepped up from the requested codec
+        let requested = self.adaptive.compression(*self.compression.lock());
+        if self.backend_accepts(requested) { requested } else { Compression::None }
+    }
+
+    fn backend_accepts(&self, compression: Compression) -> bool {
+        let capabilities = self.backend_capabilities.load(Ordering::Acquire);
+        match compression {
+            Compression::None => true,
+            Compression::Lz4 => capabilities & handshake::CAP_LZ4 != 0,
+            Compression::Zstd => capabilities & handshake::CAP_ZSTD != 0,
+        }
+    }
+
+    // A pose from outside the transform pipe (WebRTC peer, OSC, a pose source), handled like one from the backend
+    fn receive_remote_pose(&self, pose: Pose) {
+        self.metrics.record_transform_received();
+        if let Err(invalid) = validation::check_pose(&pose) {
+            let source = pose::device_name(pose.device_id).into_owned();
+            emit_transform_invalid(&self.app_handle, source, invalid, validation::pose_values(&pose));
+            return;
+        }
+        // The sender's clock isn't ours; prediction needs the time the pose arrived here
+        let pose = Pose { timestamp_us: protocol::timestamp_us(), ..pose };
+        let (pose, _) = self.transform_filter.apply(pose);
+        let pose = self.pose_predictor.predict(pose, pose.timestamp_us);
+        forward_transform(&self.app_handle, &self.transforms, pose, pose.to_matrix().to_vec());
+    }
+
+    // The frame XORed against the previous one, if delta frames are on and accepted (None for keyframes)
+    fn delta_frame(&self, frame: &QueuedFrame) -> Option<Vec<u8>> {
+        let mut delta = self.delta.lock();
+        let accepted = self.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_DELTA != 0;
+        if !self.delta_frames.load(Ordering::Acquire) || !accepted {
+            // Whatever the backend receives next is whole, so start over from there
+            delta.reset();
+            return None;
+        }
+        delta.encode(&frame.header, &frame.pixels)
+    }
+
+    // Protocol version of the current connection, which decides the frame header layout
+    fn protocol_version(&self) -> u8 {
+        self.backend_info.lock().as_ref().map_or(MIN_PROTOCOL_VERSION, |info| info.protocol_version)
+    }
+
+    // Register an overlay for a window and tell the backend about it
+    async fn add_overlay(&self, name: &str, window: &str) -> Result<Overlay, PipeError> {
+        if self.connections.is_connected(PipeKind::Frame) && self.protocol_version() < 2 {
+            return Err(PipeError::Unsupported("Backend does not support multiple overlays".to_string()));
+        }
+        let overlay = self.overlays.register(name, window)?;
+        info!("[Rust Frame Pipe] Registered overlay {} ({:?}) for window {}.", overlay.id, overlay.name, overlay.window);
+        // Not connected yet: the connection loop announces it once the backend is there
+        if self.connections.is_connected(PipeKind::Frame) {
+            self.send_message(&protocol::encode(MessageType::OverlayRegister, 0, &overlay.encode_register())).await?;
+        }
+        Ok(overlay)
+    }
+
+    async fn remove_overlay(&self, id: u32) -> Result<(), PipeError> {
+        let Some(overlay) = self.overlays.unregister(id) else {
+            return Err(PipeError::InvalidArgument(format!("Unknown overlay {}", id)));
+        };
+        self.remove_overlays(vec![overlay]).await
+    }
+
+    // Tell the backend to destroy overlays that were just unregistered
+    // Every overlay is cleaned up even if telling the backend fails; the first failure is returned
+    async fn remove_overlays(&self, overlays: Vec<Overlay>) -> Result<(), PipeError> {
+        let mut result = Ok(());
+        for overlay in overlays {
+            info!("[Rust Frame Pipe] Unregistered overlay {} ({:?}).", overlay.id, overlay.name);
+            self.placement.remove(overlay.id);
+            // Stopped (and its thread joined) outside the lock
+            let removed = self.captures.lock().remove(&overlay.id);
+            if let Some(capture) = removed {
+                info!("[Rust Desktop Capture] Stopped capturing {} to overlay {}.", capture.describe(), overlay.id);
+            }
+            if self.connections.is_connected(PipeKind::Frame) && self.protocol_version() >= 2 {
+                let message = protocol::encode(MessageType::OverlayUnregister, 0, &overlay.encode_unregister());
+                if let Err(e) = self.send_message(&message).await {
+                    error!("[Rust Frame Pipe] Error unregistering overlay {}: {}", overlay.id, e);
+                    result = result.and(Err(e));
+                }
+            }
+        }
+        result
+    }
+
+    // Tell a freshly connected backend about the overlays registered so far (v1 backends only know overlay 0)
+    async fn announce_overlays(&self) {
+        if self.protocol_version() < 2 {
+            return;
+        }
+        for overlay in self.overlays.all() {
+            let message = protocol::encode(MessageType::OverlayRegister, 0, &overlay.encode_register());
+            if let Err(e) = self.send_message(&message).await {
+                error!("[Rust Frame Pipe] Error announcing overlay {}: {}", overlay.id, e);
+                return;
+            }
+        }
+    }
+
+    // Send a freshly connected backend the placement set so far
+    async fn announce_placements(&self) {
+        for (overlay_id, placement) in self.placement.all() {
+            for change in placement.changes() {
+                self.send_control(&change.to_control(overlay_id)).await;
+            }
+        }
+    }
+
+    // Ask a freshly connected backend to keep the surface in the dashboard
+    async fn announce_dashboard(&self) {
+        let dashboard = self.dashboard.config();
+        if dashboard.enabled {
+            self.send_control(&ControlMessage::SetDashboardMode { enabled: true, icon_path: dashboard.icon_path }).await;
+        }
+    }
+
+    // The dashboard tab opened or closed, as the backend or the OpenVR layer saw it
+    fn dashboard_visibility(&self, visible: bool) {
+        if !self.dashboard.set_visible(visible) {
+            return;
+        }
+        info!("[Rust Dashboard] Dashboard tab {}.", if visible { "opened" } else { "closed" });
+        if let Err(e) = self.app_handle.emit("dashboard-visibility", DashboardVisibility { visible }) {
+            error!("[Rust Dashboard] Error emitting dashboard-visibility event: {}", e);
+        }
+    }
+
+    // Move or restyle an overlay: tell the backend, then the in-process runtime layers
+    async fn set_overlay_placement(&self, overlay_id: u32, change: PlacementChange) -> Result<(), PipeError> {
+        change.validate().map_err(PipeError::InvalidArgument)?;
+        if overlay_id != DEFAULT_OVERLAY_ID && self.overlays.get(overlay_id).is_none() {
+            return Err(PipeError::InvalidArgument(format!("Unknown overlay {}", overlay_id)));
+        }
+        self.send_control(&change.to_control(overlay_id)).await;
+        self.placement.set(overlay_id, change).map_err(|e| PipeError::sink_start("Placing the overlay", &e))
+    }
+
+    // Stop or restart streaming, telling the backend; returns false if it already was in that state
+    async fn set_paused(&self, paused: bool) -> bool {
+        if self.paused.swap(paused, Ordering::AcqRel) == paused {
+            return false;
+        }
+        if paused {
+            // Frames queued or held back before the pause would otherwise go out after it
+            self.queue.clear();
+            self.frame_rate.clear();
+            info!("[Rust Frame Pipe] Stream paused.");
+            self.send_control(&ControlMessage::Pause).await;
+        } else {
+            info!("[Rust Frame Pipe] Stream resumed.");
+            self.send_control(&ControlMessage::Resume).await;
+        }
+        true
+    }
+
+    // Send a Control message if the backend accepted them; a backend that didn't is left alone
+    async fn send_control(&self, message: &ControlMessage) {
+        if self.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_CONTROL == 0
+            || !self.connections.is_connected(PipeKind::Frame)
+        {
+            return;
+        }
+        let message = protocol::encode(MessageType::Control, 0, &control::encode(message));
+        if let Err(e) = self.send_message(&message).await {
+            error!("[Rust Frame Pipe] Error sending control message: {}", e);
+        }
+    }
+
+    // Feed the frame to the hardware encoder if encoding is enabled and available.
+    // Returns false if the frame should go out raw instead.
+    async fn encode_frame(&self, frame: &QueuedFrame) -> bool {
+        let settings = *self.encoder_settings.lock();
+        if settings.codec == VideoCodec::None || self.encoder_failed.load(Ordering::Acquire) || self.shm_ring.lock().is_some() {
+            return false;
+        }
+        let header = frame.header;
+        if header.overlay_id != DEFAULT_OVERLAY_ID {
+            // There is a single encoder stream; other overlays go out raw
+            return false;
+        }
+        // ffmpeg is fed 4:2:0 YUV; packed NV12 from the webview can go in as is
+        let passthrough = settings.input == YuvLayout::Nv12
+            && header.pixel_format == PixelFormat::Nv12
+            && header.stride == header.width;
+        let pixels = if passthrough {
+            frame.pixels.clone()
+        } else {
+            let converted = pixel_format::packed_rgba(frame, &self.buffers);
+            let rgba = &converted.as_ref().unwrap_or(frame).pixels;
+            Bytes::from(colorspace::to_yuv(settings.input, rgba, header.width as usize, header.height as usize))
+        };
+
+        let mut encoder_guard = self.video_encoder.lock().await;
+        if !encoder_guard.as_ref().is_some_and(|encoder| encoder.matches(&settings, header.width, header.height)) {
+            // First frame, new resolution or new settings: restart the encoder
+            if let Some(old) = encoder_guard.take() {
+                old.stop().await;
+            }
+            match VideoEncoder::start(settings, header.width, header.height).await {
+                Ok((encoder, output)) => {
+                    info!(
+                        "[Rust Encoder] Started {} for {}x{} at {} kbps.",
+                        encoder.encoder_name, header.width, header.height, settings.bitrate_kbps
+                    );
+                    self.spawn_encoder_forwarder(output, settings.codec);
+                    *encoder_guard = Some(encoder);
+                }
+                Err(e) => {
+                    self.fall_back_to_raw(format!("Failed to start encoder: {}", e));
+                    return false;
+                }
+            }
+        }
+
+        let Some(encoder) = encoder_guard.as_mut() else {
+            return false;
+        };
+        let write_started = Instant::now();
+        match encoder.encode(&pixels).await {
+            Ok(()) => {
+                self.preview.offer(frame, &self.buffers);
+                let write_us = write_started.elapsed().as_micros() as u64;
+                let latency_us = protocol::timestamp_us().saturating_sub(header.timestamp_us);
+                self.metrics.record_frame_sent(header.sequence, pixels.len(), write_us, latency_us);
+                true
+            }
+            Err(e) => {
+                if let Some(encoder) = encoder_guard.take() {
+                    encoder.stop().await;
+                }
+                self.fall_back_to_raw(format!("Encoder stopped accepting frames: {}", e));
+                false
+            }
+        }
+    }
+
+    // Stop trying to encode until the settings change, and tell the frontend why
+    fn fall_back_to_raw(&self, reason: String) {
+        warn!("[Rust Encoder] {}. Falling back to raw frames.", reason);
+        self.encoder_failed.store(true, Ordering::Release);
+        if let Err(e) = self.app_handle.emit("encoder-fallback", EncoderFallbackPayload { reason }) {
+            error!("[Rust Encoder] Error emitting encoder-fallback event: {}", e);
+        }
+    }
+
+    // Spawns the task that forwards ffmpeg's encoded output to the frame pipe as VideoChunk messages
+    fn spawn_encoder_forwarder(&self, output: tokio::process::ChildStdout, codec: VideoCodec) {
+        let pipe_writer = Arc::clone(&self.pipe_writer);
+        // The encoder's output is a plain byte stream, so a restarted task just reads on
+        let output = Arc::new(TokioMutex::new(output));
+        self.tasks.encoder_output.replace(|| {
+            self.supervisor.spawn("encoder-output", move || {
+                let (pipe_writer, output) = (Arc::clone(&pipe_writer), Arc::clone(&output));
+                async move {
+                    let mut output = output.lock().await;
+                    let mut chunk = vec![0u8; ENCODED_CHUNK_SIZE];
+                    loop {
+                        let n = match output.read(&mut chunk).await {
+                            Ok(0) => break, // Encoder exited
+                            Ok(n) => n,
+                            Err(e) => {
+                                error!("[Rust Encoder] Error reading encoder output: {}", e);
+                                break;
+                            }
+                        };
+                        let message = protocol::encode(MessageType::VideoChunk, codec.flag(), &chunk[..n]);
+                        let mut pipe_guard = pipe_writer.lock().await;
+                        if let Some(writer) = pipe_guard.as_mut() {
+                            // A failed write is picked up (and reconnected) by the frame writer task
+                            if let Err(e) = writer.write_all(&message).await {
+                                error!("[Rust Encoder] Error writing encoded chunk: {}", e);
+                            }
+                        }
+                    }
+                }
+            })
+        });
+    }
+
+    // Write one small, already encoded message straight to the frame pipe
+    async fn send_message(&self, message: &[u8]) -> Result<(), PipeError> {
+        let mut pipe_guard = self.pipe_writer.lock().await;
+        let Some(writer) = pipe_guard.as_mut() else {
+            return Err(PipeError::NotConnected(PipeKind::Frame));
+        };
+        if let Err(e) = writer.write_all(message).await {
+            *pipe_guard = None;
+            drop(pipe_guard);
+            self.handle_write_error(&e);
+            return Err(PipeError::write_failed(PipeKind::Frame, &e));
+        }
+        Ok(())
+    }
+
+    // Watches the frame connection that was just opened; a dead connection is dropped and reconnected
+    fn spawn_frame_heartbeat(&self, reader: ReadHalf<PlatformTransport>) {
+        let state = self.clone();
+        // A panic may have left the reader mid-message, so a restarted task reconnects instead of reading on
+        let reader = Arc::new(parking_lot::Mutex::new(Some(reader)));
+        self.tasks.frame_heartbeat.replace(|| {
+            self.supervisor.spawn("frame-heartbeat", move || {
+                let (state, reader) = (state.clone(), reader.lock().take());
+                async move {
+                    let Some(reader) = reader else {
+                        state.drop_dead_frame_connection("heartbeat task restarted").await;
+                        return;
+                    };
+                    let liveness = Liveness::default();
+                    let reason = tokio::select! {
+                        reason = read_frame_pipe(reader, &liveness, |message| state.handle_frame_pipe_message(message)) => reason,
+                        reason = heartbeat::monitor(&state.pipe_writer, &liveness, &state.heartbeat, || state.heartbeat_enabled()) => reason,
+                    };
+                    state.drop_dead_frame_connection(&reason).await;
+                }
+            })
+        });
+    }
+
+    // A message the backend sent on the frame pipe, other than heartbeats
+    fn handle_frame_pipe_message(&self, message: Message) {
+        self.session.record_received(PipeKind::Frame, &message);
+        match message.header.message_type {
+            MessageType::Control => handle_control_message(self, &message.payload),
+            MessageType::FrameAck => handle_frame_ack(self, &message.payload),
+            message_type => warn!("[Rust Frame Pipe] Ignoring unexpected {:?} message.", message_type),
+        }
+    }
+
+    fn heartbeat_enabled(&self) -> bool {
+        self.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_HEARTBEAT != 0
+    }
+
+    // Like handle_write_error, for a connection found dead by the heartbeat task
+    async fn drop_dead_frame_connection(&self, reason: &str) {
+        let Some(writer) = self.pipe_writer.lock().await.take() else {
+            // Already torn down (write error, disconnect request)
+            return;
+        };
+        drop(writer);
+        warn!("[Rust Frame Pipe] Frame pipe is dead: {}. Disconnecting and attempting reconnect.", reason);
+        self.connections.mark_disconnected(PipeKind::Frame, reason);
+        self.queue.clear();
+        self.spawn_connection_loop();
+    }
+
+    // Shared teardown after a failed write; the caller has already cleared pipe_writer
+    fn handle_write_error(&self, e: &io::Error) {
+        self.tasks.frame_heartbeat.abort();
+        error!("[Rust Frame Pipe] Error writing to frame pipe: {}. Disconnecting and attempting reconnect.", e);
+        self.connections.mark_disconnected(PipeKind::Frame, format!("Write failed: {}", e));
+        // Frames queued for the dead connection are stale by the time we reconnect
+        self.queue.clear();
+        // Spawn a new connection attempt
+        self.spawn_connection_loop();
+    }
+
+    // Spawns the single task that drains the frame queue into the pipe
+    fn spawn_writer_task(&self) {
+        let state = self.clone();
+        self.tasks.writer.replace(|| self.supervisor.spawn("writer", move || {
+            let state = state.clone();
+            async move {
+                loop {
+                    let frame = state.queue.pop().await;
+                    // Replayed frames are queued without going through send_frame_data
+                    if state.paused.load(Ordering::Acquire) {
+                        continue;
+                    }
+                    // Write errors are handled (with a reconnect) inside write_frame, the pipe sink never fails
+                    let _ = FrameSink::write(&state, &frame).await;
+                }
+            }
+        }));
+    }
+
+    // Spawns the task that hands frames held back by the FPS limit on when their interval is up
+    fn spawn_frame_release_task(&self) {
+        let state = self.clone();
+        self.tasks.frame_release.replace(|| self.supervisor.spawn("frame-release", move || {
+            let state = state.clone();
+            async move {
+                loop {
+                    let (due, next) = state.frame_rate.take_due();
+                    for frame in due {
+                        state.deliver(frame).await;
+                    }
+                    match next {
+                        Some(next) => {
+                            tokio::select! {
+                                _ = tokio::time::sleep_until(next.into()) => {}
+                                _ = state.frame_rate.frame_held() => {}
+                            }
+                        }
+                        None => state.frame_rate.frame_held().await,
+                    }
+                }
+            }
+        }));
+    }
+
+    // Hand a frame that's due to go out to the recorder, the other sinks and the writer task
+    async fn deliver(&self, frame: QueuedFrame) {
+        let header = frame.header;
+        self.recorder.record(&frame);
+        self.session.record_frame(&frame);
+        // Every other sink gets the frame in its own queue, under its own backpressure policy
+        self.sinks.offer(&frame).await;
+
+        // Enqueue header + data; the backpressure policy decides what happens when full
+        let span = debug_span!("enqueue_frame", sequence = header.sequence, overlay_id = header.overlay_id, bytes = frame.pixels.len());
+        let dropped = self.queue.push(frame).instrument(span).await;
+        self.metrics.latency.enqueue.record(protocol::timestamp_us().saturating_sub(header.timestamp_us));
+        if dropped > 0 {
+            let payload = FramesDroppedPayload { dropped, total: self.queue.dropped_frames() };
+            if let Err(e) = self.app_handle.emit("frames-dropped", payload) {
+                error!("[Rust Frame Pipe] Error emitting frames-dropped event: {}", e);
+            }
+        }
+    }
+
+    // Attach a Spout sender under the "spout" sink id, or detach it with None
+    fn set_spout_sender(&self, name: Option<&str>) -> io::Result<()> {
+        // The old sender unregisters itself when dropped, before the new name is registered
+        self.sinks.detach(SPOUT_SINK_ID);
+        if let Some(name) = name {
+            let sink = SinkSpec::Spout { name: name.to_string() }.open(&self.buffers)?;
+            self.sinks.attach(&self.rt, SPOUT_SINK_ID, sink, SinkQueueOptions::default());
+        }
+        Ok(())
+    }
+
+    // Start, restart or stop the NDI source under the "ndi" sink id to match `config`.
+    // The settings are kept even if the source can't be started.
+    fn apply_ndi_config(&self, config: NdiConfig) -> io::Result<()> {
+        config.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.sinks.detach(NDI_SINK_ID);
+        *self.ndi_config.lock() = NdiConfig { enabled: false, ..config.clone() };
+        if config.enabled {
+            let sink = SinkSpec::Ndi(config).open(&self.buffers)?;
+            self.sinks.attach(&self.rt, NDI_SINK_ID, sink, SinkQueueOptions::default());
+        }
+        Ok(())
+    }
+
+    // Start, restart or stop the in-process OpenVR overlay and its pose polling to match `config`.
+    // The settings are kept even if OpenVR can't be started.
+    fn apply_openvr_config(&self, config: OpenVrConfig) -> io::Result<()> {
+        config.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.sinks.detach(OPENVR_SINK_ID);
+        self.pose_sources.stop(OPENVR_SINK_ID);
+        self.placement.detach(OPENVR_SINK_ID);
+        *self.openvr_config.lock() = OpenVrConfig { enabled: false, ..config.clone() };
+        if config.enabled {
+            let state = self.clone();
+            let events = Arc::new(move |visible| state.dashboard_visibility(visible));
+            let output = openvr::start(&config, &self.dashboard.config(), &self.buffers, events)?;
+            self.placement.attach(OPENVR_SINK_ID, output.placement)?;
+            self.sinks.attach(&self.rt, OPENVR_SINK_ID, output.sink, SinkQueueOptions::default());
+            let state = self.clone();
+            self.pose_sources.start(&self.rt, OPENVR_SINK_ID, output.poses, config.pose_rate_hz, Arc::new(move |pose| state.receive_remote_pose(pose)));
+        }
+        Ok(())
+    }
+
+    // The same for the OpenXR overlay session
+    fn apply_openxr_config(&self, config: OpenXrConfig) -> io::Result<()> {
+        config.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.sinks.detach(OPENXR_SINK_ID);
+        self.pose_sources.stop(OPENXR_SINK_ID);
+        self.placement.detach(OPENXR_SINK_ID);
+        *self.openxr_config.lock() = OpenXrConfig { enabled: false, ..config.clone() };
+        if config.enabled {
+            let output = openxr::start(&config, &self.buffers)?;
+            self.placement.attach(OPENXR_SINK_ID, output.placement)?;
+            self.sinks.attach(&self.rt, OPENXR_SINK_ID, output.sink, SinkQueueOptions::default());
+            let state = self.clone();
+            self.pose_sources.start(&self.rt, OPENXR_SINK_ID, output.poses, config.pose_rate_hz, Arc::new(move |pose| state.receive_remote_pose(pose)));
+        }
+        Ok(())
+    }
+
+    // Write one queued frame (or its shared-memory notification) to the pipe
+    async fn write_frame(&self, frame: &QueuedFrame) {
+        // Hardware encoding takes over the frame entirely; it has to run before the pipe lock is taken
+        // because the encoder output forwarder needs that lock to drain ffmpeg
+        if self.connections.is_connected(PipeKind::Frame) && self.encode_frame(frame).await {
+            // The backend's previous raw frames are no reference for the next ones anymore
+            self.delta.lock().reset();
+            return;
+        }
+
+        // Backends that don't take this pixel format or stride get the frame as packed RGBA
+        let capabilities = self.backend_capabilities.load(Ordering::Acquire);
+        let converted = if pixel_format::backend_accepts(&frame.header, self.protocol_version(), capabilities) {
+            None
+        } else {
+            pixel_format::packed_rgba(frame, &self.buffers)
+        };
+        let frame = converted.as_ref().unwrap_or(frame);
+        let foveated = self.foveate(frame).await;
+        let frame = foveated.as_ref().unwrap_or(frame);
+        self.preview.offer(frame, &self.buffers);
+        let colored = self.convert_color(frame, capabilities).await;
+        let frame = colored.as_ref().unwrap_or(frame);
+
+        // With an ack window, wait for the backend to catch up before putting more on the pipe
+        let acks = self.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_FRAME_ACK != 0;
+        if acks && !self.acks.wait_for_room(self.timeouts.write()).await {
+            warn!("[Rust Frame Pipe] Backend hasn't acknowledged {} frames in time, sending on.", self.acks.in_flight());
+            self.acks.reset();
+        }
+
+        let mut pipe_guard = self.pipe_writer.lock().await;
+        let Some(writer) = pipe_guard.as_mut() else {
+            // Disconnected while the frame was queued, nothing to write it to
+            return;
+        };
+
+        // The header is only encoded now, for the protocol version of the connection it goes out on
+        let frame_header = frame.header.encode();
+        let frame_header = &frame_header[..FrameHeader::size(self.protocol_version())];
+        // In shared-memory mode copy the payload into the ring and only send the frame-ready message
+        let ready_message = match self.shm_ring.lock().as_mut() {
+            Some(ring) => match ring.write_frame(&[frame_header, &frame.pixels]) {
+                Ok(message) => Some(message),
+                Err(e) => {
+                    error!("[Rust Frame Pipe] Error writing frame to shared memory: {}", e);
+                    return;
+                }
+            },
+            None => None,
+        };
+        // Otherwise write the header and the pixels (or their delta) to the pipe, compressing them if negotiated
+        let delta = match &ready_message {
+            Some(_) => {
+                self.delta.lock().reset();
+                None
+            }
+            None => self.delta_frame(frame),
+        };
+        let delta_flag = if delta.is_some() { FLAG_DELTA } else { 0 };
+        // A delta is mostly zeros and only pays off compressed; backends with CAP_DELTA decode LZ4
+        let compression = match self.negotiated_compression() {
+            Compression::None if delta.is_some() => Compression::Lz4,
+            negotiated => negotiated,
+        };
+        let pixels = delta.map_or_else(|| frame.pixels.clone(), Bytes::from);
+        let compressed_pixels = match (&ready_message, compression) {
+            (None, Compression::Lz4 | Compression::Zstd) => {
+                let tiled = capabilities & handshake::CAP_TILED_COMPRESSION != 0;
+                match self.compressor.compress(compression, pixels.clone(), tiled).await {
+                    Ok(compressed) => Some(compressed),
+                    Err(e) => {
+                        error!("[Rust Frame Pipe] Error compressing frame, sending uncompressed: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let (message_type, flags, parts): (MessageType, u16, [&[u8]; 2]) = match (&ready_message, &compressed_pixels) {
+            (Some(message), _) => (MessageType::FrameReady, 0, [message, &[]]),
+            (None, Some((compressed, tiled))) => {
+                let tiled_flag = if *tiled { FLAG_TILED } else { 0 };
+                (MessageType::Frame, delta_flag | tiled_flag | compression.flag(), [frame_header, &compressed[..]])
+            }
+            (None, None) => (MessageType::Frame, delta_flag, [frame_header, &pixels]),
+        };
+        let corrupted = self.chaos.corrupt_frame(parts);
+        let parts = corrupted.as_deref().map_or(parts, |payload| [payload, &[]]);
+        let write_started = Instant::now();
+        let write = async {
+            self.chaos.before_write().await?;
+            write_message(writer, message_type, flags, parts).await
+        };
+        let result = match self.timeouts.write() {
+            Some(limit) => tokio::time::timeout(limit, write).await.unwrap_or_else(|_| {
+                // The backend stopped reading; the half-written frame goes down with the connection
+                self.connections.record_timeout(PipeKind::Frame, PipeOperation::Write, limit);
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("frame write took longer than {:?}", limit)))
+            }),
+            None => write.await,
+        };
+        match result {
+            Ok(written) => {
+                // Record how long the frame spent between send_frame_data and the pipe
+                let write_us = write_started.elapsed().as_micros() as u64;
+                let latency_us = protocol::timestamp_us().saturating_sub(frame.header.timestamp_us);
+                self.metrics.record_frame_sent(frame.header.sequence, written, write_us, latency_us);
+                self.adaptive.record_write(write_us);
+                if acks {
+                    self.acks.sent(frame.header.sequence, protocol::timestamp_us());
+                }
+            }
+            Err(e) => {
+                // Clear the writer to signal disconnection
+                *pipe_guard = None;
+                drop(pipe_guard);
+                self.handle_write_error(&e);
+            }
+        }
+    }
+
+    // The frame with its periphery averaged, if foveation is on and the user is looking at its overlay
+    async fn foveate(&self, frame: &QueuedFrame) -> Option<QueuedFrame> {
+        let (config, center) = self.foveation.target(frame.header.overlay_id)?;
+        let (frame, buffers) = (frame.clone(), Arc::clone(&self.buffers));
+        match tokio::task::spawn_blocking(move || foveation::foveate(&frame, &config, center, &buffers)).await {
+            Ok(foveated) => foveated,
+            Err(e) => {
+                error!("[Rust Frame Pipe] Foveation task failed: {}", e);
+                None
+            }
+        }
+    }
+
+    // The frame in the gamma, alpha and row order the settings (or else the backend) ask for, if that isn't as drawn
+    async fn convert_color(&self, frame: &QueuedFrame, capabilities: u32) -> Option<QueuedFrame> {
+        let conversion = self.color.lock().resolve(capabilities);
+        if conversion == ColorConversion::default() {
+            return None;
+        }
+        let (frame, buffers) = (frame.clone(), Arc::clone(&self.buffers));
+        match tokio::task::spawn_blocking(move || color::convert(&frame, conversion, &buffers)).await {
+            Ok(converted) => converted,
+            Err(e) => {
+                error!("[Rust Frame Pipe] Color conversion task failed: {}", e);
+                None
+            }
+        }
+    }
+
+    // Disconnect every pipe and reconnect to `paths`; shared by set_pipe_paths and config reloads
+    async fn set_pipe_paths(&self, paths: PipePaths) {
+        self.disconnect().await;
+        *self.pipe_paths.lock() = paths.clone();
+        self.connections.set_path(PipeKind::Frame, &paths.frame);
+        self.connections.set_path(PipeKind::Transform, &paths.transform);
+        self.connections.set_path(PipeKind::Input, &paths.input);
+        self.connections.set_path(PipeKind::Audio, &paths.audio);
+        self.connections.set_path(PipeKind::Microphone, &paths.microphone);
+        self.connections.set_path(PipeKind::Video, &paths.video);
+        self.connections.set_path(PipeKind::Files, &paths.files);
+        self.connections.set_path(PipeKind::Pointer, &paths.pointer);
+        info!("[Rust Connection] Pipe paths set to {:?}, reconnecting.", paths);
+        self.connect();
+    }
+
+    // Shared by configure_stream and config reloads
+    fn configure_stream(&self, options: StreamOptions) -> Result<(), PipeError> {
+        if options.queue_depth == Some(0) {
+            return Err(PipeError::InvalidArgument("Queue depth must be at least 1".to_string()));
+        }
+        if options.keyframe_interval == Some(0) {
+            return Err(PipeError::InvalidArgument("Keyframe interval must be at least 1".to_string()));
+        }
+        if let Some(level) = options.zstd_level.filter(|level| !compression::zstd_levels().contains(level)) {
+            let levels = compression::zstd_levels();
+            return Err(PipeError::InvalidArgument(format!(
+                "zstd level {} is outside {}..={}",
+                level,
+                levels.start(),
+                levels.end()
+            )));
+        }
+        if options.compression_workers.is_some_and(|workers| workers > compression::MAX_WORKERS) {
+            return Err(PipeError::InvalidArgument(format!("Compression workers must be at most {}", compression::MAX_WORKERS)));
+        }
+        if options.target_fps.is_some_and(|fps| fps > MAX_TARGET_FPS) {
+            return Err(PipeError::InvalidArgument(format!("Target FPS must be at most {}", MAX_TARGET_FPS)));
+        }
+        if let Some(policy) = options.backpressure {
+            self.queue.set_policy(policy);
+            info!("[Rust Frame Pipe] Backpressure policy set to {:?}.", policy);
+        }
+        if let Some(depth) = options.queue_depth {
+            self.queue.set_depth(depth);
+            info!("[Rust Frame Pipe] Frame queue depth set to {}.", depth);
+        }
+        if let Some(compression) = options.compression {
+            *self.compression.lock() = compression;
+            if self.negotiated_compression() != compression {
+                info!("[Rust Frame Pipe] Backend has not accepted {:?} compression; frames stay uncompressed until it does.", compression);
+            } else {
+                info!("[Rust Frame Pipe] Frame compression set to {:?}.", compression);
+            }
+        }
+        if let Some(level) = options.zstd_level {
+            self.compressor.set_zstd_level(level);
+            info!("[Rust Frame Pipe] zstd level set to {}.", level);
+        }
+        if let Some(workers) = options.compression_workers {
+            self.compressor.set_workers(workers);
+            info!("[Rust Frame Pipe] Compression workers set to {}.", if workers == 0 { "one per core".to_string() } else { workers.to_string() });
+        }
+        if let Some(encoding) = options.encoding {
+            *self.encoder_settings.lock() = encoding;
+            // Give the encoder another chance with the new settings
+            self.encoder_failed.store(false, Ordering::Release);
+            info!("[Rust Frame Pipe] Video encoding set to {:?}.", encoding);
+        }
+        if let Some(window) = options.ack_window {
+            self.acks.set_window(window);
+            if window > 0 && self.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_FRAME_ACK == 0 {
+                info!("[Rust Frame Pipe] Backend doesn't acknowledge frames; the ack window applies once one does.");
+            } else {
+                info!("[Rust Frame Pipe] Ack window set to {} frames.", window);
+            }
+        }
+        if let Some(fps) = options.target_fps {
+            self.frame_rate.set_target_fps(fps);
+            match fps {
+                0 => info!("[Rust Frame Pipe] Frame rate limit lifted."),
+                fps => info!("[Rust Frame Pipe] Frame rate limited to {} FPS.", fps),
+            }
+        }
+        if let Some(interval) = options.keyframe_interval {
+            self.delta.lock().set_keyframe_interval(interval);
+            info!("[Rust Frame Pipe] Delta keyframe interval set to {} frames.", interval);
+        }
+        if let Some(name) = options.spout_sender {
+            self
+                .set_spout_sender((!name.is_empty()).then_some(name.as_str()))
+                .map_err(|e| PipeError::sink_start("Starting the Spout sender", &e))?;
+        }
+        if let Some(color) = options.color {
+            *self.color.lock() = color;
+            info!("[Rust Frame Pipe] Frame color set to {:?}.", color);
+        }
+        if let Some(delta) = options.delta {
+            self.delta_frames.store(delta, Ordering::Release);
+            if delta && self.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_DELTA == 0 {
+                info!("[Rust Frame Pipe] Backend has not accepted delta frames; frames are sent whole until it does.");
+            } else {
+                info!("[Rust Frame Pipe] Delta frames {}.", if delta { "enabled" } else { "disabled" });
+            }
+        }
+        Ok(())
+    }
+}
+
+// The frame pipe is a sink too, fed from the state's own queue (see spawn_writer_task)
+impl FrameSink for FramePipeState {
+    fn describe(&self) -> String {
+        "frame pipe".to_string()
+    }
+
+    fn write<'a>(&'a self, frame: &'a QueuedFrame) -> SinkFuture<'a> {
+        Box::pin(async move {
+            self.metrics.latency.queued.record(protocol::timestamp_us().saturating_sub(frame.header.timestamp_us));
+            let span = debug_span!("write_frame", sequence = frame.header.sequence, bytes = frame.pixels.len());
+            self.write_frame(frame).instrument(span).await;
+            Ok(())
+        })
+    }
+}
+
+
+// Lock `slot` to install a writer opened by the connection loop holding `token`, unless a newer loop (or a disconnect) superseded it
+async fn lock_if_current<'a, W>(
+    slot: &'a TokioMutex<Option<W>>,
+    generation: &Generation,
+    token: u64,
+) -> Option<TokioMutexGuard<'a, Option<W>>> {
+    let guard = slot.lock().await;
+    generation.is_current(token).then_some(guard)
+}
+
+// Open the frame pipe, check and authenticate the backend, and run the capability handshake
+async fn open_frame_pipe(
+    path: &str,
+    options: &PipeOptions,
+    security: &PipeSecurity,
+    auth_token: &AuthToken,
+    connections: &ConnectionTracker,
+) -> io::Result<(ReadHalf<PlatformTransport>, WriteHalf<PlatformTransport>, BackendInfo)> {
+    let client = <PlatformTransport as Transport>::connect(path, options).await?;
+    connections.mark_handshaking(PipeKind::Frame);
+    security.verify_server(&client)?;
+    start_frame_session(client, auth_token).await
+}
+
+// Authenticate and run the capability handshake on any stream to a backend (a pipe, or an in-memory one in tests)
+async fn start_frame_session<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    auth_token: &AuthToken,
+) -> io::Result<(ReadHalf<S>, WriteHalf<S>, BackendInfo)> {
+    auth::authenticate(&mut stream, auth_token).await?;
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let info = handshake::negotiate(&mut reader, &mut writer).await?;
+    Ok((reader, writer, info))
+}
+
+// Write a message whose payload is `parts` joined; returns the bytes written. The protocol header,
+// frame header and pixels go out in one gathered write, without joining them.
+async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message_type: MessageType, flags: u16, parts: [&[u8]; 2]) -> io::Result<usize> {
+    let length = parts.iter().map(|part| part.len()).sum();
+    let header = protocol::encode_header(message_type, flags, length);
+    transport::write_all_vectored(writer, &[&header[..], parts[0], parts[1]]).await?;
+    Ok(header.len() + length)
+}
+
+// Drain what the backend sends on the frame pipe until it closes, handing everything
+// but heartbeats (control messages, acks) to `on_message`; returns why it stopped
+async fn read_frame_pipe<R: AsyncRead + Unpin>(reader: R, liveness: &Liveness, mut on_message: impl FnMut(Message)) -> String {
+    let mut reader = BufReader::new(reader);
+    loop {
+        match protocol::read_message(&mut reader).await {
+            Ok(message) => {
+                liveness.touch();
+                if !heartbeat::is_heartbeat(message.header.message_type) {
+                    on_message(message);
+                }
+            }
+            Err(e) if e.is_eof() => return "Connection closed by backend".to_string(),
+            Err(e) => return format!("Read failed: {}", e),
+        }
+    }
+}
+
+fn handle_frame_ack(state: &FramePipeState, payload: &[u8]) {
+    let sequence = match ack::decode(payload) {
+        Ok(sequence) => sequence,
+        Err(e) => {
+            warn!("[Rust Frame Pipe] Ignoring malformed frame ack: {}", e);
+            return;
+        }
+    };
+    let acked = state.acks.ack(sequence, protocol::timestamp_us());
+    // Nothing new if a later ack already covered it
+    if acked.frames == 0 {
+        return;
+    }
+    let payload = FrameAckedPayload {
+        connection: state.connections.name().map(str::to_string),
+        sequence,
+        frames: acked.frames,
+        round_trip_us: acked.round_trip_us,
+    };
+    if let Err(e) = state.app_handle.emit("frame-acked", payload) {
+        error!("[Rust Frame Pipe] Error emitting frame-acked event: {}", e);
+    }
+}
+
+// Forward a Control message from the backend to the frontend
+fn handle_control_message(state: &FramePipeState, payload: &[u8]) {
+    let message = match control::decode(payload) {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("[Rust Frame Pipe] Ignoring malformed control message: {}", e);
+            return;
+        }
+    };
+    debug!("[Rust Frame Pipe] Backend control message: {:?}", message);
+    match message {
+        ControlMessage::DashboardShown => state.dashboard_visibility(true),
+        ControlMessage::DashboardHidden => state.dashboard_visibility(false),
+        _ => {}
+    }
+    let payload = BackendControlPayload { connection: state.connections.name().map(str::to_string), message };
+    if let Err(e) = state.app_handle.emit("backend-control", payload) {
+        error!("[Rust Frame Pipe] Error emitting backend-control event: {}", e);
+    }
+}
+
+// --- Tauri Commands ---
+
+// Validates the frame and hands it to the writer task; never waits on the pipe.
+// Returns the frame's sequence number, which frame-acked refers to, or None if the stream is paused.
+#[tauri::command(async)] // Make the command async
+async fn send_frame_data(
+    request: tauri::ipc::Request<'_>, // Accept the full request
+    state: State<'_, FramePipeState>, // Keep the state
+    window: tauri::Window, // Picks the named connection and default overlay
+    manager: State<'_, PipeManager>,
+) -> Result<Option<u64>, PipeError> {
+    // Start of the enqueue/queued/total latency stages, and the frame's capture timestamp
+    let received_us = protocol::timestamp_us();
+    // --- Extract Raw Payload Data --- 
+    let tauri::ipc::InvokeBody::Raw(payload) = request.body() else {
+        return Err(PipeError::RequestBodyMustBeRaw);
+    };
+
+    // Ensure the payload is large enough for the header
+    if payload.len() < CLIENT_FRAME_HEADER_SIZE {
+        return Err(PipeError::PayloadTooSmall { length: payload.len(), min: CLIENT_FRAME_HEADER_SIZE });
+    }
+
+    // Parse width and height from the header (length checked above, so these reads can't fail)
+    let mut cursor = Cursor::new(&payload[..CLIENT_FRAME_HEADER_SIZE]);
+    let width = ReadBytesExt::read_u32::<LittleEndian>(&mut cursor).map_err(|e| PipeError::io("Failed to read width from payload", &e))?;
+    let height = ReadBytesExt::read_u32::<LittleEndian>(&mut cursor).map_err(|e| PipeError::io("Failed to read height from payload", &e))?;
+
+    // Windows bound to a named connection stream to it instead of the main frame pipe
+    let named_state = manager.for_window(window.label());
+    let state = named_state.as_ref().unwrap_or(&state);
+
+    // Optional "pixel-format" (rgba8, bgra8, rgb8, nv12) and "stride" (bytes per row) headers describe the pixels
+    let pixel_format = match request.headers().get("pixel-format") {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(PixelFormat::from_name)
+            .ok_or_else(|| PipeError::InvalidArgument("Invalid pixel-format header".to_string()))?,
+        None => PixelFormat::Rgba8,
+    };
+    let stride = match request.headers().get("stride") {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .ok_or_else(|| PipeError::InvalidArgument("Invalid stride header".to_string()))?,
+        None => 0,
+    };
+    let stride = pixel_format::check_layout(pixel_format, width, height, stride).map_err(PipeError::InvalidArgument)?;
+
+    // A corrupted or truncated frame would garble the backend's texture, so the pixels have to match the header
+    let pixel_bytes = payload.len() - CLIENT_FRAME_HEADER_SIZE;
+    if let Err(e) = check_frame_size(pixel_format, width, height, stride, pixel_bytes, state.max_frame_bytes) {
+        state.metrics.record_frame_rejected();
+        return Err(e);
+    }
+
+    // Paused streams swallow frames so the webview can keep rendering as usual
+    if state.paused.load(Ordering::Acquire) {
+        return Ok(None);
+    }
+
+    // Other sinks (Spout, NDI) take frames even while no backend is connected
+    if !state.connections.is_connected(PipeKind::Frame) && state.sinks.is_empty() {
+        // eprintln!("[Rust Frame Pipe] Send failed: Not connected.");
+        return Err(PipeError::NotConnected(PipeKind::Frame));
+    }
+
+    // An "overlay-id" request header picks the overlay, e.g. for windows that drive several
+    let overlay_id = match request.headers().get("overlay-id") {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .ok_or_else(|| PipeError::InvalidArgument("Invalid overlay-id header".to_string()))?,
+        None => state.overlays.default_for_window(window.label()),
+    };
+    if overlay_id != DEFAULT_OVERLAY_ID && state.overlays.get(overlay_id).is_none() {
+        return Err(PipeError::InvalidArgument(format!("Unknown overlay {}", overlay_id)));
+    }
+    // Optional "sent-at-us" header (microseconds since the Unix epoch, e.g. Date.now() * 1000) measures the IPC hop
+    if let Some(sent_at_us) = request.headers().get("sent-at-us").and_then(|value| value.to_str().ok()?.parse::<u64>().ok()) {
+        state.metrics.latency.ipc.record(received_us.saturating_sub(sent_at_us));
+    }
+
+    // The full frame header (sequence number + capture timestamp + overlay) is kept beside the pixels
+    let header = FrameHeader {
+        width,
+        height,
+        sequence: state.next_sequence.fetch_add(1, Ordering::Relaxed),
+        timestamp_us: received_us,
+        overlay_id,
+        stride,
+        pixel_format,
+    };
+    // The IPC body is only borrowed, so the pixels are copied out once here (into a pooled buffer);
+    // from the queue to the pipe (or shared memory / encoder) they're never copied again
+    let mut pixels = state.buffers.take(payload.len() - CLIENT_FRAME_HEADER_SIZE);
+    pixels.extend_from_slice(&payload[CLIENT_FRAME_HEADER_SIZE..]);
+    let frame = QueuedFrame { header, pixels: pixels.into_bytes() };
+    *state.last_frame.lock() = Some(frame.clone());
+    // Over the target FPS the frame waits for its turn, unless a newer one takes its place first
+    if let Some(frame) = state.frame_rate.admit(frame) {
+        state.deliver(frame).await;
+    }
+    Ok(Some(header.sequence))
+}
+
+// Send at most `fps` frames per second and overlay, coalescing the rest (latest wins); 0 lifts the limit
+#[tauri::command]
+fn set_target_fps(fps: u32, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    state.configure_stream(StreamOptions { target_fps: Some(fps), ..StreamOptions::default() })
+}
+
+// Start capturing frames to `path` (relative to the recordings directory in the app data directory), or to a new
+// file there; returns the file's path
+#[tauri::command]
+fn start_recording(path: Option<String>, state: State<'_, FramePipeState>, app_handle: AppHandle) -> Result<PathBuf, PipeError> {
+    let path = app_data_file(&app_handle, RECORDINGS_DIR, path.as_deref(), || {
+        format!("frames-{}.{}", protocol::timestamp_us() / 1_000_000, recording::RECORDING_EXTENSION)
+    })?;
+    state.recorder.start(&state.rt, path.clone()).map_err(|e| PipeError::io("Failed to start recording", &e))?;
+    info!("[Rust Recorder] Recording frames to {}.", path.display());
+    Ok(path)
+}
+
+// Finish the running recording; the file can be replayed with --replay <path>
+#[tauri::command]
+async fn stop_recording(state: State<'_, FramePipeState>) -> Result<RecordingSummary, PipeError> {
+    let summary = state
+        .recorder
+        .stop()
+        .await
+        .ok_or(PipeError::Unavailable("Frame recording"))?
+        .map_err(|e| PipeError::io("Failed to write recording", &e))?;
+    info!(
+        "[Rust Recorder] Saved {} frames ({} bytes, {} dropped) to {}.",
+        summary.frames,
+        summary.bytes,
+        summary.dropped,
+        summary.path.display()
+    );
+    Ok(summary)
+}
+
+// Start capturing both directions of every pipe to `path` (relative to the recordings directory in the app data
+// directory), or to a new file there; returns the file's path
+#[tauri::command]
+fn start_session_recording(path: Option<String>, state: State<'_, FramePipeState>, app_handle: AppHandle) -> Result<PathBuf, PipeError> {
+    let path = app_data_file(&app_handle, RECORDINGS_DIR, path.as_deref(), || {
+        format!("session-{}.{}", protocol::timestamp_us() / 1_000_000, session::SESSION_EXTENSION)
+    })?;
+    state.session.start(&state.rt, path.clone()).map_err(|e| PipeError::io("Failed to start session recording", &e))?;
+    info!("[Rust Session] Recording the session to {}.", path.display());
+    Ok(path)
+}
+
+// Finish the running session recording; the file can be replayed with replay_session
+#[tauri::command]
+async fn stop_session_recording(state: State<'_, FramePipeState>) -> Result<SessionSummary, PipeError> {
+    let summary = state
+        .session
+        .stop()
+        .await
+        .ok_or(PipeError::Unavailable("Session recording"))?
+        .map_err(|e| PipeError::io("Failed to write session recording", &e))?;
+    info!(
+        "[Rust Session] Saved {} messages ({} bytes, {} dropped) to {}.",
+        summary.messages,
+        summary.bytes,
+        summary.dropped,
+        summary.path.display()
+    );
+    Ok(summary)
+}
+
+// Re-emit the events of the session recording at `path` (relative to the recordings directory in the app data
+// directory) at `speed` times the recorded pace (default 1, 0 for as fast as possible).
+// Resolves once the whole recording has been replayed.
+#[tauri::command]
+async fn replay_session(
+    path: String,
+    speed: Option<f64>,
+    state: State<'_, FramePipeState>,
+    app_handle: AppHandle,
+) -> Result<SessionReplaySummary, PipeError> {
+    let path = app_data_file(&app_handle, RECORDINGS_DIR, Some(&path), String::new)?;
+    let speed = speed.unwrap_or(1.0);
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(PipeError::InvalidArgument("Replay speed must be 0 or more".to_string()));
+    }
+    let summary = state
+        .replay_session(&path, speed)
+        .await
+        .map_err(|e| PipeError::io("Failed to replay session", &e))?;
+    info!("[Rust Session] Replayed {} messages from {}.", summary.replayed, path.display());
+    Ok(summary)
+}
+
+// Save the most recent frame as a PNG at `path`, relative to the screenshots directory in the app data directory
+#[tauri::command]
+async fn capture_screenshot(path: String, state: State<'_, FramePipeState>, app_handle: AppHandle) -> Result<Screenshot, PipeError> {
+    let path = app_data_file(&app_handle, SCREENSHOTS_DIR, Some(&path), String::new)?;
+    let frame = state.last_frame.lock().clone().ok_or(PipeError::NoFrame)?;
+    // Encoding a full frame takes a while; keep it off the runtime's worker threads
+    let buffers = Arc::clone(&state.buffers);
+    let screenshot = tokio::task::spawn_blocking(move || screenshot::write_png(&frame, &path, &buffers))
+        .await
+        .map_err(|e| PipeError::io("Screenshot task failed", &io::Error::other(e)))?
+        .map_err(|e| PipeError::io("Failed to save screenshot", &e))?;
+    info!(
+        "[Rust Frame Pipe] Saved frame {} ({}x{}) to {}.",
+        screenshot.sequence,
+        screenshot.width,
+        screenshot.height,
+        screenshot.path.display()
+    );
+    Ok(screenshot)
+}
+
+// Zip recent logs, the config, metrics, connection state, backend info and the last frame into one
+// file for a bug report, at `path` (relative to the diagnostics directory in the app data directory) or in a new file
+// there; see diagnostics.rs
+#[tauri::command]
+async fn export_diagnostics(
+    path: Option<String>,
+    state: State<'_, FramePipeState>,
+    manager: State<'_, PipeManager>,
+    app_handle: AppHandle,
+) -> Result<DiagnosticsBundle, PipeError> {
+    let path = app_data_file(&app_handle, DIAGNOSTICS_DIR, path.as_deref(), || {
+        format!("puppyweb-diagnostics-{}.zip", protocol::timestamp_us() / 1_000_000)
+    })?;
+    let json = |value: serde_json::Value| serde_json::to_vec_pretty(&value).unwrap_or_default();
+    let named = manager.states();
+    let mut entries = vec![(
+        "about.json".to_string(),
+        json(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "createdAtUs": protocol::timestamp_us(),
+        })),
+    )];
+    match config_path(&app_handle).map_err(|e| e.to_string()).and_then(|path| AppConfig::load(&path).map_err(|e| e.to_string())) {
+        Ok(mut config) => {
+            if config.auth_token.is_some() {
+                config.auth_token = Some("<redacted>".to_string());
+            }
+            entries.push(("config.toml".to_string(), toml::to_string_pretty(&config).unwrap_or_default().into_bytes()));
+        }
+        Err(e) => entries.push(("config-error.txt".to_string(), e.into_bytes())),
+    }
+    let mut metrics = serde_json::Map::new();
+    for (name, pipe) in std::iter::once(("main".to_string(), state.inner().clone())).chain(named) {
+        let value = serde_json::json!({ "pipe": pipe.metrics_snapshot(), "latency": pipe.metrics.latency.snapshot() });
+        metrics.insert(name, value);
+    }
+    entries.push(("metrics.json".to_string(), json(metrics.into())));
+    let status = ConnectionStatus { tasks: state.supervisor.health(), ..state.connections.snapshot() };
+    entries.push(("connections.json".to_string(), json(serde_json::json!({ "main": status, "named": manager.list() }))));
+    if let Some(info) = state.backend_info.lock().clone() {
+        entries.push(("backend.json".to_string(), json(serde_json::json!(info))));
+    }
+    let frame = state.last_frame.lock().clone();
+    let buffers = Arc::clone(&state.buffers);
+    let log_dir = app_handle.path().app_log_dir().ok();
+    // Reading logs, encoding the frame and deflating take a while; keep them off the runtime's worker threads
+    let bundle = tokio::task::spawn_blocking(move || {
+        if let Some(frame) = frame {
+            let mut png = Vec::new();
+            match screenshot::encode_png(&frame, &mut png, &buffers) {
+                Ok(()) => entries.push(("last-frame.png".to_string(), png)),
+                Err(e) => warn!("[Rust Diagnostics] Leaving out the last frame: {}", e),
+            }
+        }
+        match log_dir.as_deref().map(diagnostics::recent_logs) {
+            Some(Ok(logs)) => entries.extend(logs),
+            Some(Err(e)) => warn!("[Rust Diagnostics] Leaving out the logs: {}", e),
+            None => warn!("[Rust Diagnostics] No log directory, leaving out the logs."),
+        }
+        diagnostics::write_bundle(&path, &entries)
+    })
+    .await
+    .map_err(|e| PipeError::io("Diagnostics task failed", &io::Error::other(e)))?
+    .map_err(|e| PipeError::io("Failed to write the diagnostics bundle", &e))?;
+    info!("[Rust Diagnostics] Saved {} files ({} bytes) to {}.", bundle.files.len(), bundle.bytes, bundle.path.display());
+    Ok(bundle)
+}
+
+// Open (or focus) the preview window mirroring outgoing frames, or close it
+#[tauri::command]
+async fn show_frame_preview(visible: bool, app_handle: AppHandle) -> Result<(), PipeError> {
+    let shown = match (visible, app_handle.get_webview_window(PREVIEW_WINDOW_LABEL)) {
+        (true, Some(window)) => window.set_focus(),
+        (true, None) => WebviewWindowBuilder::new(&app_handle, PREVIEW_WINDOW_LABEL, WebviewUrl::App(PREVIEW_PAGE.into()))
+            .title("Frame preview")
+            .inner_size(960.0, 540.0)
+            .build()
+            .map(|_| ()),
+        (false, Some(window)) => window.close(),
+        (false, None) => Ok(()),
+    };
+    shown.map_err(|e| PipeError::Unsupported(format!("Can't show the frame preview: {}", e)))
+}
+
+// Called by the preview window with the channel frames are mirrored to
+#[tauri::command]
+fn subscribe_frame_preview(channel: Channel<InvokeResponseBody>, state: State<'_, FramePipeState>) {
+    state.preview.set_channel(Some(channel));
+    info!("[Rust Frame Preview] Preview window subscribed.");
+}
+
+// Raise or lower logging at runtime, for the whole app or one module (e.g. "heartbeat")
+#[tauri::command]
+fn set_log_level(level: LogLevel, module: Option<String>, logging: State<'_, Logging>) -> Result<(), PipeError> {
+    logging.set_level(level, module.as_deref())?;
+    info!("[Rust Logging] Log level of {} set to {}.", module.as_deref().unwrap_or("the app"), level.as_str());
+    Ok(())
+}
+
+// Start the backend executable from puppyweb.toml (or the one given) under the watchdog
+#[tauri::command]
+fn launch_backend(
+    executable: Option<String>,
+    args: Option<Vec<String>>,
+    state: State<'_, FramePipeState>,
+    supervisor: State<'_, BackendSupervisor>,
+) -> Result<Option<u32>, PipeError> {
+    let mut config = load_config(&state.app_handle).backend;
+    if let Some(executable) = executable {
+        config.executable = Some(executable);
+        config.args = Vec::new();
+    }
+    if let Some(args) = args {
+        config.args = args;
+    }
+    supervisor.launch(config).map_err(|e| PipeError::io("Failed to launch backend", &e))
+}
+
+// Kill the backend started by launch_backend; false if none was running
+#[tauri::command(async)]
+async fn stop_backend(supervisor: State<'_, BackendSupervisor>) -> Result<bool, PipeError> {
+    Ok(supervisor.stop().await)
+}
+
+// Open an extra frame pipe at `path`; frames from `window` (if given) go there instead of the main frame pipe
+#[tauri::command]
+fn create_pipe_connection(
+    name: String,
+    path: String,
+    window: Option<String>,
+    state: State<'_, FramePipeState>,
+    manager: State<'_, PipeManager>,
+) -> Result<(), PipeError> {
+    if name.is_empty() || path.is_empty() {
+        return Err(PipeError::InvalidArgument("Connection name and path must not be empty".to_string()));
+    }
+    let config = load_config(&state.app_handle);
+    let named = FramePipeState::new_named(state.rt.clone(), state.app_handle.clone(), &config, state.auth_token.clone(), &name, &path);
+    if let Err(e) = manager.insert(&name, window.clone(), named.clone()) {
+        state.rt.spawn(async move { named.shutdown().await });
+        return Err(e);
+    }
+    info!("[Rust Connection] Created connection {:?} to {} (window {}).", name, path, window.as_deref().unwrap_or("none"));
+    Ok(())
+}
+
+// Flush and close a named connection; its window goes back to the main frame pipe
+#[tauri::command(async)]
+async fn destroy_pipe_connection(name: String, manager: State<'_, PipeManager>) -> Result<(), PipeError> {
+    let Some(named) = manager.remove(&name) else {
+        return Err(PipeError::InvalidArgument(format!("Unknown connection {:?}", name)));
+    };
+    named.shutdown().await;
+    info!("[Rust Connection] Destroyed connection {:?}.", name);
+    Ok(())
+}
+
+#[tauri::command]
+fn list_pipe_connections(manager: State<'_, PipeManager>) -> Vec<NamedPipeStatus> {
+    manager.list()
+}
+
+// Create an overlay quad owned by the calling window; its transforms are emitted to that window only
+#[tauri::command(async)]
+async fn register_overlay(name: String, window: tauri::Window, state: State<'_, FramePipeState>) -> Result<Overlay, PipeError> {
+    state.add_overlay(&name, window.label()).await
+}
+
+// register_overlay with its placement set up front (see overlays.rs)
+#[tauri::command(async)]
+async fn create_overlay(
+    name: String,
+    settings: Option<OverlaySettings>,
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+) -> Result<OverlayInfo, PipeError> {
+    let changes = settings.unwrap_or_default().changes().map_err(PipeError::InvalidArgument)?;
+    let overlay = state.add_overlay(&name, window.label()).await?;
+    for change in changes {
+        state.set_overlay_placement(overlay.id, change).await?;
+    }
+    Ok(OverlayInfo { placement: state.placement.get(overlay.id), overlay })
+}
+
+// Every registered overlay with its placement, in id order; the default overlay 0 isn't listed
+#[tauri::command]
+fn list_overlays(state: State<'_, FramePipeState>) -> Vec<OverlayInfo> {
+    state.overlays.all().into_iter().map(|overlay| OverlayInfo { placement: state.placement.get(overlay.id), overlay }).collect()
+}
+
+// Place an overlay (the calling window's first, or the default one) with a row-major 4x4 matrix in the
+// backend's tracking space (see placement.rs)
+#[tauri::command(async)]
+async fn set_overlay_transform(
+    matrix: [f32; 16],
+    overlay_id: Option<u32>,
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+) -> Result<(), PipeError> {
+    let overlay_id = overlay_id.unwrap_or_else(|| state.overlays.default_for_window(window.label()));
+    state.set_overlay_placement(overlay_id, PlacementChange::Transform(matrix)).await
+}
+
+// Resize an overlay; the height follows from the frames' aspect ratio
+#[tauri::command(async)]
+async fn set_overlay_width(
+    width_meters: f32,
+    overlay_id: Option<u32>,
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+) -> Result<(), PipeError> {
+    let overlay_id = overlay_id.unwrap_or_else(|| state.overlays.default_for_window(window.label()));
+    state.set_overlay_placement(overlay_id, PlacementChange::Width(width_meters)).await
+}
+
+// Bend an overlay around the viewer, from 0 (flat) to 1 (a full circle)
+#[tauri::command(async)]
+async fn set_overlay_curvature(
+    curvature: f32,
+    overlay_id: Option<u32>,
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+) -> Result<(), PipeError> {
+    let overlay_id = overlay_id.unwrap_or_else(|| state.overlays.default_for_window(window.label()));
+    state.set_overlay_placement(overlay_id, PlacementChange::Curvature(curvature)).await
+}
+
+// Fade an overlay, from 0 (invisible) to 1 (opaque)
+#[tauri::command(async)]
+async fn set_overlay_alpha(
+    alpha: f32,
+    overlay_id: Option<u32>,
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+) -> Result<(), PipeError> {
+    let overlay_id = overlay_id.unwrap_or_else(|| state.overlays.default_for_window(window.label()));
+    state.set_overlay_placement(overlay_id, PlacementChange::Alpha(alpha)).await
+}
+
+// Placement set so far for an overlay (the calling window's first, or the default one)
+#[tauri::command]
+fn get_overlay_placement(overlay_id: Option<u32>, window: tauri::Window, state: State<'_, FramePipeState>) -> OverlayPlacement {
+    state.placement.get(overlay_id.unwrap_or_else(|| state.overlays.default_for_window(window.label())))
+}
+
+#[tauri::command(async)]
+async fn unregister_overlay(id: u32, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    state.remove_overlay(id).await
+}
+
+// Show the surface as a tab of the SteamVR dashboard instead of in the room, through the backend and the
+// in-process OpenVR layer (see dashboard.rs); the icon is kept unless a new one is given. Returns the mode now in effect.
+#[tauri::command(async)]
+async fn set_dashboard_mode(
+    enabled: bool,
+    icon_path: Option<String>,
+    state: State<'_, FramePipeState>,
+) -> Result<DashboardConfig, PipeError> {
+    let config = DashboardConfig { enabled, icon_path: icon_path.or(state.dashboard.config().icon_path) };
+    config.validate().map_err(PipeError::InvalidArgument)?;
+    if !enabled {
+        // Out of the dashboard, the tab can't be open anymore
+        state.dashboard_visibility(false);
+    }
+    state.dashboard.set_config(config.clone());
+    state.send_control(&ControlMessage::SetDashboardMode { enabled, icon_path: config.icon_path.clone() }).await;
+    // The OpenVR overlay has to be created again as the other kind
+    if state.sinks.contains(OPENVR_SINK_ID) {
+        let openvr = OpenVrConfig { enabled: true, ..state.openvr_config.lock().clone() };
+        state.apply_openvr_config(openvr).map_err(|e| PipeError::sink_start("Restarting OpenVR", &e))?;
+    }
+    Ok(config)
+}
+
+// Remove an overlay made with create_overlay
+#[tauri::command(async)]
+async fn destroy_overlay(id: u32, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    state.remove_overlay(id).await
+}
+
+// Make an overlay's transform relative to a tracked device (a name as in transform-update events), or
+// to the tracking space again with none
+#[tauri::command(async)]
+async fn set_overlay_attachment(
+    device: Option<String>,
+    overlay_id: Option<u32>,
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+) -> Result<(), PipeError> {
+    let device_id = match device {
+        Some(name) => Some(pose::device_id_from_name(&name).ok_or_else(|| PipeError::InvalidArgument(format!("Unknown device {:?}", name)))?),
+        None => None,
+    };
+    let overlay_id = overlay_id.unwrap_or_else(|| state.overlays.default_for_window(window.label()));
+    state.set_overlay_placement(overlay_id, PlacementChange::Attachment(device_id)).await
+}
+
+// Blank the overlay without dropping the connection: frames are accepted but not sent until resume_stream.
+// Windows bound to a named connection pause that connection. Returns false if it was already paused.
+#[tauri::command(async)]
+async fn pause_stream(
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+    manager: State<'_, PipeManager>,
+) -> Result<bool, PipeError> {
+    let named_state = manager.for_window(window.label());
+    Ok(named_state.as_ref().unwrap_or(&state).set_paused(true).await)
+}
+
+// Undo pause_stream; returns false if the stream wasn't paused
+#[tauri::command(async)]
+async fn resume_stream(
+    window: tauri::Window,
+    state: State<'_, FramePipeState>,
+    manager: State<'_, PipeManager>,
+) -> Result<bool, PipeError> {
+    let named_state = manager.for_window(window.label());
+    Ok(named_state.as_ref().unwrap_or(&state).set_paused(false).await)
+}
+
+// Hand a DXGI shared texture to the backend instead of CPU pixels (Windows only)
+#[tauri::command(async)]
+async fn share_gpu_texture(texture: SharedTexture, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    if !cfg!(windows) {
+        return Err(PipeError::Unsupported("Shared GPU textures are only supported on Windows".to_string()));
+    }
+    if state.backend_capabilities.load(Ordering::Acquire) & handshake::CAP_GPU_TEXTURE == 0 {
+        return Err(PipeError::Unsupported("Backend does not support shared GPU textures".to_string()));
+    }
+    if state.paused.load(Ordering::Acquire) {
+        return Ok(());
+    }
+    let sequence = state.next_sequence.fetch_add(1, Ordering::Relaxed);
+    let payload = texture.encode(sequence, protocol::timestamp_us());
+    state.send_message(&protocol::encode(MessageType::SharedTexture, 0, &payload)).await
+}
+
+// Adjust the frame pipeline (backpressure policy, queue depth, compression, encoding) at runtime
+#[tauri::command]
+fn configure_stream(options: StreamOptions, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    state.configure_stream(options)
+}
+
+// Switch between sending pixels through the pipe and through the shared-memory ring
+#[tauri::command]
+fn set_frame_channel(channel: FrameChannel, state: State<'_, FramePipeState>, app_handle: AppHandle) -> Result<String, PipeError> {
+    let mut ring_guard = state.shm_ring.lock();
+    match channel {
+        FrameChannel::Pipe => {
+            *ring_guard = None;
+            info!("[Rust Frame Pipe] Frame channel set to pipe.");
+            Ok(String::new())
+        }
+        FrameChannel::SharedMemory => {
+            if ring_guard.is_none() {
+                let dir = app_handle
+                    .path()
+                    .app_data_dir()
+                    .map_err(|e| PipeError::Unsupported(format!("No app data directory: {}", e)))?;
+                let ring = SharedFrameRing::create_default(&dir)
+                    .map_err(|e| PipeError::io("Failed to create shared-memory frame ring", &e))?;
+                *ring_guard = Some(ring);
+            }
+            // Return the backing file path so it can be handed to the backend
+            let path = ring_guard.as_ref().map(|ring| ring.path().display().to_string()).unwrap_or_default();
+            info!("[Rust Frame Pipe] Frame channel set to shared memory: {}", path);
+            Ok(path)
+        }
+    }
+}
+
+// (Re)start connecting any pipe that isn't connected, e.g. after the backend was restarted
+#[tauri::command]
+fn connect_pipes(state: State<'_, FramePipeState>) {
+    info!("[Rust Connection] Connect requested.");
+    state.connect();
+}
+
+// Tear down all pipes and stop all reconnect attempts until connect_pipes is called
+#[tauri::command(async)]
+async fn disconnect_pipes(state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    info!("[Rust Connection] Disconnect requested.");
+    state.disconnect().await;
+    Ok(())
+}
+
+// Drop the frame pipe connection and establish a fresh one
+#[tauri::command(async)]
+async fn reconnect_frame_pipe(state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    info!("[Rust Frame Pipe] Reconnect requested.");
+    state.reconnect_frame().await;
+    Ok(())
+}
+
+// Point the app at different pipe names (e.g. another petplay instance): every pipe is
+// disconnected and reconnected to the new paths, and the paths are saved to puppyweb.toml
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)] // One optional path per pipe
+async fn set_pipe_paths(
+    frame: String,
+    transform: String,
+    input: Option<String>,
+    audio: Option<String>,
+    microphone: Option<String>,
+    video: Option<String>,
+    files: Option<String>,
+    pointer: Option<String>,
+    state: State<'_, FramePipeState>,
+) -> Result<(), PipeError> {
+    if frame.is_empty() || transform.is_empty() || [&input, &audio, &microphone, &video, &files, &pointer].iter().any(|path| path.as_deref() == Some("")) {
+        return Err(PipeError::InvalidArgument("Pipe paths must not be empty".to_string()));
+    }
+    let paths = {
+        let current = state.pipe_paths.lock();
+        PipePaths {
+            frame,
+            transform,
+            input: input.unwrap_or_else(|| current.input.clone()),
+            audio: audio.unwrap_or_else(|| current.audio.clone()),
+            microphone: microphone.unwrap_or_else(|| current.microphone.clone()),
+            video: video.unwrap_or_else(|| current.video.clone()),
+            files: files.unwrap_or_else(|| current.files.clone()),
+            pointer: pointer.unwrap_or_else(|| current.pointer.clone()),
+        }
+    };
+    state.set_pipe_paths(paths.clone()).await;
+
+    // Persist the choice; the connection change above already happened either way
+    let path = config_path(&state.app_handle).map_err(|e| PipeError::io("No app config directory", &io::Error::other(e.to_string())))?;
+    AppConfig::update(&path, |config| config.pipes = paths).map_err(|e| PipeError::io("Failed to save pipe paths", &e))
+}
+
+// Change the pipe buffer sizes and/or mode (see transport.rs). Like set_pipe_paths this
+// reconnects every pipe and saves the options to puppyweb.toml; named connections
+// pick them up when they're created.
+#[tauri::command(async)]
+async fn configure_pipe(
+    in_buffer_size: Option<u32>,
+    out_buffer_size: Option<u32>,
+    mode: Option<PipeMode>,
+    state: State<'_, FramePipeState>,
+    app_handle: AppHandle,
+) -> Result<PipeOptions, PipeError> {
+    let mut options = *state.pipe_options.lock();
+    options.in_buffer_size = in_buffer_size.or(options.in_buffer_size);
+    options.out_buffer_size = out_buffer_size.or(options.out_buffer_size);
+    options.mode = mode.unwrap_or(options.mode);
+    options.validate().map_err(PipeError::InvalidArgument)?;
+    state.disconnect().await;
+    *state.pipe_options.lock() = options;
+    info!("[Rust Connection] Pipe options set to {:?}, reconnecting.", options);
+    state.connect();
+
+    let path = config_path(&app_handle).map_err(|e| PipeError::io("No app config directory", &io::Error::other(e.to_string())))?;
+    AppConfig::update(&path, |config| config.pipe_options = options).map_err(|e| PipeError::io("Failed to save pipe options", &e))?;
+    Ok(options)
+}
+
+// Protocol version, capabilities and name the backend agreed to in the last frame pipe handshake
+#[tauri::command]
+fn get_backend_info(state: State<'_, FramePipeState>) -> Option<BackendInfo> {
+    state.backend_info.lock().clone()
+}
+
+// Ping interval and timeout after which a silent pipe is reconnected; interval 0 disables heartbeats
+#[tauri::command]
+fn set_heartbeat(config: HeartbeatConfig, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    if config.interval_ms > 0 && config.timeout_ms <= config.interval_ms {
+        return Err(PipeError::InvalidArgument("Heartbeat timeout must be longer than the interval".to_string()));
+    }
+    info!("[Rust Connection] Heartbeat set to {:?}.", config);
+    *state.heartbeat.lock() = config;
+    Ok(())
+}
+
+// Max transform-update events per second per device; 0 emits every pose as it arrives
+#[tauri::command]
+fn set_transform_rate(rate_hz: u32, state: State<'_, FramePipeState>) {
+    info!("[Rust Transform Pipe] Transform rate set to {} Hz (was {} Hz).", rate_hz, state.transforms.rate_hz());
+    state.transforms.set_rate_hz(rate_hz);
+}
+
+// Newest pose of a device for render loops that poll instead of listening; None until
+// the backend has sent one. The pose has been through filtering and prediction.
+#[tauri::command]
+fn get_last_transform(device: String, state: State<'_, FramePipeState>) -> Result<Option<LastTransformPayload>, PipeError> {
+    let device_id = parse_device(&device)?;
+    Ok(state.transforms.latest(device_id).map(|(pose, matrix, age)| LastTransformPayload {
+        device,
+        pose,
+        matrix,
+        age_us: age.as_micros() as u64,
+    }))
+}
+
+// Smoothing of incoming poses (see filter.rs); parameters left out keep their current value.
+// Returns the filter settings now in effect.
+#[tauri::command]
+fn set_transform_filter(
+    mode: Option<FilterMode>,
+    min_cutoff: Option<f32>,
+    beta: Option<f32>,
+    d_cutoff: Option<f32>,
+    state: State<'_, FramePipeState>,
+) -> Result<FilterConfig, PipeError> {
+    let current = state.transform_filter.config();
+    let config = FilterConfig {
+        mode: mode.unwrap_or(current.mode),
+        min_cutoff: min_cutoff.unwrap_or(current.min_cutoff),
+        beta: beta.unwrap_or(current.beta),
+        d_cutoff: d_cutoff.unwrap_or(current.d_cutoff),
+    };
+    config.validate().map_err(PipeError::InvalidArgument)?;
+    info!("[Rust Transform Pipe] Transform filter set to {:?}.", config);
+    state.transform_filter.set_config(config);
+    Ok(config)
+}
+
+// Degrade compression/resolution automatically when the pipe falls behind (see adaptive.rs);
+// parameters left out keep their current value. Returns the settings now in effect.
+#[tauri::command]
+fn set_adaptive_quality(
+    mode: Option<AdaptiveMode>,
+    target_fps: Option<u32>,
+    min_scale: Option<f32>,
+    state: State<'_, FramePipeState>,
+) -> Result<AdaptiveConfig, PipeError> {
+    let current = state.adaptive.config();
+    let config = AdaptiveConfig {
+        mode: mode.unwrap_or(current.mode),
+        target_fps: target_fps.unwrap_or(current.target_fps),
+        min_scale: min_scale.unwrap_or(current.min_scale),
+    };
+    config.validate().map_err(PipeError::InvalidArgument)?;
+    info!("[Rust Adaptive] Adaptive quality set to {:?}.", config);
+    for adjustment in state.adaptive.set_config(config, *state.compression.lock()) {
+        state.apply_adjustment(adjustment);
+    }
+    Ok(config)
+}
+
+// Average the periphery of frames around the gaze point (see foveation.rs); parameters left out
+// keep their current value. Returns the settings now in effect.
+#[tauri::command]
+fn set_foveation(
+    enabled: Option<bool>,
+    radius: Option<f32>,
+    falloff: Option<f32>,
+    max_block: Option<u32>,
+    state: State<'_, FramePipeState>,
+) -> Result<FoveationConfig, PipeError> {
+    let current = state.foveation.config();
+    let config = FoveationConfig {
+        enabled: enabled.unwrap_or(current.enabled),
+        radius: radius.unwrap_or(current.radius),
+        falloff: falloff.unwrap_or(current.falloff),
+        max_block: max_block.unwrap_or(current.max_block),
+    };
+    config.validate().map_err(PipeError::InvalidArgument)?;
+    info!("[Rust Frame Pipe] Foveation set to {:?}.", config);
+    state.foveation.set_config(config);
+    Ok(config)
+}
+
+// WebRTC publishing settings for the frontend's peer connection (see webrtc.rs)
+#[tauri::command]
+fn get_webrtc_config(webrtc: State<'_, WebRtcConfig>) -> WebRtcConfig {
+    webrtc.inner().clone()
+}
+
+// Poses a WebRTC peer sent on the "poses" data channel, as Poses records in a raw body;
+// handled like poses from the transform pipe. Returns how many records there were.
+#[tauri::command]
+fn submit_remote_poses(
+    request: tauri::ipc::Request<'_>,
+    state: State<'_, FramePipeState>,
+    webrtc: State<'_, WebRtcConfig>,
+) -> Result<usize, PipeError> {
+    if !webrtc.receive_poses {
+        return Err(PipeError::Unsupported("Receiving poses over WebRTC is disabled".to_string()));
+    }
+    let tauri::ipc::InvokeBody::Raw(payload) = request.body() else {
+        return Err(PipeError::RequestBodyMustBeRaw);
+    };
+    let poses = pose::decode_records(payload).map_err(PipeError::InvalidArgument)?;
+    let count = poses.len();
+    for pose in poses {
+        state.receive_remote_pose(pose);
+    }
+    Ok(count)
+}
+
+// Mirror poses and controller input to OSC and back (see osc.rs); returns the settings now in effect
+#[tauri::command]
+fn enable_osc_bridge(enabled: bool, osc: State<'_, OscBridge>, app_handle: AppHandle) -> Result<OscConfig, PipeError> {
+    let config = OscConfig { enabled, ..osc.config() };
+    apply_osc_config(&osc, config, app_handle)
+}
+
+// Change the OSC addresses and what gets sent; parameters left out keep their current value
+#[tauri::command]
+fn configure_osc(
+    send_to: Option<String>,
+    listen: Option<String>,
+    send_tracking: Option<bool>,
+    send_input: Option<bool>,
+    osc: State<'_, OscBridge>,
+    app_handle: AppHandle,
+) -> Result<OscConfig, PipeError> {
+    let current = osc.config();
+    let config = OscConfig {
+        enabled: current.enabled,
+        send_to: send_to.unwrap_or(current.send_to),
+        listen: listen.unwrap_or(current.listen),
+        send_tracking: send_tracking.unwrap_or(current.send_tracking),
+        send_input: send_input.unwrap_or(current.send_input),
+    };
+    apply_osc_config(&osc, config, app_handle)
+}
+
+fn apply_osc_config(osc: &OscBridge, config: OscConfig, app_handle: AppHandle) -> Result<OscConfig, PipeError> {
+    config.validate().map_err(PipeError::InvalidArgument)?;
+    osc.apply(config.clone(), osc_handler(app_handle)).map_err(|e| PipeError::io("Opening the OSC socket", &e))?;
+    info!("[Rust OSC] OSC bridge set to {:?}.", config);
+    Ok(config)
+}
+
+// Feeds what the OSC bridge receives into the same paths as the backend's poses and input
+fn osc_handler(app_handle: AppHandle) -> impl Fn(Incoming) + Send + Sync + 'static {
+    move |incoming| match incoming {
+        Incoming::Pose(pose) => {
+            if let Some(state) = app_handle.try_state::<FramePipeState>() {
+                state.receive_remote_pose(pose);
+            }
+        }
+        Incoming::Input(event) => emit_controller_input(&app_handle, &event),
+        Incoming::Other(message) => {
+            if let Err(e) = app_handle.emit("osc-message", message) {
+                error!("[Rust OSC] Error emitting osc-message event: {}", e);
+            }
+        }
+    }
+}
+
+// Start or stop the NDI source, optionally renaming it / changing its frame rate; returns the settings now in effect
+#[tauri::command]
+fn enable_ndi_output(
+    enabled: bool,
+    name: Option<String>,
+    frame_rate: Option<u32>,
+    state: State<'_, FramePipeState>,
+) -> Result<NdiConfig, PipeError> {
+    let current = state.ndi_config.lock().clone();
+    let config = NdiConfig {
+        enabled,
+        name: name.unwrap_or(current.name),
+        frame_rate: frame_rate.unwrap_or(current.frame_rate),
+        ..current
+    };
+    state.apply_ndi_config(config).map_err(|e| PipeError::sink_start("Starting the NDI output", &e))?;
+    Ok(NdiConfig { enabled: state.sinks.contains(NDI_SINK_ID), ..state.ndi_config.lock().clone() })
+}
+
+// Show the overlay in SteamVR directly instead of (or beside) through a backend, and read poses from it
+// (see openvr.rs); needs the "openvr" cargo feature. Returns the settings now in effect.
+#[tauri::command]
+fn enable_openvr(
+    enabled: bool,
+    width_meters: Option<f32>,
+    state: State<'_, FramePipeState>,
+) -> Result<OpenVrConfig, PipeError> {
+    let current = state.openvr_config.lock().clone();
+    let config = OpenVrConfig { enabled, width_meters: width_meters.unwrap_or(current.width_meters), ..current };
+    state.apply_openvr_config(config).map_err(|e| PipeError::sink_start("Starting OpenVR", &e))?;
+    Ok(OpenVrConfig { enabled: state.sinks.contains(OPENVR_SINK_ID), ..state.openvr_config.lock().clone() })
+}
+
+// Show the overlay as an overlay session of the running OpenXR application, and read the headset pose from it
+// (see openxr.rs); needs the "openxr" cargo feature and a runtime with XR_EXTX_overlay. Returns the settings now in effect.
+#[tauri::command]
+fn enable_openxr(
+    enabled: bool,
+    width_meters: Option<f32>,
+    state: State<'_, FramePipeState>,
+) -> Result<OpenXrConfig, PipeError> {
+    let current = state.openxr_config.lock().clone();
+    let config = OpenXrConfig { enabled, width_meters: width_meters.unwrap_or(current.width_meters), ..current };
+    state.apply_openxr_config(config).map_err(|e| PipeError::sink_start("Starting OpenXR", &e))?;
+    Ok(OpenXrConfig { enabled: state.sinks.contains(OPENXR_SINK_ID), ..state.openxr_config.lock().clone() })
+}
+
+// Attach another output beside the frame pipe (see sink.rs), replacing any sink with the same id; returns its id
+#[tauri::command]
+fn add_sink(
+    sink: SinkSpec,
+    id: Option<String>,
+    queue_depth: Option<usize>,
+    backpressure: Option<BackpressurePolicy>,
+    state: State<'_, FramePipeState>,
+) -> Result<String, PipeError> {
+    if queue_depth == Some(0) {
+        return Err(PipeError::InvalidArgument("Queue depth must be at least 1".to_string()));
+    }
+    let id = match id {
+        Some(id) if id.is_empty() || id == PIPE_SINK_ID => {
+            return Err(PipeError::InvalidArgument(format!("Invalid sink id {:?}", id)));
+        }
+        Some(id) => id,
+        None => state.sinks.generate_id(sink.kind()),
+    };
+    let defaults = SinkQueueOptions::default();
+    let options = SinkQueueOptions {
+        queue_depth: queue_depth.unwrap_or(defaults.queue_depth),
+        backpressure: backpressure.unwrap_or(defaults.backpressure),
+    };
+    // A sink already under this id goes first, so a Spout/NDI name can be reused
+    state.sinks.detach(&id);
+    let sink = sink.open(&state.buffers).map_err(|e| PipeError::sink_start("Starting the sink", &e))?;
+    state.sinks.attach(&state.rt, &id, sink, options);
+    Ok(id)
+}
+
+// Detach the output added under `id`; the frame pipe itself can't be removed (see disconnect_pipes)
+#[tauri::command]
+fn remove_sink(id: String, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    if id == PIPE_SINK_ID {
+        return Err(PipeError::InvalidArgument("The frame pipe can't be removed".to_string()));
+    }
+    if !state.sinks.detach(&id) {
+        return Err(PipeError::InvalidArgument(format!("Unknown sink {:?}", id)));
+    }
+    Ok(())
+}
+
+// The frame pipe and every attached sink, with their queue settings
+#[tauri::command]
+fn list_sinks(state: State<'_, FramePipeState>) -> Vec<SinkInfo> {
+    let pipe = SinkInfo::new(PIPE_SINK_ID, state.inner(), &state.queue);
+    std::iter::once(pipe).chain(state.sinks.list()).collect()
+}
+
+// Extrapolate poses this far ahead (see prediction.rs); 0 turns prediction off
+#[tauri::command]
+fn set_pose_prediction(horizon_ms: f32, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    let horizon_us = horizon_ms * 1000.0;
+    if !(0.0..=MAX_HORIZON_US as f32).contains(&horizon_us) {
+        return Err(PipeError::InvalidArgument(format!("Prediction horizon must be between 0 and {} ms", MAX_HORIZON_US / 1000)));
+    }
+    info!("[Rust Transform Pipe] Pose prediction set to {} ms.", horizon_ms);
+    state.pose_predictor.set_horizon_us(horizon_us as u64);
+    Ok(())
+}
+
+// Stream poses to the calling window over a channel as raw bytes (see transform_stream.rs);
+// devices limits the stream to some devices by name, format picks the message layout ("pose" or
+// the smaller "matrix"). Returns the id for unsubscribe_transforms.
+#[tauri::command]
+fn subscribe_transforms(
+    channel: Channel<InvokeResponseBody>,
+    devices: Option<Vec<String>>,
+    format: Option<TransformFormat>,
+    subscribers: State<'_, TransformSubscribers>,
+) -> Result<u32, PipeError> {
+    let devices = devices
+        .map(|names| names.iter().map(|name| parse_device(name)).collect::<Result<Vec<_>, _>>())
+        .transpose()?;
+    let id = subscribers.subscribe(channel, devices, format.unwrap_or_default());
+    info!("[Rust Transform Pipe] Transform channel {} subscribed.", id);
+    Ok(id)
+}
+
+// Returns false if there was no such subscription
+#[tauri::command]
+fn unsubscribe_transforms(id: u32, subscribers: State<'_, TransformSubscribers>) -> bool {
+    subscribers.unsubscribe(id)
+}
+
+// Latest battery, tracking state and firmware of every device the backend reported (see device_status.rs)
+#[tauri::command]
+fn get_device_status(state: State<'_, FramePipeState>) -> Vec<DeviceStatus> {
+    state.device_status.all()
+}
+
+// Play area bounds and standing zero for a minimap (see playspace.rs); None until the backend sent them
+#[tauri::command]
+fn get_playspace_bounds(playspace: State<'_, PlayspaceState>) -> Option<Playspace> {
+    playspace.get()
+}
+
+// Stream hand skeletons to the calling window over a channel as raw bytes (see hands.rs); hand limits
+// the stream to "left" or "right". Returns the id for unsubscribe_hands.
+#[tauri::command]
+fn subscribe_hands(channel: Channel<InvokeResponseBody>, hand: Option<Hand>, subscribers: State<'_, HandSubscribers>) -> u32 {
+    let id = subscribers.subscribe(channel, hand);
+    info!("[Rust Transform Pipe] Hand channel {} subscribed.", id);
+    id
+}
+
+// Returns false if there was no such subscription
+#[tauri::command]
+fn unsubscribe_hands(id: u32, subscribers: State<'_, HandSubscribers>) -> bool {
+    subscribers.unsubscribe(id)
+}
+
+// Stream the video pipe's frames to the calling window over a channel, decoded to RGBA (see video.rs);
+// only stream_id's frames if given. Returns the id for unsubscribe_video.
+#[tauri::command]
+fn subscribe_video(channel: Channel<InvokeResponseBody>, stream_id: Option<u32>, state: State<'_, FramePipeState>) -> u32 {
+    let id = state.video.subscribe(channel, stream_id);
+    info!("[Rust Video Pipe] Video channel {} subscribed.", id);
+    id
+}
+
+// Returns false if there was no such subscription
+#[tauri::command]
+fn unsubscribe_video(id: u32, state: State<'_, FramePipeState>) -> bool {
+    state.video.unsubscribe(id)
+}
+
+// Vibrate a controller; device is a name from the transform events ("controller-left", ...)
+#[tauri::command]
+fn send_haptic_pulse(device: String, duration_us: u32, amplitude: f32, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    let pulse = HapticPulse::new(parse_device(&device)?, duration_us, amplitude)?;
+    state.haptics.try_send(pulse).map_err(|e| match e {
+        mpsc::error::TrySendError::Full(_) => PipeError::QueueFull("Haptic queue"),
+        mpsc::error::TrySendError::Closed(_) => PipeError::Unavailable("Haptics task"),
+    })
+}
+
+// Queue interleaved PCM (the raw body) for the audio pipe. "sample-rate", "channels", "sample-format"
+// and "overlay-id" headers describe it (see audio.rs); a full queue drops its oldest chunk, which is
+// reported as "audio-dropped" rather than failing the call
+#[tauri::command]
+fn send_audio_chunk(request: tauri::ipc::Request<'_>, state: State<'_, FramePipeState>, window: tauri::Window) -> Result<(), PipeError> {
+    let received_us = protocol::timestamp_us();
+    let tauri::ipc::InvokeBody::Raw(payload) = request.body() else {
+        return Err(PipeError::RequestBodyMustBeRaw);
+    };
+    let header = |name: &str| -> Result<Option<&str>, PipeError> {
+        request
+            .headers()
+            .get(name)
+            .map(|value| value.to_str().map_err(|_| PipeError::InvalidArgument(format!("Invalid {} header", name))))
+            .transpose()
+    };
+    let number = |name: &str| -> Result<Option<u32>, PipeError> {
+        header(name)?
+            .map(|value| value.parse::<u32>().map_err(|_| PipeError::InvalidArgument(format!("Invalid {} header", name))))
+            .transpose()
+    };
+    let defaults = AudioFormat::default();
+    let format = AudioFormat {
+        sample_rate: number("sample-rate")?.unwrap_or(defaults.sample_rate),
+        channels: match number("channels")? {
+            Some(channels) => u8::try_from(channels).map_err(|_| PipeError::InvalidArgument("Invalid channels header".to_string()))?,
+            None => defaults.channels,
+        },
+        sample_format: match header("sample-format")? {
+            Some(name) => SampleFormat::from_name(name).ok_or_else(|| PipeError::InvalidArgument("Invalid sample-format header".to_string()))?,
+            None => defaults.sample_format,
+        },
+    };
+    let frames = format.frames(payload.len()).map_err(PipeError::InvalidArgument)?;
+    let overlay_id = number("overlay-id")?.unwrap_or_else(|| state.overlays.default_for_window(window.label()));
+    if overlay_id != DEFAULT_OVERLAY_ID && state.overlays.get(overlay_id).is_none() {
+        return Err(PipeError::InvalidArgument(format!("Unknown overlay {}", overlay_id)));
+    }
+    if !state.connections.is_connected(PipeKind::Audio) {
+        return Err(PipeError::NotConnected(PipeKind::Audio));
+    }
+    let header = AudioHeader { overlay_id, format, timestamp_us: received_us, frames };
+    let dropped = state.audio.push(AudioChunk { header, samples: Bytes::copy_from_slice(payload), queued_at: Instant::now() });
+    emit_audio_dropped(&state.app_handle, PipeKind::Audio, &state.audio, dropped);
+    Ok(())
+}
+
+// Send a file (the raw body, named by the "file-name" header) to the backend; returns the transfer id of
+// the "file-transfer-progress" and "file-transfer-complete" events. If the files pipe is down the file
+// is offered once it connects.
+#[tauri::command(async)]
+async fn send_file_to_backend(request: tauri::ipc::Request<'_>, state: State<'_, FramePipeState>) -> Result<u32, PipeError> {
+    let tauri::ipc::InvokeBody::Raw(data) = request.body() else {
+        return Err(PipeError::RequestBodyMustBeRaw);
+    };
+    let name = match request.headers().get("file-name") {
+        Some(value) => value.to_str().map_err(|_| PipeError::InvalidArgument("Invalid file-name header".to_string()))?,
+        None => return Err(PipeError::InvalidArgument("Missing file-name header".to_string())),
+    };
+    let offer = state.file_transfers.send(name, Bytes::copy_from_slice(data)).map_err(PipeError::InvalidArgument)?;
+    info!("[Rust Files Pipe] Sending {} ({} bytes) as file {}.", offer.name, offer.size, offer.id);
+    if let Err(e) = state.offer_file(&offer).await {
+        // The connection is going down; it's offered again on the next one
+        warn!("[Rust Files Pipe] Error offering {}: {}", offer.name, e);
+    }
+    Ok(offer.id)
+}
+
+// The contents of a received file, announced by "file-transfer-complete"; each file can be taken once
+#[tauri::command]
+fn take_received_file(transfer_id: u32, state: State<'_, FramePipeState>) -> Result<tauri::ipc::Response, PipeError> {
+    let file = state
+        .file_transfers
+        .take_received(transfer_id)
+        .ok_or_else(|| PipeError::InvalidArgument(format!("No received file {}", transfer_id)))?;
+    debug!("[Rust Files Pipe] Handing over {} ({} bytes).", file.name, file.data.len());
+    Ok(tauri::ipc::Response::new(file.data))
+}
+
+// Microphones and other capture devices, for start_microphone
+#[tauri::command(async)]
+fn list_audio_inputs() -> Result<Vec<AudioInput>, PipeError> {
+    microphone::list_inputs().map_err(|e| PipeError::io("Failed to list audio inputs", &e))
+}
+
+// Capture `device_id` (an id from list_audio_inputs, or the default input) and stream it to the backend
+#[tauri::command(async)]
+fn start_microphone(device_id: Option<String>, state: State<'_, FramePipeState>) -> Result<MicrophoneInfo, PipeError> {
+    state.start_microphone(device_id)
+}
+
+// Stop capturing; nothing happens if no microphone was started
+#[tauri::command(async)]
+fn stop_microphone(state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    if state.microphone.lock().take().is_some() {
+        info!("[Rust Microphone] Capture stopped.");
+    }
+    Ok(())
+}
+
+// Stream a monitor ({ monitor: index }) or a window ({ window: hwnd }) to an overlay, bypassing the webview
+#[tauri::command(async)]
+fn start_desktop_capture(
+    target: CaptureTarget,
+    options: Option<CaptureOptions>,
+    state: State<'_, FramePipeState>,
+) -> Result<DesktopCaptureInfo, PipeError> {
+    state.start_desktop_capture(target, options.unwrap_or_default())
+}
+
+// Stop the capture (of either kind) to `overlay_id`, or every capture without one; nothing happens if none is running
+#[tauri::command(async)]
+fn stop_desktop_capture(overlay_id: Option<u32>, state: State<'_, FramePipeState>) -> Result<(), PipeError> {
+    // Taken out under the lock, stopped (and their threads joined) after it's released
+    let stopped: Vec<(u32, OverlayCapture)> = {
+        let mut captures = state.captures.lock();
+        match overlay_id {
+            Some(overlay_id) => captures.remove(&overlay_id).map(|capture| (overlay_id, capture)).into_iter().collect(),
+            None => std::mem::take(&mut *captures).into_iter().collect(),
+        }
+    };
+    for (overlay_id, capture) in stopped {
+        info!("[Rust Desktop Capture] Stopped capturing {} to overlay {}.", capture.describe(), overlay_id);
+    }
+    Ok(())
+}
+
+// Windows that capture_window can stream, with thumbnails fitting in `thumbnail_size` pixels (160 by default, 0 for none)
+#[tauri::command(async)]
+fn list_capturable_windows(thumbnail_size: Option<u32>) -> Result<Vec<CapturableWindow>, PipeError> {
+    window_capture::list_windows(thumbnail_size.unwrap_or(DEFAULT_THUMBNAIL_SIZE)).map_err(|e| PipeError::io("Failed to list windows", &e))
+}
+
+// Stream window `hwnd` to an overlay as it draws itself, even while it's covered; stopped with stop_desktop_capture
+#[tauri::command(async)]
+fn capture_window(hwnd: u64, options: Option<WindowCaptureOptions>, state: State<'_, FramePipeState>) -> Result<WindowCaptureInfo, PipeError> {
+    state.capture_window(hwnd, options.unwrap_or_default())
+}
+
+// Device id for a device name coming from the frontend
+fn parse_device(name: &str) -> Result<u32, PipeError> {
+    pose::device_id_from_name(name).ok_or_else(|| PipeError::InvalidArgument(format!("Unknown device '{}'", name)))
+}
+
+// Per-stage frame latency since startup (or the last reset)
+#[tauri::command]
+fn get_latency_histogram(reset: Option<bool>, state: State<'_, FramePipeState>) -> FrameLatencySnapshot {
+    let snapshot = state.metrics.latency.snapshot();
+    if reset.unwrap_or(false) {
+        state.metrics.latency.reset();
+    }
+    snapshot
+}
+
+// Throughput, FPS, latency and drop counters for the frame and transform pipes
+#[tauri::command]
+fn get_pipe_metrics(state: State<'_, FramePipeState>) -> PipeMetricsSnapshot {
+    state.metrics_snapshot()
+}
+
+// What the metrics endpoint reports: the main pipes and every named connection
+fn metrics_scraper(app_handle: AppHandle) -> impl Fn() -> Vec<PipeSample> + Send + Sync + 'static {
+    move || {
+        let sample = |connection: Option<String>, state: &FramePipeState| PipeSample {
+            connection,
+            metrics: state.metrics_snapshot(),
+            latency: state.metrics.latency.snapshot(),
+            status: state.connections.snapshot(),
+        };
+        let mut samples: Vec<PipeSample> = app_handle.try_state::<FramePipeState>().map(|state| sample(None, &state)).into_iter().collect();
+        if let Some(manager) = app_handle.try_state::<PipeManager>() {
+            samples.extend(manager.states().iter().map(|(name, state)| sample(Some(name.clone()), state)));
+        }
+        samples
+    }
+}
+
+// Emit "pipe-stats" every interval_ms milliseconds; 0 turns the event off
+#[tauri::command]
+fn set_pipe_stats_interval(interval_ms: u64, state: State<'_, FramePipeState>) {
+    state.stats_interval_ms.store(interval_ms, Ordering::Relaxed);
+}
+
+// Current state of every pipe and the health of the background tasks
+#[tauri::command]
+fn get_connection_status(state: State<'_, FramePipeState>) -> ConnectionStatus {
+    ConnectionStatus { tasks: state.supervisor.health(), ..state.connections.snapshot() }
+}
+
+// Replace the reconnect policy; takes effect the next time a connection loop starts
+#[tauri::command]
+fn set_reconnect_policy(policy: ReconnectPolicy, connections: State<'_, ConnectionTracker>) {
+    info!("[Rust Connection] Reconnect policy set to {:?}.", policy);
+    connections.set_reconnect_policy(policy);
+}
+
+// --- Inbound Pipe Listener (every pipe but the frame pipe shares the retry logic) ---
+async fn inbound_pipe_listener(pipe: PipeKind, state: FramePipeState) {
+    let label = match pipe {
+        PipeKind::Input => "[Rust Input Pipe]",
+        PipeKind::Audio => "[Rust Audio Pipe]",
+        PipeKind::Microphone => "[Rust Microphone Pipe]",
+        PipeKind::Video => "[Rust Video Pipe]",
+        PipeKind::Files => "[Rust Files Pipe]",
+        PipeKind::Pointer => "[Rust Pointer Pipe]",
+        _ => "[Rust Transform Pipe]",
+    };
+    let connections = &state.connections;
+    let mut backoff = Backoff::new(connections.reconnect_policy());
+    loop {
+        // Re-read every attempt so set_pipe_paths takes effect on the next connect
+        let path = match pipe {
+            PipeKind::Input => state.pipe_paths.lock().input.clone(),
+            PipeKind::Audio => state.pipe_paths.lock().audio.clone(),
+            PipeKind::Microphone => state.pipe_paths.lock().microphone.clone(),
+            PipeKind::Video => state.pipe_paths.lock().video.clone(),
+            PipeKind::Files => state.pipe_paths.lock().files.clone(),
+            PipeKind::Pointer => state.pipe_paths.lock().pointer.clone(),
+            _ => state.pipe_paths.lock().transform.clone(),
+        };
+        let options = *state.pipe_options.lock();
+        debug!("{} Attempting to connect to {:?} pipe: {}", label, pipe, path);
+        connections.mark_connecting(pipe);
+        let connect = async {
+            let mut client = <PlatformTransport as Transport>::connect(&path, &options).await?;
+            connections.mark_handshaking(pipe);
+            state.pipe_security.verify_server(&client)?;
+            auth::authenticate(&mut client, &state.auth_token).await?;
+            io::Result::Ok(client)
+        };
+        match connect.await {
+            Ok(client) => {
+                info!("{} Successfully connected.", label);
+                connections.mark_connected(pipe);
+                // Start over with a fresh (possibly updated) policy after a successful connect
+                backoff = Backoff::new(connections.reconnect_policy());
+                // The write half carries pings, and on the input, audio and files pipes also their writer tasks' messages
+                let (read_half, write_half) = tokio::io::split(client);
+                let writer = match pipe {
+                    PipeKind::Input => Arc::clone(&state.input_writer),
+                    PipeKind::Audio => Arc::clone(&state.audio_writer),
+                    PipeKind::Microphone => Arc::clone(&state.microphone_writer),
+                    PipeKind::Files => Arc::clone(&state.files_writer),
+                    _ => Arc::new(TokioMutex::new(None)),
+                };
+                *writer.lock().await = Some(write_half);
+                let mut reader = BufReader::new(read_half);
+                let liveness = Liveness::default();
+                // Pass the reader and app_handle to the handler function
+                let handler = async {
+                    match pipe {
+                        PipeKind::Input => handle_input_connection(&mut reader, state.app_handle.clone(), &state.session, &liveness).await,
+                        PipeKind::Audio | PipeKind::Microphone => handle_audio_connection(&mut reader, label, &liveness).await,
+                        PipeKind::Video => handle_video_connection(&mut reader, &state, &liveness).await,
+                        PipeKind::Files => handle_files_connection(&mut reader, &state, &liveness).await,
+                        PipeKind::Pointer => handle_pointer_connection(&mut reader, &state, &liveness).await,
+                        _ => {
+                            handle_transform_connection(&mut reader, &state, &liveness).await
+                        }
+                    }
+                };
+                let reason = tokio::select! {
+                    // If the handler returns, it means the client disconnected
+                    _ = handler => "Connection closed".to_string(),
+                    reason = heartbeat::monitor(&writer, &liveness, &state.heartbeat, || state.heartbeat_enabled()) => {
+                        warn!("{} Pipe is dead: {}.", label, reason);
+                        reason
+                    }
+                };
+                // Best-effort close of the old connection before reconnecting
+                if let Some(write_half) = writer.lock().await.take() {
+                    let _ = reader.into_inner().unsplit(write_half).close().await;
+                }
+                info!("{} Client disconnected. Attempting to reconnect...", label);
+                connections.mark_disconnected(pipe, reason);
+            }
+            Err(e) => {
+                let failure = ConnectFailure::of(&e);
+                connections.record_failure(pipe, failure, e.to_string());
+                let Some(delay) = backoff.next_delay() else {
+                    error!("{} Failed to connect ({}): {}. Giving up after {} attempts.", label, failure, e, backoff.attempt() - 1);
+                    connections.mark_gave_up(pipe);
+                    break;
+                };
+                warn!("{} Failed to connect ({}): {}. Retrying in {:?}...", label, failure, e, delay);
+                connections.mark_backoff(pipe, delay);
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+// --- Handle Transform Data --- Reads framed messages until disconnection or error
+async fn handle_transform_connection<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    state: &FramePipeState,
+    liveness: &Liveness,
+) {
+    let FramePipeState { app_handle, metrics, transforms, transform_filter, pose_predictor, overlays, connections, .. } = state;
+    // Don't smooth the first poses of this connection against the last ones of the previous
+    transform_filter.reset();
+    // Devices of the previous connection may be gone or changed; this one reports them anew
+    state.device_status.clear();
+    // Hands tracked on the previous connection are lost until this one reports them
+    if let Some(subscribers) = app_handle.try_state::<HandSubscribers>() {
+        for change in subscribers.reset() {
+            emit_hand_tracking_changed(app_handle, change);
+        }
+    }
+    // Devices that have sent at least one pose on this connection
+    let mut seen_devices = HashSet::new();
+    // Sources that sent something invalid on this connection, so the log isn't flooded at pose rate
+    let mut invalid_sources = HashSet::new();
+    let mut report_invalid = |source: String, invalid: Invalid, values: Vec<f32>| {
+        if invalid_sources.insert(source.clone()) {
+            warn!("[Rust Transform Pipe] Dropping invalid transform for {}: {}. Keeping the last good one.", source, invalid);
+        }
+        emit_transform_invalid(app_handle, source, invalid, values);
+    };
+    loop {
+        let result = match state.timeouts.read() {
+            Some(limit) => match tokio::time::timeout(limit, protocol::read_message(reader)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!("[Rust Transform Pipe] Nothing received for {:?}. Disconnecting.", limit);
+                    connections.record_timeout(PipeKind::Transform, PipeOperation::Read, limit);
+                    break; // Exit inner loop to reconnect
+                }
+            },
+            None => protocol::read_message(reader).await,
+        };
+        if let Ok(message) = &result {
+            liveness.touch();
+            if !heartbeat::is_heartbeat(message.header.message_type) {
+                state.session.record_received(PipeKind::Transform, message);
+            }
+            if state.chaos.disconnect_transform() {
+                warn!("[Rust Transform Pipe] Dropping the connection for chaos mode.");
+                break; // Exit inner loop to reconnect
+            }
+        }
+        let result = result.map(|message| state.chaos.truncate_transform(message));
+        match result {
+            Ok(message) if heartbeat::is_heartbeat(message.header.message_type) => {}
+            Ok(message) if message.header.message_type == MessageType::Poses => {
+                // --- Process the received pose records ---
+                match pose::decode_records(&message.payload) {
+                    Ok(poses) => {
+                        for pose in poses {
+                            if seen_devices.insert(pose.device_id) {
+                                info!("[Rust Transform Pipe] Receiving poses for {}.", pose::device_name(pose.device_id));
+                            }
+                            metrics.record_transform_received();
+                            if let Err(invalid) = validation::check_pose(&pose) {
+                                report_invalid(pose::device_name(pose.device_id).into_owned(), invalid, validation::pose_values(&pose));
+                                continue;
+                            }
+                            let (pose, _) = transform_filter.apply(pose);
+                            let pose = pose_predictor.predict(pose, protocol::timestamp_us());
+                            forward_transform(app_handle, transforms, pose, pose.to_matrix().to_vec());
+                        }
+                    }
+                    // The message itself was framed correctly, so only this one is lost
+                    Err(e) => warn!("[Rust Transform Pipe] Ignoring malformed pose message: {}.", e),
+                }
+            }
+            Ok(message) if message.header.message_type == MessageType::OverlayTransform => {
+                match overlays::decode_transform(&message.payload) {
+                    Ok((overlay_id, matrix)) => match validation::check_matrix(&matrix) {
+                        Ok(()) => emit_overlay_transform(app_handle, overlays, overlay_id, matrix),
+                        Err(invalid) => report_invalid(format!("overlay-{}", overlay_id), invalid, matrix.to_vec()),
+                    },
+                    Err(e) => warn!("[Rust Transform Pipe] Ignoring malformed overlay transform: {}.", e),
+                }
+            }
+            Ok(message) if message.header.message_type == MessageType::Gaze => match Gaze::decode(&message.payload) {
+                Ok(gaze) => match validation::check_gaze(&gaze) {
+                    Ok(()) => {
+                        let gaze = gaze.clamped();
+                        state.foveation.update_gaze(&gaze);
+                        if state.gaze.admit(Instant::now()) {
+                            emit_gaze_update(app_handle, gaze);
+                        }
+                    }
+                    Err(invalid) => report_invalid("gaze".to_string(), invalid, validation::gaze_values(&gaze)),
+                },
+                Err(e) => warn!("[Rust Transform Pipe] Ignoring malformed gaze message: {}.", e),
+            },
+            Ok(message) if message.header.message_type == MessageType::HandSkeleton => match hands::decode_records(&message.payload) {
+                Ok(skeletons) => {
+                    let Some(subscribers) = app_handle.try_state::<HandSubscribers>() else { continue };
+                    for skeleton in skeletons {
+                        if let Err(invalid) = validation::check_hand(&skeleton) {
+                            report_invalid(format!("hand-{}", skeleton.hand.name()), invalid, validation::hand_values(&skeleton));
+                            continue;
+                        }
+                        if let Some(change) = subscribers.send(&skeleton) {
+                            emit_hand_tracking_changed(app_handle, change);
+                        }
+                    }
+                }
+                Err(e) => warn!("[Rust Transform Pipe] Ignoring malformed hand skeleton message: {}.", e),
+            },
+            Ok(message) if message.header.message_type == MessageType::Playspace => match Playspace::decode(&message.payload) {
+                Ok(playspace) => match validation::check_playspace(&playspace) {
+                    Ok(()) => {
+                        let Some(current) = app_handle.try_state::<PlayspaceState>() else { continue };
+                        if current.update(playspace.clone()) {
+                            emit_playspace_changed(app_handle, playspace);
+                        }
+                    }
+                    Err(invalid) => report_invalid("playspace".to_string(), invalid, validation::playspace_values(&playspace)),
+                },
+                Err(e) => warn!("[Rust Transform Pipe] Ignoring malformed playspace message: {}.", e),
+            },
+            Ok(message) if message.header.message_type == MessageType::DeviceStatus => match device_status::decode_records(&message.payload) {
+                Ok(statuses) => {
+                    for status in statuses {
+                        let (changed, low_battery) = state.device_status.update(status.clone());
+                        if changed {
+                            emit_device_status(app_handle, status);
+                        }
+                        if let Some(low_battery) = low_battery {
+                            emit_device_battery_low(app_handle, low_battery);
+                        }
+                    }
+                }
+                Err(e) => warn!("[Rust Transform Pipe] Ignoring malformed device status message: {}.", e),
+            },
+            Ok(message) if message.header.message_type != MessageType::Transform => {
+                // Framing is still intact, so just skip messages we don't handle here
+                warn!("[Rust Transform Pipe] Ignoring unexpected {:?} message.", message.header.message_type);
+            }
+            Ok(message) if message.payload.len() != protocol::TRANSFORM_PAYLOAD_SIZE => {
+                warn!(
+                    "[Rust Transform Pipe] Ignoring transform message with {} byte payload (expected {}).",
+                    message.payload.len(),
+                    protocol::TRANSFORM_PAYLOAD_SIZE
+                );
+            }
+            Ok(message) => {
+                // --- Process the received transform data (single matrix, device 0) ---
+                metrics.record_transform_received();
+                // The payload length was checked above
+                let Some(matrix) = protocol::decode_transform(&message.payload) else { continue };
+                if let Err(invalid) = validation::check_matrix(&matrix) {
+                    report_invalid(pose::device_name(0).into_owned(), invalid, matrix.to_vec());
+                    continue;
+                }
+                let pose = Pose { timestamp_us: protocol::timestamp_us(), ..Pose::from_matrix(&matrix) };
+                let (pose, filtered) = transform_filter.apply(pose);
+                // Forward the matrix as received rather than rebuilding it from the pose, unless it was smoothed
+                let matrix = if filtered { pose.to_matrix() } else { matrix }.to_vec();
+                forward_transform(app_handle, transforms, pose, matrix);
+            }
+            Err(e) if e.is_eof() => {
+                // This is the expected error when the client disconnects gracefully
+                info!("[Rust Transform Pipe] Client closed the connection.");
+                break; // Exit inner loop to reconnect
+            }
+            Err(ProtocolError::Io(e)) => {
+                error!("[Rust Transform Pipe] Error reading from pipe: {}. Disconnecting.", e);
+                break; // Exit inner loop to reconnect
+            }
+            Err(e) => {
+                // Malformed message: the stream can't be resynchronized, so start over
+                warn!("[Rust Transform Pipe] Protocol violation: {}. Disconnecting.", e);
+                break; // Exit inner loop to reconnect
+            }
+        }
+    }
+}
+
+// Emit now, or leave it to the transform emitter when coalescing is on
+fn forward_transform(app_handle: &AppHandle, transforms: &TransformCoalescer, pose: Pose, matrix: Vec<f32>) {
+    if let Some((pose, matrix)) = transforms.push(pose, matrix) {
+        emit_transform_update(app_handle, pose, matrix);
+    }
+}
+
+// --- Emit event to frontend ---
+// Every pose goes to the channel subscribers and out twice as events: on "transform-update" for listeners that want all
+// devices (and check the device field), and on "transform-update:<device>" for
+// listeners that only care about one of them
+fn emit_transform_update(app_handle: &AppHandle, pose: Pose, matrix: Vec<f32>) {
+    if let Some(subscribers) = app_handle.try_state::<TransformSubscribers>() {
+        subscribers.send(&pose, &matrix);
+    }
+    if let Some(osc) = app_handle.try_state::<OscBridge>() {
+        osc.send_pose(&pose);
+    }
+    let device = pose::device_name(pose.device_id).into_owned();
+    let device_event = format!("transform-update:{}", device);
+    let payload = TransformUpdatePayload { device, pose, matrix };
+    if let Err(e) = app_handle.emit(&device_event, payload.clone()) {
+         error!("[Rust Transform Pipe] Error emitting {} event: {}", device_event, e);
+    }
+    if let Err(e) = app_handle.emit("transform-update", payload) {
+         error!("[Rust Transform Pipe] Error emitting transform-update event: {}", e);
+    }
+}
+
+fn emit_hand_tracking_changed(app_handle: &AppHandle, change: HandTrackingChanged) {
+    info!("[Rust Transform Pipe] {:?} hand {}.", change.hand, if change.tracked { "tracked" } else { "lost" });
+    if let Err(e) = app_handle.emit("hand-tracking-changed", change) {
+        error!("[Rust Transform Pipe] Error emitting hand-tracking-changed event: {}", e);
+    }
+}
+
+fn emit_playspace_changed(app_handle: &AppHandle, playspace: Playspace) {
+    info!(
+        "[Rust Transform Pipe] Playspace is {} x {} m with {} bounds points.",
+        playspace.play_area[0],
+        playspace.play_area[1],
+        playspace.bounds.len()
+    );
+    if let Err(e) = app_handle.emit("playspace-changed", playspace) {
+        error!("[Rust Transform Pipe] Error emitting playspace-changed event: {}", e);
+    }
+}
+
+fn emit_device_status(app_handle: &AppHandle, status: DeviceStatus) {
+    if let Err(e) = app_handle.emit("device-status", status) {
+        error!("[Rust Transform Pipe] Error emitting device-status event: {}", e);
+    }
+}
+
+fn emit_device_battery_low(app_handle: &AppHandle, low_battery: BatteryLow) {
+    warn!("[Rust Transform Pipe] {} battery is at {}%.", low_battery.device, low_battery.battery_percent);
+    if let Err(e) = app_handle.emit("device-battery-low", low_battery) {
+        error!("[Rust Transform Pipe] Error emitting device-battery-low event: {}", e);
+    }
+}
+
+fn emit_gaze_update(app_handle: &AppHandle, gaze: Gaze) {
+    if let Err(e) = app_handle.emit("gaze-update", gaze) {
+        error!("[Rust Transform Pipe] Error emitting gaze-update event: {}", e);
+    }
+}
+
+// Placement of one overlay, sent only to the window that registered it
+fn emit_overlay_transform(app_handle: &AppHandle, overlays: &OverlayRegistry, overlay_id: u32, matrix: [f32; 16]) {
+    let Some(overlay) = overlays.get(overlay_id) else {
+        // Backend still sending for an overlay that was just unregistered
+        return;
+    };
+    let payload = OverlayTransformPayload { overlay_id, matrix: matrix.to_vec() };
+    if let Err(e) = app_handle.emit_to(&overlay.window, "overlay-transform", payload) {
+        error!("[Rust Transform Pipe] Error emitting overlay-transform event to {}: {}", overlay.window, e);
+    }
+}
+
+fn emit_transform_invalid(app_handle: &AppHandle, source: String, invalid: Invalid, values: Vec<f32>) {
+    let payload = TransformInvalidPayload { source, reason: invalid.to_string(), values };
+    if let Err(e) = app_handle.emit("transform-invalid", payload) {
+        error!("[Rust Transform Pipe] Error emitting transform-invalid event: {}", e);
+    }
+}
+
+fn emit_controller_input(app_handle: &AppHandle, event: &InputEvent) {
+    let payload = ControllerInputPayload {
+        device: pose::device_name(event.device_id).into_owned(),
+        device_id: event.device_id,
+        control: input::control_name(event.control_id).into_owned(),
+        pressed: event.pressed,
+        touched: event.touched,
+        x: event.x,
+        y: event.y,
+        timestamp_us: event.timestamp_us,
+    };
+    if let Err(e) = app_handle.emit("controller-input", payload) {
+        error!("[Rust Input Pipe] Error emitting controller-input event: {}", e);
+    }
+}
+
+// --- Handle Input Data --- Forwards controller state changes until disconnection or error
+async fn handle_input_connection<R: AsyncRead + Unpin>(reader: &mut R, app_handle: AppHandle, session: &SessionRecorder, liveness: &Liveness) {
+    loop {
+        let result = protocol::read_message(reader).await;
+        if let Ok(message) = &result {
+            liveness.touch();
+            if !heartbeat::is_heartbeat(message.header.message_type) {
+                session.record_received(PipeKind::Input, message);
+            }
+        }
+        match result {
+            Ok(message) if heartbeat::is_heartbeat(message.header.message_type) => {}
+            Ok(message) if message.header.message_type == MessageType::ControllerInput => {
+                match input::decode_records(&message.payload) {
+                    Ok(events) => {
+                        for event in events {
+                            emit_controller_input(&app_handle, &event);
+                            if let Some(osc) = app_handle.try_state::<OscBridge>() {
+                                osc.send_input(&event);
+                            }
+                        }
+                    }
+                    Err(e) => warn!("[Rust Input Pipe] Ignoring malformed input message: {}.", e),
+                }
+            }
+            Ok(message) => {
+                warn!("[Rust Input Pipe] Ignoring unexpected {:?} message.", message.header.message_type);
+            }
+            Err(e) if e.is_eof() => {
+                info!("[Rust Input Pipe] Client closed the connection.");
+                break;
+            }
+            Err(ProtocolError::Io(e)) => {
+                error!("[Rust Input Pipe] Error reading from pipe: {}. Disconnecting.", e);
+                break;
+            }
+            Err(e) => {
+                warn!("[Rust Input Pipe] Protocol violation: {}. Disconnecting.", e);
+                break;
+            }
+        }
+    }
+}
+
+fn emit_audio_dropped(app_handle: &AppHandle, pipe: PipeKind, queue: &AudioQueue, dropped: usize) {
+    if dropped == 0 {
+        return;
+    }
+    let payload = AudioDroppedPayload { pipe, dropped, total: queue.dropped_chunks() };
+    if let Err(e) = app_handle.emit("audio-dropped", payload) {
+        error!("[Rust Audio Pipe] Error emitting audio-dropped event: {}", e);
+    }
+}
+
+// --- Handle Audio Data --- Nothing but pongs is expected back on the audio and microphone pipes; reads until disconnection or error
+async fn handle_audio_connection<R: AsyncRead + Unpin>(reader: &mut R, label: &str, liveness: &Liveness) {
+    loop {
+        match protocol::read_message(reader).await {
+            Ok(message) => {
+                liveness.touch();
+                if !heartbeat::is_heartbeat(message.header.message_type) {
+                    warn!("{} Ignoring unexpected {:?} message.", label, message.header.message_type);
+                }
+            }
+            Err(e) if e.is_eof() => {
+                info!("{} Client closed the connection.", label);
+                break;
+            }
+            Err(ProtocolError::Io(e)) => {
+                error!("{} Error reading from pipe: {}. Disconnecting.", label, e);
+                break;
+            }
+            Err(e) => {
+                warn!("{} Protocol violation: {}. Disconnecting.", label, e);
+                break;
+            }
+        }
+    }
+}
+
+fn emit_file_transfer_complete(app_handle: &AppHandle, complete: FileTransferComplete) {
+    if let Err(e) = app_handle.emit("file-transfer-complete", complete) {
+        error!("[Rust Files Pipe] Error emitting file-transfer-complete event: {}", e);
+    }
+}
+
+// --- Handle File Transfers --- Offers unfinished files again, then answers the backend's offers, chunks,
+// accepts and confirmations until disconnection or error
+async fn handle_files_connection<R: AsyncRead + Unpin>(reader: &mut R, state: &FramePipeState, liveness: &Liveness) {
+    let FramePipeState { app_handle, file_transfers: transfers, session, .. } = state;
+    let reply = |message_type: MessageType, payload: Vec<u8>| async move {
+        if let Err(e) = state.write_files_message(message_type, &payload, &Bytes::new()).await {
+            error!("[Rust Files Pipe] Error writing {:?}: {}", message_type, e);
+        }
+    };
+    for offer in transfers.unfinished_offers() {
+        info!("[Rust Files Pipe] Offering {} ({} bytes) again.", offer.name, offer.size);
+        if let Err(e) = state.offer_file(&offer).await {
+            error!("[Rust Files Pipe] Error offering {}: {}", offer.name, e);
+        }
+    }
+    loop {
+        let result = protocol::read_message(reader).await;
+        if let Ok(message) = &result {
+            liveness.touch();
+            if !heartbeat::is_heartbeat(message.header.message_type) {
+                session.record_received(PipeKind::Files, message);
+            }
+        }
+        let message = match result {
+            Ok(message) => message,
+            Err(e) if e.is_eof() => {
+                info!("[Rust Files Pipe] Client closed the connection.");
+                break;
+            }
+            Err(ProtocolError::Io(e)) => {
+                error!("[Rust Files Pipe] Error reading from pipe: {}. Disconnecting.", e);
+                break;
+            }
+            Err(e) => {
+                warn!("[Rust Files Pipe] Protocol violation: {}. Disconnecting.", e);
+                break;
+            }
+        };
+        match message.header.message_type {
+            message_type if heartbeat::is_heartbeat(message_type) => {}
+            MessageType::FileOffer => {
+                let Some(offer) = FileOffer::decode(&message.payload) else {
+                    warn!("[Rust Files Pipe] Ignoring malformed file offer.");
+                    continue;
+                };
+                let id = offer.id;
+                match transfers.receive_offer(offer) {
+                    Ok(offset) => reply(MessageType::FileAccept, file_transfer::encode_position(id, offset).to_vec()).await,
+                    Err(reason) => {
+                        warn!("[Rust Files Pipe] Refusing file {}: {}.", id, reason);
+                        reply(MessageType::FileDone, file_transfer::encode_done(id, FileStatus::Rejected).to_vec()).await;
+                    }
+                }
+            }
+            MessageType::FileChunk => {
+                let Some((id, offset)) = file_transfer::decode_position(&message.payload) else {
+                    warn!("[Rust Files Pipe] Ignoring malformed file chunk.");
+                    continue;
+                };
+                match transfers.receive_chunk(id, offset, &message.payload[CHUNK_HEADER_SIZE..]) {
+                    Ok(ChunkOutcome::Progress(progress)) => {
+                        if let Err(e) = app_handle.emit("file-transfer-progress", progress) {
+                            error!("[Rust Files Pipe] Error emitting file-transfer-progress event: {}", e);
+                        }
+                    }
+                    Ok(ChunkOutcome::Done { status, name, size }) => {
+                        reply(MessageType::FileDone, file_transfer::encode_done(id, status).to_vec()).await;
+                        info!("[Rust Files Pipe] Received {} ({} bytes): {:?}.", name, size, status);
+                        let error = status.error();
+                        emit_file_transfer_complete(app_handle, FileTransferComplete { transfer_id: id, direction: TransferDirection::FromBackend, name, size, error });
+                    }
+                    Err(e) => warn!("[Rust Files Pipe] Ignoring file chunk: {}.", e),
+                }
+            }
+            MessageType::FileAccept => match file_transfer::decode_position(&message.payload) {
+                Some((id, offset)) if transfers.accept(id, offset) => debug!("[Rust Files Pipe] Backend accepted file {} from offset {}.", id, offset),
+                _ => warn!("[Rust Files Pipe] Ignoring accept of an unknown file or offset."),
+            },
+            MessageType::FileDone => {
+                let Some((id, status)) = file_transfer::decode_done(&message.payload) else {
+                    warn!("[Rust Files Pipe] Ignoring malformed file confirmation.");
+                    continue;
+                };
+                let Some(offer) = transfers.finish_outgoing(id) else {
+                    warn!("[Rust Files Pipe] Ignoring confirmation of unknown file {}.", id);
+                    continue;
+                };
+                info!("[Rust Files Pipe] Backend got {} ({} bytes): {:?}.", offer.name, offer.size, status);
+                let complete = FileTransferComplete { transfer_id: id, direction: TransferDirection::ToBackend, name: offer.name, size: offer.size, error: status.error() };
+                emit_file_transfer_complete(app_handle, complete);
+            }
+            other => warn!("[Rust Files Pipe] Ignoring unexpected {:?} message.", other),
+        }
+    }
+}
+
+// --- Handle Pointer Data --- Pointer rays and VR keyboard keys, sent on to the window showing the overlay (see vr_pointer.rs)
+async fn handle_pointer_connection<R: AsyncRead + Unpin>(reader: &mut R, state: &FramePipeState, liveness: &Liveness) {
+    loop {
+        let result = protocol::read_message(reader).await;
+        if let Ok(message) = &result {
+            liveness.touch();
+            if !heartbeat::is_heartbeat(message.header.message_type) {
+                state.session.record_received(PipeKind::Pointer, message);
+            }
+        }
+        let inject = state.inject_pointer_dom.load(Ordering::Relaxed);
+        match result {
+            Ok(message) if heartbeat::is_heartbeat(message.header.message_type) => {}
+            Ok(message) if message.header.message_type == MessageType::VrPointer => match vr_pointer::decode_pointer(&message.payload) {
+                Ok(event) => {
+                    let script = inject.then(|| vr_pointer::pointer_script(&event));
+                    deliver_vr_event(state, event.overlay_id, "vr-pointer", &event, script);
+                }
+                Err(e) => warn!("[Rust Pointer Pipe] Ignoring malformed pointer event: {}.", e),
+            },
+            Ok(message) if message.header.message_type == MessageType::VrKey => match vr_pointer::decode_key(&message.payload) {
+                Ok(event) => {
+                    let script = inject.then(|| vr_pointer::key_script(&event));
+                    deliver_vr_event(state, event.overlay_id, "vr-key", &event, script);
+                }
+                Err(e) => warn!("[Rust Pointer Pipe] Ignoring malformed key event: {}.", e),
+            },
+            Ok(message) => {
+                warn!("[Rust Pointer Pipe] Ignoring unexpected {:?} message.", message.header.message_type);
+            }
+            Err(e) if e.is_eof() => {
+                info!("[Rust Pointer Pipe] Client closed the connection.");
+                break;
+            }
+            Err(ProtocolError::Io(e)) => {
+                error!("[Rust Pointer Pipe] Error reading from pipe: {}. Disconnecting.", e);
+                break;
+            }
+            Err(e) => {
+                warn!("[Rust Pointer Pipe] Protocol violation: {}. Disconnecting.", e);
+                break;
+            }
+        }
+    }
+}
+
+// Emit `event` to the window that registered `overlay_id` (every window for overlay 0 or one that's gone)
+// and, with DOM injection on, run `script` in the same window(s)
+fn deliver_vr_event<S: Serialize + Clone>(state: &FramePipeState, overlay_id: u32, event: &str, payload: &S, script: Option<String>) {
+    let app_handle = &state.app_handle;
+    let window = state.overlays.get(overlay_id).filter(|_| overlay_id != 0).map(|overlay| overlay.window);
+    let emitted = match &window {
+        Some(window) => app_handle.emit_to(window, event, payload),
+        None => app_handle.emit(event, payload),
+    };
+    if let Err(e) = emitted {
+        error!("[Rust Pointer Pipe] Error emitting {} event: {}", event, e);
+    }
+    let Some(script) = script else {
+        return;
+    };
+    let targets: Vec<_> = match &window {
+        Some(window) => app_handle.get_webview_window(window).into_iter().collect(),
+        None => app_handle.webview_windows().into_values().filter(|target| target.label() != PREVIEW_WINDOW_LABEL).collect(),
+    };
+    for target in targets {
+        if let Err(e) = target.eval(&script) {
+            warn!("[Rust Pointer Pipe] Can't inject {} into window {:?}: {}", event, target.label(), e);
+        }
+    }
+}
+
+// --- Handle Video Data --- Keeps each stream's latest frame for petplay-video:// and decodes it for channel subscribers
+async fn handle_video_connection<R: AsyncRead + Unpin>(reader: &mut R, state: &FramePipeState, liveness: &Liveness) {
+    let FramePipeState { app_handle, video, session, .. } = state;
+    // Streams that failed to decode on this connection, so the log isn't flooded at frame rate
+    let mut undecodable = HashSet::new();
+    loop {
+        let result = protocol::read_message(reader).await;
+        if let Ok(message) = &result {
+            liveness.touch();
+            if !heartbeat::is_heartbeat(message.header.message_type) {
+                session.record_received(PipeKind::Video, message);
+            }
+        }
+        match result {
+            Ok(message) if heartbeat::is_heartbeat(message.header.message_type) => {}
+            Ok(message) if message.header.message_type == MessageType::VideoFrame => {
+                let Some(header) = VideoHeader::decode(&message.payload) else {
+                    warn!("[Rust Video Pipe] Ignoring malformed video frame.");
+                    continue;
+                };
+                let image = Bytes::from(message.payload).slice(VIDEO_HEADER_SIZE..);
+                let payload = VideoFramePayload { stream_id: header.stream_id, codec: header.codec, timestamp_us: header.timestamp_us, bytes: image.len() };
+                video.store(EncodedFrame { header, image: image.clone() });
+                if let Err(e) = app_handle.emit("video-frame", payload) {
+                    error!("[Rust Video Pipe] Error emitting video-frame event: {}", e);
+                }
+                if !video.wants(header.stream_id) {
+                    continue;
+                }
+                // Off the runtime's worker threads; the next frame isn't read until this one is decoded
+                match tokio::task::spawn_blocking(move || video::decode(header.codec, &image)).await {
+                    Ok(Ok(decoded)) => {
+                        undecodable.remove(&header.stream_id);
+                        video.send(&header, &decoded);
+                    }
+                    Ok(Err(e)) => {
+                        if undecodable.insert(header.stream_id) {
+                            warn!("[Rust Video Pipe] Can't decode {:?} frames of stream {}: {}.", header.codec, header.stream_id, e);
+                        }
+                    }
+                    Err(e) => error!("[Rust Video Pipe] Decoder task failed: {}", e),
+                }
+            }
+            Ok(message) => {
+                warn!("[Rust Video Pipe] Ignoring unexpected {:?} message.", message.header.message_type);
+            }
+            Err(e) if e.is_eof() => {
+                info!("[Rust Video Pipe] Client closed the connection.");
+                break;
+            }
+            Err(ProtocolError::Io(e)) => {
+                error!("[Rust Video Pipe] Error reading from pipe: {}. Disconnecting.", e);
+                break;
+            }
+            Err(e) => {
+                warn!("[Rust Video Pipe] Protocol violation: {}. Disconnecting.", e);
+                break;
+            }
+        }
+    }
+}
+
+// The latest frame of the stream in the path, as the backend encoded it
+fn serve_video_frame(app_handle: &AppHandle, request: &tauri::http::Request<Vec<u8>>) -> tauri::http::Response<Vec<u8>> {
+    let respond = |status: u16, content_type: &str, body: Vec<u8>| {
+        tauri::http::Response::builder()
+            .status(status)
+            .header("Content-Type", content_type)
+            .header("Cache-Control", "no-store")
+            .header("Access-Control-Allow-Origin", "*")
+            .body(body)
+            .unwrap_or_default()
+    };
+    let Some(stream_id) = video::stream_of_path(request.uri().path()) else {
+        return respond(400, "text/plain", b"Expected a stream id".to_vec());
+    };
+    let frame = app_handle.try_state::<FramePipeState>().and_then(|state| state.video.latest(stream_id));
+    match frame {
+        Some(frame) => respond(200, frame.header.codec.mime_type(), frame.image.to_vec()),
+        None => respond(404, "text/plain", format!("No frame of stream {} yet", stream_id).into_bytes()),
+    }
+}
+
+// Pixel data of a width x height frame has to be exactly what its format and stride add up to, within the limit
+fn check_frame_size(format: PixelFormat, width: u32, height: u32, stride: u32, actual: usize, max: usize) -> Result<(), PipeError> {
+    let expected = format.frame_size(height, stride);
+    if expected > max as u64 {
+        return Err(PipeError::FrameTooLarge { bytes: expected, max });
+    }
+    if expected != actual as u64 {
+        return Err(PipeError::FrameSizeMismatch { width, height, expected, actual });
+    }
+    Ok(())
+}
+
+// --- Tauri Setup ---
+pub use cli::{CliArgs, CliError};
+
+// puppyweb.toml in the app config dir, unless another file was given with --config
+fn config_path(app_handle: &AppHandle) -> tauri::Result<PathBuf> {
+    if let Some(path) = app_handle.try_state::<CliArgs>().and_then(|args| args.config.clone()) {
+        return Ok(path);
+    }
+    Ok(app_handle.path().app_config_dir()?.join(CONFIG_FILE_NAME))
+}
+
+// A file in `subdir` of the app data directory for a command that writes or reads one: `name` if the webview
+// gave one, `default_name()` otherwise
+fn app_data_file(app_handle: &AppHandle, subdir: &str, name: Option<&str>, default_name: impl FnOnce() -> String) -> Result<PathBuf, PipeError> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| PipeError::Unsupported(format!("No app data directory: {}", e)))?
+        .join(subdir);
+    match name {
+        Some(name) => confined_path(&dir, name),
+        None => Ok(dir.join(default_name())),
+    }
+}
+
+// `name` under `dir`; absolute paths, drive prefixes and `..` are refused so the webview can't reach other files
+fn confined_path(dir: &Path, name: &str) -> Result<PathBuf, PipeError> {
+    let relative = Path::new(name);
+    if name.is_empty() || !relative.components().all(|component| matches!(component, std::path::Component::Normal(_))) {
+        return Err(PipeError::InvalidArgument(format!("{:?} must be a relative path without '..'", name)));
+    }
+    Ok(dir.join(relative))
+}
+
+// Read puppyweb.toml (or the --config file), falling back to the defaults if it's missing or broken
+fn load_config(app_handle: &AppHandle) -> AppConfig {
+    let path = match config_path(app_handle) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("[Rust Config] No app config directory ({}), using defaults.", e);
+            return AppConfig::default();
+        }
+    };
+    match AppConfig::load(&path) {
+        Ok(config) if path.exists() => {
+            info!("[Rust Config] Loaded {} (log level {:?}).", path.display(), config.log_level);
+            config
+        }
+        Ok(config) => {
+            info!("[Rust Config] No {} found, using defaults.", path.display());
+            config
+        }
+        Err(e) => {
+            error!("[Rust Config] Error reading {}: {}. Using defaults.", path.display(), e);
+            AppConfig::default()
+        }
+    }
+}
+
+// Name the commands are invoked under ("plugin:petplay-ipc|send_frame_data") and the permission prefix
+pub const PLUGIN_NAME: &str = "petplay-ipc";
+
+// The plugin: `tauri::Builder::default().plugin(tauri_plugin_petplay_ipc::init())`.
+// Commands are invoked as "plugin:petplay-ipc|<command>"; the "petplay-ipc:default"
+// permission allows all of them.
+// Wraps the command handler so the payload cap and rate limits apply before a command's arguments are even deserialized
+fn limited(commands: impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        if let Some(state) = invoke.message.webview().try_state::<FramePipeState>() {
+            if let Err(e) = state.check_invoke(invoke.message.command(), invoke.message.payload()) {
+                invoke.resolver.reject(e);
+                return true;
+            }
+        }
+        commands(invoke)
+    }
+}
+
+pub fn init() -> TauriPlugin<Wry> {
+    init_with_args(CliArgs::default())
+}
+
+// The plugin with command line overrides, parsed by the app with CliArgs::parse()
+pub fn init_with_args(args: CliArgs) -> TauriPlugin<Wry> {
+    // Tauri's runtime, through a wrapper that can abort everything the plugin spawned
+    let rt_handle = IpcRuntime::from_tauri();
+
+    Builder::new(PLUGIN_NAME)
+        .invoke_handler(limited(tauri::generate_handler![
+            send_frame_data,
+            set_frame_channel,
+            configure_stream,
+            get_connection_status,
+            set_reconnect_policy,
+            connect_pipes,
+            disconnect_pipes,
+            reconnect_frame_pipe,
+            pause_stream,
+            resume_stream,
+            get_pipe_metrics,
+            set_pipe_stats_interval,
+            share_gpu_texture,
+            send_haptic_pulse,
+            send_audio_chunk,
+            list_audio_inputs,
+            start_microphone,
+            stop_microphone,
+            start_desktop_capture,
+            stop_desktop_capture,
+            list_capturable_windows,
+            capture_window,
+            set_transform_rate,
+            set_transform_filter,
+            get_last_transform,
+            set_pose_prediction,
+            subscribe_transforms,
+            unsubscribe_transforms,
+            subscribe_hands,
+            get_device_status,
+            get_playspace_bounds,
+            unsubscribe_hands,
+            subscribe_video,
+            unsubscribe_video,
+            send_file_to_backend,
+            take_received_file,
+            set_heartbeat,
+            get_backend_info,
+            set_pipe_paths,
+            configure_pipe,
+            register_overlay,
+            unregister_overlay,
+            set_overlay_transform,
+            set_overlay_width,
+            set_overlay_curvature,
+            set_overlay_alpha,
+            set_overlay_attachment,
+            create_overlay,
+            destroy_overlay,
+            list_overlays,
+            set_dashboard_mode,
+            get_overlay_placement,
+            create_pipe_connection,
+            destroy_pipe_connection,
+            list_pipe_connections,
+            launch_backend,
+            stop_backend,
+            set_log_level,
+            get_latency_histogram,
+            start_recording,
+            stop_recording,
+            start_session_recording,
+            stop_session_recording,
+            replay_session,
+            capture_screenshot,
+            export_diagnostics,
+            show_frame_preview,
+            subscribe_frame_preview,
+            set_adaptive_quality,
+            set_foveation,
+            set_target_fps,
+            get_webrtc_config,
+            submit_remote_poses,
+            enable_osc_bridge,
+            configure_osc,
+            enable_ndi_output,
+            enable_openvr,
+            enable_openxr,
+            add_sink,
+            remove_sink,
+            list_sinks
+        ]))
+        .register_uri_scheme_protocol(video::VIDEO_SCHEME, |ctx, request| serve_video_frame(ctx.app_handle(), &request))
+        .setup(move |app, _api| {
+            // Tasks spawned through it are aborted on exit
+            app.manage(rt_handle.clone());
+            // Managed first: load_config needs --config
+            app.manage(args);
+            let app_handle = app.clone();
+            // Logging comes first so loading the config is logged too; its levels are applied right after
+            let logging = Logging::init(app_handle.path().app_log_dir().ok().as_deref());
+            let mut config = load_config(&app_handle);
+            app.state::<CliArgs>().apply(&mut config);
+            if let Err(e) = logging.configure(config.log_level, &config.log_modules) {
+                warn!("[Rust Config] Ignoring log levels: {}", e);
+            }
+            // Before anything touches the pipes: a second launch hands its arguments to the first and exits
+            if !config.multi_instance {
+                if let single_instance::Instance::Forwarded = single_instance::claim(&rt_handle, &app_handle, &config.pipes.frame) {
+                    // Through Tauri so the app shuts down properly; nothing else is set up in the meantime
+                    app_handle.exit(0);
+                    return Ok(());
+                }
+            }
+            if let Err(e) = config.pipe_options.validate() {
+                warn!("[Rust Config] Ignoring pipe options: {}", e);
+                config.pipe_options = PipeOptions::default();
+            }
+            if let Err(e) = config.stream.validate() {
+                warn!("[Rust Config] Ignoring compression settings: {}", e);
+                config.stream = StreamConfig { zstd_level: DEFAULT_ZSTD_LEVEL, compression_workers: 0, ..config.stream };
+            }
+            if let Err(e) = config.pipe_security.validate() {
+                warn!("[Rust Config] Ignoring pipe security settings: {}", e);
+                config.pipe_security = PipeSecurity::default();
+            }
+            if let Err(e) = config.limits.validate() {
+                warn!("[Rust Config] Ignoring command limits: {}", e);
+                config.limits = CommandLimits::default();
+            }
+            if let Err(e) = config.adaptive.validate() {
+                warn!("[Rust Config] Ignoring adaptive quality settings: {}", e);
+                config.adaptive = AdaptiveConfig::default();
+            }
+            if let Err(e) = config.foveation.validate() {
+                warn!("[Rust Config] Ignoring foveation settings: {}", e);
+                config.foveation = FoveationConfig::default();
+            }
+            if let Err(e) = config.webrtc.validate() {
+                warn!("[Rust Config] Ignoring WebRTC settings: {}", e);
+                config.webrtc = WebRtcConfig::default();
+            }
+            app.manage(config.webrtc.clone());
+            if let Err(e) = config.osc.validate() {
+                warn!("[Rust Config] Ignoring OSC settings: {}", e);
+                config.osc = OscConfig::default();
+            }
+            if let Err(e) = config.openvr.validate() {
+                warn!("[Rust Config] Ignoring OpenVR settings: {}", e);
+                config.openvr = OpenVrConfig::default();
+            }
+            if let Err(e) = config.dashboard.validate() {
+                warn!("[Rust Config] Ignoring dashboard settings: {}", e);
+                config.dashboard = DashboardConfig::default();
+            }
+            if let Err(e) = config.openxr.validate() {
+                warn!("[Rust Config] Ignoring OpenXR settings: {}", e);
+                config.openxr = OpenXrConfig::default();
+            }
+            if let Err(e) = config.ndi.validate() {
+                warn!("[Rust Config] Ignoring NDI settings: {}", e);
+                config.ndi = NdiConfig::default();
+            }
+            if let Err(e) = config.metrics_endpoint.validate() {
+                warn!("[Rust Config] Ignoring metrics endpoint settings: {}", e);
+                config.metrics_endpoint = MetricsEndpointConfig::default();
+            }
+            if let Err(e) = config.audio.validate() {
+                warn!("[Rust Config] Ignoring audio settings: {}", e);
+                config.audio = AudioConfig::default();
+            }
+            if let Err(e) = config.gaze.validate() {
+                warn!("[Rust Config] Ignoring gaze settings: {}", e);
+                config.gaze = GazeConfig::default();
+            }
+            if let Err(e) = config.device_status.validate() {
+                warn!("[Rust Config] Ignoring device status settings: {}", e);
+                config.device_status = DeviceStatusConfig::default();
+            }
+            if let Err(e) = config.chaos.validate() {
+                warn!("[Rust Config] Ignoring chaos settings: {}", e);
+                config.chaos = ChaosConfig::default();
+            }
+            if config.chaos.enabled {
+                warn!("[Rust Chaos] Fault injection is on: {:?}", config.chaos);
+            }
+            let osc = OscBridge::new(rt_handle.clone(), OscConfig { enabled: false, ..config.osc.clone() });
+            if config.osc.enabled {
+                if let Err(e) = osc.apply(config.osc.clone(), osc_handler(app_handle.clone())) {
+                    error!("[Rust OSC] Failed to start the OSC bridge: {}", e);
+                }
+            }
+            app.manage(osc);
+            app.manage(logging);
+            // Connection state is shared by all pipes, so it's created first
+            let paths = &config.pipes;
+            let connections = ConnectionTracker::new(app_handle.clone(), paths);
+            // Panics are reported to the log directory; the previous run's reports go out once the frontend has loaded
+            match app_handle.path().app_log_dir() {
+                Ok(log_dir) => {
+                    app.manage(PendingReports::new(crash_report::pending(&log_dir)));
+                    let tracker = connections.clone();
+                    crash_report::install(log_dir, move || {
+                        tracker.try_snapshot().and_then(|status| serde_json::to_string_pretty(&status).ok())
+                    });
+                }
+                Err(e) => warn!("[Rust Crash] No log directory, panics won't be reported: {}", e),
+            }
+            // Generated per session unless configured; the mock and launched backends are handed the same one
+            let auth_token = AuthToken::resolve(config.auth_token.as_deref())
+                .map_err(|e| format!("Failed to generate a pipe auth token: {}", e))?;
+            if config.mock_backend {
+                mock_backend::spawn(&rt_handle, paths, &config.pipe_options, &config.pipe_security, &auth_token);
+            }
+            connections.set_reconnect_policy(config.reconnect);
+            app.manage(connections.clone());
+            app.manage(TransformSubscribers::default());
+            app.manage(HandSubscribers::default());
+            app.manage(PlayspaceState::default());
+            app.manage(PipeManager::default());
+            // The pipe state needs the tracker and app handle, so it's managed here rather than on the builder.
+            // It spawns the frame connection loop and the transform listener on the runtime handle.
+            app.manage(FramePipeState::new(rt_handle.clone(), connections, app_handle.clone(), &config, auth_token.clone())); // Clone the handle here
+            if config.metrics_endpoint.enabled {
+                if let Err(e) = metrics_http::spawn(&rt_handle, &config.metrics_endpoint, metrics_scraper(app_handle.clone())) {
+                    error!("[Rust Metrics] Failed to start the metrics endpoint on port {}: {}", config.metrics_endpoint.port, e);
+                }
+            }
+            // Edits to the config file apply from now on
+            match config_path(&app_handle) {
+                Ok(path) => config_watch::spawn(&app_handle.state::<FramePipeState>().supervisor, app_handle.clone(), path, config.clone()),
+                Err(e) => warn!("[Rust Config] Not watching the config file: {}", e),
+            }
+            // A (re)started backend gets connected to right away, even if the loops had given up
+            let started_handle = app_handle.clone();
+            let supervisor = BackendSupervisor::new(rt_handle.clone(), app_handle, auth_token, move || {
+                if let Some(state) = started_handle.try_state::<FramePipeState>() {
+                    state.connect();
+                }
+            });
+            if config.backend.auto_start {
+                if let Err(e) = supervisor.launch(config.backend.clone()) {
+                    error!("[Rust Backend] Failed to launch backend: {}.", e);
+                }
+            }
+            app.manage(supervisor);
+            if !config.hide_tray {
+                let changes = app.state::<FramePipeState>().connections.subscribe();
+                if let Err(e) = tray::spawn(app.app_handle(), &rt_handle, changes) {
+                    error!("[Rust Tray] Failed to create the tray icon: {}", e);
+                }
+            }
+            // Replay mode: the recording stands in for the webview, which is closed so it can't send frames too
+            if let Some(path) = app.state::<CliArgs>().replay.clone() {
+                for window in app.webview_windows().into_values() {
+                    let _ = window.destroy();
+                }
+                let state = app.state::<FramePipeState>().inner().clone();
+                let app_handle = app.clone();
+                rt_handle.spawn(async move {
+                    match state.replay(&path).await {
+                        Ok(frames) => info!("[Rust Recorder] Replayed {} frames from {}.", frames, path.display()),
+                        Err(e) => error!("[Rust Recorder] Error replaying {}: {}", path.display(), e),
+                    }
+                    app_handle.exit(0);
+                });
+            }
+            Ok(())
+        })
+        .on_page_load(|webview, payload| {
+            // Only now is the frontend listening for reports of the previous run's panics
+            if payload.event() == PageLoadEvent::Finished {
+                if let Some(reports) = webview.try_state::<PendingReports>() {
+                    reports.emit(webview.app_handle());
+                }
+            }
+        })
+        .on_event(|app_handle, event| {
+            // A closed window can't render its overlays anymore
+            if let RunEvent::WindowEvent { label, event: WindowEvent::Destroyed, .. } = event {
+                if let Some(state) = app_handle.try_state::<FramePipeState>() {
+                    if label == PREVIEW_WINDOW_LABEL {
+                        state.preview.set_channel(None);
+                    }
+                    let overlays = state.overlays.unregister_window(label);
+                    if !overlays.is_empty() {
+                        let state = state.inner().clone();
+                        state.rt.clone().spawn(async move {
+                            if let Err(e) = state.remove_overlays(overlays).await {
+                                error!("[Rust Frame Pipe] Error removing overlays of a closed window: {}", e);
+                            }
+                        });
+                    }
+                }
+            }
+            // Closing the last window would end a replay early; it exits by itself when it's done
+            if let RunEvent::ExitRequested { code: None, api, .. } = event {
+                if app_handle.try_state::<CliArgs>().is_some_and(|args| args.replay.is_some()) {
+                    api.prevent_exit();
+                    return;
+                }
+            }
+            // Flush and close the pipes before the runtime goes away; whichever exit event comes first does it
+            if let RunEvent::ExitRequested { .. } | RunEvent::Exit = event {
+                if let Some(state) = app_handle.try_state::<FramePipeState>() {
+                    let named = app_handle.try_state::<PipeManager>().map(|manager| manager.remove_all()).unwrap_or_default();
+                    state.rt.block_on(async {
+                        for named in named {
+                            named.shutdown().await;
+                        }
+                        state.shutdown().await;
+                        // Only after the Goodbye, so the backend sees a clean disconnect first
+                        if let Some(supervisor) = app_handle.try_state::<BackendSupervisor>() {
+                            supervisor.stop().await;
+                        }
+                    });
+                }
+            }
+            // Tauri's runtime outlives the app; nothing the plugin started should keep running on it
+            if let RunEvent::Exit = event {
+                if let Some(rt) = app_handle.try_state::<IpcRuntime>() {
+                    debug!("[Rust Connection] Aborted {} background tasks.", rt.abort_all());
+                }
+            }
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::DuplexStream;
+    use transport::PipeListener;
+
+    // Small enough that frames take several writes and reads
+    const DUPLEX_BUFFER: usize = 4096;
+
+    fn test_token() -> AuthToken {
+        AuthToken::resolve(Some("integration-test-token")).unwrap()
+    }
+
+    // The app's end of a connection to the mock backend's `handler`, which runs until the app hangs up
+    fn mock_connection<F, Fut>(token: &AuthToken, handler: F) -> (DuplexStream, tokio::task::JoinHandle<io::Result<()>>)
+    where
+        F: FnOnce(DuplexStream) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = io::Result<()>> + Send,
+    {
+        let (app, mut backend) = tokio::io::duplex(DUPLEX_BUFFER);
+        let token = token.clone();
+        let backend = tokio::spawn(async move {
+            auth::accept(&mut backend, &token).await?;
+            handler(backend).await
+        });
+        (app, backend)
+    }
+
+    #[test]
+    fn command_paths_stay_in_their_directory() {
+        let dir = Path::new("data").join(RECORDINGS_DIR);
+        assert_eq!(confined_path(&dir, "takes/first.pwrec").unwrap(), dir.join("takes").join("first.pwrec"));
+        for name in ["", "..", "./first.pwrec", "../escape.pwrec", "takes/../../escape.pwrec", "/etc/passwd"] {
+            assert!(confined_path(&dir, name).is_err(), "{:?} was let through", name);
+        }
+    }
+
+    fn test_frame(sequence: u64) -> (FrameHeader, Bytes) {
+        let frame = QueuedFrame::test(sequence, PixelFormat::Rgba8, 64, 32, 64 * 4, (0..64 * 32 * 4).map(|i| i as u8).collect::<Vec<u8>>());
+        (FrameHeader { timestamp_us: protocol::timestamp_us(), ..frame.header }, frame.pixels)
+    }
+
+    #[cfg(windows)]
+    fn test_endpoint(name: &str) -> String {
+        format!(r"\\.\pipe\petplay-ipc-test-{}-{}", name, std::process::id())
+    }
+
+    #[cfg(unix)]
+    fn test_endpoint(name: &str) -> String {
+        std::env::temp_dir().join(format!("petplay-ipc-test-{}-{}.sock", name, std::process::id())).display().to_string()
+    }
+
+    #[tokio::test]
+    async fn a_superseded_connection_loop_keeps_its_hands_off_the_live_writer() {
+        let endpoint = test_endpoint("generation");
+        let options = PipeOptions::default();
+        let mut server = PipeListener::bind(&endpoint, &options, &PipeSecurity::default()).unwrap();
+        let slot = TokioMutex::new(None);
+        let generation = Generation::default();
+
+        // A write error starts a new loop while the old one is still retrying; both reach the backend
+        let old = generation.advance();
+        let new = generation.advance();
+        let (old_client, old_server) = tokio::join!(PlatformTransport::connect(&endpoint, &options), server.accept());
+        let (new_client, new_server) = tokio::join!(PlatformTransport::connect(&endpoint, &options), server.accept());
+        let (_, old_writer) = tokio::io::split(old_client.unwrap());
+        let (_, new_writer) = tokio::io::split(new_client.unwrap());
+        let (mut old_server, mut new_server) = (old_server.unwrap(), new_server.unwrap());
+
+        *lock_if_current(&slot, &generation, new).await.expect("the newest loop may install its writer") = Some(new_writer);
+        assert!(lock_if_current(&slot, &generation, old).await.is_none());
+        // The stale loop drops its connection instead of installing it
+        drop(old_writer);
+
+        slot.lock().await.as_mut().unwrap().write_all(b"frame").await.unwrap();
+        let mut received = [0; 5];
+        new_server.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"frame");
+        assert_eq!(old_server.read(&mut received).await.unwrap(), 0);
+
+        // A disconnect invalidates the current loop too
+        generation.advance();
+        assert!(lock_if_current(&slot, &generation, new).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn a_frame_session_handshakes_with_the_mock_backend() {
+        let token = test_token();
+        let (app, backend) = mock_connection(&token, mock_backend::serve_frames);
+        let (reader, mut writer, info) = start_frame_session(app, &token).await.unwrap();
+        assert_eq!(info.backend_name.as_deref(), Some("puppyweb mock backend"));
+        assert_eq!(info.protocol_version, protocol::PROTOCOL_VERSION);
+        assert_ne!(info.capabilities & handshake::CAP_HEARTBEAT, 0);
+
+        // Frames are taken, pings answered, and Goodbye ends the session from the backend's side
+        let (header, pixels) = test_frame(1);
+        write_message(&mut writer, MessageType::Frame, 0, [&header.encode()[..], &pixels]).await.unwrap();
+        writer.write_all(&protocol::encode(MessageType::Ping, 0, &[1, 2, 3, 4])).await.unwrap();
+        writer.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        let liveness = Liveness::default();
+        let mut unexpected = Vec::new();
+        let reason = read_frame_pipe(reader, &liveness, |message| unexpected.push(message.header.message_type)).await;
+        assert_eq!(reason, "Connection closed by backend");
+        // The Pong came through as a sign of life, not as a message to handle
+        assert!(unexpected.is_empty());
+        assert!(liveness.silence() < Duration::from_secs(5));
+        backend.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_backend_with_another_token_is_refused() {
+        let (app, backend) = mock_connection(&AuthToken::resolve(Some("someone-else")).unwrap(), mock_backend::serve_frames);
+        let e = start_frame_session(app, &test_token()).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        assert!(backend.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn frames_arrive_whole_in_gathered_writes() {
+        let (mut app, backend) = tokio::io::duplex(DUPLEX_BUFFER);
+        let backend = tokio::spawn(async move {
+            let mut reader = BufReader::new(backend);
+            let mut frames = Vec::new();
+            while let Ok(message) = protocol::read_message(&mut reader).await {
+                frames.push(message);
+            }
+            frames
+        });
+
+        let mut sent = Vec::new();
+        for sequence in 1..=3 {
+            let (header, pixels) = test_frame(sequence);
+            let encoded = header.encode();
+            let written = write_message(&mut app, MessageType::Frame, FLAG_DELTA, [&encoded[..], &pixels]).await.unwrap();
+            assert_eq!(written, protocol::HEADER_SIZE + protocol::FRAME_HEADER_SIZE + pixels.len());
+            sent.push((header, pixels));
+        }
+        drop(app);
+
+        let received = backend.await.unwrap();
+        assert_eq!(received.len(), sent.len());
+        for (message, (header, pixels)) in received.iter().zip(&sent) {
+            assert_eq!(message.header.message_type, MessageType::Frame);
+            assert_eq!(message.header.flags, FLAG_DELTA);
+            assert_eq!(FrameHeader::decode(&message.payload, protocol::PROTOCOL_VERSION), Some(*header));
+            assert_eq!(&message.payload[protocol::FRAME_HEADER_SIZE..], &pixels[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn control_messages_and_acks_reach_the_frame_pipe_handler() {
+        let (app, mut backend) = tokio::io::duplex(DUPLEX_BUFFER);
+        backend.write_all(&protocol::encode(MessageType::Control, 0, &control::encode(&ControlMessage::Pause))).await.unwrap();
+        backend.write_all(&protocol::encode(MessageType::Ping, 0, &[0; 8])).await.unwrap();
+        backend.write_all(&protocol::encode(MessageType::FrameAck, 0, &7u64.to_le_bytes())).await.unwrap();
+        drop(backend);
+
+        let mut handled = Vec::new();
+        let reason = read_frame_pipe(app, &Liveness::default(), |message| handled.push(message)).await;
+        assert_eq!(reason, "Connection closed by backend");
+        assert_eq!(handled.len(), 2);
+        assert_eq!(control::decode(&handled[0].payload), Ok(ControlMessage::Pause));
+        assert_eq!(ack::decode(&handled[1].payload), Ok(7));
+    }
+
+    #[tokio::test]
+    async fn the_transform_pipe_streams_poses_for_every_device() {
+        let token = test_token();
+        let (mut app, backend) = mock_connection(&token, mock_backend::serve_transforms);
+        auth::authenticate(&mut app, &token).await.unwrap();
+
+        let mut reader = BufReader::new(app);
+        // The playspace and every device's status come first
+        let message = protocol::read_message(&mut reader).await.unwrap();
+        assert_eq!(message.header.message_type, MessageType::Playspace);
+        let playspace = Playspace::decode(&message.payload).unwrap();
+        assert!(validation::check_playspace(&playspace).is_ok());
+        assert_eq!(playspace.bounds.len(), 8);
+        let message = protocol::read_message(&mut reader).await.unwrap();
+        assert_eq!(message.header.message_type, MessageType::DeviceStatus);
+        let statuses = device_status::decode_records(&message.payload).unwrap();
+        assert_eq!(statuses.iter().map(|status| status.device_id).collect::<Vec<_>>(), [pose::DEVICE_HMD, pose::DEVICE_CONTROLLER_LEFT, pose::DEVICE_CONTROLLER_RIGHT]);
+        assert!(statuses[1].battery_percent.is_some());
+
+        let mut devices = HashSet::new();
+        while devices.len() < 3 {
+            let message = protocol::read_message(&mut reader).await.unwrap();
+            assert_eq!(message.header.message_type, MessageType::Poses);
+            for pose in pose::decode_records(&message.payload).unwrap() {
+                assert!(validation::check_pose(&pose).is_ok());
+                devices.insert(pose.device_id);
+            }
+        }
+        assert_eq!(devices, HashSet::from([pose::DEVICE_HMD, pose::DEVICE_CONTROLLER_LEFT, pose::DEVICE_CONTROLLER_RIGHT]));
+
+        // Each pose update is followed by gaze and both hands
+        let message = protocol::read_message(&mut reader).await.unwrap();
+        assert_eq!(message.header.message_type, MessageType::Gaze);
+        assert!(validation::check_gaze(&Gaze::decode(&message.payload).unwrap()).is_ok());
+        let message = protocol::read_message(&mut reader).await.unwrap();
+        assert_eq!(message.header.message_type, MessageType::HandSkeleton);
+        let skeletons = hands::decode_records(&message.payload).unwrap();
+        assert_eq!(skeletons.iter().map(|skeleton| skeleton.hand).collect::<Vec<_>>(), [Hand::Left, Hand::Right]);
+        for skeleton in &skeletons {
+            assert_eq!(skeleton.joints.len(), hands::OPENXR_JOINT_COUNT);
+            assert!(validation::check_hand(skeleton).is_ok());
+        }
+
+        // Hanging up ends the backend's side too
+        drop(reader);
+        assert!(backend.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn the_input_pipe_takes_haptic_pulses_until_goodbye() {
+        let token = test_token();
+        let (mut app, backend) = mock_connection(&token, mock_backend::serve_input);
+        auth::authenticate(&mut app, &token).await.unwrap();
+
+        let mut pulse = Vec::new();
+        pulse.extend_from_slice(&pose::DEVICE_CONTROLLER_LEFT.to_le_bytes());
+        pulse.extend_from_slice(&5000u32.to_le_bytes());
+        pulse.extend_from_slice(&0.5f32.to_le_bytes());
+        app.write_all(&protocol::encode(MessageType::HapticPulse, 0, &pulse)).await.unwrap();
+        app.write_all(&protocol::encode(MessageType::Ping, 0, &[9; 8])).await.unwrap();
+        let pong = protocol::read_message(&mut app).await.unwrap();
+        assert_eq!((pong.header.message_type, &pong.payload[..]), (MessageType::Pong, &[9; 8][..]));
+        app.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        backend.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn audio_chunks_go_out_with_their_format() {
+        let token = test_token();
+        let (mut app, backend) = mock_connection(&token, mock_backend::serve_audio);
+        auth::authenticate(&mut app, &token).await.unwrap();
+
+        let format = AudioFormat { sample_rate: 44_100, channels: 1, sample_format: SampleFormat::S16 };
+        let samples = [0u8; 960];
+        let header = AudioHeader { overlay_id: DEFAULT_OVERLAY_ID, format, timestamp_us: protocol::timestamp_us(), frames: format.frames(samples.len()).unwrap() };
+        let written = write_message(&mut app, MessageType::AudioChunk, 0, [&header.encode(), &samples]).await.unwrap();
+        assert_eq!(written, protocol::HEADER_SIZE + audio::AUDIO_HEADER_SIZE + samples.len());
+        app.write_all(&protocol::encode(MessageType::Ping, 0, &[3; 8])).await.unwrap();
+        let pong = protocol::read_message(&mut app).await.unwrap();
+        assert_eq!(pong.header.message_type, MessageType::Pong);
+        app.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        backend.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_video_pipe_streams_decodable_frames() {
+        let token = test_token();
+        let (mut app, backend) = mock_connection(&token, mock_backend::serve_video);
+        auth::authenticate(&mut app, &token).await.unwrap();
+
+        let message = protocol::read_message(&mut app).await.unwrap();
+        assert_eq!(message.header.message_type, MessageType::VideoFrame);
+        let header = VideoHeader::decode(&message.payload).unwrap();
+        assert_eq!((header.stream_id, header.codec), (0, video::ImageCodec::Png));
+        let image = video::decode(header.codec, &message.payload[VIDEO_HEADER_SIZE..]).unwrap();
+        assert_eq!(image.pixels.len(), image.width as usize * image.height as usize * 4);
+        app.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        backend.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn the_pointer_pipe_sends_pointer_events_inside_the_overlay() {
+        let token = test_token();
+        let (mut app, backend) = mock_connection(&token, mock_backend::serve_pointer);
+        auth::authenticate(&mut app, &token).await.unwrap();
+
+        // The first sweep step clicks: a move, then the trigger going down and up
+        let mut kinds = Vec::new();
+        for _ in 0..3 {
+            let message = protocol::read_message(&mut app).await.unwrap();
+            assert_eq!(message.header.message_type, MessageType::VrPointer);
+            let event = vr_pointer::decode_pointer(&message.payload).unwrap();
+            assert_eq!((event.overlay_id, event.device.as_str()), (0, "controller-right"));
+            assert!((0.0..=1.0).contains(&event.x) && (0.0..=1.0).contains(&event.y));
+            kinds.push(event.kind);
+        }
+        assert_eq!(kinds, [vr_pointer::PointerKind::Move, vr_pointer::PointerKind::Down, vr_pointer::PointerKind::Up]);
+        app.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        backend.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn files_are_accepted_and_verified_by_the_backend() {
+        let token = test_token();
+        let (mut app, backend) = mock_connection(&token, mock_backend::serve_files);
+        auth::authenticate(&mut app, &token).await.unwrap();
+
+        let transfers = FileTransfers::default();
+        let offer = transfers.send("avatar.vrm", Bytes::from_static(b"not really an avatar")).unwrap();
+        app.write_all(&protocol::encode(MessageType::FileOffer, 0, &offer.encode())).await.unwrap();
+        let accept = protocol::read_message(&mut app).await.unwrap();
+        assert_eq!(accept.header.message_type, MessageType::FileAccept);
+        let (id, offset) = file_transfer::decode_position(&accept.payload).unwrap();
+        assert!(transfers.accept(id, offset));
+        let (chunk, _, _) = transfers.chunk(id, offset).unwrap();
+        write_message(&mut app, MessageType::FileChunk, 0, [&file_transfer::encode_position(id, offset), &chunk]).await.unwrap();
+        let done = protocol::read_message(&mut app).await.unwrap();
+        assert_eq!(file_transfer::decode_done(&done.payload), Some((offer.id, FileStatus::Verified)));
+        app.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        backend.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_backend_that_went_away_can_be_reconnected() {
+        let token = test_token();
+        let slot = TokioMutex::new(None);
+        let generation = Generation::default();
+
+        let first = generation.advance();
+        let (app, backend) = mock_connection(&token, mock_backend::serve_frames);
+        let (reader, writer, _) = start_frame_session(app, &token).await.unwrap();
+        *lock_if_current(&slot, &generation, first).await.unwrap() = Some(writer);
+
+        // The backend dies mid-session: the reader notices, and the writer is torn down for the reconnect
+        backend.abort();
+        let reason = read_frame_pipe(reader, &Liveness::default(), |_| {}).await;
+        assert_eq!(reason, "Connection closed by backend");
+        let mut dead = slot.lock().await.take().unwrap();
+        let (header, pixels) = test_frame(1);
+        assert!(write_message(&mut dead, MessageType::Frame, 0, [&header.encode()[..], &pixels]).await.is_err());
+
+        // The next connection loop finds a new backend and streams to it
+        let second = generation.advance();
+        let (app, backend) = mock_connection(&token, mock_backend::serve_frames);
+        let (reader, writer, info) = start_frame_session(app, &token).await.unwrap();
+        assert!(lock_if_current(&slot, &generation, first).await.is_none());
+        *lock_if_current(&slot, &generation, second).await.unwrap() = Some(writer);
+        let mut guard = slot.lock().await;
+        let writer = guard.as_mut().unwrap();
+        let (header, pixels) = test_frame(2);
+        let encoded = header.encode();
+        write_message(writer, MessageType::Frame, 0, [&encoded[..FrameHeader::size(info.protocol_version)], &pixels]).await.unwrap();
+        writer.write_all(&protocol::encode(MessageType::Goodbye, 0, &[])).await.unwrap();
+        drop(guard);
+        assert_eq!(read_frame_pipe(reader, &Liveness::default(), |_| {}).await, "Connection closed by backend");
+        backend.await.unwrap().unwrap();
+    }
+}
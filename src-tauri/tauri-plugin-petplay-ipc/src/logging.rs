@@ -0,0 +1,126 @@
+// --- Logging ---
+// Everything goes through tracing, to stdout and to a daily rotating file in
+// the app log directory (release builds have no console to read). The level
+// comes from logLevel in puppyweb.toml, with per-module overrides under
+// [logModules], e.g.
+//
+//   logLevel = "info"
+//
+//   [logModules]
+//   heartbeat = "debug"
+//
+// set_log_level changes either at runtime. Other crates (tauri, tokio) only
+// log warnings and errors.
+use crate::{config::LogLevel, error::PipeError};
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, path::Path};
+use tracing::warn;
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+
+pub const LOG_FILE_PREFIX: &str = "puppyweb";
+pub const MAX_LOG_FILES: usize = 7;
+// Target prefix of everything logged from this crate
+const CRATE_TARGET: &str = env!("CARGO_CRATE_NAME");
+
+#[derive(Clone, Default)]
+struct Levels {
+    default: LogLevel,
+    // Module name (e.g. "heartbeat") -> level
+    modules: BTreeMap<String, LogLevel>,
+}
+
+impl Levels {
+    fn filter(&self) -> EnvFilter {
+        let mut directives = format!("warn,{}={}", CRATE_TARGET, self.default.as_str());
+        for (module, level) in &self.modules {
+            directives.push_str(&format!(",{}::{}={}", CRATE_TARGET, module, level.as_str()));
+        }
+        EnvFilter::new(directives)
+    }
+}
+
+// Managed as Tauri state
+pub struct Logging {
+    filter: reload::Handle<EnvFilter, Registry>,
+    levels: Mutex<Levels>,
+    // Flushes the log file when dropped, so it has to live as long as the app
+    _file_guard: Option<WorkerGuard>,
+}
+
+impl Logging {
+    // Install the global subscriber at the default level; without a log dir only stdout is used
+    pub fn init(log_dir: Option<&Path>) -> Self {
+        let levels = Levels::default();
+        let (filter_layer, filter) = reload::Layer::new(levels.filter());
+        let appender = log_dir.map(|dir| {
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .filename_suffix("log")
+                .max_log_files(MAX_LOG_FILES)
+                .build(dir)
+        });
+        let (file_layer, file_guard, file_error) = match appender {
+            Some(Ok(appender)) => {
+                let (writer, guard) = tracing_appender::non_blocking(appender);
+                (Some(fmt::layer().with_ansi(false).with_writer(writer)), Some(guard), None)
+            }
+            Some(Err(e)) => (None, None, Some(e)),
+            None => (None, None, None),
+        };
+        let installed = tracing_subscriber::registry()
+            .with(filter_layer)
+            .with(fmt::layer())
+            .with(file_layer)
+            .try_init();
+        if let Err(e) = installed {
+            warn!("[Rust Logging] Logging was already set up: {}", e);
+        }
+        if let Some(e) = file_error {
+            warn!("[Rust Logging] Can't write log files, logging to stdout only: {}", e);
+        }
+        Self { filter, levels: Mutex::new(levels), _file_guard: file_guard }
+    }
+
+    // Apply the levels from the config file
+    pub fn configure(&self, default: LogLevel, modules: &BTreeMap<String, LogLevel>) -> Result<(), PipeError> {
+        if let Some(module) = modules.keys().find(|module| !is_module_name(module)) {
+            return Err(invalid_module(module));
+        }
+        self.apply(Levels { default, modules: modules.clone() })
+    }
+
+    // Change the crate-wide level, or one module's level if `module` is given
+    pub fn set_level(&self, level: LogLevel, module: Option<&str>) -> Result<(), PipeError> {
+        let mut levels = self.levels.lock().clone();
+        match module {
+            Some(module) if !is_module_name(module) => return Err(invalid_module(module)),
+            Some(module) => {
+                levels.modules.insert(module.to_string(), level);
+            }
+            None => levels.default = level,
+        }
+        self.apply(levels)
+    }
+
+    fn apply(&self, levels: Levels) -> Result<(), PipeError> {
+        self.filter
+            .reload(levels.filter())
+            .map_err(|e| PipeError::Unsupported(format!("Can't change the log level: {}", e)))?;
+        *self.levels.lock() = levels;
+        Ok(())
+    }
+}
+
+// Module names end up in a filter directive, so only allow what a Rust path segment can contain
+fn is_module_name(module: &str) -> bool {
+    !module.is_empty() && module.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn invalid_module(module: &str) -> PipeError {
+    PipeError::InvalidArgument(format!("Invalid module name {:?}", module))
+}
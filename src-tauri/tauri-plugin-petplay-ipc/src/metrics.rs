@@ -0,0 +1,174 @@
+// --- Pipe metrics ---
+// Lock-free counters updated from the frame writer and transform listener,
+// read by get_pipe_metrics. Rates (FPS, bytes/sec) are computed by a sampler
+// task once per interval, which can also push them to the frontend as
+// "pipe-stats" events. Per-stage frame latency is kept in histograms
+// (latency.rs) next to the counters.
+use crate::{buffer_pool::BufferPoolStats, latency::FrameLatency};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+pub const DEFAULT_SAMPLE_INTERVAL_MS: u64 = 1000;
+
+pub struct PipeMetrics {
+    frames_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    // Sum of write durations, divided by frames_sent for the average
+    total_write_us: AtomicU64,
+    transforms_received: AtomicU64,
+    // Frames send_frame_data refused because their size didn't add up
+    frames_rejected: AtomicU64,
+    // Invokes refused by the command limits (see rate_limit.rs)
+    rate_limited: AtomicU64,
+    payloads_too_large: AtomicU64,
+    last_sequence: AtomicU64,
+    // Time from the frame reaching Rust until its pipe write completed
+    last_latency_us: AtomicU64,
+    rates: Mutex<RateWindow>,
+    pub latency: FrameLatency,
+}
+
+// Counter values at the previous sample, used to turn totals into rates
+struct RateWindow {
+    sampled_at: Instant,
+    frames_sent: u64,
+    bytes_sent: u64,
+    transforms_received: u64,
+    fps: f64,
+    bytes_per_sec: f64,
+    transforms_per_sec: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipeMetricsSnapshot {
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    pub frames_dropped: u64,
+    // Frames replaced by a newer one while held back by the FPS limit (see frame_rate.rs)
+    pub frames_coalesced: u64,
+    pub frames_rejected: u64,
+    pub rate_limited: u64,
+    pub payloads_too_large: u64,
+    pub transforms_received: u64,
+    // Frames actually written per second, after the FPS limit
+    pub fps: f64,
+    // 0 when frames aren't limited
+    pub target_fps: u32,
+    pub bytes_per_sec: f64,
+    pub transforms_per_sec: f64,
+    pub avg_write_us: u64,
+    pub last_sequence: u64,
+    pub last_latency_us: u64,
+    // Frame buffers reused vs. allocated (see buffer_pool.rs)
+    pub buffer_pool: BufferPoolStats,
+}
+
+impl Default for PipeMetrics {
+    fn default() -> Self {
+        Self {
+            frames_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            total_write_us: AtomicU64::new(0),
+            transforms_received: AtomicU64::new(0),
+            frames_rejected: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            payloads_too_large: AtomicU64::new(0),
+            last_sequence: AtomicU64::new(0),
+            last_latency_us: AtomicU64::new(0),
+            rates: Mutex::new(RateWindow {
+                sampled_at: Instant::now(),
+                frames_sent: 0,
+                bytes_sent: 0,
+                transforms_received: 0,
+                fps: 0.0,
+                bytes_per_sec: 0.0,
+                transforms_per_sec: 0.0,
+            }),
+            latency: FrameLatency::default(),
+        }
+    }
+}
+
+impl PipeMetrics {
+    // Record a frame whose write to the pipe just completed
+    pub fn record_frame_sent(&self, sequence: u64, bytes: usize, write_us: u64, latency_us: u64) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.total_write_us.fetch_add(write_us, Ordering::Relaxed);
+        self.last_sequence.store(sequence, Ordering::Relaxed);
+        self.last_latency_us.store(latency_us, Ordering::Relaxed);
+        self.latency.write.record(write_us);
+        self.latency.total.record(latency_us);
+    }
+
+    pub fn record_frame_rejected(&self) {
+        self.frames_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_payload_too_large(&self) {
+        self.payloads_too_large.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_transform_received(&self) {
+        self.transforms_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Recompute the rates from the counters' change since the previous sample
+    pub fn sample_rates(&self) {
+        let frames_sent = self.frames_sent.load(Ordering::Relaxed);
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let transforms_received = self.transforms_received.load(Ordering::Relaxed);
+        let mut rates = self.rates.lock();
+        let elapsed = rates.sampled_at.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+        rates.fps = (frames_sent - rates.frames_sent) as f64 / elapsed;
+        rates.bytes_per_sec = (bytes_sent - rates.bytes_sent) as f64 / elapsed;
+        rates.transforms_per_sec = (transforms_received - rates.transforms_received) as f64 / elapsed;
+        rates.sampled_at = Instant::now();
+        rates.frames_sent = frames_sent;
+        rates.bytes_sent = bytes_sent;
+        rates.transforms_received = transforms_received;
+    }
+
+    // frames_dropped is owned by the frame queue and the FPS limit numbers by the limiter, so the caller passes them in
+    pub fn snapshot(
+        &self,
+        frames_dropped: u64,
+        frames_coalesced: u64,
+        target_fps: u32,
+        buffer_pool: BufferPoolStats,
+    ) -> PipeMetricsSnapshot {
+        let frames_sent = self.frames_sent.load(Ordering::Relaxed);
+        let total_write_us = self.total_write_us.load(Ordering::Relaxed);
+        let rates = self.rates.lock();
+        PipeMetricsSnapshot {
+            frames_sent,
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            frames_dropped,
+            frames_coalesced,
+            frames_rejected: self.frames_rejected.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            payloads_too_large: self.payloads_too_large.load(Ordering::Relaxed),
+            transforms_received: self.transforms_received.load(Ordering::Relaxed),
+            fps: rates.fps,
+            target_fps,
+            bytes_per_sec: rates.bytes_per_sec,
+            transforms_per_sec: rates.transforms_per_sec,
+            avg_write_us: total_write_us.checked_div(frames_sent).unwrap_or(0),
+            last_sequence: self.last_sequence.load(Ordering::Relaxed),
+            last_latency_us: self.last_latency_us.load(Ordering::Relaxed),
+            buffer_pool,
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// --- Metrics endpoint ---
+// Serves the pipe metrics in the Prometheus text format on
+// http://127.0.0.1:<port>/metrics, so long sessions can be charted in
+// Grafana. It's off unless [metricsEndpoint] enabled = true, and only ever
+// bound to localhost. Every scrape reads the same counters get_pipe_metrics,
+// get_latency_histogram and get_connection_status do, for the main pipes and
+// each named connection (with a connection="<name>" label):
+//
+//   puppyweb_frames_sent_total, _frame_bytes_sent_total, _frames_dropped_total, ...   counters
+//   puppyweb_frames_per_second, _target_fps, _buffer_pool_retained_bytes, ...         gauges
+//   puppyweb_pipe_connected{pipe}, puppyweb_pipe_connects_total{pipe}                 per pipe; connects beyond the first are reconnects
+//   puppyweb_frame_latency_seconds{stage}                                              histogram of the latency.rs stages
+//
+// The server is just enough HTTP for a scraper: one GET per connection,
+// answered and closed.
+use crate::{
+    connection::{ConnectionStatus, PipeStatus},
+    latency::{FrameLatencySnapshot, HistogramSnapshot},
+    metrics::PipeMetricsSnapshot,
+    runtime::IpcRuntime,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, error, info};
+
+// The port commonly used by Prometheus exporters of OpenTelemetry metrics
+pub const DEFAULT_METRICS_PORT: u16 = 9464;
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MetricsEndpointConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for MetricsEndpointConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: DEFAULT_METRICS_PORT }
+    }
+}
+
+impl MetricsEndpointConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.port == 0 {
+            return Err("Metrics endpoint port must not be 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+// What one scrape reports for a connection
+pub struct PipeSample {
+    // None for the main pipes
+    pub connection: Option<String>,
+    pub metrics: PipeMetricsSnapshot,
+    pub latency: FrameLatencySnapshot,
+    pub status: ConnectionStatus,
+}
+
+type Gauge = fn(&PipeSample) -> f64;
+
+const COUNTERS: &[(&str, &str, Gauge)] = &[
+    ("frames_sent_total", "Frames written to the frame pipe.", |s| s.metrics.frames_sent as f64),
+    ("frame_bytes_sent_total", "Frame bytes written to the frame pipe.", |s| s.metrics.bytes_sent as f64),
+    ("frames_dropped_total", "Frames dropped by the queue's backpressure policy.", |s| s.metrics.frames_dropped as f64),
+    ("frames_coalesced_total", "Frames replaced by a newer one under the FPS limit.", |s| s.metrics.frames_coalesced as f64),
+    ("frames_rejected_total", "Frames refused because their size didn't add up.", |s| s.metrics.frames_rejected as f64),
+    ("commands_rate_limited_total", "Commands refused by the command limits.", |s| s.metrics.rate_limited as f64),
+    ("payloads_too_large_total", "Commands refused for their payload size.", |s| s.metrics.payloads_too_large as f64),
+    ("transforms_received_total", "Transforms received from the backend.", |s| s.metrics.transforms_received as f64),
+    ("buffer_pool_hits_total", "Frame buffers reused from the pool.", |s| s.metrics.buffer_pool.hits as f64),
+    ("buffer_pool_misses_total", "Frame buffers that had to be allocated.", |s| s.metrics.buffer_pool.misses as f64),
+];
+
+const GAUGES: &[(&str, &str, Gauge)] = &[
+    ("frames_per_second", "Frames written per second.", |s| s.metrics.fps),
+    ("target_fps", "Frame rate limit, 0 when frames aren't limited.", |s| s.metrics.target_fps as f64),
+    ("frame_bytes_per_second", "Frame bytes written per second.", |s| s.metrics.bytes_per_sec),
+    ("transforms_per_second", "Transforms received per second.", |s| s.metrics.transforms_per_sec),
+    ("last_frame_latency_seconds", "Time from the last frame reaching Rust to its write completing.", |s| {
+        s.metrics.last_latency_us as f64 / 1e6
+    }),
+    ("buffer_pool_retained_bytes", "Bytes of frame buffers waiting to be reused.", |s| s.metrics.buffer_pool.retained_bytes as f64),
+];
+
+// Label set `{name="value",...}`, or nothing if there are no labels
+fn labels(pairs: &[(&str, &str)]) -> String {
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let escape = |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+    let pairs: Vec<String> = pairs.iter().map(|(name, value)| format!("{}=\"{}\"", name, escape(value))).collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+fn connection_label(sample: &PipeSample) -> Vec<(&'static str, &str)> {
+    sample.connection.as_deref().map(|name| ("connection", name)).into_iter().collect()
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP puppyweb_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE puppyweb_{} {}", name, kind);
+}
+
+fn histogram(out: &mut String, name: &str, base_labels: &[(&str, &str)], histogram: &HistogramSnapshot) {
+    let mut cumulative = 0;
+    for bucket in &histogram.buckets {
+        cumulative += bucket.count;
+        let le = bucket.le_us.map_or("+Inf".to_string(), |le_us| (le_us as f64 / 1e6).to_string());
+        let labels = labels(&[base_labels, &[("le", le.as_str())]].concat());
+        let _ = writeln!(out, "puppyweb_{}_bucket{} {}", name, labels, cumulative);
+    }
+    let labels = labels(base_labels);
+    let _ = writeln!(out, "puppyweb_{}_sum{} {}", name, labels, histogram.sum_us as f64 / 1e6);
+    let _ = writeln!(out, "puppyweb_{}_count{} {}", name, labels, histogram.count);
+}
+
+// The pipes a sample reports; named connections only have a frame pipe
+fn pipes(status: &ConnectionStatus) -> impl Iterator<Item = (&'static str, &PipeStatus)> {
+    [("frame", &status.frame), ("transform", &status.transform), ("input", &status.input), ("audio", &status.audio), ("microphone", &status.microphone), ("video", &status.video), ("files", &status.files), ("pointer", &status.pointer)]
+        .into_iter()
+        .filter(|(_, pipe)| !pipe.path.is_empty())
+}
+
+// The Prometheus text exposition of `samples`
+pub fn render(samples: &[PipeSample]) -> String {
+    let mut out = String::new();
+    for (kinds, table) in [("counter", COUNTERS), ("gauge", GAUGES)] {
+        for (name, help, value) in table {
+            family(&mut out, name, kinds, help);
+            for sample in samples {
+                let _ = writeln!(out, "puppyweb_{}{} {}", name, labels(&connection_label(sample)), value(sample));
+            }
+        }
+    }
+
+    family(&mut out, "pipe_connected", "gauge", "1 while the pipe is connected.");
+    for sample in samples {
+        for (pipe, status) in pipes(&sample.status) {
+            let labels = labels(&[&connection_label(sample)[..], &[("pipe", pipe)]].concat());
+            let _ = writeln!(out, "puppyweb_pipe_connected{} {}", labels, u8::from(status.connected));
+        }
+    }
+    family(&mut out, "pipe_connects_total", "counter", "Successful connects of the pipe; all but the first are reconnects.");
+    for sample in samples {
+        for (pipe, status) in pipes(&sample.status) {
+            let labels = labels(&[&connection_label(sample)[..], &[("pipe", pipe)]].concat());
+            let _ = writeln!(out, "puppyweb_pipe_connects_total{} {}", labels, status.connects);
+        }
+    }
+
+    family(&mut out, "frame_latency_seconds", "histogram", "Frame latency by stage, see get_latency_histogram.");
+    for sample in samples {
+        let latency = &sample.latency;
+        let stages = [
+            ("ipc", &latency.ipc),
+            ("enqueue", &latency.enqueue),
+            ("queued", &latency.queued),
+            ("write", &latency.write),
+            ("total", &latency.total),
+        ];
+        for (stage, snapshot) in stages {
+            histogram(&mut out, "frame_latency_seconds", &[&connection_label(sample)[..], &[("stage", stage)]].concat(), snapshot);
+        }
+    }
+    out
+}
+
+// Start serving on localhost:`config.port`; `scrape` collects the samples for each request
+pub fn spawn(
+    rt: &IpcRuntime,
+    config: &MetricsEndpointConfig,
+    scrape: impl Fn() -> Vec<PipeSample> + Send + Sync + 'static,
+) -> io::Result<()> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, config.port));
+    let listener = std::net::TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let listener = {
+        let _runtime = rt.enter();
+        TcpListener::from_std(listener)?
+    };
+    info!("[Rust Metrics] Serving metrics on http://{}/metrics.", address);
+    let scrape = Arc::new(scrape);
+    rt.spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let scrape = Arc::clone(&scrape);
+                    tokio::spawn(async move {
+                        if let Err(e) = serve(stream, || render(&scrape())).await {
+                            debug!("[Rust Metrics] Request from {} failed: {}", peer, e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("[Rust Metrics] Error accepting a connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// Answer one request on `stream`
+async fn serve(mut stream: TcpStream, body: impl FnOnce() -> String) -> io::Result<()> {
+    let mut request = Vec::new();
+    let read_head = async {
+        let mut chunk = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            if request.len() > MAX_REQUEST_BYTES {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "request header too large"));
+            }
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            request.extend_from_slice(&chunk[..read]);
+        }
+        Ok(())
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT, read_head)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no request in time"))??;
+
+    let line = String::from_utf8_lossy(request.split(|byte| *byte == b'\n').next().unwrap_or_default());
+    let mut parts = line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let path = target.split('?').next().unwrap_or_default();
+    let (status, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", body()),
+        ("GET", _) => ("404 Not Found", "Metrics are at /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", "Only GET is supported\n".to_string()),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        CONTENT_TYPE,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latency::Histogram;
+
+    #[test]
+    fn histogram_buckets_are_cumulative_and_in_seconds() {
+        let latency = Histogram::default();
+        for value_us in [40, 90, 90, 2_000_000] {
+            latency.record(value_us);
+        }
+        let mut out = String::new();
+        histogram(&mut out, "frame_latency_seconds", &[("stage", "write")], &latency.snapshot());
+
+        assert!(out.contains("puppyweb_frame_latency_seconds_bucket{stage=\"write\",le=\"0.00005\"} 1\n"));
+        assert!(out.contains("puppyweb_frame_latency_seconds_bucket{stage=\"write\",le=\"0.0001\"} 3\n"));
+        assert!(out.contains("puppyweb_frame_latency_seconds_bucket{stage=\"write\",le=\"+Inf\"} 4\n"));
+        assert!(out.contains("puppyweb_frame_latency_seconds_sum{stage=\"write\"} 2.00022\n"));
+        assert!(out.contains("puppyweb_frame_latency_seconds_count{stage=\"write\"} 4\n"));
+    }
+}
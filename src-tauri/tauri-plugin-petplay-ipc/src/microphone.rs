@@ -0,0 +1,403 @@
+// --- Microphone capture ---
+// Forwards a microphone to the backend, e.g. for voice chat from inside VR.
+// list_audio_inputs names the capture devices; start_microphone opens one
+// (the system default unless an id is given) and stop_microphone closes it.
+// Capture is shared-mode WASAPI on a thread of its own, with Windows
+// converting to f32 at the device's rate (mono or stereo), one chunk per
+// device period of about 10 ms. Chunks go out as MicrophoneChunk messages on
+// the microphone pipe (petplay-ipc-mic), laid out like audio.rs's AudioChunk
+// with overlay id 0, through the same kind of queue: a stalled pipe drops old
+// sound rather than building up delay. While capturing, "microphone-level"
+// carries the peak and RMS level (0..1 of full scale) every LEVEL_INTERVAL
+// for a UI meter, and "microphone-stopped" says when capture ended on its own
+// (e.g. the device was unplugged).
+//
+// Windows only; elsewhere there are no inputs and start_microphone fails
+// with Unsupported.
+use crate::audio::{AudioFormat, SampleFormat};
+use serde::Serialize;
+use std::{io, time::Duration};
+
+pub const LEVEL_INTERVAL: Duration = Duration::from_millis(50);
+
+// An entry of list_audio_inputs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioInput {
+    // Endpoint id to pass to start_microphone
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+// Result of start_microphone
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MicrophoneInfo {
+    pub device: AudioInput,
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+// Payload of "microphone-level"
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MicrophoneLevel {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+// Peak and RMS over the samples measured since the last take()
+#[derive(Default)]
+pub struct LevelMeter {
+    peak: f32,
+    sum_squares: f64,
+    samples: u64,
+}
+
+impl LevelMeter {
+    pub fn measure(&mut self, format: AudioFormat, bytes: &[u8]) {
+        let size = format.sample_format.bytes_per_sample();
+        for sample in bytes.chunks_exact(size) {
+            let value = match format.sample_format {
+                SampleFormat::F32 => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+                SampleFormat::S16 => i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0,
+            };
+            let value = if value.is_finite() { value.abs().min(1.0) } else { 0.0 };
+            self.peak = self.peak.max(value);
+            self.sum_squares += (value * value) as f64;
+            self.samples += 1;
+        }
+    }
+
+    pub fn take(&mut self) -> MicrophoneLevel {
+        let level = MicrophoneLevel {
+            peak: self.peak,
+            rms: if self.samples == 0 { 0.0 } else { (self.sum_squares / self.samples as f64).sqrt() as f32 },
+        };
+        *self = Self::default();
+        level
+    }
+}
+
+pub fn list_inputs() -> io::Result<Vec<AudioInput>> {
+    platform::list_inputs()
+}
+
+// A running capture; it stops when dropped
+pub struct Microphone {
+    info: MicrophoneInfo,
+    _capture: platform::Capture,
+}
+
+impl Microphone {
+    // Open `device_id` (or the default input) and hand every captured chunk to `on_chunk` on the capture
+    // thread; `on_stopped` is called there if capture fails after it started
+    pub fn start(
+        device_id: Option<String>,
+        on_chunk: impl FnMut(AudioFormat, &[u8]) + Send + 'static,
+        on_stopped: impl FnOnce(io::Error) + Send + 'static,
+    ) -> io::Result<Self> {
+        let (info, capture) = platform::Capture::start(device_id, on_chunk, on_stopped)?;
+        Ok(Self { info, _capture: capture })
+    }
+
+    pub fn info(&self) -> &MicrophoneInfo {
+        &self.info
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{AudioInput, MicrophoneInfo};
+    use crate::audio::{AudioFormat, SampleFormat};
+    use std::{
+        io, ptr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+    };
+    use windows::{
+        core::{HSTRING, PCWSTR},
+        Win32::{
+            Devices::FunctionDiscovery::PKEY_Device_FriendlyName,
+            Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0},
+            Media::{
+                Audio::{
+                    eCapture, eConsole, IAudioCaptureClient, IAudioClient, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator,
+                    AUDCLNT_BUFFERFLAGS_SILENT, AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM,
+                    AUDCLNT_STREAMFLAGS_EVENTCALLBACK, AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY, DEVICE_STATE_ACTIVE, WAVEFORMATEX,
+                },
+                Multimedia::WAVE_FORMAT_IEEE_FLOAT,
+            },
+            System::{
+                Com::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ},
+                Threading::{CreateEventW, WaitForSingleObject},
+            },
+        },
+    };
+
+    // Shared-mode buffer; packets are taken off it every device period, so this only matters if the thread stalls
+    const BUFFER_DURATION_100NS: i64 = 2_000_000;
+    // How often the capture thread checks for stop while the device is silent
+    const WAIT_TIMEOUT_MS: u32 = 100;
+
+    // COM is initialized per thread; the commands run on threads that may already have picked another apartment
+    fn on_com_thread<T: Send + 'static>(f: impl FnOnce() -> io::Result<T> + Send + 'static) -> io::Result<T> {
+        thread::spawn(move || {
+            // SAFETY: paired with the CoUninitialize below, on this thread only
+            unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.ok().map_err(io::Error::other)?;
+            let result = f();
+            // SAFETY: COM was initialized above, and everything created since has been dropped by now
+            unsafe { CoUninitialize() };
+            result
+        })
+        .join()
+        .map_err(|_| io::Error::other("audio device thread panicked"))?
+    }
+
+    fn enumerator() -> io::Result<IMMDeviceEnumerator> {
+        // SAFETY: COM is initialized on the calling thread (see on_com_thread)
+        unsafe { CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL) }.map_err(io::Error::other)
+    }
+
+    fn device_id(device: &IMMDevice) -> io::Result<String> {
+        // SAFETY: GetId returns a CoTaskMemAlloc'ed string, copied and freed here
+        unsafe {
+            let id = device.GetId().map_err(io::Error::other)?;
+            let owned = id.to_string().map_err(io::Error::other);
+            CoTaskMemFree(Some(id.0 as *const _));
+            owned
+        }
+    }
+
+    fn describe(device: &IMMDevice, default_id: Option<&str>) -> io::Result<AudioInput> {
+        let id = device_id(device)?;
+        // SAFETY: the property store and its value are owned and released by their wrappers
+        let name = unsafe {
+            let store = device.OpenPropertyStore(STGM_READ).map_err(io::Error::other)?;
+            store.GetValue(&PKEY_Device_FriendlyName).map_err(io::Error::other)?.to_string()
+        };
+        let is_default = default_id == Some(id.as_str());
+        Ok(AudioInput { name: if name.is_empty() { id.clone() } else { name }, id, is_default })
+    }
+
+    fn default_id(enumerator: &IMMDeviceEnumerator) -> Option<String> {
+        // SAFETY: plain COM call; fails when there's no input at all
+        let device = unsafe { enumerator.GetDefaultAudioEndpoint(eCapture, eConsole) }.ok()?;
+        device_id(&device).ok()
+    }
+
+    pub fn list_inputs() -> io::Result<Vec<AudioInput>> {
+        on_com_thread(|| {
+            let enumerator = enumerator()?;
+            let default_id = default_id(&enumerator);
+            // SAFETY: plain COM calls on objects owned by their wrappers
+            let devices = unsafe { enumerator.EnumAudioEndpoints(eCapture, DEVICE_STATE_ACTIVE) }.map_err(io::Error::other)?;
+            let count = unsafe { devices.GetCount() }.map_err(io::Error::other)?;
+            (0..count)
+                .map(|index| {
+                    let device = unsafe { devices.Item(index) }.map_err(io::Error::other)?;
+                    describe(&device, default_id.as_deref())
+                })
+                .collect()
+        })
+    }
+
+    // An opened and started capture client, with the event it signals
+    struct Stream {
+        client: IAudioClient,
+        capture: IAudioCaptureClient,
+        event: HANDLE,
+        format: AudioFormat,
+        block_align: usize,
+    }
+
+    impl Stream {
+        fn open(device: &IMMDevice) -> io::Result<Self> {
+            // SAFETY: the mix format is CoTaskMemAlloc'ed and freed right after reading it; the rest are COM calls
+            // on objects owned by their wrappers, and the event handle is closed by Drop
+            unsafe {
+                let client: IAudioClient = device.Activate(CLSCTX_ALL, None).map_err(io::Error::other)?;
+                let mix = client.GetMixFormat().map_err(io::Error::other)?;
+                let (sample_rate, mix_channels) = ((*mix).nSamplesPerSec, (*mix).nChannels);
+                CoTaskMemFree(Some(mix as *const _));
+                // Voice doesn't need more than stereo; AUTOCONVERTPCM mixes down and converts to f32
+                let channels = mix_channels.clamp(1, 2);
+                let block_align = channels * 4;
+                let wave = WAVEFORMATEX {
+                    wFormatTag: WAVE_FORMAT_IEEE_FLOAT as u16,
+                    nChannels: channels,
+                    nSamplesPerSec: sample_rate,
+                    nAvgBytesPerSec: sample_rate * block_align as u32,
+                    nBlockAlign: block_align,
+                    wBitsPerSample: 32,
+                    cbSize: 0,
+                };
+                let flags = AUDCLNT_STREAMFLAGS_EVENTCALLBACK | AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM | AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY;
+                client.Initialize(AUDCLNT_SHAREMODE_SHARED, flags, BUFFER_DURATION_100NS, 0, &wave, None).map_err(io::Error::other)?;
+                let event = CreateEventW(None, false, false, PCWSTR::null()).map_err(io::Error::other)?;
+                let stream = Self {
+                    capture: client.GetService().map_err(io::Error::other)?,
+                    client,
+                    event,
+                    format: AudioFormat { sample_rate, channels: channels as u8, sample_format: SampleFormat::F32 },
+                    block_align: block_align as usize,
+                };
+                stream.client.SetEventHandle(stream.event).map_err(io::Error::other)?;
+                stream.client.Start().map_err(io::Error::other)?;
+                Ok(stream)
+            }
+        }
+
+        // Hand every packet that's ready to `on_chunk`, after waiting up to WAIT_TIMEOUT_MS for one
+        fn drain(&self, silence: &mut Vec<u8>, on_chunk: &mut impl FnMut(AudioFormat, &[u8])) -> io::Result<()> {
+            // SAFETY: the event belongs to this stream; a buffer from GetBuffer is valid for `frames` frames
+            // until the matching ReleaseBuffer
+            unsafe {
+                if WaitForSingleObject(self.event, WAIT_TIMEOUT_MS) != WAIT_OBJECT_0 {
+                    return Ok(());
+                }
+                while self.capture.GetNextPacketSize().map_err(io::Error::other)? > 0 {
+                    let (mut data, mut frames, mut flags) = (ptr::null_mut(), 0u32, 0u32);
+                    self.capture.GetBuffer(&mut data, &mut frames, &mut flags, None, None).map_err(io::Error::other)?;
+                    let len = frames as usize * self.block_align;
+                    if flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 || data.is_null() {
+                        silence.clear();
+                        silence.resize(len, 0);
+                        on_chunk(self.format, silence);
+                    } else {
+                        on_chunk(self.format, std::slice::from_raw_parts(data, len));
+                    }
+                    self.capture.ReleaseBuffer(frames).map_err(io::Error::other)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Stream {
+        fn drop(&mut self) {
+            // SAFETY: stopping a started client and closing the event created in open
+            unsafe {
+                let _ = self.client.Stop();
+                let _ = CloseHandle(self.event);
+            }
+        }
+    }
+
+    pub struct Capture {
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Capture {
+        pub fn start(
+            device_id: Option<String>,
+            mut on_chunk: impl FnMut(AudioFormat, &[u8]) + Send + 'static,
+            on_stopped: impl FnOnce(io::Error) + Send + 'static,
+        ) -> io::Result<(MicrophoneInfo, Self)> {
+            let stop = Arc::new(AtomicBool::new(false));
+            let (opened, result) = mpsc::sync_channel(1);
+            let stopping = Arc::clone(&stop);
+            let thread = thread::Builder::new().name("puppyweb-microphone".to_string()).spawn(move || {
+                // SAFETY: paired with the CoUninitialize at the end of this thread
+                if let Err(e) = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.ok() {
+                    let _ = opened.send(Err(io::Error::other(e)));
+                    return;
+                }
+                let open = || {
+                    let enumerator = enumerator()?;
+                    let default_id = default_id(&enumerator);
+                    // SAFETY: plain COM calls; an unknown id fails with an error rather than a bad device
+                    let device = unsafe {
+                        match &device_id {
+                            Some(id) => enumerator.GetDevice(&HSTRING::from(id.as_str())),
+                            None => enumerator.GetDefaultAudioEndpoint(eCapture, eConsole),
+                        }
+                    }
+                    .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+                    let device_info = describe(&device, default_id.as_deref())?;
+                    let stream = Stream::open(&device)?;
+                    let info = MicrophoneInfo { device: device_info, sample_rate: stream.format.sample_rate, channels: stream.format.channels };
+                    io::Result::Ok((info, stream))
+                };
+                match open() {
+                    Ok((info, stream)) => {
+                        let _ = opened.send(Ok(info));
+                        let mut silence = Vec::new();
+                        while !stopping.load(Ordering::Acquire) {
+                            if let Err(e) = stream.drain(&mut silence, &mut on_chunk) {
+                                on_stopped(e);
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ = opened.send(Err(e));
+                    }
+                }
+                // SAFETY: the stream and every other COM object of this thread are gone by now
+                unsafe { CoUninitialize() };
+            })?;
+            let capture = Self { stop, thread: Some(thread) };
+            let info = result.recv().map_err(|_| io::Error::other("microphone thread ended before opening the device"))??;
+            Ok((info, capture))
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Release);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::{AudioInput, MicrophoneInfo};
+    use crate::audio::AudioFormat;
+    use std::io;
+
+    pub fn list_inputs() -> io::Result<Vec<AudioInput>> {
+        Ok(Vec::new())
+    }
+
+    // Can't be created, so there's never a capture to stop
+    pub enum Capture {}
+
+    impl Capture {
+        pub fn start(
+            _device_id: Option<String>,
+            _on_chunk: impl FnMut(AudioFormat, &[u8]) + Send + 'static,
+            _on_stopped: impl FnOnce(io::Error) + Send + 'static,
+        ) -> io::Result<(MicrophoneInfo, Self)> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "Microphone capture is only available on Windows"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_meter_reports_peak_and_rms_of_full_scale() {
+        let mut meter = LevelMeter::default();
+        let stereo = AudioFormat::default();
+        let samples: Vec<u8> = [0.5f32, -1.0, 0.5, -1.0].iter().flat_map(|sample| sample.to_le_bytes()).collect();
+        meter.measure(stereo, &samples);
+        let level = meter.take();
+        assert_eq!(level.peak, 1.0);
+        assert!((level.rms - (0.625f32).sqrt()).abs() < 1e-6);
+        assert_eq!(meter.take(), MicrophoneLevel::default());
+
+        let mono_s16 = AudioFormat { channels: 1, sample_format: SampleFormat::S16, ..stereo };
+        meter.measure(mono_s16, &i16::MIN.to_le_bytes());
+        assert_eq!(meter.take().peak, 1.0);
+    }
+}
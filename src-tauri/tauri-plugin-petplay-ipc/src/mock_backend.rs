@@ -0,0 +1,588 @@
+// --- Mock backend ---
+// Development mode for working on the web UI without SteamVR or the real
+// overlay backend: the app serves its own frame, transform, input, audio,
+// microphone, video, files and pointer pipes and plays the backend's part on them.
+// Frames are counted and dropped, haptic pulses and audio chunks are logged,
+// the transform pipe gets synthetic poses (a slowly turning headset with both
+// controllers circling it, hands opening and closing where the controllers
+// are), gaze glancing around in front of the headset, controller batteries
+// slowly draining and an octagonal play area, the video pipe a scrolling test pattern, the pointer pipe
+// a right controller ray circling the overlay and clicking every few seconds,
+// and files sent to the backend are verified and thrown away. Enabled with the --mock-backend command line flag or `mockBackend =
+// true` in puppyweb.toml. It checks the app's auth token and account like a
+// real backend would (see auth.rs and pipe_security.rs).
+//
+// The handlers take any byte stream once it's authenticated, so the tests in
+// lib.rs run them against the app's side over tokio::io::duplex.
+use crate::{
+    audio::AudioHeader,
+    auth::{self, AuthToken},
+    config::PipePaths,
+    device_status::{DeviceStatus, TrackingState},
+    file_transfer::{self, ChunkOutcome, FileOffer, FileStatus, FileTransfers, CHUNK_HEADER_SIZE},
+    gaze::{Gaze, OverlayHit},
+    hands::{Hand, HandSkeleton, Joint, OPENXR_JOINT_COUNT},
+    handshake::{CAP_DELTA, CAP_FORMAT_BGRA8, CAP_FORMAT_NV12, CAP_FORMAT_RGB8, CAP_HEARTBEAT, CAP_LZ4, CAP_MULTI_DEVICE, CAP_ROW_STRIDE, CAP_ZSTD},
+    pose::{self, Pose, DEVICE_CONTROLLER_LEFT, DEVICE_CONTROLLER_RIGHT, DEVICE_HMD, MIN_RECORD_SIZE},
+    pipe_security::PipeSecurity,
+    playspace::Playspace,
+    protocol::{self, FrameHeader, Message, MessageType, PROTOCOL_VERSION},
+    runtime::IpcRuntime,
+    transport::{PipeListener, PipeOptions, ServerTransport},
+    video::{ImageCodec, VideoHeader},
+    vr_pointer::POINTER_MESSAGE_SIZE,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use std::{
+    f32::consts::PI,
+    future::Future,
+    io,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::Mutex as TokioMutex,
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{error, info, warn};
+
+const MOCK_BACKEND_NAME: &str = "puppyweb mock backend";
+// Everything except GPU textures, which would need a real compositor
+const MOCK_CAPABILITIES: u32 = CAP_LZ4
+    | CAP_ZSTD
+    | CAP_HEARTBEAT
+    | CAP_MULTI_DEVICE
+    | CAP_FORMAT_BGRA8
+    | CAP_FORMAT_RGB8
+    | CAP_FORMAT_NV12
+    | CAP_ROW_STRIDE
+    | CAP_DELTA;
+const MOCK_POSE_RATE_HZ: u64 = 90;
+// Headset yaw speed, and how fast the controllers circle it (rad/s)
+const HMD_YAW_RATE: f32 = 0.5;
+const CONTROLLER_ORBIT_RATE: f32 = 1.0;
+const CONTROLLER_ORBIT_RADIUS: f32 = 0.4;
+// How far (rad) and how fast the mock gaze glances sideways, and how often and long it blinks (s)
+const GAZE_GLANCE_ANGLE: f32 = 0.3;
+const GAZE_GLANCE_RATE: f32 = 2.0;
+const BLINK_INTERVAL_S: f32 = 4.0;
+const BLINK_DURATION_S: f32 = 0.15;
+// How fast the mock hands open and close (rad/s)
+const HAND_CURL_RATE: f32 = 3.0;
+// How often the mock reports device status, and the controller batteries' charge at startup (%);
+// each report is a percent lower, so the right one runs low within a few minutes
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+const CONTROLLER_CHARGE: [u8; 2] = [80, 30];
+// Size of the mock room and how much of each corner is cut off (m)
+const PLAY_AREA: [f32; 2] = [3.0, 2.5];
+const PLAY_AREA_CORNER: f32 = 0.5;
+const FRAME_STATS_INTERVAL: Duration = Duration::from_secs(5);
+// Size and rate of the test pattern on the video pipe
+const MOCK_VIDEO_SIZE: u32 = 128;
+const MOCK_VIDEO_FPS: u64 = 10;
+// Rate of pointer moves, and how many of them between clicks
+const MOCK_POINTER_RATE_HZ: u64 = 30;
+const MOCK_CLICK_EVERY: u32 = 90;
+
+// Serve all the pipes on the runtime until the app exits
+pub fn spawn(rt: &IpcRuntime, paths: &PipePaths, options: &PipeOptions, security: &PipeSecurity, auth_token: &AuthToken) {
+    info!("[Rust Mock Backend] Serving mock backend pipes.");
+    let access = Access { security: security.clone(), auth_token: auth_token.clone() };
+    rt.spawn(serve(rt.clone(), "frame", paths.frame.clone(), *options, access.clone(), serve_frames));
+    rt.spawn(serve(rt.clone(), "transform", paths.transform.clone(), *options, access.clone(), serve_transforms));
+    rt.spawn(serve(rt.clone(), "input", paths.input.clone(), *options, access.clone(), serve_input));
+    rt.spawn(serve(rt.clone(), "audio", paths.audio.clone(), *options, access.clone(), serve_audio));
+    rt.spawn(serve(rt.clone(), "microphone", paths.microphone.clone(), *options, access.clone(), serve_audio));
+    rt.spawn(serve(rt.clone(), "video", paths.video.clone(), *options, access.clone(), serve_video));
+    rt.spawn(serve(rt.clone(), "files", paths.files.clone(), *options, access.clone(), serve_files));
+    rt.spawn(serve(rt.clone(), "pointer", paths.pointer.clone(), *options, access, serve_pointer));
+}
+
+// Who may connect to the mock pipes
+#[derive(Clone)]
+struct Access {
+    security: PipeSecurity,
+    auth_token: AuthToken,
+}
+
+// Accept clients on one pipe, handing each that is allowed in and authenticates to `handler`
+async fn serve<F, Fut>(rt: IpcRuntime, name: &'static str, path: String, options: PipeOptions, access: Access, handler: F)
+where
+    F: Fn(ServerTransport) -> Fut + Copy + Send + Sync + 'static,
+    Fut: Future<Output = io::Result<()>> + Send + 'static,
+{
+    let mut listener = match PipeListener::bind(&path, &options, &access.security) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("[Rust Mock Backend] Can't serve {} pipe {}: {}. Is a real backend running?", name, path, e);
+            return;
+        }
+    };
+    loop {
+        match listener.accept().await {
+            Ok(mut stream) => {
+                info!("[Rust Mock Backend] App connected to {} pipe.", name);
+                let access = access.clone();
+                rt.spawn(async move {
+                    let connection = async {
+                        access.security.verify_client(&stream)?;
+                        auth::accept(&mut stream, &access.auth_token).await?;
+                        handler(stream).await
+                    };
+                    match connection.await {
+                        Ok(()) => info!("[Rust Mock Backend] App closed {} pipe.", name),
+                        Err(e) => warn!("[Rust Mock Backend] {} pipe connection ended: {}", name, e),
+                    }
+                });
+            }
+            Err(e) => {
+                error!("[Rust Mock Backend] Error accepting on {} pipe: {}", name, e);
+                return;
+            }
+        }
+    }
+}
+
+// Answer the handshake, then swallow frames and answer pings
+pub async fn serve_frames<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let hello = read(&mut reader).await?;
+    if hello.header.message_type != MessageType::Hello || hello.payload.len() < 6 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected Hello"));
+    }
+    let capabilities = LittleEndian::read_u32(&hello.payload[..4]) & MOCK_CAPABILITIES;
+    let version = hello.payload[5].min(PROTOCOL_VERSION);
+    let mut ack = vec![0u8; 8];
+    LittleEndian::write_u32(&mut ack[..4], capabilities);
+    ack[4] = version;
+    LittleEndian::write_u16(&mut ack[6..8], MOCK_BACKEND_NAME.len() as u16);
+    ack.extend_from_slice(MOCK_BACKEND_NAME.as_bytes());
+    writer.write_all(&protocol::encode(MessageType::HelloAck, 0, &ack)).await?;
+
+    let mut frames = 0u64;
+    let mut bytes = 0u64;
+    let mut last_size = None;
+    let mut stats_since = Instant::now();
+    loop {
+        let message = read(&mut reader).await?;
+        match message.header.message_type {
+            MessageType::Ping => pong(&mut writer, &message).await?,
+            MessageType::Goodbye => return Ok(()),
+            MessageType::Frame | MessageType::FrameReady | MessageType::VideoChunk | MessageType::SharedTexture => {
+                frames += 1;
+                bytes += message.payload.len() as u64;
+                if message.header.message_type == MessageType::Frame {
+                    last_size = FrameHeader::decode(&message.payload, version).map(|header| (header.width, header.height));
+                }
+            }
+            MessageType::OverlayRegister | MessageType::OverlayUnregister => {
+                let id = message.payload.get(..4).map_or(0, LittleEndian::read_u32);
+                info!("[Rust Mock Backend] {:?} for overlay {}.", message.header.message_type, id);
+            }
+            other => warn!("[Rust Mock Backend] Ignoring {:?} on frame pipe.", other),
+        }
+        if stats_since.elapsed() >= FRAME_STATS_INTERVAL {
+            let seconds = stats_since.elapsed().as_secs_f64();
+            info!(
+                "[Rust Mock Backend] {:.1} frames/s, {:.1} MB/s{}",
+                frames as f64 / seconds,
+                bytes as f64 / seconds / 1_000_000.0,
+                last_size.map_or(String::new(), |(width, height)| format!(", last frame {}x{}", width, height))
+            );
+            frames = 0;
+            bytes = 0;
+            stats_since = Instant::now();
+        }
+    }
+}
+
+// Stream synthetic poses while answering pings
+pub async fn serve_transforms<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let writer = TokioMutex::new(writer);
+    let pings = async {
+        loop {
+            let message = read(&mut reader).await?;
+            match message.header.message_type {
+                MessageType::Ping => pong(&mut *writer.lock().await, &message).await?,
+                MessageType::Goodbye => return Ok(()),
+                other => warn!("[Rust Mock Backend] Ignoring {:?} on transform pipe.", other),
+            }
+        }
+    };
+    let poses = async {
+        let started = Instant::now();
+        let mut ticker = interval(Duration::from_micros(1_000_000 / MOCK_POSE_RATE_HZ));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        writer.lock().await.write_all(&protocol::encode(MessageType::Playspace, 0, &synthetic_playspace().encode())).await?;
+        let mut reports = 0;
+        loop {
+            if started.elapsed() >= STATUS_INTERVAL * reports {
+                let statuses = synthetic_statuses(reports).iter().map(DeviceStatus::encode_record).collect::<Vec<_>>().concat();
+                writer.lock().await.write_all(&protocol::encode(MessageType::DeviceStatus, 0, &statuses)).await?;
+                reports += 1;
+            }
+            ticker.tick().await;
+            let t = started.elapsed().as_secs_f32();
+            let payload = encode_poses(&synthetic_poses(t));
+            let mut writer = writer.lock().await;
+            writer.write_all(&protocol::encode(MessageType::Poses, 0, &payload)).await?;
+            writer.write_all(&protocol::encode(MessageType::Gaze, 0, &synthetic_gaze(t).encode())).await?;
+            let hands = [synthetic_hand(Hand::Left, t), synthetic_hand(Hand::Right, t)].map(|hand| hand.encode_record()).concat();
+            writer.write_all(&protocol::encode(MessageType::HandSkeleton, 0, &hands)).await?;
+        }
+    };
+    tokio::select! {
+        result = pings => result,
+        result = poses => result,
+    }
+}
+
+// Log haptic pulses and answer pings
+pub async fn serve_input<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    loop {
+        let message = read(&mut reader).await?;
+        match message.header.message_type {
+            MessageType::Ping => pong(&mut writer, &message).await?,
+            MessageType::Goodbye => return Ok(()),
+            MessageType::HapticPulse if message.payload.len() >= 12 => {
+                info!(
+                    "[Rust Mock Backend] Haptic pulse on device {}: {} us at {:.2}.",
+                    LittleEndian::read_u32(&message.payload[0..4]),
+                    LittleEndian::read_u32(&message.payload[4..8]),
+                    LittleEndian::read_f32(&message.payload[8..12])
+                );
+            }
+            other => warn!("[Rust Mock Backend] Ignoring {:?} on input pipe.", other),
+        }
+    }
+}
+
+// Count audio or microphone chunks and log their format now and then, answering pings
+pub async fn serve_audio<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut chunks = 0u64;
+    let mut frames = 0u64;
+    let mut last_format = None;
+    let mut stats_since = Instant::now();
+    loop {
+        let message = read(&mut reader).await?;
+        match message.header.message_type {
+            MessageType::Ping => pong(&mut writer, &message).await?,
+            MessageType::Goodbye => return Ok(()),
+            MessageType::AudioChunk | MessageType::MicrophoneChunk => match AudioHeader::decode(&message.payload) {
+                Some(header) => {
+                    chunks += 1;
+                    frames += header.frames as u64;
+                    last_format = Some(header.format);
+                }
+                None => warn!("[Rust Mock Backend] Ignoring malformed audio chunk."),
+            },
+            other => warn!("[Rust Mock Backend] Ignoring {:?} on an audio pipe.", other),
+        }
+        if stats_since.elapsed() >= FRAME_STATS_INTERVAL {
+            let seconds = stats_since.elapsed().as_secs_f64();
+            if let Some(format) = last_format {
+                info!(
+                    "[Rust Mock Backend] {:.1} audio chunks/s, {:.0} frames/s of {} Hz x{} {:?}",
+                    chunks as f64 / seconds,
+                    frames as f64 / seconds,
+                    format.sample_rate,
+                    format.channels,
+                    format.sample_format
+                );
+            }
+            chunks = 0;
+            frames = 0;
+            stats_since = Instant::now();
+        }
+    }
+}
+
+// Send a scrolling test pattern as PNG frames of stream 0 while answering pings
+pub async fn serve_video<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let writer = TokioMutex::new(writer);
+    let pings = async {
+        loop {
+            let message = read(&mut reader).await?;
+            match message.header.message_type {
+                MessageType::Ping => pong(&mut *writer.lock().await, &message).await?,
+                MessageType::Goodbye => return Ok(()),
+                other => warn!("[Rust Mock Backend] Ignoring {:?} on video pipe.", other),
+            }
+        }
+    };
+    let frames = async {
+        let mut ticker = interval(Duration::from_millis(1000 / MOCK_VIDEO_FPS));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        for offset in 0u32.. {
+            ticker.tick().await;
+            let header = VideoHeader { stream_id: 0, codec: ImageCodec::Png, timestamp_us: protocol::timestamp_us() };
+            let mut payload = header.encode().to_vec();
+            payload.extend_from_slice(&test_pattern(offset)?);
+            let mut writer = writer.lock().await;
+            writer.write_all(&protocol::encode(MessageType::VideoFrame, 0, &payload)).await?;
+        }
+        Ok(())
+    };
+    tokio::select! {
+        result = pings => result,
+        result = frames => result,
+    }
+}
+
+// Sweep the right controller's ray around overlay 0, clicking now and then, while answering pings
+pub async fn serve_pointer<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let writer = TokioMutex::new(writer);
+    let pings = async {
+        loop {
+            let message = read(&mut reader).await?;
+            match message.header.message_type {
+                MessageType::Ping => pong(&mut *writer.lock().await, &message).await?,
+                MessageType::Goodbye => return Ok(()),
+                other => warn!("[Rust Mock Backend] Ignoring {:?} on pointer pipe.", other),
+            }
+        }
+    };
+    let events = async {
+        let mut ticker = interval(Duration::from_millis(1000 / MOCK_POINTER_RATE_HZ));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        for step in 0u32.. {
+            ticker.tick().await;
+            let angle = step as f32 / MOCK_POINTER_RATE_HZ as f32;
+            let (x, y) = (0.5 + 0.3 * angle.cos(), 0.5 + 0.3 * angle.sin());
+            let mut messages = vec![pointer_event(0, x, y)];
+            if step.is_multiple_of(MOCK_CLICK_EVERY) {
+                messages.extend([pointer_event(1, x, y), pointer_event(2, x, y)]);
+            }
+            let mut writer = writer.lock().await;
+            for payload in messages {
+                writer.write_all(&protocol::encode(MessageType::VrPointer, 0, &payload)).await?;
+            }
+        }
+        Ok(())
+    };
+    tokio::select! {
+        result = pings => result,
+        result = events => result,
+    }
+}
+
+// A VrPointer payload of the right controller on overlay 0 (see vr_pointer.rs)
+fn pointer_event(kind: u8, x: f32, y: f32) -> [u8; POINTER_MESSAGE_SIZE] {
+    let mut payload = [0u8; POINTER_MESSAGE_SIZE];
+    LittleEndian::write_u32(&mut payload[4..8], DEVICE_CONTROLLER_RIGHT);
+    payload[8] = kind;
+    LittleEndian::write_f32(&mut payload[12..16], x);
+    LittleEndian::write_f32(&mut payload[16..20], y);
+    LittleEndian::write_u64(&mut payload[28..36], protocol::timestamp_us());
+    payload
+}
+
+// Receive the files the app sends, verifying them like the app does, and answer pings
+pub async fn serve_files<S: AsyncRead + AsyncWrite + Send>(stream: S) -> io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let transfers = FileTransfers::default();
+    loop {
+        let message = read(&mut reader).await?;
+        match message.header.message_type {
+            MessageType::Ping => pong(&mut writer, &message).await?,
+            MessageType::Goodbye => return Ok(()),
+            MessageType::FileOffer => {
+                let Some(offer) = FileOffer::decode(&message.payload) else {
+                    warn!("[Rust Mock Backend] Ignoring malformed file offer.");
+                    continue;
+                };
+                let id = offer.id;
+                let reply = match transfers.receive_offer(offer) {
+                    Ok(offset) => protocol::encode(MessageType::FileAccept, 0, &file_transfer::encode_position(id, offset)),
+                    Err(_) => protocol::encode(MessageType::FileDone, 0, &file_transfer::encode_done(id, FileStatus::Rejected)),
+                };
+                writer.write_all(&reply).await?;
+            }
+            MessageType::FileChunk => {
+                let Some((id, offset)) = file_transfer::decode_position(&message.payload) else {
+                    warn!("[Rust Mock Backend] Ignoring malformed file chunk.");
+                    continue;
+                };
+                match transfers.receive_chunk(id, offset, &message.payload[CHUNK_HEADER_SIZE..]) {
+                    Ok(ChunkOutcome::Progress(_)) => {}
+                    Ok(ChunkOutcome::Done { status, name, size }) => {
+                        info!("[Rust Mock Backend] Received {} ({} bytes): {:?}.", name, size, status);
+                        transfers.take_received(id);
+                        writer.write_all(&protocol::encode(MessageType::FileDone, 0, &file_transfer::encode_done(id, status))).await?;
+                    }
+                    Err(e) => warn!("[Rust Mock Backend] Ignoring file chunk: {}.", e),
+                }
+            }
+            other => warn!("[Rust Mock Backend] Ignoring {:?} on files pipe.", other),
+        }
+    }
+}
+
+// A diagonal RGB gradient shifted by `offset` pixels, as a PNG
+fn test_pattern(offset: u32) -> io::Result<Vec<u8>> {
+    let size = MOCK_VIDEO_SIZE;
+    let pixels: Vec<u8> = (0..size * size)
+        .flat_map(|i| {
+            let (x, y) = ((i % size + offset) % size, i / size);
+            [(x * 255 / size) as u8, (y * 255 / size) as u8, 160]
+        })
+        .collect();
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size, size);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().and_then(|mut writer| writer.write_image_data(&pixels)).map_err(io::Error::other)?;
+    Ok(png)
+}
+
+async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Message> {
+    protocol::read_message(reader).await.map_err(|e| match e {
+        protocol::ProtocolError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+    })
+}
+
+async fn pong<W: AsyncWrite + Unpin>(writer: &mut W, ping: &Message) -> io::Result<()> {
+    writer.write_all(&protocol::encode(MessageType::Pong, 0, &ping.payload)).await
+}
+
+// Headset at standing height turning in place, controllers circling it at opposite sides
+fn synthetic_poses(t: f32) -> [Pose; 3] {
+    let timestamp_us = protocol::timestamp_us();
+    let hmd_yaw = t * HMD_YAW_RATE;
+    let hmd = Pose {
+        device_id: DEVICE_HMD,
+        position: [0.0, 1.6, 0.0],
+        orientation: yaw_quaternion(hmd_yaw),
+        angular_velocity: [0.0, HMD_YAW_RATE, 0.0],
+        timestamp_us,
+        ..Pose::default()
+    };
+    let controller = |device_id, phase: f32| {
+        let angle = t * CONTROLLER_ORBIT_RATE + phase;
+        let speed = CONTROLLER_ORBIT_RATE * CONTROLLER_ORBIT_RADIUS;
+        Pose {
+            device_id,
+            position: [CONTROLLER_ORBIT_RADIUS * angle.cos(), 1.2, CONTROLLER_ORBIT_RADIUS * angle.sin()],
+            orientation: yaw_quaternion(-angle),
+            linear_velocity: [-speed * angle.sin(), 0.0, speed * angle.cos()],
+            angular_velocity: [0.0, -CONTROLLER_ORBIT_RATE, 0.0],
+            timestamp_us,
+        }
+    };
+    [hmd, controller(DEVICE_CONTROLLER_LEFT, PI), controller(DEVICE_CONTROLLER_RIGHT, 0.0)]
+}
+
+// A flat hand at the controller's place, fingers pointing along its forward axis and curling every couple of seconds
+fn synthetic_hand(hand: Hand, t: f32) -> HandSkeleton {
+    let phase = if hand == Hand::Left { PI } else { 0.0 };
+    let angle = t * CONTROLLER_ORBIT_RATE + phase;
+    let (yaw, wrist) = (-angle, [CONTROLLER_ORBIT_RADIUS * angle.cos(), 1.2, CONTROLLER_ORBIT_RADIUS * angle.sin()]);
+    let curl = 0.5 - 0.5 * (t * HAND_CURL_RATE).cos();
+    let side = if hand == Hand::Left { -1.0 } else { 1.0 };
+    // Offset from the wrist in the hand's own frame (x to the thumb side, -z forward) to tracking space
+    let joint = |[x, y, z]: [f32; 3], radius: f32| Joint {
+        position: [wrist[0] + x * yaw.cos() + z * yaw.sin(), wrist[1] + y, wrist[2] - x * yaw.sin() + z * yaw.cos()],
+        orientation: yaw_quaternion(yaw),
+        radius,
+    };
+    let mut joints = Vec::with_capacity(OPENXR_JOINT_COUNT);
+    // Palm, wrist
+    joints.extend([joint([0.0, 0.0, -0.05], 0.02), joint([0.0, 0.0, 0.0], 0.02)]);
+    // Thumb: metacarpal, proximal, distal, tip
+    joints.extend((0..4).map(|k| joint([side * (0.02 + 0.012 * k as f32), -0.01 * curl * k as f32, -0.02 - 0.02 * k as f32], 0.01)));
+    // Index to little finger: metacarpal, proximal, intermediate, distal, tip
+    for finger in 0..4 {
+        let x = side * (0.02 - 0.015 * finger as f32);
+        joints.extend((0..5).map(|k| joint([x, -0.015 * curl * k as f32, -0.03 - 0.025 * k as f32], 0.009 - 0.001 * k as f32)));
+    }
+    HandSkeleton { hand, tracked: true, timestamp_us: protocol::timestamp_us(), joints }
+}
+
+// Looking ahead of the headset with a side to side glance, blinking every few seconds. The glance
+// sweeps across overlay 0 as if it hung in front of the headset, wherever that is.
+// A 3 x 2.5 m room with the corners cut off, standing space lined up with tracking space
+fn synthetic_playspace() -> Playspace {
+    let (x, z, corner) = (PLAY_AREA[0] / 2.0, PLAY_AREA[1] / 2.0, PLAY_AREA_CORNER);
+    let mut standing_zero = [0.0; 16];
+    for diagonal in [0, 5, 10, 15] {
+        standing_zero[diagonal] = 1.0;
+    }
+    Playspace {
+        standing_zero,
+        play_area: [PLAY_AREA[0] - corner, PLAY_AREA[1] - corner],
+        bounds: vec![
+            [-x + corner, -z],
+            [x - corner, -z],
+            [x, -z + corner],
+            [x, z - corner],
+            [x - corner, z],
+            [-x + corner, z],
+            [-x, z - corner],
+            [-x, -z + corner],
+        ],
+    }
+}
+
+// The headset on external power and both controllers, `reports` percent below their starting charge
+fn synthetic_statuses(reports: u32) -> Vec<DeviceStatus> {
+    let status = |device_id: u32, battery_percent: Option<u8>| DeviceStatus {
+        device: pose::device_name(device_id).into_owned(),
+        device_id,
+        battery_percent,
+        charging: false,
+        connected: true,
+        tracking: TrackingState::Ok,
+        firmware: "mock 1.0".to_string(),
+    };
+    let charge = |start: u8| Some(start.saturating_sub(reports.min(u8::MAX as u32) as u8).max(1));
+    vec![
+        status(DEVICE_HMD, None),
+        status(DEVICE_CONTROLLER_LEFT, charge(CONTROLLER_CHARGE[0])),
+        status(DEVICE_CONTROLLER_RIGHT, charge(CONTROLLER_CHARGE[1])),
+    ]
+}
+
+fn synthetic_gaze(t: f32) -> Gaze {
+    let glance = (t * GAZE_GLANCE_RATE).sin();
+    let yaw = t * HMD_YAW_RATE + GAZE_GLANCE_ANGLE * glance;
+    let openness = if t % BLINK_INTERVAL_S < BLINK_DURATION_S { 0.0 } else { 1.0 };
+    Gaze {
+        origin: [0.0, 1.6, 0.0],
+        direction: [-yaw.sin(), 0.0, -yaw.cos()],
+        confidence: 0.9,
+        left_openness: openness,
+        right_openness: openness,
+        timestamp_us: protocol::timestamp_us(),
+        overlay_hit: Some(OverlayHit { overlay_id: 0, x: 0.5 + 0.4 * glance, y: 0.5 }),
+    }
+}
+
+// Rotation about +Y as an xyzw quaternion
+fn yaw_quaternion(yaw: f32) -> [f32; 4] {
+    let half = yaw / 2.0;
+    [0.0, half.sin(), 0.0, half.cos()]
+}
+
+fn encode_poses(poses: &[Pose]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(poses.len() * (2 + MIN_RECORD_SIZE));
+    for pose in poses {
+        let mut length = [0u8; 2];
+        LittleEndian::write_u16(&mut length, MIN_RECORD_SIZE as u16);
+        payload.extend_from_slice(&length);
+        payload.extend_from_slice(&pose.encode());
+    }
+    payload
+}
@@ -0,0 +1,271 @@
+// --- NDI output ---
+// Publishes outgoing frames as an NDI source, so production tools on the LAN
+// (OBS with obs-ndi, vMix, NDI Studio Monitor) can pick up the overlay. It
+// is a frame sink (sink.rs) beside the pipe, started from [ndi] in
+// puppyweb.toml or with enable_ndi_output ("ndi"), or added with add_sink.
+//
+// The NDI runtime isn't bundled; it's loaded when the output is enabled,
+// from $NDI_RUNTIME_DIR_V6/$NDI_RUNTIME_DIR_V5 or the system library path
+// (Processing.NDI.Lib.x64.dll, libndi.dylib, libndi.so). Frames go out as
+// RGBA with their resolution, the configured frame rate and the aspect
+// ratio set on each video frame, and only while a receiver is connected.
+//
+//   [ndi]
+//   enabled = true
+//   name = "PuppyWeb"
+//   frameRate = 90
+use crate::{
+    buffer_pool::BufferPool,
+    frame_queue::QueuedFrame,
+    pixel_format,
+    sink::{FrameSink, SinkFuture},
+};
+use libloading::Library;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::{c_char, c_int, c_void, CString},
+    io,
+    path::PathBuf,
+    ptr,
+    sync::Arc,
+};
+use tracing::{debug, error, info};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NdiConfig {
+    pub enabled: bool,
+    // Source name; receivers list it as "<MACHINE> (<name>)"
+    pub name: String,
+    // Comma separated NDI groups to announce the source in; empty for the default group
+    pub groups: String,
+    // Nominal frame rate receivers are told, in frames per second
+    pub frame_rate: u32,
+}
+
+impl Default for NdiConfig {
+    fn default() -> Self {
+        Self { enabled: false, name: "PuppyWeb".to_string(), groups: String::new(), frame_rate: 60 }
+    }
+}
+
+impl NdiConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains('\0') {
+            return Err("NDI source name must not be empty".to_string());
+        }
+        if self.groups.contains('\0') {
+            return Err("NDI groups must not contain NUL characters".to_string());
+        }
+        if self.frame_rate == 0 {
+            return Err("NDI frame rate must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+// --- NDI SDK types (Processing.NDI.Send.h / Processing.NDI.structs.h) ---
+#[repr(C)]
+struct SendCreate {
+    p_ndi_name: *const c_char,
+    p_groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+#[repr(C)]
+struct VideoFrameV2 {
+    xres: c_int,
+    yres: c_int,
+    four_cc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    picture_aspect_ratio: f32,
+    frame_format_type: c_int,
+    timecode: i64,
+    p_data: *const u8,
+    line_stride_in_bytes: c_int,
+    p_metadata: *const c_char,
+    timestamp: i64,
+}
+
+const FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+// NDIlib_send_timecode_synthesize: let the SDK stamp the frame
+const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+type InitializeFn = unsafe extern "C" fn() -> bool;
+type SendCreateFn = unsafe extern "C" fn(*const SendCreate) -> *mut c_void;
+type SendDestroyFn = unsafe extern "C" fn(*mut c_void);
+type SendVideoFn = unsafe extern "C" fn(*mut c_void, *const VideoFrameV2);
+type SendConnectionsFn = unsafe extern "C" fn(*mut c_void, u32) -> c_int;
+
+// The entry points used here, resolved from the runtime library
+struct Runtime {
+    send_create: SendCreateFn,
+    send_destroy: SendDestroyFn,
+    send_video: SendVideoFn,
+    send_connections: SendConnectionsFn,
+    // Keeps the function pointers above valid
+    _library: Library,
+}
+
+impl Runtime {
+    fn load() -> io::Result<Self> {
+        let mut last_error = None;
+        for candidate in library_candidates() {
+            // SAFETY: the NDI runtime has no initialisation side effects beyond NDIlib_initialize
+            match unsafe { Library::new(&candidate) } {
+                Ok(library) => return Self::resolve(library),
+                Err(e) => {
+                    debug!("[Rust NDI] Can't load {}: {}", candidate.display(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let detail = last_error.map(|e| e.to_string()).unwrap_or_default();
+        Err(io::Error::new(io::ErrorKind::Unsupported, format!("NDI runtime not found ({})", detail)))
+    }
+
+    fn resolve(library: Library) -> io::Result<Self> {
+        // SAFETY: the signatures match the declarations in the NDI SDK headers
+        unsafe {
+            let symbol_error = |e: libloading::Error| io::Error::new(io::ErrorKind::Unsupported, e.to_string());
+            let initialize = *library.get::<InitializeFn>(b"NDIlib_initialize\0").map_err(symbol_error)?;
+            let send_create = *library.get::<SendCreateFn>(b"NDIlib_send_create\0").map_err(symbol_error)?;
+            let send_destroy = *library.get::<SendDestroyFn>(b"NDIlib_send_destroy\0").map_err(symbol_error)?;
+            let send_video = *library.get::<SendVideoFn>(b"NDIlib_send_send_video_v2\0").map_err(symbol_error)?;
+            let send_connections =
+                *library.get::<SendConnectionsFn>(b"NDIlib_send_get_no_connections\0").map_err(symbol_error)?;
+            // Fails on CPUs the runtime doesn't support; safe to call more than once
+            if !initialize() {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "NDI isn't supported on this CPU"));
+            }
+            Ok(Self { send_create, send_destroy, send_video, send_connections, _library: library })
+        }
+    }
+}
+
+fn library_candidates() -> Vec<PathBuf> {
+    #[cfg(windows)]
+    const LIBRARY: &str = "Processing.NDI.Lib.x64.dll";
+    #[cfg(target_os = "macos")]
+    const LIBRARY: &str = "libndi.dylib";
+    #[cfg(all(unix, not(target_os = "macos")))]
+    const LIBRARY: &str = "libndi.so";
+
+    let mut candidates: Vec<PathBuf> = ["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|dir| PathBuf::from(dir).join(LIBRARY))
+        .collect();
+    #[cfg(target_os = "macos")]
+    candidates.push(PathBuf::from("/usr/local/lib").join(LIBRARY));
+    #[cfg(all(unix, not(target_os = "macos")))]
+    candidates.extend(["libndi.so.6", "libndi.so.5"].map(PathBuf::from));
+    candidates.push(PathBuf::from(LIBRARY));
+    candidates
+}
+
+struct Sender {
+    runtime: Runtime,
+    instance: *mut c_void,
+    frame_rate: u32,
+}
+
+// SAFETY: NDI send instances may be used from any thread, one call at a time (NdiOutput's mutex)
+unsafe impl Send for Sender {}
+
+impl Sender {
+    fn create(config: &NdiConfig) -> io::Result<Self> {
+        let runtime = Runtime::load()?;
+        let name = CString::new(config.name.as_str()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let groups = CString::new(config.groups.as_str()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let create = SendCreate {
+            p_ndi_name: name.as_ptr(),
+            p_groups: if config.groups.is_empty() { ptr::null() } else { groups.as_ptr() },
+            // Frames are paced by the renderer; clocking would block the writer task
+            clock_video: false,
+            clock_audio: false,
+        };
+        // SAFETY: `create` and the strings it points to outlive the call; the SDK copies them
+        let instance = unsafe { (runtime.send_create)(&create) };
+        if instance.is_null() {
+            return Err(io::Error::other("NDIlib_send_create failed"));
+        }
+        Ok(Self { runtime, instance, frame_rate: config.frame_rate })
+    }
+
+    fn has_receivers(&self) -> bool {
+        // SAFETY: `instance` is a live send instance; a timeout of 0 doesn't wait
+        unsafe { (self.runtime.send_connections)(self.instance, 0) > 0 }
+    }
+
+    // `pixels` is tightly packed RGBA
+    fn send(&self, width: u32, height: u32, pixels: &[u8]) {
+        let frame = VideoFrameV2 {
+            xres: width as c_int,
+            yres: height as c_int,
+            four_cc: FOURCC_RGBA,
+            frame_rate_n: self.frame_rate as c_int,
+            frame_rate_d: 1,
+            picture_aspect_ratio: width as f32 / height.max(1) as f32,
+            frame_format_type: FRAME_FORMAT_PROGRESSIVE,
+            timecode: TIMECODE_SYNTHESIZE,
+            p_data: pixels.as_ptr(),
+            line_stride_in_bytes: (width * 4) as c_int,
+            p_metadata: ptr::null(),
+            timestamp: 0,
+        };
+        // SAFETY: the synchronous send is done with `pixels` when it returns
+        unsafe { (self.runtime.send_video)(self.instance, &frame) };
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        // SAFETY: `instance` came from send_create and isn't used after this
+        unsafe { (self.runtime.send_destroy)(self.instance) };
+    }
+}
+
+// A running NDI source; it's torn down when dropped
+pub struct NdiOutput {
+    sender: Mutex<Sender>,
+    config: NdiConfig,
+    buffers: Arc<BufferPool>,
+}
+
+impl NdiOutput {
+    pub fn start(config: NdiConfig, buffers: Arc<BufferPool>) -> io::Result<Self> {
+        config.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let sender = Sender::create(&config)?;
+        info!("[Rust NDI] Publishing frames as NDI source {:?} at {} fps.", config.name, config.frame_rate);
+        Ok(Self { sender: Mutex::new(sender), config, buffers })
+    }
+}
+
+impl FrameSink for NdiOutput {
+    fn describe(&self) -> String {
+        format!("NDI source {:?}", self.config.name)
+    }
+
+    // Send `frame` to the connected receivers, if there are any
+    fn write<'a>(&'a self, frame: &'a QueuedFrame) -> SinkFuture<'a> {
+        Box::pin(async move {
+            let sender = self.sender.lock();
+            if !sender.has_receivers() {
+                return Ok(());
+            }
+            let converted = pixel_format::packed_rgba(frame, &self.buffers);
+            let frame = converted.as_ref().unwrap_or(frame);
+            if frame.header.width > c_int::MAX as u32 / 4 || frame.header.height > c_int::MAX as u32 {
+                error!("[Rust NDI] Frame of {}x{} is too large for NDI, skipped.", frame.header.width, frame.header.height);
+                return Ok(());
+            }
+            sender.send(frame.header.width, frame.header.height, &frame.pixels);
+            Ok(())
+        })
+    }
+}
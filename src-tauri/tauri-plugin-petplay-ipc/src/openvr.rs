@@ -0,0 +1,527 @@
+// --- In-process OpenVR ---
+// For setups without the external backend: with the "openvr" cargo feature
+// the app talks to SteamVR itself. It creates an overlay, submits frames to
+// it (a frame sink, see sink.rs, under the id "openvr") and polls device
+// poses from the runtime (a pose source, see pose_source.rs), so the web UI
+// sees the same frames-out, transforms-in behavior as with a backend. The
+// pipes stay the default; this is switched on with [openvr] enabled in
+// puppyweb.toml or with enable_openvr, and runs beside them.
+//
+// Like the NDI runtime, openvr_api isn't linked but loaded when enabled, from
+// libraryPath or the system library path (openvr_api.dll,
+// libopenvr_api.so), and only the flat C interface tables are used
+// (openvr_capi.h: IVRSystem_022, IVROverlay_027). Frames go to the overlay
+// with SetOverlayRaw as packed RGBA, which copies them on the CPU: fine for
+// UI-sized overlays, too slow for a 4K video wall. Only overlay 0 is shown.
+// Poses are read in standing space; the headset is device 0, the controllers
+// with the left and right hand roles 1 and 2, and any other device OpenVR
+// index + 2. Placement set with set_overlay_transform and friends (see
+// placement.rs) goes straight to the overlay, transforms in standing space or
+// relative to the device the overlay is attached to. In dashboard mode (see
+// dashboard.rs) the overlay is created as a dashboard overlay with the icon
+// as its thumbnail, and the dashboard tab opening and closing is picked up
+// from the overlay's events whenever poses are polled.
+//
+//   [openvr]
+//   enabled = true
+//   overlayKey = "puppyweb.overlay"
+//   widthMeters = 1.5
+use crate::{
+    buffer_pool::BufferPool,
+    dashboard::{DashboardConfig, DashboardEvents},
+    pose_source::RuntimeLayer,
+};
+use serde::{Deserialize, Serialize};
+use std::{io, sync::Arc};
+
+pub const OPENVR_SINK_ID: &str = "openvr";
+// OpenVR's k_unVROverlayMaxKeyLength and k_unVROverlayMaxNameLength, including the NUL
+const MAX_KEY_LEN: usize = 256;
+const MAX_NAME_LEN: usize = 128;
+const MAX_POSE_RATE_HZ: u32 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OpenVrConfig {
+    pub enabled: bool,
+    // Unique key of the overlay in SteamVR; another app using it makes creation fail
+    pub overlay_key: String,
+    // Shown in SteamVR's overlay list
+    pub overlay_name: String,
+    pub width_meters: f32,
+    // How often poses are read from the runtime
+    pub pose_rate_hz: u32,
+    // openvr_api library to load instead of searching the library path
+    pub library_path: Option<String>,
+}
+
+impl Default for OpenVrConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            overlay_key: "puppyweb.overlay".to_string(),
+            overlay_name: "PuppyWeb".to_string(),
+            width_meters: 1.0,
+            pose_rate_hz: 90,
+            library_path: None,
+        }
+    }
+}
+
+impl OpenVrConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.overlay_key.is_empty() || self.overlay_key.len() >= MAX_KEY_LEN || self.overlay_key.contains('\0') {
+            return Err(format!("overlayKey must be 1 to {} bytes without NUL characters", MAX_KEY_LEN - 1));
+        }
+        if self.overlay_name.is_empty() || self.overlay_name.len() >= MAX_NAME_LEN || self.overlay_name.contains('\0') {
+            return Err(format!("overlayName must be 1 to {} bytes without NUL characters", MAX_NAME_LEN - 1));
+        }
+        if !(self.width_meters > 0.0 && self.width_meters <= 100.0) {
+            return Err("widthMeters must be above 0 and at most 100".to_string());
+        }
+        if self.pose_rate_hz == 0 || self.pose_rate_hz > MAX_POSE_RATE_HZ {
+            return Err(format!("poseRateHz must be between 1 and {}", MAX_POSE_RATE_HZ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "openvr"))]
+pub fn start(_config: &OpenVrConfig, _dashboard: &DashboardConfig, _buffers: &Arc<BufferPool>, _events: DashboardEvents) -> io::Result<RuntimeLayer> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "this build doesn't include OpenVR support (cargo feature \"openvr\")"))
+}
+
+// Load the runtime, create the overlay and show it (or put it in the dashboard)
+#[cfg(feature = "openvr")]
+pub fn start(config: &OpenVrConfig, dashboard: &DashboardConfig, buffers: &Arc<BufferPool>, events: DashboardEvents) -> io::Result<RuntimeLayer> {
+    config.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    dashboard.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let session = Arc::new(session::Session::open(config, dashboard, Arc::clone(buffers), events)?);
+    Ok(RuntimeLayer { sink: session.clone(), poses: session.clone(), placement: session })
+}
+
+#[cfg(feature = "openvr")]
+mod session {
+    use super::OpenVrConfig;
+    use crate::{
+        buffer_pool::BufferPool,
+        dashboard::{DashboardConfig, DashboardEvents},
+        frame_queue::QueuedFrame,
+        overlays::DEFAULT_OVERLAY_ID,
+        pixel_format,
+        placement::{PlacementChange, PlacementTarget},
+        pose::{Pose, DEVICE_CONTROLLER_LEFT, DEVICE_CONTROLLER_RIGHT, DEVICE_HMD},
+        pose_source::PoseSource,
+        sink::{FrameSink, SinkFuture},
+    };
+    use libloading::Library;
+    use parking_lot::Mutex;
+    use std::{
+        ffi::{c_char, c_void, CStr, CString},
+        io,
+        path::PathBuf,
+        sync::Arc,
+    };
+    use tracing::{debug, info, warn};
+
+    // --- OpenVR types (openvr.h / openvr_capi.h) ---
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct HmdMatrix34 {
+        m: [[f32; 4]; 3],
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct TrackedDevicePose {
+        device_to_absolute_tracking: HmdMatrix34,
+        velocity: [f32; 3],
+        angular_velocity: [f32; 3],
+        tracking_result: i32,
+        pose_is_valid: bool,
+        device_is_connected: bool,
+    }
+
+    // VREvent_t; openvr.h packs it to 4 bytes outside Windows
+    #[cfg_attr(windows, repr(C))]
+    #[cfg_attr(not(windows), repr(C, packed(4)))]
+    #[derive(Clone, Copy, Default)]
+    struct VrEvent {
+        event_type: u32,
+        tracked_device_index: u32,
+        event_age_seconds: f32,
+        // VREvent_Data_t, a union of at most six u64s
+        data: [u64; 6],
+    }
+
+    type OverlayHandle = u64;
+    const EVENT_OVERLAY_SHOWN: u32 = 500;
+    const EVENT_OVERLAY_HIDDEN: u32 = 501;
+    const APPLICATION_OVERLAY: i32 = 2;
+    const TRACKING_UNIVERSE_STANDING: i32 = 1;
+    const CONTROLLER_ROLE_LEFT_HAND: i32 = 1;
+    const CONTROLLER_ROLE_RIGHT_HAND: i32 = 2;
+    const INVALID_DEVICE_INDEX: u32 = u32::MAX;
+    const MAX_TRACKED_DEVICES: usize = 64;
+    const IVR_SYSTEM: &CStr = c"FnTable:IVRSystem_022";
+    const IVR_OVERLAY: &CStr = c"FnTable:IVROverlay_027";
+    // Where the overlay starts out: 2 m in front of the standing origin at eye height
+    const INITIAL_TRANSFORM: HmdMatrix34 = HmdMatrix34 { m: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 1.5], [0.0, 0.0, 1.0, -2.0]] };
+
+    type InitFn = unsafe extern "system" fn(*mut i32, i32, *const c_char) -> u32;
+    type ShutdownFn = unsafe extern "system" fn();
+    type GetInterfaceFn = unsafe extern "system" fn(*const c_char, *mut i32) -> *mut c_void;
+    type InitErrorDescriptionFn = unsafe extern "system" fn(i32) -> *const c_char;
+    // Entries of the tables that aren't called; only their size matters
+    type Unused = Option<unsafe extern "system" fn()>;
+
+    // VR_IVRSystem_FnTable, up to the last entry used
+    #[repr(C)]
+    struct SystemTable {
+        // GetRecommendedRenderTargetSize through SetDisplayVisibility
+        _display: [Unused; 11],
+        get_device_to_absolute_tracking_pose: unsafe extern "system" fn(i32, f32, *mut TrackedDevicePose, u32),
+        // GetSeatedZeroPoseToStandingAbsoluteTrackingPose through ApplyTransform
+        _zero_poses: [Unused; 5],
+        get_tracked_device_index_for_controller_role: unsafe extern "system" fn(i32) -> u32,
+    }
+
+    // VR_IVROverlay_FnTable, up to the last entry used
+    #[repr(C)]
+    struct OverlayTable {
+        _find_overlay: Unused,
+        create_overlay: unsafe extern "system" fn(*const c_char, *const c_char, *mut OverlayHandle) -> i32,
+        destroy_overlay: unsafe extern "system" fn(OverlayHandle) -> i32,
+        // GetOverlayKey through GetOverlayColor
+        _properties: [Unused; 12],
+        set_overlay_alpha: unsafe extern "system" fn(OverlayHandle, f32) -> i32,
+        // GetOverlayAlpha through GetOverlaySortOrder
+        _alpha_to_width: [Unused; 5],
+        set_overlay_width_in_meters: unsafe extern "system" fn(OverlayHandle, f32) -> i32,
+        _get_overlay_width_in_meters: Unused,
+        set_overlay_curvature: unsafe extern "system" fn(OverlayHandle, f32) -> i32,
+        // GetOverlayCurvature through GetOverlayTransformType
+        _curvature_to_transform: [Unused; 8],
+        set_overlay_transform_absolute: unsafe extern "system" fn(OverlayHandle, i32, *const HmdMatrix34) -> i32,
+        _get_overlay_transform_absolute: Unused,
+        set_overlay_transform_tracked_device_relative: unsafe extern "system" fn(OverlayHandle, u32, *const HmdMatrix34) -> i32,
+        // GetOverlayTransformTrackedDeviceRelative through SetOverlayTransformProjection
+        _transforms: [Unused; 6],
+        show_overlay: unsafe extern "system" fn(OverlayHandle) -> i32,
+        // HideOverlay through WaitFrameSync
+        _visibility: [Unused; 4],
+        poll_next_overlay_event: unsafe extern "system" fn(OverlayHandle, *mut VrEvent, u32) -> bool,
+        // GetOverlayInputMethod through ClearOverlayTexture
+        _input_and_textures: [Unused; 13],
+        set_overlay_raw: unsafe extern "system" fn(OverlayHandle, *mut c_void, u32, u32, u32) -> i32,
+        set_overlay_from_file: unsafe extern "system" fn(OverlayHandle, *const c_char) -> i32,
+        // GetOverlayTexture through GetOverlayTextureSize
+        _texture_queries: [Unused; 3],
+        create_dashboard_overlay: unsafe extern "system" fn(*const c_char, *const c_char, *mut OverlayHandle, *mut OverlayHandle) -> i32,
+    }
+
+    fn library_candidates(config: &OpenVrConfig) -> Vec<PathBuf> {
+        #[cfg(windows)]
+        const LIBRARY: &str = "openvr_api.dll";
+        #[cfg(target_os = "macos")]
+        const LIBRARY: &str = "libopenvr_api.dylib";
+        #[cfg(all(unix, not(target_os = "macos")))]
+        const LIBRARY: &str = "libopenvr_api.so";
+
+        match &config.library_path {
+            Some(path) => vec![PathBuf::from(path)],
+            None => vec![PathBuf::from(LIBRARY)],
+        }
+    }
+
+    // An initialized OpenVR runtime with our overlay; shut down when dropped
+    pub struct Session {
+        system: &'static SystemTable,
+        overlay_table: &'static OverlayTable,
+        overlay: OverlayHandle,
+        // The dashboard tab's icon, in dashboard mode
+        thumbnail: OverlayHandle,
+        // Told about the dashboard tab opening and closing, in dashboard mode
+        events: Option<DashboardEvents>,
+        shutdown: ShutdownFn,
+        // Serializes frame submission; OpenVR copies the pixels before SetOverlayRaw returns
+        submit: Mutex<()>,
+        // Device id the overlay is attached to, and its transform (in standing space or relative to the device)
+        anchor: Mutex<(Option<u32>, HmdMatrix34)>,
+        buffers: Arc<BufferPool>,
+        key: String,
+        // Keeps the function pointers and tables above valid
+        _library: Library,
+    }
+
+    // SAFETY: the OpenVR interfaces may be called from any thread
+    unsafe impl Send for Session {}
+    unsafe impl Sync for Session {}
+
+    fn runtime_error(what: &str, code: i32) -> io::Error {
+        io::Error::other(format!("{} failed with OpenVR error {}", what, code))
+    }
+
+    impl Session {
+        pub fn open(config: &OpenVrConfig, dashboard: &DashboardConfig, buffers: Arc<BufferPool>, events: DashboardEvents) -> io::Result<Self> {
+            let library = load(config)?;
+            // SAFETY: the signatures match openvr_capi.h; the interface tables live as long as the runtime
+            // stays initialized, which is until `shutdown` in drop (the library stays loaded until after it)
+            unsafe {
+                let symbol_error = |e: libloading::Error| io::Error::new(io::ErrorKind::Unsupported, e.to_string());
+                let init = *library.get::<InitFn>(b"VR_InitInternal2\0").map_err(symbol_error)?;
+                let shutdown = *library.get::<ShutdownFn>(b"VR_ShutdownInternal\0").map_err(symbol_error)?;
+                let get_interface = *library.get::<GetInterfaceFn>(b"VR_GetGenericInterface\0").map_err(symbol_error)?;
+                let describe = *library.get::<InitErrorDescriptionFn>(b"VR_GetVRInitErrorAsEnglishDescription\0").map_err(symbol_error)?;
+
+                let mut error = 0;
+                init(&mut error, APPLICATION_OVERLAY, std::ptr::null());
+                if error != 0 {
+                    let description = CStr::from_ptr(describe(error)).to_string_lossy().into_owned();
+                    return Err(io::Error::new(io::ErrorKind::NotConnected, format!("OpenVR didn't start: {}", description)));
+                }
+                let interface = |name: &CStr| {
+                    let mut error = 0;
+                    let table = get_interface(name.as_ptr(), &mut error);
+                    if error != 0 || table.is_null() {
+                        let description = CStr::from_ptr(describe(error)).to_string_lossy().into_owned();
+                        return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{:?} unavailable: {}", name, description)));
+                    }
+                    Ok(table)
+                };
+                let tables = interface(IVR_SYSTEM).and_then(|system| Ok((system, interface(IVR_OVERLAY)?)));
+                let (system, overlay_table) = match tables {
+                    Ok((system, overlay)) => (&*(system as *const SystemTable), &*(overlay as *const OverlayTable)),
+                    Err(e) => {
+                        shutdown();
+                        return Err(e);
+                    }
+                };
+
+                let mut session = Self {
+                    system,
+                    overlay_table,
+                    overlay: 0,
+                    thumbnail: 0,
+                    events: dashboard.enabled.then_some(events),
+                    shutdown,
+                    submit: Mutex::new(()),
+                    anchor: Mutex::new((None, INITIAL_TRANSFORM)),
+                    buffers,
+                    key: config.overlay_key.clone(),
+                    _library: library,
+                };
+                // Both were validated to be free of NUL characters
+                let key = CString::new(config.overlay_key.as_str()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                let name = CString::new(config.overlay_name.as_str()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                // Dropping the session on an error below shuts the runtime down again
+                if dashboard.enabled {
+                    let error = (overlay_table.create_dashboard_overlay)(key.as_ptr(), name.as_ptr(), &mut session.overlay, &mut session.thumbnail);
+                    if error != 0 {
+                        return Err(runtime_error("CreateDashboardOverlay", error));
+                    }
+                    if let Some(icon_path) = &dashboard.icon_path {
+                        // Validated to be free of NUL characters; a missing icon isn't worth failing over
+                        let icon = CString::new(icon_path.as_str()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                        let error = (overlay_table.set_overlay_from_file)(session.thumbnail, icon.as_ptr());
+                        if error != 0 {
+                            warn!("[Rust OpenVR] Can't use {:?} as the dashboard icon: OpenVR error {}.", icon_path, error);
+                        }
+                    }
+                } else {
+                    let error = (overlay_table.create_overlay)(key.as_ptr(), name.as_ptr(), &mut session.overlay);
+                    if error != 0 {
+                        return Err(runtime_error("CreateOverlay", error));
+                    }
+                }
+                let error = (overlay_table.set_overlay_width_in_meters)(session.overlay, config.width_meters);
+                if error != 0 {
+                    return Err(runtime_error("SetOverlayWidthInMeters", error));
+                }
+                // The dashboard places and shows its overlays itself
+                if !dashboard.enabled {
+                    for (what, error) in [
+                        ("SetOverlayTransformAbsolute", (overlay_table.set_overlay_transform_absolute)(session.overlay, TRACKING_UNIVERSE_STANDING, &INITIAL_TRANSFORM)),
+                        ("ShowOverlay", (overlay_table.show_overlay)(session.overlay)),
+                    ] {
+                        if error != 0 {
+                            return Err(runtime_error(what, error));
+                        }
+                    }
+                }
+                let mode = if dashboard.enabled { "Dashboard overlay" } else { "Overlay" };
+                info!("[Rust OpenVR] {} {:?} created, {} m wide.", mode, config.overlay_key, config.width_meters);
+                Ok(session)
+            }
+        }
+
+        // Set the overlay transform, relative to the attached device if it's tracked and in standing space otherwise
+        unsafe fn place(&self, (device_id, transform): (Option<u32>, HmdMatrix34)) -> (&'static str, i32) {
+            let index = device_id.map(|device_id| match device_id {
+                DEVICE_HMD => 0,
+                DEVICE_CONTROLLER_LEFT => (self.system.get_tracked_device_index_for_controller_role)(CONTROLLER_ROLE_LEFT_HAND),
+                DEVICE_CONTROLLER_RIGHT => (self.system.get_tracked_device_index_for_controller_role)(CONTROLLER_ROLE_RIGHT_HAND),
+                other => other - 2,
+            });
+            match index {
+                Some(INVALID_DEVICE_INDEX) => {
+                    warn!("[Rust OpenVR] Device {:?} isn't tracked; placing the overlay in standing space.", device_id);
+                }
+                Some(index) => {
+                    let error = (self.overlay_table.set_overlay_transform_tracked_device_relative)(self.overlay, index, &transform);
+                    return ("SetOverlayTransformTrackedDeviceRelative", error);
+                }
+                None => {}
+            }
+            ("SetOverlayTransformAbsolute", (self.overlay_table.set_overlay_transform_absolute)(self.overlay, TRACKING_UNIVERSE_STANDING, &transform))
+        }
+
+        // Maps OpenVR device indices to our device ids, for the controller roles as they are now
+        fn device_ids(&self) -> impl Fn(u32) -> u32 {
+            // SAFETY: plain queries on the live system interface
+            let (left, right) = unsafe {
+                (
+                    (self.system.get_tracked_device_index_for_controller_role)(CONTROLLER_ROLE_LEFT_HAND),
+                    (self.system.get_tracked_device_index_for_controller_role)(CONTROLLER_ROLE_RIGHT_HAND),
+                )
+            };
+            move |index| match index {
+                0 => DEVICE_HMD,
+                index if index == left && left != INVALID_DEVICE_INDEX => DEVICE_CONTROLLER_LEFT,
+                index if index == right && right != INVALID_DEVICE_INDEX => DEVICE_CONTROLLER_RIGHT,
+                index => index + 2,
+            }
+        }
+    }
+
+    fn load(config: &OpenVrConfig) -> io::Result<Library> {
+        let mut last_error = None;
+        for candidate in library_candidates(config) {
+            // SAFETY: loading openvr_api has no side effects until VR_InitInternal2
+            match unsafe { Library::new(&candidate) } {
+                Ok(library) => return Ok(library),
+                Err(e) => {
+                    debug!("[Rust OpenVR] Can't load {}: {}", candidate.display(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let detail = last_error.map(|e| e.to_string()).unwrap_or_default();
+        Err(io::Error::new(io::ErrorKind::Unsupported, format!("OpenVR runtime not found ({})", detail)))
+    }
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            // SAFETY: the overlay (if created) and the runtime aren't used after this
+            unsafe {
+                if self.thumbnail != 0 {
+                    (self.overlay_table.destroy_overlay)(self.thumbnail);
+                }
+                if self.overlay != 0 {
+                    (self.overlay_table.destroy_overlay)(self.overlay);
+                }
+                (self.shutdown)();
+            }
+            info!("[Rust OpenVR] Overlay {:?} closed.", self.key);
+        }
+    }
+
+    impl FrameSink for Session {
+        fn describe(&self) -> String {
+            format!("OpenVR overlay {:?}", self.key)
+        }
+
+        fn write<'a>(&'a self, frame: &'a QueuedFrame) -> SinkFuture<'a> {
+            Box::pin(async move {
+                if frame.header.overlay_id != DEFAULT_OVERLAY_ID {
+                    return Ok(());
+                }
+                let converted = pixel_format::packed_rgba(frame, &self.buffers);
+                let frame = converted.as_ref().unwrap_or(frame);
+                let _submit = self.submit.lock();
+                // SAFETY: OpenVR only reads the pixels, and is done with them when SetOverlayRaw returns
+                let error = unsafe {
+                    (self.overlay_table.set_overlay_raw)(self.overlay, frame.pixels.as_ptr() as *mut c_void, frame.header.width, frame.header.height, 4)
+                };
+                if error != 0 {
+                    // A frame the compositor rejects (e.g. too large) isn't worth dropping the overlay for
+                    warn!("[Rust OpenVR] SetOverlayRaw failed with OpenVR error {} for a {}x{} frame.", error, frame.header.width, frame.header.height);
+                }
+                Ok(())
+            })
+        }
+    }
+
+    impl PlacementTarget for Session {
+        fn apply(&self, overlay_id: u32, change: PlacementChange) -> io::Result<()> {
+            if overlay_id != DEFAULT_OVERLAY_ID {
+                return Ok(());
+            }
+            // SAFETY: plain setters on the live overlay
+            let (what, error) = unsafe {
+                match change {
+                    PlacementChange::Transform(matrix) => {
+                        let mut anchor = self.anchor.lock();
+                        anchor.1.m.as_flattened_mut().copy_from_slice(&matrix[..12]);
+                        self.place(*anchor)
+                    }
+                    PlacementChange::Attachment(device_id) => {
+                        let mut anchor = self.anchor.lock();
+                        anchor.0 = device_id;
+                        self.place(*anchor)
+                    }
+                    PlacementChange::Width(width) => ("SetOverlayWidthInMeters", (self.overlay_table.set_overlay_width_in_meters)(self.overlay, width)),
+                    PlacementChange::Curvature(curvature) => ("SetOverlayCurvature", (self.overlay_table.set_overlay_curvature)(self.overlay, curvature)),
+                    PlacementChange::Alpha(alpha) => ("SetOverlayAlpha", (self.overlay_table.set_overlay_alpha)(self.overlay, alpha)),
+                }
+            };
+            if error != 0 {
+                return Err(runtime_error(what, error));
+            }
+            Ok(())
+        }
+    }
+
+    impl PoseSource for Session {
+        fn describe(&self) -> String {
+            "OpenVR".to_string()
+        }
+
+        fn poll(&self) -> io::Result<Vec<Pose>> {
+            if let Some(events) = &self.events {
+                let mut event = VrEvent::default();
+                // SAFETY: `event` is a VREvent_t of the size the call is told about
+                while unsafe { (self.overlay_table.poll_next_overlay_event)(self.overlay, &mut event, size_of::<VrEvent>() as u32) } {
+                    match event.event_type {
+                        EVENT_OVERLAY_SHOWN => events(true),
+                        EVENT_OVERLAY_HIDDEN => events(false),
+                        _ => {}
+                    }
+                }
+            }
+            let mut poses = [TrackedDevicePose::default(); MAX_TRACKED_DEVICES];
+            // SAFETY: the array holds the MAX_TRACKED_DEVICES poses the call is told about
+            unsafe {
+                (self.system.get_device_to_absolute_tracking_pose)(TRACKING_UNIVERSE_STANDING, 0.0, poses.as_mut_ptr(), MAX_TRACKED_DEVICES as u32)
+            };
+            let (device_id, timestamp_us) = (self.device_ids(), crate::protocol::timestamp_us());
+            Ok(poses
+                .iter()
+                .enumerate()
+                .filter(|(_, pose)| pose.pose_is_valid && pose.device_is_connected)
+                .map(|(index, pose)| {
+                    let mut matrix = [0.0; 16];
+                    matrix[..12].copy_from_slice(pose.device_to_absolute_tracking.m.as_flattened());
+                    matrix[15] = 1.0;
+                    Pose {
+                        device_id: device_id(index as u32),
+                        linear_velocity: pose.velocity,
+                        angular_velocity: pose.angular_velocity,
+                        timestamp_us,
+                        ..Pose::from_matrix(&matrix)
+                    }
+                })
+                .collect())
+        }
+    }
+}
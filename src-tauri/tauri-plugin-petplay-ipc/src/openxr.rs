@@ -0,0 +1,983 @@
+// --- In-process OpenXR ---
+// The OpenXR counterpart of openvr.rs, for runtimes other than SteamVR (or
+// SteamVR through its OpenXR side): with the "openxr" cargo feature the app
+// joins the running VR application as an XR_EXTX_overlay session and shows
+// the overlay as a quad composition layer. It's the same kind of runtime
+// layer as the OpenVR one (see pose_source.rs): a frame sink under the id
+// "openxr" and a pose source, switched on with [openxr] enabled in
+// puppyweb.toml or with enable_openxr, beside the pipes.
+//
+// The OpenXR loader is loaded when enabled, from libraryPath or the system
+// library path (openxr_loader.dll). Frames are uploaded to a D3D11 swapchain,
+// so this is Windows only, and a runtime without XR_EXTX_overlay is refused
+// rather than started as a main session that would take over the headset.
+// All OpenXR calls happen on a thread of the layer's own that runs the frame
+// loop; the sink only hands it the newest frame (overlay 0) and the pose
+// source reads the headset pose it last located. Overlay sessions get no
+// controller input, so only the headset (device 0) is tracked. The quad and
+// the poses live in the stage space, or the local space where there's no
+// stage. set_overlay_transform and set_overlay_width (see placement.rs) move
+// and resize the quad, and attaching it to the headset makes the transform
+// relative to the view; it has no curvature or alpha.
+//
+//   [openxr]
+//   enabled = true
+//   widthMeters = 1.5
+use crate::{buffer_pool::BufferPool, pose_source::RuntimeLayer};
+use serde::{Deserialize, Serialize};
+use std::{io, sync::Arc};
+
+pub const OPENXR_SINK_ID: &str = "openxr";
+const MAX_POSE_RATE_HZ: u32 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OpenXrConfig {
+    pub enabled: bool,
+    pub width_meters: f32,
+    // Where our layers go relative to other overlay sessions' (sessionLayersPlacement); higher is in front
+    pub placement: u32,
+    // How often the headset pose is handed on
+    pub pose_rate_hz: u32,
+    // OpenXR loader to load instead of searching the library path
+    pub library_path: Option<String>,
+}
+
+impl Default for OpenXrConfig {
+    fn default() -> Self {
+        Self { enabled: false, width_meters: 1.0, placement: 0, pose_rate_hz: 90, library_path: None }
+    }
+}
+
+impl OpenXrConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.width_meters > 0.0 && self.width_meters <= 100.0) {
+            return Err("widthMeters must be above 0 and at most 100".to_string());
+        }
+        if self.pose_rate_hz == 0 || self.pose_rate_hz > MAX_POSE_RATE_HZ {
+            return Err(format!("poseRateHz must be between 1 and {}", MAX_POSE_RATE_HZ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(all(feature = "openxr", windows)))]
+pub fn start(_config: &OpenXrConfig, _buffers: &Arc<BufferPool>) -> io::Result<RuntimeLayer> {
+    let reason = if cfg!(feature = "openxr") {
+        "the OpenXR overlay is only available on Windows"
+    } else {
+        "this build doesn't include OpenXR support (cargo feature \"openxr\")"
+    };
+    Err(io::Error::new(io::ErrorKind::Unsupported, reason))
+}
+
+// Load the loader, join the running application as an overlay session and start the frame loop
+#[cfg(all(feature = "openxr", windows))]
+pub fn start(config: &OpenXrConfig, buffers: &Arc<BufferPool>) -> io::Result<RuntimeLayer> {
+    config.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let layer = Arc::new(session::Layer::open(config, Arc::clone(buffers))?);
+    Ok(RuntimeLayer { sink: layer.clone(), poses: layer.clone(), placement: layer })
+}
+
+#[cfg(all(feature = "openxr", windows))]
+mod session {
+    use super::OpenXrConfig;
+    use crate::{
+        buffer_pool::BufferPool,
+        frame_queue::QueuedFrame,
+        overlays::DEFAULT_OVERLAY_ID,
+        pixel_format,
+        placement::{PlacementChange, PlacementTarget},
+        pose::{Pose, DEVICE_HMD},
+        pose_source::PoseSource,
+        sink::{FrameSink, SinkFuture},
+    };
+    use libloading::Library;
+    use parking_lot::Mutex;
+    use std::{
+        ffi::{c_char, c_void, CStr},
+        io, mem, ptr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+        time::Duration,
+    };
+    use tracing::{info, warn};
+    use windows::{
+        core::Interface,
+        Win32::{
+            Foundation::{HMODULE, LUID},
+            Graphics::{
+                Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+                Direct3D11::{D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_SDK_VERSION},
+                Dxgi::{CreateDXGIFactory1, IDXGIAdapter, IDXGIFactory4},
+            },
+        },
+    };
+
+    // --- OpenXR types (openxr.h, openxr_platform.h) ---
+    type XrResult = i32;
+    type Handle = u64;
+    type XrTime = i64;
+    const NULL_HANDLE: Handle = 0;
+    const SUCCESS: XrResult = 0;
+    const EVENT_UNAVAILABLE: XrResult = 4;
+
+    const TYPE_EXTENSION_PROPERTIES: i32 = 2;
+    const TYPE_INSTANCE_CREATE_INFO: i32 = 3;
+    const TYPE_SYSTEM_GET_INFO: i32 = 4;
+    const TYPE_SESSION_CREATE_INFO: i32 = 8;
+    const TYPE_SWAPCHAIN_CREATE_INFO: i32 = 9;
+    const TYPE_SESSION_BEGIN_INFO: i32 = 10;
+    const TYPE_FRAME_END_INFO: i32 = 12;
+    const TYPE_EVENT_DATA_BUFFER: i32 = 16;
+    const TYPE_EVENT_DATA_INSTANCE_LOSS_PENDING: i32 = 17;
+    const TYPE_EVENT_DATA_SESSION_STATE_CHANGED: i32 = 18;
+    const TYPE_FRAME_WAIT_INFO: i32 = 33;
+    const TYPE_COMPOSITION_LAYER_QUAD: i32 = 36;
+    const TYPE_REFERENCE_SPACE_CREATE_INFO: i32 = 37;
+    const TYPE_SPACE_LOCATION: i32 = 42;
+    const TYPE_FRAME_STATE: i32 = 44;
+    const TYPE_FRAME_BEGIN_INFO: i32 = 46;
+    const TYPE_SWAPCHAIN_IMAGE_ACQUIRE_INFO: i32 = 55;
+    const TYPE_SWAPCHAIN_IMAGE_WAIT_INFO: i32 = 56;
+    const TYPE_SWAPCHAIN_IMAGE_RELEASE_INFO: i32 = 57;
+    const TYPE_GRAPHICS_BINDING_D3D11_KHR: i32 = 1_000_027_000;
+    const TYPE_SWAPCHAIN_IMAGE_D3D11_KHR: i32 = 1_000_027_001;
+    const TYPE_GRAPHICS_REQUIREMENTS_D3D11_KHR: i32 = 1_000_027_002;
+    const TYPE_SESSION_CREATE_INFO_OVERLAY_EXTX: i32 = 1_000_033_000;
+
+    const FORM_FACTOR_HEAD_MOUNTED_DISPLAY: i32 = 1;
+    const VIEW_CONFIGURATION_PRIMARY_STEREO: i32 = 2;
+    const REFERENCE_SPACE_VIEW: i32 = 1;
+    const REFERENCE_SPACE_LOCAL: i32 = 2;
+    const REFERENCE_SPACE_STAGE: i32 = 3;
+    const ENVIRONMENT_BLEND_MODE_OPAQUE: i32 = 1;
+    const SESSION_STATE_READY: i32 = 2;
+    const SESSION_STATE_STOPPING: i32 = 6;
+    const SESSION_STATE_LOSS_PENDING: i32 = 7;
+    const SESSION_STATE_EXITING: i32 = 8;
+    const SWAPCHAIN_USAGE_COLOR_ATTACHMENT: u64 = 0x1;
+    const SWAPCHAIN_USAGE_TRANSFER_DST: u64 = 0x10;
+    const SWAPCHAIN_USAGE_SAMPLED: u64 = 0x20;
+    const LAYER_BLEND_TEXTURE_SOURCE_ALPHA: u64 = 0x2;
+    const LOCATION_ORIENTATION_VALID: u64 = 0x1;
+    const LOCATION_POSITION_VALID: u64 = 0x2;
+    const INFINITE_DURATION: i64 = i64::MAX;
+    // DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, then DXGI_FORMAT_R8G8B8A8_UNORM; web content is sRGB encoded
+    const SWAPCHAIN_FORMATS: [i64; 2] = [29, 28];
+
+    const EXTX_OVERLAY: &CStr = c"XR_EXTX_overlay";
+    const KHR_D3D11_ENABLE: &CStr = c"XR_KHR_D3D11_enable";
+    const APPLICATION_NAME: &[u8] = b"PuppyWeb";
+    // Core 1.0 API, which is all this uses
+    const API_VERSION: u64 = 1 << 48;
+    // How long the loop idles between event polls while the session isn't running
+    const IDLE_INTERVAL: Duration = Duration::from_millis(20);
+    // Where the quad starts out: 2 m in front, at eye height in the stage space
+    const QUAD_DISTANCE: f32 = 2.0;
+    const QUAD_STAGE_HEIGHT: f32 = 1.5;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct Posef {
+        orientation: [f32; 4],
+        position: [f32; 3],
+    }
+
+    const IDENTITY: Posef = Posef { orientation: [0.0, 0.0, 0.0, 1.0], position: [0.0; 3] };
+
+    #[repr(C)]
+    struct ExtensionProperties {
+        ty: i32,
+        next: *mut c_void,
+        extension_name: [c_char; 128],
+        extension_version: u32,
+    }
+
+    #[repr(C)]
+    struct ApplicationInfo {
+        application_name: [u8; 128],
+        application_version: u32,
+        engine_name: [u8; 128],
+        engine_version: u32,
+        api_version: u64,
+    }
+
+    #[repr(C)]
+    struct InstanceCreateInfo {
+        ty: i32,
+        next: *const c_void,
+        create_flags: u64,
+        application_info: ApplicationInfo,
+        enabled_api_layer_count: u32,
+        enabled_api_layer_names: *const *const c_char,
+        enabled_extension_count: u32,
+        enabled_extension_names: *const *const c_char,
+    }
+
+    #[repr(C)]
+    struct SystemGetInfo {
+        ty: i32,
+        next: *const c_void,
+        form_factor: i32,
+    }
+
+    #[repr(C)]
+    struct GraphicsRequirementsD3D11 {
+        ty: i32,
+        next: *mut c_void,
+        adapter_luid: LUID,
+        min_feature_level: i32,
+    }
+
+    #[repr(C)]
+    struct GraphicsBindingD3D11 {
+        ty: i32,
+        next: *const c_void,
+        device: *mut c_void,
+    }
+
+    #[repr(C)]
+    struct SessionCreateInfoOverlay {
+        ty: i32,
+        next: *const c_void,
+        create_flags: u64,
+        session_layers_placement: u32,
+    }
+
+    #[repr(C)]
+    struct SessionCreateInfo {
+        ty: i32,
+        next: *const c_void,
+        create_flags: u64,
+        system_id: u64,
+    }
+
+    #[repr(C)]
+    struct EventDataBuffer {
+        ty: i32,
+        next: *const c_void,
+        varying: [u8; 4000],
+    }
+
+    #[repr(C)]
+    struct EventDataSessionStateChanged {
+        ty: i32,
+        next: *const c_void,
+        session: Handle,
+        state: i32,
+        time: XrTime,
+    }
+
+    #[repr(C)]
+    struct SessionBeginInfo {
+        ty: i32,
+        next: *const c_void,
+        primary_view_configuration_type: i32,
+    }
+
+    #[repr(C)]
+    struct ReferenceSpaceCreateInfo {
+        ty: i32,
+        next: *const c_void,
+        reference_space_type: i32,
+        pose_in_reference_space: Posef,
+    }
+
+    #[repr(C)]
+    struct SwapchainCreateInfo {
+        ty: i32,
+        next: *const c_void,
+        create_flags: u64,
+        usage_flags: u64,
+        format: i64,
+        sample_count: u32,
+        width: u32,
+        height: u32,
+        face_count: u32,
+        array_size: u32,
+        mip_count: u32,
+    }
+
+    #[repr(C)]
+    struct SwapchainImageD3D11 {
+        ty: i32,
+        next: *mut c_void,
+        texture: *mut c_void,
+    }
+
+    // XrSwapchainImageAcquireInfo, XrSwapchainImageReleaseInfo, XrFrameWaitInfo and XrFrameBeginInfo
+    #[repr(C)]
+    struct EmptyInfo {
+        ty: i32,
+        next: *const c_void,
+    }
+
+    impl EmptyInfo {
+        fn new(ty: i32) -> Self {
+            Self { ty, next: ptr::null() }
+        }
+    }
+
+    #[repr(C)]
+    struct SwapchainImageWaitInfo {
+        ty: i32,
+        next: *const c_void,
+        timeout: i64,
+    }
+
+    #[repr(C)]
+    struct FrameState {
+        ty: i32,
+        next: *mut c_void,
+        predicted_display_time: XrTime,
+        predicted_display_period: i64,
+        should_render: u32,
+    }
+
+    #[repr(C)]
+    struct SwapchainSubImage {
+        swapchain: Handle,
+        image_rect: [i32; 4],
+        image_array_index: u32,
+    }
+
+    #[repr(C)]
+    struct CompositionLayerQuad {
+        ty: i32,
+        next: *const c_void,
+        layer_flags: u64,
+        space: Handle,
+        eye_visibility: i32,
+        sub_image: SwapchainSubImage,
+        pose: Posef,
+        size: [f32; 2],
+    }
+
+    #[repr(C)]
+    struct FrameEndInfo {
+        ty: i32,
+        next: *const c_void,
+        display_time: XrTime,
+        environment_blend_mode: i32,
+        layer_count: u32,
+        layers: *const *const CompositionLayerQuad,
+    }
+
+    #[repr(C)]
+    struct SpaceLocation {
+        ty: i32,
+        next: *mut c_void,
+        location_flags: u64,
+        pose: Posef,
+    }
+
+    type GetInstanceProcAddrFn = unsafe extern "system" fn(Handle, *const c_char, *mut Option<unsafe extern "system" fn()>) -> XrResult;
+
+    // Resolve one entry point; `instance` is NULL_HANDLE for the ones usable before there is one
+    unsafe fn resolve<F: Copy>(get: GetInstanceProcAddrFn, instance: Handle, name: &CStr) -> io::Result<F> {
+        let mut function = None;
+        let result = get(instance, name.as_ptr(), &mut function);
+        match function {
+            // SAFETY: F is the entry point's function pointer type, as declared in openxr.h
+            Some(function) if result == SUCCESS => Ok(mem::transmute_copy(&function)),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, format!("{:?} unavailable ({})", name, result))),
+        }
+    }
+
+    // The entry points used here, resolved once the instance exists
+    macro_rules! entry_points {
+        ($($field:ident = $name:literal ($($arg:ty),*);)*) => {
+            struct Api {
+                $($field: unsafe extern "system" fn($($arg),*) -> XrResult,)*
+            }
+
+            impl Api {
+                unsafe fn resolve(get: GetInstanceProcAddrFn, instance: Handle) -> io::Result<Self> {
+                    Ok(Self { $($field: resolve(get, instance, $name)?,)* })
+                }
+            }
+        };
+    }
+
+    entry_points! {
+        destroy_instance = c"xrDestroyInstance" (Handle);
+        get_system = c"xrGetSystem" (Handle, *const SystemGetInfo, *mut u64);
+        get_d3d11_graphics_requirements = c"xrGetD3D11GraphicsRequirementsKHR" (Handle, u64, *mut GraphicsRequirementsD3D11);
+        create_session = c"xrCreateSession" (Handle, *const SessionCreateInfo, *mut Handle);
+        destroy_session = c"xrDestroySession" (Handle);
+        poll_event = c"xrPollEvent" (Handle, *mut EventDataBuffer);
+        begin_session = c"xrBeginSession" (Handle, *const SessionBeginInfo);
+        end_session = c"xrEndSession" (Handle);
+        request_exit_session = c"xrRequestExitSession" (Handle);
+        create_reference_space = c"xrCreateReferenceSpace" (Handle, *const ReferenceSpaceCreateInfo, *mut Handle);
+        destroy_space = c"xrDestroySpace" (Handle);
+        locate_space = c"xrLocateSpace" (Handle, Handle, XrTime, *mut SpaceLocation);
+        enumerate_swapchain_formats = c"xrEnumerateSwapchainFormats" (Handle, u32, *mut u32, *mut i64);
+        create_swapchain = c"xrCreateSwapchain" (Handle, *const SwapchainCreateInfo, *mut Handle);
+        destroy_swapchain = c"xrDestroySwapchain" (Handle);
+        enumerate_swapchain_images = c"xrEnumerateSwapchainImages" (Handle, u32, *mut u32, *mut SwapchainImageD3D11);
+        acquire_swapchain_image = c"xrAcquireSwapchainImage" (Handle, *const EmptyInfo, *mut u32);
+        wait_swapchain_image = c"xrWaitSwapchainImage" (Handle, *const SwapchainImageWaitInfo);
+        release_swapchain_image = c"xrReleaseSwapchainImage" (Handle, *const EmptyInfo);
+        wait_frame = c"xrWaitFrame" (Handle, *const EmptyInfo, *mut FrameState);
+        begin_frame = c"xrBeginFrame" (Handle, *const EmptyInfo);
+        end_frame = c"xrEndFrame" (Handle, *const FrameEndInfo);
+    }
+
+    fn check(what: &str, result: XrResult) -> io::Result<XrResult> {
+        if result < 0 {
+            return Err(io::Error::other(format!("{} failed with OpenXR error {}", what, result)));
+        }
+        Ok(result)
+    }
+
+    fn library_candidates(config: &OpenXrConfig) -> Vec<String> {
+        match &config.library_path {
+            Some(path) => vec![path.clone()],
+            None => vec!["openxr_loader.dll".to_string()],
+        }
+    }
+
+    // Quad placement set since the last frame
+    #[derive(Default)]
+    struct Placement {
+        pose: Option<Posef>,
+        width_meters: Option<f32>,
+        head_locked: Option<bool>,
+    }
+
+    // What the sink, the pose source and the placement target share with the frame loop
+    #[derive(Default)]
+    struct Shared {
+        // Newest frame not yet uploaded
+        frame: Mutex<Option<QueuedFrame>>,
+        placement: Mutex<Placement>,
+        // Headset pose as of the last frame
+        hmd: Mutex<Option<Pose>>,
+        // Why the loop ended, once it has
+        failure: Mutex<Option<String>>,
+        stop: AtomicBool,
+    }
+
+    impl Shared {
+        fn check_running(&self) -> io::Result<()> {
+            match &*self.failure.lock() {
+                Some(failure) => Err(io::Error::other(failure.clone())),
+                None => Ok(()),
+            }
+        }
+    }
+
+    pub struct Layer {
+        shared: Arc<Shared>,
+        thread: Option<JoinHandle<()>>,
+        buffers: Arc<BufferPool>,
+    }
+
+    impl Layer {
+        pub fn open(config: &OpenXrConfig, buffers: Arc<BufferPool>) -> io::Result<Self> {
+            let shared = Arc::new(Shared::default());
+            let (started_tx, started_rx) = mpsc::channel();
+            let thread = {
+                let (config, shared) = (config.clone(), Arc::clone(&shared));
+                thread::Builder::new().name("openxr".to_string()).spawn(move || {
+                    let outcome = match Session::create(&config) {
+                        Ok(mut session) => {
+                            let _ = started_tx.send(Ok(()));
+                            let outcome = session.run(&shared);
+                            session.destroy();
+                            outcome
+                        }
+                        Err(e) => {
+                            let _ = started_tx.send(Err(e));
+                            return;
+                        }
+                    };
+                    let failure = match outcome {
+                        Ok(()) => "the OpenXR session ended".to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    info!("[Rust OpenXR] Overlay session closed: {}.", failure);
+                    *shared.failure.lock() = Some(failure);
+                })?
+            };
+            let started = started_rx.recv().unwrap_or_else(|_| Err(io::Error::other("the OpenXR thread exited")));
+            if let Err(e) = started {
+                let _ = thread.join();
+                return Err(e);
+            }
+            Ok(Self { shared, thread: Some(thread), buffers })
+        }
+    }
+
+    impl Drop for Layer {
+        fn drop(&mut self) {
+            self.shared.stop.store(true, Ordering::Release);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    impl FrameSink for Layer {
+        fn describe(&self) -> String {
+            "OpenXR overlay".to_string()
+        }
+
+        // Hand the frame to the frame loop, replacing one it hasn't uploaded yet
+        fn write<'a>(&'a self, frame: &'a QueuedFrame) -> SinkFuture<'a> {
+            Box::pin(async move {
+                self.shared.check_running()?;
+                if frame.header.overlay_id != DEFAULT_OVERLAY_ID {
+                    return Ok(());
+                }
+                let converted = pixel_format::packed_rgba(frame, &self.buffers);
+                *self.shared.frame.lock() = Some(converted.unwrap_or_else(|| frame.clone()));
+                Ok(())
+            })
+        }
+    }
+
+    impl PlacementTarget for Layer {
+        fn apply(&self, overlay_id: u32, change: PlacementChange) -> io::Result<()> {
+            self.shared.check_running()?;
+            if overlay_id != DEFAULT_OVERLAY_ID {
+                return Ok(());
+            }
+            let mut placement = self.shared.placement.lock();
+            match change {
+                PlacementChange::Transform(matrix) => {
+                    let pose = Pose::from_matrix(&matrix);
+                    placement.pose = Some(Posef { orientation: pose.orientation, position: pose.position });
+                }
+                PlacementChange::Width(width) => placement.width_meters = Some(width),
+                PlacementChange::Attachment(device_id) => placement.head_locked = Some(device_id == Some(DEVICE_HMD)),
+                PlacementChange::Curvature(_) | PlacementChange::Alpha(_) => {}
+            }
+            Ok(())
+        }
+    }
+
+    impl PoseSource for Layer {
+        fn describe(&self) -> String {
+            "OpenXR".to_string()
+        }
+
+        fn poll(&self) -> io::Result<Vec<Pose>> {
+            self.shared.check_running()?;
+            Ok(self.shared.hmd.lock().take().into_iter().collect())
+        }
+    }
+
+    struct Swapchain {
+        handle: Handle,
+        width: u32,
+        height: u32,
+        images: Vec<ID3D11Texture2D>,
+        // Whether an image has been released yet, which a layer needs to show anything
+        ready: bool,
+    }
+
+    // Everything owned by the frame loop's thread
+    struct Session {
+        api: Api,
+        instance: Handle,
+        session: Handle,
+        space: Handle,
+        view_space: Handle,
+        quad_pose: Posef,
+        // Whether the quad is placed in the view space instead
+        head_locked: bool,
+        format: i64,
+        width_meters: f32,
+        swapchain: Option<Swapchain>,
+        running: bool,
+        // The device the session was created with, which has to outlive it
+        _device: ID3D11Device,
+        context: ID3D11DeviceContext,
+        // Keeps the entry points above valid
+        _library: Library,
+    }
+
+    impl Session {
+        fn create(config: &OpenXrConfig) -> io::Result<Self> {
+            let library = load(config)?;
+            // SAFETY: the declarations above match openxr.h; every handle created here is destroyed in
+            // destroy() (or on the error paths) before the library is unloaded
+            unsafe {
+                let get = *library
+                    .get::<GetInstanceProcAddrFn>(b"xrGetInstanceProcAddr\0")
+                    .map_err(|e| io::Error::new(io::ErrorKind::Unsupported, e.to_string()))?;
+                let extensions = instance_extensions(get)?;
+                for required in [EXTX_OVERLAY, KHR_D3D11_ENABLE] {
+                    if !extensions.iter().any(|extension| extension.as_c_str() == required) {
+                        return Err(io::Error::new(io::ErrorKind::Unsupported, format!("the OpenXR runtime doesn't support {:?}", required)));
+                    }
+                }
+
+                let create_instance: unsafe extern "system" fn(*const InstanceCreateInfo, *mut Handle) -> XrResult =
+                    resolve(get, NULL_HANDLE, c"xrCreateInstance")?;
+                let mut application_info = ApplicationInfo {
+                    application_name: [0; 128],
+                    application_version: 1,
+                    engine_name: [0; 128],
+                    engine_version: 0,
+                    api_version: API_VERSION,
+                };
+                application_info.application_name[..APPLICATION_NAME.len()].copy_from_slice(APPLICATION_NAME);
+                let enabled = [EXTX_OVERLAY.as_ptr(), KHR_D3D11_ENABLE.as_ptr()];
+                let info = InstanceCreateInfo {
+                    ty: TYPE_INSTANCE_CREATE_INFO,
+                    next: ptr::null(),
+                    create_flags: 0,
+                    application_info,
+                    enabled_api_layer_count: 0,
+                    enabled_api_layer_names: ptr::null(),
+                    enabled_extension_count: enabled.len() as u32,
+                    enabled_extension_names: enabled.as_ptr(),
+                };
+                let mut instance = NULL_HANDLE;
+                check("xrCreateInstance", create_instance(&info, &mut instance))?;
+                let api = match Api::resolve(get, instance) {
+                    Ok(api) => api,
+                    Err(e) => {
+                        if let Ok(destroy) = resolve::<unsafe extern "system" fn(Handle) -> XrResult>(get, instance, c"xrDestroyInstance") {
+                            destroy(instance);
+                        }
+                        return Err(e);
+                    }
+                };
+                match Self::create_session(&api, instance, config) {
+                    Ok((session, device, context)) => {
+                        let mut this = Self {
+                            api,
+                            instance,
+                            session,
+                            space: NULL_HANDLE,
+                            view_space: NULL_HANDLE,
+                            quad_pose: IDENTITY,
+                            head_locked: false,
+                            format: 0,
+                            width_meters: config.width_meters,
+                            swapchain: None,
+                            running: false,
+                            _device: device,
+                            context,
+                            _library: library,
+                        };
+                        if let Err(e) = this.create_spaces_and_format() {
+                            this.destroy();
+                            return Err(e);
+                        }
+                        info!("[Rust OpenXR] Overlay session created, {} m wide.", config.width_meters);
+                        Ok(this)
+                    }
+                    Err(e) => {
+                        (api.destroy_instance)(instance);
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        // An overlay session on a D3D11 device of the adapter the runtime asks for
+        unsafe fn create_session(api: &Api, instance: Handle, config: &OpenXrConfig) -> io::Result<(Handle, ID3D11Device, ID3D11DeviceContext)> {
+            let get_info = SystemGetInfo { ty: TYPE_SYSTEM_GET_INFO, next: ptr::null(), form_factor: FORM_FACTOR_HEAD_MOUNTED_DISPLAY };
+            let mut system_id = 0;
+            check("xrGetSystem", (api.get_system)(instance, &get_info, &mut system_id))?;
+            let mut requirements =
+                GraphicsRequirementsD3D11 { ty: TYPE_GRAPHICS_REQUIREMENTS_D3D11_KHR, next: ptr::null_mut(), adapter_luid: LUID::default(), min_feature_level: 0 };
+            check("xrGetD3D11GraphicsRequirementsKHR", (api.get_d3d11_graphics_requirements)(instance, system_id, &mut requirements))?;
+
+            let factory: IDXGIFactory4 = CreateDXGIFactory1().map_err(io::Error::other)?;
+            let adapter: IDXGIAdapter = factory.EnumAdapterByLuid(requirements.adapter_luid).map_err(io::Error::other)?;
+            let (mut device, mut context) = (None, None);
+            D3D11CreateDevice(
+                &adapter,
+                D3D_DRIVER_TYPE_UNKNOWN,
+                HMODULE::default(),
+                D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                None,
+                D3D11_SDK_VERSION,
+                Some(&mut device),
+                None,
+                Some(&mut context),
+            )
+            .map_err(io::Error::other)?;
+            let (Some(device), Some(context)) = (device, context) else {
+                return Err(io::Error::other("D3D11CreateDevice returned no device"));
+            };
+
+            let overlay = SessionCreateInfoOverlay {
+                ty: TYPE_SESSION_CREATE_INFO_OVERLAY_EXTX,
+                next: ptr::null(),
+                create_flags: 0,
+                session_layers_placement: config.placement,
+            };
+            let binding = GraphicsBindingD3D11 { ty: TYPE_GRAPHICS_BINDING_D3D11_KHR, next: (&overlay as *const SessionCreateInfoOverlay).cast(), device: device.as_raw() };
+            let info = SessionCreateInfo { ty: TYPE_SESSION_CREATE_INFO, next: (&binding as *const GraphicsBindingD3D11).cast(), create_flags: 0, system_id };
+            let mut session = NULL_HANDLE;
+            check("xrCreateSession", (api.create_session)(instance, &info, &mut session))?;
+            Ok((session, device, context))
+        }
+
+        // Stage space if the runtime has one, else local; and a swapchain format we can upload RGBA to
+        unsafe fn create_spaces_and_format(&mut self) -> io::Result<()> {
+            let space_info = |ty| ReferenceSpaceCreateInfo { ty: TYPE_REFERENCE_SPACE_CREATE_INFO, next: ptr::null(), reference_space_type: ty, pose_in_reference_space: IDENTITY };
+            let height = if check("xrCreateReferenceSpace", (self.api.create_reference_space)(self.session, &space_info(REFERENCE_SPACE_STAGE), &mut self.space)).is_ok() {
+                QUAD_STAGE_HEIGHT
+            } else {
+                check("xrCreateReferenceSpace", (self.api.create_reference_space)(self.session, &space_info(REFERENCE_SPACE_LOCAL), &mut self.space))?;
+                0.0
+            };
+            self.quad_pose = Posef { orientation: IDENTITY.orientation, position: [0.0, height, -QUAD_DISTANCE] };
+            check("xrCreateReferenceSpace", (self.api.create_reference_space)(self.session, &space_info(REFERENCE_SPACE_VIEW), &mut self.view_space))?;
+
+            let mut count = 0;
+            check("xrEnumerateSwapchainFormats", (self.api.enumerate_swapchain_formats)(self.session, 0, &mut count, ptr::null_mut()))?;
+            let mut formats = vec![0i64; count as usize];
+            check("xrEnumerateSwapchainFormats", (self.api.enumerate_swapchain_formats)(self.session, count, &mut count, formats.as_mut_ptr()))?;
+            self.format = SWAPCHAIN_FORMATS
+                .into_iter()
+                .find(|format| formats.contains(format))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "the OpenXR runtime has no RGBA8 swapchain format"))?;
+            Ok(())
+        }
+
+        // The frame loop, until the session ends or the layer is dropped
+        fn run(&mut self, shared: &Shared) -> io::Result<()> {
+            let mut exit_requested = false;
+            loop {
+                if shared.stop.load(Ordering::Acquire) && !exit_requested {
+                    if !self.running {
+                        return Ok(());
+                    }
+                    // SAFETY: the session is live; the runtime answers with STOPPING and then EXITING
+                    check("xrRequestExitSession", unsafe { (self.api.request_exit_session)(self.session) })?;
+                    exit_requested = true;
+                }
+                if !self.poll_events()? {
+                    return Ok(());
+                }
+                if !self.running {
+                    thread::sleep(IDLE_INTERVAL);
+                    continue;
+                }
+                self.frame(shared)?;
+            }
+        }
+
+        // Handle pending events; false once the session is over
+        fn poll_events(&mut self) -> io::Result<bool> {
+            loop {
+                let mut event = EventDataBuffer { ty: TYPE_EVENT_DATA_BUFFER, next: ptr::null(), varying: [0; 4000] };
+                // SAFETY: `event` is a valid XrEventDataBuffer
+                let result = check("xrPollEvent", unsafe { (self.api.poll_event)(self.instance, &mut event) })?;
+                if result == EVENT_UNAVAILABLE {
+                    return Ok(true);
+                }
+                match event.ty {
+                    TYPE_EVENT_DATA_INSTANCE_LOSS_PENDING => return Ok(false),
+                    TYPE_EVENT_DATA_SESSION_STATE_CHANGED => {
+                        // SAFETY: the runtime wrote an XrEventDataSessionStateChanged into the buffer
+                        let changed = unsafe { &*(&event as *const EventDataBuffer).cast::<EventDataSessionStateChanged>() };
+                        match changed.state {
+                            SESSION_STATE_READY => {
+                                let info = SessionBeginInfo { ty: TYPE_SESSION_BEGIN_INFO, next: ptr::null(), primary_view_configuration_type: VIEW_CONFIGURATION_PRIMARY_STEREO };
+                                // SAFETY: the session is live and ready
+                                check("xrBeginSession", unsafe { (self.api.begin_session)(self.session, &info) })?;
+                                self.running = true;
+                                info!("[Rust OpenXR] Overlay session running.");
+                            }
+                            SESSION_STATE_STOPPING => {
+                                // SAFETY: the session is live and stopping
+                                check("xrEndSession", unsafe { (self.api.end_session)(self.session) })?;
+                                self.running = false;
+                            }
+                            SESSION_STATE_EXITING | SESSION_STATE_LOSS_PENDING => return Ok(false),
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // One pass of wait, begin, upload and end
+        fn frame(&mut self, shared: &Shared) -> io::Result<()> {
+            let mut state = FrameState { ty: TYPE_FRAME_STATE, next: ptr::null_mut(), predicted_display_time: 0, predicted_display_period: 0, should_render: 0 };
+            // SAFETY: the session is running; every struct passed is valid for its call
+            unsafe {
+                check("xrWaitFrame", (self.api.wait_frame)(self.session, &EmptyInfo::new(TYPE_FRAME_WAIT_INFO), &mut state))?;
+                check("xrBeginFrame", (self.api.begin_frame)(self.session, &EmptyInfo::new(TYPE_FRAME_BEGIN_INFO)))?;
+            }
+            if let Some(pose) = self.locate_hmd(state.predicted_display_time) {
+                *shared.hmd.lock() = Some(pose);
+            }
+            {
+                let mut placement = shared.placement.lock();
+                self.quad_pose = placement.pose.take().unwrap_or(self.quad_pose);
+                self.width_meters = placement.width_meters.take().unwrap_or(self.width_meters);
+                self.head_locked = placement.head_locked.take().unwrap_or(self.head_locked);
+            }
+            let frame = shared.frame.lock().take();
+            if let Some(frame) = frame {
+                if let Err(e) = self.upload(&frame) {
+                    warn!("[Rust OpenXR] Couldn't upload a {}x{} frame: {}", frame.header.width, frame.header.height, e);
+                }
+            }
+
+            let quad = self.swapchain.as_ref().filter(|swapchain| swapchain.ready && state.should_render != 0).map(|swapchain| CompositionLayerQuad {
+                ty: TYPE_COMPOSITION_LAYER_QUAD,
+                next: ptr::null(),
+                layer_flags: LAYER_BLEND_TEXTURE_SOURCE_ALPHA,
+                space: if self.head_locked { self.view_space } else { self.space },
+                eye_visibility: 0,
+                sub_image: SwapchainSubImage { swapchain: swapchain.handle, image_rect: [0, 0, swapchain.width as i32, swapchain.height as i32], image_array_index: 0 },
+                pose: self.quad_pose,
+                size: [self.width_meters, self.width_meters * swapchain.height as f32 / swapchain.width as f32],
+            });
+            let layers: Vec<*const CompositionLayerQuad> = quad.iter().map(|quad| quad as *const CompositionLayerQuad).collect();
+            let info = FrameEndInfo {
+                ty: TYPE_FRAME_END_INFO,
+                next: ptr::null(),
+                display_time: state.predicted_display_time,
+                environment_blend_mode: ENVIRONMENT_BLEND_MODE_OPAQUE,
+                layer_count: layers.len() as u32,
+                layers: layers.as_ptr(),
+            };
+            // SAFETY: `layers` points at `quad`, which outlives the call
+            check("xrEndFrame", unsafe { (self.api.end_frame)(self.session, &info) })?;
+            Ok(())
+        }
+
+        fn locate_hmd(&self, time: XrTime) -> Option<Pose> {
+            let mut location = SpaceLocation { ty: TYPE_SPACE_LOCATION, next: ptr::null_mut(), location_flags: 0, pose: IDENTITY };
+            // SAFETY: both spaces are live
+            let result = unsafe { (self.api.locate_space)(self.view_space, self.space, time, &mut location) };
+            let valid = LOCATION_ORIENTATION_VALID | LOCATION_POSITION_VALID;
+            (result == SUCCESS && location.location_flags & valid == valid).then(|| Pose {
+                device_id: DEVICE_HMD,
+                position: location.pose.position,
+                orientation: location.pose.orientation,
+                timestamp_us: crate::protocol::timestamp_us(),
+                ..Pose::default()
+            })
+        }
+
+        // Copy a packed RGBA frame into the next swapchain image, recreating the swapchain for a new size
+        fn upload(&mut self, frame: &QueuedFrame) -> io::Result<()> {
+            let (width, height) = (frame.header.width, frame.header.height);
+            if self.swapchain.as_ref().is_none_or(|swapchain| swapchain.width != width || swapchain.height != height) {
+                self.destroy_swapchain();
+                self.swapchain = Some(self.create_swapchain(width, height)?);
+            }
+            let Some(swapchain) = &mut self.swapchain else {
+                return Ok(());
+            };
+            let mut index = 0;
+            // SAFETY: the swapchain is live; the image is written only between wait and release, and `frame`
+            // holds height rows of width * 4 bytes
+            unsafe {
+                check("xrAcquireSwapchainImage", (self.api.acquire_swapchain_image)(swapchain.handle, &EmptyInfo::new(TYPE_SWAPCHAIN_IMAGE_ACQUIRE_INFO), &mut index))?;
+                let wait = SwapchainImageWaitInfo { ty: TYPE_SWAPCHAIN_IMAGE_WAIT_INFO, next: ptr::null(), timeout: INFINITE_DURATION };
+                check("xrWaitSwapchainImage", (self.api.wait_swapchain_image)(swapchain.handle, &wait))?;
+                if let Some(image) = swapchain.images.get(index as usize) {
+                    self.context.UpdateSubresource(image, 0, None, frame.pixels.as_ptr().cast(), width * 4, 0);
+                }
+                check("xrReleaseSwapchainImage", (self.api.release_swapchain_image)(swapchain.handle, &EmptyInfo::new(TYPE_SWAPCHAIN_IMAGE_RELEASE_INFO)))?;
+            }
+            swapchain.ready = true;
+            Ok(())
+        }
+
+        fn create_swapchain(&self, width: u32, height: u32) -> io::Result<Swapchain> {
+            let info = SwapchainCreateInfo {
+                ty: TYPE_SWAPCHAIN_CREATE_INFO,
+                next: ptr::null(),
+                create_flags: 0,
+                usage_flags: SWAPCHAIN_USAGE_COLOR_ATTACHMENT | SWAPCHAIN_USAGE_TRANSFER_DST | SWAPCHAIN_USAGE_SAMPLED,
+                format: self.format,
+                sample_count: 1,
+                width,
+                height,
+                face_count: 1,
+                array_size: 1,
+                mip_count: 1,
+            };
+            let mut handle = NULL_HANDLE;
+            // SAFETY: the session is live and the structs are valid for each call; the textures the runtime
+            // hands out stay valid until the swapchain is destroyed, and are AddRef'd by from_raw_borrowed's clone
+            unsafe {
+                check("xrCreateSwapchain", (self.api.create_swapchain)(self.session, &info, &mut handle))?;
+                let mut count = 0;
+                let images = check("xrEnumerateSwapchainImages", (self.api.enumerate_swapchain_images)(handle, 0, &mut count, ptr::null_mut())).and_then(|_| {
+                    let mut images: Vec<SwapchainImageD3D11> = (0..count)
+                        .map(|_| SwapchainImageD3D11 { ty: TYPE_SWAPCHAIN_IMAGE_D3D11_KHR, next: ptr::null_mut(), texture: ptr::null_mut() })
+                        .collect();
+                    check("xrEnumerateSwapchainImages", (self.api.enumerate_swapchain_images)(handle, count, &mut count, images.as_mut_ptr()))?;
+                    Ok(images.iter().filter_map(|image| ID3D11Texture2D::from_raw_borrowed(&image.texture).cloned()).collect())
+                });
+                match images {
+                    Ok(images) => Ok(Swapchain { handle, width, height, images, ready: false }),
+                    Err(e) => {
+                        (self.api.destroy_swapchain)(handle);
+                        Err(e)
+                    }
+                }
+            }
+        }
+
+        fn destroy_swapchain(&mut self) {
+            if let Some(swapchain) = self.swapchain.take() {
+                drop(swapchain.images);
+                // SAFETY: nothing references the swapchain anymore
+                unsafe { (self.api.destroy_swapchain)(swapchain.handle) };
+            }
+        }
+
+        fn destroy(&mut self) {
+            self.destroy_swapchain();
+            // SAFETY: the handles aren't used after this; destroying the instance destroys anything left
+            unsafe {
+                for space in [self.space, self.view_space] {
+                    if space != NULL_HANDLE {
+                        (self.api.destroy_space)(space);
+                    }
+                }
+                (self.api.destroy_session)(self.session);
+                (self.api.destroy_instance)(self.instance);
+            }
+        }
+    }
+
+    // Names of the instance extensions the runtime offers
+    unsafe fn instance_extensions(get: GetInstanceProcAddrFn) -> io::Result<Vec<std::ffi::CString>> {
+        let enumerate: unsafe extern "system" fn(*const c_char, u32, *mut u32, *mut ExtensionProperties) -> XrResult =
+            resolve(get, NULL_HANDLE, c"xrEnumerateInstanceExtensionProperties")?;
+        let mut count = 0;
+        check("xrEnumerateInstanceExtensionProperties", enumerate(ptr::null(), 0, &mut count, ptr::null_mut()))?;
+        let mut properties: Vec<ExtensionProperties> = (0..count)
+            .map(|_| ExtensionProperties { ty: TYPE_EXTENSION_PROPERTIES, next: ptr::null_mut(), extension_name: [0; 128], extension_version: 0 })
+            .collect();
+        check("xrEnumerateInstanceExtensionProperties", enumerate(ptr::null(), count, &mut count, properties.as_mut_ptr()))?;
+        Ok(properties.iter().map(|property| CStr::from_ptr(property.extension_name.as_ptr()).to_owned()).collect())
+    }
+
+    fn load(config: &OpenXrConfig) -> io::Result<Library> {
+        let mut last_error = None;
+        for candidate in library_candidates(config) {
+            // SAFETY: loading the OpenXR loader has no side effects until an instance is created
+            match unsafe { Library::new(&candidate) } {
+                Ok(library) => return Ok(library),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let detail = last_error.map(|e| e.to_string()).unwrap_or_default();
+        Err(io::Error::new(io::ErrorKind::Unsupported, format!("OpenXR loader not found ({})", detail)))
+    }
+}
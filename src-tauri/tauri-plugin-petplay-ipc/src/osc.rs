@@ -0,0 +1,434 @@
+// --- OSC bridge ---
+// Mirrors poses and controller input to OSC over UDP, and takes them back
+// from OSC, so VRChat-style tools can be used alongside the pipes. One UDP
+// socket does both: it's bound to `listen` (or an ephemeral port if that's
+// empty) and sends to `send_to`. Addresses:
+//
+//   /tracking/trackers/head/position    f x, y, z      headset (device 0)
+//   /tracking/trackers/head/rotation    f x, y, z
+//   /tracking/trackers/<n>/position     f x, y, z      device n, 1..=8
+//   /tracking/trackers/<n>/rotation     f x, y, z
+//   /puppyweb/input/<device>/<control>  f x, f y, T/F pressed, T/F touched
+//
+// The tracking addresses are the ones VRChat takes for OSC trackers, in its
+// (Unity) coordinates: left handed, metres, rotations as Euler angles in
+// degrees applied Z, X, Y. Devices and controls go by the names of the
+// transform and controller-input events. Incoming messages on these
+// addresses are handled like poses/input from the backend; everything else
+// is passed on to the frontend as an "osc-message" event. Poses that came in
+// over OSC aren't sent back out.
+use crate::{
+    input::{self, InputEvent},
+    pose::{self, Pose, DEVICE_HMD},
+    protocol,
+    runtime::IpcRuntime,
+};
+use byteorder::{BigEndian, ByteOrder};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{net::UdpSocket, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+const TRACKING_PREFIX: &str = "/tracking/trackers/";
+const INPUT_PREFIX: &str = "/puppyweb/input/";
+// VRChat numbers its OSC trackers 1 to 8
+const MAX_TRACKER: u32 = 8;
+const MAX_PACKET_SIZE: usize = 65536;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OscConfig {
+    pub enabled: bool,
+    // Where outgoing messages go (VRChat listens on 9000)
+    pub send_to: String,
+    // Local address to receive on (VRChat sends to 9001); empty to only send
+    pub listen: String,
+    pub send_tracking: bool,
+    pub send_input: bool,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            send_to: "127.0.0.1:9000".to_string(),
+            listen: "127.0.0.1:9001".to_string(),
+            send_tracking: true,
+            send_input: true,
+        }
+    }
+}
+
+impl OscConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.send_to.parse::<SocketAddr>().map_err(|e| format!("Invalid OSC send address {:?}: {}", self.send_to, e))?;
+        if !self.listen.is_empty() {
+            self.listen.parse::<SocketAddr>().map_err(|e| format!("Invalid OSC listen address {:?}: {}", self.listen, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    String(String),
+}
+
+// A decoded message the bridge has no mapping for, as emitted in "osc-message"
+#[derive(Clone, Debug, Serialize)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+// What came in over OSC, for the caller to feed into the rest of the app
+pub enum Incoming {
+    Pose(Pose),
+    Input(InputEvent),
+    Other(OscMessage),
+}
+
+struct Running {
+    socket: Arc<UdpSocket>,
+    target: SocketAddr,
+    receiver: Option<JoinHandle<()>>,
+}
+
+struct Bridge {
+    config: OscConfig,
+    running: Option<Running>,
+    // Devices whose poses arrive over OSC; not echoed back
+    remote_devices: Arc<Mutex<HashSet<u32>>>,
+}
+
+pub struct OscBridge {
+    rt: IpcRuntime,
+    bridge: Mutex<Bridge>,
+}
+
+impl OscBridge {
+    pub fn new(rt: IpcRuntime, config: OscConfig) -> Self {
+        let bridge = Bridge { config, running: None, remote_devices: Arc::default() };
+        Self { rt, bridge: Mutex::new(bridge) }
+    }
+
+    pub fn config(&self) -> OscConfig {
+        self.bridge.lock().config.clone()
+    }
+
+    // Apply `config`, (re)opening the socket if the bridge is enabled; `on_message` gets what's received
+    pub fn apply(&self, config: OscConfig, on_message: impl Fn(Incoming) + Send + Sync + 'static) -> io::Result<()> {
+        let mut bridge = self.bridge.lock();
+        bridge.stop();
+        bridge.config = config.clone();
+        if !config.enabled {
+            return Ok(());
+        }
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+        config.validate().map_err(invalid)?;
+        let target: SocketAddr = config.send_to.parse().map_err(|e| invalid(format!("{}", e)))?;
+        let local: SocketAddr = match config.listen.as_str() {
+            // Only sending: any port of the matching address family
+            "" if target.is_ipv4() => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            "" => (Ipv6Addr::UNSPECIFIED, 0).into(),
+            listen => listen.parse().map_err(|e| invalid(format!("{}", e)))?,
+        };
+        let socket = std::net::UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        let socket = {
+            let _runtime = self.rt.enter();
+            Arc::new(UdpSocket::from_std(socket)?)
+        };
+        bridge.remote_devices.lock().clear();
+        let receiver = (!config.listen.is_empty()).then(|| {
+            let remote_devices = Arc::clone(&bridge.remote_devices);
+            self.rt.spawn(receive(Arc::clone(&socket), remote_devices, on_message))
+        });
+        match config.listen.as_str() {
+            "" => info!("[Rust OSC] Bridge sending to {}.", target),
+            listen => info!("[Rust OSC] Bridge sending to {}, listening on {}.", target, listen),
+        }
+        bridge.running = Some(Running { socket, target, receiver });
+        Ok(())
+    }
+
+    pub fn send_pose(&self, pose: &Pose) {
+        let bridge = self.bridge.lock();
+        let Some(running) = bridge.running.as_ref().filter(|_| bridge.config.send_tracking) else {
+            return;
+        };
+        let Some(tracker) = tracker_name(pose.device_id) else {
+            return;
+        };
+        if bridge.remote_devices.lock().contains(&pose.device_id) {
+            return;
+        }
+        let (position, rotation) = to_unity(pose);
+        let position: Vec<OscArg> = position.into_iter().map(OscArg::Float).collect();
+        let rotation: Vec<OscArg> = rotation.into_iter().map(OscArg::Float).collect();
+        running.send(&encode_message(&format!("{}{}/position", TRACKING_PREFIX, tracker), &position));
+        running.send(&encode_message(&format!("{}{}/rotation", TRACKING_PREFIX, tracker), &rotation));
+    }
+
+    pub fn send_input(&self, event: &InputEvent) {
+        let bridge = self.bridge.lock();
+        let Some(running) = bridge.running.as_ref().filter(|_| bridge.config.send_input) else {
+            return;
+        };
+        let address = format!(
+            "{}{}/{}",
+            INPUT_PREFIX,
+            pose::device_name(event.device_id),
+            input::control_name(event.control_id)
+        );
+        let args = [OscArg::Float(event.x), OscArg::Float(event.y), OscArg::Bool(event.pressed), OscArg::Bool(event.touched)];
+        running.send(&encode_message(&address, &args));
+    }
+}
+
+impl Bridge {
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            if let Some(receiver) = running.receiver {
+                receiver.abort();
+            }
+            info!("[Rust OSC] Bridge stopped.");
+        }
+    }
+}
+
+impl Running {
+    // Never waits: a message the socket can't take right now is dropped, like UDP would
+    fn send(&self, packet: &[u8]) {
+        if let Err(e) = self.socket.try_send_to(packet, self.target) {
+            debug!("[Rust OSC] Dropped message to {}: {}", self.target, e);
+        }
+    }
+}
+
+async fn receive(socket: Arc<UdpSocket>, remote_devices: Arc<Mutex<HashSet<u32>>>, on_message: impl Fn(Incoming)) {
+    let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+    // Position and rotation come in separate messages; each one updates the device's last pose
+    let mut poses: HashMap<u32, Pose> = HashMap::new();
+    loop {
+        let length = match socket.recv_from(&mut buffer).await {
+            Ok((length, _)) => length,
+            // E.g. ICMP port unreachable for an earlier send on Windows; the socket is still fine
+            Err(e) => {
+                debug!("[Rust OSC] Receive failed: {}", e);
+                continue;
+            }
+        };
+        let mut messages = Vec::new();
+        if let Err(e) = decode_packet(&buffer[..length], &mut messages) {
+            warn!("[Rust OSC] Ignoring malformed packet: {}", e);
+            continue;
+        }
+        for message in messages {
+            if let Some((device_id, field)) = parse_tracking(&message.address) {
+                let Some(vector) = floats3(&message.args) else {
+                    continue;
+                };
+                remote_devices.lock().insert(device_id);
+                let pose = poses.entry(device_id).or_insert(Pose { device_id, orientation: [0.0, 0.0, 0.0, 1.0], ..Pose::default() });
+                from_unity(pose, field, vector);
+                on_message(Incoming::Pose(Pose { timestamp_us: protocol::timestamp_us(), ..*pose }));
+            } else if let Some(event) = parse_input(&message) {
+                on_message(Incoming::Input(event));
+            } else {
+                on_message(Incoming::Other(message));
+            }
+        }
+    }
+}
+
+// --- Address mapping ---
+
+fn tracker_name(device_id: u32) -> Option<String> {
+    match device_id {
+        DEVICE_HMD => Some("head".to_string()),
+        1..=MAX_TRACKER => Some(device_id.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TrackingField {
+    Position,
+    Rotation,
+}
+
+fn parse_tracking(address: &str) -> Option<(u32, TrackingField)> {
+    let (tracker, field) = address.strip_prefix(TRACKING_PREFIX)?.split_once('/')?;
+    let device_id = match tracker {
+        "head" => DEVICE_HMD,
+        number => number.parse().ok().filter(|n| (1..=MAX_TRACKER).contains(n))?,
+    };
+    let field = match field {
+        "position" => TrackingField::Position,
+        "rotation" => TrackingField::Rotation,
+        _ => return None,
+    };
+    Some((device_id, field))
+}
+
+fn parse_input(message: &OscMessage) -> Option<InputEvent> {
+    let (device, control) = message.address.strip_prefix(INPUT_PREFIX)?.split_once('/')?;
+    let float = |index: usize| match message.args.get(index) {
+        Some(OscArg::Float(value)) => *value,
+        Some(OscArg::Int(value)) => *value as f32,
+        _ => 0.0,
+    };
+    let flag = |index: usize| match message.args.get(index) {
+        Some(OscArg::Bool(value)) => *value,
+        Some(OscArg::Int(value)) => *value != 0,
+        _ => false,
+    };
+    Some(InputEvent {
+        device_id: pose::device_id_from_name(device)?,
+        control_id: input::control_id_from_name(control)?,
+        x: float(0),
+        y: float(1),
+        pressed: flag(2),
+        touched: flag(3),
+        timestamp_us: protocol::timestamp_us(),
+    })
+}
+
+fn floats3(args: &[OscArg]) -> Option<[f32; 3]> {
+    match args {
+        [OscArg::Float(x), OscArg::Float(y), OscArg::Float(z), ..] => Some([*x, *y, *z]),
+        _ => None,
+    }
+}
+
+// --- Coordinates ---
+// Poses are right handed (-Z forward); Unity is left handed (+Z forward), so Z flips
+
+fn to_unity(pose: &Pose) -> ([f32; 3], [f32; 3]) {
+    let [px, py, pz] = pose.position;
+    let [qx, qy, qz, qw] = pose.orientation;
+    // Mirroring Z negates the rotation about X and Y
+    let (x, y, z, w) = (-qx, -qy, qz, qw);
+    // Euler angles of R = Ry * Rx * Rz
+    let sin_pitch = (-2.0 * (y * z - w * x)).clamp(-1.0, 1.0);
+    let pitch = sin_pitch.asin();
+    let (yaw, roll) = if sin_pitch.abs() < 0.9999 {
+        ((2.0 * (x * z + w * y)).atan2(1.0 - 2.0 * (x * x + y * y)), (2.0 * (x * y + w * z)).atan2(1.0 - 2.0 * (x * x + z * z)))
+    } else {
+        // Looking straight up or down, yaw and roll turn about the same axis: put it all in yaw
+        ((-2.0 * (x * z - w * y)).atan2(1.0 - 2.0 * (y * y + z * z)), 0.0)
+    };
+    ([px, py, -pz], [pitch.to_degrees(), yaw.to_degrees(), roll.to_degrees()])
+}
+
+fn from_unity(pose: &mut Pose, field: TrackingField, [a, b, c]: [f32; 3]) {
+    match field {
+        TrackingField::Position => pose.position = [a, b, -c],
+        TrackingField::Rotation => {
+            let half = |degrees: f32| (degrees.to_radians() / 2.0).sin_cos();
+            let ((sx, cx), (sy, cy), (sz, cz)) = (half(a), half(b), half(c));
+            // q = qy * qx * qz
+            let x = cy * sx * cz + sy * cx * sz;
+            let y = sy * cx * cz - cy * sx * sz;
+            let z = cy * cx * sz - sy * sx * cz;
+            let w = cy * cx * cz + sy * sx * sz;
+            pose.orientation = [-x, -y, z, w];
+        }
+    }
+}
+
+// --- Wire format ---
+// Strings are NUL terminated and padded to 4 bytes; numbers are big endian
+
+fn push_string(packet: &mut Vec<u8>, value: &str) {
+    packet.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    packet.resize(packet.len() + padding, 0);
+}
+
+fn encode_message(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    push_string(&mut packet, address);
+    let tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+            OscArg::Bool(true) => 'T',
+            OscArg::Bool(false) => 'F',
+            OscArg::String(_) => 's',
+        }))
+        .collect();
+    push_string(&mut packet, &tags);
+    for arg in args {
+        match arg {
+            OscArg::Int(value) => packet.extend_from_slice(&value.to_be_bytes()),
+            OscArg::Float(value) => packet.extend_from_slice(&value.to_be_bytes()),
+            OscArg::Bool(_) => {}
+            OscArg::String(value) => push_string(&mut packet, value),
+        }
+    }
+    packet
+}
+
+// Reads a padded string, returning it and the rest of the data
+fn read_string(data: &[u8]) -> Result<(&str, &[u8]), String> {
+    let end = data.iter().position(|&b| b == 0).ok_or("unterminated string")?;
+    let value = std::str::from_utf8(&data[..end]).map_err(|_| "string is not UTF-8")?;
+    let padded = (end / 4 + 1) * 4;
+    Ok((value, data.get(padded..).unwrap_or(&[])))
+}
+
+// A message, or a bundle of messages/bundles, appended to `messages`
+fn decode_packet(data: &[u8], messages: &mut Vec<OscMessage>) -> Result<(), String> {
+    if let Some(mut rest) = data.strip_prefix(b"#bundle\0") {
+        // Time tag: everything is handled as soon as it arrives
+        rest = rest.get(8..).ok_or("truncated bundle")?;
+        while !rest.is_empty() {
+            let size = rest.get(..4).map(BigEndian::read_u32).ok_or("truncated bundle element")? as usize;
+            let element = rest.get(4..4 + size).ok_or("bundle element runs past end of packet")?;
+            decode_packet(element, messages)?;
+            rest = &rest[4 + size..];
+        }
+        return Ok(());
+    }
+    let (address, rest) = read_string(data)?;
+    if !address.starts_with('/') {
+        return Err(format!("invalid address {:?}", address));
+    }
+    let (tags, mut rest) = if rest.is_empty() { (",", rest) } else { read_string(rest)? };
+    let tags = tags.strip_prefix(',').ok_or("missing type tags")?;
+    let mut args = Vec::with_capacity(tags.len());
+    for tag in tags.chars() {
+        let mut word = || -> Result<[u8; 4], String> {
+            let bytes = rest.get(..4).ok_or("truncated argument")?.try_into().unwrap();
+            rest = &rest[4..];
+            Ok(bytes)
+        };
+        args.push(match tag {
+            'i' => OscArg::Int(i32::from_be_bytes(word()?)),
+            'f' => OscArg::Float(f32::from_be_bytes(word()?)),
+            'T' => OscArg::Bool(true),
+            'F' => OscArg::Bool(false),
+            's' => {
+                let (value, after) = read_string(rest)?;
+                rest = after;
+                OscArg::String(value.to_string())
+            }
+            // Other types can't be skipped without knowing their size
+            other => return Err(format!("unsupported argument type '{}'", other)),
+        });
+    }
+    messages.push(OscMessage { address: address.to_string(), args });
+    Ok(())
+}
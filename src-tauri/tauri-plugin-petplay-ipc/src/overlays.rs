@@ -0,0 +1,178 @@
+// --- Overlays ---
+// One frame pipe can carry frames for several overlay quads. Each webview
+// window that renders an overlay calls register_overlay and gets an id; the
+// id goes into the header of every frame it sends (protocol v2) and the
+// backend is told about new and removed overlays with OverlayRegister /
+// OverlayUnregister messages so it can create or destroy the quads. Overlay 0
+// is the implicit default quad that exists without registration, which is
+// what frames from unregistered windows (and v1 backends) use.
+//
+// A window that builds a HUD out of several quads can create_overlay each
+// one with its placement up front (the device it's attached to, its
+// transform and width; see placement.rs), destroy_overlay them again and
+// list_overlays what exists. Those are register_overlay and
+// unregister_overlay plus the placement, which goes out as Control messages
+// right after the OverlayRegister; frames still pick their quad by the
+// overlay id in the frame header.
+//
+// Backend placement updates come back on the transform pipe as
+// OverlayTransform messages and are emitted only to the window that owns the
+// overlay.
+//
+// OverlayRegister payload:
+//   [0..4)  overlay id (u32 LE)
+//   [4..6)  name length (u16 LE), followed by the name (UTF-8)
+// OverlayUnregister payload:
+//   [0..4)  overlay id (u32 LE)
+// OverlayTransform payload:
+//   [0..4)  overlay id (u32 LE)
+//   [4..68) placement matrix, 16 f32 LE, row-major
+use crate::{
+    error::PipeError,
+    placement::{OverlayPlacement, PlacementChange},
+    pose,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+pub const DEFAULT_OVERLAY_ID: u32 = 0;
+pub const MAX_OVERLAY_NAME_LEN: usize = 256;
+pub const OVERLAY_TRANSFORM_SIZE: usize = 4 + 16 * 4;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Overlay {
+    pub id: u32,
+    pub name: String,
+    // Label of the window that registered it and receives its transforms
+    pub window: String,
+}
+
+impl Overlay {
+    pub fn encode_register(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; 6];
+        LittleEndian::write_u32(&mut bytes[0..4], self.id);
+        LittleEndian::write_u16(&mut bytes[4..6], self.name.len() as u16);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes
+    }
+
+    pub fn encode_unregister(&self) -> [u8; 4] {
+        let mut bytes = [0u8; 4];
+        LittleEndian::write_u32(&mut bytes, self.id);
+        bytes
+    }
+}
+
+// Placement create_overlay starts an overlay with; anything left out is the backend's default
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OverlaySettings {
+    // Device name as in transform-update events, e.g. "controller-left"; the transform is then relative to it
+    pub attached_device: Option<String>,
+    pub transform: Option<[f32; 16]>,
+    pub width_meters: Option<f32>,
+}
+
+impl OverlaySettings {
+    // The settings as validated placement changes
+    pub fn changes(&self) -> Result<Vec<PlacementChange>, String> {
+        let attachment = match &self.attached_device {
+            Some(name) => Some(PlacementChange::Attachment(Some(
+                pose::device_id_from_name(name).ok_or_else(|| format!("Unknown device {:?}", name))?,
+            ))),
+            None => None,
+        };
+        let changes: Vec<PlacementChange> =
+            [attachment, self.transform.map(PlacementChange::Transform), self.width_meters.map(PlacementChange::Width)].into_iter().flatten().collect();
+        changes.iter().try_for_each(PlacementChange::validate)?;
+        Ok(changes)
+    }
+}
+
+// Entry of list_overlays and the result of create_overlay
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayInfo {
+    #[serde(flatten)]
+    pub overlay: Overlay,
+    pub placement: OverlayPlacement,
+}
+
+pub struct OverlayRegistry {
+    next_id: AtomicU32,
+    overlays: Mutex<BTreeMap<u32, Overlay>>,
+}
+
+impl Default for OverlayRegistry {
+    fn default() -> Self {
+        Self {
+            // 0 is the default overlay and never handed out
+            next_id: AtomicU32::new(DEFAULT_OVERLAY_ID + 1),
+            overlays: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl OverlayRegistry {
+    pub fn register(&self, name: &str, window: &str) -> Result<Overlay, PipeError> {
+        if name.is_empty() || name.len() > MAX_OVERLAY_NAME_LEN {
+            return Err(PipeError::InvalidArgument(format!(
+                "Overlay name must be between 1 and {} bytes",
+                MAX_OVERLAY_NAME_LEN
+            )));
+        }
+        let overlay = Overlay {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            window: window.to_string(),
+        };
+        self.overlays.lock().insert(overlay.id, overlay.clone());
+        Ok(overlay)
+    }
+
+    pub fn unregister(&self, id: u32) -> Option<Overlay> {
+        self.overlays.lock().remove(&id)
+    }
+
+    // Drop everything a closed window registered
+    pub fn unregister_window(&self, window: &str) -> Vec<Overlay> {
+        let mut overlays = self.overlays.lock();
+        let ids: Vec<u32> = overlays.values().filter(|overlay| overlay.window == window).map(|overlay| overlay.id).collect();
+        ids.iter().filter_map(|id| overlays.remove(id)).collect()
+    }
+
+    pub fn get(&self, id: u32) -> Option<Overlay> {
+        self.overlays.lock().get(&id).cloned()
+    }
+
+    // Overlay used for frames from this window when none is given explicitly:
+    // the first one it registered, or the default overlay
+    pub fn default_for_window(&self, window: &str) -> u32 {
+        self.overlays
+            .lock()
+            .values()
+            .find(|overlay| overlay.window == window)
+            .map_or(DEFAULT_OVERLAY_ID, |overlay| overlay.id)
+    }
+
+    // All registered overlays in id order, re-announced after a reconnect
+    pub fn all(&self) -> Vec<Overlay> {
+        self.overlays.lock().values().cloned().collect()
+    }
+}
+
+// Overlay id and placement matrix of an OverlayTransform message
+pub fn decode_transform(payload: &[u8]) -> Result<(u32, [f32; 16]), String> {
+    if payload.len() != OVERLAY_TRANSFORM_SIZE {
+        return Err(format!("{} byte payload (expected {})", payload.len(), OVERLAY_TRANSFORM_SIZE));
+    }
+    let mut matrix = [0.0; 16];
+    LittleEndian::read_f32_into(&payload[4..], &mut matrix);
+    Ok((LittleEndian::read_u32(&payload[0..4]), matrix))
+}
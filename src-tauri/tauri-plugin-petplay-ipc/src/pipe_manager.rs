@@ -0,0 +1,87 @@
+// --- Named frame pipe connections ---
+// The main FramePipeState owns the default frame, transform and input pipes.
+// Multi-window setups can open extra frame pipes by name at runtime, each
+// with its own queue, writer, handshake and reconnect loop, optionally bound
+// to a window: frames sent from a bound window go to its own connection
+// instead of the default frame pipe.
+use crate::{connection::PipeStatus, error::PipeError, FramePipeState};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const MAX_CONNECTIONS: usize = 32;
+
+struct NamedPipe {
+    // Label of the window whose frames go here, if any
+    window: Option<String>,
+    state: FramePipeState,
+}
+
+// One entry of list_pipe_connections
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedPipeStatus {
+    pub name: String,
+    pub window: Option<String>,
+    pub frame: PipeStatus,
+}
+
+#[derive(Default)]
+pub struct PipeManager {
+    pipes: Mutex<BTreeMap<String, NamedPipe>>,
+}
+
+impl PipeManager {
+    pub fn insert(&self, name: &str, window: Option<String>, state: FramePipeState) -> Result<(), PipeError> {
+        let mut pipes = self.pipes.lock();
+        if pipes.contains_key(name) {
+            return Err(PipeError::InvalidArgument(format!("Connection {:?} already exists", name)));
+        }
+        if let Some(window) = &window {
+            if let Some((other, _)) = pipes.iter().find(|(_, pipe)| pipe.window.as_ref() == Some(window)) {
+                return Err(PipeError::InvalidArgument(format!("Window {} is already bound to connection {:?}", window, other)));
+            }
+        }
+        if pipes.len() >= MAX_CONNECTIONS {
+            return Err(PipeError::InvalidArgument(format!("At most {} named connections are allowed", MAX_CONNECTIONS)));
+        }
+        pipes.insert(name.to_string(), NamedPipe { window, state });
+        Ok(())
+    }
+
+    // Forget the connection; the caller shuts it down
+    pub fn remove(&self, name: &str) -> Option<FramePipeState> {
+        self.pipes.lock().remove(name).map(|pipe| pipe.state)
+    }
+
+    // Everything still open, for app exit
+    pub fn remove_all(&self) -> Vec<FramePipeState> {
+        std::mem::take(&mut *self.pipes.lock()).into_values().map(|pipe| pipe.state).collect()
+    }
+
+    // Connection bound to this window, if any
+    pub fn for_window(&self, window: &str) -> Option<FramePipeState> {
+        self.pipes
+            .lock()
+            .values()
+            .find(|pipe| pipe.window.as_deref() == Some(window))
+            .map(|pipe| pipe.state.clone())
+    }
+
+    // Every connection by name, e.g. for the metrics endpoint
+    pub fn states(&self) -> Vec<(String, FramePipeState)> {
+        self.pipes.lock().iter().map(|(name, pipe)| (name.clone(), pipe.state.clone())).collect()
+    }
+
+    pub fn list(&self) -> Vec<NamedPipeStatus> {
+        self.pipes
+            .lock()
+            .iter()
+            .map(|(name, pipe)| NamedPipeStatus {
+                name: name.clone(),
+                window: pipe.window.clone(),
+                frame: pipe.state.connections.snapshot().frame,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,332 @@
+// --- Pipe access control ---
+// Who may be at the other end of a local pipe. Pipes the app serves itself
+// (the mock backend) are created so only the current user (plus any
+// configured accounts) can open them: on Windows with a security descriptor
+// granting access to just those SIDs instead of the default one, on Linux and
+// macOS by making the socket file readable and writable by its owner only.
+// On connect, the process at the other end of a pipe is checked the same way
+// (verifyPeer): on Windows its token's user or one of its groups has to be
+// the current user or an allowed SID, on Linux and macOS it has to run as the
+// same user. TCP endpoints have no peer process to check; the auth token
+// handshake (auth.rs) still applies to them.
+//
+//   [pipeSecurity]
+//   allowedSids = ["S-1-5-18"]   # SID strings or SDDL aliases like "BA"; Windows only
+//   verifyPeer = true
+use crate::transport::{PlatformTransport, ServerTransport, Stream};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PipeSecurity {
+    // Accounts allowed besides the current user
+    pub allowed_sids: Vec<String>,
+    // Check the account of the process at the other end of every local pipe
+    pub verify_peer: bool,
+}
+
+impl Default for PipeSecurity {
+    fn default() -> Self {
+        Self { allowed_sids: Vec::new(), verify_peer: true }
+    }
+}
+
+impl PipeSecurity {
+    pub fn validate(&self) -> Result<(), String> {
+        platform::validate(&self.allowed_sids)
+    }
+
+    // Check the process serving a pipe the app just connected to
+    pub fn verify_server(&self, stream: &PlatformTransport) -> io::Result<()> {
+        match stream {
+            Stream::Local(pipe) if self.verify_peer => platform::verify_server(pipe, &self.allowed_sids),
+            _ => Ok(()),
+        }
+    }
+
+    // Check the process that connected to a pipe the mock backend serves
+    pub fn verify_client(&self, stream: &ServerTransport) -> io::Result<()> {
+        match stream {
+            Stream::Local(pipe) if self.verify_peer => platform::verify_client(pipe, &self.allowed_sids),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn not_allowed(peer: &str, pid: Option<u32>, account: &str) -> io::Error {
+    let pid = pid.map_or(String::new(), |pid| format!(" (pid {})", pid));
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("{}{} runs as {}, which isn't allowed on this pipe", peer, pid, account))
+}
+
+// --- Windows: security descriptors and process tokens ---
+#[cfg(windows)]
+pub use platform::SecurityAttributes;
+
+#[cfg(windows)]
+mod platform {
+    use super::{not_allowed, PipeSecurity};
+    use std::{ffi::c_void, io, os::windows::io::AsRawHandle, ptr, slice};
+    use tokio::net::windows::named_pipe::{NamedPipeClient, NamedPipeServer};
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, LocalFree, HANDLE},
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, ConvertStringSidToSidW,
+                SDDL_REVISION_1,
+            },
+            EqualSid, GetTokenInformation, TokenGroups, TokenUser, PSECURITY_DESCRIPTOR, PSID, SECURITY_ATTRIBUTES,
+            TOKEN_GROUPS, TOKEN_INFORMATION_CLASS, TOKEN_QUERY, TOKEN_USER,
+        },
+        System::{
+            Pipes::{GetNamedPipeClientProcessId, GetNamedPipeServerProcessId},
+            Threading::{GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION},
+        },
+    };
+
+    // Groups that only count for deny entries (e.g. Administrators in a non-elevated token)
+    const SE_GROUP_USE_FOR_DENY_ONLY: u32 = 0x10;
+
+    pub fn validate(allowed_sids: &[String]) -> Result<(), String> {
+        for sid in allowed_sids {
+            Sid::parse(sid).map_err(|e| format!("Invalid SID {:?} in allowedSids: {}", sid, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn verify_server(pipe: &NamedPipeClient, allowed_sids: &[String]) -> io::Result<()> {
+        let mut pid = 0;
+        // SAFETY: the handle belongs to the open pipe and `pid` is a valid out pointer
+        if unsafe { GetNamedPipeServerProcessId(pipe.as_raw_handle() as HANDLE, &mut pid) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        verify_process("Pipe server", pid, allowed_sids)
+    }
+
+    pub fn verify_client(pipe: &NamedPipeServer, allowed_sids: &[String]) -> io::Result<()> {
+        let mut pid = 0;
+        // SAFETY: the handle belongs to the connected pipe and `pid` is a valid out pointer
+        if unsafe { GetNamedPipeClientProcessId(pipe.as_raw_handle() as HANDLE, &mut pid) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        verify_process("Pipe client", pid, allowed_sids)
+    }
+
+    fn verify_process(peer: &str, pid: u32, allowed_sids: &[String]) -> io::Result<()> {
+        // SAFETY: plain call; a null handle is checked below
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let process = Handle(process);
+        let peer_account = Account::of_process(process.0)?;
+        // SAFETY: the pseudo handle of the current process needs no closing
+        let own_account = Account::of_process(unsafe { GetCurrentProcess() })?;
+        // SAFETY: both SIDs point into token information buffers that outlive the call
+        if unsafe { EqualSid(peer_account.user(), own_account.user()) } != 0 {
+            return Ok(());
+        }
+        let allowed = allowed_sids.iter().map(|sid| Sid::parse(sid)).collect::<io::Result<Vec<_>>>()?;
+        let permitted = peer_account.sids().any(|sid| {
+            // SAFETY: both SIDs stay alive for the duration of the comparison
+            allowed.iter().any(|allowed| unsafe { EqualSid(sid, allowed.0) } != 0)
+        });
+        if permitted {
+            Ok(())
+        } else {
+            Err(not_allowed(peer, Some(pid), &sid_string(peer_account.user()).unwrap_or_else(|_| "an unknown account".to_string())))
+        }
+    }
+
+    // Security attributes for pipes the app creates: full access for the current user and the allowed SIDs only
+    pub struct SecurityAttributes {
+        descriptor: PSECURITY_DESCRIPTOR,
+        attributes: SECURITY_ATTRIBUTES,
+    }
+
+    // SAFETY: the descriptor is an immutable LocalAlloc'd buffer owned by this value
+    unsafe impl Send for SecurityAttributes {}
+    unsafe impl Sync for SecurityAttributes {}
+
+    impl SecurityAttributes {
+        pub fn new(security: &PipeSecurity) -> io::Result<Self> {
+            // SAFETY: the pseudo handle of the current process needs no closing
+            let own_account = Account::of_process(unsafe { GetCurrentProcess() })?;
+            // Protected DACL (no inherited entries) with one generic-all entry per account
+            let mut sddl = format!("D:P(A;;GA;;;{})", sid_string(own_account.user())?);
+            for sid in &security.allowed_sids {
+                // Parsing first keeps anything but a SID out of the SDDL string
+                Sid::parse(sid)?;
+                sddl.push_str(&format!("(A;;GA;;;{})", sid));
+            }
+            let sddl = wide(&sddl);
+            let mut descriptor = ptr::null_mut();
+            // SAFETY: `sddl` is NUL terminated and `descriptor` is a valid out pointer
+            let converted = unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(sddl.as_ptr(), SDDL_REVISION_1, &mut descriptor, ptr::null_mut())
+            };
+            if converted == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let attributes = SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                lpSecurityDescriptor: descriptor,
+                bInheritHandle: 0,
+            };
+            Ok(Self { descriptor, attributes })
+        }
+
+        // For ServerOptions::create_with_security_attributes_raw; valid while self is
+        pub fn as_raw(&self) -> *mut c_void {
+            &self.attributes as *const SECURITY_ATTRIBUTES as *mut c_void
+        }
+    }
+
+    impl Drop for SecurityAttributes {
+        fn drop(&mut self) {
+            // SAFETY: the descriptor was allocated by ConvertStringSecurityDescriptorToSecurityDescriptorW
+            unsafe { LocalFree(self.descriptor) };
+        }
+    }
+
+    struct Handle(HANDLE);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            // SAFETY: the handle is owned by self
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    // A SID parsed from its string form
+    struct Sid(PSID);
+
+    impl Sid {
+        fn parse(text: &str) -> io::Result<Self> {
+            let text = wide(text);
+            let mut sid = ptr::null_mut();
+            // SAFETY: `text` is NUL terminated and `sid` is a valid out pointer
+            if unsafe { ConvertStringSidToSidW(text.as_ptr(), &mut sid) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self(sid))
+        }
+    }
+
+    impl Drop for Sid {
+        fn drop(&mut self) {
+            // SAFETY: the SID was allocated by ConvertStringSidToSidW
+            unsafe { LocalFree(self.0) };
+        }
+    }
+
+    // User and group SIDs from a process token
+    struct Account {
+        // Token information is read into u64s so the structs in it are aligned
+        user: Vec<u64>,
+        groups: Vec<u64>,
+    }
+
+    impl Account {
+        fn of_process(process: HANDLE) -> io::Result<Self> {
+            let mut token = ptr::null_mut();
+            // SAFETY: `process` is a valid process handle and `token` a valid out pointer
+            if unsafe { OpenProcessToken(process, TOKEN_QUERY, &mut token) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let token = Handle(token);
+            Ok(Self { user: token_information(token.0, TokenUser)?, groups: token_information(token.0, TokenGroups)? })
+        }
+
+        fn user(&self) -> PSID {
+            // SAFETY: the buffer holds the TOKEN_USER GetTokenInformation wrote
+            unsafe { (*self.user.as_ptr().cast::<TOKEN_USER>()).User.Sid }
+        }
+
+        // The user, then every group that counts for granting access
+        fn sids(&self) -> impl Iterator<Item = PSID> + '_ {
+            // SAFETY: the buffer holds the TOKEN_GROUPS GetTokenInformation wrote, GroupCount entries long
+            let groups = unsafe {
+                let groups = self.groups.as_ptr().cast::<TOKEN_GROUPS>();
+                slice::from_raw_parts((*groups).Groups.as_ptr(), (*groups).GroupCount as usize)
+            };
+            let groups = groups.iter().filter(|group| group.Attributes & SE_GROUP_USE_FOR_DENY_ONLY == 0).map(|group| group.Sid);
+            std::iter::once(self.user()).chain(groups)
+        }
+    }
+
+    fn token_information(token: HANDLE, class: TOKEN_INFORMATION_CLASS) -> io::Result<Vec<u64>> {
+        let mut size = 0;
+        // SAFETY: a size query with no buffer; fails with ERROR_INSUFFICIENT_BUFFER and sets `size`
+        unsafe { GetTokenInformation(token, class, ptr::null_mut(), 0, &mut size) };
+        let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+        // SAFETY: `buffer` holds at least `size` bytes
+        if unsafe { GetTokenInformation(token, class, buffer.as_mut_ptr().cast(), size, &mut size) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(buffer)
+    }
+
+    fn sid_string(sid: PSID) -> io::Result<String> {
+        let mut string = ptr::null_mut();
+        // SAFETY: `sid` is a valid SID and `string` a valid out pointer
+        if unsafe { ConvertSidToStringSidW(sid, &mut string) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the string is NUL terminated and freed right after copying it
+        unsafe {
+            let length = (0..).take_while(|&i| *string.add(i) != 0).count();
+            let text = String::from_utf16_lossy(slice::from_raw_parts(string, length));
+            LocalFree(string.cast());
+            Ok(text)
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+}
+
+// --- Linux / macOS: socket file permissions and peer credentials ---
+#[cfg(unix)]
+mod platform {
+    use super::not_allowed;
+    use std::{fs, io, os::unix::fs::PermissionsExt, path::Path};
+    use tokio::net::UnixStream;
+
+    pub fn validate(allowed_sids: &[String]) -> Result<(), String> {
+        if allowed_sids.is_empty() {
+            Ok(())
+        } else {
+            Err("allowedSids only applies to Windows named pipes".to_string())
+        }
+    }
+
+    // Owner-only access to a socket the app created
+    pub fn restrict(endpoint: &str) -> io::Result<()> {
+        fs::set_permissions(Path::new(endpoint), fs::Permissions::from_mode(0o600))
+    }
+
+    pub fn verify_server(stream: &UnixStream, _allowed_sids: &[String]) -> io::Result<()> {
+        verify_peer("Socket server", stream)
+    }
+
+    pub fn verify_client(stream: &UnixStream, _allowed_sids: &[String]) -> io::Result<()> {
+        verify_peer("Socket client", stream)
+    }
+
+    fn verify_peer(peer: &str, stream: &UnixStream) -> io::Result<()> {
+        let credentials = stream.peer_cred()?;
+        // SAFETY: geteuid can't fail
+        let own_uid = unsafe { libc::geteuid() };
+        if credentials.uid() == own_uid {
+            Ok(())
+        } else {
+            let pid = credentials.pid().and_then(|pid| u32::try_from(pid).ok());
+            Err(not_allowed(peer, pid, &format!("uid {}", credentials.uid())))
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use platform::restrict;
@@ -0,0 +1,169 @@
+// --- Pixel formats ---
+// Canvas readbacks aren't always tightly packed RGBA: some paths hand out
+// BGRA, RGB or NV12, with rows padded to an alignment. send_frame_data takes
+// the layout from optional request headers ("pixel-format", "stride") and the
+// frame goes out as is to backends that accept it (protocol v3 and the
+// matching capability bit, see handshake.rs). For everyone else, and for the
+// video encoder, the writer converts it to tightly packed RGBA8 first.
+//
+// NV12: a full-resolution Y plane followed by a half-resolution plane of
+// interleaved U/V samples, both with the same stride; BT.601 limited range.
+use crate::{buffer_pool::BufferPool, frame_queue::QueuedFrame, handshake, protocol::FrameHeader};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum PixelFormat {
+    #[default]
+    Rgba8 = 0,
+    Bgra8 = 1,
+    Rgb8 = 2,
+    Nv12 = 3,
+}
+
+impl TryFrom<u8> for PixelFormat {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, u8> {
+        match value {
+            0 => Ok(Self::Rgba8),
+            1 => Ok(Self::Bgra8),
+            2 => Ok(Self::Rgb8),
+            3 => Ok(Self::Nv12),
+            other => Err(other),
+        }
+    }
+}
+
+impl PixelFormat {
+    // Name used in the "pixel-format" request header and in get_backend_info
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgba8 => "rgba8",
+            Self::Bgra8 => "bgra8",
+            Self::Rgb8 => "rgb8",
+            Self::Nv12 => "nv12",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Rgba8, Self::Bgra8, Self::Rgb8, Self::Nv12].into_iter().find(|format| format.name() == name)
+    }
+
+    // Capability bit a backend sets to take this format directly; RGBA8 is always accepted
+    pub fn capability(self) -> u32 {
+        match self {
+            Self::Rgba8 => 0,
+            Self::Bgra8 => handshake::CAP_FORMAT_BGRA8,
+            Self::Rgb8 => handshake::CAP_FORMAT_RGB8,
+            Self::Nv12 => handshake::CAP_FORMAT_NV12,
+        }
+    }
+
+    // Bytes of one tightly packed row (of the Y plane for NV12)
+    pub fn min_stride(self, width: u32) -> u64 {
+        let width = width as u64;
+        match self {
+            Self::Rgba8 | Self::Bgra8 => width * 4,
+            Self::Rgb8 => width * 3,
+            Self::Nv12 => width,
+        }
+    }
+
+    // Total bytes of a frame with rows `stride` bytes apart
+    pub fn frame_size(self, height: u32, stride: u32) -> u64 {
+        let (height, stride) = (height as u64, stride as u64);
+        match self {
+            Self::Nv12 => stride * height + stride * height.div_ceil(2),
+            _ => stride * height,
+        }
+    }
+}
+
+// Check a frame layout from the webview; returns the stride to use (0 in the headers means packed)
+pub fn check_layout(format: PixelFormat, width: u32, height: u32, stride: u32) -> Result<u32, String> {
+    let min_stride = format.min_stride(width);
+    if format == PixelFormat::Nv12 && (!width.is_multiple_of(2) || !height.is_multiple_of(2)) {
+        return Err(format!("NV12 frames need an even width and height, got {}x{}", width, height));
+    }
+    let stride = if stride == 0 { min_stride } else { stride as u64 };
+    if stride < min_stride {
+        return Err(format!("Stride {} is less than a {}-pixel {} row ({} bytes)", stride, width, format.name(), min_stride));
+    }
+    u32::try_from(stride).map_err(|_| format!("Row of {} bytes is too long", stride))
+}
+
+// Whether a backend with these capabilities can take the frame without conversion
+pub fn backend_accepts(header: &FrameHeader, protocol_version: u8, capabilities: u32) -> bool {
+    if header.pixel_format == PixelFormat::Rgba8 && header.stride as u64 == PixelFormat::Rgba8.min_stride(header.width) {
+        return true;
+    }
+    // Formats and strides only exist in the v3 frame header
+    let packed = header.stride as u64 == header.pixel_format.min_stride(header.width);
+    protocol_version >= 3
+        && capabilities & header.pixel_format.capability() == header.pixel_format.capability()
+        && (packed || capabilities & handshake::CAP_ROW_STRIDE != 0)
+}
+
+// The frame as tightly packed RGBA8 (in a buffer from `buffers`), or None if it already is
+pub fn packed_rgba(frame: &QueuedFrame, buffers: &Arc<BufferPool>) -> Option<QueuedFrame> {
+    let header = frame.header;
+    if header.pixel_format == PixelFormat::Rgba8 && header.stride as u64 == PixelFormat::Rgba8.min_stride(header.width) {
+        return None;
+    }
+    let mut rgba = buffers.take(header.width as usize * header.height as usize * 4);
+    to_rgba(header.pixel_format, header.width, header.height, header.stride as usize, &frame.pixels, &mut rgba);
+    let header = FrameHeader { pixel_format: PixelFormat::Rgba8, stride: header.width * 4, ..header };
+    Some(QueuedFrame { header, pixels: rgba.into_bytes() })
+}
+
+// Convert a frame (already checked against check_layout) to tightly packed RGBA8 in `rgba`, replacing its contents
+pub fn to_rgba(format: PixelFormat, width: u32, height: u32, stride: usize, pixels: &[u8], rgba: &mut Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    rgba.clear();
+    rgba.resize(width * height * 4, 0);
+    match format {
+        PixelFormat::Rgba8 => {
+            for (row, out) in pixels.chunks(stride).zip(rgba.chunks_exact_mut(width * 4)) {
+                out.copy_from_slice(&row[..width * 4]);
+            }
+        }
+        PixelFormat::Bgra8 => {
+            for (row, out) in pixels.chunks(stride).zip(rgba.chunks_exact_mut(width * 4)) {
+                for (bgra, out) in row[..width * 4].chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+                    out.copy_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+                }
+            }
+        }
+        PixelFormat::Rgb8 => {
+            for (row, out) in pixels.chunks(stride).zip(rgba.chunks_exact_mut(width * 4)) {
+                for (rgb, out) in row[..width * 3].chunks_exact(3).zip(out.chunks_exact_mut(4)) {
+                    out.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+        }
+        PixelFormat::Nv12 => {
+            let (luma, chroma) = pixels.split_at(stride * height);
+            for (y, out_row) in rgba.chunks_exact_mut(width * 4).enumerate() {
+                let luma_row = &luma[y * stride..];
+                let chroma_row = &chroma[(y / 2) * stride..];
+                for (x, (&luma, out)) in luma_row[..width].iter().zip(out_row.chunks_exact_mut(4)).enumerate() {
+                    let uv = (x / 2) * 2;
+                    let rgb = yuv_to_rgb(luma, chroma_row[uv], chroma_row[uv + 1]);
+                    out.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+        }
+    }
+}
+
+// BT.601 limited range, in 8.8 fixed point
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = (y as i32 - 16) * 298;
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+    let clamp = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+    [clamp(c + 409 * e), clamp(c - 100 * d - 208 * e), clamp(c + 516 * d)]
+}
@@ -0,0 +1,198 @@
+// --- Overlay placement ---
+// Where an overlay quad sits and how it looks, set from the web UI with
+// set_overlay_transform, set_overlay_width, set_overlay_curvature and
+// set_overlay_alpha (and set_overlay_attachment, or all at once for a new
+// overlay with create_overlay, see overlays.rs) instead of backend-side
+// configuration. Each change goes
+// to the backend as a Control message (see control.rs; only backends that
+// accepted CAP_CONTROL get them) and to the in-process runtime layers that
+// are running (see pose_source.rs), which apply what they can: OpenVR all of
+// it for overlay 0, OpenXR the transform, the width and attaching to the
+// headset.
+//
+// The latest value of each setting is kept per overlay, sent again to a
+// backend that connects later and applied to a layer when it starts, the way
+// registered overlays are re-announced. A backend still moves overlays on
+// its own (OverlayTransform, see overlays.rs); this doesn't track those.
+//
+// Transforms are row-major 4x4 matrices in the backend's tracking space
+// (standing space for OpenVR), or relative to the tracked device the overlay
+// is attached to, checked like transforms from the pipe (see
+// validation.rs). Curvature goes from 0 (flat) to 1 (a full circle around
+// the viewer) as in OpenVR, alpha from 0 (invisible) to 1 (opaque).
+use crate::{control::ControlMessage, validation};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{collections::BTreeMap, io, sync::Arc};
+
+pub const MAX_WIDTH_METERS: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlacementChange {
+    Transform([f32; 16]),
+    Width(f32),
+    Curvature(f32),
+    Alpha(f32),
+    // Device id as in poses, or None for the tracking space
+    Attachment(Option<u32>),
+}
+
+impl PlacementChange {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::Transform(matrix) => validation::check_matrix(&matrix).map_err(|e| format!("Invalid overlay transform: {}", e)),
+            Self::Width(width) if !(width > 0.0 && width <= MAX_WIDTH_METERS) => {
+                Err(format!("Overlay width must be above 0 and at most {} meters", MAX_WIDTH_METERS))
+            }
+            Self::Curvature(curvature) if !(0.0..=1.0).contains(&curvature) => Err("Overlay curvature must be between 0 and 1".to_string()),
+            Self::Alpha(alpha) if !(0.0..=1.0).contains(&alpha) => Err("Overlay alpha must be between 0 and 1".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_control(self, overlay_id: u32) -> ControlMessage {
+        match self {
+            Self::Transform(matrix) => ControlMessage::SetOverlayTransform { overlay_id, matrix },
+            Self::Width(width_meters) => ControlMessage::SetOverlayWidth { overlay_id, width_meters },
+            Self::Curvature(curvature) => ControlMessage::SetOverlayCurvature { overlay_id, curvature },
+            Self::Alpha(alpha) => ControlMessage::SetOverlayAlpha { overlay_id, alpha },
+            Self::Attachment(device_id) => ControlMessage::SetOverlayAttachment { overlay_id, device_id },
+        }
+    }
+}
+
+// What has been set for one overlay; None is whatever the backend or layer uses by default
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayPlacement {
+    pub transform: Option<[f32; 16]>,
+    pub width_meters: Option<f32>,
+    pub curvature: Option<f32>,
+    pub alpha: Option<f32>,
+    // Device the transform is relative to, as in poses
+    pub attached_device_id: Option<u32>,
+}
+
+impl OverlayPlacement {
+    pub fn apply(&mut self, change: PlacementChange) {
+        match change {
+            PlacementChange::Transform(matrix) => self.transform = Some(matrix),
+            PlacementChange::Width(width) => self.width_meters = Some(width),
+            PlacementChange::Curvature(curvature) => self.curvature = Some(curvature),
+            PlacementChange::Alpha(alpha) => self.alpha = Some(alpha),
+            PlacementChange::Attachment(device_id) => self.attached_device_id = device_id,
+        }
+    }
+
+    // The settings as changes, to replay them somewhere new
+    pub fn changes(&self) -> Vec<PlacementChange> {
+        // Attached first, so the transform is taken relative to the device
+        let changes = [
+            self.attached_device_id.map(|device_id| PlacementChange::Attachment(Some(device_id))),
+            self.transform.map(PlacementChange::Transform),
+            self.width_meters.map(PlacementChange::Width),
+            self.curvature.map(PlacementChange::Curvature),
+            self.alpha.map(PlacementChange::Alpha),
+        ];
+        changes.into_iter().flatten().collect()
+    }
+}
+
+// An in-process runtime layer that can place overlays itself
+pub trait PlacementTarget: Send + Sync + 'static {
+    // Apply a validated change; an error is returned to the command that made it. Overlays the
+    // layer doesn't show and changes it can't make are ignored.
+    fn apply(&self, overlay_id: u32, change: PlacementChange) -> io::Result<()>;
+}
+
+// Placement per overlay id, and the layers it's applied to, keyed like sinks
+#[derive(Default)]
+pub struct PlacementState {
+    overlays: Mutex<BTreeMap<u32, OverlayPlacement>>,
+    targets: Mutex<BTreeMap<String, Arc<dyn PlacementTarget>>>,
+}
+
+impl PlacementState {
+    // Remember the change and apply it to every layer
+    pub fn set(&self, overlay_id: u32, change: PlacementChange) -> io::Result<()> {
+        self.overlays.lock().entry(overlay_id).or_default().apply(change);
+        let targets: Vec<_> = self.targets.lock().values().cloned().collect();
+        targets.iter().try_for_each(|target| target.apply(overlay_id, change))
+    }
+
+    pub fn get(&self, overlay_id: u32) -> OverlayPlacement {
+        self.overlays.lock().get(&overlay_id).copied().unwrap_or_default()
+    }
+
+    pub fn all(&self) -> Vec<(u32, OverlayPlacement)> {
+        self.overlays.lock().iter().map(|(id, placement)| (*id, *placement)).collect()
+    }
+
+    // Forget an overlay that was unregistered
+    pub fn remove(&self, overlay_id: u32) {
+        self.overlays.lock().remove(&overlay_id);
+    }
+
+    // Start applying changes to a layer, after bringing it up to date
+    pub fn attach(&self, id: &str, target: Arc<dyn PlacementTarget>) -> io::Result<()> {
+        for (overlay_id, placement) in self.all() {
+            placement.changes().into_iter().try_for_each(|change| target.apply(overlay_id, change))?;
+        }
+        self.targets.lock().insert(id.to_string(), target);
+        Ok(())
+    }
+
+    pub fn detach(&self, id: &str) {
+        self.targets.lock().remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(u32, PlacementChange)>>);
+
+    impl PlacementTarget for Recorder {
+        fn apply(&self, overlay_id: u32, change: PlacementChange) -> io::Result<()> {
+            self.0.lock().push((overlay_id, change));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn placements_are_remembered_and_replayed_to_new_layers() {
+        let mut identity = [0.0; 16];
+        identity[0] = 1.0;
+        identity[5] = 1.0;
+        identity[10] = 1.0;
+        identity[15] = 1.0;
+        assert!(PlacementChange::Transform(identity).validate().is_ok());
+        assert!(PlacementChange::Transform([0.0; 16]).validate().is_err());
+        assert!(PlacementChange::Width(0.0).validate().is_err());
+        assert!(PlacementChange::Curvature(f32::NAN).validate().is_err());
+        assert!(PlacementChange::Alpha(1.5).validate().is_err());
+
+        let state = PlacementState::default();
+        state.set(0, PlacementChange::Width(2.0)).unwrap();
+        state.set(0, PlacementChange::Width(1.5)).unwrap();
+        state.set(3, PlacementChange::Alpha(0.5)).unwrap();
+        assert_eq!(state.get(0), OverlayPlacement { width_meters: Some(1.5), ..OverlayPlacement::default() });
+
+        let recorder = Arc::new(Recorder::default());
+        state.attach("test", recorder.clone()).unwrap();
+        assert_eq!(*recorder.0.lock(), [(0, PlacementChange::Width(1.5)), (3, PlacementChange::Alpha(0.5))]);
+        state.set(0, PlacementChange::Transform(identity)).unwrap();
+        assert_eq!(recorder.0.lock().len(), 3);
+        state.detach("test");
+        state.remove(3);
+        state.set(0, PlacementChange::Curvature(0.2)).unwrap();
+        assert_eq!(recorder.0.lock().len(), 3);
+        assert_eq!(state.all().len(), 1);
+
+        // Replayed attachment first, so the transform lands relative to the device
+        let attached = OverlayPlacement { transform: Some(identity), attached_device_id: Some(1), ..OverlayPlacement::default() };
+        assert_eq!(attached.changes(), [PlacementChange::Attachment(Some(1)), PlacementChange::Transform(identity)]);
+    }
+}
@@ -0,0 +1,113 @@
+// --- Playspace ---
+// The user's play area as the VR runtime knows it (the chaperone / guardian
+// boundary), for a minimap in the web UI. The backend sends a Playspace
+// message on the transform pipe after connecting and again whenever the user
+// redraws the boundary or recenters. It's validated like a transform (see
+// validation.rs), kept for get_playspace_bounds and emitted as
+// "playspace-changed" when it differs from the last one. It survives
+// reconnects: the room doesn't change because the backend restarted.
+//
+// Bounds are a polygon on the floor in standing space (meters, x to the
+// right and z towards the user when facing forward, as in poses), which is
+// what a top-down map needs. The standing-zero matrix places that space in
+// the tracking space poses arrive in, so devices can be drawn on it.
+//
+// Playspace payload (little endian):
+//   [0..64)   standing zero, 4x4 row-major matrix (16 x f32)
+//   [64..72)  play area width (x) and depth (z) in meters, the largest
+//             rectangle inside the bounds; 0 x 0 if unknown (2 x f32)
+//   [72..76)  number of bounds points (u32)
+//   [76..)    bounds points in order around the polygon, x and z (2 x f32 each)
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+pub const MIN_PLAYSPACE_SIZE: usize = 76;
+const POINT_SIZE: usize = 8;
+// Runtimes simplify their boundaries to a few dozen points; this is plenty
+const MAX_POINTS: usize = 4096;
+
+// Payload of "playspace-changed" and the result of get_playspace_bounds
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Playspace {
+    pub standing_zero: [f32; 16],
+    pub play_area: [f32; 2],
+    pub bounds: Vec<[f32; 2]>,
+}
+
+impl Playspace {
+    pub fn decode(payload: &[u8]) -> Result<Self, String> {
+        if payload.len() < MIN_PLAYSPACE_SIZE {
+            return Err(format!("playspace message of {} bytes is shorter than {}", payload.len(), MIN_PLAYSPACE_SIZE));
+        }
+        let count = LittleEndian::read_u32(&payload[72..76]) as usize;
+        if count > MAX_POINTS {
+            return Err(format!("{} bounds points is more than {}", count, MAX_POINTS));
+        }
+        if payload.len() != MIN_PLAYSPACE_SIZE + count * POINT_SIZE {
+            return Err(format!("playspace message of {} bytes doesn't hold {} points", payload.len(), count));
+        }
+        let mut playspace = Self { bounds: vec![[0.0; 2]; count], ..Self::default() };
+        LittleEndian::read_f32_into(&payload[0..64], &mut playspace.standing_zero);
+        LittleEndian::read_f32_into(&payload[64..72], &mut playspace.play_area);
+        for (point, bytes) in playspace.bounds.iter_mut().zip(payload[MIN_PLAYSPACE_SIZE..].chunks_exact(POINT_SIZE)) {
+            LittleEndian::read_f32_into(bytes, point);
+        }
+        Ok(playspace)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = vec![0u8; MIN_PLAYSPACE_SIZE + self.bounds.len() * POINT_SIZE];
+        LittleEndian::write_f32_into(&self.standing_zero, &mut payload[0..64]);
+        LittleEndian::write_f32_into(&self.play_area, &mut payload[64..72]);
+        LittleEndian::write_u32(&mut payload[72..76], self.bounds.len() as u32);
+        for (point, bytes) in self.bounds.iter().zip(payload[MIN_PLAYSPACE_SIZE..].chunks_exact_mut(POINT_SIZE)) {
+            LittleEndian::write_f32_into(point, bytes);
+        }
+        payload
+    }
+}
+
+// The last valid playspace the backend sent
+#[derive(Default)]
+pub struct PlayspaceState {
+    current: Mutex<Option<Playspace>>,
+}
+
+impl PlayspaceState {
+    pub fn get(&self) -> Option<Playspace> {
+        self.current.lock().clone()
+    }
+
+    // Returns whether it differs from the one before
+    pub fn update(&self, playspace: Playspace) -> bool {
+        self.current.lock().replace(playspace.clone()).as_ref() != Some(&playspace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playspaces_round_trip_and_report_changes() {
+        let mut standing_zero = [0.0; 16];
+        standing_zero[0] = 1.0;
+        standing_zero[5] = 1.0;
+        standing_zero[10] = 1.0;
+        standing_zero[15] = 1.0;
+        let playspace = Playspace { standing_zero, play_area: [2.0, 1.5], bounds: vec![[-1.0, -0.75], [1.0, -0.75], [1.0, 0.75], [-1.0, 0.75]] };
+        let payload = playspace.encode();
+        assert_eq!(Playspace::decode(&payload).unwrap(), playspace);
+        assert!(Playspace::decode(&payload[..payload.len() - 1]).is_err());
+        let mut too_many = payload.clone();
+        LittleEndian::write_u32(&mut too_many[72..76], MAX_POINTS as u32 + 1);
+        assert!(Playspace::decode(&too_many).is_err());
+
+        let state = PlayspaceState::default();
+        assert!(state.update(playspace.clone()));
+        assert!(!state.update(playspace.clone()));
+        assert!(state.update(Playspace { play_area: [0.0, 0.0], ..playspace }));
+    }
+}
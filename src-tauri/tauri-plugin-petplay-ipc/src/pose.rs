@@ -0,0 +1,162 @@
+// --- Tracked device poses ---
+// Payload of a Poses message (transform pipe, backend -> app): one or more
+// variable-length records back to back. Each record starts with its own
+// length so the backend can append fields later without breaking older apps;
+// anything past the fields below is skipped.
+//
+//   [0..2)   record length in bytes, not counting these two (u16)
+//   [2..6)   device id (u32)
+//   [6..18)  position x, y, z in meters (f32)
+//   [18..34) orientation quaternion x, y, z, w (f32)
+//   [34..46) linear velocity x, y, z in m/s (f32)
+//   [46..58) angular velocity x, y, z in rad/s (f32)
+//   [58..66) timestamp, microseconds since the Unix epoch (u64)
+//
+// All values little endian.
+use byteorder::{ByteOrder, LittleEndian};
+use serde::Serialize;
+use std::borrow::Cow;
+
+pub const RECORD_LENGTH_SIZE: usize = 2;
+// Smallest record body this version understands
+pub const MIN_RECORD_SIZE: usize = 64;
+
+// Well-known device ids. The backend assigns these to the headset and the
+// controllers by role; anything else (trackers, base stations) is numbered from 3.
+pub const DEVICE_HMD: u32 = 0;
+pub const DEVICE_CONTROLLER_LEFT: u32 = 1;
+pub const DEVICE_CONTROLLER_RIGHT: u32 = 2;
+
+// Name used in per-device event names, e.g. "transform-update:controller-left"
+pub fn device_name(device_id: u32) -> Cow<'static, str> {
+    match device_id {
+        DEVICE_HMD => Cow::Borrowed("hmd"),
+        DEVICE_CONTROLLER_LEFT => Cow::Borrowed("controller-left"),
+        DEVICE_CONTROLLER_RIGHT => Cow::Borrowed("controller-right"),
+        other => Cow::Owned(format!("device-{}", other)),
+    }
+}
+
+// Inverse of device_name, for commands that take a device name from the frontend
+pub fn device_id_from_name(name: &str) -> Option<u32> {
+    match name {
+        "hmd" => Some(DEVICE_HMD),
+        "controller-left" => Some(DEVICE_CONTROLLER_LEFT),
+        "controller-right" => Some(DEVICE_CONTROLLER_RIGHT),
+        other => other.strip_prefix("device-")?.parse().ok(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pose {
+    pub device_id: u32,
+    pub position: [f32; 3],
+    pub orientation: [f32; 4],
+    pub linear_velocity: [f32; 3],
+    pub angular_velocity: [f32; 3],
+    pub timestamp_us: u64,
+}
+
+fn read_vec3(bytes: &[u8]) -> [f32; 3] {
+    [
+        LittleEndian::read_f32(&bytes[0..4]),
+        LittleEndian::read_f32(&bytes[4..8]),
+        LittleEndian::read_f32(&bytes[8..12]),
+    ]
+}
+
+impl Pose {
+    // Decode one record body (without its length prefix)
+    fn decode(body: &[u8]) -> Self {
+        Self {
+            device_id: LittleEndian::read_u32(&body[0..4]),
+            position: read_vec3(&body[4..16]),
+            orientation: [
+                LittleEndian::read_f32(&body[16..20]),
+                LittleEndian::read_f32(&body[20..24]),
+                LittleEndian::read_f32(&body[24..28]),
+                LittleEndian::read_f32(&body[28..32]),
+            ],
+            linear_velocity: read_vec3(&body[32..44]),
+            angular_velocity: read_vec3(&body[44..56]),
+            timestamp_us: LittleEndian::read_u64(&body[56..64]),
+        }
+    }
+
+    // Record body (without the length prefix), the inverse of decode
+    pub fn encode(&self) -> [u8; MIN_RECORD_SIZE] {
+        let mut body = [0u8; MIN_RECORD_SIZE];
+        LittleEndian::write_u32(&mut body[0..4], self.device_id);
+        LittleEndian::write_f32_into(&self.position, &mut body[4..16]);
+        LittleEndian::write_f32_into(&self.orientation, &mut body[16..32]);
+        LittleEndian::write_f32_into(&self.linear_velocity, &mut body[32..44]);
+        LittleEndian::write_f32_into(&self.angular_velocity, &mut body[44..56]);
+        LittleEndian::write_u64(&mut body[56..64], self.timestamp_us);
+        body
+    }
+
+    // Row-major 4x4 transform (rotation in the upper 3x3, translation in the
+    // last column), the same layout the single-matrix Transform message uses
+    #[rustfmt::skip]
+    pub fn to_matrix(self) -> [f32; 16] {
+        let [x, y, z, w] = self.orientation;
+        let [px, py, pz] = self.position;
+        [
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), px,
+            2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), py,
+            2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), pz,
+            0.0, 0.0, 0.0, 1.0,
+        ]
+    }
+
+    // Pose from a legacy Transform matrix (row-major, see to_matrix). The
+    // matrix carries no velocity, device or time, so those stay zero.
+    pub fn from_matrix(m: &[f32; 16]) -> Self {
+        let (m00, m01, m02) = (m[0], m[1], m[2]);
+        let (m10, m11, m12) = (m[4], m[5], m[6]);
+        let (m20, m21, m22) = (m[8], m[9], m[10]);
+        let trace = m00 + m11 + m22;
+        // Pick the largest diagonal term to keep the division well conditioned
+        let orientation = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [(m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s, 0.25 * s]
+        } else if m00 > m11 && m00 > m22 {
+            let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+            [0.25 * s, (m01 + m10) / s, (m02 + m20) / s, (m21 - m12) / s]
+        } else if m11 > m22 {
+            let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+            [(m01 + m10) / s, 0.25 * s, (m12 + m21) / s, (m02 - m20) / s]
+        } else {
+            let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+            [(m02 + m20) / s, (m12 + m21) / s, 0.25 * s, (m10 - m01) / s]
+        };
+        Self {
+            position: [m[3], m[7], m[11]],
+            orientation,
+            ..Self::default()
+        }
+    }
+}
+
+// Split a Poses payload into records. A truncated or undersized record means
+// the rest of the payload can't be trusted, so the whole message is rejected.
+pub fn decode_records(payload: &[u8]) -> Result<Vec<Pose>, String> {
+    let mut poses = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        if rest.len() < RECORD_LENGTH_SIZE {
+            return Err(format!("{} trailing bytes after last record", rest.len()));
+        }
+        let length = LittleEndian::read_u16(&rest[..RECORD_LENGTH_SIZE]) as usize;
+        if length < MIN_RECORD_SIZE {
+            return Err(format!("record of {} bytes is shorter than {}", length, MIN_RECORD_SIZE));
+        }
+        let body = rest
+            .get(RECORD_LENGTH_SIZE..RECORD_LENGTH_SIZE + length)
+            .ok_or_else(|| format!("record of {} bytes runs past end of payload", length))?;
+        poses.push(Pose::decode(body));
+        rest = &rest[RECORD_LENGTH_SIZE + length..];
+    }
+    Ok(poses)
+}
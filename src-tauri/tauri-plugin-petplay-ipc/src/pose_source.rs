@@ -0,0 +1,79 @@
+// --- Pose sources ---
+// Poses normally arrive from the backend on the transform pipe. An in-process
+// VR runtime layer (openvr.rs, openxr.rs) has no pipe to push them through, so it
+// implements PoseSource instead and is polled at a fixed rate by a task of
+// its own. Polled poses take the same path as poses from the pipe:
+// validation, the transform filter, prediction and the transform-update
+// events, channels and OSC. A source whose poll fails is stopped.
+//
+// Sources are started under an id, like frame sinks (see sink.rs), and a
+// source started under an id that's in use replaces the running one.
+use crate::{placement::PlacementTarget, pose::Pose, runtime::IpcRuntime, sink::FrameSink};
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, io, sync::Arc, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{error, info};
+
+pub trait PoseSource: Send + Sync + 'static {
+    // Shown in logs, e.g. "OpenVR"
+    fn describe(&self) -> String;
+
+    // The current pose of every tracked device; an error stops polling
+    fn poll(&self) -> io::Result<Vec<Pose>>;
+}
+
+// The parts of an in-process runtime layer: frames go to its sink, poses come from its source and
+// overlay placement goes to its target (see placement.rs). The layer shuts down when all are dropped.
+pub struct RuntimeLayer {
+    pub sink: Arc<dyn FrameSink>,
+    pub poses: Arc<dyn PoseSource>,
+    pub placement: Arc<dyn PlacementTarget>,
+}
+
+// Where polled poses go
+pub type PoseSink = Arc<dyn Fn(Pose) + Send + Sync>;
+
+// The running sources, keyed by id
+#[derive(Default)]
+pub struct PoseSources {
+    running: Arc<Mutex<BTreeMap<String, JoinHandle<()>>>>,
+}
+
+impl PoseSources {
+    // Poll `source` rate_hz times a second (at least once), handing every pose to `deliver`
+    pub fn start(&self, rt: &IpcRuntime, id: &str, source: Arc<dyn PoseSource>, rate_hz: u32, deliver: PoseSink) {
+        self.stop(id);
+        info!("[Rust Pose Sources] Polling {} at {} Hz as {:?}.", source.describe(), rate_hz, id);
+        let period = Duration::from_secs_f64(1.0 / rate_hz.max(1) as f64);
+        let task = rt.spawn(async move {
+            let mut ticker = interval(period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                match source.poll() {
+                    Ok(poses) => poses.into_iter().for_each(|pose| deliver(pose)),
+                    Err(e) => {
+                        error!("[Rust Pose Sources] Error polling {}: {}. Source stopped.", source.describe(), e);
+                        return;
+                    }
+                }
+            }
+        });
+        if let Some(raced) = self.running.lock().insert(id.to_string(), task) {
+            raced.abort();
+        }
+    }
+
+    // Stop polling the source started under `id`; returns whether there was one
+    pub fn stop(&self, id: &str) -> bool {
+        let Some(task) = self.running.lock().remove(id) else {
+            return false;
+        };
+        task.abort();
+        info!("[Rust Pose Sources] Stopped {:?}.", id);
+        true
+    }
+}
@@ -0,0 +1,73 @@
+// --- Pose prediction ---
+// By the time a frame rendered from a pose reaches the compositor the device
+// has moved on, so the overlay trails head motion. With a prediction horizon
+// set (set_pose_prediction) every pose from a Poses message is extrapolated
+// with its own velocities to `horizon` past now: the age of the pose (its
+// timestamp vs. our clock) is made up as well. Legacy Transform messages carry
+// no velocity and pass through unchanged.
+//
+// Angular velocity is taken to be in tracking space, like OpenVR reports it.
+use crate::pose::Pose;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Longest horizon set_pose_prediction accepts; beyond this the guess is worse than the lag
+pub const MAX_HORIZON_US: u64 = 100_000;
+// Older poses are extrapolated as if they were this old; a larger gap means clocks disagree
+const MAX_POSE_AGE_US: u64 = 50_000;
+
+#[derive(Default)]
+pub struct PosePredictor {
+    // 0 turns prediction off
+    horizon_us: AtomicU64,
+}
+
+impl PosePredictor {
+    pub fn horizon_us(&self) -> u64 {
+        self.horizon_us.load(Ordering::Relaxed)
+    }
+
+    pub fn set_horizon_us(&self, horizon_us: u64) {
+        self.horizon_us.store(horizon_us.min(MAX_HORIZON_US), Ordering::Relaxed);
+    }
+
+    // The pose `horizon` after `now_us`; its timestamp becomes that time
+    pub fn predict(&self, pose: Pose, now_us: u64) -> Pose {
+        let horizon_us = self.horizon_us();
+        if horizon_us == 0 {
+            return pose;
+        }
+        let age_us = now_us.saturating_sub(pose.timestamp_us).min(MAX_POSE_AGE_US);
+        let dt = (age_us + horizon_us) as f32 / 1_000_000.0;
+        let position = [0, 1, 2].map(|i| pose.position[i] + pose.linear_velocity[i] * dt);
+        let orientation = rotate(pose.orientation, pose.angular_velocity, dt);
+        Pose { position, orientation, timestamp_us: now_us + horizon_us, ..pose }
+    }
+}
+
+// Integrate a constant angular velocity (rad/s, tracking space) over `dt` seconds
+fn rotate(q: [f32; 4], angular_velocity: [f32; 3], dt: f32) -> [f32; 4] {
+    let [wx, wy, wz] = angular_velocity;
+    let speed = (wx * wx + wy * wy + wz * wz).sqrt();
+    if speed * dt < 1e-6 {
+        return q;
+    }
+    let half = speed * dt / 2.0;
+    let s = half.sin() / speed;
+    let delta = [wx * s, wy * s, wz * s, half.cos()];
+    // World-space rotation is applied on the left
+    let product = multiply(delta, q);
+    let length = product.iter().map(|c| c * c).sum::<f32>().sqrt();
+    product.map(|c| c / length)
+}
+
+// Hamilton product of two xyzw quaternions
+fn multiply(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let [ax, ay, az, aw] = a;
+    let [bx, by, bz, bw] = b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
@@ -0,0 +1,78 @@
+// --- Frame preview ---
+// show_frame_preview opens a second window (preview.html) that mirrors the
+// frames as they leave for the backend: the writer task offers each frame
+// after it has been converted for the backend, right before it's written, so
+// the preview shows the pixels the backend receives. The window subscribes
+// with subscribe_frame_preview; each channel message is (little endian)
+//
+//   [0..4)    width (u32)
+//   [4..8)    height (u32)
+//   [8..16)   sequence number (u64)
+//   [16..)    the pixels as packed RGBA8, i.e. ready for an ImageData
+//
+// Frames are only copied for the preview while it's open, and at most
+// PREVIEW_MAX_FPS times a second so it can't hold up the pipe.
+use crate::{buffer_pool::BufferPool, frame_queue::QueuedFrame, pixel_format};
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tracing::debug;
+
+pub const PREVIEW_WINDOW_LABEL: &str = "frame-preview";
+pub const PREVIEW_PAGE: &str = "preview.html";
+pub const PREVIEW_HEADER_SIZE: usize = 16;
+const PREVIEW_MAX_FPS: u32 = 30;
+
+#[derive(Default)]
+struct Subscription {
+    channel: Option<Channel<InvokeResponseBody>>,
+    last_sent: Option<Instant>,
+}
+
+#[derive(Default)]
+pub struct FramePreview {
+    subscription: Mutex<Subscription>,
+}
+
+impl FramePreview {
+    // Replace the preview window's channel; None when the window goes away
+    pub fn set_channel(&self, channel: Option<Channel<InvokeResponseBody>>) {
+        *self.subscription.lock() = Subscription { channel, last_sent: None };
+    }
+
+    // Send `frame` to the preview if one is open and it's due for another frame
+    pub fn offer(&self, frame: &QueuedFrame, buffers: &Arc<BufferPool>) {
+        let channel = {
+            let mut subscription = self.subscription.lock();
+            let min_interval = Duration::from_secs(1) / PREVIEW_MAX_FPS;
+            if subscription.last_sent.is_some_and(|sent| sent.elapsed() < min_interval) {
+                return;
+            }
+            let Some(channel) = subscription.channel.clone() else {
+                return;
+            };
+            subscription.last_sent = Some(Instant::now());
+            channel
+        };
+        // Converted outside the lock; the writer task is the only caller anyway
+        if let Err(e) = channel.send(InvokeResponseBody::Raw(encode(frame, buffers))) {
+            debug!("[Rust Frame Preview] Preview window gone ({}), no longer mirroring frames.", e);
+            self.set_channel(None);
+        }
+    }
+}
+
+fn encode(frame: &QueuedFrame, buffers: &Arc<BufferPool>) -> Vec<u8> {
+    let converted = pixel_format::packed_rgba(frame, buffers);
+    let pixels = &converted.as_ref().unwrap_or(frame).pixels;
+    let mut message = vec![0u8; PREVIEW_HEADER_SIZE + pixels.len()];
+    LittleEndian::write_u32(&mut message[0..4], frame.header.width);
+    LittleEndian::write_u32(&mut message[4..8], frame.header.height);
+    LittleEndian::write_u64(&mut message[8..16], frame.header.sequence);
+    message[PREVIEW_HEADER_SIZE..].copy_from_slice(pixels);
+    message
+}
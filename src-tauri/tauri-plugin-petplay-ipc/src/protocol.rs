@@ -0,0 +1,378 @@
+// --- Wire protocol ---
+// Every message on every pipe is framed the same way so a reader can always
+// tell where one message ends and the next begins, even after a partial write:
+//
+//   [0..4)   magic "PPWB"
+//   [4]      protocol version
+//   [5]      message type
+//   [6..8)   flags (message specific, e.g. frame compression)
+//   [8..12)  payload length in bytes (u32, little endian)
+//   [12..)   payload
+//
+// Everything a backend sends is decoded by pure functions over byte slices
+// (decode_header, decode_message, decode_transform here, and the payload
+// decoders in pose.rs, input.rs, control.rs and friends), so they can be
+// fuzzed without a pipe; see fuzz.rs. None of them may panic on any input.
+use crate::pixel_format::PixelFormat;
+use byteorder::{ByteOrder, LittleEndian};
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub const MAGIC: [u8; 4] = *b"PPWB";
+// Range of versions this app can speak; the backend picks one in the handshake
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+// v2: frame header carries an overlay id (see overlays.rs)
+// v3: frame header carries a pixel format and row stride (see pixel_format.rs)
+pub const PROTOCOL_VERSION: u8 = 3;
+pub const HEADER_SIZE: usize = 12;
+// Generous upper bound (8K RGBA + header) so a corrupt length can't make us allocate gigabytes
+pub const MAX_PAYLOAD_SIZE: usize = 160 * 1024 * 1024;
+// Payload of a Transform message: one 4x4 matrix of f32
+pub const TRANSFORM_PAYLOAD_SIZE: usize = 16 * 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    // Frame header + pixels (frame pipe, app -> backend)
+    Frame = 1,
+    // Shared-memory frame-ready notification (frame pipe, app -> backend)
+    FrameReady = 2,
+    // Single overlay transform matrix (transform pipe, backend -> app)
+    Transform = 3,
+    // Capabilities offered by the app right after connecting (app -> backend)
+    Hello = 4,
+    // Capabilities accepted by the backend (backend -> app)
+    HelloAck = 5,
+    // Piece of an encoded H.264/HEVC elementary stream (frame pipe, app -> backend)
+    VideoChunk = 6,
+    // DXGI shared texture handle + metadata (frame pipe, app -> backend)
+    SharedTexture = 7,
+    // Variable-length tracked device pose records (transform pipe, backend -> app)
+    Poses = 8,
+    // Button/axis state changes (input pipe, backend -> app)
+    ControllerInput = 9,
+    // Controller vibration request (input pipe, app -> backend)
+    HapticPulse = 10,
+    // Keepalive request, either direction on any pipe
+    Ping = 11,
+    // Answer to a Ping, echoing its payload
+    Pong = 12,
+    // The app is exiting and about to close the pipe (app -> backend, empty payload)
+    Goodbye = 13,
+    // An overlay quad was registered (frame pipe, app -> backend)
+    OverlayRegister = 14,
+    // An overlay quad went away (frame pipe, app -> backend)
+    OverlayUnregister = 15,
+    // Placement matrix of one overlay (transform pipe, backend -> app)
+    OverlayTransform = 16,
+    // Nonce the backend has to answer with the shared token (any pipe, app -> backend, see auth.rs)
+    AuthChallenge = 17,
+    // Answer to AuthChallenge plus the backend's own nonce (backend -> app)
+    AuthResponse = 18,
+    // The app's answer to the backend's nonce (app -> backend)
+    AuthProof = 19,
+    // Out-of-band notice such as pause or a resolution change (frame pipe, either direction, see control.rs)
+    Control = 20,
+    // Sequence number of the newest frame the backend has taken (frame pipe, backend -> app, see ack.rs)
+    FrameAck = 21,
+    // Interleaved PCM with its format and overlay (audio pipe, app -> backend, see audio.rs)
+    AudioChunk = 22,
+    // Captured microphone PCM, laid out like AudioChunk (microphone pipe, app -> backend, see microphone.rs)
+    MicrophoneChunk = 23,
+    // An encoded image of a backend-rendered stream (video pipe, backend -> app, see video.rs)
+    VideoFrame = 24,
+    // File transfer, in either direction on the files pipe (see file_transfer.rs)
+    FileOffer = 25,
+    FileAccept = 26,
+    FileChunk = 27,
+    FileDone = 28,
+    // Where a controller's pointer ray hits an overlay, and VR keyboard keys (pointer pipe, backend -> app, see vr_pointer.rs)
+    VrPointer = 29,
+    VrKey = 30,
+    // Where the user is looking, from eye tracking (transform pipe, backend -> app, see gaze.rs)
+    Gaze = 31,
+    // Hand joints from hand tracking (transform pipe, backend -> app, see hands.rs)
+    HandSkeleton = 32,
+    // Battery, tracking state and firmware of tracked devices (transform pipe, backend -> app, see device_status.rs)
+    DeviceStatus = 33,
+    // Play area bounds and standing zero (transform pipe, backend -> app, see playspace.rs)
+    Playspace = 34,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = ProtocolError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Frame),
+            2 => Ok(Self::FrameReady),
+            3 => Ok(Self::Transform),
+            4 => Ok(Self::Hello),
+            5 => Ok(Self::HelloAck),
+            6 => Ok(Self::VideoChunk),
+            7 => Ok(Self::SharedTexture),
+            8 => Ok(Self::Poses),
+            9 => Ok(Self::ControllerInput),
+            10 => Ok(Self::HapticPulse),
+            11 => Ok(Self::Ping),
+            12 => Ok(Self::Pong),
+            13 => Ok(Self::Goodbye),
+            14 => Ok(Self::OverlayRegister),
+            15 => Ok(Self::OverlayUnregister),
+            16 => Ok(Self::OverlayTransform),
+            17 => Ok(Self::AuthChallenge),
+            18 => Ok(Self::AuthResponse),
+            19 => Ok(Self::AuthProof),
+            20 => Ok(Self::Control),
+            21 => Ok(Self::FrameAck),
+            22 => Ok(Self::AudioChunk),
+            23 => Ok(Self::MicrophoneChunk),
+            24 => Ok(Self::VideoFrame),
+            25 => Ok(Self::FileOffer),
+            26 => Ok(Self::FileAccept),
+            27 => Ok(Self::FileChunk),
+            28 => Ok(Self::FileDone),
+            29 => Ok(Self::VrPointer),
+            30 => Ok(Self::VrKey),
+            31 => Ok(Self::Gaze),
+            32 => Ok(Self::HandSkeleton),
+            33 => Ok(Self::DeviceStatus),
+            34 => Ok(Self::Playspace),
+            other => Err(ProtocolError::UnknownMessageType(other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub version: u8,
+    pub message_type: MessageType,
+    pub flags: u16,
+    pub length: u32,
+}
+
+#[derive(Debug)]
+pub struct Message {
+    pub header: MessageHeader,
+    pub payload: Vec<u8>,
+}
+
+// Why a message was rejected
+#[derive(Debug)]
+pub enum ProtocolError {
+    BadMagic([u8; 4]),
+    UnsupportedVersion(u8),
+    UnknownMessageType(u8),
+    PayloadTooLarge { length: usize, max: usize },
+    Io(io::Error),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic(magic) => write!(f, "bad magic bytes {:02x?}", magic),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported protocol version {} (expected {}..={})", version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION)
+            }
+            Self::UnknownMessageType(message_type) => write!(f, "unknown message type {}", message_type),
+            Self::PayloadTooLarge { length, max } => write!(f, "payload of {} bytes exceeds limit of {}", length, max),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl ProtocolError {
+    // The peer closed the stream cleanly between messages
+    pub fn is_eof(&self) -> bool {
+        matches!(self, Self::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+    }
+}
+
+pub fn encode_header(message_type: MessageType, flags: u16, length: usize) -> [u8; HEADER_SIZE] {
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4] = PROTOCOL_VERSION;
+    header[5] = message_type as u8;
+    LittleEndian::write_u16(&mut header[6..8], flags);
+    LittleEndian::write_u32(&mut header[8..12], length as u32);
+    header
+}
+
+// Header + payload in one buffer, for small messages
+pub fn encode(message_type: MessageType, flags: u16, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_SIZE + payload.len());
+    message.extend_from_slice(&encode_header(message_type, flags, payload.len()));
+    message.extend_from_slice(payload);
+    message
+}
+
+pub fn decode_header(bytes: &[u8; HEADER_SIZE]) -> Result<MessageHeader, ProtocolError> {
+    let magic = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    if magic != MAGIC {
+        return Err(ProtocolError::BadMagic(magic));
+    }
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&bytes[4]) {
+        return Err(ProtocolError::UnsupportedVersion(bytes[4]));
+    }
+    let message_type = MessageType::try_from(bytes[5])?;
+    let length = LittleEndian::read_u32(&bytes[8..12]);
+    if length as usize > MAX_PAYLOAD_SIZE {
+        return Err(ProtocolError::PayloadTooLarge { length: length as usize, max: MAX_PAYLOAD_SIZE });
+    }
+    Ok(MessageHeader {
+        version: bytes[4],
+        message_type,
+        flags: LittleEndian::read_u16(&bytes[6..8]),
+        length,
+    })
+}
+
+// Split the first message off `bytes`: the message and how many bytes it took,
+// or None if `bytes` ends before the message does. The pipe readers go through
+// read_message, which frames messages the same way straight off the stream.
+#[cfg(any(test, feature = "fuzzing"))]
+pub fn decode_message(bytes: &[u8]) -> Result<Option<(Message, usize)>, ProtocolError> {
+    let Some(header_bytes) = bytes.first_chunk::<HEADER_SIZE>() else {
+        return Ok(None);
+    };
+    let header = decode_header(header_bytes)?;
+    let end = HEADER_SIZE + header.length as usize;
+    Ok(bytes.get(HEADER_SIZE..end).map(|payload| (Message { header, payload: payload.to_vec() }, end)))
+}
+
+// The row-major 4x4 matrix of a Transform payload
+pub fn decode_transform(payload: &[u8]) -> Option<[f32; 16]> {
+    let mut matrix = [0.0; 16];
+    LittleEndian::read_f32_into(payload.get(..TRANSFORM_PAYLOAD_SIZE)?, &mut matrix);
+    Some(matrix)
+}
+
+// Read one complete message. Any error other than EOF leaves the stream
+// out of sync, so callers should drop the connection.
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message, ProtocolError> {
+    let mut header_bytes = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut header_bytes).await?;
+    let header = decode_header(&header_bytes)?;
+    let mut payload = vec![0u8; header.length as usize];
+    reader.read_exact(&mut payload).await?;
+    Ok(Message { header, payload })
+}
+
+// --- Frame header ---
+// Payload of a Frame message: this header followed by the pixel data.
+// The webview only sends width/height (CLIENT_FRAME_HEADER_SIZE bytes);
+// the sequence number, capture timestamp, overlay id and pixel layout are
+// added on the Rust side. Each version's header is a prefix of the next:
+//
+//   [0..4)    width (u32)
+//   [4..8)    height (u32)
+//   [8..16)   sequence (u64)
+//   [16..24)  timestamp (u64)              -- end of v1
+//   [24..28)  overlay id (u32)
+//   [28..32)  reserved                     -- end of v2
+//   [28..32)  row stride in bytes (u32)    (v3)
+//   [32]      pixel format (u8)            (v3)
+//   [33..40)  reserved                     -- end of v3
+//
+// Older backends only get RGBA8 frames with packed rows, so the stride
+// bytes are free to reuse.
+pub const CLIENT_FRAME_HEADER_SIZE: usize = 8;
+pub const FRAME_HEADER_SIZE_V1: usize = 24;
+pub const FRAME_HEADER_SIZE_V2: usize = 32;
+pub const FRAME_HEADER_SIZE: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub width: u32,
+    pub height: u32,
+    // Monotonically increasing per app run, so the backend can spot gaps
+    pub sequence: u64,
+    // Microseconds since the Unix epoch when the frame reached Rust
+    pub timestamp_us: u64,
+    // Overlay quad the frame is for (v2+, always 0 on v1)
+    pub overlay_id: u32,
+    // Bytes from one row to the next (v3; packed RGBA8 before that)
+    pub stride: u32,
+    pub pixel_format: PixelFormat,
+}
+
+impl FrameHeader {
+    // Header length on the wire for a negotiated protocol version
+    pub fn size(version: u8) -> usize {
+        match version {
+            0 | 1 => FRAME_HEADER_SIZE_V1,
+            2 => FRAME_HEADER_SIZE_V2,
+            _ => FRAME_HEADER_SIZE,
+        }
+    }
+
+    // Encodes the full header; v1 connections only send the first FRAME_HEADER_SIZE_V1 bytes
+    pub fn encode(&self) -> [u8; FRAME_HEADER_SIZE] {
+        let mut bytes = [0u8; FRAME_HEADER_SIZE];
+        LittleEndian::write_u32(&mut bytes[0..4], self.width);
+        LittleEndian::write_u32(&mut bytes[4..8], self.height);
+        LittleEndian::write_u64(&mut bytes[8..16], self.sequence);
+        LittleEndian::write_u64(&mut bytes[16..24], self.timestamp_us);
+        LittleEndian::write_u32(&mut bytes[24..28], self.overlay_id);
+        LittleEndian::write_u32(&mut bytes[28..32], self.stride);
+        bytes[32] = self.pixel_format as u8;
+        // [33..40) reserved
+        bytes
+    }
+
+    // None if the buffer is too short to hold a header of that version, or the pixel format is unknown
+    pub fn decode(bytes: &[u8], version: u8) -> Option<Self> {
+        let bytes = bytes.get(..Self::size(version))?;
+        let width = LittleEndian::read_u32(&bytes[0..4]);
+        let (stride, pixel_format) = match bytes.get(28..33) {
+            Some(layout) => (LittleEndian::read_u32(&layout[..4]), PixelFormat::try_from(layout[4]).ok()?),
+            None => (width.saturating_mul(4), PixelFormat::Rgba8),
+        };
+        Some(Self {
+            width,
+            height: LittleEndian::read_u32(&bytes[4..8]),
+            sequence: LittleEndian::read_u64(&bytes[8..16]),
+            timestamp_us: LittleEndian::read_u64(&bytes[16..24]),
+            overlay_id: bytes.get(24..28).map_or(0, LittleEndian::read_u32),
+            stride,
+            pixel_format,
+        })
+    }
+}
+
+// Wall-clock microseconds, comparable across processes on the same machine
+pub fn timestamp_us() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_message_waits_for_the_whole_message() {
+        let mut bytes = encode(MessageType::Transform, 0, &[7; TRANSFORM_PAYLOAD_SIZE]);
+        bytes.extend_from_slice(&encode(MessageType::Ping, 0, &[1, 2])[..HEADER_SIZE + 1]);
+
+        for end in 0..HEADER_SIZE + TRANSFORM_PAYLOAD_SIZE {
+            assert!(decode_message(&bytes[..end]).unwrap().is_none());
+        }
+        let (message, consumed) = decode_message(&bytes).unwrap().unwrap();
+        assert_eq!((message.header.message_type, consumed), (MessageType::Transform, HEADER_SIZE + TRANSFORM_PAYLOAD_SIZE));
+        assert_eq!(decode_transform(&message.payload), Some([f32::from_le_bytes([7; 4]); 16]));
+        // The Ping after it is cut short
+        assert!(decode_message(&bytes[consumed..]).unwrap().is_none());
+        assert!(matches!(decode_message(b"XXXXXXXXXXXXXXXX"), Err(ProtocolError::BadMagic(_))));
+    }
+}
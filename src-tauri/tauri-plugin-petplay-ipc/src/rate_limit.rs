@@ -0,0 +1,101 @@
+// --- Command rate limits ---
+// A buggy or compromised webview could flood the plugin's commands, e.g.
+// send_frame_data with 100 MB bodies in a tight loop. Every invoke goes
+// through CommandLimiter before the command runs or its arguments are
+// deserialized (lib.rs wraps the generated invoke handler): a raw request
+// body over maxPayloadBytes is refused with PayloadTooLarge, and each command
+// has a token bucket refilled at its rate, holding one second's worth of
+// calls, that refuses with RateLimited when empty. Both are counted in
+// get_pipe_metrics. A rate of 0 means unlimited.
+//
+//   [limits]
+//   maxPayloadBytes = 67174400
+//   defaultRate = 200
+//
+//   [limits.rates]
+//   send_frame_data = 240
+use crate::{config::DEFAULT_MAX_FRAME_BYTES, error::PipeError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+
+// Room for the frame header and a little more on top of the largest default frame
+const DEFAULT_MAX_PAYLOAD_BYTES: usize = DEFAULT_MAX_FRAME_BYTES + 64 * 1024;
+const DEFAULT_RATE: u32 = 200;
+// Commands that are expected to be called much more often than the rest
+const DEFAULT_RATES: &[(&str, u32)] = &[("send_frame_data", 240), ("submit_remote_poses", 1000), ("send_audio_chunk", 500)];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CommandLimits {
+    // Largest raw request body (send_frame_data, send_file_to_backend, ...) in bytes
+    pub max_payload_bytes: usize,
+    // Calls per second for commands not listed in rates
+    pub default_rate: u32,
+    // Calls per second by command name
+    pub rates: BTreeMap<String, u32>,
+}
+
+impl Default for CommandLimits {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            default_rate: DEFAULT_RATE,
+            rates: DEFAULT_RATES.iter().map(|(command, rate)| (command.to_string(), *rate)).collect(),
+        }
+    }
+}
+
+impl CommandLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_payload_bytes == 0 {
+            return Err("maxPayloadBytes must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    fn rate(&self, command: &str) -> u32 {
+        self.rates.get(command).copied().unwrap_or(self.default_rate)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+pub struct CommandLimiter {
+    limits: CommandLimits,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl CommandLimiter {
+    pub fn new(limits: CommandLimits) -> Self {
+        Self { limits, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    // Take one call of `command` out of its bucket; `body_len` is the size of a raw request body
+    pub fn check(&self, command: &str, body_len: Option<usize>) -> Result<(), PipeError> {
+        if let Some(bytes) = body_len.filter(|&bytes| bytes > self.limits.max_payload_bytes) {
+            return Err(PipeError::PayloadTooLarge { bytes, max: self.limits.max_payload_bytes });
+        }
+        let rate = self.limits.rate(command);
+        if rate == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(command.to_string()).or_insert(Bucket { tokens: rate as f64, refilled_at: now });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate as f64).min(rate as f64);
+        bucket.refilled_at = now;
+        if bucket.tokens < 1.0 {
+            return Err(PipeError::RateLimited { command: command.to_string(), rate });
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
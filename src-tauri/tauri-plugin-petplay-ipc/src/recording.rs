@@ -0,0 +1,199 @@
+// --- Frame recording and replay ---
+// For debugging a backend without the webview in the loop. start_recording
+// captures every frame handed to send_frame_data (header and pixels, exactly
+// as queued) to a file until stop_recording. Launching the app with
+// `--replay <file>` closes the window and feeds the file into the frame pipe
+// at the pace it was recorded, then exits.
+//
+// File layout, little endian:
+//   "PWREC" NUL, u8 format version, u8 protocol version of the frame headers
+//   per frame: u64 microseconds since the first frame, frame header
+//              (protocol.rs), then the pixels (size given by the header)
+use crate::{
+    frame_queue::QueuedFrame,
+    protocol::{FrameHeader, PROTOCOL_VERSION},
+    runtime::IpcRuntime,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    sync::mpsc,
+    task::JoinHandle,
+};
+use tracing::error;
+
+pub const REPLAY_FLAG: &str = "--replay";
+// Extension of recordings written to the default location
+pub const RECORDING_EXTENSION: &str = "pwrec";
+
+const MAGIC: &[u8; 6] = b"PWREC\0";
+const FORMAT_VERSION: u8 = 1;
+// Frames buffered between send_frame_data and the file; when the disk falls further behind, frames are left out
+const RECORD_QUEUE_DEPTH: usize = 64;
+
+// Result of stop_recording
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSummary {
+    pub path: PathBuf,
+    pub frames: u64,
+    pub bytes: u64,
+    // Frames left out because the file couldn't keep up
+    pub dropped: u64,
+    // First to last recorded frame
+    pub duration_ms: u64,
+}
+
+// What the writer task got through before the channel closed
+struct Written {
+    frames: u64,
+    bytes: u64,
+    duration_us: u64,
+}
+
+struct ActiveRecording {
+    path: PathBuf,
+    frames: mpsc::Sender<QueuedFrame>,
+    writer: JoinHandle<io::Result<Written>>,
+    dropped: u64,
+}
+
+#[derive(Default)]
+pub struct Recorder {
+    active: Mutex<Option<ActiveRecording>>,
+}
+
+impl Recorder {
+    // Create `path` (and its directory) and start writing queued frames to it
+    pub fn start(&self, rt: &IpcRuntime, path: PathBuf) -> io::Result<()> {
+        let mut active = self.active.lock();
+        if active.is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "a recording is already running"));
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = File::from_std(std::fs::File::create(&path)?);
+        let (frames, receiver) = mpsc::channel(RECORD_QUEUE_DEPTH);
+        let writer = rt.spawn(write_recording(file, receiver));
+        *active = Some(ActiveRecording { path, frames, writer, dropped: 0 });
+        Ok(())
+    }
+
+    // Hand a frame to the recording, if one is running; never waits on the disk
+    pub fn record(&self, frame: &QueuedFrame) {
+        if let Some(active) = self.active.lock().as_mut() {
+            if active.frames.try_send(frame.clone()).is_err() {
+                active.dropped += 1;
+            }
+        }
+    }
+
+    // Finish writing what's queued and close the file; None if nothing was being recorded
+    pub async fn stop(&self) -> Option<io::Result<RecordingSummary>> {
+        let ActiveRecording { path, frames, writer, dropped } = self.active.lock().take()?;
+        // Closing the channel lets the writer drain it and return
+        drop(frames);
+        let written = match writer.await {
+            Ok(written) => written,
+            Err(e) => Err(io::Error::other(e)),
+        };
+        Some(written.map(|written| RecordingSummary {
+            path,
+            frames: written.frames,
+            bytes: written.bytes,
+            dropped,
+            duration_ms: written.duration_us / 1000,
+        }))
+    }
+}
+
+async fn write_recording(file: File, mut frames: mpsc::Receiver<QueuedFrame>) -> io::Result<Written> {
+    let result = async {
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC).await?;
+        out.write_all(&[FORMAT_VERSION, PROTOCOL_VERSION]).await?;
+        let mut written = Written { frames: 0, bytes: (MAGIC.len() + 2) as u64, duration_us: 0 };
+        let mut first_us = None;
+        while let Some(frame) = frames.recv().await {
+            let first_us = *first_us.get_or_insert(frame.header.timestamp_us);
+            let offset_us = frame.header.timestamp_us.saturating_sub(first_us);
+            let mut offset = [0u8; 8];
+            LittleEndian::write_u64(&mut offset, offset_us);
+            let header = frame.header.encode();
+            out.write_all(&offset).await?;
+            out.write_all(&header).await?;
+            out.write_all(&frame.pixels).await?;
+            written.frames += 1;
+            written.bytes += (offset.len() + header.len() + frame.pixels.len()) as u64;
+            written.duration_us = offset_us;
+        }
+        out.flush().await?;
+        Ok(written)
+    }
+    .await;
+    if let Err(e) = &result {
+        // Frames sent after this are counted as dropped until stop_recording
+        error!("[Rust Recorder] Error writing recording: {}", e);
+    }
+    result
+}
+
+// Reads frames back from a recording
+pub struct Replay {
+    reader: BufReader<File>,
+    // Layout of the frame headers in this file
+    protocol_version: u8,
+    // Frames claiming more pixel data than this are taken as corruption
+    max_frame_bytes: usize,
+}
+
+impl Replay {
+    pub async fn open(path: &Path, max_frame_bytes: usize) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path).await?);
+        let mut preamble = [0u8; 8];
+        reader.read_exact(&mut preamble).await?;
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        if &preamble[..6] != MAGIC {
+            return Err(invalid(format!("{} is not a frame recording", path.display())));
+        }
+        let (format_version, protocol_version) = (preamble[6], preamble[7]);
+        if format_version != FORMAT_VERSION || protocol_version > PROTOCOL_VERSION {
+            return Err(invalid(format!(
+                "unsupported recording (format {}, protocol v{})",
+                format_version, protocol_version
+            )));
+        }
+        Ok(Self { reader, protocol_version, max_frame_bytes })
+    }
+
+    // The next frame and its time after the first one; None at the end of the file
+    pub async fn next_frame(&mut self) -> io::Result<Option<(u64, QueuedFrame)>> {
+        let mut offset = [0u8; 8];
+        match self.reader.read_exact(&mut offset).await {
+            Ok(_) => {}
+            // A recording cut short (e.g. the app was killed) just ends early
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut header = vec![0u8; FrameHeader::size(self.protocol_version)];
+        self.reader.read_exact(&mut header).await?;
+        let header = FrameHeader::decode(&header, self.protocol_version)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated frame header"))?;
+        let size = header.pixel_format.frame_size(header.height, header.stride);
+        if size > self.max_frame_bytes as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes exceeds the limit", size)));
+        }
+        let mut pixels = vec![0u8; size as usize];
+        self.reader.read_exact(&mut pixels).await?;
+        Ok(Some((LittleEndian::read_u64(&offset), QueuedFrame { header, pixels: Bytes::from(pixels) })))
+    }
+}
@@ -0,0 +1,59 @@
+// --- Async runtime ---
+// The plugin runs on Tauri's own Tokio runtime (tauri::async_runtime) rather
+// than a second one with its own thread pool. Everything the plugin spawns
+// goes through IpcRuntime, which remembers an abort handle for each task, so
+// on app exit (after the pipes have been flushed and closed) abort_all()
+// stops every connection loop, listener and watcher the plugin started
+// instead of leaving them to whatever else is still using the runtime.
+use parking_lot::Mutex;
+use std::{future::Future, sync::Arc};
+use tokio::{
+    runtime::{EnterGuard, Handle},
+    task::{AbortHandle, JoinHandle},
+};
+
+#[derive(Clone)]
+pub struct IpcRuntime {
+    handle: Handle,
+    // Tasks spawned so far; finished ones are pruned on the next spawn
+    tasks: Arc<Mutex<Vec<AbortHandle>>>,
+}
+
+impl IpcRuntime {
+    // The runtime Tauri uses for its async commands
+    pub fn from_tauri() -> Self {
+        Self::new(tauri::async_runtime::handle().inner().clone())
+    }
+
+    pub fn new(handle: Handle) -> Self {
+        Self { handle, tasks: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let task = self.handle.spawn(future);
+        let mut tasks = self.tasks.lock();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task.abort_handle());
+        task
+    }
+
+    // Only from outside the runtime (setup, exit handling)
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.handle.block_on(future)
+    }
+
+    // Lets code outside the runtime create Tokio I/O objects bound to it
+    pub fn enter(&self) -> EnterGuard<'_> {
+        self.handle.enter()
+    }
+
+    // Abort every task spawned through this runtime, returning how many were still running
+    pub fn abort_all(&self) -> usize {
+        let tasks = std::mem::take(&mut *self.tasks.lock());
+        tasks.iter().filter(|task| !task.is_finished()).inspect(|task| task.abort()).count()
+    }
+}
@@ -0,0 +1,56 @@
+// --- Screenshots ---
+// capture_screenshot saves the last frame handed to send_frame_data as a PNG
+// (under screenshots/ in the app data directory), for bug reports about
+// rendering artifacts. FramePipeState keeps that frame
+// around by reference (the pixels aren't copied per frame); only a screenshot
+// pays for converting it to packed RGBA and encoding it.
+use crate::{buffer_pool::BufferPool, frame_queue::QueuedFrame, pixel_format};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// Result of capture_screenshot
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Screenshot {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    // Sequence number and overlay of the captured frame, to match it against backend logs
+    pub sequence: u64,
+    pub overlay_id: u32,
+}
+
+// Encode `frame` as an 8-bit RGBA PNG at `path`
+pub fn write_png(frame: &QueuedFrame, path: &Path, buffers: &Arc<BufferPool>) -> io::Result<Screenshot> {
+    let header = frame.header;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    encode_png(frame, BufWriter::new(File::create(path)?), buffers)?;
+    Ok(Screenshot {
+        path: path.to_path_buf(),
+        width: header.width,
+        height: header.height,
+        sequence: header.sequence,
+        overlay_id: header.overlay_id,
+    })
+}
+
+// Encode `frame` as an 8-bit RGBA PNG into `out`
+pub fn encode_png(frame: &QueuedFrame, out: impl Write, buffers: &Arc<BufferPool>) -> io::Result<()> {
+    let header = frame.header;
+    let converted = pixel_format::packed_rgba(frame, buffers);
+    let pixels = &converted.as_ref().unwrap_or(frame).pixels;
+
+    let mut encoder = png::Encoder::new(out, header.width, header.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
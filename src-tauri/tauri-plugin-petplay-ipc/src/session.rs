@@ -0,0 +1,336 @@
+// --- Session recording and replay ---
+// Frame recordings (recording.rs) only hold what went to the backend. A
+// session recording holds both directions of every pipe: frames and audio out, and
+// poses, transforms, controller input, VR pointer events, control messages and
+// acks coming in.
+// start_session_recording captures until stop_session_recording;
+// replay_session then feeds the incoming half back through the same readers
+// the pipes use, so the frontend gets the same events in the same order and
+// at the same pace, with no backend running. Frames and audio out are kept for
+// looking at the exchange, but aren't replayed: they went to the backend, not the UI.
+//
+// File layout, little endian:
+//   "PWSES" NUL, u8 format version, u8 protocol version of the frame headers
+//   per message: u64 microseconds since the recording started,
+//                u8 direction (0 app -> backend, 1 backend -> app),
+//                u8 pipe (0 frame, 1 transform, 2 input, 3 audio, 4 microphone, 5 video, 6 files,
+//                7 pointer),
+//                then the message as on the wire (protocol.rs), uncompressed
+use crate::{
+    connection::PipeKind,
+    frame_queue::QueuedFrame,
+    protocol::{self, FrameHeader, Message, MessageType, HEADER_SIZE, PROTOCOL_VERSION},
+    runtime::IpcRuntime,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    sync::mpsc,
+    task::JoinHandle,
+};
+use tracing::error;
+
+// Extension of session recordings written to the default location
+pub const SESSION_EXTENSION: &str = "pwses";
+
+const MAGIC: &[u8; 6] = b"PWSES\0";
+const FORMAT_VERSION: u8 = 1;
+// Offset, direction and pipe in front of every message
+const ENTRY_HEADER_SIZE: usize = 10;
+// Messages buffered between the pipes and the file; when the disk falls further behind, messages are left out
+const SESSION_QUEUE_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    ToBackend,
+    FromBackend,
+}
+
+// Result of stop_session_recording
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSummary {
+    pub path: PathBuf,
+    pub messages: u64,
+    pub bytes: u64,
+    // Messages left out because the file couldn't keep up
+    pub dropped: u64,
+    pub duration_ms: u64,
+}
+
+// Result of replay_session
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionReplaySummary {
+    // Messages fed back to the readers
+    pub replayed: u64,
+    // Frames the app had sent, which aren't replayed
+    pub skipped: u64,
+    pub duration_ms: u64,
+}
+
+struct Entry {
+    offset_us: u64,
+    direction: Direction,
+    pipe: PipeKind,
+    message_type: MessageType,
+    flags: u16,
+    // The payload is `prefix` followed by `body`, so frames don't have to be joined first
+    prefix: Vec<u8>,
+    body: Bytes,
+}
+
+struct Written {
+    messages: u64,
+    bytes: u64,
+    duration_us: u64,
+}
+
+struct ActiveSession {
+    path: PathBuf,
+    started: Instant,
+    entries: mpsc::Sender<Entry>,
+    writer: JoinHandle<io::Result<Written>>,
+    dropped: u64,
+}
+
+#[derive(Default)]
+pub struct SessionRecorder {
+    active: Mutex<Option<ActiveSession>>,
+}
+
+impl SessionRecorder {
+    // Create `path` (and its directory) and start writing both directions of the pipes to it
+    pub fn start(&self, rt: &IpcRuntime, path: PathBuf) -> io::Result<()> {
+        let mut active = self.active.lock();
+        if active.is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "a session recording is already running"));
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = File::from_std(std::fs::File::create(&path)?);
+        let (entries, receiver) = mpsc::channel(SESSION_QUEUE_DEPTH);
+        let writer = rt.spawn(write_session(file, receiver));
+        *active = Some(ActiveSession { path, started: Instant::now(), entries, writer, dropped: 0 });
+        Ok(())
+    }
+
+    fn push(&self, make: impl FnOnce(u64) -> Entry) {
+        if let Some(active) = self.active.lock().as_mut() {
+            let entry = make(active.started.elapsed().as_micros() as u64);
+            if active.entries.try_send(entry).is_err() {
+                active.dropped += 1;
+            }
+        }
+    }
+
+    // A frame on its way to the backend, with its header as the latest protocol version lays it out
+    pub fn record_frame(&self, frame: &QueuedFrame) {
+        self.push(|offset_us| Entry {
+            offset_us,
+            direction: Direction::ToBackend,
+            pipe: PipeKind::Frame,
+            message_type: MessageType::Frame,
+            flags: 0,
+            prefix: frame.header.encode()[..FrameHeader::size(PROTOCOL_VERSION)].to_vec(),
+            body: frame.pixels.clone(),
+        });
+    }
+
+    // Any other message the app sent on `pipe`, whose payload is `prefix` followed by `body`
+    pub fn record_sent(&self, pipe: PipeKind, message_type: MessageType, prefix: &[u8], body: &Bytes) {
+        self.push(|offset_us| Entry {
+            offset_us,
+            direction: Direction::ToBackend,
+            pipe,
+            message_type,
+            flags: 0,
+            prefix: prefix.to_vec(),
+            body: body.clone(),
+        });
+    }
+
+    // A message the backend sent on `pipe`
+    pub fn record_received(&self, pipe: PipeKind, message: &Message) {
+        self.push(|offset_us| Entry {
+            offset_us,
+            direction: Direction::FromBackend,
+            pipe,
+            message_type: message.header.message_type,
+            flags: message.header.flags,
+            prefix: Vec::new(),
+            body: Bytes::copy_from_slice(&message.payload),
+        });
+    }
+
+    // Finish writing what's queued and close the file; None if no session was being recorded
+    pub async fn stop(&self) -> Option<io::Result<SessionSummary>> {
+        let ActiveSession { path, entries, writer, dropped, .. } = self.active.lock().take()?;
+        drop(entries);
+        let written = match writer.await {
+            Ok(written) => written,
+            Err(e) => Err(io::Error::other(e)),
+        };
+        Some(written.map(|written| SessionSummary {
+            path,
+            messages: written.messages,
+            bytes: written.bytes,
+            dropped,
+            duration_ms: written.duration_us / 1000,
+        }))
+    }
+}
+
+fn pipe_code(pipe: PipeKind) -> u8 {
+    match pipe {
+        PipeKind::Frame => 0,
+        PipeKind::Transform => 1,
+        PipeKind::Input => 2,
+        PipeKind::Audio => 3,
+        PipeKind::Microphone => 4,
+        PipeKind::Video => 5,
+        PipeKind::Files => 6,
+        PipeKind::Pointer => 7,
+    }
+}
+
+async fn write_session(file: File, mut entries: mpsc::Receiver<Entry>) -> io::Result<Written> {
+    let result = async {
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC).await?;
+        out.write_all(&[FORMAT_VERSION, PROTOCOL_VERSION]).await?;
+        let mut written = Written { messages: 0, bytes: (MAGIC.len() + 2) as u64, duration_us: 0 };
+        while let Some(entry) = entries.recv().await {
+            let mut header = [0u8; ENTRY_HEADER_SIZE];
+            LittleEndian::write_u64(&mut header[..8], entry.offset_us);
+            header[8] = match entry.direction {
+                Direction::ToBackend => 0,
+                Direction::FromBackend => 1,
+            };
+            header[9] = pipe_code(entry.pipe);
+            let length = entry.prefix.len() + entry.body.len();
+            let message_header = protocol::encode_header(entry.message_type, entry.flags, length);
+            out.write_all(&header).await?;
+            out.write_all(&message_header).await?;
+            out.write_all(&entry.prefix).await?;
+            out.write_all(&entry.body).await?;
+            written.messages += 1;
+            written.bytes += (ENTRY_HEADER_SIZE + HEADER_SIZE + length) as u64;
+            written.duration_us = entry.offset_us;
+        }
+        out.flush().await?;
+        Ok(written)
+    }
+    .await;
+    if let Err(e) = &result {
+        error!("[Rust Session] Error writing session recording: {}", e);
+    }
+    result
+}
+
+// One message read back from a session recording
+pub struct RecordedMessage {
+    pub offset_us: u64,
+    pub direction: Direction,
+    pub pipe: PipeKind,
+    pub message: Message,
+}
+
+// Reads messages back from a session recording
+pub struct SessionReplay {
+    reader: BufReader<File>,
+}
+
+impl SessionReplay {
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path).await?);
+        let mut preamble = [0u8; 8];
+        reader.read_exact(&mut preamble).await?;
+        if &preamble[..6] != MAGIC {
+            return Err(invalid(format!("{} is not a session recording", path.display())));
+        }
+        if preamble[6] != FORMAT_VERSION || preamble[7] > PROTOCOL_VERSION {
+            return Err(invalid(format!("unsupported session recording (format {}, protocol v{})", preamble[6], preamble[7])));
+        }
+        Ok(Self { reader })
+    }
+
+    // The next message; None at the end of the file
+    pub async fn next_message(&mut self) -> io::Result<Option<RecordedMessage>> {
+        let mut header = [0u8; ENTRY_HEADER_SIZE];
+        match self.reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            // A recording cut short (e.g. the app was killed) just ends early
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let direction = match header[8] {
+            0 => Direction::ToBackend,
+            1 => Direction::FromBackend,
+            other => return Err(invalid(format!("unknown direction {}", other))),
+        };
+        let pipe = match header[9] {
+            0 => PipeKind::Frame,
+            1 => PipeKind::Transform,
+            2 => PipeKind::Input,
+            3 => PipeKind::Audio,
+            4 => PipeKind::Microphone,
+            5 => PipeKind::Video,
+            6 => PipeKind::Files,
+            7 => PipeKind::Pointer,
+            other => return Err(invalid(format!("unknown pipe {}", other))),
+        };
+        let message = protocol::read_message(&mut self.reader).await.map_err(|e| match e {
+            protocol::ProtocolError::Io(e) => e,
+            e => invalid(e.to_string()),
+        })?;
+        Ok(Some(RecordedMessage { offset_us: LittleEndian::read_u64(&header[..8]), direction, pipe, message }))
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pixel_format::PixelFormat, protocol::MessageHeader};
+
+    #[tokio::test]
+    async fn a_session_reads_back_in_both_directions() {
+        let rt = IpcRuntime::new(tokio::runtime::Handle::current());
+        let path = std::env::temp_dir().join(format!("puppyweb-session-test-{}.{}", std::process::id(), SESSION_EXTENSION));
+        let recorder = SessionRecorder::default();
+        recorder.start(&rt, path.clone()).unwrap();
+
+        let sent = QueuedFrame::test(1, PixelFormat::Rgba8, 2, 1, 8, Bytes::from_static(&[1, 2, 3, 4, 5, 6, 7, 8]));
+        recorder.record_frame(&sent);
+        let frame_header = sent.header;
+        let header = MessageHeader { version: PROTOCOL_VERSION, message_type: MessageType::Poses, flags: 0, length: 3 };
+        recorder.record_received(PipeKind::Transform, &Message { header, payload: vec![9, 9, 9] });
+        let summary = recorder.stop().await.unwrap().unwrap();
+        assert_eq!(summary.messages, 2);
+
+        let mut replay = SessionReplay::open(&path).await.unwrap();
+        let frame = replay.next_message().await.unwrap().unwrap();
+        assert_eq!((frame.direction, frame.pipe, frame.message.header.message_type), (Direction::ToBackend, PipeKind::Frame, MessageType::Frame));
+        assert_eq!(FrameHeader::decode(&frame.message.payload, PROTOCOL_VERSION), Some(frame_header));
+        assert_eq!(&frame.message.payload[FrameHeader::size(PROTOCOL_VERSION)..], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let poses = replay.next_message().await.unwrap().unwrap();
+        assert_eq!((poses.direction, poses.pipe, &poses.message.payload[..]), (Direction::FromBackend, PipeKind::Transform, &[9, 9, 9][..]));
+        assert!(poses.offset_us >= frame.offset_us);
+        assert!(replay.next_message().await.unwrap().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
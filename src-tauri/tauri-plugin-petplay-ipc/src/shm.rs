@@ -0,0 +1,139 @@
+// --- Shared-memory frame ring ---
+// Large frames are expensive to push through a pipe. In shared-memory mode the
+// frame payload (the frame header for the negotiated protocol version, see
+// protocol.rs, then the pixels) is copied into one slot of a memory-mapped ring
+// buffer and only a small "frame ready" message goes over the frame pipe. That
+// message doubles as the event signal for the backend.
+//
+// The file lives in the app data directory, not the shared temp directory. A
+// leftover one is removed and a fresh one created exclusively (owner-only on
+// Unix), so a planted file or symlink is never written through.
+//
+// Layout of the mapped file (all integers little endian):
+//   [0..4)   magic "PSHM"
+//   [4..8)   layout version
+//   [8..12)  slot count
+//   [12..16) slot size in bytes
+//   [16..20) index of the most recently written slot
+//   [20..24) number of frames written (wrapping)
+//   [24..64) reserved
+//   [64..)   slot_count * slot_size bytes of frame slots
+use byteorder::{ByteOrder, LittleEndian};
+use memmap2::MmapMut;
+use serde::Deserialize;
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+pub const SHM_FILE_NAME: &str = "petplay-ipc-frames.shm";
+pub const DEFAULT_SLOT_COUNT: u32 = 3;
+pub const DEFAULT_SLOT_SIZE: u32 = 16 * 1024 * 1024; // Fits a 2560x1440 RGBA frame + header
+pub const FRAME_READY_SIZE: usize = 16;
+
+const SHM_MAGIC: u32 = u32::from_le_bytes(*b"PSHM");
+const SHM_VERSION: u32 = 1;
+const FRAME_READY_MAGIC: u32 = u32::from_le_bytes(*b"SHMR");
+const HEADER_SIZE: usize = 64;
+
+// How send_frame_data hands frames to the backend
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrameChannel {
+    // Full payload is written to the frame pipe
+    Pipe,
+    // Payload goes into the shared ring, a FRAME_READY_SIZE message goes over the pipe
+    SharedMemory,
+}
+
+pub struct SharedFrameRing {
+    map: MmapMut,
+    path: PathBuf,
+    slot_count: u32,
+    slot_size: u32,
+    next_slot: u32,
+    frames_written: u32,
+}
+
+impl SharedFrameRing {
+    // Replace any file at `path` with a new backing file and map it
+    pub fn create(path: &Path, slot_count: u32, slot_size: u32) -> io::Result<Self> {
+        if slot_count == 0 || slot_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "slot count and size must be non-zero"));
+        }
+        let total_size = HEADER_SIZE as u64 + slot_count as u64 * slot_size as u64;
+        // Removing a symlink removes the link, not what it points to
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options.open(path)?;
+        file.set_len(total_size)?;
+        // SAFETY: the file is owned by this process for the lifetime of the ring;
+        // the backend only reads slots after receiving a frame-ready message.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+
+        LittleEndian::write_u32(&mut map[0..4], SHM_MAGIC);
+        LittleEndian::write_u32(&mut map[4..8], SHM_VERSION);
+        LittleEndian::write_u32(&mut map[8..12], slot_count);
+        LittleEndian::write_u32(&mut map[12..16], slot_size);
+        LittleEndian::write_u32(&mut map[16..20], 0);
+        LittleEndian::write_u32(&mut map[20..24], 0);
+
+        Ok(Self {
+            map,
+            path: path.to_path_buf(),
+            slot_count,
+            slot_size,
+            next_slot: 0,
+            frames_written: 0,
+        })
+    }
+
+    // Create the ring under `dir` (the app data directory), creating that if needed
+    pub fn create_default(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Self::create(&dir.join(SHM_FILE_NAME), DEFAULT_SLOT_COUNT, DEFAULT_SLOT_SIZE)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Copy the payload (given in parts, e.g. frame header and pixels) into the next slot
+    // and return the frame-ready message that must be sent over the frame pipe to signal the backend
+    pub fn write_frame(&mut self, parts: &[&[u8]]) -> io::Result<[u8; FRAME_READY_SIZE]> {
+        let length: usize = parts.iter().map(|part| part.len()).sum();
+        if length > self.slot_size as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame of {} bytes exceeds shared-memory slot size {}", length, self.slot_size),
+            ));
+        }
+
+        let slot = self.next_slot;
+        let mut offset = HEADER_SIZE + slot as usize * self.slot_size as usize;
+        for part in parts {
+            self.map[offset..offset + part.len()].copy_from_slice(part);
+            offset += part.len();
+        }
+
+        // Update the header so polling readers can find the latest frame too
+        self.frames_written = self.frames_written.wrapping_add(1);
+        LittleEndian::write_u32(&mut self.map[16..20], slot);
+        LittleEndian::write_u32(&mut self.map[20..24], self.frames_written);
+        self.next_slot = (slot + 1) % self.slot_count;
+
+        let mut message = [0u8; FRAME_READY_SIZE];
+        LittleEndian::write_u32(&mut message[0..4], FRAME_READY_MAGIC);
+        LittleEndian::write_u32(&mut message[4..8], slot);
+        LittleEndian::write_u32(&mut message[8..12], length as u32);
+        LittleEndian::write_u32(&mut message[12..16], self.frames_written);
+        Ok(message)
+    }
+}
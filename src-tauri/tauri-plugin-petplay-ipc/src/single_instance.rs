@@ -0,0 +1,150 @@
+// --- Single instance ---
+// Two copies of the app would fight over the same backend pipes, so the first
+// one to start serves a small "instance" pipe of its own, named after its
+// frame pipe. A later launch connects to it before touching anything else,
+// hands over its command line and working directory, and exits; the running
+// instance brings its window to the front and emits a "second-instance" event
+// carrying them, so the frontend can act on arguments such as a file to open.
+// Both ends check that the other runs as the same account (see
+// pipe_security.rs). Copies pointed at different backends with --frame-pipe
+// (see cli.rs) don't get in each other's way; `multiInstance = true` in
+// puppyweb.toml turns the check off altogether.
+use crate::{
+    pipe_security::PipeSecurity,
+    runtime::IpcRuntime,
+    transport::{PipeListener, PipeOptions, PlatformTransport, ServerTransport, Transport},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{io, time::Duration};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tracing::{error, info, warn};
+
+#[cfg(windows)]
+const INSTANCE_PIPE_PREFIX: &str = r"\\.\pipe\petplay-ipc-instance-";
+#[cfg(unix)]
+const INSTANCE_PIPE_PREFIX: &str = "/tmp/petplay-ipc-instance-";
+#[cfg(windows)]
+const INSTANCE_PIPE_SUFFIX: &str = "";
+#[cfg(unix)]
+const INSTANCE_PIPE_SUFFIX: &str = ".sock";
+// Hex digits of the frame pipe path's hash in the instance pipe name
+const INSTANCE_ID_LEN: usize = 16;
+// A command line is small; anything bigger isn't a well-behaved second instance
+const MAX_HANDOFF_BYTES: u64 = 64 * 1024;
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(2);
+const HANDOFF_ACK: &str = "ok";
+const MAIN_WINDOW_LABEL: &str = "main";
+
+// What a second launch hands to the running instance
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SecondInstancePayload {
+    pub args: Vec<String>,
+    pub cwd: String,
+}
+
+pub enum Instance {
+    // This is the only instance; it now serves the instance pipe
+    Primary,
+    // Another instance is running and has been handed this launch's arguments
+    Forwarded,
+}
+
+// Hand off to a running instance using `frame_pipe`, or become the one later launches hand off to
+pub fn claim(rt: &IpcRuntime, app_handle: &AppHandle, frame_pipe: &str) -> Instance {
+    let security = PipeSecurity::default();
+    let endpoint = instance_endpoint(frame_pipe);
+    match rt.block_on(forward(&endpoint, &security)) {
+        Ok(()) => return Instance::Forwarded,
+        // Nobody is serving the pipe: we're first
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) => {}
+        Err(e) => warn!("[Rust Instance] Couldn't hand off to a running instance, starting anyway: {}", e),
+    }
+    // Bound inside the runtime, which the listener registers with
+    let listener = {
+        let _guard = rt.enter();
+        PipeListener::bind(&endpoint, &PipeOptions::default(), &security)
+    };
+    match listener {
+        Ok(listener) => {
+            rt.spawn(serve(listener, endpoint, security, app_handle.clone()));
+        }
+        Err(e) => warn!("[Rust Instance] Not serving {}, later launches won't be handed off: {}", endpoint, e),
+    }
+    Instance::Primary
+}
+
+fn instance_endpoint(frame_pipe: &str) -> String {
+    let hash: String = Sha256::digest(frame_pipe.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}{}{}", INSTANCE_PIPE_PREFIX, &hash[..INSTANCE_ID_LEN], INSTANCE_PIPE_SUFFIX)
+}
+
+async fn forward(endpoint: &str, security: &PipeSecurity) -> io::Result<()> {
+    let mut stream = PlatformTransport::connect(endpoint, &PipeOptions::default()).await?;
+    // Don't hand our arguments to a pipe someone else created
+    security.verify_server(&stream)?;
+    let payload = SecondInstancePayload {
+        args: std::env::args().collect(),
+        cwd: std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default(),
+    };
+    let mut line = serde_json::to_string(&payload).map_err(io::Error::other)?;
+    line.push('\n');
+    let mut ack = String::new();
+    tokio::time::timeout(HANDOFF_TIMEOUT, async {
+        stream.write_all(line.as_bytes()).await?;
+        BufReader::new(&mut stream).read_line(&mut ack).await
+    })
+    .await
+    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "running instance didn't answer"))??;
+    if ack.trim_end() != HANDOFF_ACK {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "running instance didn't accept the handoff"));
+    }
+    info!("[Rust Instance] Handed off to the running instance.");
+    Ok(())
+}
+
+async fn serve(mut listener: PipeListener, endpoint: String, security: PipeSecurity, app_handle: AppHandle) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("[Rust Instance] Stopped serving {}: {}", endpoint, e);
+                return;
+            }
+        };
+        if let Err(e) = security.verify_client(&stream) {
+            warn!("[Rust Instance] Rejected handoff: {}", e);
+            continue;
+        }
+        match tokio::time::timeout(HANDOFF_TIMEOUT, receive(stream)).await {
+            Ok(Ok(payload)) => on_second_instance(&app_handle, payload),
+            Ok(Err(e)) => warn!("[Rust Instance] Bad handoff: {}", e),
+            Err(_) => warn!("[Rust Instance] Handoff timed out."),
+        }
+    }
+}
+
+async fn receive(mut stream: ServerTransport) -> io::Result<SecondInstancePayload> {
+    let mut line = String::new();
+    BufReader::new((&mut stream).take(MAX_HANDOFF_BYTES)).read_line(&mut line).await?;
+    let payload = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    stream.write_all(format!("{}\n", HANDOFF_ACK).as_bytes()).await?;
+    Ok(payload)
+}
+
+fn on_second_instance(app_handle: &AppHandle, payload: SecondInstancePayload) {
+    info!("[Rust Instance] Another launch handed off: {:?}", payload.args);
+    let window = app_handle
+        .get_webview_window(MAIN_WINDOW_LABEL)
+        .or_else(|| app_handle.webview_windows().into_values().next());
+    if let Some(window) = window {
+        let focused = window.unminimize().and_then(|_| window.show()).and_then(|_| window.set_focus());
+        if let Err(e) = focused {
+            error!("[Rust Instance] Error focusing the window: {}", e);
+        }
+    }
+    if let Err(e) = app_handle.emit("second-instance", payload) {
+        error!("[Rust Instance] Error emitting second-instance event: {}", e);
+    }
+}
@@ -0,0 +1,212 @@
+// --- Frame sinks ---
+// Everything outgoing frames are written to implements FrameSink: the frame
+// pipe (FramePipeState), a Spout sender, an NDI source and the in-process
+// OpenVR overlay. send_frame_data
+// hands each frame to the pipe's queue and to the queue of every attached
+// sink, and each queue is drained by its own task, so every sink has its
+// own depth and backpressure policy: a slow NDI receiver drops its own
+// frames instead of holding up the pipe. Frames are reference counted, so
+// fanning out doesn't copy the pixels.
+//
+// Sinks are attached under an id with add_sink and detached with
+// remove_sink. configure_stream (spoutSender), enable_ndi_output,
+// enable_openvr and enable_openxr manage the sinks with the ids "spout",
+// "ndi", "openvr" and "openxr". A sink whose write fails is
+// detached (and dropped, which stops its output).
+use crate::{
+    buffer_pool::BufferPool,
+    frame_queue::{BackpressurePolicy, FrameQueue, QueuedFrame, DEFAULT_QUEUE_DEPTH},
+    ndi::{NdiConfig, NdiOutput},
+    runtime::IpcRuntime,
+    spout::SpoutOutput,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+// The id of the frame pipe in list_sinks; it can't be removed
+pub const PIPE_SINK_ID: &str = "pipe";
+pub const SPOUT_SINK_ID: &str = "spout";
+pub const NDI_SINK_ID: &str = "ndi";
+
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>>;
+
+pub trait FrameSink: Send + Sync + 'static {
+    // Shown in logs and list_sinks, e.g. `NDI source "PuppyWeb"`
+    fn describe(&self) -> String;
+
+    // Write one frame; an error detaches the sink
+    fn write<'a>(&'a self, frame: &'a QueuedFrame) -> SinkFuture<'a>;
+}
+
+// What add_sink opens
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SinkSpec {
+    Spout { name: String },
+    Ndi(NdiConfig),
+}
+
+impl SinkSpec {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SinkSpec::Spout { .. } => SPOUT_SINK_ID,
+            SinkSpec::Ndi(_) => NDI_SINK_ID,
+        }
+    }
+
+    // `buffers` is where the sink gets frames converted to RGBA from
+    pub fn open(self, buffers: &Arc<BufferPool>) -> io::Result<Arc<dyn FrameSink>> {
+        Ok(match self {
+            SinkSpec::Spout { name } => Arc::new(SpoutOutput::start(&name, Arc::clone(buffers))?),
+            SinkSpec::Ndi(config) => Arc::new(NdiOutput::start(NdiConfig { enabled: true, ..config }, Arc::clone(buffers))?),
+        })
+    }
+}
+
+// Queue settings of an attached sink
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SinkQueueOptions {
+    pub queue_depth: usize,
+    pub backpressure: BackpressurePolicy,
+}
+
+impl Default for SinkQueueOptions {
+    fn default() -> Self {
+        Self { queue_depth: DEFAULT_QUEUE_DEPTH, backpressure: BackpressurePolicy::DropOldest }
+    }
+}
+
+// Entry of list_sinks
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SinkInfo {
+    pub id: String,
+    pub description: String,
+    #[serde(flatten)]
+    pub queue: SinkQueueOptions,
+    // Frames discarded by the sink's backpressure policy
+    pub dropped: u64,
+}
+
+impl SinkInfo {
+    pub fn new(id: &str, sink: &dyn FrameSink, queue: &FrameQueue) -> Self {
+        Self {
+            id: id.to_string(),
+            description: sink.describe(),
+            queue: SinkQueueOptions { queue_depth: queue.depth(), backpressure: queue.policy() },
+            dropped: queue.dropped_frames(),
+        }
+    }
+}
+
+// Nothing drains a detached sink's queue anymore, so senders blocked on it must give up
+fn release_senders(queue: &FrameQueue) {
+    queue.set_policy(BackpressurePolicy::DropNewest);
+}
+
+struct Attached {
+    sink: Arc<dyn FrameSink>,
+    queue: Arc<FrameQueue>,
+    task: JoinHandle<()>,
+}
+
+// The sinks attached beside the frame pipe, keyed by id
+#[derive(Default)]
+pub struct SinkSet {
+    attached: Arc<Mutex<BTreeMap<String, Attached>>>,
+    // Numbers the ids of sinks added without one
+    next_id: AtomicU64,
+}
+
+impl SinkSet {
+    // An unused id for a sink of `kind`, e.g. "ndi-2"
+    pub fn generate_id(&self, kind: &str) -> String {
+        loop {
+            let id = format!("{}-{}", kind, self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+            if !self.attached.lock().contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    // Start feeding frames to `sink`, replacing (and dropping) any sink attached under `id`
+    pub fn attach(&self, rt: &IpcRuntime, id: &str, sink: Arc<dyn FrameSink>, options: SinkQueueOptions) {
+        let queue = Arc::new(FrameQueue::new(options.queue_depth));
+        queue.set_policy(options.backpressure);
+        let task = {
+            let (id, queue, attached) = (id.to_string(), queue.clone(), self.attached.clone());
+            // Only the set owns the sink, so detaching it stops the output right away
+            let weak = Arc::downgrade(&sink);
+            rt.spawn(async move {
+                loop {
+                    let frame = queue.pop().await;
+                    let Some(sink) = weak.upgrade() else {
+                        return;
+                    };
+                    if let Err(e) = sink.write(&frame).await {
+                        error!("[Rust Sinks] Error writing to {} ({}): {}. Sink removed.", sink.describe(), id, e);
+                        let mut attached = attached.lock();
+                        // Unless it was replaced in the meantime
+                        if attached.get(&id).is_some_and(|entry| Arc::ptr_eq(&entry.queue, &queue)) {
+                            attached.remove(&id);
+                        }
+                        release_senders(&queue);
+                        return;
+                    }
+                }
+            })
+        };
+        self.detach(id);
+        info!("[Rust Sinks] Attached {} as {:?}.", sink.describe(), id);
+        // Another attach may have raced in between
+        if let Some(raced) = self.attached.lock().insert(id.to_string(), Attached { sink, queue, task }) {
+            raced.task.abort();
+            release_senders(&raced.queue);
+        }
+    }
+
+    // Stop feeding frames to the sink attached under `id`; returns whether there was one
+    pub fn detach(&self, id: &str) -> bool {
+        let Some(detached) = self.attached.lock().remove(id) else {
+            return false;
+        };
+        detached.task.abort();
+        release_senders(&detached.queue);
+        info!("[Rust Sinks] Removed {} ({}).", detached.sink.describe(), id);
+        true
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.attached.lock().contains_key(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attached.lock().is_empty()
+    }
+
+    // Queue `frame` for every attached sink, each under its own backpressure policy
+    pub async fn offer(&self, frame: &QueuedFrame) {
+        // The lock can't be held across the pushes, which may wait (BackpressurePolicy::Block)
+        let queues: Vec<Arc<FrameQueue>> = self.attached.lock().values().map(|entry| entry.queue.clone()).collect();
+        for queue in queues {
+            queue.push(frame.clone()).await;
+        }
+    }
+
+    pub fn list(&self) -> Vec<SinkInfo> {
+        self.attached.lock().iter().map(|(id, entry)| SinkInfo::new(id, entry.sink.as_ref(), &entry.queue)).collect()
+    }
+}
@@ -0,0 +1,369 @@
+// --- Spout output ---
+// Publishes outgoing frames as a Spout sender (Windows only), so OBS and
+// other Spout receivers can pick up the overlay beside the backend. It's a
+// frame sink (sink.rs): set the sender name with configure_stream
+// (spoutSender, "" to stop) or [stream] spoutSender in puppyweb.toml, or add
+// more senders with add_sink. While a sender is running frames are accepted
+// even without a backend on the frame pipe.
+//
+// There's no Spout SDK involved; this speaks the Spout 2 conventions itself:
+//   - the pixels live in a D3D11 texture created with
+//     D3D11_RESOURCE_MISC_SHARED (R8G8B8A8_UNORM), recreated on resize
+//   - a shared memory block named after the sender holds its
+//     SharedTextureInfo (share handle, size, format)
+//   - the name is added to the "SpoutSenderNames" list (256 byte slots,
+//     guarded by the "SpoutSenderNames_mutex" mutex), and becomes the
+//     "ActiveSenderName" if there is none
+//   - "<name>_SpoutAccessMutex" is held while the texture is written
+use crate::{
+    buffer_pool::BufferPool,
+    frame_queue::QueuedFrame,
+    pixel_format,
+    sink::{FrameSink, SinkFuture},
+};
+use parking_lot::Mutex;
+use std::{io, sync::Arc};
+
+// Spout sender names are fixed 256 byte strings, NUL included
+pub const MAX_SENDER_NAME_LEN: usize = 255;
+
+pub fn validate_sender_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_SENDER_NAME_LEN || !name.is_ascii() || name.contains('\0') {
+        return Err(format!("Spout sender names must be 1 to {} ASCII characters", MAX_SENDER_NAME_LEN));
+    }
+    Ok(())
+}
+
+// A running Spout sender; it unregisters itself when dropped
+pub struct SpoutOutput {
+    name: String,
+    sender: Mutex<platform::Sender>,
+    buffers: Arc<BufferPool>,
+}
+
+impl SpoutOutput {
+    pub fn start(name: &str, buffers: Arc<BufferPool>) -> io::Result<Self> {
+        validate_sender_name(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let sender = platform::Sender::create(name)?;
+        Ok(Self { name: name.to_string(), sender: Mutex::new(sender), buffers })
+    }
+}
+
+impl FrameSink for SpoutOutput {
+    fn describe(&self) -> String {
+        format!("Spout sender {:?}", self.name)
+    }
+
+    // Copy `frame` into the shared texture. Errors are typically a removed/reset GPU;
+    // publishing has to be restarted explicitly then.
+    fn write<'a>(&'a self, frame: &'a QueuedFrame) -> SinkFuture<'a> {
+        Box::pin(async move {
+            let converted = pixel_format::packed_rgba(frame, &self.buffers);
+            let frame = converted.as_ref().unwrap_or(frame);
+            self.sender.lock().send(frame.header.width, frame.header.height, &frame.pixels)
+        })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::MAX_SENDER_NAME_LEN;
+    use std::{ffi::CString, io, ptr};
+    use windows::{
+        core::Interface,
+        Win32::{
+            Foundation::HMODULE,
+            Graphics::{
+                Direct3D::D3D_DRIVER_TYPE_HARDWARE,
+                Direct3D11::{
+                    D3D11CreateDevice, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_BIND_RENDER_TARGET,
+                    D3D11_BIND_SHADER_RESOURCE, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_RESOURCE_MISC_SHARED,
+                    D3D11_SDK_VERSION, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+                },
+                Dxgi::{
+                    Common::{DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_SAMPLE_DESC},
+                    IDXGIResource,
+                },
+            },
+        },
+    };
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE, WAIT_ABANDONED, WAIT_OBJECT_0},
+        System::{
+            Memory::{
+                CreateFileMappingA, MapViewOfFile, UnmapViewOfFile, VirtualQuery, FILE_MAP_ALL_ACCESS,
+                MEMORY_BASIC_INFORMATION, MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
+            },
+            Threading::{CreateMutexA, ReleaseMutex, WaitForSingleObject},
+        },
+    };
+
+    const SENDER_NAMES: &str = "SpoutSenderNames";
+    const ACTIVE_SENDER: &str = "ActiveSenderName";
+    const NAME_SLOT: usize = MAX_SENDER_NAME_LEN + 1;
+    // Spout's default sender limit; a list created by an older Spout may be shorter
+    const MAX_SENDERS: usize = 64;
+    // sizeof(SharedTextureInfo): 5 u32, wchar_t description[128], u32 partnerId
+    const TEXTURE_INFO_SIZE: usize = 280;
+    // Spout waits this long for its mutexes too
+    const MUTEX_TIMEOUT_MS: u32 = 67;
+
+    // A named, pagefile-backed shared memory block
+    struct SharedMemory {
+        handle: HANDLE,
+        view: MEMORY_MAPPED_VIEW_ADDRESS,
+        len: usize,
+    }
+
+    impl SharedMemory {
+        // Opens the block if another process created it already; `size` is only used to create it
+        fn open_or_create(name: &str, size: usize) -> io::Result<Self> {
+            let name = CString::new(name).map_err(io::Error::other)?;
+            // SAFETY: `name` is NUL terminated and outlives the call; no security attributes
+            let handle = unsafe {
+                CreateFileMappingA(INVALID_HANDLE_VALUE, ptr::null(), PAGE_READWRITE, 0, size as u32, name.as_ptr().cast())
+            };
+            if handle.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `handle` is a valid mapping; 0 bytes maps all of it, whatever size its creator picked
+            let view = unsafe { MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, 0) };
+            if view.Value.is_null() {
+                let e = io::Error::last_os_error();
+                // SAFETY: closing the handle opened above
+                unsafe { CloseHandle(handle) };
+                return Err(e);
+            }
+            let mut info: MEMORY_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+            // SAFETY: `view` is a live mapping and `info` is large enough for the answer
+            unsafe { VirtualQuery(view.Value, &mut info, std::mem::size_of::<MEMORY_BASIC_INFORMATION>()) };
+            Ok(Self { handle, view, len: info.RegionSize.min(size) })
+        }
+
+        fn bytes(&mut self) -> &mut [u8] {
+            // SAFETY: the view stays mapped for the lifetime of self and is at least `len` bytes long.
+            // Other processes may write it concurrently; the names list is guarded by its named mutex.
+            unsafe { std::slice::from_raw_parts_mut(self.view.Value.cast::<u8>(), self.len) }
+        }
+    }
+
+    impl Drop for SharedMemory {
+        fn drop(&mut self) {
+            // SAFETY: unmapping the view and closing the handle created in open_or_create
+            unsafe {
+                UnmapViewOfFile(self.view);
+                CloseHandle(self.handle);
+            }
+        }
+    }
+
+    struct NamedMutex(HANDLE);
+
+    impl NamedMutex {
+        fn open_or_create(name: &str) -> io::Result<Self> {
+            let name = CString::new(name).map_err(io::Error::other)?;
+            // SAFETY: `name` is NUL terminated and outlives the call
+            let handle = unsafe { CreateMutexA(ptr::null(), 0, name.as_ptr().cast()) };
+            if handle.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self(handle))
+        }
+
+        // Runs `f` holding the mutex; a process that died holding it doesn't block us
+        fn with<T>(&self, f: impl FnOnce() -> T) -> io::Result<T> {
+            // SAFETY: waiting on a mutex handle owned by self
+            match unsafe { WaitForSingleObject(self.0, MUTEX_TIMEOUT_MS) } {
+                WAIT_OBJECT_0 | WAIT_ABANDONED => {}
+                _ => return Err(io::Error::new(io::ErrorKind::TimedOut, "Spout mutex is held by another process")),
+            }
+            let result = f();
+            // SAFETY: this thread acquired the mutex above
+            unsafe { ReleaseMutex(self.0) };
+            Ok(result)
+        }
+    }
+
+    impl Drop for NamedMutex {
+        fn drop(&mut self) {
+            // SAFETY: closing the handle created in open_or_create
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    fn read_slots(bytes: &[u8]) -> Vec<String> {
+        bytes
+            .chunks_exact(NAME_SLOT)
+            .map(|slot| &slot[..slot.iter().position(|&b| b == 0).unwrap_or(slot.len())])
+            .take_while(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect()
+    }
+
+    fn write_slots(bytes: &mut [u8], names: &[String]) {
+        bytes.fill(0);
+        for (slot, name) in bytes.chunks_exact_mut(NAME_SLOT).zip(names) {
+            slot[..name.len()].copy_from_slice(name.as_bytes());
+        }
+    }
+
+    // Add or remove `name` in the sender list, and claim/release the active sender slot
+    fn update_registry(name: &str, register: bool) -> io::Result<()> {
+        let mut names_memory = SharedMemory::open_or_create(SENDER_NAMES, NAME_SLOT * MAX_SENDERS)?;
+        let mutex = NamedMutex::open_or_create(&format!("{}_mutex", SENDER_NAMES))?;
+        mutex.with(|| {
+            let bytes = names_memory.bytes();
+            let mut names = read_slots(bytes);
+            names.retain(|existing| existing != name);
+            if register {
+                if names.len() >= bytes.len() / NAME_SLOT {
+                    return Err(io::Error::new(io::ErrorKind::StorageFull, "too many Spout senders"));
+                }
+                names.push(name.to_string());
+                // Spout keeps the list as a sorted set
+                names.sort();
+            }
+            write_slots(bytes, &names);
+            Ok(())
+        })??;
+        let mut active = SharedMemory::open_or_create(ACTIVE_SENDER, NAME_SLOT)?;
+        let active = active.bytes();
+        let current = read_slots(active).pop();
+        match (register, current.as_deref()) {
+            (true, None) => write_slots(active, &[name.to_string()]),
+            (false, Some(current)) if current == name => write_slots(active, &[]),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    struct Texture {
+        texture: ID3D11Texture2D,
+        width: u32,
+        height: u32,
+    }
+
+    pub struct Sender {
+        name: String,
+        device: ID3D11Device,
+        context: ID3D11DeviceContext,
+        texture: Option<Texture>,
+        info: SharedMemory,
+        access: NamedMutex,
+        registered: bool,
+    }
+
+    // SAFETY: D3D11 devices are free threaded; the immediate context is not, but a Sender is only
+    // ever used behind SpoutOutput's mutex, so one thread at a time touches it
+    unsafe impl Send for Sender {}
+
+    impl Sender {
+        pub fn create(name: &str) -> io::Result<Self> {
+            let mut device = None;
+            let mut context = None;
+            // SAFETY: out pointers are valid for the call; default adapter, no software rasterizer
+            unsafe {
+                D3D11CreateDevice(
+                    None,
+                    D3D_DRIVER_TYPE_HARDWARE,
+                    HMODULE::default(),
+                    D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+                    None,
+                    D3D11_SDK_VERSION,
+                    Some(&mut device),
+                    None,
+                    Some(&mut context),
+                )
+            }
+            .map_err(io::Error::other)?;
+            let (Some(device), Some(context)) = (device, context) else {
+                return Err(io::Error::other("D3D11CreateDevice returned no device"));
+            };
+            let info = SharedMemory::open_or_create(name, TEXTURE_INFO_SIZE)?;
+            let access = NamedMutex::open_or_create(&format!("{}_SpoutAccessMutex", name))?;
+            // Registered with the first frame, once there's a texture to point at
+            Ok(Self { name: name.to_string(), device, context, texture: None, info, access, registered: false })
+        }
+
+        pub fn send(&mut self, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+            if self.texture.as_ref().is_none_or(|texture| texture.width != width || texture.height != height) {
+                self.resize(width, height)?;
+            }
+            let Some(texture) = &self.texture else {
+                return Ok(());
+            };
+            self.access.with(|| {
+                // SAFETY: `rgba` holds height rows of width * 4 bytes (a packed RGBA8 frame of this size)
+                unsafe {
+                    self.context.UpdateSubresource(&texture.texture, 0, None, rgba.as_ptr().cast(), width * 4, 0);
+                    self.context.Flush();
+                }
+            })?;
+            Ok(())
+        }
+
+        // New shared texture for a new size, published through the sender info
+        fn resize(&mut self, width: u32, height: u32) -> io::Result<()> {
+            let desc = D3D11_TEXTURE2D_DESC {
+                Width: width,
+                Height: height,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: (D3D11_BIND_SHADER_RESOURCE.0 | D3D11_BIND_RENDER_TARGET.0) as u32,
+                CPUAccessFlags: 0,
+                MiscFlags: D3D11_RESOURCE_MISC_SHARED.0 as u32,
+            };
+            let mut texture = None;
+            // SAFETY: `desc` describes a valid texture and the out pointer is valid for the call
+            unsafe { self.device.CreateTexture2D(&desc, None, Some(&mut texture)) }.map_err(io::Error::other)?;
+            let texture = texture.ok_or_else(|| io::Error::other("CreateTexture2D returned no texture"))?;
+            let resource: IDXGIResource = texture.cast().map_err(io::Error::other)?;
+            // SAFETY: the texture was created shareable, so it has a legacy shared handle
+            let handle = unsafe { resource.GetSharedHandle() }.map_err(io::Error::other)?;
+
+            // shareHandle, width, height, format, usage; description and partnerId stay zero
+            let info = self.info.bytes();
+            info.fill(0);
+            // Legacy shared handles are 32 bit values even in 64 bit processes
+            let fields = [handle.0 as usize as u32, width, height, DXGI_FORMAT_R8G8B8A8_UNORM.0 as u32, 0];
+            for (chunk, value) in info.chunks_exact_mut(4).zip(fields) {
+                chunk.copy_from_slice(&value.to_le_bytes());
+            }
+            self.texture = Some(Texture { texture, width, height });
+            if !self.registered {
+                update_registry(&self.name, true)?;
+                self.registered = true;
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            if self.registered {
+                let _ = update_registry(&self.name, false);
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use std::io;
+
+    // Can't be created, so there's never a sender to publish to
+    pub enum Sender {}
+
+    impl Sender {
+        pub fn create(_name: &str) -> io::Result<Self> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "Spout is only available on Windows"))
+        }
+
+        pub fn send(&mut self, _width: u32, _height: u32, _rgba: &[u8]) -> io::Result<()> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,226 @@
+// --- Task supervisor ---
+// A panic in a background task (the transform listener, the frame writer,
+// a connection loop) used to end that task silently and take its feature
+// down with it until the app was restarted. Tasks spawned through the
+// supervisor run under catch_unwind instead: a panic is logged, counted, and
+// the task is started again from scratch after a backoff delay (reset once
+// it has run for a while without panicking). After too many panics in a row
+// it stays down. The health of every supervised task is part of
+// get_connection_status. A task that returns normally is not restarted.
+use crate::{
+    backoff::{Backoff, ReconnectPolicy},
+    runtime::IpcRuntime,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{error, warn};
+
+const RESTART_POLICY: ReconnectPolicy =
+    ReconnectPolicy { initial_delay_ms: 500, max_delay_ms: 30_000, multiplier: 2.0, jitter: 0.2, max_retries: Some(10) };
+// A task that ran this long before panicking starts over with the shortest delay
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskHealth {
+    pub running: bool,
+    pub panics: u32,
+    pub restarts: u32,
+    pub last_panic: Option<String>,
+    // Too many panics in a row; the task won't be restarted
+    pub gave_up: bool,
+    // Bumped by every spawn under this name, so a replaced task that's still winding down can't mark its
+    // successor stopped
+    #[serde(skip)]
+    instance: u64,
+}
+
+type HealthMap = Arc<Mutex<BTreeMap<&'static str, TaskHealth>>>;
+
+#[derive(Clone)]
+pub struct TaskSupervisor {
+    rt: IpcRuntime,
+    health: HealthMap,
+}
+
+impl TaskSupervisor {
+    pub fn new(rt: IpcRuntime) -> Self {
+        Self { rt, health: Arc::new(Mutex::new(BTreeMap::new())) }
+    }
+
+    // Run the future made by `task`, making a new one whenever the previous one panicked
+    pub fn spawn<F, Fut>(&self, name: &'static str, mut task: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let health = Arc::clone(&self.health);
+        self.rt.spawn(async move {
+            // Also marks the task stopped when it's aborted
+            let _running = Running::start(&health, name);
+            let mut backoff = Backoff::new(RESTART_POLICY);
+            loop {
+                let started = Instant::now();
+                let Err(panic) = CatchUnwind(Box::pin(task())).await else {
+                    return;
+                };
+                let message = panic_message(panic.as_ref());
+                error!("[Rust Supervisor] {} task panicked: {}", name, message);
+                if started.elapsed() >= STABLE_RUN {
+                    backoff = Backoff::new(RESTART_POLICY);
+                }
+                let delay = backoff.next_delay();
+                update(&health, name, |task| {
+                    task.panics = task.panics.saturating_add(1);
+                    task.last_panic = Some(message);
+                    task.gave_up = delay.is_none();
+                });
+                let Some(delay) = delay else {
+                    error!("[Rust Supervisor] {} task keeps panicking, not restarting it.", name);
+                    return;
+                };
+                warn!("[Rust Supervisor] Restarting {} task in {:?}.", name, delay);
+                sleep(delay).await;
+                update(&health, name, |task| task.restarts = task.restarts.saturating_add(1));
+            }
+        })
+    }
+
+    // Health of every task spawned so far, by name
+    pub fn health(&self) -> BTreeMap<String, TaskHealth> {
+        self.health.lock().iter().map(|(name, health)| (name.to_string(), health.clone())).collect()
+    }
+}
+
+fn update(health: &HealthMap, name: &'static str, change: impl FnOnce(&mut TaskHealth)) {
+    change(health.lock().entry(name).or_default());
+}
+
+// Marks a task running for as long as it's alive, unless another one under its name started since
+struct Running {
+    health: HealthMap,
+    name: &'static str,
+    instance: u64,
+}
+
+impl Running {
+    fn start(health: &HealthMap, name: &'static str) -> Self {
+        let mut instance = 0;
+        update(health, name, |task| {
+            task.instance += 1;
+            task.running = true;
+            task.gave_up = false;
+            instance = task.instance;
+        });
+        Self { health: Arc::clone(health), name, instance }
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        update(&self.health, self.name, |task| {
+            if task.instance == self.instance {
+                task.running = false;
+            }
+        });
+    }
+}
+
+// Resolves to Err with the panic payload if polling the future panics
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = self.0.as_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+pub fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::TaskSlot;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc,
+    };
+
+    #[tokio::test]
+    async fn a_panicking_task_is_restarted() {
+        let supervisor = TaskSupervisor::new(IpcRuntime::new(tokio::runtime::Handle::current()));
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&runs);
+        let task = supervisor.spawn("flaky", move || {
+            let runs = Arc::clone(&counter);
+            async move {
+                if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("first run fails");
+                }
+            }
+        });
+        task.await.unwrap();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let health = &supervisor.health()["flaky"];
+        assert_eq!((health.panics, health.restarts, health.running, health.gave_up), (1, 1, false, false));
+        assert_eq!(health.last_panic.as_deref(), Some("first run fails"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn a_replaced_task_leaves_its_successor_running() {
+        let supervisor = TaskSupervisor::new(IpcRuntime::new(tokio::runtime::Handle::current()));
+        let slot = TaskSlot::default();
+        // The first task is in the middle of a poll when it's replaced, so it only goes away after its
+        // successor started
+        let (entered, stuck) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        let released = Arc::new(Mutex::new(released));
+        slot.replace(|| {
+            supervisor.spawn("loop", move || {
+                let (entered, released) = (entered.clone(), Arc::clone(&released));
+                async move {
+                    entered.send(()).unwrap();
+                    released.lock().recv().unwrap();
+                    std::future::pending().await
+                }
+            })
+        });
+        stuck.recv().unwrap();
+        slot.replace(|| supervisor.spawn("loop", std::future::pending::<()>));
+        sleep(Duration::from_millis(10)).await;
+        release.send(()).unwrap();
+        sleep(Duration::from_millis(10)).await;
+
+        assert!(supervisor.health()["loop"].running);
+        assert!(slot.abort());
+        sleep(Duration::from_millis(10)).await;
+        assert!(!supervisor.health()["loop"].running);
+    }
+}
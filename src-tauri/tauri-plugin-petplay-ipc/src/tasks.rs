@@ -0,0 +1,93 @@
+// --- Background task slots ---
+// Holds the JoinHandle of a background task that must never run twice
+// (e.g. a pipe's connection loop). Spawning into an occupied slot aborts the
+// previous task first, so repeated reconnects can't stack up racing loops.
+// An abort only lands at the task's next await, though, so a loop that has
+// already opened its pipe can still finish installing it. Such loops hold a
+// Generation token and check it's still current before they do.
+use parking_lot::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::task::JoinHandle;
+
+#[derive(Default)]
+pub struct TaskSlot {
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl TaskSlot {
+    // Abort whatever is in the slot, then store the task returned by `spawn`
+    pub fn replace(&self, spawn: impl FnOnce() -> JoinHandle<()>) {
+        let mut handle = self.handle.lock();
+        if let Some(old) = handle.take() {
+            old.abort();
+        }
+        *handle = Some(spawn());
+    }
+
+    // Abort the task in the slot, returning whether one was still running
+    pub fn abort(&self) -> bool {
+        match self.handle.lock().take() {
+            Some(task) => {
+                let was_running = !task.is_finished();
+                task.abort();
+                was_running
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.handle.lock().as_ref().is_some_and(|task| !task.is_finished())
+    }
+}
+
+// Counts connection attempts; only the latest one may install what it opened
+#[derive(Clone, Default)]
+pub struct Generation(Arc<AtomicU64>);
+
+impl Generation {
+    // Start a new generation, invalidating every token handed out before
+    pub fn advance(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    pub fn is_current(&self, token: u64) -> bool {
+        self.0.load(Ordering::Acquire) == token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test]
+    async fn repeated_replace_keeps_a_single_task_alive() {
+        let slot = TaskSlot::default();
+        // Every spawned task holds a clone; aborted tasks drop theirs
+        let alive = Arc::new(());
+
+        for _ in 0..100 {
+            let alive = Arc::clone(&alive);
+            slot.replace(|| {
+                tokio::spawn(async move {
+                    let _alive = alive;
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                })
+            });
+        }
+        // Give the runtime a chance to drop the aborted futures
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(slot.is_running());
+        assert_eq!(Arc::strong_count(&alive), 2);
+
+        assert!(slot.abort());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!slot.is_running());
+        assert_eq!(Arc::strong_count(&alive), 1);
+    }
+}
@@ -0,0 +1,104 @@
+// --- Transform channels ---
+// Alternative to the transform-update events for windows that render poses
+// every frame: subscribe_transforms hands us a tauri::ipc::Channel and only
+// that window receives the poses, as raw bytes instead of JSON. The
+// frontend gets an ArrayBuffer and can view the floats as a Float32Array
+// without parsing. Layout of each message (little endian), by format:
+//
+//   "pose" (default)
+//   [0..64)    pose record body (see pose.rs, without the length prefix)
+//   [64..128)  the same pose as a row-major 4x4 matrix (16 x f32)
+//
+//   "matrix", for render loops that only want the matrix
+//   [0..4)     device id (u32)
+//   [4..8)     reserved
+//   [8..16)    timestamp, microseconds since the Unix epoch (u64)
+//   [16..80)   row-major 4x4 matrix (16 x f32), i.e. new Float32Array(buffer, 16, 16)
+//
+// Poses go through the same coalescing as the events.
+use crate::pose::{Pose, MIN_RECORD_SIZE};
+use byteorder::{ByteOrder, LittleEndian};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use tauri::ipc::{Channel, InvokeResponseBody};
+
+pub const TRANSFORM_MESSAGE_SIZE: usize = MIN_RECORD_SIZE + 16 * 4;
+pub const MATRIX_HEADER_SIZE: usize = 16;
+pub const MATRIX_MESSAGE_SIZE: usize = MATRIX_HEADER_SIZE + 16 * 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformFormat {
+    #[default]
+    Pose,
+    Matrix,
+}
+
+struct Subscriber {
+    channel: Channel<InvokeResponseBody>,
+    // Only these devices, or all of them if None
+    devices: Option<Vec<u32>>,
+    format: TransformFormat,
+}
+
+#[derive(Default)]
+pub struct TransformSubscribers {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+pub fn encode(pose: &Pose, matrix: &[f32]) -> Vec<u8> {
+    let mut message = vec![0u8; TRANSFORM_MESSAGE_SIZE];
+    message[..MIN_RECORD_SIZE].copy_from_slice(&pose.encode());
+    for (chunk, value) in message[MIN_RECORD_SIZE..].chunks_exact_mut(4).zip(matrix) {
+        LittleEndian::write_f32(chunk, *value);
+    }
+    message
+}
+
+pub fn encode_matrix(pose: &Pose, matrix: &[f32]) -> Vec<u8> {
+    let mut message = vec![0u8; MATRIX_MESSAGE_SIZE];
+    LittleEndian::write_u32(&mut message[0..4], pose.device_id);
+    LittleEndian::write_u64(&mut message[8..16], pose.timestamp_us);
+    for (chunk, value) in message[MATRIX_HEADER_SIZE..].chunks_exact_mut(4).zip(matrix) {
+        LittleEndian::write_f32(chunk, *value);
+    }
+    message
+}
+
+impl TransformSubscribers {
+    // Returns the subscription id (the channel id) for unsubscribe
+    pub fn subscribe(&self, channel: Channel<InvokeResponseBody>, devices: Option<Vec<u32>>, format: TransformFormat) -> u32 {
+        let id = channel.id();
+        self.subscribers.lock().push(Subscriber { channel, devices, format });
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        let mut subscribers = self.subscribers.lock();
+        let before = subscribers.len();
+        subscribers.retain(|subscriber| subscriber.channel.id() != id);
+        subscribers.len() != before
+    }
+
+    // Send a pose to every interested subscriber. A failed send means the
+    // window (and its channel) is gone, so that subscriber is dropped.
+    pub fn send(&self, pose: &Pose, matrix: &[f32]) {
+        let mut subscribers = self.subscribers.lock();
+        if subscribers.is_empty() {
+            return;
+        }
+        // Each format is encoded at most once per pose
+        let (mut pose_message, mut matrix_message) = (None, None);
+        subscribers.retain(|subscriber| {
+            let wanted = subscriber.devices.as_ref().is_none_or(|devices| devices.contains(&pose.device_id));
+            if !wanted {
+                return true;
+            }
+            let message = match subscriber.format {
+                TransformFormat::Pose => pose_message.get_or_insert_with(|| encode(pose, matrix)),
+                TransformFormat::Matrix => matrix_message.get_or_insert_with(|| encode_matrix(pose, matrix)),
+            };
+            subscriber.channel.send(InvokeResponseBody::Raw(message.clone())).is_ok()
+        });
+    }
+}
@@ -0,0 +1,465 @@
+// --- Transport abstraction ---
+// The petplay backend protocol is the same on every platform, only the
+// underlying byte stream differs: named pipes on Windows, Unix domain
+// sockets on Linux and macOS. Everything above this module talks to a
+// `PlatformTransport` and never to the OS-specific type directly.
+//
+// PipeOptions tune the OS buffers of a connection ([pipeOptions] in
+// puppyweb.toml, or configure_pipe). On Windows the buffer sizes of a named
+// pipe are fixed by whoever creates it, so they only apply to pipes the app
+// serves itself (the mock backend); the backend has to create its pipes with
+// large enough buffers too. Unix sockets take the sizes on the client side as
+// SO_RCVBUF / SO_SNDBUF. Message mode only exists for Windows named pipes.
+//
+// A named pipe whose instances are all taken fails to open with
+// ERROR_PIPE_BUSY. Connecting then waits (WaitNamedPipe) until an instance is
+// free and retries right away instead of going through the reconnect backoff;
+// failures are classified so logs and events can tell "busy" from "not found".
+//
+// Any endpoint of the form "tcp://host:port" connects over TCP instead, so
+// the backend can run on another machine. The protocol is the same byte
+// stream either way and the connection loops don't know the difference:
+// reconnects, backoff and heartbeats work as they do for the local pipes.
+// Buffer sizes apply to the socket like they do for Unix sockets; the pipe
+// mode doesn't apply (TCP is always a byte stream).
+//
+// Pipes the app serves are only open to the accounts PipeSecurity allows
+// (see pipe_security.rs).
+use crate::pipe_security::PipeSecurity;
+use serde::{Deserialize, Serialize};
+use socket2::SockRef;
+use std::{
+    future::Future,
+    io::{self, IoSlice},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::{TcpListener, TcpStream},
+};
+
+// Endpoint prefix selecting the TCP transport, e.g. "tcp://192.168.1.20:7100"
+pub const TCP_SCHEME: &str = "tcp://";
+
+// Largest buffer size configure_pipe accepts
+pub const MAX_PIPE_BUFFER_SIZE: u32 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PipeMode {
+    // Plain byte stream; the protocol does its own framing
+    #[default]
+    Byte,
+    // Every write is delivered as one message (Windows only); the backend's pipe has to be in message mode as well
+    Message,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PipeOptions {
+    // Backend -> app buffer in bytes; None keeps the OS default
+    pub in_buffer_size: Option<u32>,
+    // App -> backend buffer in bytes; frames go this way, so this is the one that matters
+    pub out_buffer_size: Option<u32>,
+    pub mode: PipeMode,
+}
+
+impl PipeOptions {
+    // Reject sizes and modes this platform can't do
+    pub fn validate(&self) -> Result<(), String> {
+        for size in [self.in_buffer_size, self.out_buffer_size].into_iter().flatten() {
+            if size == 0 || size > MAX_PIPE_BUFFER_SIZE {
+                return Err(format!("Pipe buffer sizes must be between 1 and {} bytes", MAX_PIPE_BUFFER_SIZE));
+            }
+        }
+        if self.mode == PipeMode::Message && !cfg!(windows) {
+            return Err("Message mode is only available for Windows named pipes".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Why a connect attempt failed, as far as the connection loops care
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectFailure {
+    // Nothing serves the endpoint (backend not running, or wrong path)
+    NotFound,
+    // The backend is there but has no free pipe instance / backlog slot
+    Busy,
+    // Whatever serves the endpoint doesn't know the auth token (see auth.rs)
+    Unauthorized,
+    // Anything else, e.g. a failed handshake
+    Other,
+}
+
+impl ConnectFailure {
+    pub fn of(error: &io::Error) -> Self {
+        if platform::is_busy(error) {
+            Self::Busy
+        } else if matches!(error.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) {
+            Self::NotFound
+        } else if error.kind() == io::ErrorKind::PermissionDenied {
+            Self::Unauthorized
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::NotFound => "not found",
+            Self::Busy => "busy",
+            Self::Unauthorized => "unauthorized",
+            Self::Other => "error",
+        })
+    }
+}
+
+// A connected, bidirectional byte stream to the backend.
+// Reading and writing go through the AsyncRead/AsyncWrite supertraits so the
+// stream can be split with tokio::io::split like any other tokio IO type.
+pub trait Transport: AsyncRead + AsyncWrite + Send + Unpin + Sized + 'static {
+    // Open a client connection to the given endpoint (pipe name or socket path)
+    fn connect(endpoint: &str, options: &PipeOptions) -> impl Future<Output = io::Result<Self>> + Send;
+
+    // Flush and shut down the write side of the connection
+    fn close(mut self) -> impl Future<Output = io::Result<()>> + Send {
+        async move { self.shutdown().await }
+    }
+}
+
+// write_all for a list of buffers: hands all of them to the OS in one
+// gathered write (writev / WriteFile on the pipe) instead of concatenating
+// them first, and only issues more writes if the pipe takes less than all.
+pub async fn write_all_vectored<W: AsyncWrite + Unpin>(writer: &mut W, parts: &[&[u8]]) -> io::Result<()> {
+    let mut slices: Vec<IoSlice> = parts.iter().filter(|part| !part.is_empty()).map(|part| IoSlice::new(part)).collect();
+    let mut remaining = &mut slices[..];
+    while !remaining.is_empty() {
+        let written = writer.write_vectored(remaining).await?;
+        if written == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        IoSlice::advance_slices(&mut remaining, written);
+    }
+    Ok(())
+}
+
+fn tcp_address(endpoint: &str) -> Option<&str> {
+    endpoint.strip_prefix(TCP_SCHEME)
+}
+
+// A connection over the platform's local pipe (T) or over TCP
+pub enum Stream<T> {
+    Local(T),
+    Tcp(TcpStream),
+}
+
+// The app's end of a backend connection
+pub type PlatformTransport = Stream<platform::LocalClient>;
+// The mock backend's end
+pub type ServerTransport = Stream<platform::LocalServer>;
+
+impl Transport for PlatformTransport {
+    fn connect(endpoint: &str, options: &PipeOptions) -> impl Future<Output = io::Result<Self>> + Send {
+        let endpoint = endpoint.to_owned();
+        let options = *options;
+        async move {
+            match tcp_address(&endpoint) {
+                Some(address) => {
+                    let stream = TcpStream::connect(address).await?;
+                    configure_tcp(&stream, options.in_buffer_size, options.out_buffer_size)?;
+                    Ok(Self::Tcp(stream))
+                }
+                None => Ok(Self::Local(<platform::LocalClient as Transport>::connect(&endpoint, &options).await?)),
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Stream<T> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Local(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Stream<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Local(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    // Forwarded so write_all_vectored stays a single gathered write on both transports
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Local(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            Self::Tcp(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Local(stream) => stream.is_write_vectored(),
+            Self::Tcp(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Local(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Local(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+// Server end of a pipe or TCP endpoint, only used by the mock backend
+pub enum PipeListener {
+    Local(platform::LocalListener),
+    Tcp(TcpListener, PipeOptions),
+}
+
+impl PipeListener {
+    pub fn bind(endpoint: &str, options: &PipeOptions, security: &PipeSecurity) -> io::Result<Self> {
+        match tcp_address(endpoint) {
+            Some(address) => {
+                let listener = std::net::TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+                Ok(Self::Tcp(TcpListener::from_std(listener)?, *options))
+            }
+            None => Ok(Self::Local(platform::LocalListener::bind(endpoint, options, security)?)),
+        }
+    }
+
+    pub async fn accept(&mut self) -> io::Result<ServerTransport> {
+        match self {
+            Self::Local(listener) => Ok(Stream::Local(listener.accept().await?)),
+            Self::Tcp(listener, options) => {
+                let stream = listener.accept().await?.0;
+                // Mirrored: the server receives what the app sends
+                configure_tcp(&stream, options.out_buffer_size, options.in_buffer_size)?;
+                Ok(Stream::Tcp(stream))
+            }
+        }
+    }
+}
+
+fn configure_tcp(stream: &TcpStream, receive: Option<u32>, send: Option<u32>) -> io::Result<()> {
+    // A frame goes out in one gathered write; Nagle would hold its tail back waiting for an ACK
+    stream.set_nodelay(true)?;
+    set_buffer_sizes(SockRef::from(stream), receive, send)
+}
+
+fn set_buffer_sizes(socket: SockRef<'_>, receive: Option<u32>, send: Option<u32>) -> io::Result<()> {
+    if let Some(size) = receive {
+        socket.set_recv_buffer_size(size as usize)?;
+    }
+    if let Some(size) = send {
+        socket.set_send_buffer_size(size as usize)?;
+    }
+    Ok(())
+}
+
+// --- Windows: named pipes ---
+#[cfg(windows)]
+mod platform {
+    use super::{PipeMode, PipeOptions, Transport};
+    use crate::pipe_security::{PipeSecurity, SecurityAttributes};
+    use std::{
+        future::Future,
+        io,
+        time::{Duration, Instant},
+    };
+    use tokio::net::windows::named_pipe::{self, ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions};
+    use tracing::debug;
+    use windows_sys::Win32::{Foundation::ERROR_PIPE_BUSY, System::Pipes::WaitNamedPipeW};
+
+    // How long one connect waits for a busy pipe before reporting it as busy
+    const PIPE_BUSY_WAIT: Duration = Duration::from_secs(2);
+
+    pub type LocalClient = NamedPipeClient;
+    pub type LocalServer = NamedPipeServer;
+
+    pub const FRAME_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-frames";
+    pub const TRANSFORM_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-transform";
+    pub const INPUT_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-input";
+    pub const AUDIO_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-audio";
+    pub const MICROPHONE_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-mic";
+    pub const VIDEO_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-video";
+    pub const FILES_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-files";
+    pub const POINTER_PIPE_PATH: &str = r"\\.\pipe\petplay-ipc-pointer";
+
+    impl From<PipeMode> for named_pipe::PipeMode {
+        fn from(mode: PipeMode) -> Self {
+            match mode {
+                PipeMode::Byte => Self::Byte,
+                PipeMode::Message => Self::Message,
+            }
+        }
+    }
+
+    impl Transport for NamedPipeClient {
+        fn connect(endpoint: &str, options: &PipeOptions) -> impl Future<Output = io::Result<Self>> + Send {
+            let endpoint = endpoint.to_owned();
+            let mode = options.mode;
+            async move {
+                let deadline = Instant::now() + PIPE_BUSY_WAIT;
+                loop {
+                    // Buffer sizes belong to the server end, only the read mode is up to the client
+                    match ClientOptions::new().pipe_mode(mode.into()).open(&endpoint) {
+                        Err(e) if is_busy(&e) => {
+                            let remaining = deadline.saturating_duration_since(Instant::now());
+                            if remaining.is_zero() {
+                                return Err(e);
+                            }
+                            debug!("[Rust Transport] {} is busy, waiting for a free instance.", endpoint);
+                            // Returns early once an instance is free; another client may still grab it
+                            // first, in which case the next open is busy again and we wait some more
+                            wait_for_instance(&endpoint, remaining).await;
+                        }
+                        result => return result,
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_busy(error: &io::Error) -> bool {
+        error.raw_os_error() == Some(ERROR_PIPE_BUSY as i32)
+    }
+
+    // WaitNamedPipeW blocks, so it runs on the blocking pool
+    async fn wait_for_instance(endpoint: &str, timeout: Duration) {
+        let name: Vec<u16> = endpoint.encode_utf16().chain(Some(0)).collect();
+        let timeout_ms = timeout.as_millis().clamp(1, u32::MAX as u128) as u32;
+        let waited = tokio::task::spawn_blocking(move || {
+            // SAFETY: `name` is a NUL-terminated UTF-16 string that outlives the call
+            unsafe { WaitNamedPipeW(name.as_ptr(), timeout_ms) }
+        });
+        // A failed wait (timeout, or the pipe went away) shows up in the next open
+        let _ = waited.await;
+    }
+
+    // Server end of a pipe
+    pub struct LocalListener {
+        endpoint: String,
+        options: PipeOptions,
+        // Every instance is created with these, so only allowed accounts can open it
+        security: SecurityAttributes,
+        // Instance waiting for the next client
+        next: NamedPipeServer,
+    }
+
+    impl LocalListener {
+        pub fn bind(endpoint: &str, options: &PipeOptions, security: &PipeSecurity) -> io::Result<Self> {
+            let security = SecurityAttributes::new(security)?;
+            // Fails if someone else (e.g. the real backend) already serves this pipe
+            let next = create(server_options(options).first_pipe_instance(true), endpoint, &security)?;
+            Ok(Self { endpoint: endpoint.to_string(), options: *options, security, next })
+        }
+
+        pub async fn accept(&mut self) -> io::Result<NamedPipeServer> {
+            self.next.connect().await?;
+            let next = create(&mut server_options(&self.options), &self.endpoint, &self.security)?;
+            Ok(std::mem::replace(&mut self.next, next))
+        }
+    }
+
+    fn create(options: &mut ServerOptions, endpoint: &str, security: &SecurityAttributes) -> io::Result<NamedPipeServer> {
+        // SAFETY: the attributes and their descriptor stay alive for the duration of the call
+        unsafe { options.create_with_security_attributes_raw(endpoint, security.as_raw()) }
+    }
+
+    fn server_options(options: &PipeOptions) -> ServerOptions {
+        let mut server = ServerOptions::new();
+        server.pipe_mode(options.mode.into());
+        // The server's in buffer is what the app writes into and vice versa
+        if let Some(size) = options.out_buffer_size {
+            server.in_buffer_size(size);
+        }
+        if let Some(size) = options.in_buffer_size {
+            server.out_buffer_size(size);
+        }
+        server
+    }
+}
+
+// --- Linux / macOS: Unix domain sockets ---
+#[cfg(unix)]
+mod platform {
+    use super::{set_buffer_sizes, PipeOptions, Transport};
+    use crate::pipe_security::{self, PipeSecurity};
+    use socket2::SockRef;
+    use std::{future::Future, io};
+    use tokio::net::{UnixListener, UnixStream};
+
+    pub type LocalClient = UnixStream;
+    pub type LocalServer = UnixStream;
+
+    pub const FRAME_PIPE_PATH: &str = "/tmp/petplay-ipc-frames.sock";
+    pub const TRANSFORM_PIPE_PATH: &str = "/tmp/petplay-ipc-transform.sock";
+    pub const INPUT_PIPE_PATH: &str = "/tmp/petplay-ipc-input.sock";
+    pub const AUDIO_PIPE_PATH: &str = "/tmp/petplay-ipc-audio.sock";
+    pub const MICROPHONE_PIPE_PATH: &str = "/tmp/petplay-ipc-mic.sock";
+    pub const VIDEO_PIPE_PATH: &str = "/tmp/petplay-ipc-video.sock";
+    pub const FILES_PIPE_PATH: &str = "/tmp/petplay-ipc-files.sock";
+    pub const POINTER_PIPE_PATH: &str = "/tmp/petplay-ipc-pointer.sock";
+
+    impl Transport for UnixStream {
+        fn connect(endpoint: &str, options: &PipeOptions) -> impl Future<Output = io::Result<Self>> + Send {
+            let endpoint = endpoint.to_owned();
+            let options = *options;
+            async move {
+                let stream = UnixStream::connect(endpoint).await?;
+                set_buffer_sizes(SockRef::from(&stream), options.in_buffer_size, options.out_buffer_size)?;
+                Ok(stream)
+            }
+        }
+    }
+
+    // Server end of a socket
+    pub struct LocalListener {
+        listener: UnixListener,
+        options: PipeOptions,
+    }
+
+    impl LocalListener {
+        pub fn bind(endpoint: &str, options: &PipeOptions, _security: &PipeSecurity) -> io::Result<Self> {
+            // A socket file left behind by a previous run would make bind fail
+            if std::path::Path::new(endpoint).exists() && std::os::unix::net::UnixStream::connect(endpoint).is_err() {
+                std::fs::remove_file(endpoint)?;
+            }
+            let listener = UnixListener::bind(endpoint)?;
+            pipe_security::restrict(endpoint)?;
+            Ok(Self { listener, options: *options })
+        }
+
+        pub async fn accept(&mut self) -> io::Result<UnixStream> {
+            let stream = self.listener.accept().await?.0;
+            // Mirrored: the server receives what the app sends
+            set_buffer_sizes(SockRef::from(&stream), self.options.out_buffer_size, self.options.in_buffer_size)?;
+            Ok(stream)
+        }
+    }
+
+    // A full listen backlog makes connect fail with EAGAIN
+    pub fn is_busy(error: &io::Error) -> bool {
+        error.kind() == io::ErrorKind::WouldBlock
+    }
+}
+
+pub use platform::{AUDIO_PIPE_PATH, FILES_PIPE_PATH, FRAME_PIPE_PATH, INPUT_PIPE_PATH, MICROPHONE_PIPE_PATH, POINTER_PIPE_PATH, TRANSFORM_PIPE_PATH, VIDEO_PIPE_PATH};
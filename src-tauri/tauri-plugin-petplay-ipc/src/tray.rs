@@ -0,0 +1,164 @@
+// --- System tray ---
+// A tray icon showing whether each pipe is connected, reconnecting or
+// disconnected and the current frame rate, with menu entries to
+// reconnect, open the log folder and quit. The status lines are refreshed
+// whenever the ConnectionTracker changes (the same state machine that feeds
+// the pipe-state-changed events) and once per second for the FPS.
+use crate::{
+    connection::{ConnectionStatus, PipeState, PipeStatus},
+    runtime::IpcRuntime,
+    FramePipeState,
+};
+use std::{io, path::Path, process::Command, time::Duration};
+use tauri::{
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    tray::TrayIconBuilder,
+    AppHandle, Manager, Wry,
+};
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+const TRAY_ID: &str = "petplay-ipc";
+const FPS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const RECONNECT_ID: &str = "petplay-ipc-reconnect";
+const OPEN_LOGS_ID: &str = "petplay-ipc-open-logs";
+const QUIT_ID: &str = "petplay-ipc-quit";
+
+struct StatusLines {
+    frame: MenuItem<Wry>,
+    transform: MenuItem<Wry>,
+    input: MenuItem<Wry>,
+    audio: MenuItem<Wry>,
+    microphone: MenuItem<Wry>,
+    video: MenuItem<Wry>,
+    files: MenuItem<Wry>,
+    pointer: MenuItem<Wry>,
+    fps: MenuItem<Wry>,
+}
+
+// Create the tray icon and the task keeping it current; `changes` ticks on every connection change
+pub fn spawn(app_handle: &AppHandle, rt: &IpcRuntime, mut changes: watch::Receiver<u64>) -> tauri::Result<()> {
+    let status_item = |id: &str| MenuItem::with_id(app_handle, id, "", false, None::<&str>);
+    let lines = StatusLines {
+        frame: status_item("petplay-ipc-frame")?,
+        transform: status_item("petplay-ipc-transform")?,
+        input: status_item("petplay-ipc-input")?,
+        audio: status_item("petplay-ipc-audio")?,
+        microphone: status_item("petplay-ipc-microphone")?,
+        video: status_item("petplay-ipc-video")?,
+        files: status_item("petplay-ipc-files")?,
+        pointer: status_item("petplay-ipc-pointer")?,
+        fps: status_item("petplay-ipc-fps")?,
+    };
+    let menu = Menu::with_items(
+        app_handle,
+        &[
+            &lines.frame,
+            &lines.transform,
+            &lines.input,
+            &lines.audio,
+            &lines.microphone,
+            &lines.video,
+            &lines.files,
+            &lines.pointer,
+            &lines.fps,
+            &PredefinedMenuItem::separator(app_handle)?,
+            &MenuItem::with_id(app_handle, RECONNECT_ID, "Reconnect", true, None::<&str>)?,
+            &MenuItem::with_id(app_handle, OPEN_LOGS_ID, "Open logs", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app_handle)?,
+            &MenuItem::with_id(app_handle, QUIT_ID, "Quit", true, None::<&str>)?,
+        ],
+    )?;
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID).menu(&menu).tooltip("PuppyWeb").on_menu_event(on_menu_event);
+    if let Some(icon) = app_handle.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    let tray = tray.build(app_handle)?;
+
+    let app_handle = app_handle.clone();
+    rt.spawn(async move {
+        loop {
+            let Some((status, fps)) = app_handle
+                .try_state::<FramePipeState>()
+                .map(|state| (state.connections.snapshot(), state.metrics_snapshot().fps))
+            else {
+                return;
+            };
+            if let Err(e) = lines.update(&status, fps) {
+                error!("[Rust Tray] Error updating the tray menu: {}", e);
+            }
+            let tooltip = format!("PuppyWeb\nFrame: {}\nTransform: {}\n{:.0} fps", describe(&status.frame), describe(&status.transform), fps);
+            if let Err(e) = tray.set_tooltip(Some(tooltip)) {
+                error!("[Rust Tray] Error updating the tray tooltip: {}", e);
+            }
+            tokio::select! {
+                changed = changes.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+                _ = sleep(FPS_REFRESH_INTERVAL) => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+impl StatusLines {
+    fn update(&self, status: &ConnectionStatus, fps: f64) -> tauri::Result<()> {
+        self.frame.set_text(format!("Frame pipe: {}", describe(&status.frame)))?;
+        self.transform.set_text(format!("Transform pipe: {}", describe(&status.transform)))?;
+        self.input.set_text(format!("Input pipe: {}", describe(&status.input)))?;
+        self.audio.set_text(format!("Audio pipe: {}", describe(&status.audio)))?;
+        self.microphone.set_text(format!("Microphone pipe: {}", describe(&status.microphone)))?;
+        self.video.set_text(format!("Video pipe: {}", describe(&status.video)))?;
+        self.files.set_text(format!("Files pipe: {}", describe(&status.files)))?;
+        self.pointer.set_text(format!("Pointer pipe: {}", describe(&status.pointer)))?;
+        self.fps.set_text(format!("{:.1} fps", fps))
+    }
+}
+
+fn describe(status: &PipeStatus) -> &'static str {
+    match status.state {
+        PipeState::Connected { .. } => "connected",
+        PipeState::Disconnected => "disconnected",
+        PipeState::Connecting { .. } | PipeState::Handshaking | PipeState::Backoff { .. } => "reconnecting",
+    }
+}
+
+fn on_menu_event(app_handle: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        RECONNECT_ID => {
+            let Some(state) = app_handle.try_state::<FramePipeState>() else {
+                return;
+            };
+            info!("[Rust Tray] Reconnect requested.");
+            let state = state.inner().clone();
+            state.rt.clone().spawn(async move {
+                state.reconnect_frame().await;
+                state.connect();
+            });
+        }
+        OPEN_LOGS_ID => match app_handle.path().app_log_dir() {
+            Ok(dir) => {
+                if let Err(e) = open_folder(&dir) {
+                    error!("[Rust Tray] Error opening {}: {}", dir.display(), e);
+                }
+            }
+            Err(e) => error!("[Rust Tray] No log directory: {}", e),
+        },
+        QUIT_ID => app_handle.exit(0),
+        _ => {}
+    }
+}
+
+fn open_folder(dir: &Path) -> io::Result<()> {
+    let opener = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener).arg(dir).spawn().map(drop)
+}
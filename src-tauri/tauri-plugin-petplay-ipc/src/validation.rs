@@ -0,0 +1,136 @@
+// --- Transform validation ---
+// A backend bug or a torn read can hand us NaNs, infinities or plain garbage,
+// which the webview would happily render as a vanished or exploded overlay.
+// Every matrix, pose, gaze sample, hand skeleton and playspace from the transform pipe is checked before it goes
+// anywhere; a bad one is dropped (so the last good pose stays in effect) and
+// reported through a transform-invalid event.
+use crate::{gaze::Gaze, hands::HandSkeleton, playspace::Playspace, pose::Pose};
+use std::fmt;
+
+// Tolerance for the affine bottom row
+const EPSILON: f32 = 1e-3;
+// Backends send f32 quaternions that drift a little from unit length
+const QUATERNION_TOLERANCE: f32 = 1e-2;
+// Below this the 3x3 part has collapsed (e.g. an all-zero matrix)
+const MIN_DETERMINANT: f32 = 1e-6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Invalid {
+    // NaN or infinity at this index of the checked values
+    NonFinite { index: usize },
+    // Subnormal float, almost certainly uninitialized memory
+    Denormal { index: usize },
+    // Bottom row isn't 0 0 0 1
+    NotAffine,
+    // Rotation/scale part can't be inverted
+    Degenerate { determinant: f32 },
+    // Orientation quaternion too far from unit length
+    NotUnitQuaternion { length: f32 },
+    // Gaze direction too far from unit length
+    NotUnitVector { length: f32 },
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonFinite { index } => write!(f, "value {} is not finite", index),
+            Self::Denormal { index } => write!(f, "value {} is denormal", index),
+            Self::NotAffine => write!(f, "bottom row is not 0 0 0 1"),
+            Self::Degenerate { determinant } => write!(f, "degenerate rotation (determinant {})", determinant),
+            Self::NotUnitQuaternion { length } => write!(f, "orientation is not a unit quaternion (length {})", length),
+            Self::NotUnitVector { length } => write!(f, "direction is not a unit vector (length {})", length),
+        }
+    }
+}
+
+fn check_values(values: &[f32]) -> Result<(), Invalid> {
+    for (index, value) in values.iter().enumerate() {
+        if !value.is_finite() {
+            return Err(Invalid::NonFinite { index });
+        }
+        if value.is_subnormal() {
+            return Err(Invalid::Denormal { index });
+        }
+    }
+    Ok(())
+}
+
+// Row-major 4x4 transform as used by Transform and OverlayTransform messages
+pub fn check_matrix(m: &[f32; 16]) -> Result<(), Invalid> {
+    check_values(m)?;
+    let bottom = [m[12], m[13], m[14], m[15] - 1.0];
+    if bottom.iter().any(|value| value.abs() > EPSILON) {
+        return Err(Invalid::NotAffine);
+    }
+    let determinant = m[0] * (m[5] * m[10] - m[6] * m[9]) - m[1] * (m[4] * m[10] - m[6] * m[8])
+        + m[2] * (m[4] * m[9] - m[5] * m[8]);
+    if determinant.abs() < MIN_DETERMINANT {
+        return Err(Invalid::Degenerate { determinant });
+    }
+    Ok(())
+}
+
+// Checks position, orientation and velocities, in that order (indices count through all of them)
+pub fn check_pose(pose: &Pose) -> Result<(), Invalid> {
+    check_values(&pose_values(pose))?;
+    let length = pose.orientation.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if (length - 1.0).abs() > QUATERNION_TOLERANCE {
+        return Err(Invalid::NotUnitQuaternion { length });
+    }
+    Ok(())
+}
+
+// Checks origin, direction, confidence, openness and the overlay hit, in that order
+pub fn check_gaze(gaze: &Gaze) -> Result<(), Invalid> {
+    check_values(&gaze_values(gaze))?;
+    let length = gaze.direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if (length - 1.0).abs() > QUATERNION_TOLERANCE {
+        return Err(Invalid::NotUnitVector { length });
+    }
+    Ok(())
+}
+
+// Checks every joint's position, orientation and radius, in that order; untracked hands aren't checked
+pub fn check_hand(skeleton: &HandSkeleton) -> Result<(), Invalid> {
+    if !skeleton.tracked {
+        return Ok(());
+    }
+    check_values(&hand_values(skeleton))?;
+    for joint in &skeleton.joints {
+        let length = joint.orientation.iter().map(|c| c * c).sum::<f32>().sqrt();
+        if (length - 1.0).abs() > QUATERNION_TOLERANCE {
+            return Err(Invalid::NotUnitQuaternion { length });
+        }
+    }
+    Ok(())
+}
+
+// Checks the standing-zero matrix, play area and bounds points, in that order
+pub fn check_playspace(playspace: &Playspace) -> Result<(), Invalid> {
+    check_values(&playspace_values(playspace))?;
+    check_matrix(&playspace.standing_zero)
+}
+
+pub fn playspace_values(playspace: &Playspace) -> Vec<f32> {
+    playspace.standing_zero.iter().chain(&playspace.play_area).chain(playspace.bounds.iter().flatten()).copied().collect()
+}
+
+pub fn hand_values(skeleton: &HandSkeleton) -> Vec<f32> {
+    skeleton.joints.iter().flat_map(|joint| joint.position.iter().chain(&joint.orientation).chain([&joint.radius])).copied().collect()
+}
+
+pub fn gaze_values(gaze: &Gaze) -> Vec<f32> {
+    let hit = gaze.overlay_hit.map(|hit| [hit.x, hit.y]);
+    gaze.origin.iter().chain(&gaze.direction).chain(&[gaze.confidence, gaze.left_openness, gaze.right_openness]).chain(hit.iter().flatten()).copied().collect()
+}
+
+// The values a check ran on, for the event's diagnostics
+pub fn pose_values(pose: &Pose) -> Vec<f32> {
+    pose.position
+        .iter()
+        .chain(&pose.orientation)
+        .chain(&pose.linear_velocity)
+        .chain(&pose.angular_velocity)
+        .copied()
+        .collect()
+}
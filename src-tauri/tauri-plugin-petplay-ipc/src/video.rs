@@ -0,0 +1,323 @@
+// --- Video channel ---
+// The reverse of the frame pipe: the backend renders something (a mirror of
+// the headset view, a desktop capture, ...) and the webview displays it. The
+// backend sends encoded images as VideoFrame messages on a pipe of its own
+// (petplay-ipc-video), each tagged with a stream id so several feeds can share
+// it. The frontend gets them two ways:
+//
+//   - subscribe_video hands us a tauri::ipc::Channel and receives every frame
+//     of a stream decoded to RGBA here, ready for an ImageData or a texture
+//     upload. Decoding only happens while someone is subscribed.
+//   - petplay-video://localhost/<stream id> (http://petplay-video.localhost/<id>
+//     on Windows) serves the latest frame of a stream as the backend encoded
+//     it, for an <img>. The "video-frame" event says when there's a new one;
+//     add its timestamp as a query string so the webview doesn't cache it.
+//
+// PNG is decoded with the png crate everywhere; JPEG through WIC, so on
+// Windows only (elsewhere JPEG frames are still served over the protocol but
+// not decoded). Images are limited to MAX_DIMENSION on each side.
+//
+// Payload layout of VideoFrame (little endian):
+//   [0..4)   stream id (u32)
+//   [4]      codec (u8, 0 = PNG, 1 = JPEG)
+//   [5..8)   reserved, 0
+//   [8..16)  timestamp, microseconds since the Unix epoch (u64)
+//   [16..)   the encoded image
+//
+// Channel message layout (little endian):
+//   [0..4)   width (u32)
+//   [4..8)   height (u32)
+//   [8..12)  stream id (u32)
+//   [12..16) reserved
+//   [16..24) timestamp (u64)
+//   [24..)   the pixels as packed RGBA8
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::Bytes;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{collections::HashMap, io};
+use tauri::ipc::{Channel, InvokeResponseBody};
+
+pub const VIDEO_HEADER_SIZE: usize = 16;
+pub const VIDEO_MESSAGE_HEADER_SIZE: usize = 24;
+pub const VIDEO_SCHEME: &str = "petplay-video";
+// Largest width or height decoded, so a hostile header can't make us allocate gigabytes
+pub const MAX_DIMENSION: u32 = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageCodec {
+    Png,
+    Jpeg,
+}
+
+impl ImageCodec {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Png),
+            1 => Some(Self::Jpeg),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Png => 0,
+            Self::Jpeg => 1,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoHeader {
+    pub stream_id: u32,
+    pub codec: ImageCodec,
+    pub timestamp_us: u64,
+}
+
+impl VideoHeader {
+    pub fn encode(&self) -> [u8; VIDEO_HEADER_SIZE] {
+        let mut bytes = [0u8; VIDEO_HEADER_SIZE];
+        LittleEndian::write_u32(&mut bytes[0..4], self.stream_id);
+        bytes[4] = self.codec.code();
+        LittleEndian::write_u64(&mut bytes[8..16], self.timestamp_us);
+        bytes
+    }
+
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        let bytes = payload.get(..VIDEO_HEADER_SIZE)?;
+        Some(Self {
+            stream_id: LittleEndian::read_u32(&bytes[0..4]),
+            codec: ImageCodec::from_code(bytes[4])?,
+            timestamp_us: LittleEndian::read_u64(&bytes[8..16]),
+        })
+    }
+}
+
+// Payload of the "video-frame" event
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoFramePayload {
+    pub stream_id: u32,
+    pub codec: ImageCodec,
+    pub timestamp_us: u64,
+    // Size of the encoded image
+    pub bytes: usize,
+}
+
+// The latest frame of a stream, as the backend sent it
+#[derive(Clone)]
+pub struct EncodedFrame {
+    pub header: VideoHeader,
+    pub image: Bytes,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    // Packed RGBA8, width * height * 4 bytes
+    pub pixels: Vec<u8>,
+}
+
+fn check_dimensions(width: u32, height: u32) -> io::Result<()> {
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}x{} image is empty or larger than {} pixels on a side", width, height, MAX_DIMENSION),
+        ));
+    }
+    Ok(())
+}
+
+pub fn decode(codec: ImageCodec, image: &[u8]) -> io::Result<DecodedImage> {
+    match codec {
+        ImageCodec::Png => decode_png(image),
+        ImageCodec::Jpeg => platform::decode_jpeg(image),
+    }
+}
+
+pub fn decode_png(image: &[u8]) -> io::Result<DecodedImage> {
+    let invalid = |e: png::DecodingError| io::Error::new(io::ErrorKind::InvalidData, e);
+    let limits = png::Limits { bytes: (MAX_DIMENSION as usize).pow(2) * 8 };
+    let mut decoder = png::Decoder::new_with_limits(image, limits);
+    // Palettes, low bit depths and 16 bit channels all come out as 8 bit gray, gray + alpha, RGB or RGBA
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let (width, height) = (reader.info().width, reader.info().height);
+    check_dimensions(width, height)?;
+    let mut buffer = vec![0u8; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer).map_err(invalid)?;
+    buffer.truncate(frame.buffer_size());
+    let pixels = match frame.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&gray| [gray, gray, gray, 255]).collect(),
+        png::ColorType::Indexed => return Err(io::Error::new(io::ErrorKind::InvalidData, "palette wasn't expanded")),
+    };
+    Ok(DecodedImage { width, height, pixels })
+}
+
+pub fn encode_message(header: &VideoHeader, image: &DecodedImage) -> Vec<u8> {
+    let mut message = vec![0u8; VIDEO_MESSAGE_HEADER_SIZE + image.pixels.len()];
+    LittleEndian::write_u32(&mut message[0..4], image.width);
+    LittleEndian::write_u32(&mut message[4..8], image.height);
+    LittleEndian::write_u32(&mut message[8..12], header.stream_id);
+    LittleEndian::write_u64(&mut message[16..24], header.timestamp_us);
+    message[VIDEO_MESSAGE_HEADER_SIZE..].copy_from_slice(&image.pixels);
+    message
+}
+
+struct Subscriber {
+    channel: Channel<InvokeResponseBody>,
+    // Only this stream, or all of them if None
+    stream_id: Option<u32>,
+}
+
+// Subscribers and the latest frame of each stream
+#[derive(Default)]
+pub struct VideoFeed {
+    subscribers: Mutex<Vec<Subscriber>>,
+    latest: Mutex<HashMap<u32, EncodedFrame>>,
+}
+
+impl VideoFeed {
+    // Returns the subscription id (the channel id) for unsubscribe
+    pub fn subscribe(&self, channel: Channel<InvokeResponseBody>, stream_id: Option<u32>) -> u32 {
+        let id = channel.id();
+        self.subscribers.lock().push(Subscriber { channel, stream_id });
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        let mut subscribers = self.subscribers.lock();
+        let before = subscribers.len();
+        subscribers.retain(|subscriber| subscriber.channel.id() != id);
+        subscribers.len() != before
+    }
+
+    // Whether anyone wants this stream decoded
+    pub fn wants(&self, stream_id: u32) -> bool {
+        self.subscribers.lock().iter().any(|subscriber| subscriber.stream_id.is_none_or(|id| id == stream_id))
+    }
+
+    // Keep `frame` as its stream's latest, for the protocol
+    pub fn store(&self, frame: EncodedFrame) {
+        self.latest.lock().insert(frame.header.stream_id, frame);
+    }
+
+    pub fn latest(&self, stream_id: u32) -> Option<EncodedFrame> {
+        self.latest.lock().get(&stream_id).cloned()
+    }
+
+    // Send a decoded frame to every interested subscriber, dropping those whose window is gone
+    pub fn send(&self, header: &VideoHeader, image: &DecodedImage) {
+        let mut subscribers = self.subscribers.lock();
+        let mut message = None;
+        subscribers.retain(|subscriber| {
+            if subscriber.stream_id.is_some_and(|id| id != header.stream_id) {
+                return true;
+            }
+            let message = message.get_or_insert_with(|| encode_message(header, image));
+            subscriber.channel.send(InvokeResponseBody::Raw(message.clone())).is_ok()
+        });
+    }
+}
+
+// The stream id of a petplay-video:// request path ("/3"); the cache-busting query isn't part of it
+pub fn stream_of_path(path: &str) -> Option<u32> {
+    path.trim_start_matches('/').trim_end_matches('/').parse().ok()
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{check_dimensions, DecodedImage};
+    use std::io;
+    use windows::Win32::{
+        Graphics::Imaging::{
+            CLSID_WICImagingFactory, GUID_WICPixelFormat32bppRGBA, IWICImagingFactory, WICConvertBitmapSource,
+            WICDecodeMetadataCacheOnDemand,
+        },
+        System::Com::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED},
+    };
+
+    // Decodes on the calling thread, which is one of the runtime's blocking threads
+    pub fn decode_jpeg(image: &[u8]) -> io::Result<DecodedImage> {
+        // SAFETY: balanced by the CoUninitialize below if it succeeded; S_FALSE (already initialized) counts too
+        let initialized = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+        let result = decode_with_wic(image);
+        if initialized {
+            // SAFETY: everything WIC handed out was dropped inside decode_with_wic
+            unsafe { CoUninitialize() };
+        }
+        result
+    }
+
+    fn decode_with_wic(image: &[u8]) -> io::Result<DecodedImage> {
+        let invalid = |e: windows::core::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+        // SAFETY: COM is initialized on this thread (see decode_jpeg); `image` outlives the stream reading it
+        unsafe {
+            let factory: IWICImagingFactory = CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER).map_err(io::Error::other)?;
+            let stream = factory.CreateStream().map_err(io::Error::other)?;
+            stream.InitializeFromMemory(image).map_err(invalid)?;
+            let decoder = factory.CreateDecoderFromStream(&stream, std::ptr::null(), WICDecodeMetadataCacheOnDemand).map_err(invalid)?;
+            let frame = decoder.GetFrame(0).map_err(invalid)?;
+            let rgba = WICConvertBitmapSource(&GUID_WICPixelFormat32bppRGBA, &frame).map_err(invalid)?;
+            let (mut width, mut height) = (0, 0);
+            rgba.GetSize(&mut width, &mut height).map_err(invalid)?;
+            check_dimensions(width, height)?;
+            let mut pixels = vec![0u8; width as usize * height as usize * 4];
+            rgba.CopyPixels(std::ptr::null(), width * 4, &mut pixels).map_err(invalid)?;
+            Ok(DecodedImage { width, height, pixels })
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::DecodedImage;
+    use std::io;
+
+    pub fn decode_jpeg(_image: &[u8]) -> io::Result<DecodedImage> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "JPEG frames are only decoded on Windows"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(color_type: png::ColorType, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        out
+    }
+
+    #[test]
+    fn pngs_of_any_color_type_decode_to_rgba() {
+        let rgb = decode_png(&png(png::ColorType::Rgb, 2, 1, &[1, 2, 3, 4, 5, 6])).unwrap();
+        assert_eq!(rgb, DecodedImage { width: 2, height: 1, pixels: vec![1, 2, 3, 255, 4, 5, 6, 255] });
+        let gray_alpha = decode_png(&png(png::ColorType::GrayscaleAlpha, 1, 1, &[9, 128])).unwrap();
+        assert_eq!(gray_alpha.pixels, [9, 9, 9, 128]);
+        assert!(decode_png(b"not a png").is_err());
+        assert!(decode_png(&png(png::ColorType::Grayscale, MAX_DIMENSION + 1, 1, &vec![0; MAX_DIMENSION as usize + 1])).is_err());
+
+        let header = VideoHeader { stream_id: 7, codec: ImageCodec::Jpeg, timestamp_us: 99 };
+        assert_eq!(VideoHeader::decode(&header.encode()), Some(header));
+        let message = encode_message(&header, &gray_alpha);
+        assert_eq!((LittleEndian::read_u32(&message[8..12]), &message[VIDEO_MESSAGE_HEADER_SIZE..]), (7, &[9, 9, 9, 128][..]));
+        assert_eq!(stream_of_path("/7"), Some(7));
+        assert_eq!(stream_of_path("/frame"), None);
+    }
+}
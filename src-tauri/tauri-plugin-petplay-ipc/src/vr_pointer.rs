@@ -0,0 +1,277 @@
+// --- VR pointer ---
+// Lets the webview be used from inside VR: the backend intersects the
+// controllers' pointer rays with the overlay quads and sends what they hit,
+// plus keys typed on the VR keyboard, on a pipe of its own
+// (petplay-ipc-pointer). Each event becomes a "vr-pointer" or "vr-key" event,
+// sent to the window that owns the overlay (every window for overlay 0),
+// with coordinates normalized to the overlay: 0,0 is the top left corner and
+// 1,1 the bottom right, so the frontend scales by its own size.
+//
+// With injectDom the events are also replayed into the page as synthetic DOM
+// events, so ordinary buttons, links and scroll areas work without frontend
+// code: pointerover/out, pointer and mouse move/down/up, click when down and
+// up hit the same element, wheel (scrolling the nearest scrollable element
+// unless a handler prevents it), and keydown/keyup on the focused element,
+// with printable keys inserted into text fields. Synthetic events are
+// untrusted, so anything that requires a user gesture (fullscreen, audio
+// autoplay, ...) still doesn't happen.
+//
+//   [vrPointer]
+//   injectDom = true
+//
+// VrPointer payload (little endian):
+//   [0..4)   overlay id (u32)
+//   [4..8)   device id of the pointing controller (u32, as in pose records)
+//   [8]      kind (u8, 0 = move, 1 = down, 2 = up, 3 = leave, 4 = scroll)
+//   [9]      button (u8, 0 = primary/trigger, 1 = secondary, 2 = middle)
+//   [10..12) reserved, 0
+//   [12..16) x, 0 at the left edge to 1 at the right (f32)
+//   [16..20) y, 0 at the top edge to 1 at the bottom (f32)
+//   [20..24) horizontal scroll in wheel notches, positive to the right (f32)
+//   [24..28) vertical scroll in wheel notches, positive down (f32)
+//   [28..36) timestamp, microseconds since the Unix epoch (u64)
+//
+// VrKey payload (little endian):
+//   [0..4)   overlay id (u32)
+//   [4]      0 = key down, 1 = key up
+//   [5]      modifiers (u8 bits: 1 = shift, 2 = ctrl, 4 = alt, 8 = meta)
+//   [6..8)   reserved, 0
+//   [8..16)  timestamp (u64)
+//   [16..)   the key as a DOM KeyboardEvent.key value (UTF-8, e.g. "a", "Enter")
+use crate::pose;
+use byteorder::{ByteOrder, LittleEndian};
+use serde::{Deserialize, Serialize};
+
+pub const POINTER_MESSAGE_SIZE: usize = 36;
+pub const KEY_HEADER_SIZE: usize = 16;
+const MAX_KEY_LENGTH: usize = 32;
+
+const MODIFIER_SHIFT: u8 = 1 << 0;
+const MODIFIER_CTRL: u8 = 1 << 1;
+const MODIFIER_ALT: u8 = 1 << 2;
+const MODIFIER_META: u8 = 1 << 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VrPointerConfig {
+    // Replay the events into the page as DOM events too
+    pub inject_dom: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointerKind {
+    Move,
+    Down,
+    Up,
+    Leave,
+    Scroll,
+}
+
+impl PointerKind {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Move),
+            1 => Some(Self::Down),
+            2 => Some(Self::Up),
+            3 => Some(Self::Leave),
+            4 => Some(Self::Scroll),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyKind {
+    Down,
+    Up,
+}
+
+// Payload of "vr-pointer"
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VrPointerPayload {
+    pub overlay_id: u32,
+    // Pointing controller, e.g. "controller-right"
+    pub device: String,
+    pub kind: PointerKind,
+    // DOM MouseEvent.button numbering: 0 primary, 1 middle, 2 secondary
+    pub button: u8,
+    pub x: f32,
+    pub y: f32,
+    pub delta_x: f32,
+    pub delta_y: f32,
+    pub timestamp_us: u64,
+}
+
+// Payload of "vr-key"
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VrKeyPayload {
+    pub overlay_id: u32,
+    pub kind: KeyKind,
+    pub key: String,
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+    pub timestamp_us: u64,
+}
+
+pub fn decode_pointer(payload: &[u8]) -> Result<VrPointerPayload, String> {
+    if payload.len() < POINTER_MESSAGE_SIZE {
+        return Err(format!("pointer message of {} bytes is shorter than {}", payload.len(), POINTER_MESSAGE_SIZE));
+    }
+    let kind = PointerKind::from_code(payload[8]).ok_or_else(|| format!("unknown pointer event kind {}", payload[8]))?;
+    let button = match payload[9] {
+        0 => 0,
+        1 => 2,
+        2 => 1,
+        other => return Err(format!("unknown button {}", other)),
+    };
+    let float = |range: std::ops::Range<usize>| LittleEndian::read_f32(&payload[range]);
+    let (x, y, delta_x, delta_y) = (float(12..16), float(16..20), float(20..24), float(24..28));
+    if ![x, y, delta_x, delta_y].iter().all(|value| value.is_finite()) {
+        return Err("pointer coordinates must be finite".to_string());
+    }
+    Ok(VrPointerPayload {
+        overlay_id: LittleEndian::read_u32(&payload[0..4]),
+        device: pose::device_name(LittleEndian::read_u32(&payload[4..8])).into_owned(),
+        kind,
+        button,
+        // Rays that graze the edge can land a hair outside the quad
+        x: x.clamp(0.0, 1.0),
+        y: y.clamp(0.0, 1.0),
+        delta_x,
+        delta_y,
+        timestamp_us: LittleEndian::read_u64(&payload[28..36]),
+    })
+}
+
+pub fn decode_key(payload: &[u8]) -> Result<VrKeyPayload, String> {
+    if payload.len() < KEY_HEADER_SIZE {
+        return Err(format!("key message of {} bytes is shorter than {}", payload.len(), KEY_HEADER_SIZE));
+    }
+    let kind = match payload[4] {
+        0 => KeyKind::Down,
+        1 => KeyKind::Up,
+        other => return Err(format!("unknown key event kind {}", other)),
+    };
+    let key = std::str::from_utf8(&payload[KEY_HEADER_SIZE..]).map_err(|_| "key isn't UTF-8".to_string())?;
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(format!("key must be 1 to {} bytes long", MAX_KEY_LENGTH));
+    }
+    let modifiers = payload[5];
+    Ok(VrKeyPayload {
+        overlay_id: LittleEndian::read_u32(&payload[0..4]),
+        kind,
+        key: key.to_string(),
+        shift: modifiers & MODIFIER_SHIFT != 0,
+        ctrl: modifiers & MODIFIER_CTRL != 0,
+        alt: modifiers & MODIFIER_ALT != 0,
+        meta: modifiers & MODIFIER_META != 0,
+        timestamp_us: LittleEndian::read_u64(&payload[8..16]),
+    })
+}
+
+// Evaluated in the page with the event's JSON; per-pointer state lives on window.__petplayVrPointers
+const INJECT_POINTER: &str = r#"(e) => {
+  const pointers = (window.__petplayVrPointers ??= {});
+  const state = (pointers[e.device] ??= { over: null, pressed: null, buttons: 0 });
+  const x = e.x * window.innerWidth, y = e.y * window.innerHeight;
+  const init = { clientX: x, clientY: y, screenX: x, screenY: y, button: e.button, pointerId: Object.keys(pointers).indexOf(e.device) + 2,
+    pointerType: 'pen', isPrimary: true, bubbles: true, cancelable: true, composed: true };
+  const fire = (target, Type, type, extra) => target.dispatchEvent(new Type(type, { ...init, buttons: state.buttons, ...extra }));
+  if (e.kind === 'leave') {
+    if (state.over) { fire(state.over, PointerEvent, 'pointerout'); fire(state.over, PointerEvent, 'pointerleave', { bubbles: false }); }
+    state.over = null; state.pressed = null; state.buttons = 0;
+    return;
+  }
+  const target = document.elementFromPoint(x, y) ?? document.documentElement;
+  if (state.over !== target) {
+    if (state.over) fire(state.over, PointerEvent, 'pointerout');
+    fire(target, PointerEvent, 'pointerover');
+    state.over = target;
+  }
+  const mask = [1, 4, 2][e.button] ?? 1;
+  if (e.kind === 'scroll') {
+    const dx = e.deltaX * 100, dy = e.deltaY * 100;
+    if (fire(target, WheelEvent, 'wheel', { deltaX: dx, deltaY: dy, deltaMode: 0 })) {
+      let element = target;
+      while (element && !(element.scrollHeight > element.clientHeight || element.scrollWidth > element.clientWidth) ||
+             element && ['visible', 'hidden'].includes(getComputedStyle(element).overflowY) && element !== document.scrollingElement) {
+        element = element.parentElement;
+      }
+      (element ?? document.scrollingElement)?.scrollBy(dx, dy);
+    }
+  } else if (e.kind === 'move') {
+    fire(target, PointerEvent, 'pointermove');
+    fire(target, MouseEvent, 'mousemove');
+  } else if (e.kind === 'down') {
+    state.buttons |= mask;
+    if (fire(target, PointerEvent, 'pointerdown') && fire(target, MouseEvent, 'mousedown')) target.focus?.({ preventScroll: true });
+    state.pressed = target;
+  } else if (e.kind === 'up') {
+    state.buttons &= ~mask;
+    fire(target, PointerEvent, 'pointerup');
+    fire(target, MouseEvent, 'mouseup');
+    if (state.pressed === target) fire(target, MouseEvent, e.button === 0 ? 'click' : 'auxclick');
+    state.pressed = null;
+  }
+}"#;
+
+const INJECT_KEY: &str = r#"(e) => {
+  const target = document.activeElement ?? document.body;
+  const init = { key: e.key, shiftKey: e.shift, ctrlKey: e.ctrl, altKey: e.alt, metaKey: e.meta, bubbles: true, cancelable: true, composed: true };
+  const proceed = target.dispatchEvent(new KeyboardEvent(e.kind === 'down' ? 'keydown' : 'keyup', init));
+  const editable = typeof target.setRangeText === 'function' && !target.readOnly;
+  if (!proceed || e.kind !== 'down' || !editable || e.ctrl || e.alt || e.meta) return;
+  if ([...e.key].length === 1) {
+    target.setRangeText(e.key, target.selectionStart ?? 0, target.selectionEnd ?? 0, 'end');
+    target.dispatchEvent(new InputEvent('input', { data: e.key, inputType: 'insertText', bubbles: true }));
+  } else if (e.key === 'Backspace' && (target.selectionStart ?? 0) > 0) {
+    const end = target.selectionEnd ?? 0, start = target.selectionStart === end ? end - 1 : target.selectionStart;
+    target.setRangeText('', start, end, 'end');
+    target.dispatchEvent(new InputEvent('input', { inputType: 'deleteContentBackward', bubbles: true }));
+  }
+}"#;
+
+// Script replaying a pointer event in the page
+pub fn pointer_script(event: &VrPointerPayload) -> String {
+    format!("({})({});", INJECT_POINTER, serde_json::to_string(event).unwrap_or_default())
+}
+
+pub fn key_script(event: &VrKeyPayload) -> String {
+    format!("({})({});", INJECT_KEY, serde_json::to_string(event).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pointer_and_key_messages_decode_with_normalized_coordinates() {
+        let mut pointer = [0u8; POINTER_MESSAGE_SIZE];
+        LittleEndian::write_u32(&mut pointer[0..4], 3);
+        LittleEndian::write_u32(&mut pointer[4..8], pose::DEVICE_CONTROLLER_RIGHT);
+        pointer[8] = 1;
+        pointer[9] = 1;
+        LittleEndian::write_f32(&mut pointer[12..16], 0.25);
+        LittleEndian::write_f32(&mut pointer[16..20], 1.001);
+        let event = decode_pointer(&pointer).unwrap();
+        assert_eq!((event.overlay_id, event.device.as_str(), event.kind), (3, "controller-right", PointerKind::Down));
+        assert_eq!((event.button, event.x, event.y), (2, 0.25, 1.0));
+        LittleEndian::write_f32(&mut pointer[12..16], f32::NAN);
+        assert!(decode_pointer(&pointer).is_err());
+        assert!(decode_pointer(&pointer[..20]).is_err());
+
+        let mut key = vec![0u8; KEY_HEADER_SIZE];
+        key[5] = MODIFIER_SHIFT | MODIFIER_CTRL;
+        key.extend_from_slice(b"Enter");
+        let event = decode_key(&key).unwrap();
+        assert_eq!((event.kind, event.key.as_str(), event.shift, event.ctrl, event.alt), (KeyKind::Down, "Enter", true, true, false));
+        assert!(decode_key(&key[..KEY_HEADER_SIZE]).is_err());
+        assert!(key_script(&event).contains(r#""key":"Enter""#));
+    }
+}
@@ -0,0 +1,70 @@
+// --- WebRTC remote viewing ---
+// Publishes the overlay to a WebRTC peer (a spectator page, or a standalone
+// headset) without any named pipes in between. The webview already ships a
+// WebRTC stack, so the peer connection lives in the frontend (src/webrtc.ts):
+// it streams the render canvas as a VP8 or H.264 video track and negotiates
+// the session with a WHIP endpoint (one HTTP POST of the SDP offer, the
+// answer comes back in the response).
+//
+// The remote side can send poses back on a "poses" data channel, as binary
+// messages in the Poses record format of the transform pipe (pose.rs). The
+// frontend hands them to submit_remote_poses, after which they go through
+// the same validation, smoothing and prediction as poses from the backend.
+//
+//   [webrtc]
+//   enabled = true
+//   whipUrl = "http://192.168.1.20:8889/overlay/whip"
+//   codec = "h264"
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebRtcCodec {
+    #[default]
+    Vp8,
+    H264,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebRtcConfig {
+    pub enabled: bool,
+    // WHIP endpoint the offer is posted to
+    pub whip_url: String,
+    // Sent as a bearer token if not empty
+    pub whip_token: String,
+    // Preferred video codec; the browser falls back to what the peer supports
+    pub codec: WebRtcCodec,
+    // Canvas capture rate
+    pub max_fps: u32,
+    // Accept poses on the "poses" data channel
+    pub receive_poses: bool,
+}
+
+impl Default for WebRtcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            whip_url: String::new(),
+            whip_token: String::new(),
+            codec: WebRtcCodec::Vp8,
+            max_fps: 30,
+            receive_poses: true,
+        }
+    }
+}
+
+impl WebRtcConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !(self.whip_url.starts_with("http://") || self.whip_url.starts_with("https://")) {
+            return Err(format!("WHIP URL must be an http(s) URL, got {:?}", self.whip_url));
+        }
+        if self.max_fps == 0 {
+            return Err("WebRTC frame rate must be positive".to_string());
+        }
+        Ok(())
+    }
+}
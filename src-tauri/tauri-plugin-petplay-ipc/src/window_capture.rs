@@ -0,0 +1,505 @@
+// --- Window capture ---
+// capture_window streams one window to an overlay, like the { window }
+// target of start_desktop_capture (see capture.rs), except that the window
+// draws itself with PrintWindow instead of being cut out of its monitor's
+// image. So the frames show the window as it is even while other windows
+// cover it or it's partly off screen, and they follow it from one monitor to
+// another. Every frame is sized like the window's frame as DWM draws it
+// (without the invisible resize borders), so a resized window just makes
+// frames of the new size. The window is drawn maxFps times a second (default
+// 30); an image only goes out when it changed, and nothing is sent while the
+// window is minimized. PrintWindow leaves the mouse pointer out.
+//
+// list_capturable_windows names the windows worth capturing (visible,
+// titled, unowned and not tool windows), each with a PNG thumbnail as a data
+// URL for a picker; thumbnailSize bounds its longer side (0 for none).
+//
+// Captures share the overlays' capture slots with desktop captures: starting
+// one replaces whatever capture the overlay had, stop_desktop_capture ends
+// either kind, and "desktop-capture-stopped" says when the window was closed.
+//
+// Windows only; elsewhere there are no windows and capture_window fails with
+// Unsupported.
+use crate::capture::MAX_CAPTURE_FPS;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 160;
+
+// An entry of list_capturable_windows
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturableWindow {
+    // HWND to pass to capture_window
+    pub hwnd: u64,
+    pub title: String,
+    pub process_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub minimized: bool,
+    // data:image/png;base64,... (None when not asked for, or when the window can't be drawn, e.g. minimized)
+    pub thumbnail: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowCaptureOptions {
+    pub max_fps: u32,
+    // Overlay the frames are for
+    pub overlay_id: u32,
+}
+
+impl Default for WindowCaptureOptions {
+    fn default() -> Self {
+        Self { max_fps: 30, overlay_id: 0 }
+    }
+}
+
+impl WindowCaptureOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_CAPTURE_FPS).contains(&self.max_fps) {
+            return Err(format!("maxFps must be between 1 and {}", MAX_CAPTURE_FPS));
+        }
+        Ok(())
+    }
+}
+
+// Result of capture_window
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowCaptureInfo {
+    pub hwnd: u64,
+    pub title: String,
+    pub overlay_id: u32,
+}
+
+// Shrink a packed BGRA8 image to fit in `max` x `max` (never enlarging it), averaging the pixels each
+// one covers, and return it as packed RGBA8 with its size
+#[cfg_attr(not(windows), allow(dead_code))] // Only Windows has thumbnails
+pub fn downscale_to_rgba(bgra: &[u8], width: u32, height: u32, max: u32) -> (Vec<u8>, u32, u32) {
+    let scale = (max as f64 / width.max(height).max(1) as f64).min(1.0);
+    let (out_width, out_height) = (((width as f64 * scale) as u32).max(1), ((height as f64 * scale) as u32).max(1));
+    let mut rgba = Vec::with_capacity(out_width as usize * out_height as usize * 4);
+    for y in 0..out_height as usize {
+        let top = y * height as usize / out_height as usize;
+        let bottom = ((y + 1) * height as usize / out_height as usize).max(top + 1);
+        for x in 0..out_width as usize {
+            let left = x * width as usize / out_width as usize;
+            let right = ((x + 1) * width as usize / out_width as usize).max(left + 1);
+            let mut sum = [0u32; 4];
+            for row in top..bottom {
+                for pixel in bgra[(row * width as usize + left) * 4..(row * width as usize + right) * 4].chunks_exact(4) {
+                    sum.iter_mut().zip(pixel).for_each(|(sum, channel)| *sum += *channel as u32);
+                }
+            }
+            let count = ((bottom - top) * (right - left)) as u32;
+            rgba.extend_from_slice(&[sum[2] / count, sum[1] / count, sum[0] / count, sum[3] / count].map(|channel| channel as u8));
+        }
+    }
+    (rgba, out_width, out_height)
+}
+
+// A packed BGRA8 image as a PNG data URL, shrunk to fit in `max` x `max`
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn thumbnail_data_url(bgra: &[u8], width: u32, height: u32, max: u32) -> io::Result<String> {
+    let (rgba, width, height) = downscale_to_rgba(bgra, width, height, max);
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&rgba).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+    Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+pub fn list_windows(thumbnail_size: u32) -> io::Result<Vec<CapturableWindow>> {
+    platform::list_windows(thumbnail_size)
+}
+
+// A running capture; it stops when dropped
+pub struct WindowCapture {
+    info: WindowCaptureInfo,
+    _capture: platform::Capture,
+}
+
+impl WindowCapture {
+    // Start drawing window `hwnd` and hand every changed image (width, height, packed BGRA8 rows) to
+    // `on_frame` on the capture thread; `on_stopped` is called there if capture fails after it started
+    pub fn start(
+        hwnd: u64,
+        options: WindowCaptureOptions,
+        on_frame: impl FnMut(u32, u32, &[u8]) + Send + 'static,
+        on_stopped: impl FnOnce(io::Error) + Send + 'static,
+    ) -> io::Result<Self> {
+        let (info, capture) = platform::Capture::start(hwnd, options, on_frame, on_stopped)?;
+        Ok(Self { info, _capture: capture })
+    }
+
+    pub fn info(&self) -> &WindowCaptureInfo {
+        &self.info
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{thumbnail_data_url, CapturableWindow, WindowCaptureInfo, WindowCaptureOptions};
+    use crate::capture::Rect;
+    use std::{
+        ffi::c_void,
+        io, mem,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    };
+    use windows::{
+        core::BOOL,
+        Win32::{
+            Foundation::{HWND, LPARAM, RECT},
+            Graphics::{
+                Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS},
+                Gdi::{
+                    CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits, ReleaseDC, SelectObject, BITMAPINFO,
+                    BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HBITMAP, HDC, HGDIOBJ,
+                },
+            },
+            Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS},
+            UI::WindowsAndMessaging::{
+                EnumWindows, GetWindow, GetWindowLongW, GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow,
+                IsWindowVisible, GWL_EXSTYLE, GW_OWNER, PW_RENDERFULLCONTENT, WS_EX_TOOLWINDOW,
+            },
+        },
+    };
+
+    fn hwnd(handle: u64) -> HWND {
+        HWND(handle as usize as *mut c_void)
+    }
+
+    fn not_found(handle: u64) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("There is no window {:#x}", handle))
+    }
+
+    fn title(window: HWND) -> String {
+        let mut title = [0u16; 512];
+        // SAFETY: the buffer's length is passed along with it
+        let len = unsafe { GetWindowTextW(window, &mut title) };
+        String::from_utf16_lossy(&title[..len.max(0) as usize])
+    }
+
+    // The window's frame as DWM draws it, in screen coordinates, and the whole window including its
+    // invisible resize borders
+    fn bounds(window: HWND) -> io::Result<(Rect, Rect)> {
+        let (mut frame, mut whole) = (RECT::default(), RECT::default());
+        // SAFETY: both are RECTs, the size each call writes
+        unsafe {
+            DwmGetWindowAttribute(window, DWMWA_EXTENDED_FRAME_BOUNDS, (&mut frame as *mut RECT).cast(), mem::size_of::<RECT>() as u32)
+                .map_err(io::Error::other)?;
+            GetWindowRect(window, &mut whole).map_err(io::Error::other)?;
+        }
+        let rect = |r: RECT| Rect { left: r.left, top: r.top, right: r.right, bottom: r.bottom };
+        Ok((rect(frame), rect(whole)))
+    }
+
+    // Whether the window is one a user would pick: on screen (not cloaked on another virtual desktop),
+    // titled, not owned by another window and not a tool window
+    fn is_capturable(window: HWND) -> bool {
+        let mut cloaked = 0u32;
+        // SAFETY: `cloaked` is a u32, the size DWMWA_CLOAKED writes; the rest only read window state
+        unsafe {
+            IsWindowVisible(window).as_bool()
+                && GetWindow(window, GW_OWNER).is_err()
+                && GetWindowLongW(window, GWL_EXSTYLE) as u32 & WS_EX_TOOLWINDOW.0 == 0
+                && DwmGetWindowAttribute(window, DWMWA_CLOAKED, (&mut cloaked as *mut u32).cast(), mem::size_of::<u32>() as u32).is_ok()
+                && cloaked == 0
+                && !title(window).is_empty()
+        }
+    }
+
+    // A memory DC with a bitmap of one size that windows are drawn into
+    struct Canvas {
+        screen: HDC,
+        dc: HDC,
+        bitmap: HBITMAP,
+        previous: HGDIOBJ,
+        width: u32,
+        height: u32,
+    }
+
+    impl Canvas {
+        fn new(width: u32, height: u32) -> io::Result<Self> {
+            // SAFETY: the DCs and the bitmap are released by Drop, after selecting the previous object back
+            unsafe {
+                let screen = GetDC(None);
+                let dc = CreateCompatibleDC(Some(screen));
+                let bitmap = CreateCompatibleBitmap(screen, width as i32, height as i32);
+                if dc.is_invalid() || bitmap.is_invalid() {
+                    let _ = DeleteObject(bitmap.into());
+                    let _ = DeleteDC(dc);
+                    ReleaseDC(None, screen);
+                    return Err(io::Error::other(format!("Can't make a {}x{} bitmap to draw a window into", width, height)));
+                }
+                let previous = SelectObject(dc, bitmap.into());
+                Ok(Self { screen, dc, bitmap, previous, width, height })
+            }
+        }
+
+        // Draw the whole window (the size of this canvas) and copy it into `pixels` as top-down BGRA8
+        fn draw(&self, window: HWND, pixels: &mut Vec<u8>) -> io::Result<()> {
+            pixels.resize(self.width as usize * self.height as usize * 4, 0);
+            let mut info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: self.width as i32,
+                    // Negative for rows from the top down
+                    biHeight: -(self.height as i32),
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..BITMAPINFOHEADER::default()
+                },
+                ..BITMAPINFO::default()
+            };
+            // SAFETY: `pixels` holds height rows of width 32-bit pixels, which is what `info` asks GetDIBits for
+            unsafe {
+                // PW_RENDERFULLCONTENT also gets what DirectComposition draws, e.g. browsers and games
+                if !PrintWindow(window, self.dc, PRINT_WINDOW_FLAGS(PW_RENDERFULLCONTENT)).as_bool() {
+                    return Err(io::Error::other("PrintWindow failed"));
+                }
+                let lines = GetDIBits(self.dc, self.bitmap, 0, self.height, Some(pixels.as_mut_ptr().cast()), &mut info, DIB_RGB_COLORS);
+                if lines != self.height as i32 {
+                    return Err(io::Error::other("GetDIBits failed"));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Canvas {
+        fn drop(&mut self) {
+            // SAFETY: everything made in new, released once
+            unsafe {
+                SelectObject(self.dc, self.previous);
+                let _ = DeleteObject(self.bitmap.into());
+                let _ = DeleteDC(self.dc);
+                ReleaseDC(None, self.screen);
+            }
+        }
+    }
+
+    // Draws one window again and again, keeping the canvas while the size stays the same
+    struct Painter {
+        window: HWND,
+        canvas: Option<Canvas>,
+        whole: Vec<u8>,
+    }
+
+    impl Painter {
+        fn new(window: HWND) -> Self {
+            Self { window, canvas: None, whole: Vec::new() }
+        }
+
+        // The window's frame as packed, opaque BGRA8 rows into `image`, with its size; None if it can't be
+        // drawn right now (minimized)
+        fn paint(&mut self, image: &mut Vec<u8>) -> io::Result<Option<(u32, u32)>> {
+            // SAFETY: only reads window state
+            if unsafe { IsIconic(self.window) }.as_bool() {
+                return Ok(None);
+            }
+            let (frame, whole) = bounds(self.window)?;
+            let Some(crop) = frame.intersect(&whole).map(|crop| crop.offset(-whole.left, -whole.top)) else {
+                return Ok(None);
+            };
+            if self.canvas.as_ref().is_none_or(|canvas| (canvas.width, canvas.height) != (whole.width(), whole.height())) {
+                self.canvas = None;
+                self.canvas = Some(Canvas::new(whole.width(), whole.height())?);
+            }
+            let Some(canvas) = &self.canvas else {
+                return Ok(None);
+            };
+            canvas.draw(self.window, &mut self.whole)?;
+            image.clear();
+            let stride = whole.width() as usize * 4;
+            for y in crop.top..crop.bottom {
+                let start = y as usize * stride + crop.left as usize * 4;
+                image.extend_from_slice(&self.whole[start..start + crop.width() as usize * 4]);
+            }
+            // What PrintWindow leaves in the alpha channel isn't defined
+            image.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 0xFF);
+            Ok(Some((crop.width(), crop.height())))
+        }
+    }
+
+    unsafe extern "system" fn collect(window: HWND, found: LPARAM) -> BOOL {
+        // SAFETY: list_windows passes a pointer to its Vec, which outlives EnumWindows
+        let found = unsafe { &mut *(found.0 as *mut Vec<HWND>) };
+        if is_capturable(window) {
+            found.push(window);
+        }
+        true.into()
+    }
+
+    pub fn list_windows(thumbnail_size: u32) -> io::Result<Vec<CapturableWindow>> {
+        let mut found: Vec<HWND> = Vec::new();
+        // SAFETY: `collect` only pushes to `found`, which lives until EnumWindows returns
+        unsafe { EnumWindows(Some(collect), LPARAM(&mut found as *mut Vec<HWND> as isize)) }.map_err(io::Error::other)?;
+        let mut image = Vec::new();
+        Ok(found
+            .into_iter()
+            .filter_map(|window| {
+                // Windows can close while being listed
+                let (frame, _) = bounds(window).ok()?;
+                let mut process_id = 0;
+                // SAFETY: only reads window state
+                let minimized = unsafe {
+                    GetWindowThreadProcessId(window, Some(&mut process_id));
+                    IsIconic(window).as_bool()
+                };
+                let thumbnail = match thumbnail_size {
+                    0 => None,
+                    size => match Painter::new(window).paint(&mut image) {
+                        Ok(Some((width, height))) => thumbnail_data_url(&image, width, height, size).ok(),
+                        _ => None,
+                    },
+                };
+                Some(CapturableWindow {
+                    hwnd: window.0 as usize as u64,
+                    title: title(window),
+                    process_id,
+                    width: frame.width(),
+                    height: frame.height(),
+                    minimized,
+                    thumbnail,
+                })
+            })
+            .collect())
+    }
+
+    fn run(
+        handle: u64,
+        options: WindowCaptureOptions,
+        stop: &AtomicBool,
+        on_frame: &mut impl FnMut(u32, u32, &[u8]),
+    ) -> io::Result<()> {
+        let period = Duration::from_secs_f64(1.0 / options.max_fps as f64);
+        let mut painter = Painter::new(hwnd(handle));
+        let (mut image, mut sent) = (Vec::new(), Vec::new());
+        let mut sent_size = (0, 0);
+        let mut due = Instant::now();
+        while !stop.load(Ordering::Acquire) {
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+            due = (due + period).max(Instant::now());
+            // SAFETY: any value is safe to pass as a window handle
+            if !unsafe { IsWindow(Some(hwnd(handle))) }.as_bool() {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("Window {:#x} was closed", handle)));
+            }
+            let Some(size) = painter.paint(&mut image)? else {
+                continue;
+            };
+            // Windows are drawn whether they changed or not; unchanged images aren't worth sending
+            if size == sent_size && image == sent {
+                continue;
+            }
+            on_frame(size.0, size.1, &image);
+            mem::swap(&mut image, &mut sent);
+            sent_size = size;
+        }
+        Ok(())
+    }
+
+    pub struct Capture {
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Capture {
+        pub fn start(
+            handle: u64,
+            options: WindowCaptureOptions,
+            mut on_frame: impl FnMut(u32, u32, &[u8]) + Send + 'static,
+            on_stopped: impl FnOnce(io::Error) + Send + 'static,
+        ) -> io::Result<(WindowCaptureInfo, Self)> {
+            // SAFETY: any value is safe to pass as a window handle
+            if !unsafe { IsWindow(Some(hwnd(handle))) }.as_bool() {
+                return Err(not_found(handle));
+            }
+            let info = WindowCaptureInfo { hwnd: handle, title: title(hwnd(handle)), overlay_id: options.overlay_id };
+            let stop = Arc::new(AtomicBool::new(false));
+            let (started, result) = mpsc::sync_channel(1);
+            let stopping = Arc::clone(&stop);
+            let thread = thread::Builder::new().name("puppyweb-window-capture".to_string()).spawn(move || {
+                // Drawn once up front, so a window that can't be drawn at all fails the command
+                let mut image = Vec::new();
+                if let Err(e) = Painter::new(hwnd(handle)).paint(&mut image) {
+                    let _ = started.send(Err(e));
+                    return;
+                }
+                let _ = started.send(Ok(()));
+                if let Err(e) = run(handle, options, &stopping, &mut on_frame) {
+                    on_stopped(e);
+                }
+            })?;
+            let capture = Self { stop, thread: Some(thread) };
+            result.recv().map_err(|_| io::Error::other("window capture thread ended before drawing the window"))??;
+            Ok((info, capture))
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Release);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::{CapturableWindow, WindowCaptureInfo, WindowCaptureOptions};
+    use std::io;
+
+    pub fn list_windows(_thumbnail_size: u32) -> io::Result<Vec<CapturableWindow>> {
+        Ok(Vec::new())
+    }
+
+    // Can't be created, so there's never a capture to stop
+    pub enum Capture {}
+
+    impl Capture {
+        pub fn start(
+            _hwnd: u64,
+            _options: WindowCaptureOptions,
+            _on_frame: impl FnMut(u32, u32, &[u8]) + Send + 'static,
+            _on_stopped: impl FnOnce(io::Error) + Send + 'static,
+        ) -> io::Result<(WindowCaptureInfo, Self)> {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "Window capture is only available on Windows"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnails_average_down_to_rgba() {
+        // 4x2 BGRA: a blue and a red 2x2 block
+        let mut bgra = Vec::new();
+        for _ in 0..2 {
+            bgra.extend_from_slice(&[0xFF, 0, 0, 0xFF, 0xFF, 0, 0, 0xFF, 0, 0, 0xFF, 0xFF, 0, 0, 0xFF, 0xFF]);
+        }
+        let (rgba, width, height) = downscale_to_rgba(&bgra, 4, 2, 2);
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(rgba, [0, 0, 0xFF, 0xFF, 0xFF, 0, 0, 0xFF]);
+
+        // Never enlarged
+        assert_eq!(downscale_to_rgba(&bgra, 4, 2, 100).1, 4);
+        let url = thumbnail_data_url(&bgra, 4, 2, 2).unwrap();
+        assert!(url.starts_with("data:image/png;base64,iVBORw0KGgo"));
+    }
+}
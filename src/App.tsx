@@ -11,6 +11,7 @@ import {
   useIpc
 } from './IPCContext.tsx';
 import { DirectXRFrameCapture_SessionLoop } from './FrameCapture.tsx';
+import { WebRtcPublisher } from './webrtc.ts';
 
 const xrDevice = new XRDevice(metaQuest3, {
   stereoEnabled: true,
@@ -46,6 +47,7 @@ const AppContent = () => {
         <XR store={xrStore}>
           <XRSessionStatus />
           <DirectXRFrameCapture_SessionLoop />
+          <WebRtcPublisher />
           <Scene />
         </XR>
       </Canvas>
@@ -1,6 +1,7 @@
 import { useRef, useEffect } from 'react';
 import { useThree } from '@react-three/fiber';
 import { useXR } from '@react-three/xr';
+import { event } from '@tauri-apps/api';
 import { useIpc } from './IPCContext.tsx';
 
 // Configuration options
@@ -22,6 +23,8 @@ export const DirectXRFrameCapture_SessionLoop = () => {
   const downsampledHeightRef = useRef<number>(0);
   const frameIdRef = useRef<number | null>(null);
   const frameCounterRef = useRef<number>(0);
+  // Extra scale requested by Rust's adaptive quality controller ("target-resolution" event)
+  const targetScaleRef = useRef<number>(1);
 
   // The core capture logic, designed to run inside requestAnimationFrame
   const captureFrame = (_timestamp: DOMHighResTimeStamp, xrFrame: XRFrame | undefined) => {
@@ -57,9 +60,10 @@ export const DirectXRFrameCapture_SessionLoop = () => {
       if (W <= 0 || H <= 0) {
         errorMsg = `Invalid framebuffer dimensions in rAF: ${W}x${H}`;
       } else {
-        // Calculate downsized dimensions if downsampling is enabled
-        const targetWidth = USE_DOWNSAMPLING ? Math.floor(W * DOWNSAMPLE_FACTOR) : W;
-        const targetHeight = USE_DOWNSAMPLING ? Math.floor(H * DOWNSAMPLE_FACTOR) : H;
+        // Calculate downsized dimensions if downsampling is enabled, or requested by the adaptive controller
+        const scale = (USE_DOWNSAMPLING ? DOWNSAMPLE_FACTOR : 1) * targetScaleRef.current;
+        const targetWidth = Math.max(1, Math.floor(W * scale));
+        const targetHeight = Math.max(1, Math.floor(H * scale));
         
         // Ensure original buffer exists and has the correct size
         const requiredSize = W * H * 4;
@@ -69,14 +73,6 @@ export const DirectXRFrameCapture_SessionLoop = () => {
             pixelsRef.current = new Uint8Array(requiredSize);
             currentWidthRef.current = W;
             currentHeightRef.current = H;
-            
-            // Also create the downsized buffer if needed
-            if (USE_DOWNSAMPLING) {
-              const downsampledSize = targetWidth * targetHeight * 4;
-              downsampledPixelsRef.current = new Uint8Array(downsampledSize);
-              downsampledWidthRef.current = targetWidth;
-              downsampledHeightRef.current = targetHeight;
-            }
           } catch (allocError) {
             console.error("Failed to allocate pixel buffer in rAF:", allocError);
             errorMsg = "Pixel buffer allocation failed in rAF.";
@@ -86,6 +82,14 @@ export const DirectXRFrameCapture_SessionLoop = () => {
           }
         }
 
+        // (Re)create the downsized buffer when the target size changes
+        if (pixelsRef.current && scale < 1 &&
+            (downsampledWidthRef.current !== targetWidth || downsampledHeightRef.current !== targetHeight)) {
+          downsampledPixelsRef.current = new Uint8Array(targetWidth * targetHeight * 4);
+          downsampledWidthRef.current = targetWidth;
+          downsampledHeightRef.current = targetHeight;
+        }
+
         // Perform readPixels if buffer is valid
         if (pixelsRef.current && W > 0 && H > 0) {
           try {
@@ -114,7 +118,8 @@ export const DirectXRFrameCapture_SessionLoop = () => {
 
     // Send data if successful
     if (captureSuccess && pixelsRef.current) {
-      if (USE_DOWNSAMPLING && downsampledPixelsRef.current) {
+      const downsample = (USE_DOWNSAMPLING ? DOWNSAMPLE_FACTOR : 1) * targetScaleRef.current < 1;
+      if (downsample && downsampledPixelsRef.current) {
         // Downsample the image
         downsampleImage(
           pixelsRef.current,
@@ -179,6 +184,17 @@ export const DirectXRFrameCapture_SessionLoop = () => {
     }
   };
 
+  // Follow the resolution the adaptive quality controller asks for
+  useEffect(() => {
+    const unlisten = event.listen<{ scale: number; width: number; height: number }>('target-resolution', ({ payload }) => {
+      console.log(`Adaptive quality: capture scale ${payload.scale.toFixed(2)} (${payload.width}x${payload.height})`);
+      targetScaleRef.current = payload.scale;
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
   // Effect to start/stop the requestAnimationFrame loop
   useEffect(() => {
     if (session) {
@@ -2,16 +2,14 @@ import React, { useState, useEffect, createContext, useContext, useCallback } fr
 import * as THREE from 'three';
 import { useXR } from '@react-three/xr';
 import { createXRStore } from '@react-three/xr';
-import { core } from '@tauri-apps/api';
-import { listen } from '@tauri-apps/api/event';
+import { Channel, commands } from '../src-tauri/tauri-plugin-petplay-ipc/guest-js/bindings';
 
 export const AUTO_START_XR = true;
 export const xrStore = createXRStore();
 
-// Define the payload type received from Rust
-type TransformUpdatePayload = {
-  matrix: number[]; // Flat 16-element array (f32)
-};
+// Binary "matrix" messages from subscribe_transforms (see transform_stream.rs):
+// u32 device id, u32 reserved, u64 timestamp, then 16 f32 (row-major 4x4), little endian
+const MATRIX_HEADER_SIZE = 16;
 
 // IPC context type definition
 export type IpcContextType = {
@@ -48,7 +46,9 @@ export const IpcProvider: React.FC<{ children: React.ReactNode }> = ({ children
     framePayload.set(pixels, metaSize);
 
     // Invoke the Tauri command with the combined payload
-    core.invoke('send_frame_data', framePayload) // Send the combined ArrayBuffer
+    // sent-at-us lets get_latency_histogram measure the IPC hop
+    const sentAtUs = Math.round((performance.timeOrigin + performance.now()) * 1000);
+    commands.sendFrameData(framePayload, { 'sent-at-us': String(sentAtUs) }) // Send the combined ArrayBuffer
         .then(() => {
       // Optionally update status or log
       // console.log(`Frame sent: ${width}x${height}`);
@@ -80,15 +80,19 @@ export const IpcProvider: React.FC<{ children: React.ReactNode }> = ({ children
     }
   }, []); // Empty dependency array ensures this runs only once on mount
 
-  // Effect for listening to Transform Updates from Rust
+  // Effect for receiving HMD transforms from Rust over a binary channel
   useEffect(() => {
-    let unlistenTransform: (() => void) | undefined;
+    let subscriptionId: number | undefined;
+    let cancelled = false;
 
     const setupListener = async () => {
       try {
-        unlistenTransform = await listen<TransformUpdatePayload>('transform-update', (event) => {
-          // console.log('Received transform-update:', event.payload.matrix); // Debugging
-          const flatMatrix = event.payload.matrix;
+        const channel = new Channel<ArrayBuffer>();
+        channel.onmessage = (buffer) => {
+          // The matrix floats are viewed in place, no JSON parsing
+          const flatMatrix = buffer.byteLength >= MATRIX_HEADER_SIZE + 64
+            ? new Float32Array(buffer, MATRIX_HEADER_SIZE, 16)
+            : undefined;
 
           // Ensure we received 16 elements
           if (flatMatrix && flatMatrix.length === 16) {
@@ -102,12 +106,18 @@ export const IpcProvider: React.FC<{ children: React.ReactNode }> = ({ children
             // Call the function to update the frontend's XR device pose
             setXRDeviceTransform(matrix3x4);
           } else {
-            console.error('Received invalid matrix data in transform-update event:', event.payload);
+            console.error('Received invalid matrix message on the transform channel:', buffer);
           }
-        });
-        console.log("[IPC Provider] Listening for 'transform-update' events from Rust.");
+        };
+        const id = await commands.subscribeTransforms({ channel, devices: ['hmd'], format: 'matrix' });
+        if (cancelled) {
+          commands.unsubscribeTransforms({ id });
+          return;
+        }
+        subscriptionId = id;
+        console.log("[IPC Provider] Subscribed to HMD transforms from Rust.");
       } catch (error) {
-        console.error("Failed to set up transform-update listener:", error);
+        console.error("Failed to subscribe to transforms:", error);
         setStatus('Error Listening for Transforms');
       }
     };
@@ -116,9 +126,10 @@ export const IpcProvider: React.FC<{ children: React.ReactNode }> = ({ children
 
     // Cleanup function
     return () => {
-      if (unlistenTransform) {
-        unlistenTransform();
-        console.log("[IPC Provider] Unlistened from 'transform-update' events.");
+      cancelled = true;
+      if (subscriptionId !== undefined) {
+        commands.unsubscribeTransforms({ id: subscriptionId });
+        console.log("[IPC Provider] Unsubscribed from HMD transforms.");
       }
     };
   }, []); // Empty dependency array ensures this runs only once on mount
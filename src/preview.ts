@@ -0,0 +1,34 @@
+import { core } from '@tauri-apps/api';
+
+// Mirrors the frames leaving Rust for the frame pipe (opened by show_frame_preview).
+// Each message: u32 width, u32 height, u64 sequence (little endian), then packed RGBA8
+const PREVIEW_HEADER_SIZE = 16;
+
+const canvas = document.getElementById('preview') as HTMLCanvasElement;
+const context = canvas.getContext('2d')!;
+const info = document.getElementById('info')!;
+
+const channel = new core.Channel<ArrayBuffer>();
+channel.onmessage = (buffer) => {
+  const view = new DataView(buffer);
+  const width = view.getUint32(0, true);
+  const height = view.getUint32(4, true);
+  const sequence = view.getBigUint64(8, true);
+  if (buffer.byteLength < PREVIEW_HEADER_SIZE + width * height * 4) {
+    console.error('Received a truncated preview frame:', buffer.byteLength, { width, height });
+    return;
+  }
+  if (canvas.width !== width || canvas.height !== height) {
+    canvas.width = width;
+    canvas.height = height;
+  }
+  // Shown as sent, so a frame that reaches the backend upside down shows up upside down here too
+  const pixels = new Uint8ClampedArray(buffer, PREVIEW_HEADER_SIZE, width * height * 4);
+  context.putImageData(new ImageData(pixels, width, height), 0, 0);
+  info.textContent = `${width}x${height} frame ${sequence}`;
+};
+
+core.invoke('plugin:petplay-ipc|subscribe_frame_preview', { channel }).catch((err) => {
+  console.error('Failed to subscribe to the frame preview:', err);
+  info.textContent = 'Preview unavailable';
+});
@@ -0,0 +1,146 @@
+import { useEffect } from 'react';
+import { useThree } from '@react-three/fiber';
+import { core } from '@tauri-apps/api';
+
+// Publishes the render canvas to a WebRTC peer over WHIP (see webrtc.rs) and
+// forwards poses the peer sends on the "poses" data channel to Rust.
+type WebRtcConfig = {
+  enabled: boolean;
+  whipUrl: string;
+  whipToken: string;
+  codec: 'vp8' | 'h264';
+  maxFps: number;
+  receivePoses: boolean;
+};
+
+const RETRY_DELAY_MS = 3000;
+// Give up waiting for more ICE candidates after this and offer what was gathered
+const ICE_GATHERING_TIMEOUT_MS = 2000;
+
+// Put the configured codec first, keeping the others as fallbacks
+function preferCodec(transceiver: RTCRtpTransceiver, codec: WebRtcConfig['codec']) {
+  const codecs = RTCRtpSender.getCapabilities('video')?.codecs;
+  if (!codecs || typeof transceiver.setCodecPreferences !== 'function') {
+    return;
+  }
+  const mimeType = codec === 'h264' ? 'video/h264' : 'video/vp8';
+  const preferred = codecs.filter((c) => c.mimeType.toLowerCase() === mimeType);
+  const others = codecs.filter((c) => c.mimeType.toLowerCase() !== mimeType);
+  transceiver.setCodecPreferences([...preferred, ...others]);
+}
+
+function iceGatheringComplete(pc: RTCPeerConnection): Promise<void> {
+  if (pc.iceGatheringState === 'complete') {
+    return Promise.resolve();
+  }
+  return new Promise((resolve) => {
+    const timer = setTimeout(resolve, ICE_GATHERING_TIMEOUT_MS);
+    pc.addEventListener('icegatheringstatechange', () => {
+      if (pc.iceGatheringState === 'complete') {
+        clearTimeout(timer);
+        resolve();
+      }
+    });
+  });
+}
+
+// One WHIP session; resolves with the peer connection and the session URL to DELETE when done
+async function publish(canvas: HTMLCanvasElement, config: WebRtcConfig) {
+  const pc = new RTCPeerConnection();
+  const stream = canvas.captureStream(config.maxFps);
+  for (const track of stream.getVideoTracks()) {
+    preferCodec(pc.addTransceiver(track, { direction: 'sendonly', streams: [stream] }), config.codec);
+  }
+  if (config.receivePoses) {
+    const poses = pc.createDataChannel('poses', { ordered: false, maxRetransmits: 0 });
+    poses.binaryType = 'arraybuffer';
+    poses.onmessage = ({ data }) => {
+      if (!(data instanceof ArrayBuffer)) {
+        return;
+      }
+      core.invoke('plugin:petplay-ipc|submit_remote_poses', new Uint8Array(data)).catch((err) => {
+        console.error('[WebRTC] Rejected poses from peer:', err);
+      });
+    };
+  }
+
+  await pc.setLocalDescription(await pc.createOffer());
+  await iceGatheringComplete(pc);
+  const headers: Record<string, string> = { 'Content-Type': 'application/sdp' };
+  if (config.whipToken) {
+    headers.Authorization = `Bearer ${config.whipToken}`;
+  }
+  const response = await fetch(config.whipUrl, { method: 'POST', headers, body: pc.localDescription!.sdp });
+  if (!response.ok) {
+    pc.close();
+    throw new Error(`WHIP endpoint answered ${response.status} ${response.statusText}`);
+  }
+  await pc.setRemoteDescription({ type: 'answer', sdp: await response.text() });
+  const location = response.headers.get('Location');
+  const session = location ? new URL(location, config.whipUrl).toString() : undefined;
+  return { pc, session };
+}
+
+// Keeps the canvas published while mounted, starting over whenever the connection fails
+export const WebRtcPublisher = () => {
+  const { gl } = useThree();
+
+  useEffect(() => {
+    let stopped = false;
+    let retryTimer: ReturnType<typeof setTimeout> | undefined;
+    let current: { pc: RTCPeerConnection; session?: string } | undefined;
+
+    const end = () => {
+      if (!current) {
+        return;
+      }
+      const { pc, session } = current;
+      current = undefined;
+      pc.close();
+      if (session) {
+        fetch(session, { method: 'DELETE' }).catch(() => {});
+      }
+    };
+
+    const retry = () => {
+      end();
+      if (!stopped) {
+        retryTimer = setTimeout(start, RETRY_DELAY_MS);
+      }
+    };
+
+    const start = async () => {
+      try {
+        const config = await core.invoke<WebRtcConfig>('plugin:petplay-ipc|get_webrtc_config');
+        if (!config.enabled || stopped) {
+          return;
+        }
+        current = await publish(gl.domElement, config);
+        if (stopped) {
+          end();
+          return;
+        }
+        console.log(`[WebRTC] Publishing to ${config.whipUrl} (${config.codec}).`);
+        const pc = current.pc;
+        pc.onconnectionstatechange = () => {
+          if (pc.connectionState === 'failed' || pc.connectionState === 'closed') {
+            console.warn(`[WebRTC] Connection ${pc.connectionState}, republishing in ${RETRY_DELAY_MS} ms.`);
+            retry();
+          }
+        };
+      } catch (err) {
+        console.error('[WebRTC] Failed to publish:', err);
+        retry();
+      }
+    };
+
+    start();
+    return () => {
+      stopped = true;
+      clearTimeout(retryTimer);
+      end();
+    };
+  }, [gl]);
+
+  return null;
+};
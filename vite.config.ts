@@ -11,6 +11,16 @@ const host = process.env.TAURI_DEV_HOST;
 export default defineConfig(() => ({
   plugins: [react()],
 
+  // The frame preview window (show_frame_preview) is a second page
+  build: {
+    rollupOptions: {
+      input: {
+        main: "index.html",
+        preview: "preview.html",
+      },
+    },
+  },
+
   // Vite options tailored for Tauri development and only applied in `tauri dev` or `tauri build`
   //
   // 1. prevent vite from obscuring rust errors